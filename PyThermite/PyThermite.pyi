@@ -1,166 +1,1258 @@
+# generated by build.rs from the signatures and doc comments in PyThermite/src, edit those instead
+# and update this file with UPDATE_STUB=1 cargo test --test stub
+from typing import Any
 
+class Indexable:
+    '''
+    Base class for objects that can be indexed.
+    All attributes will be indexed unless the attribute name is prefixed with an underscore
+    Nested Indexable objects are supported and fully queryable via dot notation.
+    list/tuple/set values are indexed by element and compared by content, their contents are
+    frozen on assignment so in-place mutation is not seen until the attribute is reassigned
+    or reindex_attr is called
+    annotated fields of subclasses are interned once when the class is defined,
+    __thermite_fields__ maps their names to the ids used by the index
+    a subclass can limit the attributes indexes maintain with class Car(Indexable, index_only=["vin", "price"]),
+    index_exclude=[...] or an __index_fields__ list, other attributes are stored and read back but never indexed,
+    so assigning them skips index maintenance. subclasses inherit the setting unless they declare their own
+    '''
+    def __init__(*_args, **kwargs): ...
+    def __init_subclass__(**kwargs): ...
+    def field(name: str, type: type, nullable: bool = False):
+        '''
+        declares the type of attribute name on objects of this class (and subclasses defined afterwards),
+        one of int, float, str, bool or bytes. values are then read as that type without checking for any other,
+        ints given to a float field are stored as floats, and assigning a value of another type raises TypeError
+        naming the attribute. nullable also allows None. objects built before the declaration are not checked
+        '''
+    ...
+    def from_values(values: tuple) -> Any:
+        '''
+        builds an instance from values given in __thermite_fields__ order (base class fields first)
+        __init__ is not run, raises ValueError if the number of values does not match
+        '''
+    ...
+    def construct_many(rows: list[dict], errors: list | None = None) -> list[Any]:
+        '''
+        builds one instance per dict of attribute values in a single call, without running __init__
+        much faster than calling the class in a loop when loading many objects
+        a value that can't be read (e.g. an unhashable object or a str with lone surrogates) raises its error and nothing
+        is returned, unless errors is a list: the row is then skipped and (row position, attr, exception) appended to it
+        '''
+    ...
+    def __setattr__(name: str, value: Any): ...
+    def update(**kwargs):
+        '''
+        sets every attribute given, like assigning them one at a time but reindexing the object once per index
+        every value is read and checked (declared field types, strict indexes) before any is set, alerts run once after
+        '''
+    ...
+    def reindex_attr(name: str) -> bool:
+        '''
+        re-reads an attribute whose value was mutated in place (e.g. a list after append)
+        and updates every index holding this object, only elements that changed are reindexed
+        returns whether the value changed, raises AttributeError if the attribute is not set
+        '''
+    ...
+    def __getattribute__(name: str) -> Any: ...
+    def __dir__() -> list: ...
+    def __repr__() -> str: ...
+
+class Attr:
+    '''
+    attribute proxy returned by attr(name) that builds query expressions with python operators
+    e.g. (attr("price") > 5) & (attr("owner.name") == "bob")
+    '''
+    def __eq__(other: Any) -> PyQueryExpr: ...
+    def __ne__(other: Any) -> PyQueryExpr: ...
+    def __lt__(other: Any) -> PyQueryExpr: ...
+    def __le__(other: Any) -> PyQueryExpr: ...
+    def __gt__(other: Any) -> PyQueryExpr: ...
+    def __ge__(other: Any) -> PyQueryExpr: ...
+    def in_(values: list[Any]) -> PyQueryExpr: ...
+    def nin(values: list[Any]) -> PyQueryExpr: ...
+    def is_null() -> PyQueryExpr: ...
+    def is_not_null() -> PyQueryExpr: ...
+    def exists() -> PyQueryExpr: ...
+    def missing() -> PyQueryExpr: ...
+    def has_flag(flag: int) -> PyQueryExpr: ...
+    def flags_all(mask: int) -> PyQueryExpr: ...
+    def flags_any(mask: int) -> PyQueryExpr: ...
+    def mod_eq(divisor: int, remainder: int) -> PyQueryExpr: ...
+    def custom(name: str, value: Any) -> PyQueryExpr: ...
+    def bt(lower: Any, upper: Any) -> PyQueryExpr: ...
+    def starts_with(value: Any) -> PyQueryExpr: ...
+    def ends_with(value: Any) -> PyQueryExpr: ...
+    def contains(value: Any) -> PyQueryExpr: ...
+    def matches(pattern: str, regex: bool = False) -> PyQueryExpr: ...
+    def overlaps(lower: Any, upper: Any) -> PyQueryExpr: ...
+    def contains_point(point: Any) -> PyQueryExpr: ...
+    def __repr__() -> str: ...
+
+class BulkUpdate:
+    '''
+    context manager returned by Index.bulk_update, holding back index maintenance for assignments
+    to the index's objects until it exits
+    '''
+    def __enter__() -> BulkUpdate:
+        '''
+        raises ValueError when this block is already open
+        '''
+    ...
+    def __exit__(_exc_type: Any | None, _exc: Any | None, _tb: Any | None) -> bool: ...
+
+class FilteredIndex:
+    '''
+    FilteredIndex is a view into an Index with an allow list of items.
+    It supports the same querying and filtering operations as Index
+    but does not support adding or removing objects.
+    '''
+    def reduced(**kwargs) -> FilteredIndex:
+        '''
+        returns a FilteredIndex containing only items that match the given attribute filters
+        '''
+    ...
+    def reduced_query(query: PyQueryExpr) -> FilteredIndex:
+        '''
+        returns a FilteredIndex containing only items that match the given query expression
+        '''
+    ...
+    def coverage() -> list[dict]:
+        '''
+        for each leaf clause of the queries that produced this FilteredIndex (against the index they started from)
+        returns clause, indexed (matched objects holding a value the clause's index structure answers),
+        fallback (matched objects without one, let in through not / ne), unindexable (objects left out holding
+        a value the structure cannot answer, e.g. a str for gt or an unknown type) and missing (objects left out
+        without the attribute), nonzero unindexable counts mean the results may be incomplete
+        '''
+    ...
+    def sort_keys(attr: str) -> list[tuple[Indexable, bytes]]:
+        '''
+        sortable keys of the objects in this FilteredIndex like Index.sort_keys
+        '''
+    ...
+    def aggregate(attr: str, funcs: list[str]) -> dict:
+        '''
+        computes aggregates over the objects in this FilteredIndex like Index.aggregate
+        '''
+    ...
+    def order_by(attr: str, descending: bool = False, limit: int | None = None) -> list[Indexable]:
+        '''
+        returns the objects in this FilteredIndex sorted by attr like Index.order_by
+        '''
+    ...
+    def nearest(attr: str, target: float, k: int = 5) -> list[Indexable]:
+        '''
+        finds the objects in this FilteredIndex closest to target like Index.nearest
+        '''
+    ...
+    def traverse(start_objs: list[Indexable], via: Any, depth: int | None = None, filter: PyQueryExpr | None = None) -> FilteredIndex:
+        '''
+        follows references from start_objs to the objects in this FilteredIndex like Index.traverse
+        '''
+    ...
+    def connected(a: Indexable, b: Indexable, via: Any, max_depth: int | None = 6) -> bool:
+        '''
+        whether a and b are linked within this FilteredIndex like Index.connected
+        '''
+    ...
+    def component_of(obj: Indexable, via: Any) -> FilteredIndex:
+        '''
+        the objects in this FilteredIndex linked to obj like Index.component_of
+        '''
+    ...
+    def outliers(attr: str, method: str = "iqr", threshold: float | None = None, filter: PyQueryExpr | None = None) -> FilteredIndex:
+        '''
+        flags outliers among the objects in this FilteredIndex like Index.outliers
+        '''
+    ...
+    def duplicates(on: Any, keep: str | None = None) -> Any:
+        '''
+        finds duplicates among the objects in this FilteredIndex like Index.duplicates
+        '''
+    ...
+    def sample(n: int, weights: str | None = None, stratify_by: str | None = None, seed: int | None = None) -> FilteredIndex:
+        '''
+        draws from the objects in this FilteredIndex like Index.sample
+        '''
+    ...
+    def pivot(rows: str, cols: str, metric: tuple[str, str] | None = None) -> dict:
+        '''
+        crosstab of the objects in this FilteredIndex like Index.pivot
+        '''
+    ...
+    def group_agg(by: Any, metrics: dict | None = None, having: PyQueryExpr | None = None) -> list[dict]:
+        '''
+        aggregates the groups of the objects in this FilteredIndex like Index.group_agg
+        '''
+    ...
+    def group_by(by: Any, flat: bool = False) -> dict:
+        '''
+        groups the objects in this FilteredIndex like Index.group_by
+        '''
+    ...
+    def group_by_count(by: Any, flat: bool = False) -> dict:
+        '''
+        counts the objects in this FilteredIndex per group like Index.group_by_count
+        '''
+    ...
+    def ids() -> PyIdSet:
+        '''
+        returns the ids of the objects in this FilteredIndex
+        '''
+    ...
+    def to_roaring_bytes() -> bytes:
+        '''
+        returns the ids of the objects in this FilteredIndex as a roaring bitmap in the portable format,
+        readable by pyroaring, java roaring and others, and by Index.filter_from_roaring_bytes
+        '''
+    ...
+    def to_parquet(path: Any, fields: list[str]) -> int:
+        '''
+        writes fields of the objects in this FilteredIndex to a parquet file at path, one row per object in collect order,
+        read straight from the index without building python objects. dotted fields read nested objects like collect
+        each column takes the type of its values (int, float, bool or str, ints and floats together as float),
+        None and objects without the field are written as null. returns the number of rows written
+        raises ValueError for a field holding other values or values no single column type can hold
+        '''
+    ...
+    def reduced_ids(ids: PyIdSet) -> FilteredIndex:
+        '''
+        returns a FilteredIndex containing only items whose ids are in ids
+        '''
+    ...
+    def union(other: FilteredIndex) -> FilteredIndex:
+        '''
+        returns a FilteredIndex of the objects in either this or other, both taken from the same index
+        like the other set operations, raises ValueError when they are not, and coverage of the result is empty
+        '''
+    ...
+    def intersect(other: FilteredIndex) -> FilteredIndex:
+        '''
+        returns a FilteredIndex of the objects in both this and other
+        '''
+    ...
+    def difference(other: FilteredIndex) -> FilteredIndex:
+        '''
+        returns a FilteredIndex of the objects in this but not in other
+        '''
+    ...
+    def symmetric_difference(other: FilteredIndex) -> FilteredIndex:
+        '''
+        returns a FilteredIndex of the objects in exactly one of this and other
+        '''
+    ...
+    def collect(attrs: list[str] | None = None) -> list[Any]:
+        '''
+        collects all valid objects in the FilteredIndex and returns them as a list, ordered like Index.collect
+        with attrs, returns a dict per object holding only those attributes (None where missing) instead of the objects.
+        objects removed from the index after this FilteredIndex was made are left out
+        '''
+    ...
+    def iter(batch_size: int = 1024) -> ResultIter:
+        '''
+        returns an iterator yielding the objects in collect order without building the whole list,
+        batch_size objects are looked up and handed to python at a time. objects added to the index
+        after the iterator is made never show up, and objects removed before their batch is reached
+        are skipped like collect skips them. attribute values are read live from the objects
+        '''
+    ...
+    def __iter__() -> ResultIter:
+        '''
+        iterates like iter() with its default batch_size
+        '''
+    ...
+    def take(n: int) -> FilteredIndex:
+        '''
+        returns a FilteredIndex of the first n objects in collect order
+        '''
+    ...
+    def skip(n: int) -> FilteredIndex:
+        '''
+        returns a FilteredIndex without the first n objects in collect order, skip(offset).take(limit) pages through results
+        '''
+    ...
+    def rebase() -> Index:
+        '''
+        returns a new Index containing only the items in this FilteredIndex
+        Only use this when a full Index is needed, as it is much less performant
+        '''
+    ...
+
+class PyIdSet:
+    '''
+    immutable set of object ids backed by a roaring bitmap, returned by ids()
+    supports len, in, iteration in ascending order, & | - ^ with other IdSets
+    and == != < <= > >= as set equality and subset tests
+    '''
+    def __init__(ids: list[int] | None = None): ...
+    def __len__() -> int: ...
+    def __bool__() -> bool: ...
+    def __contains__(id: int) -> bool: ...
+    def __iter__() -> Any: ...
+    def __and__(other: PyIdSet) -> PyIdSet: ...
+    def __or__(other: PyIdSet) -> PyIdSet: ...
+    def __sub__(other: PyIdSet) -> PyIdSet: ...
+    def __xor__(other: PyIdSet) -> PyIdSet: ...
+    def __eq__(other: PyIdSet) -> bool: ...
+    def __ne__(other: PyIdSet) -> bool: ...
+    def __lt__(other: PyIdSet) -> bool: ...
+    def __le__(other: PyIdSet) -> bool: ...
+    def __gt__(other: PyIdSet) -> bool: ...
+    def __ge__(other: PyIdSet) -> bool: ...
+    def __repr__() -> str: ...
+    def to_list() -> list[int]:
+        '''
+        returns the ids in ascending order
+        '''
+    ...
+    def to_numpy() -> Any:
+        '''
+        returns the ids as a uint32 numpy array in ascending order, requires numpy
+        '''
+    ...
 
 class Index:
     '''
     Index is a collection of Indexable objects that can be queried and filtered.
     It supports adding and removing objects, as well as querying via attribute filters or query expressions.
-    Experemential Thread safe, but not officially supported yet.
+    Experimental Thread safe, but not officially supported yet.
     '''
-
-    def collect() -> list[Indexable]: 
+    def __init__(max_str_len: int | None = None, strict: bool | None = None, opaque: list[str] | None = None, auto_remove_on_gc: bool | None = None, order: str | None = None, *, config: IndexConfig | None = None):
+        '''
+        max_str_len caps the length, in characters, of strings kept in the positional string index,
+        longer strings are still queryable, matched by content rather than by position
+        strict raises TypeError when adding an object, or assigning a value to an indexed one, holding a value
+        of a type the index can only match by identity (anything but numbers, str, bool, Indexable, containers and None),
+        including inside containers and nested objects, the object is left unchanged
+        opaque lists attributes (dotted paths allowed) that may hold such values in a strict index
+        auto_remove_on_gc holds objects weakly, so python frees them once nothing else refers to them. freed objects are
+        left out of the objects returned straight away, and removed from the index by the next add, reduce, set_column,
+        adopt or union_with, which only look at the objects freed since
+        order sets the order collect returns objects in, on the index and every FilteredIndex taken from it:
+        "id" (ascending object id, the guaranteed default) or "insertion" (the order objects were first added,
+        adding an object already held keeps its place, and numbers objects as sequence() does). raises ValueError for any other order
+        config takes all of these, and attribute declarations, from an IndexConfig instead, raises ValueError
+        when given together with any of the other arguments
+        '''
+    ...
+    def collect(attrs: list[str] | None = None) -> list[Any]:
+        '''
+        collects all valid objects in the index and returns them as a list, ordered as set by the index's order
+        with attrs, returns a dict per object holding only those attributes (None where missing) instead of the objects,
+        dotted attributes like "owner.name" read a nested object's value
+        '''
+    ...
+    def reduced(**kwargs) -> FilteredIndex:
+        '''
+        returns a FilteredIndex containing only items that match the given attribute filters
+        '''
+    ...
+    def reduce(**kwargs):
+        '''
+        removes items in place that do not match the given attribute filters
+        Note that this modifies the current index in place and is less performant
+        than using a method that returns a filteredIndex such as reduced_query or reduced
+        '''
+    ...
+    def get_by_attribute(**kwargs) -> list[Indexable]:
+        '''
+        a shorthand for reduced(**kwargs).collect()
+        more performant than using reduced when you only need the collected results
+        and not the FilteredIndex to further query
+        '''
+    ...
+    def ingest_stream(capacity: int = 16, block: bool = True) -> IngestStream:
+        '''
+        returns an IngestStream adding the batches fed to it on a background thread, for producers that
+        must not outrun indexing. at most capacity batches wait to be added, feeding a full queue waits
+        for room when block is true, otherwise raises queue.Full. errors adding a batch are raised by the
+        next feed, flush or close, use it as a context manager to flush and close on exit
+        '''
+    ...
+    def add_object_many(objs: list[Indexable]):
+        '''
+        adds a multiple objects to the index
+        '''
+    ...
+    def add_dicts(rows: list[dict], cls: type | None = None, *, errors: list | None = None) -> list[Any]:
+        '''
+        builds an object of cls (Indexable by default) from each dict of attribute values and adds them all
+        to the index, like Indexable.construct_many followed by add_object_many. returns the new objects
+        a value that can't be read raises its error and nothing is added, unless errors is a list: the row is then
+        skipped and (row position, attr, exception) appended to it, the other rows are still added
+        '''
+    ...
+    def from_arrow(data: Any, cls: type | None = None) -> Index:
+        '''
+        builds a new Index holding one object of cls (Indexable by default) per row of an arrow record batch,
+        table or anything else exporting the arrow pycapsule interface (__arrow_c_stream__ or __arrow_c_array__).
+        the columns are decoded from the arrow buffers and each is indexed in one pass, both in parallel.
+        values are copied out of the buffers onto the objects directly, without attribute assignment.
+        supports bool, integer, float and string columns, dictionary encoded ones included,
+        null values leave the attribute unset. raises TypeError for any other column type
+        '''
+    ...
+    def add_arrow(data: Any, cls: type | None = None, *, checkpoint_every: int | None = None, on_checkpoint: Any | None = None, resume_from: int = 0) -> int:
+        '''
+        adds one object of cls (Indexable by default) per row of data, read as from_arrow reads it (pandas DataFrames included),
+        returns the number of rows of data added, counting those skipped by resume_from
+        with checkpoint_every, rows are added that many at a time and each chunk is queryable as soon as it is added.
+        on_checkpoint is called with the count after each chunk, the token to resume from: after a crash part way,
+        restore what was added (Index.load of a log kept by enable_wal) and call add_arrow again with resume_from set to it
+        '''
+    ...
+    def save(path: Any):
+        '''
+        writes a snapshot of the index to path: the int, float, str and bool attribute values of every object,
+        in the order collect gives them, the constructor settings (max_str_len, strict, auto_remove_on_gc, order, sequence,
+        hash_mode) and the declarations made on the index or its IndexConfig (semver, units, limit_nested and the like)
+        other values (nested objects, containers, arbitrary objects) are not saved
+        '''
+    ...
+    def enable_wal(path: Any):
+        '''
+        logs every add, remove and update to path so the index can be restored with Index.load(path) after a crash
+        path starts as a snapshot of the index (see save), each change is then appended as a record written straight
+        to the file, so a crash loses at most the change being written. values save leaves out are logged as unset
+        calling it again moves the log to a new path. once a write fails, adding or removing objects raises OSError
+        until compact succeeds
+        '''
+    ...
+    def compact():
+        '''
+        rewrites the log as a snapshot of the current state, dropping the records it has replaced
+        raises ValueError if enable_wal has not been called
+        '''
+    ...
+    def load(path: Any, cls: type | None = None, *, config: IndexConfig | None = None) -> Index:
+        '''
+        builds a new Index from a snapshot written by save or a log kept by enable_wal, one object of cls (Indexable by default) per saved object
+        holding its saved values, added in the saved order. the index takes the saved settings and declarations, and its maps
+        are filled a column at a time from the saved values, without going through python objects
+        raises ValueError if path is not a snapshot or is corrupt
+        with config (an IndexConfig) the index is built from it instead of from what the snapshot holds
+        '''
+    ...
+    def from_pandas(df: Any, cls: type | None = None) -> Index:
+        '''
+        like from_arrow for a pandas DataFrame, one object per row with an attribute per column.
+        needs pandas 2.2 or newer with pyarrow installed, the DataFrame is exported through __arrow_c_stream__
+        '''
+    ...
+    def add_object(py_ref: Indexable):
+        '''
+        adds a single object to the index
+        '''
+    ...
+    def refresh(obj: Indexable, attrs: list[str] | None = None) -> list[str]:
+        '''
+        calls reindex_attr on obj for each of attrs (all of its attributes by default)
+        returns the attributes whose values changed, raises ValueError if obj is not in the index
+        '''
+    ...
+    def reduced_query(query: PyQueryExpr) -> FilteredIndex:
+        '''
+        returns a FilteredIndex containing only items that match the given query expression
+        '''
+    ...
+    def reduced_many(queries: list[PyQueryExpr]) -> list[FilteredIndex]:
+        '''
+        returns a FilteredIndex for each query, like calling reduced_query on each in turn
+        but evaluated together, clauses repeated across the queries are only evaluated once
+        '''
+    ...
+    def ids() -> PyIdSet:
+        '''
+        returns the ids of every object in the index
+        '''
+    ...
+    def reduced_ids(ids: PyIdSet) -> FilteredIndex:
+        '''
+        returns a FilteredIndex of the objects in the index whose ids are in ids
+        '''
+    ...
+    def filter_from_roaring_bytes(buf: bytes) -> FilteredIndex:
+        '''
+        returns a FilteredIndex of the objects in the index whose ids are in the roaring bitmap buf,
+        read in the portable format shared by pyroaring, java roaring and others, ids not in the index are ignored
+        raises ValueError if buf is not a portable roaring bitmap
+        '''
+    ...
+    def explain(query: PyQueryExpr) -> dict:
+        '''
+        runs the query and returns a trace of how it was evaluated
+        each node has clause, matched, time_ns, levels (nested indexes traversed below it) and children
+        clauses on nested attributes also carry nested with attr, levels, child_matches, rollup_ns
+        (time spent mapping matching children back to parents) and the child trace
+        '''
+    ...
+    def column(attr: str, dtype: str = "f64", fill: float | None = None) -> Any:
+        '''
+        builds a dense array.array of attr's numeric values, position i holding the object with id i
+        dtype is f64, f32 or i64, ids without a numeric value take fill (NaN by default for floats)
+        i64 columns require fill, unknown dtypes raise ValueError
+        '''
+    ...
+    def sort_keys(attr: str) -> list[tuple[Indexable, bytes]]:
+        '''
+        returns (object, key) for every object with a numeric or encoded attr, where the 10 byte keys compare like the values,
+        so external or distributed sorts can order objects the way gt, lt and bt do, coerce_numeric and semver included.
+        keys of different kinds, such as numbers and versions, share no meaningful order
+        keys are the index's own encoding and stay the same across indexes and runs
+        '''
+    ...
+    def aggregate(attr: str, funcs: list[str]) -> dict:
+        '''
+        computes numeric aggregates of attr from the numeric index without reading the objects, keyed by func in the order given
+        funcs are sum, count, min, max, mean (or avg), median and percentiles like p95 or p99.9, interpolated like numpy's default
+        objects whose attr is not a number are left out, count is an int and the rest are None when no object has a number there
+        raises ValueError for an unknown func
+        '''
+    ...
+    def order_by(attr: str, descending: bool = False, limit: int | None = None) -> list[Indexable]:
+        '''
+        returns the objects sorted by attr, read in key order from the numeric and string indexes so only
+        the first limit are visited when limit is given. numbers sort first, then encoded values such as versions
+        a kind at a time, then strings, which sort by code point, descending reverses the order.
+        ties go by id and objects without a number, encoded value or string at attr come last
+        '''
+    ...
+    def nearest(attr: str, target: float, k: int = 5) -> list[Indexable]:
+        '''
+        returns up to k objects whose numeric attr is closest to target, nearest first
+        found by walking the numeric index outward from target's position, on equal distance the lower value comes first
+        '''
+    ...
+    def traverse(start_objs: list[Indexable], via: Any, depth: int | None = None, filter: PyQueryExpr | None = None) -> FilteredIndex:
+        '''
+        returns the objects reachable from start_objs by following the Indexables held in the via attributes,
+        directly or inside lists, tuples and sets, for at most depth hops (until nothing new is reached without one)
+        only objects in the index (and matching filter when given) are returned and followed further,
+        start_objs are only returned when reached again e.g. traverse([alice], via=["manager"]) is alice's chain of managers
+        references are read from the stored values, so graphs with cycles can use limit_nested(attr, depth=0) on the via attributes
+        '''
+    ...
+    def connected(a: Indexable, b: Indexable, via: Any, max_depth: int | None = 6) -> bool:
+        '''
+        whether a and b are linked through at most max_depth references held in the via attributes (no limit with None),
+        references count in both directions and only objects in the index are passed through
+        '''
+    ...
+    def component_of(obj: Indexable, via: Any) -> FilteredIndex:
+        '''
+        returns obj and every object in the index linked to it through references held in the via attributes,
+        followed in both directions, empty when obj is not in the index
+        '''
+    ...
+    def outliers(attr: str, method: str = "iqr", threshold: float | None = None, filter: PyQueryExpr | None = None) -> FilteredIndex:
+        '''
+        returns a FilteredIndex of the objects whose numeric attr lies outside the bounds of method, taken over
+        the objects matching filter (all of them by default). iqr bounds are threshold (1.5) interquartile ranges
+        beyond the quartiles, read from the numeric index by rank, zscore bounds threshold (3.0) standard deviations
+        from the mean. both take the same numbers aggregate does
+        '''
+    ...
+    def sequence() -> int:
+        '''
+        returns the sequence number the next object added will get. every add numbers each object new to the index
+        with the next number of a counter that never goes back, re-adding an object already held keeps its number
+        objects are only numbered by an index created with IndexConfig().sequence() or order="insertion",
+        sequence, since and latest raise ValueError on any other
+        '''
+    ...
+    def since(seq: int, filter: PyQueryExpr | None = None) -> FilteredIndex:
+        '''
+        returns the objects added at or after seq (as given by sequence()), only those matching filter when given
+        '''
+    ...
+    def latest(n: int, filter: PyQueryExpr | None = None) -> list[Indexable]:
+        '''
+        returns up to n of the most recently added objects, newest first, only those matching filter when given
+        '''
+    ...
+    def duplicates(on: Any, keep: str | None = None) -> Any:
+        '''
+        returns the ids of each group of objects sharing the values of every attribute in on
+        with keep, returns a FilteredIndex of the redundant objects instead, all but the lowest id ("first")
+        or the highest id ("last") of each group. objects missing an attribute or holding a list, set or
+        dict there are never duplicates
+        '''
+    ...
+    def sample(n: int, weights: str | None = None, stratify_by: str | None = None, seed: int | None = None) -> FilteredIndex:
+        '''
+        returns a FilteredIndex of n objects drawn at random without replacement, all of them when there are fewer
+        weights draws proportionally to a numeric attribute, objects without a positive number there are never drawn
+        stratify_by draws n objects for each value of the attribute, seed makes the draw repeatable
+        '''
+    ...
+    def pivot(rows: str, cols: str, metric: tuple[str, str] | None = None) -> dict:
+        '''
+        crosstab of rows against cols as {row value: {col value: cell}}, combinations without objects are left out
+        a cell holds the number of objects, or with metric as (attr, func) the aggregate of attr over them
+        like the group_agg metrics
+        '''
+    ...
+    def group_agg(by: Any, metrics: dict | None = None, having: PyQueryExpr | None = None) -> list[dict]:
+        '''
+        one row per group of by, sorted by key, holding the group's values, its count and a {attr}_{func} column
+        for each metric (sum, count, min, max, avg or mean over the attr's numbers, None when a group has none)
+        having filters the rows with comparisons, bt, in, and, or and not over count and the metric columns
+        '''
+    ...
+    def group_by(by: Any, flat: bool = False) -> dict:
+        '''
+        groups the objects by the value of one attribute, or of each attribute in a list, into FilteredIndexes
+        a single attribute is keyed by its value, a list gives one nested dict level per attribute
+        or, with flat, a dict keyed by tuples of values. objects missing an attribute or holding
+        a list, set or dict there are left out. dotted attributes like "owner.region" group by a nested object's value.
+        groups are sorted by key, numbers before strings, values python considers equal (1, 1.0, True) sharing one
+        '''
+    ...
+    def group_by_count(by: Any, flat: bool = False) -> dict:
+        '''
+        like group_by, with the number of objects in each group in place of the FilteredIndex
+        '''
+    ...
+    def attributes() -> list[dict]:
+        '''
+        describes every queryable attribute on the index, sorted by name
+        each entry has name, types (python types seen), count (objects holding the attribute),
+        structures (numeric, string, boolean, exact) and nested (whether nested objects are indexed under it)
+        '''
+    ...
+    def hot_attributes(limit: int | None = None) -> list[dict]:
+        '''
+        lists the attributes whose values changed on objects already in the index, the most changed first
+        each entry has name, updates (how many times a value was set) and last_modified (the number of the latest
+        change, counted across all attributes so a higher one changed more recently). adding objects is not counted
+        '''
+    ...
+    def metrics() -> dict:
+        '''
+        returns the index's counters: reads (queries run through reduced, reduced_query, reduced_many and
+        get_by_attribute, each query of a batch counted), writes (objects added, updated or removed, counted per
+        object and change), lock_contention (times a lock was found held and had to be waited on) and
+        slow_queries (entries in the slow query log)
+        '''
+    ...
+    def log_slow_queries(threshold_ms: float | None, capacity: int = ...):
+        '''
+        logs queries taking at least threshold_ms milliseconds, keeping the latest capacity of them,
+        see slow_queries. a threshold of None turns the log off, entries already logged are kept
+        '''
+    ...
+    def slow_queries(clear: bool = False) -> list[dict]:
+        '''
+        returns the logged slow queries, oldest first, each with query (its repr), time_ns, matched and
+        profile (an explain trace of running it again, None for a reduced_many batch, logged as one entry)
+        clear empties the log after reading it
         '''
-        collects all valid objects in the index and returns them as a list
+    ...
+    def sparsity() -> dict[str, float]:
+        '''
+        maps every attribute held by an object in the index to the fraction of objects holding it, sorted by name
+        attributes excluded from indexing are reported too, an empty index gives an empty dict
         '''
     ...
-    def add_object(obj: Indexable): 
+    def infer_schema(categorical_limit: int = 20) -> dict:
         '''
-        adds a single object to the index
+        returns a json-schema like description of the indexed objects
+        properties carry the json type(s) seen, nested objects and list elements are described recursively,
+        "null" is added to the type when None was seen and only attributes on every object are required
+        string attributes with at most categorical_limit repeated values are reported with an enum
         '''
     ...
-    def add_object_many(objects: list[Indexable]):
+    def limit_nested(attr: str, depth: int | None = None, only: list[str] | None = None):
         '''
-        adds a multiple objects to the index
+        limits how much of the object graph under attr is indexed
+        depth is the number of nested levels indexed below attr (0 indexes none of attr's fields)
+        only is a projection of attr's fields to index, dotted paths reach further down e.g. ["id", "address.city"]
+        must be called before any objects are added
         '''
     ...
-    def reduce(**kwargs): 
+    def coerce_numeric(attr: str):
         '''
-        removes items in place that do not match the given attribute filters
-        Note that this modifies the current index in place and is less performant
-        than using a method that returns a filteredIndex such as reduced_query or reduced
+        also indexes strings held by attr (dotted paths allowed) that parse as numbers, e.g. "42" or "1.5e3",
+        so numeric comparisons and ranges match them, the stored value stays the original string
+        must be called before any objects are added
         '''
     ...
-    def reduced(**kwargs) -> FilteredIndex:
+    def semver(attr: str):
         '''
-        returns a FilteredIndex containing only items that match the given attribute filters
+        orders strings held by attr (dotted paths allowed) that parse as semantic versions, e.g. "1.10.0" or "v2.0.0-rc.1",
+        so gt, ge, lt, le and bt take version strings and compare by major, minor, patch then prerelease
+        major, minor and patch must be below 16384, prereleases are ordered by the first three letters of their tag then its first number
+        packaging.version.Version values are ordered on the same scale when packaging is installed, through an encoder
+        registered for the whole process like register_encoder, unregister_encoder(Version) removes it
+        eq keeps comparing the strings, must be called before any objects are added
         '''
     ...
-    def reduced_query(query: PyQueryExpr) -> FilteredIndex:
+    def interval(attr: str):
         '''
-        returns a FilteredIndex containing only items that match the given query expression
+        indexes (start, end) pairs held by attr (dotted paths allowed) as closed intervals,
+        so overlaps and contains_point can match them, pairs are two item lists or tuples of numbers with start <= end
+        must be called before any objects are added
         '''
     ...
-    def get_by_attribute(**kwargs):
+    def flags(attr: str):
+        '''
+        indexes ints held by attr (dotted paths allowed) bit by bit, so has_flag, flags_all and flags_any
+        are a few bitmap ANDs / ORs instead of a scan over every number held by attr
+        must be called before any objects are added
+        '''
+    ...
+    def float_tolerance(attr: str, tolerance: float):
+        '''
+        makes eq, ne and in on numbers match values held by attr (dotted paths allowed) within tolerance of them,
+        e.g. after float_tolerance("temp", 0.05) eq("temp", 21.5) is bt("temp", 21.45, 21.55), also through FilteredIndex
+        only read at query time, so it can be set or changed at any point, 0 goes back to exact matching
+        raises ValueError on a negative or non finite tolerance
+        '''
+    ...
+    def units(attr: str, unit: str):
+        '''
+        declares the numbers held by attr (dotted paths allowed) to be in unit, so comparisons on it also take quantities,
+        e.g. after units("latency", "ms") gt("latency", "2s") is gt("latency", 2000), plain numbers are taken as already in unit
+        units are ns, us, ms, s, min, h, d (time), B, KB, MB, GB, TB, KiB, MiB, GiB, TiB (data size), mm, cm, m, km (length)
+        and mg, g, kg (mass). only read at query time, stored values are not converted
+        raises ValueError for an unknown unit, and when queried with a quantity of another dimension or a string that is not one
+        '''
+    ...
+    def fixed_point(attr: str, places: int = 2):
+        '''
+        stores the numbers held by attr (dotted paths allowed) as whole counts of 10^-places, places being 2 or 4,
+        so comparisons on it are exact and sum / avg in group_agg, pivot, agg and alert_when add without float error.
+        floats are rounded to places when stored and when given in a query. must be declared before objects are added
+        '''
+    ...
+    def set_column(attr: str, ids: list[int], values: list[Any]):
+        '''
+        assigns values[i] to attr of the object with id ids[i] in one pass, ids as given by ids()
+        any sequences work, numpy arrays included, and alerts run once after every value is in place
+        every id and value is checked first, nothing is assigned if one is not in the index or refused by a strict index
+        '''
+    ...
+    def bulk_update() -> BulkUpdate:
+        '''
+        returns a context manager holding back index maintenance for assignments to this index's objects until
+        it exits, then updating each object assigned to once, from its value before the block to the one it holds.
+        for many assignments, or several to the same attribute, in a row. queries in the block see the values
+        from before it for the attributes assigned, alerts run on exit. blocks may be nested, the outermost applies
+        the changes. the index is brought up to date even when the block raises
+        '''
+    ...
+    def update_many(objs: list[Indexable], **kwargs):
+        '''
+        sets every attribute given on each of objs, like Indexable.update on each but with alerts run once at the end
+        every value is checked for every object first, nothing is set if one is refused (declared field types, strict indexes)
+        '''
+    ...
+    def track_top_k(attr: str, k: int = 100):
+        '''
+        keeps the k objects with the largest numeric attr up to date as objects are added, updated and removed,
+        so top_k can return them without querying, ties on value are broken by object id
+        calling it again for attr replaces k
+        '''
+    ...
+    def top_k(attr: str) -> list[Indexable]:
+        '''
+        returns the objects tracked for attr by track_top_k, largest first
+        raises ValueError when attr is not tracked
+        '''
+    ...
+    def alert_when(metric: tuple[str, str], op: str, value: float, filter: PyQueryExpr | None = None, *, callback: Any):
+        '''
+        calls callback with the aggregate when a mutation (add, update, reduce, adopt, union) brings it across the threshold
+        metric is (func, attr) with func one of sum, count, min, max, avg over the objects matching filter,
+        op is one of == != > >= < <= comparing the aggregate to value, e.g. alert_when(("sum", "exposure"), ">", 1e6, callback=notify)
+        the aggregate is kept up to date from the changed objects only, it fires again only after falling back out of the threshold
+        a callback raising is reported as a RuntimeWarning, the mutation that fired it still goes through
+        '''
+    ...
+    def adopt(other: Index, query: PyQueryExpr | None = None):
+        '''
+        adds every object held by another index (optionally only those matching query) to this index
+        the objects are shared, so later attribute updates keep both indexes consistent
+        '''
+    ...
+    def warmup(attrs: list[str] | None = None):
+        '''
+        prepares the structures behind attrs, every attribute when None, so the first queries after a load run as fast as later ones
+        pending ids are flushed, bitmaps run compressed and shrunk, hash shards rehashed, nested indexes under attrs included
+        queries return the same results before and after, raises ValueError if an attribute is not indexed
+        '''
+    ...
+    def freeze_mutations():
+        '''
+        makes the index read-only, for serving queries after a load phase
+        adding, removing (reduce, reap) or updating objects held by it then raises ValueError, on nested objects too:
+        attribute assignment, Indexable.update, refresh, set_column, update_many, bulk_update, add_dicts, add_arrow and ingest streams
+        queries and views keep working, a frozen index cannot be unfrozen
+        waits for changes already under way and applies those an open bulk_update holds back, then optimizes the maps
+        like warmup and takes them out of their locks, so queries from many threads read them without waiting on each other.
+        raises ValueError when called by code changing the index, such as an alert callback
+        '''
+    ...
+    def is_frozen() -> bool:
+        '''
+        whether freeze_mutations has been called
+        '''
+    ...
+    def reap() -> int:
+        '''
+        removes objects nothing outside the index refers to any more, letting python free them, returns how many were removed
+        the index itself keeps every object it holds alive, so they would otherwise stay until removed by reduce.
+        scans every object, an index made with auto_remove_on_gc only has to drop those python already freed
+        raises ValueError on a frozen index
+        '''
+    ...
+    def replicate_to(other: Index):
+        '''
+        keeps other a warm standby of this index: it gets a copy of every object held here, then every add, update and
+        removal made here is applied to it as well, before the call making it returns. copies hold the same values as the
+        objects they copy but are separate objects, so queries on other never wait on writes here
+        other stays writable, changes made to it are not sent back. replication ends when other is dropped
+        a change other refuses (frozen, strict) is raised by the call here that made it, changes made while this runs may be left out
+        raises ValueError if other is this index, already a standby of it, or replicates back to it
+        for a standby in another process, keep a log with enable_wal and read it there with Index.load
         '''
-        a shorthand for reduced(**kwargs).collect()
-        more performant than using reduced when you only need the collected results
-        and not the FilteredIndex to further query
-        '''    
     ...
     def union_with(other: Index):
         '''
         returns a new Index that is the union of this index and another index
         does not mutate the other index
-        '''    
+        safe to run from several threads at once, in either direction, objects added to other while it runs are left out
+        '''
     ...
 
-class FilteredIndex:
+class IndexConfig:
     '''
-    FilteredIndex is a view into an Index with an allow list of items.
-    It supports the same querying and filtering operations as Index
-    but does not support adding or removing objects.
+    settings for a new Index, built up one call at a time and passed as Index(config=...)
+    every method returns a new IndexConfig, e.g. IndexConfig().strict().order("insertion").semver("version")
+    attribute declarations are applied when the index is created, as if called on it before adding objects
+    to_dict / from_dict round trip it through plain python values. Index.save writes every setting but wal into the snapshot,
+    declarations included, so Index.load rebuilds the index without one. object ids are 32 bit whatever the settings,
+    an index holds at most 2**32 objects
     '''
-    def reduced(**kwargs) -> FilteredIndex:
+    def __init__(): ...
+    def max_str_len(max_str_len: int) -> IndexConfig:
+        '''
+        see Index for what each setting does
         '''
-        returns a FilteredIndex containing only items that match the given attribute filters
-        '''    
     ...
-    def reduced_query(query: PyQueryExpr) -> FilteredIndex:
+    def strict(strict: bool = True) -> IndexConfig: ...
+    def auto_remove_on_gc(auto_remove_on_gc: bool = True) -> IndexConfig: ...
+    def order(order: str) -> IndexConfig:
         '''
-        returns a FilteredIndex containing only items that match the given query expression
+        raises ValueError for an order other than "id" or "insertion"
         '''
     ...
-    def collect() -> list[Indexable]:
+    def sequence(sequence: bool = True) -> IndexConfig:
         '''
-        collects all valid objects in the FilteredIndex and returns them as a list
+        numbers objects as they are added, for Index.sequence / since / latest
         '''
     ...
-    def rebase() -> Index:
+    def hash_mode(mode: str) -> IndexConfig:
+        '''
+        raises ValueError for a mode other than "identity" or "stable"
+        '''
+    ...
+    def shards(shards: int) -> IndexConfig:
+        '''
+        shards of each attribute's exact value map, assignments to values in different shards don't wait on each other
+        raises ValueError unless a power of two
+        '''
+    ...
+    def threads(threads: int) -> IndexConfig:
+        '''
+        worker threads for add_arrow and load, which fill the maps a column per thread, rayon's global pool when not set
+        raises ValueError for 0
         '''
-        returns a new Index containing only the items in this FilteredIndex
-        Only use this when a full Index is needed, as it is much less performant
-        '''    
     ...
+    def wal(path: Any) -> IndexConfig:
+        '''
+        keeps a write ahead log at path from the start, as Index.enable_wal(path) does
+        '''
+    ...
+    def opaque(attr: str) -> IndexConfig: ...
+    def coerce_numeric(attr: str) -> IndexConfig: ...
+    def semver(attr: str) -> IndexConfig: ...
+    def interval(attr: str) -> IndexConfig: ...
+    def flags(attr: str) -> IndexConfig: ...
+    def units(attr: str, unit: str) -> IndexConfig:
+        '''
+        raises ValueError for an unknown unit
+        '''
+    ...
+    def fixed_point(attr: str, places: int = 2) -> IndexConfig: ...
+    def float_tolerance(attr: str, tolerance: float) -> IndexConfig: ...
+    def limit_nested(attr: str, depth: int | None = None, only: list[str] | None = None) -> IndexConfig: ...
+    def to_dict() -> dict:
+        '''
+        returns the settings as a dict of plain python values, only those changed from the defaults
+        '''
+    ...
+    def from_dict(data: dict) -> IndexConfig:
+        '''
+        builds an IndexConfig from a dict written by to_dict, raises ValueError for an unknown key or setting
+        '''
+    ...
+    def __eq__(other: IndexConfig) -> bool: ...
+    def __repr__() -> str: ...
 
-class PyQueryExpr:
-    def and_(queries: list[PyQueryExpr]) -> PyQueryExpr:
+class IngestStream:
+    '''
+    writer returned by Index.ingest_stream, adding batches of objects to the index on a background thread
+    '''
+    def feed(batch: list[Indexable]):
+        '''
+        queues a batch of objects to be added to the index. when the queue is full, waits for room if the
+        stream blocks, otherwise raises queue.Full and the batch is not queued
+        raises the error adding an earlier batch, if one failed since the last check
         '''
-        all subqueries must be true
-        '''    
     ...
-    def or_(queries: list[PyQueryExpr]) -> PyQueryExpr:
+    def flush():
+        '''
+        waits until every batch fed so far is in the index, then raises the error adding one of them, if any
         '''
-        at least one subquery must be true
-        '''    
     ...
-    def not_(queries: PyQueryExpr) -> PyQueryExpr:
+    def close():
+        '''
+        flushes and stops the background thread, feeding afterwards raises ValueError. closing twice does nothing
         '''
-        negates the subquery
-        '''    
     ...
+    def __enter__() -> IngestStream: ...
+    def __exit__(exc_type: Any | None, _exc: Any | None, _tb: Any | None) -> bool: ...
+
+class QueryParam:
+    '''
+    a named placeholder created by QueryExpr.param
+    '''
+    def __repr__() -> str: ...
 
-    def eq(attr: str, value: any) -> PyQueryExpr:
+class PreparedQuery:
+    '''
+    a normalized query template with named placeholders
+    '''
+    params: list[str]
+    def bind(**kwargs) -> PyQueryExpr:
+        '''
+        returns the query with every placeholder replaced by the matching keyword value
+        raises ValueError on missing or unknown parameters
         '''
-        attribute equals value
-        '''    
     ...
-    def in_(attr: str, values: list[any]) -> PyQueryExpr:
+    def __repr__() -> str: ...
+
+class PyQueryExpr:
+    def eq(attr: str, value: Any) -> PyQueryExpr:
         '''
-        attribute is in the list of values
-        equivilant to multiple or'd eq queries
+        attribute equals value
         '''
     ...
-    def ne(attr: str, value: any) -> PyQueryExpr:
+    def ne(attr: str, value: Any) -> PyQueryExpr:
         '''
         attribute does not equal value
-        '''    
+        '''
     ...
-
-    def gt(attr: str, value: int | float) -> PyQueryExpr:
+    def is_null(attr: str) -> PyQueryExpr:
+        '''
+        attribute is set to None, objects without the attribute are not included (see missing)
+        '''
+    ...
+    def is_not_null(attr: str) -> PyQueryExpr:
+        '''
+        attribute is set to a value other than None, objects without the attribute are not included
+        '''
+    ...
+    def gt(attr: str, value: Any) -> PyQueryExpr:
         '''
         attribute is greater than value
         '''
     ...
-    def ge(attr: str, value: int | float) -> PyQueryExpr:
+    def ge(attr: str, value: Any) -> PyQueryExpr:
         '''
         attribute is greater than or equal to value
-        '''    
+        '''
+    ...
+    def le(attr: str, value: Any) -> PyQueryExpr:
+        '''
+        attribute is less than or equal to value
+        '''
+    ...
+    def bt(attr: str, lower: Any, upper: Any) -> PyQueryExpr:
+        '''
+        attribute is between lower and upper, inclusive
+        string bounds match string values, compared by code point (so "B" < "a" and "A100" < "A20"),
+        lower and upper must both be strings or both numbers. datetimes, dates and timedeltas are ordered
+        against their own kind only (datetimes as instants in UTC, naive datetimes taken as UTC)
+        '''
     ...
-    def lt(attr: str, value: int | float) -> PyQueryExpr:
+    def lt(attr: str, value: Any) -> PyQueryExpr:
         '''
         attribute is less than value
         '''
     ...
-    def le(attr: str, value: int | float) -> PyQueryExpr:
+    def in_(attr: str, values: list[Any]) -> PyQueryExpr:
+        '''
+        attribute is in the list of values
+        equivalent to multiple or'd eq queries
         '''
-        attribute is less than or equal to value
-        '''    
     ...
-    def bt(attr: str, lower: int | float, upper: int | float) -> PyQueryExpr:
+    def nin(attr: str, values: list[Any]) -> PyQueryExpr:
+        '''
+        attribute is not in the list of values, objects without the attribute included
+        equivalent to not_(in_(attr, values))
         '''
-        attribute is between lower and upper, inclusive
-        '''    
     ...
-
-    def starts_with(attr, start: str) -> PyQueryExpr:
+    def and_(*exprs) -> PyQueryExpr:
+        '''
+        all subqueries must be true
+        '''
+    ...
+    def or_(*exprs) -> PyQueryExpr:
+        '''
+        at least one subquery must be true
+        '''
+    ...
+    def not_(exprs: PyQueryExpr) -> PyQueryExpr:
+        '''
+        negates the subquery
+        '''
+    ...
+    def starts_with(attr: str, value: Any) -> PyQueryExpr:
         '''
         attribute where the string value starts with the specified start
         '''
     ...
-    def ends_with(attr, ends: str) -> PyQueryExpr:
+    def ends_with(attr: str, value: Any) -> PyQueryExpr:
         '''
         attribute where the string value ends with the specified end
         '''
     ...
-    def contains(attr, inner: str) -> PyQueryExpr:
+    def contains(attr: str, value: Any) -> PyQueryExpr:
         '''
         attribute where the string value contains with the specified string
         '''
     ...
+    def matches(attr: str, pattern: str, regex: bool = False) -> PyQueryExpr:
+        '''
+        attribute where the whole string value matches pattern, a glob (* any run, ? one character,
+        [abc] / [a-z] / [!abc] a class) or with regex a regular expression. raises ValueError on an invalid pattern
+        e.g. matches("sku", "AB-*-2?") or matches("sku", "AB-[0-9]+", regex=True)
+        '''
+    ...
+    def overlaps(attr: str, lower: Any, upper: Any) -> PyQueryExpr:
+        '''
+        attribute declared with Index.interval shares at least one point with [lower, upper], endpoints included
+        '''
+    ...
+    def contains_point(attr: str, point: Any) -> PyQueryExpr:
+        '''
+        attribute declared with Index.interval contains point, endpoints included
+        '''
+    ...
+    def has_flag(attr: str, flag: int) -> PyQueryExpr:
+        '''
+        int attribute has flag set, flag is a single bit e.g. has_flag("permissions", 0b0100)
+        raises ValueError when flag has no or several bits set (see flags_all)
+        '''
+    ...
+    def flags_all(attr: str, mask: int) -> PyQueryExpr:
+        '''
+        int attribute has every bit of mask set, attr & mask == mask
+        answered from per bit bitmaps when attr was declared with Index.flags, otherwise by scanning its numbers
+        '''
+    ...
+    def flags_any(attr: str, mask: int) -> PyQueryExpr:
+        '''
+        int attribute has at least one bit of mask set, attr & mask != 0
+        '''
+    ...
+    def mod_eq(attr: str, divisor: int, remainder: int) -> PyQueryExpr:
+        '''
+        int attribute leaves remainder when divided by divisor, python's % so negatives count too
+        e.g. workers each claim mod_eq("id", divisor=8, remainder=worker), every object falls to exactly one.
+        answered from per bit bitmaps when attr was declared with Index.flags, otherwise by scanning its numbers.
+        raises ValueError unless divisor > 0 and 0 <= remainder < divisor
+        '''
+    ...
+    def agg(attr: str, func: str, child_attr: str, **kwargs) -> PyQueryExpr:
+        '''
+        aggregate over the nested Indexables held in a collection attribute
+        func is one of sum, count, min, max, avg and exactly one comparison
+        (eq, ne, gt, ge, lt, le) is applied to the aggregated value
+        e.g. agg("items", "sum", "price", gt=100)
+        '''
+    ...
+    def count_children(attr: str, **kwargs) -> PyQueryExpr:
+        '''
+        number of nested Indexables currently indexed under attr
+        exactly one comparison (eq, ne, gt, ge, lt, le) is applied to the count
+        e.g. count_children("items", ge=3)
+        '''
+    ...
+    def exists(attr: str) -> PyQueryExpr:
+        '''
+        attribute is set on the object, whatever its value (None included)
+        '''
+    ...
+    def missing(attr: str) -> PyQueryExpr:
+        '''
+        attribute is not set on the object at all
+        equivalent to not_(exists(attr))
+        '''
+    ...
+    def custom(name: str, attr: str, value: Any) -> PyQueryExpr:
+        '''
+        attribute matches the operator registered under name (see register_operator) for value
+        e.g. custom("bits_all", "flags", 0b101). raises ValueError when no operator is registered under name
+        '''
+    ...
+    def param(name: str) -> QueryParam:
+        '''
+        placeholder usable anywhere a constant is expected, bound later through prepare()
+        e.g. QueryExpr.gt("price", QueryExpr.param("min_price"))
+        '''
+    ...
+    def prepare() -> PreparedQuery:
+        '''
+        returns a PreparedQuery for this expression so its placeholders can be bound repeatedly
+        '''
+    ...
+    def rebind(old: str, new: str) -> PyQueryExpr:
+        '''
+        returns a copy of this expression with attribute old renamed to new
+        paths below old are renamed too, e.g. rebind("owner", "holder") turns owner.name into holder.name
+        '''
+    ...
+    def normalize() -> PyQueryExpr:
+        '''
+        returns a simplified copy of this expression
+        nested and_/or_ are flattened, not_ is pushed through and_/or_ (not_ of eq becomes ne and back)
+        and an inverted bt collapses to an expression that matches nothing
+        bounds on the same attribute are kept apart, list and nested values can meet each with a different element
+        queries are normalized automatically when evaluated, and_ branches then run most selective first,
+        estimated from the index (bool and object values are counted exactly, missing attributes match nothing)
+        '''
+    ...
+    def __and__(other: PyQueryExpr) -> PyQueryExpr:
+        '''
+        a & b is the same as and_(a, b)
+        '''
+    ...
+    def __or__(other: PyQueryExpr) -> PyQueryExpr:
+        '''
+        a | b is the same as or_(a, b)
+        '''
+    ...
+    def __invert__() -> PyQueryExpr:
+        '''
+        ~a is the same as not_(a)
+        '''
+    ...
+    def __str__() -> str: ...
+    def __repr__() -> str: ...
 
-class Indexable:
+class ResultIter:
     '''
-    Base class for objects that can be indexed.
-    All attributes will be indexed unless the attribute name is prefixed with an underscore
-    Nested Indexable objects are supported and fully queryable via dot notation.
+    lazy iterator over query results returned by FilteredIndex.iter
+    the ids are fixed when the iterator is made, objects added afterwards never show up
+    and objects removed before their batch is reached are skipped
+    '''
+    def __iter__() -> ResultIter: ...
+    def __next__() -> Indexable | None: ...
+    def __length_hint__() -> int: ...
+
+def attr(name: str) -> Attr:
+    '''
+    returns an attribute proxy for building queries with operators
+    '''
+    ...
+
+def why_not(index: Index, query: PyQueryExpr, obj: Indexable) -> list[dict]:
+    '''
+    lists the clauses of query that obj fails, empty when obj matches
+    each entry has clause, attr, present (whether obj has the attribute) and actual (its value)
+    '''
+    ...
+
+def handles(index: Index) -> dict:
+    '''
+    python handles kept alive by index, for tracking down objects that are not freed
+    held and stale (held but no longer allowed) count slots, handle_refs the strong references across all indexes sharing them
+    classes breaks held down by type name, nested_indexes and nested_held cover the attribute indexes below
+    '''
+    ...
+
+def register_encoder(cls: type, encoder: Any):
+    '''
+    indexes values of exactly type cls under encoder(value), which returns an int, a float
+    or a tuple of up to 4 non negative ints (each below 2**(63 // len)) compared in tuple order
+    eq, gt, lt, bt etc. then take instances of cls, ordered against other instances of cls only,
+    so an encoded value never matches or compares with a plain number
+    an encoder raising fails the object's construction or the assignment with its exception,
+    query values it raises on are matched by identity only
+    applies to values assigned afterwards, so register before building indexes
+    '''
+    ...
+
+def unregister_encoder(cls: type) -> bool:
+    '''
+    removes the encoder for cls, returns whether one was registered
+    '''
+    ...
+
+def register_extractor(cls: type, extractor: Any):
+    '''
+    indexes values of exactly type cls that have no encoder through extractor(value), which returns a dict
+    of sub fields indexed like a nested object, so "attr.field" queries reach them (e.g. numpy scalars, value objects)
+    the value itself is still matched by identity. an extractor raising, or returning something other than a dict,
+    fails the object's construction or the assignment with that error
+    applies to values assigned afterwards, so register before building indexes
+    '''
+    ...
+
+def unregister_extractor(cls: type) -> bool:
+    '''
+    removes the extractor for cls, returns whether one was registered
+    '''
+    ...
+
+def register_operator(name: str, predicate: Any):
+    '''
+    makes name usable in QueryExpr.custom(name, attr, value), evaluated as predicate(attribute value, value)
+    once per object holding the attribute, objects where it returns a truthy value match and raising counts as no match
+    python operators hold the GIL for the whole scan, so they suit small candidate sets,
+    bits_all and bits_any (int flag tests) are built in and run natively
+    replaces any operator already registered under name
+    '''
+    ...
+
+def unregister_operator(name: str) -> bool:
+    '''
+    removes the operator registered under name, returns whether one was registered
+    queries already built with it match nothing afterwards
+    '''
+    ...
+
+def set_hash_mode(mode: str):
+    '''
+    selects how indexes created afterwards hash Indexable values, "identity" (default) hashes by object address,
+    "stable" hashes by id so hashes repeat across processes
+    list/tuple/set/dict values always hash by content
+    indexes already built keep their mode, IndexConfig().hash_mode(...) sets it for one index
+    '''
+    ...
+
+def get_hash_mode() -> str:
+    '''
+    returns the hash mode new indexes take, "identity" or "stable"
+    '''
+    ...
+
+def sum_as_string(a: int, b: int) -> str:
     '''
-...
+    Formats the sum of two numbers as string.
+    '''
+    ...
@@ -1,4 +1,12 @@
 from .PyThermite import Indexable
 from .PyThermite import Index
+from .PyThermite import IndexConfig
 from .PyThermite import FilteredIndex
-from .PyThermite import PyQueryExpr as QueryExpr
\ No newline at end of file
+from .PyThermite import PyQueryExpr as QueryExpr
+from .PyThermite import PyIdSet as IdSet
+from .PyThermite import PreparedQuery
+from .PyThermite import attr
+from .PyThermite import why_not, handles
+from .PyThermite import set_hash_mode, get_hash_mode
+from .PyThermite import register_encoder, unregister_encoder, register_extractor, unregister_extractor
+from .PyThermite import register_operator, unregister_operator
@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+
+use croaring::Bitmap;
+use ordered_float::OrderedFloat;
+use pyo3::{Py, PyAny, PyResult, Python, exceptions::PyValueError};
+use rustc_hash::FxHashMap;
+
+use crate::index::{core::{fixed_point::{from_fixed, to_fixed}, query::{aggregate::{AggFunc, CmpOp}, query_ops::QueryExpr}, stored_item::StoredItem, structures::string_interner::INTERNER}, types::StrId, value::{PyValue, RustCastValue}};
+
+pub fn parse_op(op: &str) -> PyResult<CmpOp> {
+    let symbol = [CmpOp::Eq, CmpOp::Ne, CmpOp::Gt, CmpOp::Ge, CmpOp::Lt, CmpOp::Le].into_iter()
+        .find(|cmp| cmp.symbol() == op);
+    symbol.or_else(|| CmpOp::parse(op))
+        .ok_or_else(|| PyValueError::new_err(format!("unknown op '{}', expected one of == != > >= < <=", op)))
+}
+
+// an aggregate over the objects matching filter, kept current one changed id at a time
+pub struct Alert {
+    attr: StrId,
+    func: AggFunc,
+    op: CmpOp,
+    threshold: f64,
+    filter: Option<QueryExpr>,
+    callback: Py<PyAny>,
+    values: FxHashMap<u32, f64>,
+    sum: f64,
+    // exact sum in whole 10^-places units, for fixed point attributes
+    places: Option<u8>,
+    units: i128,
+    // how many members hold each value, for min and max
+    ordered: BTreeMap<OrderedFloat<f64>, u32>,
+    // whether the aggregate met the threshold after the last update
+    holds: bool,
+}
+
+impl Alert {
+    pub fn new(func: &str, attr: &str, op: CmpOp, threshold: f64, filter: Option<QueryExpr>, callback: Py<PyAny>) -> PyResult<Self> {
+        let func = AggFunc::parse(func).ok_or_else(|| {
+            PyValueError::new_err(format!("unknown aggregate '{}', expected one of sum, count, min, max, avg", func))
+        })?;
+        Ok(Self {
+            attr: INTERNER.intern(attr),
+            func,
+            op,
+            threshold,
+            filter,
+            callback,
+            values: FxHashMap::default(),
+            sum: 0.0,
+            places: None,
+            units: 0,
+            ordered: BTreeMap::new(),
+            holds: false,
+        })
+    }
+
+    pub fn with_places(mut self, places: Option<u8>) -> Self {
+        self.places = places;
+        self
+    }
+
+    #[inline]
+    fn units_of(&self, value: f64) -> i128 {
+        self.places.and_then(|places| to_fixed(&RustCastValue::Float(value), places)).unwrap_or(0) as i128
+    }
+
+    fn insert(&mut self, id: u32, value: f64) {
+        self.values.insert(id, value);
+        self.sum += value;
+        self.units += self.units_of(value);
+        *self.ordered.entry(OrderedFloat(value)).or_default() += 1;
+    }
+
+    fn remove(&mut self, id: u32) {
+        let Some(value) = self.values.remove(&id) else {
+            return;
+        };
+        self.sum -= value;
+        self.units -= self.units_of(value);
+        if let Some(count) = self.ordered.get_mut(&OrderedFloat(value)) {
+            *count -= 1;
+            if *count == 0 {
+                self.ordered.remove(&OrderedFloat(value));
+            }
+        }
+    }
+
+    // same results as the group_agg metrics
+    pub fn value(&self) -> Option<f64> {
+        let count = self.values.len();
+        if count == 0 {
+            return self.func.empty_value();
+        }
+        let sum = self.places.map_or(self.sum, |places| from_fixed(self.units, places));
+        match self.func {
+            AggFunc::Sum => Some(sum),
+            AggFunc::Count => Some(count as f64),
+            AggFunc::Min => self.ordered.first_key_value().map(|(value, _)| value.0),
+            AggFunc::Max => self.ordered.last_key_value().map(|(value, _)| value.0),
+            AggFunc::Avg => Some(sum / count as f64),
+        }
+    }
+
+    pub fn filter(&self) -> Option<&QueryExpr> {
+        self.filter.as_ref()
+    }
+
+    // ids were added, updated or removed, members are those of them now matching the filter.
+    // the new aggregate when this crossed into the threshold
+    pub fn update(&mut self, items: &[StoredItem], ids: &Bitmap, members: &Bitmap) -> Option<f64> {
+        for id in ids.iter() {
+            self.remove(id);
+        }
+        for id in members.iter() {
+            let value = items.get(id as usize)
+                .and_then(|item| item.with_attr_id(self.attr, PyValue::as_f64))
+                .flatten();
+            if let Some(value) = value {
+                self.insert(id, value);
+            }
+        }
+
+        let value = self.value();
+        let holds = value.is_some_and(|value| self.op.matches(value, self.threshold));
+        let crossed = holds && !self.holds;
+        self.holds = holds;
+        value.filter(|_| crossed)
+    }
+
+    pub fn callback(&self, py: Python) -> Py<PyAny> {
+        self.callback.clone_ref(py)
+    }
+}
+
+#[derive(Default)]
+pub struct Alerts {
+    pub alerts: Vec<Alert>,
+    // changed since the alerts were last updated
+    pub dirty: Bitmap,
+}
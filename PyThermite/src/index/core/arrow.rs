@@ -0,0 +1,297 @@
+use std::ffi::{CStr, c_char, c_int, c_void};
+
+use pyo3::{
+    PyResult, Python,
+    exceptions::{PyTypeError, PyValueError},
+    prelude::*,
+    types::{PyCapsule, PyTuple},
+};
+use rayon::prelude::*;
+
+use crate::index::core::structures::string_interner::StrValue;
+use crate::index::value::RustCastValue;
+
+// the arrow c data interface, https://arrow.apache.org/docs/format/CDataInterface.html
+#[repr(C)]
+struct ArrowSchema {
+    format: *const c_char,
+    name: *const c_char,
+    metadata: *const c_char,
+    flags: i64,
+    n_children: i64,
+    children: *mut *mut ArrowSchema,
+    dictionary: *mut ArrowSchema,
+    release: Option<unsafe extern "C" fn(*mut ArrowSchema)>,
+    private_data: *mut c_void,
+}
+
+#[repr(C)]
+struct ArrowArray {
+    length: i64,
+    null_count: i64,
+    offset: i64,
+    n_buffers: i64,
+    n_children: i64,
+    buffers: *mut *const c_void,
+    children: *mut *mut ArrowArray,
+    dictionary: *mut ArrowArray,
+    release: Option<unsafe extern "C" fn(*mut ArrowArray)>,
+    private_data: *mut c_void,
+}
+
+#[repr(C)]
+struct ArrowArrayStream {
+    get_schema: Option<unsafe extern "C" fn(*mut ArrowArrayStream, *mut ArrowSchema) -> c_int>,
+    get_next: Option<unsafe extern "C" fn(*mut ArrowArrayStream, *mut ArrowArray) -> c_int>,
+    get_last_error: Option<unsafe extern "C" fn(*mut ArrowArrayStream) -> *const c_char>,
+    release: Option<unsafe extern "C" fn(*mut ArrowArrayStream)>,
+    private_data: *mut c_void,
+}
+
+trait Released: Sized {
+    // all null pointers and no release callback, the released state of the c interface
+    fn empty() -> Self {
+        unsafe { std::mem::zeroed() }
+    }
+
+    fn mark_released(&mut self);
+}
+
+macro_rules! released {
+    ($($ty:ident),*) => {$(
+        impl Released for $ty {
+            fn mark_released(&mut self) {
+                self.release = None;
+            }
+        }
+
+        impl Drop for $ty {
+            fn drop(&mut self) {
+                if let Some(release) = self.release {
+                    unsafe { release(self) };
+                }
+            }
+        }
+    )*};
+}
+
+released!(ArrowSchema, ArrowArray, ArrowArrayStream);
+
+// moves the struct out of a capsule, the producer's copy is marked released so its destructor skips it
+unsafe fn take<T: Released>(capsule: &Bound<PyCapsule>, name: &str) -> PyResult<T> {
+    if capsule.name()?.and_then(|n| n.to_str().ok()) != Some(name) {
+        return Err(PyTypeError::new_err(format!("expected an {} capsule", name)));
+    }
+    let ptr = capsule.pointer() as *mut T;
+    let taken = std::ptr::read(ptr);
+    (*ptr).mark_released();
+    Ok(taken)
+}
+
+// one column of a batch, pointers into buffers the batch keeps alive until it is dropped
+#[derive(Clone, Copy)]
+struct Column {
+    schema: *const ArrowSchema,
+    array: *const ArrowArray,
+    // the parent struct's offset, children of a sliced struct are not sliced themselves
+    offset: usize,
+    len: usize,
+}
+
+unsafe impl Send for Column {}
+unsafe impl Sync for Column {}
+
+// decoded columns in schema order, None where the value is null
+pub type Columns = Vec<Vec<Option<RustCastValue>>>;
+
+unsafe fn format(schema: *const ArrowSchema) -> String {
+    CStr::from_ptr((*schema).format).to_string_lossy().into_owned()
+}
+
+unsafe fn field_names(schema: &ArrowSchema) -> PyResult<Vec<String>> {
+    if format(schema) != "+s" {
+        return Err(PyTypeError::new_err("expected a record batch or table (an arrow struct of columns)"));
+    }
+    Ok((0..schema.n_children as usize)
+        .map(|i| {
+            let child = *schema.children.add(i);
+            if (*child).name.is_null() {
+                format!("f{}", i)
+            } else {
+                CStr::from_ptr((*child).name).to_string_lossy().into_owned()
+            }
+        })
+        .collect())
+}
+
+unsafe fn buffer<T>(array: *const ArrowArray, i: usize) -> *const T {
+    if (i as i64) < (*array).n_buffers { *(*array).buffers.add(i) as *const T } else { std::ptr::null() }
+}
+
+unsafe fn bit(bits: *const u8, i: usize) -> bool {
+    *bits.add(i / 8) & (1 << (i % 8)) != 0
+}
+
+// the value at each of rows, null ones as None
+unsafe fn decode(schema: *const ArrowSchema, array: *const ArrowArray, rows: impl Iterator<Item = usize>) -> Result<Vec<Option<RustCastValue>>, String> {
+    let base = (*array).offset as usize;
+    let validity = buffer::<u8>(array, 0);
+    let valid = |i: usize| (*array).null_count == 0 || validity.is_null() || bit(validity, base + i);
+    let fmt = format(schema);
+
+    if !(*schema).dictionary.is_null() {
+        let indices: Vec<Option<i64>> = decode_ints(&fmt, array, rows)?;
+        let dictionary = (*array).dictionary;
+        let values = decode((*schema).dictionary, dictionary, 0..(*dictionary).length as usize)?;
+        return Ok(indices.into_iter().map(|i| i.and_then(|i| values.get(i as usize).cloned().flatten())).collect());
+    }
+
+    let out = match fmt.as_str() {
+        "n" => rows.map(|_| None).collect(),
+        "b" => {
+            let data = buffer::<u8>(array, 1);
+            rows.map(|i| valid(i).then(|| RustCastValue::Bool(bit(data, base + i)))).collect()
+        },
+        "f" => {
+            let data = buffer::<f32>(array, 1);
+            rows.map(|i| valid(i).then(|| RustCastValue::Float(*data.add(base + i) as f64))).collect()
+        },
+        "g" => {
+            let data = buffer::<f64>(array, 1);
+            rows.map(|i| valid(i).then(|| RustCastValue::Float(*data.add(base + i)))).collect()
+        },
+        "u" | "U" => {
+            let data = buffer::<u8>(array, 2);
+            let bounds = |i: usize| if fmt == "u" {
+                let offsets = buffer::<i32>(array, 1);
+                (*offsets.add(base + i) as usize, *offsets.add(base + i + 1) as usize)
+            } else {
+                let offsets = buffer::<i64>(array, 1);
+                (*offsets.add(base + i) as usize, *offsets.add(base + i + 1) as usize)
+            };
+            rows.map(|i| valid(i).then(|| {
+                let (start, end) = bounds(i);
+                let bytes = std::slice::from_raw_parts(data.add(start), end - start);
+                RustCastValue::Str(StrValue::new(&String::from_utf8_lossy(bytes)))
+            })).collect()
+        },
+        _ => decode_ints(&fmt, array, rows)?.into_iter()
+            .map(|v| v.map(RustCastValue::Int))
+            .collect(),
+    };
+    Ok(out)
+}
+
+unsafe fn decode_ints(fmt: &str, array: *const ArrowArray, rows: impl Iterator<Item = usize>) -> Result<Vec<Option<i64>>, String> {
+    let base = (*array).offset as usize;
+    let validity = buffer::<u8>(array, 0);
+    let valid = |i: usize| (*array).null_count == 0 || validity.is_null() || bit(validity, base + i);
+
+    macro_rules! ints {
+        ($ty:ty) => {{
+            let data = buffer::<$ty>(array, 1);
+            rows.map(|i| valid(i).then(|| *data.add(base + i) as i64)).collect()
+        }};
+    }
+
+    Ok(match fmt {
+        "c" => ints!(i8),
+        "C" => ints!(u8),
+        "s" => ints!(i16),
+        "S" => ints!(u16),
+        "i" => ints!(i32),
+        "I" => ints!(u32),
+        "l" => ints!(i64),
+        "L" => {
+            let data = buffer::<u64>(array, 1);
+            rows.map(|i| if valid(i) { i64::try_from(*data.add(base + i)).map(Some).map_err(|_| "a uint64 above int64 range".to_string()) } else { Ok(None) })
+                .collect::<Result<_, _>>()?
+        },
+        other => return Err(format!("unsupported arrow format '{}'", other)),
+    })
+}
+
+// every column of one batch, decoded in parallel
+fn decode_batch(py: Python, schema: &ArrowSchema, batch: &ArrowArray, names: &[String]) -> PyResult<Columns> {
+    let columns: Vec<Column> = (0..schema.n_children as usize)
+        .map(|i| unsafe {
+            Column {
+                schema: *schema.children.add(i),
+                array: *batch.children.add(i),
+                offset: batch.offset as usize,
+                len: batch.length as usize,
+            }
+        })
+        .collect();
+    py.allow_threads(|| {
+        columns.par_iter()
+            .zip(names)
+            .map(|(col, name)| unsafe {
+                decode(col.schema, col.array, col.offset..col.offset + col.len)
+                    .map_err(|e| PyTypeError::new_err(format!("column '{}': {}", name, e)))
+            })
+            .collect()
+    })
+}
+
+fn stream_error(stream: &mut ArrowArrayStream, code: c_int) -> PyErr {
+    let message = stream.get_last_error
+        .map(|f| unsafe { f(stream) })
+        .filter(|msg| !msg.is_null())
+        .map(|msg| unsafe { CStr::from_ptr(msg).to_string_lossy().into_owned() })
+        .unwrap_or_else(|| format!("error code {}", code));
+    PyValueError::new_err(format!("reading arrow stream failed: {}", message))
+}
+
+// the column names and values of anything exporting the arrow pycapsule interface
+// (__arrow_c_stream__ like a pyarrow Table or pandas DataFrame, or __arrow_c_array__ like a RecordBatch),
+// batches are appended in order
+pub fn read_columns(py: Python, data: &Bound<PyAny>) -> PyResult<(Vec<String>, Columns)> {
+    if data.hasattr("__arrow_c_stream__")? {
+        let capsule = data.call_method1("__arrow_c_stream__", (py.None(),))?;
+        let mut stream: ArrowArrayStream = unsafe { take(capsule.downcast::<PyCapsule>()?, "arrow_array_stream")? };
+        let mut schema = ArrowSchema::empty();
+        let get_schema = stream.get_schema.ok_or_else(|| PyValueError::new_err("arrow stream was already released"))?;
+        let code = unsafe { get_schema(&mut stream, &mut schema) };
+        if code != 0 {
+            return Err(stream_error(&mut stream, code));
+        }
+        let names = unsafe { field_names(&schema)? };
+        let mut columns: Columns = vec![Vec::new(); names.len()];
+        loop {
+            let mut batch = ArrowArray::empty();
+            let get_next = stream.get_next.ok_or_else(|| PyValueError::new_err("arrow stream was already released"))?;
+            let code = unsafe { get_next(&mut stream, &mut batch) };
+            if code != 0 {
+                return Err(stream_error(&mut stream, code));
+            }
+            // a released array marks the end of the stream
+            if batch.release.is_none() {
+                break;
+            }
+            for (column, values) in columns.iter_mut().zip(decode_batch(py, &schema, &batch, &names)?) {
+                column.extend(values);
+            }
+        }
+        return Ok((names, columns));
+    }
+    if data.hasattr("__arrow_c_array__")? {
+        let capsules = data.call_method1("__arrow_c_array__", (py.None(),))?;
+        let capsules = capsules.downcast::<PyTuple>()?;
+        let schema_capsule = capsules.get_item(0)?;
+        let schema_capsule = schema_capsule.downcast::<PyCapsule>()?;
+        if schema_capsule.name()?.and_then(|n| n.to_str().ok()) != Some("arrow_schema") {
+            return Err(PyTypeError::new_err("expected an arrow_schema capsule"));
+        }
+        // the schema stays owned by its capsule, only the array is moved out
+        let schema = unsafe { &*(schema_capsule.pointer() as *const ArrowSchema) };
+        let batch: ArrowArray = unsafe { take(capsules.get_item(1)?.downcast::<PyCapsule>()?, "arrow_array")? };
+        let names = unsafe { field_names(schema)? };
+        let columns = decode_batch(py, schema, &batch, &names)?;
+        return Ok((names, columns));
+    }
+    Err(PyTypeError::new_err(format!(
+        "expected an object exporting arrow data (__arrow_c_stream__ or __arrow_c_array__), got {}",
+        data.get_type().name()?
+    )))
+}
@@ -0,0 +1,22 @@
+use crate::index::core::units::Unit;
+
+// how one attribute's own values are indexed and queried, as declared on the index or its IndexConfig
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AttrDecl {
+    // strings that parse as numbers are also indexed numerically
+    pub coerce_numeric: bool,
+    // strings that parse as semantic versions are indexed in version order
+    pub semver: bool,
+    // strict indexes accept values of any type here
+    pub opaque: bool,
+    // (start, end) pairs are indexed as intervals
+    pub interval: bool,
+    // ints are also indexed bit by bit for flag tests
+    pub flags: bool,
+    // eq on a number matches values within this distance of it
+    pub tolerance: Option<f64>,
+    // numbers are in this unit, quantities in queries are converted to it
+    pub unit: Option<Unit>,
+    // numbers are stored as whole counts of 10^-places
+    pub fixed_point: Option<u8>,
+}
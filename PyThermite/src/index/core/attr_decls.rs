@@ -0,0 +1,112 @@
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::index::{core::{attr_decl::AttrDecl, structures::string_interner::INTERNER}, types::StrId};
+
+// what is declared on an attribute path: how deep and which fields of it are indexed (limit_nested),
+// and how its own values are indexed (AttrDecl), one node per attribute down the path
+#[derive(Clone, Debug, Default)]
+pub struct AttrDecls {
+    // how many more levels of indexables may be indexed below this one
+    pub depth: Option<u32>,
+    // None means every attribute is indexed
+    pub fields: Option<FxHashSet<StrId>>,
+    pub children: FxHashMap<StrId, AttrDecls>,
+    // declarations on this attribute, kept when its nested limits are replaced
+    pub decl: AttrDecl,
+}
+
+impl AttrDecls {
+    pub fn new(depth: Option<u32>, only: Option<Vec<String>>) -> Self {
+        let mut limits = Self {
+            depth,
+            fields: only.as_ref().map(|_| FxHashSet::default()),
+            ..Default::default()
+        };
+        for path in only.iter().flatten() {
+            limits.project(path);
+        }
+        limits
+    }
+
+    fn project(&mut self, path: &str) {
+        let (head, rest) = match path.split_once('.') {
+            Some((head, rest)) => (head, Some(rest)),
+            None => (path, None),
+        };
+        let head_id = INTERNER.intern(head);
+        self.fields.get_or_insert_with(FxHashSet::default).insert(head_id);
+        if let Some(rest) = rest {
+            self.children.entry(head_id).or_default().project(rest);
+        }
+    }
+
+    // attach limits under a (possibly dotted) attribute path
+    pub fn set(&mut self, attr: &str, limits: AttrDecls) {
+        match attr.split_once('.') {
+            Some((head, rest)) => {
+                let head_id = INTERNER.intern(head);
+                self.children.entry(head_id).or_default().set(rest, limits);
+            }
+            None => {
+                let attr_id = INTERNER.intern(attr);
+                let prev = self.children.remove(&attr_id).unwrap_or_default();
+                self.children.insert(attr_id, AttrDecls {
+                    decl: prev.decl,
+                    ..limits
+                });
+            }
+        }
+    }
+
+    // changes the declarations on a (possibly dotted) attribute path
+    pub fn declare(&mut self, attr: &str, f: impl FnOnce(&mut AttrDecl)) {
+        f(&mut self.child_mut(attr).decl);
+    }
+
+    // limits of a (possibly dotted) attribute path, created if missing
+    fn child_mut(&mut self, attr: &str) -> &mut AttrDecls {
+        let (head, rest) = match attr.split_once('.') {
+            Some((head, rest)) => (head, Some(rest)),
+            None => (attr, None),
+        };
+        let child = self.children.entry(INTERNER.intern(head)).or_default();
+        match rest {
+            Some(rest) => child.child_mut(rest),
+            None => child,
+        }
+    }
+
+    pub fn child(&self, attr: &str) -> Option<&AttrDecls> {
+        attr.split('.').try_fold(self, |limits, part| limits.children.get(&INTERNER.intern(part)))
+    }
+
+    pub fn decl(&self, attr: &str) -> AttrDecl {
+        self.child(attr).map(|limits| limits.decl).unwrap_or_default()
+    }
+
+    // whether a declaration on this path or any below it passes f
+    pub fn any_decl(&self, f: &impl Fn(&AttrDecl) -> bool) -> bool {
+        f(&self.decl) || self.children.values().any(|child| child.any_decl(f))
+    }
+
+    #[inline]
+    pub fn is_indexed(&self, attr_id: StrId) -> bool {
+        self.fields.as_ref().is_none_or(|fields| fields.contains(&attr_id))
+    }
+
+    #[inline]
+    pub fn allows_nesting(&self) -> bool {
+        self.depth != Some(0)
+    }
+
+    // limits for the nested index built under attr_id
+    pub fn for_child(&self, attr_id: StrId) -> AttrDecls {
+        let inherited = self.depth.map(|d| d.saturating_sub(1));
+        let mut child = self.children.get(&attr_id).cloned().unwrap_or_default();
+        child.depth = match (child.depth, inherited) {
+            (Some(own), Some(inherited)) => Some(own.min(inherited)),
+            (own, inherited) => own.or(inherited),
+        };
+        child
+    }
+}
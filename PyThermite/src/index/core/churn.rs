@@ -0,0 +1,73 @@
+use std::sync::{RwLock, atomic::{AtomicU64, Ordering}};
+
+use pyo3::{Bound, PyResult, Python, types::{PyDict, PyDictMethods}};
+
+use crate::index::{core::structures::string_interner::INTERNER, types::StrId};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Churn {
+    pub updates: u64,
+    // the update number of the latest change
+    pub last_modified: u64,
+}
+
+// value changes of objects already held, counted per attribute.
+// every change gets the next update number so the latest ones can be told apart.
+// counters are atomics, a write only takes the lock to grow the table for an attribute id it has not seen
+#[derive(Debug, Default)]
+pub struct ChurnLog {
+    updates: AtomicU64,
+    attrs: RwLock<Vec<AttrChurn>>,
+}
+
+#[derive(Debug, Default)]
+struct AttrChurn {
+    updates: AtomicU64,
+    last_modified: AtomicU64,
+}
+
+impl AttrChurn {
+    fn record(&self, update: u64) {
+        self.updates.fetch_add(1, Ordering::Relaxed);
+        self.last_modified.fetch_max(update, Ordering::Relaxed);
+    }
+}
+
+impl ChurnLog {
+    pub fn record(&self, attr: StrId) {
+        let update = self.updates.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some(churn) = self.attrs.read().unwrap().get(attr as usize) {
+            churn.record(update);
+            return;
+        }
+        let mut attrs = self.attrs.write().unwrap();
+        if attrs.len() <= attr as usize {
+            attrs.resize_with(attr as usize + 1, AttrChurn::default);
+        }
+        attrs[attr as usize].record(update);
+    }
+
+    // most updated first, ties by the latest change
+    pub fn hottest(&self, limit: Option<usize>) -> Vec<(StrId, Churn)> {
+        let mut attrs: Vec<(StrId, Churn)> = self.attrs.read().unwrap().iter().enumerate()
+            .map(|(attr, churn)| (attr as StrId, Churn {
+                updates: churn.updates.load(Ordering::Relaxed),
+                last_modified: churn.last_modified.load(Ordering::Relaxed),
+            }))
+            .filter(|(_, churn)| churn.updates > 0)
+            .collect();
+        attrs.sort_unstable_by(|(_, a), (_, b)| b.updates.cmp(&a.updates).then(b.last_modified.cmp(&a.last_modified)));
+        attrs.truncate(limit.unwrap_or(usize::MAX));
+        attrs
+    }
+}
+
+impl Churn {
+    pub fn to_dict<'py>(self, py: Python<'py>, attr: StrId) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("name", INTERNER.resolve(attr).to_string())?;
+        dict.set_item("updates", self.updates)?;
+        dict.set_item("last_modified", self.last_modified)?;
+        Ok(dict)
+    }
+}
@@ -0,0 +1,162 @@
+use std::sync::{Arc, RwLock};
+
+use once_cell::sync::Lazy;
+use pyo3::{Bound, PyAny, PyResult, exceptions::{PyTypeError, PyValueError}, types::{PyAnyMethods, PyBool, PyBytes, PyDict, PyDictMethods, PyFloat, PyInt, PyString, PyTupleMethods, PyType, PyTypeMethods}};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::index::{core::structures::string_interner::{INTERNER, intern_for_class}, types::StrId, value::PyValue};
+
+// which attributes of an Indexable subclass the index maintains, declared on the class
+#[derive(Debug)]
+pub enum ClassFields {
+    Only(FxHashSet<StrId>),
+    Exclude(FxHashSet<StrId>),
+}
+
+impl ClassFields {
+    #[inline]
+    pub fn is_indexed(&self, attr_id: StrId) -> bool {
+        match self {
+            ClassFields::Only(fields) => fields.contains(&attr_id),
+            ClassFields::Exclude(fields) => !fields.contains(&attr_id),
+        }
+    }
+}
+
+// keyed by type pointer, every subclass definition sets its entry so a reused pointer never sees a stale one
+static CLASS_FIELDS: Lazy<RwLock<FxHashMap<usize, Arc<ClassFields>>>> = Lazy::new(|| RwLock::new(FxHashMap::default()));
+
+pub fn class_fields(type_ptr: usize) -> Option<Arc<ClassFields>> {
+    let registry = CLASS_FIELDS.read().unwrap();
+    if registry.is_empty() {
+        return None;
+    }
+    registry.get(&type_ptr).cloned()
+}
+
+// reads index_only / index_exclude from the class keywords (taken out of kwargs) or __index_fields__,
+// a class declaring neither keeps the fields of its nearest configured base
+pub fn register_class_fields(cls: &Bound<'_, PyType>, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<()> {
+    inherit_field_types(cls);
+    let type_ptr = cls.as_ptr() as usize;
+    let take = |key: &str| -> PyResult<Option<Vec<String>>> {
+        let Some(kwargs) = kwargs else {
+            return Ok(None);
+        };
+        let value = kwargs.get_item(key)?;
+        kwargs.del_item(key).ok();
+        value.map(|value| value.extract()).transpose()
+    };
+    let intern = |names: Vec<String>| names.iter().map(|name| intern_for_class(type_ptr, name)).collect();
+
+    let fields = match (take("index_only")?, take("index_exclude")?) {
+        (Some(_), Some(_)) => {
+            return Err(PyValueError::new_err("index_only and index_exclude cannot both be given"));
+        },
+        (Some(only), None) => Some(ClassFields::Only(intern(only))),
+        (None, Some(exclude)) => Some(ClassFields::Exclude(intern(exclude))),
+        (None, None) => match cls.getattr("__index_fields__") {
+            Ok(only) => Some(ClassFields::Only(intern(only.extract()?))),
+            Err(_) => None,
+        },
+    };
+
+    let mut registry = CLASS_FIELDS.write().unwrap();
+    let fields = match fields {
+        Some(fields) => Some(Arc::new(fields)),
+        None => cls.mro().iter().skip(1).find_map(|base| registry.get(&(base.as_ptr() as usize)).cloned()),
+    };
+    match fields {
+        Some(fields) => registry.insert(type_ptr, fields),
+        None => registry.remove(&type_ptr),
+    };
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldType {
+    Int,
+    Float,
+    Str,
+    Bool,
+    Bytes,
+}
+
+impl FieldType {
+    pub fn parse(ty: &Bound<'_, PyType>) -> PyResult<Self> {
+        let py = ty.py();
+        let kinds = [
+            (py.get_type::<PyInt>(), FieldType::Int),
+            (py.get_type::<PyFloat>(), FieldType::Float),
+            (py.get_type::<PyString>(), FieldType::Str),
+            (py.get_type::<PyBool>(), FieldType::Bool),
+            (py.get_type::<PyBytes>(), FieldType::Bytes),
+        ];
+        kinds.into_iter().find(|(kind, _)| ty.is(kind)).map(|(_, kind)| kind).ok_or_else(|| {
+            PyTypeError::new_err(format!("cannot declare a field of type {}, expected int, float, str, bool or bytes", ty.name().map(|n| n.to_string()).unwrap_or_default()))
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FieldType::Int => "int",
+            FieldType::Float => "float",
+            FieldType::Str => "str",
+            FieldType::Bool => "bool",
+            FieldType::Bytes => "bytes",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FieldDecl {
+    pub ty: FieldType,
+    pub nullable: bool,
+}
+
+// declared field types per class, subclasses start from a copy of their nearest declaring base
+static FIELD_TYPES: Lazy<RwLock<FxHashMap<usize, FxHashMap<StrId, FieldDecl>>>> = Lazy::new(|| RwLock::new(FxHashMap::default()));
+
+pub fn declare_field(cls: &Bound<'_, PyType>, name: &str, ty: &Bound<'_, PyType>, nullable: bool) -> PyResult<()> {
+    let decl = FieldDecl { ty: FieldType::parse(ty)?, nullable };
+    let type_ptr = cls.as_ptr() as usize;
+    FIELD_TYPES.write().unwrap().entry(type_ptr).or_default().insert(intern_for_class(type_ptr, name), decl);
+    Ok(())
+}
+
+fn inherit_field_types(cls: &Bound<'_, PyType>) {
+    let mut registry = FIELD_TYPES.write().unwrap();
+    let inherited = cls.mro().iter().skip(1).find_map(|base| registry.get(&(base.as_ptr() as usize)).cloned());
+    match inherited {
+        Some(types) => registry.insert(cls.as_ptr() as usize, types),
+        None => registry.remove(&(cls.as_ptr() as usize)),
+    };
+}
+
+pub fn field_decl(type_ptr: usize, attr_id: StrId) -> Option<FieldDecl> {
+    let registry = FIELD_TYPES.read().unwrap();
+    if registry.is_empty() {
+        return None;
+    }
+    registry.get(&type_ptr)?.get(&attr_id).copied()
+}
+
+// the value stored for attr_id on an object of the class at type_ptr, checked against its declared type
+pub fn class_value(type_ptr: usize, attr_id: StrId, obj: Bound<'_, PyAny>) -> PyResult<PyValue> {
+    let Some(decl) = field_decl(type_ptr, attr_id) else {
+        return PyValue::try_new(obj);
+    };
+    if decl.nullable && obj.is_none() {
+        return PyValue::try_new(obj);
+    }
+    match PyValue::of_type(&obj, decl.ty)? {
+        Some(value) => Ok(value),
+        None => Err(PyTypeError::new_err(format!(
+            "attribute '{}' is declared {}{}, got {}",
+            INTERNER.resolve(attr_id),
+            decl.ty.name(),
+            if decl.nullable { " or None" } else { "" },
+            obj.get_type().name()?,
+        ))),
+    }
+}
@@ -0,0 +1,107 @@
+use croaring::Bitmap;
+use pyo3::{Bound, PyAny, PyResult, Python, exceptions::PyValueError, types::{PyAnyMethods, PyBytes}};
+
+use crate::index::{core::{query::{QueryMap, b_tree::Key}, stored_item::StoredItem, structures::{composite_key::CompositeKey128, string_interner::INTERNER}}, types::StrId, value::RustCastValue};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnType {
+    F64,
+    F32,
+    I64,
+}
+
+impl ColumnType {
+    pub fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "f64" => Ok(Self::F64),
+            "f32" => Ok(Self::F32),
+            "i64" => Ok(Self::I64),
+            _ => Err(PyValueError::new_err(format!("unknown dtype '{}', expected f64, f32 or i64", name))),
+        }
+    }
+
+    // python array module typecode
+    fn typecode(self) -> &'static str {
+        match self {
+            Self::F64 => "d",
+            Self::F32 => "f",
+            Self::I64 => "q",
+        }
+    }
+
+    fn width(self) -> usize {
+        match self {
+            Self::F64 | Self::I64 => 8,
+            Self::F32 => 4,
+        }
+    }
+}
+
+// numeric value of attr for every id up to the largest held, None where missing or not a number
+pub fn numeric_column(items: &[StoredItem], allowed: &Bitmap, attr: StrId) -> Vec<Option<Key>> {
+    let len = allowed.maximum().map_or(0, |max| max as usize + 1);
+    let mut column = vec![None; len];
+    for id in allowed.iter() {
+        column[id as usize] = items[id as usize].with_attr_id(attr, |value| match value.get_primitive() {
+            RustCastValue::Int(i) => Some(Key::Int(*i)),
+            RustCastValue::Float(f) => Some(Key::FloatOrdered((*f).into())),
+            RustCastValue::Bool(b) => Some(Key::Int(*b as i64)),
+            _ => None,
+        }).flatten();
+    }
+    column
+}
+
+// packs the column into a python array.array, missing slots take fill (NaN for floats by default)
+pub fn column_to_array<'py>(
+    py: Python<'py>,
+    column: &[Option<Key>],
+    dtype: ColumnType,
+    fill: Option<f64>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let mut bytes = Vec::with_capacity(column.len() * dtype.width());
+    for value in column {
+        match dtype {
+            ColumnType::F64 | ColumnType::F32 => {
+                let v = match value {
+                    Some(Key::Int(i)) => *i as f64,
+                    Some(Key::FloatOrdered(f)) => f.0,
+                    None => fill.unwrap_or(f64::NAN),
+                };
+                if dtype == ColumnType::F64 {
+                    bytes.extend_from_slice(&v.to_ne_bytes());
+                } else {
+                    bytes.extend_from_slice(&(v as f32).to_ne_bytes());
+                }
+            }
+            ColumnType::I64 => {
+                let v = match (value, fill) {
+                    (Some(Key::Int(i)), _) => *i,
+                    (Some(Key::FloatOrdered(f)), _) => f.0 as i64,
+                    (None, Some(fill)) => fill as i64,
+                    (None, None) => return Err(PyValueError::new_err("i64 columns need a fill value for missing ids")),
+                };
+                bytes.extend_from_slice(&v.to_ne_bytes());
+            }
+        }
+    }
+    py.import("array")?
+        .getattr("array")?
+        .call1((dtype.typecode(), PyBytes::new(py, &bytes)))
+}
+
+// the ordered key encoding of attr for every id of scope holding a number or an encoded value, ascending by id.
+// covers coerced strings and versions too, keys only compare within one space
+pub fn sort_keys(index: &[QueryMap], scope: &Bitmap, attr: &str) -> Vec<(u32, [u8; 10])> {
+    let Some(qmap) = index.get(INTERNER.intern(attr) as usize).filter(|qmap| !qmap.is_placeholder()) else {
+        return vec![];
+    };
+    let mut keys = qmap.read_num_ordered().values_from_valid(scope);
+    for held in qmap.read_encoded().values() {
+        keys.extend(held.values_from_valid(scope));
+    }
+    keys.sort_by_key(|(id, _)| *id);
+    keys.into_iter()
+        .map(|(id, bits)| (id, CompositeKey128::float76_to_bytes(bits)))
+        .collect()
+}
@@ -0,0 +1,91 @@
+use pyo3::{PyResult, exceptions::PyValueError};
+
+use crate::index::core::structures::positional_bitmap::DEFAULT_MAX_STR_LEN;
+use crate::index::value::HashMode;
+
+// shards of each exact map, a power of two
+pub const DEFAULT_SHARDS: usize = 16;
+
+// the order collect returns objects in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResultOrder {
+    // ascending object id, what bitmap iteration gives
+    #[default]
+    Id,
+    // the order objects were first added to the index
+    Insertion,
+}
+
+impl ResultOrder {
+    pub fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "id" => Ok(Self::Id),
+            "insertion" => Ok(Self::Insertion),
+            _ => Err(PyValueError::new_err(format!("unknown order '{}', expected id or insertion", name))),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Id => "id",
+            Self::Insertion => "insertion",
+        }
+    }
+}
+
+// index wide settings, shared with every nested index
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexConfig {
+    // longest string, in characters, kept in the positional string index
+    pub max_str_len: usize,
+    // reject values that would only be held by identity, outside attributes marked opaque
+    pub strict: bool,
+    // drop objects the index holds the last reference to before each mutation
+    pub auto_remove_on_gc: bool,
+    // what collect orders results by
+    pub order: ResultOrder,
+    // number objects as they are added, for since / latest
+    pub sequence: bool,
+    // how Indexable values are keyed in the exact maps
+    pub hash_mode: HashMode,
+    // shards of each exact map, writers to different shards don't wait on each other
+    pub shards: usize,
+    // worker threads filling the maps column by column (add_arrow, load), rayon's global pool when None
+    pub threads: Option<usize>,
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        Self {
+            max_str_len: DEFAULT_MAX_STR_LEN,
+            strict: false,
+            auto_remove_on_gc: false,
+            order: ResultOrder::Id,
+            sequence: false,
+            hash_mode: HashMode::current(),
+            shards: DEFAULT_SHARDS,
+            threads: None,
+        }
+    }
+}
+
+impl IndexConfig {
+    pub fn check_shards(shards: usize) -> PyResult<usize> {
+        if !shards.is_power_of_two() {
+            return Err(PyValueError::new_err(format!("shards must be a power of two, got {}", shards)));
+        }
+        Ok(shards)
+    }
+
+    pub fn check_threads(threads: usize) -> PyResult<usize> {
+        if threads == 0 {
+            return Err(PyValueError::new_err("threads must be at least 1"));
+        }
+        Ok(threads)
+    }
+
+    // insertion order is read from the sequence, so it numbers objects too
+    pub fn stamps(&self) -> bool {
+        self.sequence || self.order == ResultOrder::Insertion
+    }
+}
@@ -0,0 +1,147 @@
+use croaring::Bitmap;
+use pyo3::{Bound, PyResult, Python, types::{PyDict, PyDictMethods}};
+
+use crate::index::{Indexable, core::{query::{QueryMap, query_ops::QueryExpr}, stored_item::StoredItem, structures::string_interner::INTERNER}, value::{PyIterable, PyValue, RustCastValue}};
+
+// clauses that produced a FilteredIndex and the objects they were evaluated over,
+// copied when the query ran so objects added to the index later are not counted
+#[derive(Debug)]
+pub struct AppliedQuery {
+    pub scope: Bitmap,
+    pub clauses: Vec<QueryExpr>,
+}
+
+#[derive(Debug, Default)]
+pub struct ClauseCoverage {
+    pub clause: String,
+    // matched objects holding a value the clause's structure answers
+    pub indexed: u64,
+    // matched objects without one, let in by a not / ne
+    pub fallback: u64,
+    // objects left out that hold a value the structure cannot answer
+    pub unindexable: u64,
+    // objects left out that do not have the attribute
+    pub missing: u64,
+}
+
+impl ClauseCoverage {
+    pub fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("clause", &self.clause)?;
+        dict.set_item("indexed", self.indexed)?;
+        dict.set_item("fallback", self.fallback)?;
+        dict.set_item("unindexable", self.unindexable)?;
+        dict.set_item("missing", self.missing)?;
+        Ok(dict)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Structure {
+    Numeric,
+    Str,
+    Any,
+}
+
+// ordered so the best answer across several nested objects wins
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Support {
+    Missing,
+    Unindexable,
+    Indexed,
+}
+
+fn structure_for(clause: &QueryExpr) -> Structure {
+    match clause {
+        // string bounds are answered by the ordered strings
+        QueryExpr::Gt(_, v) | QueryExpr::Ge(_, v) | QueryExpr::Lt(_, v) | QueryExpr::Le(_, v) | QueryExpr::Bt(_, v, _)
+            if matches!(v.get_primitive(), RustCastValue::Str(_)) => Structure::Str,
+        QueryExpr::Gt(..) | QueryExpr::Ge(..) | QueryExpr::Lt(..) | QueryExpr::Le(..) | QueryExpr::Bt(..) => Structure::Numeric,
+        QueryExpr::StartsWith(..) | QueryExpr::EndsWith(..) | QueryExpr::Contains(..) | QueryExpr::Matches(..) => Structure::Str,
+        _ => Structure::Any,
+    }
+}
+
+fn collect_leaves<'a>(expr: &'a QueryExpr, leaves: &mut Vec<&'a QueryExpr>) {
+    match expr {
+        QueryExpr::And(exprs) | QueryExpr::Or(exprs) => exprs.iter().for_each(|e| collect_leaves(e, leaves)),
+        QueryExpr::Not(inner) => collect_leaves(inner, leaves),
+        _ => leaves.push(expr),
+    }
+}
+
+fn answers(value: &PyValue, qmap: Option<&QueryMap>, structure: Structure) -> bool {
+    match (structure, value.get_primitive()) {
+        (_, RustCastValue::Iterable(PyIterable::Dict(_))) => false,
+        (Structure::Any, _) => true,
+        (Structure::Numeric, RustCastValue::Int(_) | RustCastValue::Float(_) | RustCastValue::Encoded(..) | RustCastValue::Wide(_)) => true,
+        (Structure::Numeric, RustCastValue::Str(s)) => qmap.is_some_and(|qmap| qmap.coerced(s).is_some()),
+        (Structure::Str, RustCastValue::Str(_)) => true,
+        // elements are indexed on their own
+        (_, RustCastValue::Iterable(_)) => value.items().iter().any(|item| answers(item, qmap, structure)),
+        _ => false,
+    }
+}
+
+fn support(index: &[QueryMap], obj: &Indexable, attr: &str, structure: Structure) -> Support {
+    let (head, rest) = match attr.split_once('.') {
+        Some((head, rest)) => (head, Some(rest)),
+        None => (attr, None),
+    };
+    let head_id = INTERNER.intern(head);
+    // cloned out so the lock is not held while walking into objects that may point back
+    let Some(value) = obj.with_attr_id(head_id, |value| value.clone()) else {
+        return Support::Missing;
+    };
+    let qmap = index.get(head_id as usize).filter(|qmap| !qmap.is_placeholder());
+    match rest {
+        None if answers(&value, qmap, structure) => Support::Indexed,
+        None => Support::Unindexable,
+        Some(rest) => {
+            let Some(qmap) = qmap else {
+                return Support::Unindexable;
+            };
+            let nested = qmap.nested.get_index_reader();
+            let children: Vec<&PyValue> = match value.get_primitive() {
+                RustCastValue::Ind(_) => vec![&value],
+                RustCastValue::Iterable(PyIterable::Dict(_)) => vec![],
+                RustCastValue::Iterable(_) => value.items().iter().collect(),
+                _ => vec![],
+            };
+            children.iter()
+                .filter_map(|child| match child.get_primitive() {
+                    RustCastValue::Ind(ind) => Some(support(&nested, &ind.owned_handle, rest, structure)),
+                    _ => None,
+                })
+                .max()
+                .unwrap_or(Support::Unindexable)
+        }
+    }
+}
+
+pub fn coverage(index: &[QueryMap], items: &[StoredItem], applied: &AppliedQuery, matched: &Bitmap) -> Vec<ClauseCoverage> {
+    let mut leaves = vec![];
+    applied.clauses.iter().for_each(|clause| collect_leaves(clause, &mut leaves));
+    let scope = &applied.scope;
+
+    leaves.iter().map(|leaf| {
+        let mut res = ClauseCoverage { clause: leaf.to_string(), ..Default::default() };
+        let (Some(attr), structure) = (leaf.attr(), structure_for(leaf)) else {
+            return res;
+        };
+        for id in scope.iter() {
+            let Some(item) = items.get(id as usize) else {
+                continue;
+            };
+            let support = support(index, item.get_owned_handle(), attr, structure);
+            match (matched.contains(id), support) {
+                (true, Support::Indexed) => res.indexed += 1,
+                (true, _) => res.fallback += 1,
+                (false, Support::Unindexable) => res.unindexable += 1,
+                (false, Support::Missing) => res.missing += 1,
+                (false, Support::Indexed) => {},
+            }
+        }
+        res
+    }).collect()
+}
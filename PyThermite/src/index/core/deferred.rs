@@ -0,0 +1,45 @@
+use rustc_hash::FxHashMap;
+
+use crate::index::{core::stored_item::StoredItem, types::StrId, value::PyValue};
+
+// per object, the value each attribute assigned in the block had in the query maps before the first assignment
+pub type Pending = FxHashMap<u32, (StoredItem, FxHashMap<StrId, Option<PyValue>>)>;
+
+// assignments held back while Index.bulk_update is open, applied as one update per object once it closes.
+// the stored item keeps the object alive, so its id can't be handed to another object meanwhile
+#[derive(Debug, Default)]
+pub struct Deferred {
+    // open blocks, the maps are repaired when the outermost one closes
+    depth: usize,
+    pending: Pending,
+}
+
+impl Deferred {
+    pub fn open(&mut self) {
+        self.depth += 1;
+    }
+
+    // the assignments to apply, once the last open block closes
+    pub fn close(&mut self) -> Option<Pending> {
+        self.depth = self.depth.saturating_sub(1);
+        (self.depth == 0).then(|| std::mem::take(&mut self.pending))
+    }
+
+    // closes every open block at once, for freeze, which can't wait for them. their own closes find nothing left
+    pub fn close_all(&mut self) -> Pending {
+        self.depth = 0;
+        std::mem::take(&mut self.pending)
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.depth > 0
+    }
+
+    // later assignments never reached the maps, so only the first old value is kept
+    pub fn record(&mut self, item_id: u32, item: &StoredItem, attr: StrId, old_pv: Option<&PyValue>) {
+        self.pending.entry(item_id)
+            .or_insert_with(|| (item.clone(), FxHashMap::default()))
+            .1.entry(attr)
+            .or_insert_with(|| old_pv.cloned());
+    }
+}
@@ -0,0 +1,225 @@
+use std::sync::{Arc, RwLock};
+
+use num_bigint::BigUint;
+use once_cell::sync::Lazy;
+use ordered_float::OrderedFloat;
+use pyo3::{exceptions::{PyTypeError, PyValueError}, prelude::*, types::{PyDate, PyDateAccess, PyDateTime, PyDelta, PyDeltaAccess, PyDict, PyFloat, PyInt, PyTimeAccess, PyTuple, PyType}};
+use rustc_hash::FxHashMap;
+
+use crate::index::{Indexable, core::{query::b_tree::Key, structures::wide_bitmap::WideNum}, types::decimal_type, value::StoredIndexable};
+
+// what an encoded key is ordered against, keys of different spaces never match or compare
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum KeySpace {
+    // Decimals and big ints with an exact key, held with the ints and floats
+    Number,
+    Datetime,
+    Date,
+    Timedelta,
+    // packaging versions and the strings of attributes declared with semver
+    Semver,
+    // values of a type with a registered encoder, one space per type
+    Registered(usize),
+}
+
+// maps values of a python type onto an ordered key, so they answer eq / ranges against
+// other values of the type. None leaves the value matched by identity
+pub trait KeyEncoder: Send + Sync {
+    fn encode(&self, value: &Bound<'_, PyAny>) -> PyResult<Option<Key>>;
+
+    // the space keys land in, None for one of the type's own
+    fn space(&self) -> Option<KeySpace> {
+        None
+    }
+}
+
+struct Registered {
+    // held so the type pointer used as the key stays valid
+    _ty: Py<PyType>,
+    encoder: Arc<dyn KeyEncoder>,
+}
+
+static ENCODERS: Lazy<RwLock<FxHashMap<usize, Registered>>> = Lazy::new(|| RwLock::new(FxHashMap::default()));
+
+// exact type match, subclasses need their own registration.
+// values already indexed keep the key they were encoded with
+pub fn register_key_encoder(ty: &Bound<'_, PyType>, encoder: Arc<dyn KeyEncoder>) {
+    ENCODERS.write().unwrap().insert(ty.as_ptr() as usize, Registered { _ty: ty.clone().unbind(), encoder });
+}
+
+pub fn is_registered(ty: &Bound<'_, PyType>) -> bool {
+    ENCODERS.read().unwrap().contains_key(&(ty.as_ptr() as usize))
+}
+
+pub fn unregister_key_encoder(ty: &Bound<'_, PyType>) -> bool {
+    ENCODERS.write().unwrap().remove(&(ty.as_ptr() as usize)).is_some()
+}
+
+// None when no encoder is registered for the type, an encoder raising is passed on
+pub fn encode_registered(obj: &Bound<'_, PyAny>) -> PyResult<Option<(KeySpace, Key)>> {
+    let ty = obj.get_type().as_ptr() as usize;
+    let encoder = {
+        let encoders = ENCODERS.read().unwrap();
+        if encoders.is_empty() {
+            return Ok(None);
+        }
+        match encoders.get(&ty) {
+            Some(registered) => registered.encoder.clone(),
+            None => return Ok(None),
+        }
+    };
+    // lock released first, python encoders may construct values themselves
+    let space = encoder.space().unwrap_or(KeySpace::Registered(ty));
+    Ok(encoder.encode(obj)?.map(|key| (space, key)))
+}
+
+const MICROS_PER_DAY: i64 = 86_400_000_000;
+
+// datetimes as microseconds since 1970-01-01 UTC, dates as days since then and timedeltas as a count
+// of microseconds, each in a space of its own kind. aware datetimes are moved to UTC by their
+// utcoffset, naive ones are taken as UTC already
+pub fn encode_temporal(obj: &Bound<'_, PyAny>) -> Option<(KeySpace, Key)> {
+    if let Ok(dt) = obj.downcast::<PyDateTime>() {
+        let offset = dt.call_method0("utcoffset").ok()?;
+        let offset = if offset.is_none() { 0 } else { delta_micros(offset.downcast::<PyDelta>().ok()?) };
+        let time = ((dt.get_hour() as i64 * 60 + dt.get_minute() as i64) * 60 + dt.get_second() as i64) * 1_000_000
+            + dt.get_microsecond() as i64;
+        return Some((KeySpace::Datetime, Key::Int(epoch_day(dt) * MICROS_PER_DAY + time - offset)));
+    }
+    if let Ok(date) = obj.downcast::<PyDate>() {
+        return Some((KeySpace::Date, Key::Int(epoch_day(date))));
+    }
+    obj.downcast::<PyDelta>().ok().map(|delta| (KeySpace::Timedelta, Key::Int(delta_micros(delta))))
+}
+
+fn delta_micros(delta: &Bound<'_, PyDelta>) -> i64 {
+    delta.get_days() as i64 * MICROS_PER_DAY + delta.get_seconds() as i64 * 1_000_000 + delta.get_microseconds() as i64
+}
+
+// days since 1970-01-01 in the proleptic gregorian calendar
+fn epoch_day(date: &impl PyDateAccess) -> i64 {
+    let (month, day) = (date.get_month() as i64, date.get_day() as i64);
+    let year = date.get_year() as i64 - (month <= 2) as i64;
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+// exact python ints and decimal.Decimal values. NaN, infinities and magnitudes past the
+// f64 range are left out, int subclasses such as IntEnum too
+pub fn encode_wide(obj: &Bound<'_, PyAny>) -> Option<WideNum> {
+    if obj.is_exact_instance_of::<PyInt>() {
+        let text = obj.str().ok()?;
+        let text = text.to_str().ok()?;
+        let (negative, digits) = text.strip_prefix('-').map_or((false, text), |digits| (true, digits));
+        return WideNum::new(negative, BigUint::parse_bytes(digits.as_bytes(), 10)?, 0);
+    }
+    if !obj.get_type().is(decimal_type(obj.py())?.bind(obj.py())) {
+        return None;
+    }
+    let (sign, digits, exp): (u8, Vec<u8>, Bound<'_, PyAny>) = obj.call_method0("as_tuple").ok()?.extract().ok()?;
+    // the exponent is 'n', 'N' or 'F' for NaN and infinities
+    let exp10 = exp.extract::<i32>().ok()?;
+    let digits: Vec<u8> = digits.iter().map(|d| b'0' + d).collect();
+    WideNum::new(sign == 1, BigUint::parse_bytes(&digits, 10)?, exp10)
+}
+
+struct Extractor {
+    _ty: Py<PyType>,
+    func: Py<PyAny>,
+}
+
+static EXTRACTORS: Lazy<RwLock<FxHashMap<usize, Extractor>>> = Lazy::new(|| RwLock::new(FxHashMap::default()));
+
+// exact type match like encoders, only consulted for values no encoder handles
+pub fn register_field_extractor(ty: &Bound<'_, PyType>, func: Py<PyAny>) {
+    EXTRACTORS.write().unwrap().insert(ty.as_ptr() as usize, Extractor { _ty: ty.clone().unbind(), func });
+}
+
+pub fn unregister_field_extractor(ty: &Bound<'_, PyType>) -> bool {
+    EXTRACTORS.write().unwrap().remove(&(ty.as_ptr() as usize)).is_some()
+}
+
+// the extracted fields as a bare Indexable, indexed like a nested object under the value's attribute.
+// None when no extractor is registered for the type, an extractor raising or not returning a dict is passed on
+pub fn extract_registered(obj: &Bound<'_, PyAny>) -> PyResult<Option<StoredIndexable>> {
+    let func = {
+        let extractors = EXTRACTORS.read().unwrap();
+        if extractors.is_empty() {
+            return Ok(None);
+        }
+        match extractors.get(&(obj.get_type().as_ptr() as usize)) {
+            Some(extractor) => extractor.func.clone_ref(obj.py()),
+            None => return Ok(None),
+        }
+    };
+    let py = obj.py();
+    let fields = func.bind(py).call1((obj,))?;
+    let fields = fields.downcast::<PyDict>().map_err(|_| PyTypeError::new_err(format!(
+        "extractor must return a dict, got {}", fields.get_type().name().map(|name| name.to_string()).unwrap_or_default()
+    )))?;
+    let holder = py.get_type::<Indexable>().call((), Some(fields))?;
+    let holder = holder.downcast::<Indexable>()?.borrow();
+    Ok(Some(StoredIndexable::from_py_ref(holder, py)))
+}
+
+// bits available to each part of a packed tuple key
+pub fn part_bits(parts: usize) -> u32 {
+    63 / parts as u32
+}
+
+// packs non negative parts big endian into one key, ordering matches the tuples
+pub fn pack_parts(parts: &[i64]) -> Option<Key> {
+    if parts.is_empty() || parts.len() > 4 {
+        return None;
+    }
+    let bits = part_bits(parts.len());
+    let mut packed: i64 = 0;
+    for part in parts {
+        if *part < 0 || (*part as u64) >> bits != 0 {
+            return None;
+        }
+        packed = (packed << bits) | part;
+    }
+    Some(Key::Int(packed))
+}
+
+// python callable returning an int, a float or a tuple of up to 4 non negative ints
+pub struct PyCallableEncoder {
+    func: Py<PyAny>,
+}
+
+impl PyCallableEncoder {
+    pub fn new(func: Py<PyAny>) -> Self {
+        Self { func }
+    }
+}
+
+impl KeyEncoder for PyCallableEncoder {
+    fn encode(&self, value: &Bound<'_, PyAny>) -> PyResult<Option<Key>> {
+        let encoded = self.func.bind(value.py()).call1((value,))?;
+        // bool is an int subclass but never a meaningful key
+        if encoded.is_instance_of::<PyInt>() && !encoded.is_instance_of::<pyo3::types::PyBool>() {
+            return Ok(Some(Key::Int(encoded.extract::<i64>()?)));
+        }
+        if encoded.is_instance_of::<PyFloat>() {
+            let f = encoded.extract::<f64>()?;
+            if f.is_nan() {
+                return Err(PyValueError::new_err("encoder returned NaN"));
+            }
+            return Ok(Some(Key::FloatOrdered(OrderedFloat(f))));
+        }
+        if let Ok(tuple) = encoded.downcast::<PyTuple>() {
+            let parts = tuple.extract::<Vec<i64>>()?;
+            return pack_parts(&parts).map(Some).ok_or_else(|| PyValueError::new_err(format!(
+                "encoder tuples take 1 to 4 parts, each in 0..2**{}", part_bits(parts.len().max(1))
+            )));
+        }
+        Err(PyTypeError::new_err(format!(
+            "encoder must return int, float or tuple of ints, got {}", encoded.get_type().name()?
+        )))
+    }
+}
+
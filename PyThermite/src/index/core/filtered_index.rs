@@ -1,26 +1,148 @@
 
+use std::sync::Arc;
+
 use croaring::Bitmap;
-use pyo3::{Py, PyResult, Python};
+use pyo3::{Py, PyObject, PyResult, Python, exceptions::PyValueError, types::{PyDict, PyDictMethods}};
 
-use crate::index::{Indexable, interfaces::filtered_index::FilteredIndex};
+use crate::index::{Indexable, core::{coverage::AppliedQuery, stored_item::StoredItem, fixed_point::scale_bounds, group_by::{AttrPath, attr_path}, query::query_ops::QueryExpr, semver::encode_version_bounds, structures::string_interner::INTERNER, tolerance::widen_eq, units::convert_units}, interfaces::filtered_index::FilteredIndex};
 
 impl FilteredIndex{
 
+    // objects removed from the index since this FilteredIndex was made are skipped
     pub fn get_from_indexes(&self, py: Python, indexes: &Bitmap) -> PyResult<Vec<Py<Indexable>>>{
         let items = self.items.read().unwrap();
-        let results: Vec<Py<Indexable>> = indexes.iter()
-            .map(|arc| items.get(arc as usize).unwrap().get_py_ref(py))
-            .collect();
+        let held = |id: u32| items.get(id as usize).filter(|item| !item.is_placeholder()).and_then(|item| item.get_py_ref(py));
+        let results: Vec<Py<Indexable>> = match &self.order {
+            Some(sequence) => sequence.read().unwrap().in_order(indexes).into_iter().filter_map(held).collect(),
+            None => indexes.iter().filter_map(held).collect(),
+        };
         Ok(results)
     }
 
+    // handles of the objects still held, in collect order, read under one lock so they are
+    // a consistent snapshot whatever other threads do afterwards
+    pub fn held_items(&self) -> Vec<StoredItem> {
+        let ids = self.ordered_ids();
+        let items = self.items.read().unwrap();
+        ids.into_iter()
+            .filter_map(|id| items.get(id as usize))
+            .filter(|item| !item.is_placeholder())
+            .cloned()
+            .collect()
+    }
+
+    // one dict per object in collect order holding just names, None where an object has no such attribute
+    pub fn collect_attrs(&self, py: Python, names: &[String]) -> PyResult<Vec<PyObject>> {
+        let paths: Vec<AttrPath> = names.iter().map(|name| attr_path(name)).collect();
+        self.held_items().into_iter().map(|item| {
+            let row = PyDict::new(py);
+            for (name, path) in names.iter().zip(paths.iter()) {
+                row.set_item(name, item.with_path(path, |value| value.get_obj(py)))?;
+            }
+            Ok(row.into_any().unbind())
+        }).collect()
+    }
+
+    // the ids held in the order collect returns them
+    pub fn ordered_ids(&self) -> Vec<u32> {
+        match &self.order {
+            Some(sequence) => sequence.read().unwrap().in_order(&self.allowed_items),
+            None => self.allowed_items.to_vec(),
+        }
+    }
+
+    // the ids from position start up to end in result order
+    pub fn slice(&self, start: usize, end: usize) -> FilteredIndex {
+        let ids = match &self.order {
+            Some(_) => self.ordered_ids().into_iter().skip(start).take(end.saturating_sub(start)).collect(),
+            None => {
+                // cut by rank without walking the ids
+                let rank = |pos: usize| u32::try_from(pos).ok().and_then(|pos| self.allowed_items.select(pos));
+                let mut ids = self.allowed_items.clone();
+                if let Some(last) = rank(end) {
+                    ids.remove_range(last..);
+                }
+                match rank(start) {
+                    Some(first) => ids.remove_range(..first),
+                    None => ids.clear(),
+                }
+                ids
+            }
+        };
+        self.filter_from_bitmap(ids)
+    }
+
+    // the declarations live on the index, reached through the attribute's map
+    pub fn apply_declarations(&self, expr: &mut QueryExpr) -> PyResult<()> {
+        let index = self.index.read().unwrap();
+        let declaring = |attr: &str| {
+            let head = attr.split_once('.').map_or(attr, |(head, _)| head);
+            index.get(INTERNER.intern(head) as usize)
+                .filter(|qmap| !qmap.is_placeholder())
+                .and_then(|qmap| qmap.parent.upgrade())
+        };
+        convert_units(expr, &|attr| declaring(attr).and_then(|parent| parent.declared_unit(attr)))?;
+        encode_version_bounds(expr, &|attr| {
+            let (head, rest) = match attr.split_once('.') {
+                Some((head, rest)) => (head, Some(rest)),
+                None => (attr, None),
+            };
+            match index.get(INTERNER.intern(head) as usize).filter(|qmap| !qmap.is_placeholder()) {
+                Some(qmap) => rest.map_or(qmap.decl.semver, |rest| qmap.nested.declares_semver(rest)),
+                None => false,
+            }
+        });
+        widen_eq(expr, &|attr| declaring(attr).and_then(|parent| parent.declared_tolerance(attr)));
+        scale_bounds(expr, &|attr| declaring(attr).and_then(|parent| parent.declared_fixed_point(attr)));
+        Ok(())
+    }
+
+    pub fn declared_fixed_point(&self, attr: &str) -> Option<u8> {
+        let head = attr.split_once('.').map_or(attr, |(head, _)| head);
+        let index = self.index.read().unwrap();
+        index.get(INTERNER.intern(head) as usize)
+            .filter(|qmap| !qmap.is_placeholder())
+            .and_then(|qmap| qmap.parent.upgrade())
+            .and_then(|parent| parent.declared_fixed_point(attr))
+    }
+
     pub fn filter_from_bitmap(&self, mut bm: Bitmap) -> FilteredIndex {
         bm.and_inplace(&self.allowed_items);
         FilteredIndex {
             index: self.index.clone(),
             items: self.items.clone(),
-            allowed_items: bm
+            allowed_items: bm,
+            applied: self.applied.clone(),
+            order: self.order.clone(),
         }
     }
 
+    // the ids of both combined by op, for FilteredIndexes taken from the same index.
+    // no clauses are kept for coverage, those of either side don't describe the result
+    pub fn combine(&self, other: &FilteredIndex, op: impl FnOnce(&Bitmap, &Bitmap) -> Bitmap) -> PyResult<FilteredIndex> {
+        if !Arc::ptr_eq(&self.index, &other.index) {
+            return Err(PyValueError::new_err("FilteredIndexes must be taken from the same index"));
+        }
+        Ok(FilteredIndex {
+            index: self.index.clone(),
+            items: self.items.clone(),
+            allowed_items: op(&self.allowed_items, &other.allowed_items),
+            applied: None,
+            order: self.order.clone(),
+        })
+    }
+
+    // appends clauses for coverage, scope is only used when no earlier query set one
+    pub fn applying<F: FnOnce() -> Bitmap>(mut self, clauses: Vec<QueryExpr>, scope: F) -> FilteredIndex {
+        let applied = match self.applied.take() {
+            Some(prev) => AppliedQuery {
+                scope: prev.scope.clone(),
+                clauses: prev.clauses.iter().cloned().chain(clauses).collect(),
+            },
+            None => AppliedQuery { scope: scope(), clauses },
+        };
+        self.applied = Some(Arc::new(applied));
+        self
+    }
+
 }
\ No newline at end of file
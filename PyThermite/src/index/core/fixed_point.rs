@@ -0,0 +1,79 @@
+use crate::index::{core::query::query_ops::QueryExpr, value::{PyValue, RustCastValue}};
+
+pub const PLACES: [u8; 2] = [2, 4];
+
+#[inline]
+pub fn scale(places: u8) -> i64 {
+    10i64.pow(places as u32)
+}
+
+// the whole number of 10^-places units a number is stored as, floats rounded to the nearest unit
+#[inline]
+pub fn to_fixed(value: &RustCastValue, places: u8) -> Option<i64> {
+    match value {
+        RustCastValue::Int(i) => Some(i.saturating_mul(scale(places))),
+        RustCastValue::Float(f) if f.is_finite() => Some((f * scale(places) as f64).round() as i64),
+        _ => None,
+    }
+}
+
+#[inline]
+pub fn from_fixed(units: i128, places: u8) -> f64 {
+    units as f64 / scale(places) as f64
+}
+
+// a query number as a bound on stored units, rounded with snap unless it is within float noise of a whole unit
+// (0.29 * 100 is 28.999999999999996), so gt(4.999) still matches 5.00 where rounding would give gt(5.00)
+fn rescale(value: &mut PyValue, places: u8, snap: fn(f64) -> f64) {
+    let units = match value.get_primitive() {
+        RustCastValue::Float(f) if f.is_finite() => {
+            let x = f * scale(places) as f64;
+            let nearest = x.round();
+            if (x - nearest).abs() < 1e-6 { nearest as i64 } else { snap(x) as i64 }
+        },
+        other => match to_fixed(other, places) {
+            Some(units) => units,
+            None => return,
+        },
+    };
+    *value = PyValue::from_primitave(RustCastValue::Int(units));
+}
+
+// rewrites numbers in comparisons on fixed point attributes into bounds on the scaled integers stored for them
+pub fn scale_bounds<F: Fn(&str) -> Option<u8>>(expr: &mut QueryExpr, places_of: &F) {
+    match expr {
+        QueryExpr::Eq(attr, v) | QueryExpr::Ne(attr, v) => {
+            if let Some(places) = places_of(attr) {
+                rescale(v, places, f64::round);
+            }
+        },
+        QueryExpr::Gt(attr, v) | QueryExpr::Le(attr, v) => {
+            if let Some(places) = places_of(attr) {
+                rescale(v, places, f64::floor);
+            }
+        },
+        QueryExpr::Ge(attr, v) | QueryExpr::Lt(attr, v) => {
+            if let Some(places) = places_of(attr) {
+                rescale(v, places, f64::ceil);
+            }
+        },
+        QueryExpr::Bt(attr, lower, upper) => {
+            if let Some(places) = places_of(attr) {
+                rescale(lower, places, f64::ceil);
+                rescale(upper, places, f64::floor);
+            }
+        },
+        QueryExpr::In(attr, values) | QueryExpr::Nin(attr, values) => {
+            if let Some(places) = places_of(attr) {
+                values.iter_mut().for_each(|v| rescale(v, places, f64::round));
+            }
+        },
+        QueryExpr::Not(inner) => scale_bounds(inner, places_of),
+        QueryExpr::And(exprs) | QueryExpr::Or(exprs) => {
+            for e in exprs.iter_mut() {
+                scale_bounds(e, places_of);
+            }
+        },
+        _ => {},
+    }
+}
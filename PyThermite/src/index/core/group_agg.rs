@@ -0,0 +1,217 @@
+use croaring::Bitmap;
+use pyo3::{IntoPyObjectExt, exceptions::{PyTypeError, PyValueError}, prelude::*, types::{PyDict, PyString, PyTuple}};
+
+use crate::index::{core::{group_by::{AttrPath, attr_path, group_by, merge_groups}, query::{QueryMap, aggregate::{Accumulator, AggFunc, CmpOp}, query_ops::QueryExpr}, stored_item::StoredItem, structures::{freeze_lock::FreezeLock, string_interner::INTERNER}}, types::StrId, value::PyValue};
+
+// reported for every group, metrics are named {attr}_{func}
+const COUNT: &str = "count";
+
+#[derive(Debug)]
+pub struct Metric {
+    pub column: String,
+    attr: StrId,
+    func: AggFunc,
+    places: Option<u8>,
+}
+
+// {"price": "mean"} or {"price": ["min", "max"]}
+pub fn parse_metrics<F: Fn(&str) -> Option<u8>>(metrics: Option<&Bound<'_, PyDict>>, places_of: &F) -> PyResult<Vec<Metric>> {
+    let mut parsed = vec![];
+    for (attr, funcs) in metrics.iter().flat_map(|metrics| metrics.iter()) {
+        let attr = attr.extract::<String>()?;
+        let funcs = match funcs.downcast::<PyString>() {
+            Ok(func) => vec![func.to_str()?.to_string()],
+            Err(_) => funcs.extract::<Vec<String>>()?,
+        };
+        for name in funcs {
+            parsed.push(Metric::new(&attr, &name)?.declared(places_of));
+        }
+    }
+    Ok(parsed)
+}
+
+impl Metric {
+    pub fn new(attr: &str, func: &str) -> PyResult<Self> {
+        let parsed = AggFunc::parse(func).ok_or_else(|| {
+            PyValueError::new_err(format!("unknown aggregate '{}', expected one of sum, count, min, max, avg", func))
+        })?;
+        Ok(Self { column: format!("{}_{}", attr, func), attr: INTERNER.intern(attr), func: parsed, places: None })
+    }
+
+    // sums over an attribute declared fixed point are taken exactly
+    pub fn declared<F: Fn(&str) -> Option<u8>>(mut self, places_of: &F) -> Self {
+        self.places = places_of(&INTERNER.resolve(self.attr));
+        self
+    }
+
+    // counts are reported as ints
+    fn to_py<'py>(&self, py: Python<'py>, value: Option<f64>) -> PyResult<Bound<'py, PyAny>> {
+        match (self.func, value) {
+            (AggFunc::Count, Some(count)) => (count as u64).into_bound_py_any(py),
+            _ => value.into_bound_py_any(py),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct GroupRow<'py> {
+    pub key: Bound<'py, PyTuple>,
+    pub count: u64,
+    pub values: Vec<Option<f64>>,
+}
+
+fn aggregate(items: &[StoredItem], ids: &Bitmap, metric: &Metric) -> Option<f64> {
+    let mut acc = Accumulator::with_places(metric.places);
+    for id in ids.iter() {
+        let value = items.get(id as usize)
+            .and_then(|item| item.with_attr_id(metric.attr, PyValue::as_f64))
+            .flatten();
+        if let Some(value) = value {
+            acc.push(value);
+        }
+    }
+    acc.finish(metric.func)
+}
+
+fn having_literal(value: &PyValue) -> PyResult<f64> {
+    value.as_f64().ok_or_else(|| PyTypeError::new_err("having compares count and metric columns to numbers"))
+}
+
+fn having_column(columns: &[(&str, Option<f64>)], name: &str) -> PyResult<Option<f64>> {
+    columns.iter()
+        .find(|(column, _)| *column == name)
+        .map(|(_, value)| *value)
+        .ok_or_else(|| PyValueError::new_err(format!(
+            "having refers to unknown column '{}', expected one of {}",
+            name,
+            columns.iter().map(|(column, _)| *column).collect::<Vec<_>>().join(", ")
+        )))
+}
+
+// columns without a value (min of a group with no numbers) fail every comparison
+fn having(expr: &QueryExpr, columns: &[(&str, Option<f64>)]) -> PyResult<bool> {
+    let compare = |name: &str, op: CmpOp, value: &PyValue| -> PyResult<bool> {
+        let rhs = having_literal(value)?;
+        Ok(having_column(columns, name)?.is_some_and(|lhs| op.matches(lhs, rhs)))
+    };
+    match expr {
+        QueryExpr::Eq(name, value) => compare(name, CmpOp::Eq, value),
+        QueryExpr::Ne(name, value) => compare(name, CmpOp::Ne, value),
+        QueryExpr::Gt(name, value) => compare(name, CmpOp::Gt, value),
+        QueryExpr::Ge(name, value) => compare(name, CmpOp::Ge, value),
+        QueryExpr::Lt(name, value) => compare(name, CmpOp::Lt, value),
+        QueryExpr::Le(name, value) => compare(name, CmpOp::Le, value),
+        QueryExpr::Bt(name, lower, upper) => Ok(compare(name, CmpOp::Ge, lower)? & compare(name, CmpOp::Le, upper)?),
+        QueryExpr::In(name, values) => values.iter()
+            .map(|value| compare(name, CmpOp::Eq, value))
+            .try_fold(false, |acc, matched| Ok(acc | matched?)),
+        QueryExpr::Nin(name, values) => Ok(!having(&QueryExpr::In(name.clone(), values.clone()), columns)?),
+        QueryExpr::Not(inner) => Ok(!having(inner, columns)?),
+        QueryExpr::And(exprs) => exprs.iter().try_fold(true, |acc, e| Ok(acc & having(e, columns)?)),
+        QueryExpr::Or(exprs) => exprs.iter().try_fold(false, |acc, e| Ok(acc | having(e, columns)?)),
+        _ => Err(PyValueError::new_err("having supports comparisons, bt, in, nin, and, or and not")),
+    }
+}
+
+fn columns<'a>(metrics: &'a [Metric], count: u64, values: &[Option<f64>]) -> Vec<(&'a str, Option<f64>)> {
+    std::iter::once((COUNT, Some(count as f64)))
+        .chain(metrics.iter().map(|metric| metric.column.as_str()).zip(values.iter().copied()))
+        .collect()
+}
+
+// one row per merged group, groups failing having are dropped
+fn group_agg<'py>(
+    items: &[StoredItem],
+    groups: Vec<(Bound<'py, PyTuple>, Bitmap)>,
+    metrics: &[Metric],
+    having_expr: Option<&QueryExpr>,
+) -> PyResult<Vec<GroupRow<'py>>> {
+    let mut rows = vec![];
+    for (key, ids) in groups {
+        let count = ids.cardinality();
+        let values: Vec<Option<f64>> = metrics.iter().map(|metric| aggregate(items, &ids, metric)).collect();
+        if let Some(expr) = having_expr {
+            if !having(expr, &columns(metrics, count, &values))? {
+                continue;
+            }
+        }
+        rows.push(GroupRow { key, count, values });
+    }
+    Ok(rows)
+}
+
+pub fn group_rows<'py>(
+    py: Python<'py>,
+    index: &FreezeLock<Vec<QueryMap>>,
+    items: &FreezeLock<Vec<StoredItem>>,
+    scope: &Bitmap,
+    names: &[String],
+    metrics: &[Metric],
+    having_expr: Option<&QueryExpr>,
+) -> PyResult<Vec<Bound<'py, PyDict>>> {
+    // every clause checked up front, not only once a group reaches it
+    if let Some(expr) = having_expr {
+        having(expr, &columns(metrics, 0, &vec![None; metrics.len()]))?;
+    }
+    let attrs: Vec<AttrPath> = names.iter().map(|name| attr_path(name)).collect();
+    let groups = group_by(&index.read().unwrap(), &items.read().unwrap(), scope, &attrs);
+    // merged without the lock, python equality may run user code
+    let groups = merge_groups(py, groups)?;
+    let rows = group_agg(&items.read().unwrap(), groups, metrics, having_expr)?;
+    rows.iter().map(|row| row.to_dict(names, metrics)).collect()
+}
+
+impl<'py> GroupRow<'py> {
+    pub fn to_dict(&self, names: &[String], metrics: &[Metric]) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(self.key.py());
+        for (name, value) in names.iter().zip(self.key.iter()) {
+            dict.set_item(name, value)?;
+        }
+        dict.set_item(COUNT, self.count)?;
+        for (metric, value) in metrics.iter().zip(self.values.iter()) {
+            dict.set_item(&metric.column, metric.to_py(dict.py(), *value)?)?;
+        }
+        Ok(dict)
+    }
+}
+
+// {row value: {col value: metric}}, combinations without objects are left out.
+// the group count without a metric
+pub fn pivot<'py>(
+    py: Python<'py>,
+    index: &FreezeLock<Vec<QueryMap>>,
+    items: &FreezeLock<Vec<StoredItem>>,
+    scope: &Bitmap,
+    rows: &str,
+    cols: &str,
+    metric: Option<&Metric>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let groups = group_by(&index.read().unwrap(), &items.read().unwrap(), scope, &[attr_path(rows), attr_path(cols)]);
+    let groups = merge_groups(py, groups)?;
+    // cells are worked out under the lock, the dicts built after it since keys may hash in user code
+    let values: Vec<Option<f64>> = match metric {
+        Some(metric) => {
+            let items = items.read().unwrap();
+            groups.iter().map(|(_, ids)| aggregate(&items, ids, metric)).collect()
+        },
+        None => vec![],
+    };
+    let table = PyDict::new(py);
+    for (i, (key, ids)) in groups.into_iter().enumerate() {
+        let cell = match metric {
+            Some(metric) => metric.to_py(py, values[i])?,
+            None => ids.cardinality().into_bound_py_any(py)?,
+        };
+        let row_key = key.get_item(0)?;
+        let row = match table.get_item(&row_key)? {
+            Some(row) => row.downcast_into::<PyDict>()?,
+            None => {
+                let row = PyDict::new(py);
+                table.set_item(&row_key, &row)?;
+                row
+            }
+        };
+        row.set_item(key.get_item(1)?, cell)?;
+    }
+    Ok(table)
+}
@@ -0,0 +1,248 @@
+use std::cmp::Ordering;
+
+use croaring::Bitmap;
+use pyo3::{exceptions::PyValueError, prelude::*, types::{PyDict, PyString, PyTuple}};
+use smallvec::SmallVec;
+
+use crate::index::{core::{query::{QueryMap, b_tree::key::cmp_int_float}, stored_item::StoredItem, structures::{freeze_lock::FreezeLock, hybrid_set::HybridSetOps, ordered_bitmap::NumericalBitmap, string_interner::INTERNER}}, types::StrId, value::{PyIterable, PyValue, RustCastValue}};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GroupKeys {
+    // one attribute, keyed by its value
+    Value,
+    // keyed by a tuple of values
+    Tuple,
+    // one dict level per attribute
+    Nested,
+}
+
+// a dotted attribute split into its parts, "owner.region" steps into owner
+pub type AttrPath = SmallVec<[StrId; 2]>;
+
+pub fn attr_path(attr: &str) -> AttrPath {
+    attr.split('.').map(|part| INTERNER.intern(part)).collect()
+}
+
+#[derive(Debug)]
+pub struct Group {
+    pub key: Vec<PyValue>,
+    pub ids: Bitmap,
+}
+
+// numbers first, bools counting as ints, then strings, then everything else
+fn value_rank(value: &RustCastValue) -> u8 {
+    match value {
+        RustCastValue::Int(_) | RustCastValue::Float(_) | RustCastValue::Bool(_) => 0,
+        RustCastValue::Str(_) => 1,
+        _ => 2,
+    }
+}
+
+// group key order, values without a natural order compare equal and keep their lowest id order
+fn cmp_values(a: &PyValue, b: &PyValue) -> Ordering {
+    let (a, b) = (a.get_primitive(), b.get_primitive());
+    value_rank(a).cmp(&value_rank(b)).then_with(|| match (a, b) {
+        (RustCastValue::Str(a), RustCastValue::Str(b)) => a.cmp(b),
+        (RustCastValue::Float(a), RustCastValue::Float(b)) => a.total_cmp(b),
+        (RustCastValue::Float(a), b) => as_int(b).map_or(Ordering::Equal, |b| cmp_int_float(b, *a).reverse()),
+        (a, RustCastValue::Float(b)) => as_int(a).map_or(Ordering::Equal, |a| cmp_int_float(a, *b)),
+        (a, b) => as_int(a).cmp(&as_int(b)),
+    })
+}
+
+fn as_int(value: &RustCastValue) -> Option<i64> {
+    match value {
+        RustCastValue::Int(i) => Some(*i),
+        RustCastValue::Bool(b) => Some(*b as i64),
+        _ => None,
+    }
+}
+
+// one group per key held within population, a value per step like order_by
+fn key_groups(keys: &NumericalBitmap, population: &Bitmap, push: &mut impl FnMut(Bitmap)) {
+    let count = population.cardinality();
+    let mut walked = 0;
+    while let Some(bits) = (walked < count).then(|| keys.kth_from_valid(walked, population)).flatten() {
+        let ids = keys.get_exact(bits).and(population);
+        walked += ids.cardinality().max(1);
+        push(ids);
+    }
+}
+
+// ids of scope per distinct value of attr, read from the attribute's index structures.
+// list, set and dict values are left out, their elements are held under masked ids scope never has
+fn id_groups(index: &[QueryMap], scope: &Bitmap, attr: &[StrId]) -> Vec<Bitmap> {
+    let Some((head, rest)) = attr.split_first() else {
+        return vec![];
+    };
+    let Some(qmap) = index.get(*head as usize).filter(|qmap| !qmap.is_placeholder()) else {
+        return vec![];
+    };
+    if !rest.is_empty() {
+        let nested = &qmap.nested;
+        let children = id_groups(&nested.get_index_reader(), &nested.get_allowed_items_reader(), rest);
+        return children.iter()
+            .map(|ids| qmap.get_allowed_parents(ids).and(scope))
+            .filter(|ids| !ids.is_empty())
+            .collect();
+    }
+
+    let mut groups = vec![];
+    let mut push = |ids: Bitmap| if !ids.is_empty() {
+        groups.push(ids);
+    };
+    let mut strings = Bitmap::new();
+    for ids in qmap.read_str_radix_map().ordered() {
+        let ids = ids.and(scope);
+        strings.or_inplace(&ids);
+        push(ids);
+    }
+    // strings coerced to numbers or versions were taken above
+    let num = qmap.read_num_ordered();
+    key_groups(&num, &num.ids().and(scope).andnot(&strings), &mut push);
+    for keys in qmap.read_encoded().values() {
+        key_groups(keys, &keys.ids().and(scope).andnot(&strings), &mut push);
+    }
+    for (_, ids) in qmap.read_wide().ordered() {
+        push(ids.and(scope));
+    }
+    let bools = qmap.get_bool_map_reader();
+    push(bools.get_exact(false).and(scope));
+    push(bools.get_exact(true).and(scope));
+    push(qmap.get_nulls_reader().and(scope));
+    qmap.exact.for_each(|key, ids| {
+        if !matches!(key.value.get_primitive(), RustCastValue::Iterable(PyIterable::List(_) | PyIterable::Set(_) | PyIterable::Dict(_))) {
+            push(ids.as_bitmap().and(scope));
+        }
+    });
+    groups
+}
+
+// ids of scope holding each value of attr, sorted by value so repeated calls group in the same order.
+// only the lowest id of each group is read for its value, objects without the attribute are left out
+fn value_bitmaps(index: &[QueryMap], items: &[StoredItem], scope: &Bitmap, attr: &[StrId]) -> Vec<(PyValue, Bitmap)> {
+    let mut values: Vec<(u32, PyValue, Bitmap)> = id_groups(index, scope, attr).into_iter()
+        .filter_map(|ids| {
+            let first = ids.minimum()?;
+            let value = items.get(first as usize)?.with_path(attr, PyValue::clone)?;
+            Some((first, value, ids))
+        })
+        .collect();
+    // by lowest id first, the stable sort then keeps unordered values in that order
+    values.sort_by_key(|(first, _, _)| *first);
+    values.sort_by(|(_, a, _), (_, b, _)| cmp_values(a, b));
+    values.into_iter().map(|(_, value, ids)| (value, ids)).collect()
+}
+
+// each level splits the groups above it by intersecting with the attribute's value bitmaps,
+// so the groups come out sorted by key
+pub fn group_by(index: &[QueryMap], items: &[StoredItem], scope: &Bitmap, attrs: &[AttrPath]) -> Vec<Group> {
+    let mut groups = vec![Group { key: vec![], ids: scope.clone() }];
+    for attr in attrs {
+        let values = value_bitmaps(index, items, scope, attr);
+        groups = groups.iter()
+            .flat_map(|group| values.iter()
+                .filter(|(_, ids)| group.ids.intersect(ids))
+                .map(|(value, ids)| Group {
+                    key: group.key.iter().chain([value]).cloned().collect(),
+                    ids: group.ids.and(ids),
+                }))
+            .collect();
+    }
+    groups
+}
+
+// values python considers equal but stored apart (1, 1.0, True) share one group
+pub fn merge_groups<'py>(py: Python<'py>, groups: Vec<Group>) -> PyResult<Vec<(Bound<'py, PyTuple>, Bitmap)>> {
+    let slots = PyDict::new(py);
+    let mut merged: Vec<(Bound<'py, PyTuple>, Bitmap)> = vec![];
+    for group in groups {
+        let key = PyTuple::new(py, group.key)?;
+        match slots.get_item(&key)? {
+            Some(slot) => merged[slot.extract::<usize>()?].1.or_inplace(&group.ids),
+            None => {
+                slots.set_item(&key, merged.len())?;
+                merged.push((key, group.ids));
+            }
+        }
+    }
+    Ok(merged)
+}
+
+pub fn groups_to_dict<'py, T, F>(py: Python<'py>, groups: Vec<Group>, keys: GroupKeys, leaf: F) -> PyResult<Bound<'py, PyDict>>
+where
+    T: IntoPyObject<'py>,
+    F: Fn(Bitmap) -> T,
+{
+    let out = PyDict::new(py);
+    for (key, ids) in merge_groups(py, groups)? {
+        let last = key.len() - 1;
+        match keys {
+            GroupKeys::Value => out.set_item(key.get_item(0)?, leaf(ids))?,
+            GroupKeys::Tuple => out.set_item(key, leaf(ids))?,
+            GroupKeys::Nested => {
+                let mut level = out.clone();
+                for part in key.iter().take(last) {
+                    level = match level.get_item(&part)? {
+                        Some(inner) => inner.downcast_into::<PyDict>()?,
+                        None => {
+                            let inner = PyDict::new(py);
+                            level.set_item(&part, &inner)?;
+                            inner
+                        }
+                    };
+                }
+                level.set_item(key.get_item(last)?, leaf(ids))?;
+            }
+        }
+    }
+    Ok(out)
+}
+
+// a single attribute name or a non empty list of them, true for the single name
+pub fn group_names(by: &Bound<'_, PyAny>) -> PyResult<(Vec<String>, bool)> {
+    if let Ok(attr) = by.downcast::<PyString>() {
+        return Ok((vec![attr.to_str()?.to_string()], true));
+    }
+    let attrs = by.extract::<Vec<String>>()?;
+    if attrs.is_empty() {
+        return Err(PyValueError::new_err("by needs at least one attribute"));
+    }
+    Ok((attrs, false))
+}
+
+pub fn group_attrs(by: &Bound<'_, PyAny>, flat: bool) -> PyResult<(Vec<AttrPath>, GroupKeys)> {
+    let (names, single) = group_names(by)?;
+    let keys = match (single, flat) {
+        (true, _) => GroupKeys::Value,
+        (false, true) => GroupKeys::Tuple,
+        (false, false) => GroupKeys::Nested,
+    };
+    Ok((names.iter().map(|attr| attr_path(attr)).collect(), keys))
+}
+
+// groups of more than one id sharing every attribute's value
+pub fn duplicate_groups(py: Python, index: &FreezeLock<Vec<QueryMap>>, items: &FreezeLock<Vec<StoredItem>>, scope: &Bitmap, attrs: &[AttrPath]) -> PyResult<Vec<Bitmap>> {
+    let groups = group_by(&index.read().unwrap(), &items.read().unwrap(), scope, attrs);
+    Ok(merge_groups(py, groups)?.into_iter()
+        .map(|(_, ids)| ids)
+        .filter(|ids| ids.cardinality() > 1)
+        .collect())
+}
+
+// all but the kept id of each group, "first" keeps the lowest id and "last" the highest
+pub fn redundant_ids(groups: &[Bitmap], keep: &str) -> PyResult<Bitmap> {
+    let mut res = Bitmap::new();
+    for ids in groups {
+        let kept = match keep {
+            "first" => ids.minimum(),
+            "last" => ids.maximum(),
+            _ => return Err(PyValueError::new_err(format!("unknown keep '{}', expected first or last", keep))),
+        };
+        res.or_inplace(ids);
+        if let Some(kept) = kept {
+            res.remove(kept);
+        }
+    }
+    Ok(res)
+}
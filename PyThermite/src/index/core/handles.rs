@@ -0,0 +1,60 @@
+use std::collections::BTreeMap;
+
+use pyo3::{PyResult, Python, types::{PyAnyMethods, PyDict, PyDictMethods, PyTypeMethods}, Bound};
+
+use crate::index::core::index::IndexAPI;
+
+// python handles an index keeps alive, to find out why removed objects are not freed
+#[derive(Debug, Default)]
+pub struct HandleCounts {
+    // slots holding an object, allowed or not
+    pub held: u64,
+    // held in a slot the index no longer allows
+    pub stale: u64,
+    // strong references to the held handles, across every index sharing them
+    pub handle_refs: u64,
+    pub classes: BTreeMap<String, u64>,
+    // one per attribute, whether or not it holds nested objects
+    pub nested_indexes: u64,
+    pub nested_held: u64,
+}
+
+impl HandleCounts {
+    pub fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("held", self.held)?;
+        dict.set_item("stale", self.stale)?;
+        dict.set_item("handle_refs", self.handle_refs)?;
+        dict.set_item("classes", &self.classes)?;
+        dict.set_item("nested_indexes", self.nested_indexes)?;
+        dict.set_item("nested_held", self.nested_held)?;
+        Ok(dict)
+    }
+}
+
+pub fn handle_counts(py: Python, index: &IndexAPI) -> HandleCounts {
+    let mut counts = HandleCounts::default();
+    {
+        let allowed = index.get_allowed_items_reader();
+        for (id, item) in index.get_items_reader().iter().enumerate() {
+            if item.is_placeholder() {
+                continue;
+            }
+            counts.held += 1;
+            if !allowed.contains(id as u32) {
+                counts.stale += 1;
+            }
+            counts.handle_refs += item.handle_refs() as u64;
+            let class = item.get_py_ref(py)
+                .and_then(|obj| obj.bind(py).get_type().name().ok().map(|name| name.to_string()))
+                .unwrap_or_else(|| "Indexable".to_string());
+            *counts.classes.entry(class).or_default() += 1;
+        }
+    }
+    for qmap in index.get_index_reader().iter().filter(|qmap| !qmap.is_placeholder()) {
+        let nested = handle_counts(py, &qmap.nested);
+        counts.nested_indexes += 1 + nested.nested_indexes;
+        counts.nested_held += nested.held + nested.nested_held;
+    }
+    counts
+}
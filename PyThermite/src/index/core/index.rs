@@ -1,38 +1,427 @@
 
-use std::{fmt, sync::{Arc, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak}, vec};
+use std::{collections::BTreeMap, ffi::CString, fmt, time::Instant, sync::{Arc, atomic::{AtomicBool, Ordering}, Mutex, MutexGuard, OnceLock, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak}, vec};
 use croaring::Bitmap;
-use pyo3::prelude::*;
-use rustc_hash::FxHashMap;
+use pyo3::{exceptions::{PyOSError, PyRuntimeWarning, PyValueError}, prelude::*, types::PyDict};
+use rayon::{ThreadPool, ThreadPoolBuilder, prelude::*};
+use rustc_hash::{FxHashMap, FxHashSet};
 use smallvec::SmallVec;
 use smol_str::SmolStr;
 
 use crate::index::{HybridHashmap, Indexable, PyQueryExpr, core::{query::{BulkQueryMapAdder, query_ops::{QueryExpr, evaluate_and_queries_vec}}, structures::{hybrid_set::{HybridSet, HybridSetOps}, m2m::M2MU32, string_interner::INTERNER}}, interfaces::filtered_index::FilteredIndex, types::{DEFAULT_INDEXABLE_ARC, IndexTree, StrId}};
-use crate::index::core::query::{QueryMap, attr_parts, evaluate_query};
-
+use crate::index::core::query::{QueryMap, attr_parts, memo::evaluate_many, evaluate_query, explain::{QueryTrace, failing_clauses, trace_query}};
+
+use crate::index::core::introspect::{AttributeStats, attribute_stats, infer_schema, sparsity};
+use crate::index::core::column::{ColumnType, column_to_array, numeric_column};
+use crate::index::core::group_by::{AttrPath, Group, GroupKeys, group_by, groups_to_dict};
+use crate::index::core::group_agg::{Metric, group_rows};
+use crate::index::core::config::IndexConfig;
+use crate::index::core::attr_decls::AttrDecls;
+use crate::index::core::semver::{encode_version_bounds, ensure_packaging_encoder};
+use crate::index::core::tolerance::widen_eq;
+use crate::index::core::units::{Unit, convert_units};
+use crate::index::core::fixed_point::{PLACES, scale_bounds};
+use crate::index::core::config::ResultOrder;
+use crate::index::core::sequence::Sequence;
+use crate::index::core::churn::ChurnLog;
+use crate::index::core::metrics::{Metrics, SlowQuery};
+use crate::index::core::replica::{Change, Replica};
+use crate::index::core::wal::Wal;
+use crate::index::core::strict::{check_object, check_value};
 use crate::index::core::stored_item::StoredItem;
-use crate::index::value::PyValue;
+use crate::index::core::top_k::TopK;
+use crate::index::core::alerts::{Alert, Alerts};
+use crate::index::core::deferred::{Deferred, Pending};
+use crate::index::core::lock_order::{lock_pair, reset_if_empty, with_slot};
+use crate::index::core::mutation_gate::{MutationGate, Mutating};
+use crate::index::core::structures::freeze_lock::{FreezeLock, FreezeReadGuard};
+use crate::index::value::{PyIterable, PyValue, RustCastValue};
 
 const QUERY_DEPTH_LEN: usize = 12;
 
 #[derive(Clone, Default)]
 pub struct IndexAPI{
     pub index: IndexTree,
-    pub items: Arc<RwLock<Vec<StoredItem>>>,
-    pub allowed_items: Arc<RwLock<Bitmap>>,
+    pub items: Arc<FreezeLock<Vec<StoredItem>>>,
+    pub allowed_items: Arc<FreezeLock<Bitmap>>,
     pub parent_child_map: Arc<RwLock<M2MU32>>,
     pub parent_index: Option<Weak<IndexAPI>>,
+    pub decls: Arc<RwLock<AttrDecls>>,
+    pub config: Arc<IndexConfig>,
+    // attributes followed by track_top_k
+    pub top_k: Arc<RwLock<FxHashMap<StrId, TopK>>>,
+    // set by the first track_top_k, until then changes skip the trackers' lock
+    pub tracking_top_k: Arc<AtomicBool>,
+    pub alerts: Arc<Mutex<Alerts>>,
+    // held by operations reading another index, see lock_pair
+    pub cross_lock: Arc<Mutex<()>>,
+    // set by freeze, never cleared
+    pub frozen: Arc<AtomicBool>,
+    // entered by every mutation, so freeze can wait out those already under way
+    pub mutations: Arc<MutationGate>,
+    // insertion order of the objects held, only kept while numbering is set
+    pub sequence: Arc<FreezeLock<Sequence>>,
+    // set when the config asks for numbers or a wal or standby takes adds in the order they came,
+    // as ids are recycled. never cleared
+    pub numbering: Arc<AtomicBool>,
+    // value changes per attribute, see hot_attributes
+    pub churn: Arc<ChurnLog>,
+    // read / write counters and the slow query log, see metrics and slow_queries
+    pub metrics: Arc<Metrics>,
+    // set by enable_wal
+    pub wal: Arc<Mutex<Option<Wal>>>,
+    // standbys set by replicate_to
+    pub replicas: Arc<RwLock<Vec<Arc<Replica>>>>,
+    // assignments held back by bulk_update
+    pub deferred: Arc<Mutex<Deferred>>,
+    // ids of objects held weakly (auto_remove_on_gc) that python has freed, removed by the next reap
+    pub freed: Arc<Mutex<Vec<u32>>>,
+    // built on first use when the config sets threads
+    pub pool: Arc<OnceLock<ThreadPool>>,
 }
 
 impl IndexAPI{
 
     pub fn new(parent_index: Option<Weak<IndexAPI>>) -> Self {
+        Self::with_decls(parent_index, AttrDecls::default())
+    }
+
+    pub fn with_decls(parent_index: Option<Weak<IndexAPI>>, decls: AttrDecls) -> Self {
         Self {
-            index: Arc::new(RwLock::new(vec![])),
-            items: Arc::new(RwLock::new(vec![])),
-            allowed_items: Arc::new(RwLock::new(Bitmap::new())),
+            index: Arc::default(),
+            items: Arc::default(),
+            allowed_items: Arc::default(),
             parent_child_map: Arc::new(RwLock::new(M2MU32::new())),
             parent_index: parent_index,
+            decls: Arc::new(RwLock::new(decls)),
+            config: Arc::default(),
+            top_k: Arc::default(),
+            tracking_top_k: Arc::default(),
+            alerts: Arc::default(),
+            cross_lock: Arc::default(),
+            frozen: Arc::default(),
+            mutations: Arc::default(),
+            sequence: Arc::default(),
+            numbering: Arc::default(),
+            churn: Arc::default(),
+            metrics: Arc::default(),
+            wal: Arc::default(),
+            replicas: Arc::default(),
+            deferred: Arc::default(),
+            freed: Arc::default(),
+            pool: Arc::default(),
+        }
+    }
+
+    pub fn with_config(mut self, config: Arc<IndexConfig>) -> Self {
+        self.numbering = Arc::new(AtomicBool::new(config.stamps()));
+        self.config = config;
+        self
+    }
+
+    pub fn limit_nested(&self, attr: &str, limits: AttrDecls) -> PyResult<()> {
+        if !self.get_allowed_items_reader().is_empty() {
+            return Err(PyValueError::new_err("nested limits must be set before objects are added to the index"));
+        }
+        self.get_decls_writer().set(attr, limits);
+        Ok(())
+    }
+
+    pub fn coerce_numeric(&self, attr: &str) -> PyResult<()> {
+        if !self.get_allowed_items_reader().is_empty() {
+            return Err(PyValueError::new_err("numeric coercion must be set before objects are added to the index"));
+        }
+        self.get_decls_writer().declare(attr, |decl| decl.coerce_numeric = true);
+        Ok(())
+    }
+
+    pub fn semver(&self, py: Python, attr: &str) -> PyResult<()> {
+        if !self.get_allowed_items_reader().is_empty() {
+            return Err(PyValueError::new_err("version ordering must be set before objects are added to the index"));
+        }
+        ensure_packaging_encoder(py);
+        self.get_decls_writer().declare(attr, |decl| decl.semver = true);
+        Ok(())
+    }
+
+    pub fn interval(&self, attr: &str) -> PyResult<()> {
+        if !self.get_allowed_items_reader().is_empty() {
+            return Err(PyValueError::new_err("intervals must be declared before objects are added to the index"));
+        }
+        self.get_decls_writer().declare(attr, |decl| decl.interval = true);
+        Ok(())
+    }
+
+    pub fn flags(&self, attr: &str) -> PyResult<()> {
+        if !self.get_allowed_items_reader().is_empty() {
+            return Err(PyValueError::new_err("flags must be declared before objects are added to the index"));
+        }
+        self.get_decls_writer().declare(attr, |decl| decl.flags = true);
+        Ok(())
+    }
+
+    pub fn fixed_point(&self, attr: &str, places: u8) -> PyResult<()> {
+        if !PLACES.contains(&places) {
+            return Err(PyValueError::new_err(format!("fixed point supports 2 or 4 decimal places, got {}", places)));
+        }
+        if !self.get_allowed_items_reader().is_empty() {
+            return Err(PyValueError::new_err("fixed point must be declared before objects are added to the index"));
+        }
+        self.get_decls_writer().declare(attr, |decl| decl.fixed_point = Some(places));
+        Ok(())
+    }
+
+    // only read at query time, so unlike the declarations above it can change at any point
+    pub fn float_tolerance(&self, attr: &str, tolerance: f64) -> PyResult<()> {
+        if !tolerance.is_finite() || tolerance < 0.0 {
+            return Err(PyValueError::new_err("tolerance must be a finite number, 0 or above"));
+        }
+        let tolerance = (tolerance > 0.0).then_some(tolerance);
+        self.get_decls_writer().declare(attr, |decl| decl.tolerance = tolerance);
+        Ok(())
+    }
+
+    pub fn declares_semver(&self, attr: &str) -> bool {
+        self.get_decls_reader().decl(attr).semver
+    }
+
+    pub fn declared_tolerance(&self, attr: &str) -> Option<f64> {
+        self.get_decls_reader().decl(attr).tolerance
+    }
+
+    // query time only as well, stored numbers are taken to already be in the unit
+    pub fn units(&self, attr: &str, unit: &str) -> PyResult<()> {
+        let unit = Unit::parse(unit)?;
+        self.get_decls_writer().declare(attr, |decl| decl.unit = Some(unit));
+        Ok(())
+    }
+
+    pub fn declared_unit(&self, attr: &str) -> Option<Unit> {
+        self.get_decls_reader().decl(attr).unit
+    }
+
+    pub fn declared_fixed_point(&self, attr: &str) -> Option<u8> {
+        self.get_decls_reader().decl(attr).fixed_point
+    }
+
+    // converts quantities on attributes declared with a unit, rewrites version strings in range clauses
+    // on attributes declared with semver and eq on numbers into ranges on attributes declared with a tolerance,
+    // then scales numbers on fixed point attributes to the integers stored for them
+    pub fn apply_declarations(&self, expr: &mut QueryExpr) -> PyResult<()> {
+        convert_units(expr, &|attr| self.declared_unit(attr))?;
+        encode_version_bounds(expr, &|attr| self.declares_semver(attr));
+        widen_eq(expr, &|attr| self.declared_tolerance(attr));
+        scale_bounds(expr, &|attr| self.declared_fixed_point(attr));
+        Ok(())
+    }
+
+    pub fn check_strict_object(&self, obj: &Indexable) -> PyResult<()> {
+        if !self.config.strict {
+            return Ok(());
+        }
+        check_object(&self.get_decls_reader(), obj, &mut FxHashSet::default())
+    }
+
+    // stops every mutation, then optimizes the structures and moves them out of their locks, so queries
+    // read them without locking. mutations already past check_mutable finish first, and assignments an open
+    // bulk_update holds back are applied
+    pub fn freeze(&self, weak_self: Weak<IndexAPI>) -> PyResult<()> {
+        if self.mutations.held_here() {
+            return Err(PyValueError::new_err("an index can't be frozen by code changing it, such as an alert callback"));
         }
+        if self.frozen.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+        self.mutations.wait_idle();
+        let pending = self.deferred.lock().unwrap().close_all();
+        self.apply_deferred(weak_self, pending);
+        self.optimize(None)?;
+        self.freeze_structures();
+        Ok(())
+    }
+
+    // nested indexes are frozen along with the maps holding them
+    pub fn freeze_structures(&self) {
+        self.frozen.store(true, Ordering::Release);
+        for qmap in self.get_index_reader().iter().filter(|qmap| !qmap.is_placeholder()) {
+            qmap.freeze();
+        }
+        self.index.freeze();
+        self.items.freeze();
+        self.allowed_items.freeze();
+        self.sequence.freeze();
+    }
+
+    // a frozen index was optimized when it was frozen
+    pub fn warmup(&self, attrs: Option<&[String]>) -> PyResult<()> {
+        let mut mutating = Mutating::default();
+        self.mutations.enter(&mut mutating);
+        if self.is_frozen() {
+            return Ok(());
+        }
+        self.optimize(attrs)
+    }
+
+    // optimizes the query maps of attrs, every attribute when None, and the nested indexes under them
+    fn optimize(&self, attrs: Option<&[String]>) -> PyResult<()> {
+        let index = self.get_index_reader();
+        let qmaps: Vec<&QueryMap> = match attrs {
+            Some(attrs) => attrs.iter().map(|attr| {
+                index.get(INTERNER.intern(attr) as usize)
+                    .filter(|qmap| !qmap.is_placeholder())
+                    .ok_or_else(|| PyValueError::new_err(format!("'{}' is not indexed", attr)))
+            }).collect::<PyResult<_>>()?,
+            None => index.iter().filter(|qmap| !qmap.is_placeholder()).collect(),
+        };
+        for qmap in qmaps {
+            qmap.optimize();
+            qmap.nested.optimize(None)?;
+        }
+        let mut allowed = self.get_allowed_items_writer();
+        allowed.run_optimize();
+        allowed.shrink_to_fit();
+        Ok(())
+    }
+
+    // removes objects nothing outside the index refers to any more, returns how many went
+    // strongly held objects are found by their refcount, repeating until stable as dropping a parent
+    // can release the last reference to its children
+    pub fn reap(&self, py: Python) -> u64 {
+        let mut reaped = self.reap_freed(py);
+        loop {
+            let allowed = self.ids();
+            let dead: Bitmap = {
+                let items = self.get_items_reader();
+                allowed.iter().filter(|id| items[*id as usize].only_held_by_index(py)).collect()
+            };
+            if dead.is_empty() {
+                return reaped + self.reap_freed(py);
+            }
+            reaped += dead.cardinality();
+            self.keep_only_from_bitmap(&allowed.andnot(&dead));
+            self.release_empty_keys();
+        }
+    }
+
+    // removes the weakly held objects python has freed since the last call, only looking at those
+    fn reap_freed(&self, py: Python) -> u64 {
+        let mut reaped = 0;
+        loop {
+            let queued = std::mem::take(&mut *self.freed.lock().unwrap());
+            if queued.is_empty() {
+                return reaped;
+            }
+            // an id may have been recycled for an object added since
+            let dead: Bitmap = {
+                let items = self.get_items_reader();
+                let allowed = self.get_allowed_items_reader();
+                queued.into_iter()
+                    .filter(|id| allowed.contains(*id) && items.get(*id as usize).is_some_and(|item| item.is_freed(py)))
+                    .collect()
+            };
+            if dead.is_empty() {
+                continue;
+            }
+            reaped += dead.cardinality();
+            self.keep_only_from_bitmap(&self.ids().andnot(&dead));
+            self.release_empty_keys();
+        }
+    }
+
+    // auto_remove_on_gc indexes hold objects weakly so python can free them, see reap
+    pub fn hold(&self, py: Python, obj: Py<Indexable>, rust_handle: Arc<Indexable>) -> PyResult<StoredItem> {
+        if self.config.auto_remove_on_gc {
+            StoredItem::weak(obj.bind(py), rust_handle, &self.freed)
+        } else {
+            Ok(StoredItem::new(Arc::new(obj), rust_handle))
+        }
+    }
+
+    // objects copied from another index are held the way this one holds them, those freed meanwhile are dropped
+    pub fn rehold(&self, py: Python, ids: &Bitmap) -> PyResult<()> {
+        let copied: Vec<(u32, StoredItem)> = {
+            let items = self.get_items_reader();
+            ids.iter().filter_map(|id| items.get(id as usize).map(|item| (id, item.clone()))).collect()
+        };
+        let mut held = Vec::with_capacity(copied.len());
+        let mut gone = Bitmap::new();
+        for (id, item) in copied {
+            let rehold = match (self.config.auto_remove_on_gc, item.get_py_ref(py)) {
+                (false, _) => item.strong(py),
+                (true, Some(obj)) => Some(StoredItem::weak(obj.bind(py), item.get_owned_handle().clone(), &self.freed)?),
+                (true, None) => None,
+            };
+            match rehold {
+                Some(rehold) => held.push((id, item, rehold)),
+                None => gone.add(id),
+            }
+        }
+        {
+            let mut items = self.get_items_writer();
+            for (id, item, rehold) in held {
+                // unless another write replaced it meanwhile
+                if items[id as usize] == item {
+                    items[id as usize] = rehold;
+                }
+            }
+        }
+        if !gone.is_empty() {
+            self.keep_only_from_bitmap(&self.ids().andnot(&gone));
+        }
+        Ok(())
+    }
+
+    pub fn release_empty_keys(&self) {
+        for qmap in self.get_index_reader().iter().filter(|qmap| !qmap.is_placeholder()) {
+            qmap.release_empty_keys();
+        }
+    }
+
+    pub fn reap_if_auto(&self, py: Python) {
+        if self.config.auto_remove_on_gc {
+            self.reap_freed(py);
+        }
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.load(Ordering::Acquire)
+    }
+
+    // nested indexes are frozen along with the index they hang off. the mutation runs for as long as the
+    // guard returned is held, freeze waits for it
+    pub fn check_mutable(&self) -> PyResult<Mutating> {
+        let mut mutating = Mutating::default();
+        self.enter_mutation(&mut mutating)?;
+        Ok(mutating)
+    }
+
+    // entered before frozen is read, see MutationGate
+    fn enter_mutation(&self, mutating: &mut Mutating) -> PyResult<()> {
+        self.mutations.enter(mutating);
+        if self.is_frozen() {
+            return Err(PyValueError::new_err("index is frozen, objects can no longer be added, removed or updated"));
+        }
+        if let Some(wal) = self.wal.lock().unwrap().as_ref() {
+            if let Some(e) = &wal.error {
+                return Err(PyOSError::new_err(format!(
+                    "writing the log {} failed: {}, compact starts a new one", wal.path().display(), e
+                )));
+            }
+        }
+        match self.parent_index.as_ref().and_then(|parent| parent.upgrade()) {
+            Some(parent) => parent.enter_mutation(mutating),
+            None => Ok(()),
+        }
+    }
+
+    pub fn check_strict_value(&self, attr_id: StrId, value: &PyValue) -> PyResult<()> {
+        if !self.config.strict {
+            return Ok(());
+        }
+        check_value(&self.get_decls_reader(), attr_id, value, &mut FxHashSet::default())
+    }
+
+    pub fn allows_nesting(&self) -> bool {
+        self.get_decls_reader().allows_nesting()
     }
 
     pub fn collect(&self, py:Python) -> PyResult<Vec<Py<Indexable>>> {
@@ -40,12 +429,35 @@ impl IndexAPI{
         let allowed_items = self.get_allowed_items_reader();
         let items_reader = self.get_items_reader();
 
-        for idx in allowed_items.iter(){
-            result.push(items_reader[idx as usize].get_py_ref(py));
+        match self.ordering() {
+            Some(sequence) => {
+                result.extend(sequence.read().unwrap().in_order(&allowed_items).into_iter()
+                    .filter_map(|idx| items_reader[idx as usize].get_py_ref(py)));
+            },
+            None => {
+                result.extend(allowed_items.iter().filter_map(|idx| items_reader[idx as usize].get_py_ref(py)));
+            },
         }
         Ok(result)
     }
 
+    // the sequence results are ordered by, None when they go by id
+    pub fn ordering(&self) -> Option<Arc<FreezeLock<Sequence>>> {
+        (self.config.order == ResultOrder::Insertion).then(|| self.sequence.clone())
+    }
+
+    fn numbers_objects(&self) -> bool {
+        self.numbering.load(Ordering::Acquire)
+    }
+
+    // the sequence since / latest read, raises ValueError when the index does not number objects
+    pub fn read_sequence(&self) -> PyResult<FreezeReadGuard<'_, Sequence>> {
+        if !self.config.stamps() {
+            return Err(PyValueError::new_err("the index does not number objects, create it with IndexConfig().sequence() or order=\"insertion\""));
+        }
+        Ok(self.sequence.read().unwrap())
+    }
+
     pub fn get_from_parent_ids(&self, parent_ids: &Bitmap) -> Bitmap {
         self.get_parent_child_map_reader().get_for_forward_many(parent_ids)
     }
@@ -58,58 +470,160 @@ impl IndexAPI{
     pub fn add_object_many(
         &self,
         weak_self: Weak<Self>,
-        raw_objs: Vec<(Indexable, Py<Indexable>)>
+        stored_items: Vec<StoredItem>
     ) {
-        // 3 pass - wrap in ARC - add meta to index with locks - add to index maps which may call meta locks
-        let arc_objs: Vec<(Arc<Indexable>, Arc<Py<Indexable>>)> = raw_objs
-            .into_iter()
-            .map(|(idx, py)| (Arc::new(idx), Arc::new(py)))
-            .collect();
+        // 2 pass - add meta to index with locks - add to index maps which may call meta locks
+        self.add_stored_items(weak_self, stored_items);
+    }
+
+    // registers this index on every object another index already holds, sharing the same handles, returns their ids
+    pub fn adopt(&self, weak_self: Weak<Self>, other: &IndexAPI, query: Option<&QueryExpr>) -> PyResult<Bitmap> {
+        let mut ids = {
+            let index = other.get_index_reader();
+            let allowed = other.get_allowed_items_reader();
+            match query {
+                Some(query) => evaluate_query(&index, &allowed, query),
+                None => allowed.clone(),
+            }
+        };
+        ids.andnot_inplace(&self.get_allowed_items_reader());
+
+        let stored_items: Vec<StoredItem> = {
+            let items = other.get_items_reader();
+            ids.iter().map(|idx| items[idx as usize].clone()).collect()
+        };
+        for item in stored_items.iter() {
+            self.check_strict_object(item.get_owned_handle())?;
+        }
+        self.add_stored_items(weak_self, stored_items);
+        Ok(ids)
+    }
+
+    fn add_stored_items(&self, weak_self: Weak<Self>, stored_items: Vec<StoredItem>) {
+        let added: Bitmap = stored_items.iter().map(|item| item.get_owned_handle().id).collect();
+        self.add_stored_items_to_index(weak_self, stored_items);
+        self.changed(None, &added);
+    }
 
+    // registers the index on every item and holds them, their values still to be put in the maps
+    fn hold_items(&self, weak_self: &Weak<Self>, stored_items: &[StoredItem]) {
+        let numbering = self.numbers_objects();
         let mut allowed_writer: RwLockWriteGuard<'_, Bitmap> = self.get_allowed_items_writer();
         let mut items_writer = self.get_items_writer();
+        let mut sequence = numbering.then(|| self.sequence.write().unwrap());
 
-        for (rust_handle, py_handle) in &arc_objs {
+        for stored_item in stored_items {
+            let rust_handle = stored_item.get_owned_handle();
 
             rust_handle.add_index(weak_self.clone());
-            allowed_writer.add(rust_handle.id);
+            // adding an object already held keeps its place
+            if allowed_writer.add_checked(rust_handle.id) {
+                if let Some(sequence) = sequence.as_mut() {
+                    sequence.stamp(rust_handle.id);
+                }
+            }
 
             let idx = rust_handle.id as usize;
-            let stored_item = StoredItem::new(py_handle.clone(), rust_handle.clone());
-
             if items_writer.len() <= idx{
-                items_writer.resize(idx * 2, StoredItem::default());
+                items_writer.resize(usize::max(idx * 2, 1), StoredItem::default());
             }
 
-            items_writer[idx] = stored_item;
+            items_writer[idx] = stored_item.clone();
 
         }
-        drop(allowed_writer);
-        drop(items_writer);
+    }
+
+    // objects built from decoded columns, columns[i][row] holds the value of attrs[i] on stored_items[row]
+    // or None where it is unset. each column goes into its map in one pass, the columns in parallel
+    // runs f on the index's own pool when the config sets threads, else on rayon's global one
+    fn in_pool<R: Send>(&self, f: impl FnOnce() -> R + Send) -> R {
+        match self.config.threads {
+            Some(threads) => self.pool.get_or_init(|| {
+                ThreadPoolBuilder::new().num_threads(threads).build().expect("thread pool")
+            }).install(f),
+            None => f(),
+        }
+    }
+
+    pub fn add_columns(&self, weak_self: Weak<Self>, stored_items: Vec<StoredItem>, attrs: &[StrId], columns: &[Vec<Option<PyValue>>]) {
+        self.hold_items(&weak_self, &stored_items);
+        let ids: Vec<u32> = stored_items.iter().map(|item| item.get_owned_handle().id).collect();
+
+        // the objects are all of one class
+        let decls = self.get_decls_reader();
+        let indexed: Vec<bool> = attrs.iter()
+            .map(|attr| decls.is_indexed(*attr) && stored_items.first().is_some_and(|item| item.get_owned_handle().is_indexed(*attr)))
+            .collect();
+        // every map made up front, so the columns fill in parallel under the read lock
+        let missing = {
+            let index = self.get_index_reader();
+            attrs.iter().zip(&indexed).any(|(attr, indexed)| *indexed && index.get(*attr as usize).is_none_or(QueryMap::is_placeholder))
+        };
+        if missing {
+            let mut writer = self.get_index_writer();
+            for (attr, _) in attrs.iter().zip(&indexed).filter(|(_, indexed)| **indexed) {
+                let attr_id = *attr as usize;
+                if attr_id >= writer.len() {
+                    writer.resize_with(attr_id + 1, Default::default);
+                }
+                if writer[attr_id].is_placeholder() {
+                    writer[attr_id] = QueryMap::new(weak_self.clone(), decls.for_child(*attr));
+                }
+            }
+        }
+        drop(decls);
+
+        let index = self.get_index_reader();
+        self.in_pool(|| attrs.par_iter().zip(columns).zip(&indexed)
+            .filter(|(_, indexed)| **indexed)
+            .for_each(|((attr, column), _)| {
+                let mut adder = index[*attr as usize].get_bulk_writer();
+                for (id, value) in ids.iter().zip(column) {
+                    if let Some(value) = value {
+                        adder.insert(value, *id);
+                        adder.mark_present(*id);
+                    }
+                }
+            }));
+        drop(index);
+        self.changed(None, &ids.iter().copied().collect());
+    }
+
+    fn add_stored_items_to_index(&self, weak_self: Weak<Self>, stored_items: Vec<StoredItem>) {
+        self.hold_items(&weak_self, &stored_items);
 
+        let decls = self.get_decls_reader();
         let mut index_reader = self.get_index_reader();
         let mut delayed_adders: Vec<BulkQueryMapAdder> = index_reader.iter().map(|i| {
             i.get_bulk_writer()
         }).collect();
 
-        for (rust_handle, _) in arc_objs {
+        for stored_item in stored_items {
+            let rust_handle = stored_item.get_owned_handle();
             let object_id = rust_handle.id;
             for (key, value) in rust_handle.get_py_values().iter() {
+                if !decls.is_indexed(*key) || !rust_handle.is_indexed(*key) {
+                    continue;
+                }
                 let attr_id = *key as usize;
-                if let Some(qmap) = delayed_adders.get_mut(attr_id) {
+                if let Some(qmap) = delayed_adders.get_mut(attr_id).filter(|q| !q.is_placeholder()) {
                     qmap.insert(value, object_id);
+                    qmap.mark_present(object_id);
                 } else {
                     drop(delayed_adders);
                     drop(index_reader);
 
                     let mut writer = self.get_index_writer();
-                    let qmap = QueryMap::new(weak_self.clone());
-                    qmap.insert(value, object_id);
                     if attr_id >= writer.len() {
                         writer.resize_with((attr_id + 1) as usize, Default::default); // or None if Option
                     }
-                    writer[attr_id as usize] = qmap;
-                    
+                    // another add may have made the map since the read lock was dropped
+                    if writer[attr_id].is_placeholder() {
+                        writer[attr_id] = QueryMap::new(weak_self.clone(), decls.for_child(*key));
+                    }
+                    writer[attr_id].insert(value, object_id);
+                    writer[attr_id].mark_present(object_id);
+
                     drop(writer);
                     index_reader = self.get_index_reader();
                     delayed_adders = index_reader.iter().map(|i| {
@@ -120,6 +634,10 @@ impl IndexAPI{
         }
     }
 
+    pub fn get_direct_parents(&self, id: u32) -> Bitmap {
+        self.get_parent_child_map_reader().get_for_reverse(id)
+    }
+
     pub fn has_object_id(&self, id: u32) -> bool {
         self.get_parent_child_map_reader().contains(id)
     }
@@ -140,7 +658,11 @@ impl IndexAPI{
         py_val_hashmap: MutexGuard<HybridHashmap<StrId, PyValue>>
     ) {
 
-        self.get_allowed_items_writer().add(idx);
+        let numbering = self.numbers_objects();
+        if self.get_allowed_items_writer().add_checked(idx) && numbering {
+            self.sequence.write().unwrap().stamp(idx);
+        }
+        let handle = stored_item.get_owned_handle().clone();
         {
             let mut items_writer = self.get_items_writer();
             if items_writer.len() <= idx as usize{
@@ -150,10 +672,13 @@ impl IndexAPI{
             items_writer[idx as usize] = stored_item;
         }
 
-        for (attr_id, value) in py_val_hashmap.iter() {
+        for (attr_id, value) in py_val_hashmap.iter().filter(|(attr_id, _)| handle.is_indexed(**attr_id)) {
             // if key.starts_with("_"){continue;}
             self.add_index(weak_self.clone(), idx, *attr_id, value);
         }
+        // the log reads the values again
+        drop(py_val_hashmap);
+        self.changed(None, &Bitmap::of(&[idx]));
     }
 
     pub fn get_parents_from_id(&self, id: usize) -> Bitmap {
@@ -171,7 +696,8 @@ impl IndexAPI{
         let item_id = item.id;
         let mut parent_child_map_writer = self.get_parent_child_map_writer();
         
-        if parent_child_map_writer.get_for_reverse(parent_id).cardinality() == 1 {
+        // only drop the child once its last parent lets go of it
+        if parent_child_map_writer.get_for_reverse(item_id).cardinality() == 1 {
             parent_child_map_writer.remove(parent_id, item_id);
             let mut items_writer = self.get_items_writer();
             items_writer[item_id as usize] = StoredItem::default();
@@ -183,6 +709,8 @@ impl IndexAPI{
             }
 
             self.get_allowed_items_writer().remove(item_id);
+            self.sequence.write().unwrap().remove(item_id);
+            self.changed(None, &Bitmap::of(&[item_id]));
         }
     }
     
@@ -198,18 +726,23 @@ impl IndexAPI{
     ){
         // this one is not ideal as it needs to trim in place
         // ideally this is a bunch of andnot_inplace calls
-        let index = self.get_index_reader();
-        let all_valid = self.get_allowed_items_reader();
         let exprs: Vec<QueryExpr> = query.into_iter().map(|(k, v)| {
             QueryExpr::Eq(k, v)
         }).collect();
-        
-        let keep = evaluate_and_queries_vec(&index, &all_valid, &exprs);
-        drop(all_valid);
-        self.keep_only_from_bitmap(&keep);
+
+        // released before trimming, which takes the cross lock a union_with holds while waiting on the maps.
+        // objects added in between are not among those removed
+        let to_remove = {
+            let index = self.get_index_reader();
+            let all_valid = self.get_allowed_items_reader();
+            all_valid.andnot(&evaluate_and_queries_vec(&index, &all_valid, &exprs))
+        };
+        self.discard(&to_remove);
     }
 
     fn keep_only_from_bitmap(&self, keep: &Bitmap) {
+        // a union_with reading this index would otherwise copy objects whose ids are being cleared from the maps
+        let _cross = self.cross_lock.lock().unwrap();
         let index = self.get_index_reader();
         let to_remove = self.get_allowed_items_reader().andnot(&keep);
         
@@ -220,10 +753,14 @@ impl IndexAPI{
         for map in index.iter() {
             map.keep_only(&keep);
         }
+        self.sequence.write().unwrap().keep_only(keep);
         let mut stored_items = self.get_items_writer();
         for idx in to_remove.iter(){
             stored_items[idx as usize] = StoredItem::default();
         }
+        drop(stored_items);
+        drop(index);
+        self.changed(None, &to_remove);
     }
 
 
@@ -231,60 +768,161 @@ impl IndexAPI{
         &self,
         query: FxHashMap<SmolStr, PyValue>
     ) -> FilteredIndex {
-        let index = self.get_index_reader();
-        let all_valid = self.get_allowed_items_reader();
+        let start = Instant::now();
         let exprs: Vec<QueryExpr> = query.into_iter().map(|(k, v)| {
             QueryExpr::Eq(k, v)
         }).collect();
-        self.filter_from_bitmap(
+        let bm = {
+            let index = self.get_index_reader();
+            let all_valid = self.get_allowed_items_reader();
             evaluate_and_queries_vec(&index, &all_valid, &exprs)
-        )
+        };
+        self.record_query(start, &bm, || QueryExpr::And(exprs));
+        self.filter_from_bitmap(bm)
     }
 
     pub fn reduced_query(
         &self,
         query: PyQueryExpr,
     ) -> FilteredIndex {
-        let index = self.get_index_reader();
-        let allowed = self.get_allowed_items_reader();
-        self.filter_from_bitmap(
-            evaluate_query(&index, &allowed, &query.inner)
-        )
+        let start = Instant::now();
+        let normalized = query.inner.normalized();
+        let bm = {
+            let index = self.get_index_reader();
+            let allowed = self.get_allowed_items_reader();
+            evaluate_query(&index, &allowed, &normalized)
+        };
+        self.record_query(start, &bm, || normalized);
+        self.filter_from_bitmap(bm)
+    }
+
+    // one read of the index for every query, repeated clauses evaluated once
+    pub fn reduced_many(&self, queries: &[QueryExpr]) -> Vec<FilteredIndex> {
+        let start = Instant::now();
+        let normalized: Vec<QueryExpr> = queries.iter().map(|query| query.normalized()).collect();
+        let bms = {
+            let index = self.get_index_reader();
+            let allowed = self.get_allowed_items_reader();
+            evaluate_many(&index, &allowed, &normalized)
+        };
+        self.metrics.record_reads(queries.len() as u64);
+        let elapsed = start.elapsed();
+        // logged as one batch, the shared clauses make a per query time meaningless
+        if self.metrics.is_slow(elapsed) {
+            let listed: Vec<String> = normalized.iter().map(|query| query.to_string()).collect();
+            self.metrics.log_slow(SlowQuery {
+                query: format!("[{}]", listed.join(", ")),
+                elapsed,
+                matched: bms.iter().map(|bm| bm.cardinality()).sum(),
+                profile: None,
+            });
+        }
+        bms.into_iter()
+            .map(|bm| self.filter_from_bitmap(bm))
+            .collect()
     }
 
-    pub fn union_with(&self, other: &IndexAPI) -> PyResult<()>{
-        let mut self_index = self.get_index_reader();
-        let other_index = other.get_index_reader();
+    // counts a query run on the index, one slower than the slow query threshold is logged and
+    // profiled by running it again traced. called with the index locks released
+    fn record_query<F: FnOnce() -> QueryExpr>(&self, start: Instant, matched: &Bitmap, query: F) {
+        self.metrics.record_reads(1);
+        let elapsed = start.elapsed();
+        if self.metrics.is_slow(elapsed) {
+            let query = query();
+            self.metrics.log_slow(SlowQuery {
+                query: query.to_string(),
+                elapsed,
+                matched: matched.cardinality(),
+                profile: Some(self.explain(&query)),
+            });
+        }
+    }
+
+    // both cross locks taken in address order, so two operations over the same pair of indexes,
+    // in either direction, run one after the other instead of each waiting on the other's locks
+    fn lock_pair<'a>(&'a self, other: &'a IndexAPI) -> (MutexGuard<'a, ()>, MutexGuard<'a, ()>) {
+        lock_pair(&*self.cross_lock, &*other.cross_lock, |lock| lock.lock().unwrap())
+    }
 
-        if self_index.len() < other_index.len() {
-            let additional = other_index.len() - self_index.len();
-            drop(self_index);
+    // returns the ids copied from other
+    pub fn union_with(&self, weak_self: Weak<IndexAPI>, other: &IndexAPI) -> PyResult<Bitmap>{
+        // already holds everything it would add
+        if Arc::ptr_eq(&self.cross_lock, &other.cross_lock) {
+            return Ok(Bitmap::new());
+        }
+        let _guards = self.lock_pair(other);
+        // one snapshot of other's ids, so objects added to it meanwhile are left out throughout
+        let mut other_ids = other.ids();
+
+        // checked before other's maps are locked, a setter waiting on them holds the values read here
+        if self.config.strict {
+            let items = other.get_items_reader();
+            for id in other_ids.iter() {
+                self.check_strict_object(items[id as usize].get_owned_handle())?;
+            }
+        }
+
+        // held exclusively until the maps are merged, so no posting reaches them meanwhile,
+        // and only the postings of the snapshot are taken. a frozen other takes none
+        let other_index = other.index.read_exclusive().unwrap();
+        other_ids.and_inplace(&other.get_allowed_items_reader());
+        {
+            // a real map for every attribute other has one for, so none of its values are left out of the merge
+            let decls = self.get_decls_reader();
             let mut self_index_writer = self.get_index_writer();
-            self_index_writer.reserve(additional);
-            drop(self_index_writer);
-            self_index = self.get_index_reader();
+            if self_index_writer.len() < other_index.len() {
+                self_index_writer.resize_with(other_index.len(), Default::default);
+            }
+            for (attr_id, other_qm) in other_index.iter().enumerate() {
+                if self_index_writer[attr_id].is_placeholder() && !other_qm.is_placeholder() && decls.is_indexed(attr_id as StrId) {
+                    self_index_writer[attr_id] = QueryMap::new(weak_self.clone(), decls.for_child(attr_id as StrId));
+                }
+            }
         }
-        
+        let self_index = self.get_index_reader();
+
         for (self_qm, other_qm) in self_index.iter().zip(other_index.iter()) {
-            self_qm.merge(other_qm);
+            if !self_qm.is_placeholder() && !other_qm.is_placeholder() {
+                self_qm.merge(other_qm, &other_ids);
+            }
         }
+        drop(other_index);
 
         let mut items_writer = self.get_items_writer();
-
-        // iterate other bitset to get allowed items
-        let other_allowed_items_reader = other.get_allowed_items_reader();
         let other_items_reader = other.get_items_reader();
 
-        for idx in other_allowed_items_reader.iter(){
+        for idx in other_ids.iter(){
             let other_item = other_items_reader.get(idx as usize).unwrap().clone();
             if items_writer.len() <= idx as usize{
                 items_writer.resize(usize::max(idx as usize * 2, 1), StoredItem::default());
             }
             items_writer[idx as usize] = other_item;
         }
-        self.get_allowed_items_writer().or_inplace(&other_allowed_items_reader);
+        let fresh = other_ids.andnot(&self.get_allowed_items_reader());
+        // objects new to this index follow in the order other took them in
+        if self.numbers_objects() {
+            let mut sequence = self.sequence.write().unwrap();
+            let mut taken = other.sequence.read().unwrap().in_order(&fresh);
+            // other did not number its objects, they follow by id
+            let stamped: Bitmap = taken.iter().copied().collect();
+            taken.extend(fresh.andnot(&stamped).iter());
+            for idx in taken {
+                sequence.stamp(idx);
+            }
+        }
+        self.get_allowed_items_writer().or_inplace(&other_ids);
+        drop(items_writer);
+        drop(self_index);
+        // later changes to the objects taken reach this index too, registered once no map is locked
+        // as a setter holds the object while it waits on them
+        let taken: Vec<StoredItem> = fresh.iter().map(|idx| other_items_reader[idx as usize].clone()).collect();
+        drop(other_items_reader);
+        for item in taken {
+            item.get_owned_handle().add_index(weak_self.clone());
+        }
+        self.changed(None, &other_ids);
 
-        Ok(())
+        Ok(other_ids)
     }
 
 
@@ -299,17 +937,345 @@ impl IndexAPI{
 //        if attr.starts_with("_") {
 //            return;
 //        }
-        
-        if let Some(old_val) = old_pv {
-            self.remove_index(item_id, attr as usize, old_val);
+        if self.defer(item_id, &[(attr, old_pv, new_pv)]) {
+            return;
+        }
+
+        self.churn.record(attr);
+        self.log_set(item_id, attr, new_pv);
+        let updated = old_pv.is_some_and(|old_val| {
+            Self::is_element_indexed(old_val) && Self::is_element_indexed(new_pv)
+                && self.get_decls_reader().is_indexed(attr)
+                && self.get_index_reader().get(attr as usize).filter(|q| !q.is_placeholder())
+                    .map(|qmap| qmap.update_iterable(old_val, new_pv, item_id))
+                    .is_some()
+        });
+        if !updated {
+            if let Some(old_val) = old_pv {
+                self.remove_index(item_id, attr as usize, old_val);
+            }
+            self.add_index(weak_self, item_id, attr, &new_pv);
+        }
+        self.changed(Some(&[attr]), &Bitmap::of(&[item_id]));
+    }
+
+    // several values of one object changed at once, each query map reached under a single read of the index,
+    // changes holds (attr, old value, new value)
+    pub fn update_values(&self, weak_self: Weak<IndexAPI>, item_id: u32, changes: &[(StrId, Option<&PyValue>, &PyValue)]) {
+        if self.defer(item_id, changes) {
+            return;
+        }
+        for (attr, _, _) in changes {
+            self.churn.record(*attr);
+        }
+        if let Some(wal) = self.wal.lock().unwrap().as_mut() {
+            for (attr, _, new_pv) in changes {
+                wal.log_set(item_id, *attr, new_pv);
+            }
+        }
+        for replica in self.replicas.read().unwrap().iter() {
+            for (attr, _, new_pv) in changes {
+                replica.push(Change::Set(item_id, *attr, (*new_pv).clone()));
+            }
+        }
+
+        // attributes without a query map yet go the single value way once the locks are dropped, add_index makes the map
+        let mut rest = Vec::new();
+        {
+            let decls = self.get_decls_reader();
+            let index = self.get_index_reader();
+            for change @ (attr, old_pv, new_pv) in changes {
+                if !decls.is_indexed(*attr) {
+                    continue;
+                }
+                let Some(qmap) = index.get(*attr as usize).filter(|q| !q.is_placeholder()) else {
+                    rest.push(change);
+                    continue;
+                };
+                match old_pv {
+                    Some(old_val) if Self::is_element_indexed(old_val) && Self::is_element_indexed(new_pv) => {
+                        qmap.update_iterable(old_val, new_pv, item_id);
+                    },
+                    Some(old_val) => {
+                        qmap.remove_id(old_val, item_id);
+                        qmap.check_prune(old_val);
+                        qmap.insert(new_pv, item_id);
+                    },
+                    None => {
+                        qmap.insert(new_pv, item_id);
+                        qmap.mark_present(item_id);
+                    },
+                }
+            }
+        }
+        for (attr, old_pv, new_pv) in rest {
+            if let Some(old_val) = old_pv {
+                self.remove_index(item_id, *attr as usize, old_val);
+            }
+            self.add_index(weak_self.clone(), item_id, *attr, new_pv);
+        }
+
+        let attrs: Vec<StrId> = changes.iter().map(|(attr, _, _)| *attr).collect();
+        self.changed(Some(&attrs), &Bitmap::of(&[item_id]));
+    }
+
+    pub fn open_deferred(&self) {
+        self.deferred.lock().unwrap().open();
+    }
+
+    // while bulk_update is open, changes to objects held are kept to be applied when it closes.
+    // returns whether they were
+    fn defer(&self, item_id: u32, changes: &[(StrId, Option<&PyValue>, &PyValue)]) -> bool {
+        let mut deferred = self.deferred.lock().unwrap();
+        if !deferred.is_open() {
+            return false;
+        }
+        let items = self.get_items_reader();
+        let Some(item) = items.get(item_id as usize).filter(|item| !item.is_placeholder()) else {
+            return false;
+        };
+        for (attr, old_pv, _) in changes {
+            deferred.record(item_id, item, *attr, *old_pv);
+        }
+        true
+    }
+
+    // closes a bulk_update block, the outermost one brings every object assigned to in it up to date,
+    // from the value indexed before the block to the one held now
+    pub fn close_deferred(&self, weak_self: Weak<IndexAPI>) {
+        let Some(pending) = self.deferred.lock().unwrap().close() else {
+            return;
+        };
+        self.apply_deferred(weak_self, pending);
+    }
+
+    fn apply_deferred(&self, weak_self: Weak<IndexAPI>, pending: Pending) {
+        for (item_id, (item, attrs)) in pending {
+            // dropped from the index in the block
+            let held = self.get_allowed_items_reader().contains(item_id)
+                && self.get_items_reader().get(item_id as usize)
+                    .is_some_and(|held| Arc::ptr_eq(held.get_owned_handle(), item.get_owned_handle()));
+            if !held {
+                continue;
+            }
+            let py_values = item.get_owned_handle().get_py_values();
+            let changes: Vec<(StrId, Option<&PyValue>, &PyValue)> = attrs.iter().filter_map(|(attr, old_pv)| {
+                let new_pv = py_values.get(attr)?;
+                (old_pv.as_ref() != Some(new_pv)).then_some((*attr, old_pv.as_ref(), new_pv))
+            }).collect();
+            if !changes.is_empty() {
+                self.update_values(weak_self.clone(), item_id, &changes);
+            }
+        }
+    }
+
+    // a value change reaching the log and standbys, without touching the query maps
+    pub fn log_set(&self, item_id: u32, attr: StrId, new_pv: &PyValue) {
+        if let Some(wal) = self.wal.lock().unwrap().as_mut() {
+            wal.log_set(item_id, attr, new_pv);
+        }
+        for replica in self.replicas.read().unwrap().iter() {
+            replica.push(Change::Set(item_id, attr, new_pv.clone()));
+        }
+    }
+
+    pub fn track_top_k(&self, attr: &str, k: usize) -> PyResult<()> {
+        if k == 0 {
+            return Err(PyValueError::new_err("k must be at least 1"));
+        }
+        let attr_id = INTERNER.intern(attr);
+        let index = self.get_index_reader();
+        let num = index.get(attr_id as usize).filter(|q| !q.is_placeholder()).map(|q| q.read_num_ordered());
+        let top = TopK::new(k, num.as_deref(), &self.get_allowed_items_reader());
+        drop(num);
+        drop(index);
+        self.top_k.write().unwrap().insert(attr_id, top);
+        self.tracking_top_k.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    // ids of the tracked top k of attr, largest first
+    pub fn top_k(&self, attr: &str) -> PyResult<Vec<u32>> {
+        self.top_k.read().unwrap()
+            .get(&INTERNER.intern(attr))
+            .map(TopK::ids)
+            .ok_or_else(|| PyValueError::new_err(format!("'{}' is not tracked, call track_top_k first", attr)))
+    }
+
+    pub fn alert_when(&self, mut alert: Alert) {
+        {
+            let index = self.get_index_reader();
+            let allowed = self.get_allowed_items_reader();
+            let members = match alert.filter() {
+                Some(filter) => evaluate_query(&index, &allowed, filter).and(&allowed),
+                None => allowed.clone(),
+            };
+            // the starting state never fires, only a later crossing does
+            alert.update(&self.get_items_reader(), &allowed, &members);
+        }
+        self.alerts.lock().unwrap().alerts.push(alert);
+    }
+
+    // brings alerts up to date with the changes since the last call and runs the callbacks of those
+    // that crossed their threshold, once the index locks are released
+    pub fn fire_alerts(&self, py: Python) -> PyResult<()> {
+        self.flush_replicas(py)?;
+        let fired: Vec<(Py<PyAny>, f64)> = {
+            let mut alerts = self.alerts.lock().unwrap();
+            if alerts.dirty.is_empty() {
+                return Ok(());
+            }
+            let dirty = std::mem::take(&mut alerts.dirty);
+            let index = self.get_index_reader();
+            let items = self.get_items_reader();
+            let scope = dirty.and(&self.get_allowed_items_reader());
+            alerts.alerts.iter_mut().filter_map(|alert| {
+                // equality lookups are not bounded by the scope they are given
+                let members = match alert.filter() {
+                    Some(filter) => evaluate_query(&index, &scope, filter).and(&scope),
+                    None => scope.clone(),
+                };
+                alert.update(&items, &dirty, &members).map(|value| (alert.callback(py), value))
+            }).collect()
+        };
+        // the change is already made, so a callback raising is reported as a warning rather than failing it
+        for (callback, value) in fired {
+            if let Err(err) = callback.call1(py, (value,)) {
+                let message = CString::new(format!("alert callback raised {}", err)).unwrap_or_default();
+                PyErr::warn(py, &py.get_type::<PyRuntimeWarning>(), &message, 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    // ids changed under every attribute, or only attr
+    // attrs None for objects added or removed, otherwise the attributes whose values changed
+    fn changed(&self, attrs: Option<&[StrId]>, ids: &Bitmap) {
+        self.metrics.record_writes(ids.cardinality());
+        if attrs.is_none() {
+            self.log_membership(ids);
+        }
+        {
+            let mut alerts = self.alerts.lock().unwrap();
+            if !alerts.alerts.is_empty() {
+                alerts.dirty.or_inplace(ids);
+            }
+        }
+        if !self.tracking_top_k.load(Ordering::Acquire) {
+            return;
+        }
+        let mut trackers = self.top_k.write().unwrap();
+        if trackers.is_empty() {
+            return;
+        }
+        let index = self.get_index_reader();
+        let allowed = self.get_allowed_items_reader();
+        for (attr_id, top) in trackers.iter_mut().filter(|(attr_id, _)| attrs.is_none_or(|attrs| attrs.contains(*attr_id))) {
+            let num = index.get(*attr_id as usize).filter(|q| !q.is_placeholder()).map(|q| q.read_num_ordered());
+            top.refresh(num.as_deref(), &allowed, ids);
+        }
+    }
+
+    // ids added or removed, told apart by whether they are still held
+    fn log_membership(&self, ids: &Bitmap) {
+        let mut wal = self.wal.lock().unwrap();
+        let replicas = self.replicas.read().unwrap();
+        if wal.is_none() && replicas.is_empty() {
+            return;
+        }
+        let members: Vec<(u32, Option<StoredItem>)> = {
+            let allowed = self.get_allowed_items_reader();
+            let items = self.get_items_reader();
+            // adds in the order they were taken in, ids are recycled so their order says nothing
+            let held = ids.and(&allowed);
+            let mut added = self.sequence.read().unwrap().in_order(&held);
+            let stamped: Bitmap = added.iter().copied().collect();
+            added.extend(held.andnot(&stamped).iter());
+            added.into_iter().map(|id| (id, Some(items[id as usize].clone())))
+                .chain(ids.andnot(&allowed).iter().map(|id| (id, None)))
+                .collect()
+        };
+        for (id, item) in members {
+            if let Some(wal) = wal.as_mut() {
+                match &item {
+                    Some(item) => wal.log_add(item),
+                    None => wal.log_remove(id),
+                }
+            }
+            for replica in replicas.iter() {
+                replica.push(match &item {
+                    Some(item) => Change::Add(item.clone()),
+                    None => Change::Remove(id),
+                });
+            }
         }
-        self.add_index(weak_self, item_id, attr, &new_pv);
+    }
+
+    pub fn replicate_to(&self, py: Python, target: &Arc<IndexAPI>) -> PyResult<()> {
+        if std::ptr::eq(self, &**target) || target.replicates_to(self) {
+            return Err(PyValueError::new_err("replicating to other would loop back into this index"));
+        }
+        if self.replicas.read().unwrap().iter().any(|r| std::ptr::eq(r.target.as_ptr(), &**target)) {
+            return Err(PyValueError::new_err("other is already a standby of this index"));
+        }
+        let replica = Arc::new(Replica::new(Arc::downgrade(target)));
+        {
+            // writes wait here, so every change after the objects are read is queued behind them
+            let mut replicas = self.replicas.write().unwrap();
+            self.numbering.store(true, Ordering::Release);
+            replica.extend(self.ordered_items().into_iter().map(Change::Add));
+            replicas.push(replica.clone());
+        }
+        replica.flush(py)
+    }
+
+    fn replicates_to(&self, target: &IndexAPI) -> bool {
+        self.replicas.read().unwrap().iter()
+            .filter_map(|r| r.target.upgrade())
+            .any(|t| std::ptr::eq(&*t, target) || t.replicates_to(target))
+    }
+
+    // applies the changes queued for every standby, forgetting those dropped
+    fn flush_replicas(&self, py: Python) -> PyResult<()> {
+        let replicas = self.replicas.read().unwrap().clone();
+        if replicas.iter().any(|r| r.target.strong_count() == 0) {
+            self.replicas.write().unwrap().retain(|r| r.target.strong_count() > 0);
+        }
+        for replica in replicas {
+            replica.flush(py)?;
+        }
+        Ok(())
+    }
+
+    // removes ids from the index
+    pub fn discard(&self, ids: &Bitmap) {
+        let keep = self.get_allowed_items_reader().andnot(ids);
+        self.keep_only_from_bitmap(&keep);
+    }
+
+    // starts logging every change to path after a snapshot of the current objects, in collect order
+    pub fn enable_wal(&self, path: &std::path::Path) -> std::io::Result<()> {
+        let mut wal = self.wal.lock().unwrap();
+        self.numbering.store(true, Ordering::Release);
+        *wal = Some(Wal::create(path, &self.config, &self.get_decls_reader(), &self.ordered_items())?);
+        Ok(())
+    }
+
+    // every object held, in collect order
+    pub fn ordered_items(&self) -> Vec<StoredItem> {
+        let ids = self.filter_from_bitmap(self.ids()).ordered_ids();
+        let items = self.get_items_reader();
+        ids.iter().map(|id| items[*id as usize].clone()).collect()
+    }
+
+    fn is_element_indexed(value: &PyValue) -> bool {
+        matches!(value.get_primitive(), RustCastValue::Iterable(itr) if !matches!(itr, PyIterable::Dict(_)))
     }
 
     pub fn get_from_indexes(&self, py: Python, indexes: Bitmap) -> PyResult<Vec<Py<Indexable>>>{
         let items_read = self.get_items_reader();
         let results: Vec<Py<Indexable>> = indexes.iter()
-            .map(|arc| items_read.get(arc as usize).unwrap().get_py_ref(py))
+            .filter_map(|arc| items_read.get(arc as usize).unwrap().get_py_ref(py))
             .collect();
 
         Ok(results)
@@ -322,19 +1288,21 @@ impl IndexAPI{
         attr_id: StrId,
         value: &PyValue
     ){
-        if let Some(qmap) = self.get_index_reader().get(attr_id as usize) {
-            qmap.insert(value, obj_id);
+        if !self.get_decls_reader().is_indexed(attr_id) {
             return;
         }
 
-        let qmap = QueryMap::new(weak_self);
-        qmap.insert(value, obj_id);
-        let mut writer = self.get_index_writer();
-
-        if attr_id >= writer.len() as u32 {
-            writer.resize_with((attr_id + 1) as usize, Default::default); // or None if Option
-        }
-        writer[attr_id as usize] = qmap;
+        with_slot(
+            || self.get_index_reader(),
+            || self.get_index_writer(),
+            attr_id as usize,
+            QueryMap::is_placeholder,
+            || QueryMap::new(weak_self, self.get_decls_reader().for_child(attr_id)),
+            |qmap| {
+                qmap.insert(value, obj_id);
+                qmap.mark_present(obj_id);
+            },
+        );
     }
 
     fn remove_index(
@@ -343,25 +1311,106 @@ impl IndexAPI{
         attr_id: usize,
         py_value: &PyValue
     ){
+        if !self.get_decls_reader().is_indexed(attr_id as StrId) {
+            return;
+        }
+        if let Some(val) = self.get_index_reader().get(attr_id) {
+            val.remove_id(py_value, idx);
+            val.get_present_writer().remove(idx);
+            val.check_prune(py_value);
+        }
+        reset_if_empty(
+            || self.get_index_reader(),
+            || self.get_index_writer(),
+            attr_id,
+            |qmap: &QueryMap| !qmap.is_placeholder() && qmap.is_empty(),
+        );
+    }
+
+    pub fn attributes(&self, py: Python) -> Vec<AttributeStats> {
         let index = self.get_index_reader();
-        if index.len() > attr_id {
-            if let Some(val) = index.get(attr_id) { 
-                val.remove_id(py_value, idx);
-                val.check_prune(py_value);
-            };
+        let items = self.get_items_reader();
+        let allowed = self.get_allowed_items_reader();
+        let decls = self.get_decls_reader();
+        attribute_stats(py, &index, &items, &allowed)
+            .into_iter()
+            .filter(|stats| decls.is_indexed(INTERNER.intern(&stats.name)))
+            .collect()
+    }
 
-            if index[attr_id].is_empty(){
-                drop(index);
-                self.get_index_writer()[attr_id] = Default::default();
-            }
+    pub fn sparsity(&self) -> BTreeMap<String, f64> {
+        sparsity(&self.get_index_reader(), &self.get_allowed_items_reader())
+    }
+
+    pub fn infer_schema<'py>(&self, py: Python<'py>, categorical_limit: usize) -> PyResult<Bound<'py, PyDict>> {
+        let index = self.get_index_reader();
+        let items = self.get_items_reader();
+        let allowed = self.get_allowed_items_reader();
+        infer_schema(py, &index, &items, &allowed, categorical_limit)
+    }
+
+    pub fn column<'py>(&self, py: Python<'py>, attr: &str, dtype: ColumnType, fill: Option<f64>) -> PyResult<Bound<'py, PyAny>> {
+        let column = {
+            let items = self.get_items_reader();
+            let allowed = self.get_allowed_items_reader();
+            numeric_column(&items, &allowed, INTERNER.intern(attr))
+        };
+        column_to_array(py, &column, dtype, fill)
+    }
+
+    pub fn group_by<'py>(&self, py: Python<'py>, attrs: &[AttrPath], keys: GroupKeys) -> PyResult<Bound<'py, PyDict>> {
+        groups_to_dict(py, self.groups(attrs), keys, |ids| self.filter_from_bitmap(ids))
+    }
+
+    pub fn group_by_count<'py>(&self, py: Python<'py>, attrs: &[AttrPath], keys: GroupKeys) -> PyResult<Bound<'py, PyDict>> {
+        groups_to_dict(py, self.groups(attrs), keys, |ids| ids.cardinality())
+    }
+
+    fn groups(&self, attrs: &[AttrPath]) -> Vec<Group> {
+        let index = self.get_index_reader();
+        let items = self.get_items_reader();
+        let allowed = self.get_allowed_items_reader();
+        group_by(&index, &items, &allowed, attrs)
+    }
+
+    pub fn group_agg<'py>(&self, py: Python<'py>, names: &[String], metrics: &[Metric], having: Option<&QueryExpr>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        group_rows(py, &self.index, &self.items, &self.ids(), names, metrics, having)
+    }
+
+    pub fn explain(&self, query: &QueryExpr) -> QueryTrace {
+        let index = self.get_index_reader();
+        let allowed = self.get_allowed_items_reader();
+        trace_query(&index, &allowed, &query.normalized()).1
+    }
+
+    pub fn contains_id(&self, obj_id: u32) -> bool {
+        self.get_allowed_items_reader().contains(obj_id)
+    }
+
+    // leaf clauses of query that obj_id does not satisfy
+    pub fn why_not(&self, obj_id: u32, query: &QueryExpr) -> PyResult<Vec<QueryExpr>> {
+        if !self.contains_id(obj_id) {
+            return Err(PyValueError::new_err("object is not in the index"));
         }
+        let index = self.get_index_reader();
+        Ok(failing_clauses(&index, obj_id, query).into_iter().cloned().collect())
+    }
+
+    pub fn ids(&self) -> Bitmap {
+        self.get_allowed_items_reader().clone()
+    }
+
+    pub fn reduced_ids(&self, ids: &Bitmap) -> FilteredIndex {
+        self.filter_from_bitmap(ids.and(&self.get_allowed_items_reader()))
     }
 
     pub fn filter_from_bitmap(&self, bm: Bitmap) -> FilteredIndex {
         FilteredIndex {
             index: self.index.clone(),
             items: self.items.clone(),
-            allowed_items: bm
+            allowed_items: bm,
+            applied: None,
+            order: self.ordering(),
         }
     }
 
@@ -375,35 +1424,43 @@ impl IndexAPI{
     }
 
     fn get_items_writer(&self) -> RwLockWriteGuard<'_, Vec<StoredItem>> {
-        self.items.write().unwrap()
+        self.metrics.write(&self.items)
         //self.items.try_write().expect("items writer deadlock")
     }
 
-    fn get_items_reader(&self) -> RwLockReadGuard<'_, Vec<StoredItem>> {
-        self.items.read().unwrap()
+    pub fn get_items_reader(&self) -> FreezeReadGuard<'_, Vec<StoredItem>> {
+        self.metrics.read(&self.items)
         //self.items.try_read().expect("cannot read from items")
     }
 
     pub fn get_index_writer(&self) -> RwLockWriteGuard<'_, Vec<QueryMap>> {
-        self.index.write().unwrap()
+        self.metrics.write(&self.index)
         //self.index.try_write().expect("index writer deadlock")
     }
 
-    pub fn get_index_reader(&self) -> RwLockReadGuard<'_, Vec<QueryMap>> {
-        self.index.read().unwrap()
+    pub fn get_index_reader(&self) -> FreezeReadGuard<'_, Vec<QueryMap>> {
+        self.metrics.read(&self.index)
         //self.index.try_read().expect("cannot read from index")
     }
 
     fn get_allowed_items_writer(&self) -> RwLockWriteGuard<'_, Bitmap> {
-        self.allowed_items.write().unwrap()
+        self.metrics.write(&self.allowed_items)
         //self.allowed_items.try_write().expect("index writer deadlock")
     }
 
-    fn get_allowed_items_reader(&self) -> RwLockReadGuard<'_, Bitmap> {
-        self.allowed_items.read().unwrap()
+    pub fn get_allowed_items_reader(&self) -> FreezeReadGuard<'_, Bitmap> {
+        self.metrics.read(&self.allowed_items)
         //self.allowed_items.try_read().expect("cannot read from index")
     }
 
+    pub fn get_decls_reader(&self) -> RwLockReadGuard<'_, AttrDecls> {
+        self.decls.read().unwrap()
+    }
+
+    fn get_decls_writer(&self) -> RwLockWriteGuard<'_, AttrDecls> {
+        self.decls.write().unwrap()
+    }
+
     fn get_parent_child_map_reader(&self) -> RwLockReadGuard<'_, M2MU32> {
         self.parent_child_map.read().unwrap()
         //self.parent_child_map.try_read().expect("cannot read from index")
@@ -0,0 +1,264 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use croaring::Bitmap;
+use pyo3::{Bound, IntoPyObject, PyAny, PyResult, Python, types::{PyAnyMethods, PyDict, PyDictMethods, PyTypeMethods}};
+
+use crate::index::{core::{query::QueryMap, stored_item::StoredItem, structures::string_interner::{INTERNER, StrValue}}, types::StrId, value::{PyIterable, PyValue, RustCastValue}};
+
+#[derive(Debug, Default)]
+pub struct AttributeStats {
+    pub name: String,
+    pub types: BTreeSet<String>,
+    pub count: u64,
+    pub structures: BTreeSet<&'static str>,
+    pub nested: bool,
+}
+
+impl AttributeStats {
+    pub fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("name", &self.name)?;
+        dict.set_item("types", self.types.iter().collect::<Vec<_>>())?;
+        dict.set_item("count", self.count)?;
+        dict.set_item("structures", self.structures.iter().collect::<Vec<_>>())?;
+        dict.set_item("nested", self.nested)?;
+        Ok(dict)
+    }
+}
+
+pub fn type_name(py: Python, value: &PyValue) -> String {
+    match value.get_primitive() {
+        RustCastValue::Int(_) => "int".to_string(),
+        RustCastValue::Float(_) => "float".to_string(),
+        RustCastValue::Str(_) => "str".to_string(),
+        RustCastValue::Bool(_) => "bool".to_string(),
+        _ => value.get_obj(py).bind(py).get_type().name()
+            .map(|name| name.to_string())
+            .unwrap_or_else(|_| "object".to_string()),
+    }
+}
+
+fn structure_for(value: &PyValue) -> Option<&'static str> {
+    match value.get_primitive() {
+        RustCastValue::Int(_) | RustCastValue::Float(_) | RustCastValue::Encoded(..) | RustCastValue::Wide(_) => Some("numeric"),
+        RustCastValue::Str(_) => Some("string"),
+        RustCastValue::Bool(_) => Some("boolean"),
+        RustCastValue::Ind(_) | RustCastValue::Bytes(_) | RustCastValue::Uuid(_) | RustCastValue::Unknown => Some("exact"),
+        RustCastValue::None => Some("null"),
+        RustCastValue::Iterable(_) => None,
+    }
+}
+
+fn record_structures(py: Python, stats: &mut AttributeStats, value: &PyValue) {
+    if let Some(structure) = structure_for(value) {
+        stats.structures.insert(structure);
+        return;
+    }
+    // iterables index each element through the element's own structure
+    let elements: Vec<PyValue> = match value.get_primitive() {
+        RustCastValue::Iterable(PyIterable::List(list)) => list.bind(py).try_iter()
+            .map(|it| it.flatten().map(PyValue::new).collect())
+            .unwrap_or_default(),
+        RustCastValue::Iterable(PyIterable::Tuple(tuple)) => tuple.bind(py).try_iter()
+            .map(|it| it.flatten().map(PyValue::new).collect())
+            .unwrap_or_default(),
+        RustCastValue::Iterable(PyIterable::Set(set)) => set.bind(py).try_iter()
+            .map(|it| it.flatten().map(PyValue::new).collect())
+            .unwrap_or_default(),
+        _ => vec![],
+    };
+    for element in elements.iter() {
+        if let Some(structure) = structure_for(element) {
+            stats.structures.insert(structure);
+        }
+    }
+}
+
+// per attribute metadata over the allowed items, only for attributes that have a query map
+pub fn attribute_stats(
+    py: Python,
+    index: &[QueryMap],
+    items: &[StoredItem],
+    allowed: &Bitmap,
+) -> Vec<AttributeStats> {
+    let mut stats: BTreeMap<StrId, AttributeStats> = BTreeMap::new();
+
+    for idx in allowed.iter() {
+        let Some(item) = items.get(idx as usize) else {
+            continue;
+        };
+        for (attr_id, value) in item.get_owned_handle().get_py_values().iter() {
+            let Some(qmap) = index.get(*attr_id as usize) else {
+                continue;
+            };
+            let entry = stats.entry(*attr_id).or_insert_with(|| AttributeStats {
+                name: INTERNER.resolve(*attr_id),
+                nested: !qmap.nested.allowed_items.read().unwrap().is_empty(),
+                ..Default::default()
+            });
+            entry.count += 1;
+            entry.types.insert(type_name(py, value));
+            record_structures(py, entry, value);
+        }
+    }
+
+    let mut res: Vec<AttributeStats> = stats.into_values().collect();
+    res.sort_by(|a, b| a.name.cmp(&b.name));
+    res
+}
+
+// fraction of allowed items holding each attribute, by name, read from each query map's presence bitmap
+pub fn sparsity(index: &[QueryMap], allowed: &Bitmap) -> BTreeMap<String, f64> {
+    let total = allowed.cardinality() as f64;
+    index.iter()
+        .enumerate()
+        .filter(|(_, qmap)| !qmap.is_placeholder())
+        .filter_map(|(attr_id, qmap)| {
+            let held = qmap.get_present_reader().and_cardinality(allowed);
+            (held > 0).then(|| (INTERNER.resolve(attr_id as StrId), held as f64 / total))
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct FieldSummary {
+    count: u64,
+    types: BTreeSet<&'static str>,
+    element_types: BTreeSet<&'static str>,
+    has_nested: bool,
+    // distinct strings, abandoned once past the categorical limit
+    distinct: Option<BTreeSet<StrValue>>,
+}
+
+fn json_type(value: &PyValue) -> &'static str {
+    match value.get_primitive() {
+        RustCastValue::Int(_) => "integer",
+        RustCastValue::Float(_) | RustCastValue::Wide(_) => "number",
+        RustCastValue::Str(_) | RustCastValue::Bytes(_) | RustCastValue::Uuid(_) => "string",
+        RustCastValue::Bool(_) => "boolean",
+        RustCastValue::Ind(_) => "object",
+        RustCastValue::Iterable(PyIterable::Dict(_)) => "object",
+        RustCastValue::Iterable(_) => "array",
+        RustCastValue::Encoded(..) => "unknown",
+        RustCastValue::None => "null",
+        RustCastValue::Unknown => "unknown",
+    }
+}
+
+fn iterable_elements(py: Python, value: &PyValue) -> Vec<PyValue> {
+    let iter = match value.get_primitive() {
+        RustCastValue::Iterable(PyIterable::List(list)) => list.bind(py).try_iter(),
+        RustCastValue::Iterable(PyIterable::Tuple(tuple)) => tuple.bind(py).try_iter(),
+        RustCastValue::Iterable(PyIterable::Set(set)) => set.bind(py).try_iter(),
+        _ => return vec![],
+    };
+    iter.map(|it| it.flatten().map(PyValue::new).collect()).unwrap_or_default()
+}
+
+fn type_entry<'py>(py: Python<'py>, types: &BTreeSet<&'static str>) -> PyResult<Bound<'py, PyAny>> {
+    // json schema has no "unknown", those fields are left unconstrained
+    let types: Vec<&str> = types.iter().copied().filter(|t| *t != "unknown").collect();
+    if types.len() == 1 {
+        Ok(types[0].into_pyobject(py)?.into_any())
+    } else {
+        Ok(types.into_pyobject(py)?.into_any())
+    }
+}
+
+// json-schema like description of the allowed items, recursing into nested indexes
+pub fn infer_schema<'py>(
+    py: Python<'py>,
+    index: &[QueryMap],
+    items: &[StoredItem],
+    allowed: &Bitmap,
+    categorical_limit: usize,
+) -> PyResult<Bound<'py, PyDict>> {
+    let mut fields: BTreeMap<StrId, FieldSummary> = BTreeMap::new();
+    let total = allowed.cardinality();
+
+    for idx in allowed.iter() {
+        let Some(item) = items.get(idx as usize) else {
+            continue;
+        };
+        for (attr_id, value) in item.get_owned_handle().get_py_values().iter() {
+            if index.get(*attr_id as usize).is_none() {
+                continue;
+            }
+            let field = fields.entry(*attr_id).or_insert_with(|| FieldSummary {
+                distinct: Some(BTreeSet::new()),
+                ..Default::default()
+            });
+            field.count += 1;
+            field.types.insert(json_type(value));
+            match value.get_primitive() {
+                RustCastValue::Ind(_) => field.has_nested = true,
+                RustCastValue::Iterable(_) => {
+                    for element in iterable_elements(py, value).iter() {
+                        field.element_types.insert(json_type(element));
+                        if matches!(element.get_primitive(), RustCastValue::Ind(_)) {
+                            field.has_nested = true;
+                        }
+                    }
+                },
+                _ => {},
+            }
+            if let Some(distinct) = field.distinct.as_mut() {
+                match value.get_primitive() {
+                    RustCastValue::Str(s) => {
+                        distinct.insert(s.clone());
+                        if distinct.len() > categorical_limit {
+                            field.distinct = None;
+                        }
+                    },
+                    _ => field.distinct = None,
+                }
+            }
+        }
+    }
+
+    let properties = PyDict::new(py);
+    let mut required: Vec<String> = vec![];
+    for (attr_id, field) in fields.iter() {
+        let name = INTERNER.resolve(*attr_id);
+        let prop = PyDict::new(py);
+        prop.set_item("type", type_entry(py, &field.types)?)?;
+
+        if field.has_nested {
+            let nested = &index[*attr_id as usize].nested;
+            let nested_schema = infer_schema(
+                py,
+                &nested.index.read().unwrap(),
+                &nested.items.read().unwrap(),
+                &nested.allowed_items.read().unwrap(),
+                categorical_limit,
+            )?;
+            if field.types.contains("array") {
+                prop.set_item("items", nested_schema)?;
+            } else {
+                prop.set_item("properties", nested_schema.get_item("properties")?)?;
+            }
+        } else if !field.element_types.is_empty() {
+            let items_schema = PyDict::new(py);
+            items_schema.set_item("type", type_entry(py, &field.element_types)?)?;
+            prop.set_item("items", items_schema)?;
+        }
+
+        // a handful of repeated strings is likely an enum
+        if let Some(distinct) = &field.distinct {
+            if !distinct.is_empty() && (distinct.len() as u64) < field.count {
+                prop.set_item("enum", distinct.iter().map(|s| s.as_str()).collect::<Vec<_>>())?;
+            }
+        }
+
+        if field.count == total && !field.types.contains("null") {
+            required.push(name.clone());
+        }
+        properties.set_item(name, prop)?;
+    }
+
+    let schema = PyDict::new(py);
+    schema.set_item("type", "object")?;
+    schema.set_item("properties", properties)?;
+    schema.set_item("required", required)?;
+    Ok(schema)
+}
@@ -0,0 +1,152 @@
+use std::ops::{Deref, DerefMut};
+
+// the lock protocols index.rs relies on, taking their locks through the closures given so the loom
+// models below can run them on loom's locks. run those with
+// RUSTFLAGS="--cfg loom" cargo test --release --lib lock_order
+
+// both locks taken in address order, so two callers over the same pair, in either order, run one after
+// the other instead of each holding the lock the other waits on. guards come back in argument order
+pub fn lock_pair<'a, L, G>(a: &'a L, b: &'a L, lock: impl Fn(&'a L) -> G) -> (G, G) {
+    if std::ptr::from_ref(a) < std::ptr::from_ref(b) {
+        let first = lock(a);
+        (first, lock(b))
+    } else {
+        let first = lock(b);
+        (lock(a), first)
+    }
+}
+
+// runs f on slot i of the vec behind read / write. a slot still unset under the read lock is filled with make()
+// under the write lock, unless another caller filled it since the read lock was dropped
+pub fn with_slot<T: Default, R, RG, WG>(
+    read: impl FnOnce() -> RG,
+    write: impl FnOnce() -> WG,
+    i: usize,
+    unset: impl Fn(&T) -> bool,
+    make: impl FnOnce() -> T,
+    f: impl Fn(&T) -> R,
+) -> R
+where
+    RG: Deref<Target = Vec<T>>,
+    WG: DerefMut<Target = Vec<T>>,
+{
+    {
+        let slots = read();
+        if let Some(slot) = slots.get(i).filter(|slot| !unset(slot)) {
+            return f(slot);
+        }
+    }
+    let made = make();
+    let mut slots = write();
+    if i >= slots.len() {
+        slots.resize_with(i + 1, Default::default);
+    }
+    if unset(&slots[i]) {
+        slots[i] = made;
+    }
+    f(&slots[i])
+}
+
+// sets slot i back to its default once empty. another caller may have filled it since the read lock
+// was dropped, so it is checked again under the write lock
+pub fn reset_if_empty<T: Default, RG, WG>(read: impl FnOnce() -> RG, write: impl FnOnce() -> WG, i: usize, empty: impl Fn(&T) -> bool)
+where
+    RG: Deref<Target = Vec<T>>,
+    WG: DerefMut<Target = Vec<T>>,
+{
+    if !read().get(i).is_some_and(&empty) {
+        return;
+    }
+    let mut slots = write();
+    if slots.get(i).is_some_and(&empty) {
+        slots[i] = T::default();
+    }
+}
+
+#[cfg(all(test, loom))]
+mod tests {
+    use super::*;
+    use loom::sync::{Arc, Mutex, RwLock};
+    use loom::thread;
+
+    // a query map standing in for the real one, unset until made
+    type Slot = Option<Mutex<Vec<u32>>>;
+
+    fn add(slots: &RwLock<Vec<Slot>>, i: usize, id: u32) {
+        with_slot(
+            || slots.read().unwrap(),
+            || slots.write().unwrap(),
+            i,
+            Option::is_none,
+            || Some(Mutex::new(Vec::new())),
+            |slot| slot.as_ref().unwrap().lock().unwrap().push(id),
+        );
+    }
+
+    fn remove(slots: &RwLock<Vec<Slot>>, i: usize, id: u32) {
+        if let Some(Some(slot)) = slots.read().unwrap().get(i) {
+            slot.lock().unwrap().retain(|held| *held != id);
+        }
+        reset_if_empty(|| slots.read().unwrap(), || slots.write().unwrap(), i, |slot: &Slot| {
+            slot.as_ref().is_some_and(|slot| slot.lock().unwrap().is_empty())
+        });
+    }
+
+    fn held(slots: &RwLock<Vec<Slot>>, i: usize) -> Vec<u32> {
+        let slots = slots.read().unwrap();
+        let mut held = slots[i].as_ref().map(|slot| slot.lock().unwrap().clone()).unwrap_or_default();
+        held.sort();
+        held
+    }
+
+    // one caller passes the pair the other way round, both still finish
+    #[test]
+    fn lock_pair_either_order() {
+        loom::model(|| {
+            let (a, b) = (Arc::new(Mutex::new(0)), Arc::new(Mutex::new(0)));
+            let handle = {
+                let (a, b) = (a.clone(), b.clone());
+                thread::spawn(move || {
+                    let (mut a, _b) = lock_pair(&*a, &*b, |lock| lock.lock().unwrap());
+                    *a += 1;
+                })
+            };
+            {
+                let (mut b, _a) = lock_pair(&*b, &*a, |lock| lock.lock().unwrap());
+                *b += 1;
+            }
+            handle.join().unwrap();
+            assert_eq!((*a.lock().unwrap(), *b.lock().unwrap()), (1, 1));
+        });
+    }
+
+    // two adds to an unset slot, neither map replaces the other's
+    #[test]
+    fn slot_made_once() {
+        loom::model(|| {
+            let slots = Arc::new(RwLock::new(Vec::new()));
+            let handle = {
+                let slots = slots.clone();
+                thread::spawn(move || add(&slots, 0, 1))
+            };
+            add(&slots, 0, 2);
+            handle.join().unwrap();
+            assert_eq!(held(&slots, 0), [1, 2]);
+        });
+    }
+
+    // the last holder leaves while another is added, the emptied map is not reset over the add
+    #[test]
+    fn reset_keeps_late_add() {
+        loom::model(|| {
+            let slots = Arc::new(RwLock::new(vec![Some(Mutex::new(vec![1]))]));
+            let handle = {
+                let slots = slots.clone();
+                thread::spawn(move || remove(&slots, 0, 1))
+            };
+            add(&slots, 0, 2);
+            handle.join().unwrap();
+            assert_eq!(held(&slots, 0), [2]);
+        });
+    }
+}
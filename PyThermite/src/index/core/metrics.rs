@@ -0,0 +1,137 @@
+use std::{collections::VecDeque, sync::{Mutex, RwLockWriteGuard, atomic::{AtomicU64, Ordering}}, time::Duration};
+
+use pyo3::{Bound, PyResult, Python, types::{PyDict, PyDictMethods}};
+
+use crate::index::core::query::explain::QueryTrace;
+use crate::index::core::structures::freeze_lock::{FreezeLock, FreezeReadGuard};
+
+pub const DEFAULT_SLOW_LOG_CAPACITY: usize = 100;
+
+pub struct SlowQuery {
+    pub query: String,
+    pub elapsed: Duration,
+    pub matched: u64,
+    // the query run again traced, None for batches
+    pub profile: Option<QueryTrace>,
+}
+
+impl SlowQuery {
+    pub fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("query", &self.query)?;
+        dict.set_item("time_ns", self.elapsed.as_nanos() as u64)?;
+        dict.set_item("matched", self.matched)?;
+        match &self.profile {
+            Some(profile) => dict.set_item("profile", profile.to_dict(py)?)?,
+            None => dict.set_item("profile", py.None())?,
+        }
+        Ok(dict)
+    }
+}
+
+struct SlowLog {
+    capacity: usize,
+    // oldest first, the oldest dropped once full
+    entries: VecDeque<SlowQuery>,
+}
+
+impl Default for SlowLog {
+    fn default() -> Self {
+        Self { capacity: DEFAULT_SLOW_LOG_CAPACITY, entries: VecDeque::new() }
+    }
+}
+
+// slow query threshold in nanoseconds while the log is off
+const SLOW_LOG_OFF: u64 = u64::MAX;
+
+// counters for queries run, objects written and lock waits, plus the slow query log
+pub struct Metrics {
+    reads: AtomicU64,
+    writes: AtomicU64,
+    contended: AtomicU64,
+    // read on every query, so kept outside the log's lock
+    slow_threshold_ns: AtomicU64,
+    slow: Mutex<SlowLog>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            reads: AtomicU64::default(),
+            writes: AtomicU64::default(),
+            contended: AtomicU64::default(),
+            slow_threshold_ns: AtomicU64::new(SLOW_LOG_OFF),
+            slow: Mutex::default(),
+        }
+    }
+}
+
+impl Metrics {
+    pub fn record_reads(&self, n: u64) {
+        self.reads.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn record_writes(&self, n: u64) {
+        self.writes.fetch_add(n, Ordering::Relaxed);
+    }
+
+    // a lock already held elsewhere counts as contended before waiting on it
+    pub fn read<'a, T>(&self, lock: &'a FreezeLock<T>) -> FreezeReadGuard<'a, T> {
+        if let Ok(guard) = lock.try_read() {
+            return guard;
+        }
+        self.contended.fetch_add(1, Ordering::Relaxed);
+        lock.read().unwrap()
+    }
+
+    pub fn write<'a, T>(&self, lock: &'a FreezeLock<T>) -> RwLockWriteGuard<'a, T> {
+        if let Ok(guard) = lock.try_write() {
+            return guard;
+        }
+        self.contended.fetch_add(1, Ordering::Relaxed);
+        lock.write().unwrap()
+    }
+
+    pub fn set_slow_log(&self, threshold: Option<Duration>, capacity: usize) {
+        let mut slow = self.slow.lock().unwrap();
+        let threshold_ns = threshold.map_or(SLOW_LOG_OFF, |threshold| (threshold.as_nanos() as u64).min(SLOW_LOG_OFF - 1));
+        self.slow_threshold_ns.store(threshold_ns, Ordering::Relaxed);
+        slow.capacity = capacity;
+        let excess = slow.entries.len().saturating_sub(capacity);
+        slow.entries.drain(..excess);
+    }
+
+    pub fn is_slow(&self, elapsed: Duration) -> bool {
+        let threshold_ns = self.slow_threshold_ns.load(Ordering::Relaxed);
+        threshold_ns != SLOW_LOG_OFF && elapsed.as_nanos() >= threshold_ns as u128
+    }
+
+    pub fn log_slow(&self, query: SlowQuery) {
+        let mut slow = self.slow.lock().unwrap();
+        if slow.capacity == 0 {
+            return;
+        }
+        if slow.entries.len() == slow.capacity {
+            slow.entries.pop_front();
+        }
+        slow.entries.push_back(query);
+    }
+
+    pub fn slow_queries<'py>(&self, py: Python<'py>, clear: bool) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        let mut slow = self.slow.lock().unwrap();
+        let entries = slow.entries.iter().map(|q| q.to_dict(py)).collect::<PyResult<Vec<_>>>()?;
+        if clear {
+            slow.entries.clear();
+        }
+        Ok(entries)
+    }
+
+    pub fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("reads", self.reads.load(Ordering::Relaxed))?;
+        dict.set_item("writes", self.writes.load(Ordering::Relaxed))?;
+        dict.set_item("lock_contention", self.contended.load(Ordering::Relaxed))?;
+        dict.set_item("slow_queries", self.slow.lock().unwrap().entries.len())?;
+        Ok(dict)
+    }
+}
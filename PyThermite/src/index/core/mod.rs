@@ -3,4 +3,39 @@ pub mod query;
 pub mod filtered_index;
 pub mod stored_item;
 pub mod structures;
-pub mod id_alloc;
\ No newline at end of file
+pub mod id_alloc;
+pub mod attr_decls;
+pub mod attr_decl;
+pub mod introspect;
+pub mod config;
+pub mod column;
+pub mod encoders;
+pub mod semver;
+pub mod coverage;
+pub mod strict;
+pub mod group_by;
+pub mod group_agg;
+pub mod sample;
+pub mod outliers;
+pub mod nearest;
+pub mod traverse;
+pub mod top_k;
+pub mod alerts;
+pub mod handles;
+pub mod tolerance;
+pub mod units;
+pub mod fixed_point;
+pub mod stats;
+pub mod sequence;
+pub mod order_by;
+pub mod churn;
+pub mod arrow;
+pub mod metrics;
+pub mod snapshot;
+pub mod wal;
+pub mod replica;
+pub mod parquet;
+pub mod class_fields;
+pub mod deferred;
+pub mod lock_order;
+pub mod mutation_gate;
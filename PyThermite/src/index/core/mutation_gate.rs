@@ -0,0 +1,109 @@
+use std::{cell::RefCell, marker::PhantomData, sync::{Arc, Condvar, Mutex}};
+
+use smallvec::SmallVec;
+
+thread_local! {
+    // gates entered by this thread and not yet left, freeze can't wait on a mutation of its own thread
+    static HELD_HERE: RefCell<Vec<*const MutationGate>> = const { RefCell::new(Vec::new()) };
+}
+
+// counts the mutations under way on an index, so freeze can wait them out before taking its snapshot.
+// a mutation enters before it reads frozen and freeze waits after setting it, both under the lock,
+// so either the mutation sees frozen or freeze sees the mutation
+#[derive(Debug, Default)]
+pub struct MutationGate {
+    running: Mutex<usize>,
+    idle: Condvar,
+}
+
+// held from check_mutable until the mutation is done, on the index and each one it is nested in.
+// left on the thread that entered it
+#[derive(Default)]
+pub struct Mutating {
+    gates: SmallVec<[Arc<MutationGate>; 2]>,
+    _thread: PhantomData<*const ()>,
+}
+
+impl MutationGate {
+    pub fn enter(self: &Arc<Self>, held: &mut Mutating) {
+        *self.running.lock().unwrap() += 1;
+        HELD_HERE.with(|here| here.borrow_mut().push(Arc::as_ptr(self)));
+        held.gates.push(self.clone());
+    }
+
+    // whether this thread is inside a mutation behind this gate
+    pub fn held_here(self: &Arc<Self>) -> bool {
+        HELD_HERE.with(|here| here.borrow().contains(&Arc::as_ptr(self)))
+    }
+
+    pub fn wait_idle(&self) {
+        let running = self.running.lock().unwrap();
+        drop(self.idle.wait_while(running, |running| *running > 0).unwrap());
+    }
+
+    fn leave(self: &Arc<Self>) {
+        HELD_HERE.with(|here| {
+            let mut here = here.borrow_mut();
+            if let Some(pos) = here.iter().rposition(|gate| *gate == Arc::as_ptr(self)) {
+                here.remove(pos);
+            }
+        });
+        let mut running = self.running.lock().unwrap();
+        *running -= 1;
+        if *running == 0 {
+            self.idle.notify_all();
+        }
+    }
+}
+
+impl Drop for Mutating {
+    fn drop(&mut self) {
+        for gate in self.gates.drain(..) {
+            gate.leave();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::{sync::mpsc, thread, time::Duration};
+
+    #[test]
+    fn wait_idle_waits_for_mutations_under_way() {
+        let gate = Arc::new(MutationGate::default());
+        let done = Arc::new(AtomicBool::new(false));
+        let (entered, on_entered) = mpsc::channel();
+        let mutation = {
+            let (gate, done) = (gate.clone(), done.clone());
+            thread::spawn(move || {
+                let mut held = Mutating::default();
+                gate.enter(&mut held);
+                assert!(gate.held_here());
+                entered.send(()).unwrap();
+                thread::sleep(Duration::from_millis(50));
+                done.store(true, Ordering::SeqCst);
+            })
+        };
+        on_entered.recv().unwrap();
+        assert!(!gate.held_here());
+        gate.wait_idle();
+        assert!(done.load(Ordering::SeqCst));
+        mutation.join().unwrap();
+    }
+
+    #[test]
+    fn nested_entries_leave_one_at_a_time() {
+        let gate = Arc::new(MutationGate::default());
+        let mut outer = Mutating::default();
+        gate.enter(&mut outer);
+        let mut inner = Mutating::default();
+        gate.enter(&mut inner);
+        drop(inner);
+        assert!(gate.held_here());
+        drop(outer);
+        assert!(!gate.held_here());
+        gate.wait_idle();
+    }
+}
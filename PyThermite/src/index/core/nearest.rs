@@ -0,0 +1,48 @@
+use croaring::Bitmap;
+use ordered_float::OrderedFloat;
+
+use crate::index::core::{query::QueryMap, structures::{composite_key::CompositeKey128, string_interner::INTERNER}};
+
+// up to k ids of scope whose numeric value of attr is closest to target, nearest first.
+// walks ranks outward from the target's position, a tie in distance takes the lower value first
+// and ids sharing the last value taken are cut lowest id first
+pub fn nearest(index: &[QueryMap], scope: &Bitmap, attr: &str, target: f64, k: usize) -> Vec<u32> {
+    let Some(qmap) = index.get(INTERNER.intern(attr) as usize).filter(|qmap| !qmap.is_placeholder()) else {
+        return vec![];
+    };
+    // nothing is near NaN
+    if target.is_nan() {
+        return vec![];
+    }
+    let target = qmap.stored_number(target);
+    let num = qmap.read_num_ordered();
+    let population = num.ids().and(scope);
+    let encoded = CompositeKey128::encode_f64_to_float76(OrderedFloat(target));
+
+    // ranks below the target run down from lower - 1, the rest up from upper
+    let mut lower = num.get_lt_from_valid(encoded, &population).cardinality();
+    let mut upper = lower;
+    let mut res = Vec::with_capacity(k);
+    while res.len() < k {
+        let below = lower.checked_sub(1).and_then(|rank| num.kth_from_valid(rank, &population));
+        let above = num.kth_from_valid(upper, &population);
+        let distance = |bits: u128| (CompositeKey128::decode_float76(bits) - target).abs();
+        let (bits, take_below) = match (below, above) {
+            (Some(b), Some(a)) if distance(b) <= distance(a) => (b, true),
+            (_, Some(a)) => (a, false),
+            (Some(b), None) => (b, true),
+            (None, None) => break,
+        };
+        let ids = num.get_exact(bits).and(&population);
+        if ids.is_empty() {
+            break;
+        }
+        if take_below {
+            lower -= ids.cardinality();
+        } else {
+            upper += ids.cardinality();
+        }
+        res.extend(ids.iter().take(k - res.len()));
+    }
+    res
+}
@@ -0,0 +1,104 @@
+use croaring::Bitmap;
+
+use crate::index::core::{query::QueryMap, structures::{string_interner::INTERNER, wide_bitmap::WideNum}};
+
+// one value per step, the rank jumps past every id holding it.
+// wide numbers are merged in, each before the first number past its floor key
+fn walk_numbers(qmap: &QueryMap, scope: &Bitmap, descending: bool, take: &mut impl FnMut(Bitmap) -> bool) {
+    let num = qmap.read_num_ordered();
+    let wide = qmap.read_wide();
+    let wide_values: Box<dyn Iterator<Item = (&WideNum, &Bitmap)>> = if descending {
+        Box::new(wide.ordered().rev())
+    } else {
+        Box::new(wide.ordered())
+    };
+    let mut wide_values = wide_values.peekable();
+    let population = num.ids().and(scope);
+    let count = population.cardinality();
+    let mut walked = 0;
+    loop {
+        let next = (walked < count)
+            .then(|| num.kth_from_valid(if descending { count - 1 - walked } else { walked }, &population))
+            .flatten();
+        while let Some((_, ids)) = wide_values.next_if(|(value, _)| next.is_none_or(|bits| (value.floor() < bits) != descending)) {
+            if !take(ids.and(scope)) {
+                return;
+            }
+        }
+        let Some(bits) = next else {
+            return;
+        };
+        let ids = num.get_exact(bits).and(&population);
+        walked += ids.cardinality().max(1);
+        if !take(ids) {
+            return;
+        }
+    }
+}
+
+// each space of encoded keys apart, in space order, one value per step like numbers
+fn walk_encoded(qmap: &QueryMap, scope: &Bitmap, descending: bool, take: &mut impl FnMut(Bitmap) -> bool) {
+    let encoded = qmap.read_encoded();
+    let mut spaces: Vec<_> = encoded.iter().collect();
+    spaces.sort_by_key(|(space, _)| **space);
+    if descending {
+        spaces.reverse();
+    }
+    for (_, keys) in spaces {
+        let population = keys.ids().and(scope);
+        let count = population.cardinality();
+        let mut walked = 0;
+        while let Some(bits) = (walked < count).then(|| keys.kth_from_valid(if descending { count - 1 - walked } else { walked }, &population)).flatten() {
+            let ids = keys.get_exact(bits).and(&population);
+            walked += ids.cardinality().max(1);
+            if !take(ids) {
+                return;
+            }
+        }
+    }
+}
+
+fn walk_strings(qmap: &QueryMap, scope: &Bitmap, descending: bool, take: &mut impl FnMut(Bitmap) -> bool) {
+    let strings = qmap.read_str_radix_map();
+    let values: Box<dyn Iterator<Item = &Bitmap>> = if descending {
+        Box::new(strings.ordered().rev())
+    } else {
+        Box::new(strings.ordered())
+    };
+    for ids in values {
+        if !take(ids.and(scope)) {
+            return;
+        }
+    }
+}
+
+// ids of scope sorted by attr, numbers, then encoded values such as versions a kind at a time, then strings,
+// equal values by id, descending reverses both.
+// the indexes are read in key order and left as soon as limit ids are taken,
+// objects without a number or string at attr come last by id whichever the direction
+pub fn order_by(index: &[QueryMap], scope: &Bitmap, attr: &str, descending: bool, limit: Option<usize>) -> Vec<u32> {
+    let limit = limit.unwrap_or(usize::MAX);
+    let mut res = Vec::with_capacity(limit.min(scope.cardinality() as usize));
+    // a value coerced to a number is also held as a string, taken once
+    let mut taken = Bitmap::new();
+    let mut take = |ids: Bitmap| {
+        let ids = ids.andnot(&taken);
+        res.extend(ids.iter().take(limit - res.len()));
+        taken.or_inplace(&ids);
+        res.len() < limit
+    };
+
+    if let Some(qmap) = index.get(INTERNER.intern(attr) as usize).filter(|qmap| limit > 0 && !qmap.is_placeholder()) {
+        if descending {
+            walk_strings(qmap, scope, true, &mut take);
+            walk_encoded(qmap, scope, true, &mut take);
+            walk_numbers(qmap, scope, true, &mut take);
+        } else {
+            walk_numbers(qmap, scope, false, &mut take);
+            walk_encoded(qmap, scope, false, &mut take);
+            walk_strings(qmap, scope, false, &mut take);
+        }
+    }
+    take(scope.clone());
+    res
+}
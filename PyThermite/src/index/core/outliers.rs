@@ -0,0 +1,69 @@
+use croaring::Bitmap;
+use ordered_float::OrderedFloat;
+use pyo3::{PyResult, exceptions::PyValueError};
+
+use crate::index::core::{query::QueryMap, stats::quantile, structures::{composite_key::CompositeKey128, ordered_bitmap::NumericalBitmap, string_interner::INTERNER}};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutlierMethod {
+    // beyond threshold interquartile ranges outside the quartiles
+    Iqr,
+    // beyond threshold standard deviations from the mean
+    ZScore,
+}
+
+impl OutlierMethod {
+    pub fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "iqr" => Ok(Self::Iqr),
+            "zscore" => Ok(Self::ZScore),
+            _ => Err(PyValueError::new_err(format!("unknown method '{}', expected iqr or zscore", name))),
+        }
+    }
+
+    pub fn default_threshold(self) -> f64 {
+        match self {
+            Self::Iqr => 1.5,
+            Self::ZScore => 3.0,
+        }
+    }
+}
+
+// over the same values the quartiles are taken from, in the units the numeric index holds
+fn mean_std(num: &NumericalBitmap, population: &Bitmap) -> Option<(f64, f64)> {
+    let values: Vec<f64> = num.values_from_valid(population).into_iter()
+        .map(|(_, bits)| CompositeKey128::decode_float76(bits))
+        .collect();
+    if values.is_empty() {
+        return None;
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance = values.iter().map(|value| (value - mean) * (value - mean)).sum::<f64>() / values.len() as f64;
+    Some((mean, variance.sqrt()))
+}
+
+// ids of scope whose numeric value of attr falls outside the method's bounds,
+// the bounds taken over the numeric values within scope only
+pub fn outliers(index: &[QueryMap], scope: &Bitmap, attr: &str, method: OutlierMethod, threshold: f64) -> Bitmap {
+    let Some(qmap) = index.get(INTERNER.intern(attr) as usize).filter(|qmap| !qmap.is_placeholder()) else {
+        return Bitmap::new();
+    };
+    let num = qmap.read_num_ordered();
+    let population = num.ids().and(scope);
+
+    let bounds = match method {
+        OutlierMethod::Iqr => quantile(&num, &population, 0.25)
+            .zip(quantile(&num, &population, 0.75))
+            .map(|(q1, q3)| (q1 - threshold * (q3 - q1), q3 + threshold * (q3 - q1))),
+        OutlierMethod::ZScore => mean_std(&num, &population)
+            .filter(|(_, std)| *std > 0.0)
+            .map(|(mean, std)| (mean - threshold * std, mean + threshold * std)),
+    };
+    let Some((lower, upper)) = bounds else {
+        return Bitmap::new();
+    };
+
+    let mut res = num.get_lt_from_valid(CompositeKey128::encode_f64_to_float76(OrderedFloat(lower)), &population);
+    res.or_inplace(&num.get_gt_from_valid(CompositeKey128::encode_f64_to_float76(OrderedFloat(upper)), &population));
+    res
+}
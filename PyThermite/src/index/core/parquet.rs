@@ -0,0 +1,160 @@
+use std::{fs::File, path::Path, sync::Arc};
+
+use ::parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use arrow_array::{ArrayRef, RecordBatch, builder::{BooleanBuilder, Float64Builder, Int64Builder, StringBuilder}};
+use arrow_schema::{DataType, Field, Schema};
+use pyo3::{PyResult, Python, exceptions::{PyOSError, PyValueError}};
+
+use crate::index::{core::{group_by::{AttrPath, attr_path}, introspect::type_name, stored_item::StoredItem}, value::{PyValue, RustCastValue}};
+
+// rows built into one record batch at a time, so a column never holds more than this many values
+const BATCH_ROWS: usize = 64 * 1024;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    // only None or missing so far
+    Unseen,
+    Int,
+    Float,
+    Bool,
+    Str,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Unseen => "None",
+            Kind::Int => "int",
+            Kind::Float => "float",
+            Kind::Bool => "bool",
+            Kind::Str => "str",
+        }
+    }
+
+    // a column never holding a value is written as nullable strings
+    fn data_type(self) -> DataType {
+        match self {
+            Kind::Int => DataType::Int64,
+            Kind::Float => DataType::Float64,
+            Kind::Bool => DataType::Boolean,
+            Kind::Unseen | Kind::Str => DataType::Utf8,
+        }
+    }
+
+    // ints are widened to floats when a column holds both, any other mix has no single column type
+    fn merge(self, other: Kind) -> Option<Kind> {
+        match (self, other) {
+            (a, b) if a == b => Some(a),
+            (Kind::Unseen, kind) | (kind, Kind::Unseen) => Some(kind),
+            (Kind::Int, Kind::Float) | (Kind::Float, Kind::Int) => Some(Kind::Float),
+            _ => None,
+        }
+    }
+}
+
+fn value_kind(py: Python, name: &str, value: &PyValue) -> PyResult<Kind> {
+    Ok(match value.get_primitive() {
+        RustCastValue::Int(_) => Kind::Int,
+        RustCastValue::Float(_) => Kind::Float,
+        RustCastValue::Bool(_) => Kind::Bool,
+        RustCastValue::Str(_) => Kind::Str,
+        RustCastValue::None => Kind::Unseen,
+        _ => return Err(PyValueError::new_err(format!(
+            "field '{}' holds a {}, only int, float, bool, str and None values can be written to parquet",
+            name, type_name(py, value)
+        ))),
+    })
+}
+
+// values of another kind, set while the file is written, are written as null
+fn build_column(kind: Kind, rows: &[&StoredItem], path: &AttrPath) -> ArrayRef {
+    let value = |item: &StoredItem| item.with_path(path, |value| value.get_primitive().clone());
+    match kind {
+        Kind::Int => {
+            let mut builder = Int64Builder::with_capacity(rows.len());
+            for item in rows {
+                builder.append_option(match value(item) {
+                    Some(RustCastValue::Int(i)) => Some(i),
+                    _ => None,
+                });
+            }
+            Arc::new(builder.finish())
+        },
+        Kind::Float => {
+            let mut builder = Float64Builder::with_capacity(rows.len());
+            for item in rows {
+                builder.append_option(match value(item) {
+                    Some(RustCastValue::Float(f)) => Some(f),
+                    Some(RustCastValue::Int(i)) => Some(i as f64),
+                    _ => None,
+                });
+            }
+            Arc::new(builder.finish())
+        },
+        Kind::Bool => {
+            let mut builder = BooleanBuilder::with_capacity(rows.len());
+            for item in rows {
+                builder.append_option(match value(item) {
+                    Some(RustCastValue::Bool(b)) => Some(b),
+                    _ => None,
+                });
+            }
+            Arc::new(builder.finish())
+        },
+        Kind::Unseen | Kind::Str => {
+            let mut builder = StringBuilder::with_capacity(rows.len(), rows.len() * 8);
+            for item in rows {
+                match value(item) {
+                    Some(RustCastValue::Str(s)) => builder.append_value(s),
+                    _ => builder.append_null(),
+                }
+            }
+            Arc::new(builder.finish())
+        },
+    }
+}
+
+fn write_error(e: impl ToString) -> pyo3::PyErr {
+    PyOSError::new_err(e.to_string())
+}
+
+// writes fields of rows, in order, as snappy compressed columns. each column takes the type of the values
+// it holds, None and objects without the attribute are written as null. returns the rows written
+pub fn write_parquet(py: Python, path: &Path, rows: &[&StoredItem], fields: &[String]) -> PyResult<usize> {
+    if fields.is_empty() {
+        return Err(PyValueError::new_err("to_parquet needs at least one field"));
+    }
+    if let Some(dup) = fields.iter().enumerate().find(|(i, f)| fields[..*i].contains(f)).map(|(_, f)| f) {
+        return Err(PyValueError::new_err(format!("field '{}' is given more than once", dup)));
+    }
+    let paths: Vec<AttrPath> = fields.iter().map(|name| attr_path(name)).collect();
+
+    let mut kinds = vec![Kind::Unseen; fields.len()];
+    for item in rows {
+        for ((name, path), kind) in fields.iter().zip(paths.iter()).zip(kinds.iter_mut()) {
+            let Some(found) = item.with_path(path, |value| value_kind(py, name, value)).transpose()? else {
+                continue;
+            };
+            *kind = kind.merge(found).ok_or_else(|| PyValueError::new_err(format!(
+                "field '{}' holds both {} and {} values, which no parquet column can hold",
+                name, kind.name(), found.name()
+            )))?;
+        }
+    }
+
+    let schema = Arc::new(Schema::new(
+        fields.iter().zip(kinds.iter()).map(|(name, kind)| Field::new(name, kind.data_type(), true)).collect::<Vec<_>>()
+    ));
+    let file = File::create(path)?;
+    py.allow_threads(|| {
+        let props = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+        let mut writer = ArrowWriter::try_new(file, schema.clone(), Some(props)).map_err(write_error)?;
+        for chunk in rows.chunks(BATCH_ROWS) {
+            let columns = kinds.iter().zip(paths.iter()).map(|(kind, path)| build_column(*kind, chunk, path)).collect();
+            let batch = RecordBatch::try_new(schema.clone(), columns).map_err(write_error)?;
+            writer.write(&batch).map_err(write_error)?;
+        }
+        writer.close().map_err(write_error)?;
+        Ok(rows.len())
+    })
+}
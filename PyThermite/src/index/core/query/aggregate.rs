@@ -0,0 +1,229 @@
+use croaring::Bitmap;
+use rustc_hash::FxHashMap;
+
+use crate::index::{core::{fixed_point::{from_fixed, to_fixed}, query::QueryMap, structures::string_interner::INTERNER}, value::{PyValue, RustCastValue}};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AggFunc {
+    Sum,
+    Count,
+    Min,
+    Max,
+    Avg,
+}
+
+impl AggFunc {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "sum" => Some(AggFunc::Sum),
+            "count" => Some(AggFunc::Count),
+            "min" => Some(AggFunc::Min),
+            "max" => Some(AggFunc::Max),
+            "avg" | "mean" => Some(AggFunc::Avg),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AggFunc::Sum => "sum",
+            AggFunc::Count => "count",
+            AggFunc::Min => "min",
+            AggFunc::Max => "max",
+            AggFunc::Avg => "avg",
+        }
+    }
+
+    // value reported for a parent with no children
+    pub fn empty_value(&self) -> Option<f64> {
+        match self {
+            AggFunc::Sum | AggFunc::Count => Some(0.0),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl CmpOp {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "eq" => Some(CmpOp::Eq),
+            "ne" => Some(CmpOp::Ne),
+            "gt" => Some(CmpOp::Gt),
+            "ge" => Some(CmpOp::Ge),
+            "lt" => Some(CmpOp::Lt),
+            "le" => Some(CmpOp::Le),
+            _ => None,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            CmpOp::Eq => "==",
+            CmpOp::Ne => "!=",
+            CmpOp::Gt => ">",
+            CmpOp::Ge => ">=",
+            CmpOp::Lt => "<",
+            CmpOp::Le => "<=",
+        }
+    }
+
+    #[inline]
+    pub fn matches(&self, lhs: f64, rhs: f64) -> bool {
+        match self {
+            CmpOp::Eq => lhs == rhs,
+            CmpOp::Ne => lhs != rhs,
+            CmpOp::Gt => lhs > rhs,
+            CmpOp::Ge => lhs >= rhs,
+            CmpOp::Lt => lhs < rhs,
+            CmpOp::Le => lhs <= rhs,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Accumulator {
+    pub count: u64,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+    // fixed point attributes are summed exactly in whole 10^-places units
+    pub places: Option<u8>,
+    pub units: i128,
+}
+
+impl Accumulator {
+    pub fn new() -> Self {
+        Self::with_places(None)
+    }
+
+    pub fn with_places(places: Option<u8>) -> Self {
+        Self {
+            count: 0,
+            sum: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            places,
+            units: 0,
+        }
+    }
+
+    #[inline]
+    pub fn push(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        if let Some(units) = self.places.and_then(|places| to_fixed(&RustCastValue::Float(value), places)) {
+            self.units += units as i128;
+        }
+    }
+
+    #[inline]
+    fn total(&self) -> f64 {
+        self.places.map_or(self.sum, |places| from_fixed(self.units, places))
+    }
+
+    pub fn finish(&self, func: AggFunc) -> Option<f64> {
+        if self.count == 0 {
+            return func.empty_value();
+        }
+        match func {
+            AggFunc::Sum => Some(self.total()),
+            AggFunc::Count => Some(self.count as f64),
+            AggFunc::Min => Some(self.min),
+            AggFunc::Max => Some(self.max),
+            AggFunc::Avg => Some(self.total() / self.count as f64),
+        }
+    }
+}
+
+impl Default for Accumulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QueryMap {
+
+    pub fn aggregate_children(&self, child_attr: &str, all_valid: &Bitmap) -> FxHashMap<u32, Accumulator> {
+        let attr_id = INTERNER.intern(child_attr);
+        let places = self.nested.declared_fixed_point(child_attr);
+        let mut groups: FxHashMap<u32, Accumulator> = FxHashMap::default();
+
+        let children = self.nested.allowed_items.read().unwrap().clone();
+        let child_items = self.nested.items.read().unwrap();
+
+        for child_id in children.iter() {
+            let value = child_items.get(child_id as usize)
+                .and_then(|item| item.with_attr_id(attr_id, PyValue::as_f64))
+                .flatten();
+
+            let Some(value) = value else {
+                continue;
+            };
+
+            let mut parents = self.nested.get_direct_parents(child_id);
+            self.unmask_ids(&mut parents);
+            parents.and_inplace(all_valid);
+
+            for parent in parents.iter() {
+                groups.entry(parent).or_insert_with(|| Accumulator::with_places(places)).push(value);
+            }
+        }
+        groups
+    }
+
+    pub fn agg(&self, func: AggFunc, child_attr: &str, op: CmpOp, threshold: f64, all_valid: &Bitmap) -> Bitmap {
+        let groups = self.aggregate_children(child_attr, all_valid);
+        let mut res = Bitmap::new();
+
+        for (parent, acc) in groups.iter() {
+            if let Some(value) = acc.finish(func) {
+                if op.matches(value, threshold) {
+                    res.add(*parent);
+                }
+            }
+        }
+
+        // parents without children still have a defined sum / count
+        if let Some(empty) = func.empty_value() {
+            if op.matches(empty, threshold) {
+                let mut childless = all_valid.clone();
+                for parent in groups.keys() {
+                    childless.remove(*parent);
+                }
+                res.or_inplace(&childless);
+            }
+        }
+        res
+    }
+
+    pub fn count_children(&self, op: CmpOp, threshold: f64, all_valid: &Bitmap) -> Bitmap {
+        let counts = self.get_child_counts_reader();
+        let mut res = Bitmap::new();
+        let mut counted = Bitmap::new();
+
+        for (parent, count) in counts.iter() {
+            counted.add(*parent);
+            if op.matches(*count as f64, threshold) {
+                res.add(*parent);
+            }
+        }
+
+        if op.matches(0.0, threshold) {
+            res.or_inplace(&all_valid.andnot(&counted));
+        }
+        res.and_inplace(all_valid);
+        res
+    }
+}
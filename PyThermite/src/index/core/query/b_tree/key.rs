@@ -17,8 +17,88 @@ impl Ord for Key {
         match (self, other) {
             (Key::Int(a), Key::Int(b)) => a.cmp(b),
             (Key::FloatOrdered(a), Key::FloatOrdered(b)) => a.cmp(b),
-            (Key::Int(a), Key::FloatOrdered(b)) => (*a as f64).partial_cmp(&b.0).unwrap_or(Ordering::Equal),
-            (Key::FloatOrdered(a), Key::Int(b)) => a.0.partial_cmp(&(*b as f64)).unwrap_or(Ordering::Equal),
+            (Key::Int(a), Key::FloatOrdered(b)) => cmp_int_float(*a, b.0),
+            (Key::FloatOrdered(a), Key::Int(b)) => cmp_int_float(*b, a.0).reverse(),
         }
     }
+}
+
+// exact comparison, casting the int to f64 would round above 2^53
+pub fn cmp_int_float(int: i64, float: f64) -> Ordering {
+    // NaN sorts above everything, matching OrderedFloat
+    if float.is_nan() || float >= 9_223_372_036_854_775_808.0 {
+        return Ordering::Less;
+    }
+    if float < -9_223_372_036_854_775_808.0 {
+        return Ordering::Greater;
+    }
+    // in range, so the truncated float converts to i64 without loss
+    let whole = float.trunc();
+    match int.cmp(&(whole as i64)) {
+        Ordering::Equal => whole.partial_cmp(&float).unwrap_or(Ordering::Equal),
+        ord => ord,
+    }
+}
+
+// numeric-looking strings ("42", " -1.5e3 ") as keys, ints kept exact
+pub fn parse_numeric(s: &str) -> Option<Key> {
+    let s = s.trim();
+    if let Ok(i) = s.parse::<i64>() {
+        return Some(Key::Int(i));
+    }
+    s.parse::<f64>().ok()
+        .filter(|f| f.is_finite())
+        .map(|f| Key::FloatOrdered(ordered_float::OrderedFloat(f)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ordered_float::OrderedFloat;
+
+    #[test]
+    fn test_int_float_exact() {
+        let big = 1i64 << 53;
+        assert_eq!(cmp_int_float(big + 1, big as f64), Ordering::Greater);
+        assert_eq!(cmp_int_float(big, big as f64), Ordering::Equal);
+        assert_eq!(cmp_int_float(i64::MAX, 9_223_372_036_854_775_808.0), Ordering::Less);
+        assert_eq!(cmp_int_float(i64::MIN, -9_223_372_036_854_775_808.0), Ordering::Equal);
+        assert_eq!(cmp_int_float(3, 3.5), Ordering::Less);
+        assert_eq!(cmp_int_float(-3, -3.5), Ordering::Greater);
+        assert_eq!(cmp_int_float(0, -0.0), Ordering::Equal);
+        assert_eq!(cmp_int_float(i64::MAX, f64::INFINITY), Ordering::Less);
+        assert_eq!(cmp_int_float(i64::MIN, f64::NEG_INFINITY), Ordering::Greater);
+        assert_eq!(cmp_int_float(0, f64::NAN), Ordering::Less);
+    }
+
+    #[test]
+    fn test_mixed_key_order() {
+        let big = (1i64 << 53) + 1;
+        let mut keys = vec![
+            Key::FloatOrdered(OrderedFloat(big as f64)),
+            Key::Int(big),
+            Key::FloatOrdered(OrderedFloat(0.5)),
+            Key::Int(0),
+        ];
+        keys.sort();
+        assert_eq!(keys, vec![
+            Key::Int(0),
+            Key::FloatOrdered(OrderedFloat(0.5)),
+            Key::FloatOrdered(OrderedFloat(big as f64)),
+            Key::Int(big),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_numeric() {
+        assert_eq!(parse_numeric("42"), Some(Key::Int(42)));
+        assert_eq!(parse_numeric(" -7 "), Some(Key::Int(-7)));
+        assert_eq!(parse_numeric("9007199254740993"), Some(Key::Int(9_007_199_254_740_993)));
+        assert_eq!(parse_numeric("1.5e3"), Some(Key::FloatOrdered(OrderedFloat(1500.0))));
+        assert_eq!(parse_numeric(".5"), Some(Key::FloatOrdered(OrderedFloat(0.5))));
+        assert_eq!(parse_numeric("inf"), None);
+        assert_eq!(parse_numeric("NaN"), None);
+        assert_eq!(parse_numeric("12 apples"), None);
+        assert_eq!(parse_numeric(""), None);
+    }
 }
\ No newline at end of file
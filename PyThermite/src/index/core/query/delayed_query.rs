@@ -1,9 +1,8 @@
 use std::sync::{Arc, RwLock, RwLockWriteGuard, Weak};
 
-use ordered_float::OrderedFloat;
-use pyo3::{Python, types::{PyListMethods, PySetMethods, PyTupleMethods}};
+use croaring::Bitmap;
 
-use crate::index::{core::{index::IndexAPI, query::{QueryMap, b_tree::Key}, stored_item::StoredItem, structures::{boolean_bitmap::BooleanBitmap, composite_key::CompositeKey128, hybrid_set::{HybridSet, HybridSetOps}, ordered_bitmap::NumericalBitmap, positional_bitmap::PositionalBitmap, shards::ShardedHashMap}}, types::StrId, value::{PyIterable, PyValue, RustCastValue, StoredIndexable}};
+use crate::index::{core::{encoders::KeySpace, index::IndexAPI, query::{QueryMap, b_tree::Key}, stored_item::StoredItem, structures::{boolean_bitmap::BooleanBitmap, composite_key::CompositeKey128, hybrid_set::{HybridSet, HybridSetOps}, ordered_bitmap::NumericalBitmap, positional_bitmap::PositionalBitmap, shards::ShardedHashMap}}, types::StrId, value::{PyIterable, PyValue, RustCastValue, StoredIndexable}};
 
 
 
@@ -11,6 +10,8 @@ pub struct BulkQueryMapAdder<'a> {
     pub str_radix_map: RwLockWriteGuard<'a, PositionalBitmap>,
     pub num_ordered: RwLockWriteGuard<'a, NumericalBitmap>,
     pub bool_map: RwLockWriteGuard<'a, BooleanBitmap>,
+    pub present: RwLockWriteGuard<'a, Bitmap>,
+    pub nulls: RwLockWriteGuard<'a, Bitmap>,
     map: &'a QueryMap,
 }
 
@@ -20,46 +21,83 @@ impl<'a> BulkQueryMapAdder<'a> {
             str_radix_map: map.write_str_radix_map(),
             num_ordered: map.write_num_ordered(),
             bool_map: map.get_bool_map_writer(),
+            present: map.get_present_writer(),
+            nulls: map.get_nulls_writer(),
             map: map,
         }
     }
 
+    #[inline]
+    pub fn is_placeholder(&self) -> bool {
+        self.map.is_placeholder()
+    }
+
     #[inline(always)]
     pub fn insert(&mut self, value: &PyValue, obj_id: u32){
         // Insert into the right ordered map based on primitive type
         match &value.get_primitive() {
-            RustCastValue::Int(i) => {
+            primitive @ (RustCastValue::Int(_) | RustCastValue::Float(_)) => {
                 //self.insert_exact(value, obj_id);
-                self.insert_num_ordered(Key::Int(*i), obj_id);
-            }
-            RustCastValue::Float(f) => {
-                //elf.insert_exact(value, obj_id);
-                self.insert_num_ordered(Key::FloatOrdered(OrderedFloat(*f)), obj_id);
+                if let Some(key) = self.map.number_key(primitive) {
+                    self.insert_num_ordered(key, obj_id);
+                }
+                self.map.insert_flags(value, obj_id);
             }
             RustCastValue::Ind(index_obj) => {
                 self.map.insert_exact(value, obj_id);
                 self.map.insert_indexable(index_obj, obj_id);
             },
-            RustCastValue::Iterable(py_iterable) => {
-                self.map.insert_iterable(py_iterable, obj_id);
+            RustCastValue::Iterable(PyIterable::Dict(_)) => {},
+            RustCastValue::Iterable(_) => {
+                self.map.insert_exact(value, obj_id);
+                self.map.insert_interval(value, obj_id);
+                self.insert_iterable(value.items(), obj_id);
             }
             RustCastValue::Bool(b) => self.insert_bool(*b, obj_id),
             RustCastValue::Str(extracted_str) => {
                 self.insert_str(extracted_str, obj_id);
+                match self.map.coerced(extracted_str) {
+                    Some((KeySpace::Number, key)) => self.insert_num_ordered(key, obj_id),
+                    Some((space, key)) => self.map.insert_key(space, key, obj_id),
+                    None => {},
+                }
                 // self.insert_exact(value, obj_id);
             },
+            RustCastValue::Encoded(KeySpace::Number, key) => self.insert_num_ordered(*key, obj_id),
+            RustCastValue::Encoded(space, key) => self.map.insert_key(*space, *key, obj_id),
+            RustCastValue::Wide(num) => self.map.write_wide().add(num, obj_id),
+            RustCastValue::None => {
+                self.nulls.add(obj_id);
+            },
+            RustCastValue::Bytes(_) | RustCastValue::Uuid(_) => self.map.insert_exact(value, obj_id),
             RustCastValue::Unknown => {
                 self.map.insert_exact(value, obj_id);
+                if let Some(fields) = value.fields() {
+                    self.map.insert_indexable(fields, obj_id);
+                }
             },
         }
     }
 
+    #[inline]
+    pub fn mark_present(&mut self, obj_id: u32) {
+        self.present.add(obj_id);
+    }
+
     #[inline]
     fn insert_num_ordered(&mut self, key: Key, obj_id: u32){
         let composit_key = CompositeKey128::new(key, obj_id);
         self.num_ordered.add_delayed(composit_key.get_value_bits(), obj_id);
     }
 
+    fn insert_iterable(&mut self, items: &[PyValue], obj_id: u32) {
+        // elements have to go through this adder, the map's own insert waits on the guards held here
+        for element in items.iter() {
+            let masked_id = self.map.allocate_masked(obj_id);
+            self.insert(element, masked_id);
+        }
+    }
+
     #[inline]
     fn insert_str(&mut self, value: &str, obj_id: u32) {
         self.str_radix_map.add_delayed(value, obj_id);
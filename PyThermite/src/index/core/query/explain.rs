@@ -0,0 +1,362 @@
+use std::{cell::RefCell, fmt, time::{Duration, Instant}};
+
+use croaring::Bitmap;
+use pyo3::{Bound, PyResult, Python, types::{PyAnyMethods, PyDict, PyDictMethods}};
+use smol_str::SmolStr;
+
+use crate::index::{core::query::{QueryMap, attr_parts, evaluate_query, query_ops::QueryExpr}, value::{PyValue, RustCastValue}};
+
+impl QueryExpr {
+    pub fn attr(&self) -> Option<&SmolStr> {
+        match self {
+            QueryExpr::Eq(attr, _)
+            | QueryExpr::Ne(attr, _)
+            | QueryExpr::In(attr, _)
+            | QueryExpr::Nin(attr, _)
+            | QueryExpr::Gt(attr, _)
+            | QueryExpr::Ge(attr, _)
+            | QueryExpr::Lt(attr, _)
+            | QueryExpr::Le(attr, _)
+            | QueryExpr::Bt(attr, _, _)
+            | QueryExpr::StartsWith(attr, _)
+            | QueryExpr::EndsWith(attr, _)
+            | QueryExpr::Contains(attr, _)
+            | QueryExpr::Matches(attr, _)
+            | QueryExpr::Overlaps(attr, _, _)
+            | QueryExpr::ContainsPoint(attr, _)
+            | QueryExpr::Agg(attr, _, _, _, _)
+            | QueryExpr::CountChildren(attr, _, _)
+            | QueryExpr::Exists(attr)
+            | QueryExpr::Missing(attr)
+            | QueryExpr::FlagsAll(attr, _)
+            | QueryExpr::FlagsAny(attr, _)
+            | QueryExpr::ModEq(attr, _, _)
+            | QueryExpr::Custom(attr, _, _) => Some(attr),
+            QueryExpr::Not(inner) => inner.attr(),
+            QueryExpr::And(_) | QueryExpr::Or(_) => None,
+        }
+    }
+
+    // same clause against a different attribute, compound expressions are returned as is
+    pub fn with_attr(&self, attr: SmolStr) -> QueryExpr {
+        match self {
+            QueryExpr::Eq(_, v) => QueryExpr::Eq(attr, v.clone()),
+            QueryExpr::Ne(_, v) => QueryExpr::Ne(attr, v.clone()),
+            QueryExpr::In(_, vals) => QueryExpr::In(attr, vals.clone()),
+            QueryExpr::Nin(_, vals) => QueryExpr::Nin(attr, vals.clone()),
+            QueryExpr::Gt(_, v) => QueryExpr::Gt(attr, v.clone()),
+            QueryExpr::Ge(_, v) => QueryExpr::Ge(attr, v.clone()),
+            QueryExpr::Lt(_, v) => QueryExpr::Lt(attr, v.clone()),
+            QueryExpr::Le(_, v) => QueryExpr::Le(attr, v.clone()),
+            QueryExpr::Bt(_, lower, upper) => QueryExpr::Bt(attr, lower.clone(), upper.clone()),
+            QueryExpr::StartsWith(_, v) => QueryExpr::StartsWith(attr, v.clone()),
+            QueryExpr::EndsWith(_, v) => QueryExpr::EndsWith(attr, v.clone()),
+            QueryExpr::Contains(_, v) => QueryExpr::Contains(attr, v.clone()),
+            QueryExpr::Matches(_, pattern) => QueryExpr::Matches(attr, pattern.clone()),
+            QueryExpr::Overlaps(_, lower, upper) => QueryExpr::Overlaps(attr, lower.clone(), upper.clone()),
+            QueryExpr::ContainsPoint(_, v) => QueryExpr::ContainsPoint(attr, v.clone()),
+            QueryExpr::Agg(_, func, child_attr, op, threshold) => {
+                QueryExpr::Agg(attr, *func, child_attr.clone(), *op, *threshold)
+            },
+            QueryExpr::CountChildren(_, op, threshold) => QueryExpr::CountChildren(attr, *op, *threshold),
+            QueryExpr::Exists(_) => QueryExpr::Exists(attr),
+            QueryExpr::Missing(_) => QueryExpr::Missing(attr),
+            QueryExpr::FlagsAll(_, mask) => QueryExpr::FlagsAll(attr, *mask),
+            QueryExpr::FlagsAny(_, mask) => QueryExpr::FlagsAny(attr, *mask),
+            QueryExpr::ModEq(_, divisor, remainder) => QueryExpr::ModEq(attr, *divisor, *remainder),
+            QueryExpr::Custom(_, name, v) => QueryExpr::Custom(attr, name.clone(), v.clone()),
+            QueryExpr::Not(_) | QueryExpr::And(_) | QueryExpr::Or(_) => self.clone(),
+        }
+    }
+}
+
+// leaf clauses (a Not counts as a leaf) that keep obj_id out of the result
+pub fn failing_clauses<'a>(index: &Vec<QueryMap>, obj_id: u32, expr: &'a QueryExpr) -> Vec<&'a QueryExpr> {
+    let single = Bitmap::of(&[obj_id]);
+    let mut failures = vec![];
+    collect_failures(index, &single, obj_id, expr, &mut failures);
+    failures
+}
+
+fn collect_failures<'a>(
+    index: &Vec<QueryMap>,
+    single: &Bitmap,
+    obj_id: u32,
+    expr: &'a QueryExpr,
+    failures: &mut Vec<&'a QueryExpr>,
+) -> bool {
+    match expr {
+        QueryExpr::And(exprs) => {
+            let mut passed = true;
+            for e in exprs {
+                passed &= collect_failures(index, single, obj_id, e, failures);
+            }
+            passed
+        },
+        QueryExpr::Or(exprs) => {
+            let mut branch_failures = vec![];
+            for e in exprs {
+                if collect_failures(index, single, obj_id, e, &mut branch_failures) {
+                    return true;
+                }
+            }
+            failures.extend(branch_failures);
+            false
+        },
+        _ => {
+            let passed = evaluate_query(index, single, expr).contains(obj_id);
+            if !passed {
+                failures.push(expr);
+            }
+            passed
+        },
+    }
+}
+
+pub struct NestedHop {
+    pub attr: SmolStr,
+    // nested index levels entered from here, including deeper hops
+    pub levels: u32,
+    pub child_matches: u64,
+    // time spent mapping matching children back onto their parents
+    pub rollup: Duration,
+    pub child: Box<QueryTrace>,
+}
+
+pub struct QueryTrace {
+    pub clause: String,
+    pub matched: u64,
+    pub elapsed: Duration,
+    pub hop: Option<NestedHop>,
+    pub children: Vec<QueryTrace>,
+}
+
+impl QueryTrace {
+    pub fn levels(&self) -> u32 {
+        let own = self.hop.as_ref().map_or(0, |hop| hop.levels);
+        self.children.iter().map(|c| c.levels()).fold(own, u32::max)
+    }
+
+    pub fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("clause", &self.clause)?;
+        dict.set_item("matched", self.matched)?;
+        dict.set_item("time_ns", self.elapsed.as_nanos() as u64)?;
+        dict.set_item("levels", self.levels())?;
+        if let Some(hop) = &self.hop {
+            let nested = PyDict::new(py);
+            nested.set_item("attr", hop.attr.as_str())?;
+            nested.set_item("levels", hop.levels)?;
+            nested.set_item("child_matches", hop.child_matches)?;
+            nested.set_item("rollup_ns", hop.rollup.as_nanos() as u64)?;
+            nested.set_item("child", hop.child.to_dict(py)?)?;
+            dict.set_item("nested", nested)?;
+        }
+        let children = self.children.iter()
+            .map(|c| c.to_dict(py))
+            .collect::<PyResult<Vec<_>>>()?;
+        dict.set_item("children", children)?;
+        Ok(dict)
+    }
+}
+
+// clauses under evaluation while trace_query runs on this thread, innermost last
+thread_local! {
+    static FRAMES: RefCell<Option<Vec<Frame>>> = const { RefCell::new(None) };
+}
+
+struct Frame {
+    start: Instant,
+    // base attribute a nested hop made from this clause is reported under
+    attr: Option<SmolStr>,
+    trace: QueryTrace,
+}
+
+impl Frame {
+    fn new(clause: String, attr: Option<SmolStr>) -> Self {
+        Self {
+            start: Instant::now(),
+            attr,
+            trace: QueryTrace { clause, matched: 0, elapsed: Duration::ZERO, hop: None, children: vec![] },
+        }
+    }
+}
+
+#[inline]
+pub fn is_tracing() -> bool {
+    FRAMES.with(|frames| frames.borrow().is_some())
+}
+
+fn push_frame(frame: Frame) {
+    FRAMES.with(|frames| {
+        if let Some(frames) = frames.borrow_mut().as_mut() {
+            frames.push(frame);
+        }
+    });
+}
+
+fn pop_frame() -> Option<Frame> {
+    FRAMES.with(|frames| frames.borrow_mut().as_mut().and_then(|frames| frames.pop()))
+}
+
+fn with_top(f: impl FnOnce(&mut Frame)) {
+    FRAMES.with(|frames| {
+        if let Some(top) = frames.borrow_mut().as_mut().and_then(|frames| frames.last_mut()) {
+            f(top);
+        }
+    });
+}
+
+// the evaluator reports each clause it runs through here, a no op unless a trace is active
+#[inline]
+pub fn traced(expr: &QueryExpr, eval: impl FnOnce() -> Bitmap) -> Bitmap {
+    traced_with(expr, eval, |bm| bm.cardinality())
+}
+
+pub fn traced_with(expr: &QueryExpr, eval: impl FnOnce() -> Bitmap, matched: impl FnOnce(&Bitmap) -> u64) -> Bitmap {
+    if !is_tracing() {
+        return eval();
+    }
+    let attr = expr.attr().map(|attr| attr_parts(attr.clone()).0);
+    push_frame(Frame::new(expr.to_string(), attr));
+    let result = eval();
+    if let Some(mut frame) = pop_frame() {
+        frame.trace.matched = matched(&result);
+        frame.trace.elapsed = frame.start.elapsed();
+        with_top(|parent| parent.trace.children.push(frame.trace));
+    }
+    result
+}
+
+// wraps the evaluation of a nested index, whose clauses are collected as the hop's child trace
+pub fn enter_hop() -> bool {
+    let tracing = is_tracing();
+    if tracing {
+        push_frame(Frame::new(String::new(), None));
+    }
+    tracing
+}
+
+pub fn leave_hop(child_matches: u64, rollup: Duration) {
+    let Some(Frame { trace: mut collector, .. }) = pop_frame() else {
+        return;
+    };
+    let child = collector.children.pop().unwrap_or(collector);
+    with_top(|frame| {
+        frame.trace.hop = Some(NestedHop {
+            attr: frame.attr.clone().unwrap_or_default(),
+            levels: child.levels() + 1,
+            child_matches,
+            rollup,
+            child: Box::new(child),
+        });
+    });
+}
+
+// runs evaluate_query with tracing switched on, recording per clause timings and nested hops
+pub fn trace_query(index: &Vec<QueryMap>, all_valid: &Bitmap, expr: &QueryExpr) -> (Bitmap, QueryTrace) {
+    let outer = FRAMES.with(|frames| frames.replace(Some(vec![Frame::new(String::new(), None)])));
+    let result = evaluate_query(index, all_valid, expr);
+    let collector = FRAMES.with(|frames| frames.replace(outer))
+        .and_then(|mut frames| frames.pop())
+        .map(|frame| frame.trace);
+    let trace = collector.and_then(|trace| trace.children.into_iter().next_back())
+        .unwrap_or_else(|| QueryTrace {
+            clause: expr.to_string(),
+            matched: result.cardinality(),
+            elapsed: Duration::ZERO,
+            hop: None,
+            children: vec![],
+        });
+    (result, trace)
+}
+
+fn fmt_value(f: &mut fmt::Formatter<'_>, value: &PyValue) -> fmt::Result {
+    match value.get_primitive() {
+        RustCastValue::Int(i) => write!(f, "{}", i),
+        RustCastValue::Float(v) => write!(f, "{:?}", v),
+        RustCastValue::Str(s) => write!(f, "{:?}", s.as_str()),
+        RustCastValue::Bool(b) => write!(f, "{}", if *b { "True" } else { "False" }),
+        RustCastValue::None => write!(f, "None"),
+        _ => {
+            let repr = Python::with_gil(|py| {
+                value.get_obj(py).bind(py).repr().map(|r| r.to_string()).unwrap_or_else(|_| "?".to_string())
+            });
+            write!(f, "{}", repr)
+        },
+    }
+}
+
+fn fmt_joined(f: &mut fmt::Formatter<'_>, exprs: &[QueryExpr], sep: &str) -> fmt::Result {
+    write!(f, "(")?;
+    for (i, e) in exprs.iter().enumerate() {
+        if i > 0 {
+            write!(f, " {} ", sep)?;
+        }
+        write!(f, "{}", e)?;
+    }
+    write!(f, ")")
+}
+
+impl fmt::Display for QueryExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let binary = |f: &mut fmt::Formatter<'_>, attr: &SmolStr, op: &str, v: &PyValue| {
+            write!(f, "{} {} ", attr, op)?;
+            fmt_value(f, v)
+        };
+        match self {
+            QueryExpr::Eq(attr, v) => binary(f, attr, "==", v),
+            QueryExpr::Ne(attr, v) => binary(f, attr, "!=", v),
+            QueryExpr::Gt(attr, v) => binary(f, attr, ">", v),
+            QueryExpr::Ge(attr, v) => binary(f, attr, ">=", v),
+            QueryExpr::Lt(attr, v) => binary(f, attr, "<", v),
+            QueryExpr::Le(attr, v) => binary(f, attr, "<=", v),
+            QueryExpr::StartsWith(attr, v) => binary(f, attr, "starts_with", v),
+            QueryExpr::EndsWith(attr, v) => binary(f, attr, "ends_with", v),
+            QueryExpr::Contains(attr, v) => binary(f, attr, "contains", v),
+            QueryExpr::Matches(attr, pattern) => {
+                let op = if pattern.regex { "matches_regex" } else { "matches" };
+                write!(f, "{} {} {:?}", attr, op, pattern.source.as_str())
+            },
+            QueryExpr::ContainsPoint(attr, v) => binary(f, attr, "contains_point", v),
+            QueryExpr::Overlaps(attr, lower, upper) => {
+                write!(f, "{} overlaps [", attr)?;
+                fmt_value(f, lower)?;
+                write!(f, ", ")?;
+                fmt_value(f, upper)?;
+                write!(f, "]")
+            },
+            QueryExpr::Bt(attr, lower, upper) => {
+                fmt_value(f, lower)?;
+                write!(f, " <= {} <= ", attr)?;
+                fmt_value(f, upper)
+            },
+            QueryExpr::In(attr, vals) | QueryExpr::Nin(attr, vals) => {
+                let op = if matches!(self, QueryExpr::In(..)) { "in" } else { "not in" };
+                write!(f, "{} {} [", attr, op)?;
+                for (i, v) in vals.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    fmt_value(f, v)?;
+                }
+                write!(f, "]")
+            },
+            QueryExpr::Not(inner) => write!(f, "not {}", inner),
+            QueryExpr::And(exprs) => fmt_joined(f, exprs, "and"),
+            QueryExpr::Or(exprs) => fmt_joined(f, exprs, "or"),
+            QueryExpr::Agg(attr, func, child_attr, op, threshold) => {
+                write!(f, "{}({}.{}) {} {}", func.name(), attr, child_attr, op.symbol(), threshold)
+            },
+            QueryExpr::CountChildren(attr, op, threshold) => {
+                write!(f, "count_children({}) {} {}", attr, op.symbol(), threshold)
+            },
+            QueryExpr::Exists(attr) => write!(f, "exists({})", attr),
+            QueryExpr::Missing(attr) => write!(f, "missing({})", attr),
+            QueryExpr::FlagsAll(attr, mask) => write!(f, "{} & {} == {}", attr, mask, mask),
+            QueryExpr::FlagsAny(attr, mask) => write!(f, "{} & {} != 0", attr, mask),
+            QueryExpr::ModEq(attr, divisor, remainder) => write!(f, "{} % {} == {}", attr, divisor, remainder),
+            QueryExpr::Custom(attr, name, v) => binary(f, attr, name, v),
+        }
+    }
+}
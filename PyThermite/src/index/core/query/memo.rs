@@ -0,0 +1,181 @@
+use std::hash::{Hash, Hasher};
+
+use croaring::Bitmap;
+use rustc_hash::FxHashMap;
+
+use crate::index::{core::query::{QueryMap, explain, planner::order_and, query_ops::{QueryExpr, evaluate_uncached, excluded}}, value::PyValue};
+
+// constants compare like python but only within the same kind, eq 1 and eq True read different maps
+fn same_value(a: &PyValue, b: &PyValue) -> bool {
+    std::mem::discriminant(a.get_primitive()) == std::mem::discriminant(b.get_primitive()) && a == b
+}
+
+// structural, so identical sub trees can share one evaluation
+impl PartialEq for QueryExpr {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (QueryExpr::Eq(a, v), QueryExpr::Eq(b, w))
+            | (QueryExpr::Ne(a, v), QueryExpr::Ne(b, w))
+            | (QueryExpr::Gt(a, v), QueryExpr::Gt(b, w))
+            | (QueryExpr::Ge(a, v), QueryExpr::Ge(b, w))
+            | (QueryExpr::Lt(a, v), QueryExpr::Lt(b, w))
+            | (QueryExpr::Le(a, v), QueryExpr::Le(b, w))
+            | (QueryExpr::StartsWith(a, v), QueryExpr::StartsWith(b, w))
+            | (QueryExpr::EndsWith(a, v), QueryExpr::EndsWith(b, w))
+            | (QueryExpr::Contains(a, v), QueryExpr::Contains(b, w))
+            | (QueryExpr::ContainsPoint(a, v), QueryExpr::ContainsPoint(b, w)) => a == b && same_value(v, w),
+            (QueryExpr::Bt(a, lower, upper), QueryExpr::Bt(b, other_lower, other_upper))
+            | (QueryExpr::Overlaps(a, lower, upper), QueryExpr::Overlaps(b, other_lower, other_upper)) => {
+                a == b && same_value(lower, other_lower) && same_value(upper, other_upper)
+            },
+            (QueryExpr::In(a, vals), QueryExpr::In(b, others))
+            | (QueryExpr::Nin(a, vals), QueryExpr::Nin(b, others)) => {
+                a == b && vals.len() == others.len() && vals.iter().zip(others).all(|(v, w)| same_value(v, w))
+            },
+            (QueryExpr::Matches(a, p), QueryExpr::Matches(b, q)) => a == b && p.source == q.source && p.regex == q.regex,
+            (QueryExpr::Agg(a, func, child, op, t), QueryExpr::Agg(b, other_func, other_child, other_op, u)) => {
+                a == b && func == other_func && child == other_child && op == other_op && t.to_bits() == u.to_bits()
+            },
+            (QueryExpr::CountChildren(a, op, t), QueryExpr::CountChildren(b, other_op, u)) => {
+                a == b && op == other_op && t.to_bits() == u.to_bits()
+            },
+            (QueryExpr::Exists(a), QueryExpr::Exists(b)) | (QueryExpr::Missing(a), QueryExpr::Missing(b)) => a == b,
+            (QueryExpr::FlagsAll(a, m), QueryExpr::FlagsAll(b, n)) | (QueryExpr::FlagsAny(a, m), QueryExpr::FlagsAny(b, n)) => a == b && m == n,
+            (QueryExpr::ModEq(a, d, r), QueryExpr::ModEq(b, e, s)) => a == b && d == e && r == s,
+            (QueryExpr::Custom(a, name, v), QueryExpr::Custom(b, other_name, w)) => a == b && name == other_name && same_value(v, w),
+            (QueryExpr::Not(a), QueryExpr::Not(b)) => a == b,
+            (QueryExpr::And(a), QueryExpr::And(b)) | (QueryExpr::Or(a), QueryExpr::Or(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for QueryExpr {}
+
+impl Hash for QueryExpr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            QueryExpr::Eq(a, v)
+            | QueryExpr::Ne(a, v)
+            | QueryExpr::Gt(a, v)
+            | QueryExpr::Ge(a, v)
+            | QueryExpr::Lt(a, v)
+            | QueryExpr::Le(a, v)
+            | QueryExpr::StartsWith(a, v)
+            | QueryExpr::EndsWith(a, v)
+            | QueryExpr::Contains(a, v)
+            | QueryExpr::ContainsPoint(a, v) => (a, v).hash(state),
+            QueryExpr::Bt(a, lower, upper) | QueryExpr::Overlaps(a, lower, upper) => (a, lower, upper).hash(state),
+            QueryExpr::In(a, vals) | QueryExpr::Nin(a, vals) => (a, vals).hash(state),
+            QueryExpr::Matches(a, p) => (a, &p.source, p.regex).hash(state),
+            QueryExpr::Agg(a, func, child, op, t) => (a, func, child, op, t.to_bits()).hash(state),
+            QueryExpr::CountChildren(a, op, t) => (a, op, t.to_bits()).hash(state),
+            QueryExpr::Exists(a) | QueryExpr::Missing(a) => a.hash(state),
+            QueryExpr::FlagsAll(a, mask) | QueryExpr::FlagsAny(a, mask) => (a, mask).hash(state),
+            QueryExpr::ModEq(a, divisor, remainder) => (a, divisor, remainder).hash(state),
+            QueryExpr::Custom(a, name, v) => (a, name, v).hash(state),
+            QueryExpr::Not(inner) => inner.hash(state),
+            QueryExpr::And(exprs) | QueryExpr::Or(exprs) => exprs.hash(state),
+        }
+    }
+}
+
+// sub trees seen more than once are evaluated against all_valid a single time and the
+// result reused wherever they appear, in one query or across several
+struct Memo<'a> {
+    index: &'a Vec<QueryMap>,
+    all_valid: &'a Bitmap,
+    // times each sub tree appears
+    seen: FxHashMap<&'a QueryExpr, usize>,
+    results: FxHashMap<&'a QueryExpr, Bitmap>,
+}
+
+impl<'a> Memo<'a> {
+    fn new(index: &'a Vec<QueryMap>, all_valid: &'a Bitmap, exprs: &'a [QueryExpr]) -> Self {
+        let mut memo = Self { index, all_valid, seen: FxHashMap::default(), results: FxHashMap::default() };
+        exprs.iter().for_each(|e| memo.count(e));
+        memo.seen.retain(|_, seen| *seen > 1);
+        memo
+    }
+
+    fn count(&mut self, expr: &'a QueryExpr) {
+        *self.seen.entry(expr).or_default() += 1;
+        match expr {
+            QueryExpr::And(exprs) | QueryExpr::Or(exprs) => exprs.iter().for_each(|e| self.count(e)),
+            QueryExpr::Not(inner) => self.count(inner),
+            _ => {},
+        }
+    }
+
+    // a superset of the matches within valid, callers intersect with it
+    fn eval(&mut self, expr: &'a QueryExpr, valid: &Bitmap) -> Bitmap {
+        if !self.seen.contains_key(expr) {
+            return self.eval_inner(expr, valid);
+        }
+        if let Some(res) = self.results.get(expr) {
+            return res.clone();
+        }
+        let res = self.eval_inner(expr, self.all_valid);
+        self.results.insert(expr, res.clone());
+        res
+    }
+
+    fn eval_inner(&mut self, expr: &'a QueryExpr, valid: &Bitmap) -> Bitmap {
+        match expr {
+            QueryExpr::And(_) | QueryExpr::Or(_) | QueryExpr::Not(_) => explain::traced(expr, || self.eval_compound(expr, valid)),
+            // leaves are traced by evaluate_uncached
+            leaf => evaluate_uncached(self.index, valid, leaf),
+        }
+    }
+
+    fn eval_compound(&mut self, expr: &'a QueryExpr, valid: &Bitmap) -> Bitmap {
+        match expr {
+            // shared clauses first, the rest narrowed by them like evaluate_and_queries_vec
+            QueryExpr::And(exprs) => {
+                let (shared, rest): (Vec<&QueryExpr>, Vec<&QueryExpr>) = exprs.iter().partition(|e| self.seen.contains_key(e));
+                let mut res = valid.clone();
+                for e in shared {
+                    res.and_inplace(&self.eval(e, valid));
+                }
+                for e in order_and(self.index, &res, rest) {
+                    if res.is_empty() {
+                        break;
+                    }
+                    if !self.seen.contains_key(e) {
+                        if let Some(ruled_out) = excluded(self.index, &res, e) {
+                            res.andnot_inplace(&ruled_out);
+                            continue;
+                        }
+                    }
+                    let matched = self.eval(e, &res);
+                    res.and_inplace(&matched);
+                }
+                res
+            },
+            QueryExpr::Or(exprs) => {
+                let mut res = Bitmap::new();
+                for e in exprs {
+                    res.or_inplace(&self.eval(e, valid));
+                }
+                res
+            },
+            QueryExpr::Not(inner) => valid - &self.eval(inner, valid),
+            leaf => evaluate_uncached(self.index, valid, leaf),
+        }
+    }
+}
+
+pub fn evaluate_query(index: &Vec<QueryMap>, all_valid: &Bitmap, expr: &QueryExpr) -> Bitmap {
+    let exprs = std::slice::from_ref(expr);
+    let mut memo = Memo::new(index, all_valid, exprs);
+    if memo.seen.is_empty() {
+        return evaluate_uncached(index, all_valid, expr);
+    }
+    memo.eval(expr, all_valid)
+}
+
+pub fn evaluate_many(index: &Vec<QueryMap>, all_valid: &Bitmap, exprs: &[QueryExpr]) -> Vec<Bitmap> {
+    let mut memo = Memo::new(index, all_valid, exprs);
+    exprs.iter().map(|e| memo.eval(e, all_valid)).collect()
+}
@@ -1,9 +1,17 @@
 pub mod query;
 pub mod b_tree;
 pub mod query_ops;
+pub mod aggregate;
+pub mod normalize;
+pub mod explain;
+pub mod planner;
+pub mod pattern;
+pub mod memo;
+pub mod operators;
 mod delayed_query;
 mod query_ops_removal;
 
 pub use query::QueryMap;
 pub use delayed_query::BulkQueryMapAdder;
-pub use query_ops::{attr_parts, evaluate_query};
+pub use query_ops::attr_parts;
+pub use memo::evaluate_query;
@@ -0,0 +1,98 @@
+use std::cmp::Ordering;
+
+use crate::index::{core::query::{b_tree::key::cmp_int_float, query_ops::QueryExpr}, value::{PyValue, RustCastValue}};
+
+fn is_numeric(value: &PyValue) -> bool {
+    matches!(value.get_primitive(), RustCastValue::Int(_) | RustCastValue::Float(_))
+}
+
+fn cmp_num(a: &PyValue, b: &PyValue) -> Ordering {
+    match (a.get_primitive(), b.get_primitive()) {
+        (RustCastValue::Int(a), RustCastValue::Int(b)) => a.cmp(b),
+        (RustCastValue::Int(a), RustCastValue::Float(b)) => cmp_int_float(*a, *b),
+        (RustCastValue::Float(a), RustCastValue::Int(b)) => cmp_int_float(*b, *a).reverse(),
+        _ => {
+            let a = a.as_f64().unwrap_or(f64::NAN);
+            let b = b.as_f64().unwrap_or(f64::NAN);
+            a.total_cmp(&b)
+        }
+    }
+}
+
+impl QueryExpr {
+    // matches nothing - an Or with no branches evaluates to an empty bitmap
+    pub fn never() -> QueryExpr {
+        QueryExpr::Or(vec![])
+    }
+
+    pub fn is_never(&self) -> bool {
+        matches!(self, QueryExpr::Or(exprs) if exprs.is_empty())
+    }
+
+    pub fn normalized(&self) -> QueryExpr {
+        match self {
+            // pushed down to the leaves so the And / Or above can flatten and reorder around them
+            QueryExpr::Not(inner) => match inner.normalized() {
+                QueryExpr::Not(inner) => *inner,
+                QueryExpr::And(exprs) => normalize_or(&negate_all(exprs)),
+                QueryExpr::Or(exprs) => normalize_and(&negate_all(exprs)),
+                QueryExpr::Eq(attr, value) => QueryExpr::Ne(attr, value),
+                QueryExpr::Ne(attr, value) => QueryExpr::Eq(attr, value),
+                QueryExpr::In(attr, values) => QueryExpr::Nin(attr, values),
+                QueryExpr::Nin(attr, values) => QueryExpr::In(attr, values),
+                QueryExpr::Exists(attr) => QueryExpr::Missing(attr),
+                QueryExpr::Missing(attr) => QueryExpr::Exists(attr),
+                inner => QueryExpr::Not(Box::new(inner)),
+            },
+            QueryExpr::And(exprs) => normalize_and(exprs),
+            QueryExpr::Or(exprs) => normalize_or(exprs),
+            QueryExpr::Bt(_, lower, upper) if is_numeric(lower) && is_numeric(upper) => {
+                if cmp_num(lower, upper) == Ordering::Greater {
+                    QueryExpr::never()
+                } else {
+                    self.clone()
+                }
+            }
+            _ => self.clone(),
+        }
+    }
+}
+
+fn negate_all(exprs: Vec<QueryExpr>) -> Vec<QueryExpr> {
+    exprs.into_iter().map(|expr| QueryExpr::Not(Box::new(expr))).collect()
+}
+
+fn normalize_and(exprs: &[QueryExpr]) -> QueryExpr {
+    // bounds on one attribute are not merged, a list or nested attribute can satisfy each with a different element
+    let mut flat: Vec<QueryExpr> = Vec::with_capacity(exprs.len());
+    for expr in exprs {
+        match expr.normalized() {
+            QueryExpr::And(inner) => flat.extend(inner),
+            expr if expr.is_never() => return QueryExpr::never(),
+            expr => flat.push(expr),
+        }
+    }
+
+    if flat.len() == 1 {
+        flat.pop().unwrap()
+    } else {
+        QueryExpr::And(flat)
+    }
+}
+
+fn normalize_or(exprs: &[QueryExpr]) -> QueryExpr {
+    let mut flat: Vec<QueryExpr> = Vec::with_capacity(exprs.len());
+    for expr in exprs {
+        match expr.normalized() {
+            expr if expr.is_never() => {}
+            QueryExpr::Or(inner) => flat.extend(inner),
+            expr => flat.push(expr),
+        }
+    }
+
+    if flat.len() == 1 {
+        flat.pop().unwrap()
+    } else {
+        QueryExpr::Or(flat)
+    }
+}
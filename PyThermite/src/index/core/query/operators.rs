@@ -0,0 +1,101 @@
+use std::sync::{Arc, RwLock};
+
+use croaring::Bitmap;
+use once_cell::sync::Lazy;
+use pyo3::{Py, PyAny, Python, types::PyAnyMethods};
+use rustc_hash::FxHashMap;
+use smol_str::SmolStr;
+
+use crate::index::{core::query::QueryMap, types::StrId, value::{PyValue, RustCastValue}};
+
+// a query operator not built into the crate, answered from the attribute's QueryMap.
+// ids outside all_valid in the result are dropped
+pub trait CustomOperator: Send + Sync {
+    fn evaluate(&self, qm: &QueryMap, value: &PyValue, all_valid: &Bitmap) -> Bitmap;
+}
+
+impl<F> CustomOperator for F
+where
+    F: Fn(&QueryMap, &PyValue, &Bitmap) -> Bitmap + Send + Sync,
+{
+    fn evaluate(&self, qm: &QueryMap, value: &PyValue, all_valid: &Bitmap) -> Bitmap {
+        self(qm, value, all_valid)
+    }
+}
+
+#[derive(Clone)]
+enum Operator {
+    Native(Arc<dyn CustomOperator>),
+    // predicate(attribute value, query value) called once per candidate object under the GIL
+    Python(Arc<Py<PyAny>>),
+}
+
+static OPERATORS: Lazy<RwLock<FxHashMap<SmolStr, Operator>>> = Lazy::new(|| RwLock::new(FxHashMap::default()));
+
+// called once from the module init, operators added in rust belong here too
+pub fn register_builtin_operators() {
+    register_native_operator("bits_all", Arc::new(|qm: &QueryMap, value: &PyValue, all_valid: &Bitmap| {
+        int_mask(value).map(|mask| qm.flags_all(mask, all_valid)).unwrap_or_default()
+    }));
+    register_native_operator("bits_any", Arc::new(|qm: &QueryMap, value: &PyValue, all_valid: &Bitmap| {
+        int_mask(value).map(|mask| qm.flags_any(mask, all_valid)).unwrap_or_default()
+    }));
+}
+
+// replaces any operator already registered under name, built in ones included
+pub fn register_native_operator(name: &str, op: Arc<dyn CustomOperator>) {
+    OPERATORS.write().unwrap().insert(SmolStr::new(name), Operator::Native(op));
+}
+
+pub fn register_py_operator(name: &str, func: Py<PyAny>) {
+    OPERATORS.write().unwrap().insert(SmolStr::new(name), Operator::Python(Arc::new(func)));
+}
+
+pub fn unregister_operator(name: &str) -> bool {
+    OPERATORS.write().unwrap().remove(name).is_some()
+}
+
+pub fn is_operator(name: &str) -> bool {
+    OPERATORS.read().unwrap().contains_key(name)
+}
+
+// operators unregistered since the query was built match nothing
+pub fn evaluate_custom(qm: &QueryMap, attr_id: StrId, name: &str, value: &PyValue, all_valid: &Bitmap) -> Bitmap {
+    // lock released first, python predicates may register operators themselves
+    let Some(op) = OPERATORS.read().unwrap().get(name).cloned() else {
+        return Bitmap::new();
+    };
+    let mut res = match op {
+        Operator::Native(op) => op.evaluate(qm, value, all_valid),
+        Operator::Python(func) => py_matches(qm, attr_id, &func, value, all_valid),
+    };
+    res.and_inplace(all_valid);
+    res
+}
+
+// values are cloned out of the items first so no index lock is held while python runs.
+// a predicate raising counts as no match
+fn py_matches(qm: &QueryMap, attr_id: StrId, func: &Py<PyAny>, value: &PyValue, all_valid: &Bitmap) -> Bitmap {
+    let candidates = qm.get_present_reader().and(all_valid);
+    let held: Vec<(u32, PyValue)> = {
+        let items = qm.get_stored_items().read().unwrap();
+        candidates.iter()
+            .filter_map(|id| Some((id, items.get(id as usize)?.with_attr_id(attr_id, |v| v.clone())?)))
+            .collect()
+    };
+    Python::with_gil(|py| {
+        let func = func.bind(py);
+        let arg = value.get_obj(py);
+        held.into_iter()
+            .filter(|(_, v)| func.call1((v.get_obj(py), &arg)).and_then(|r| r.is_truthy()).unwrap_or(false))
+            .map(|(id, _)| id)
+            .collect()
+    })
+}
+
+fn int_mask(value: &PyValue) -> Option<i64> {
+    match value.get_primitive() {
+        RustCastValue::Int(mask) => Some(*mask),
+        _ => None,
+    }
+}
@@ -0,0 +1,104 @@
+use pyo3::{PyResult, exceptions::PyValueError};
+use regex::Regex;
+use regex_syntax::hir::literal::{ExtractKind, Extractor};
+use smol_str::SmolStr;
+
+// a matches pattern, a glob unless regex is set. both have to match the whole string
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    pub source: SmolStr,
+    pub regex: bool,
+    compiled: Regex,
+    // literal text every match starts / ends with, for pruning with the positional bitmap
+    pub prefix: String,
+    pub suffix: String,
+}
+
+impl Pattern {
+    pub fn new(source: &str, regex: bool) -> PyResult<Self> {
+        let translated = if regex { source.to_string() } else { glob_to_regex(source)? };
+        let anchored = format!("^(?:{})$", translated);
+        let compiled = Regex::new(&anchored)
+            .map_err(|e| PyValueError::new_err(format!("invalid pattern '{}': {}", source, e)))?;
+        let (prefix, suffix) = literal_bounds(&anchored);
+        Ok(Self { source: SmolStr::new(source), regex, compiled, prefix, suffix })
+    }
+
+    pub fn is_match(&self, s: &str) -> bool {
+        self.compiled.is_match(s)
+    }
+}
+
+// * any run of characters, ? one character, [abc] / [a-z] / [!abc] a class, \ escapes the next character
+fn glob_to_regex(glob: &str) -> PyResult<String> {
+    let mut out = String::from("(?s)");
+    let mut chars = glob.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '*' => out.push_str(".*"),
+            '?' => out.push('.'),
+            '\\' => match chars.next() {
+                Some(escaped) => out.push_str(&regex::escape(&escaped.to_string())),
+                None => return Err(PyValueError::new_err(format!("invalid pattern '{}': trailing \\", glob))),
+            },
+            '[' => {
+                out.push('[');
+                let mut class = chars.clone();
+                if let Some('!' | '^') = class.next() {
+                    out.push('^');
+                    chars = class;
+                }
+                let mut closed = false;
+                let mut first = true;
+                for ch in chars.by_ref() {
+                    match ch {
+                        // a ] right after the opening bracket is a member
+                        ']' if !first => {
+                            closed = true;
+                            break;
+                        },
+                        '\\' | '[' | ']' | '&' | '~' => {
+                            out.push('\\');
+                            out.push(ch);
+                        },
+                        _ => out.push(ch),
+                    }
+                    first = false;
+                }
+                if !closed {
+                    return Err(PyValueError::new_err(format!("invalid pattern '{}': unclosed [", glob)));
+                }
+                out.push(']');
+            },
+            _ => out.push_str(&regex::escape(&ch.to_string())),
+        }
+    }
+    Ok(out)
+}
+
+// the longest literal prefix and suffix shared by every match, empty when there is none
+fn literal_bounds(anchored: &str) -> (String, String) {
+    let Ok(hir) = regex_syntax::parse(anchored) else {
+        return (String::new(), String::new());
+    };
+    let prefixes = Extractor::new().kind(ExtractKind::Prefix).extract(&hir);
+    let suffixes = Extractor::new().kind(ExtractKind::Suffix).extract(&hir);
+    let prefix = prefixes.longest_common_prefix().map(utf8_prefix).unwrap_or_default();
+    let suffix = suffixes.longest_common_suffix().map(utf8_suffix).unwrap_or_default();
+    (prefix, suffix)
+}
+
+// the common bytes may stop inside a character, only whole characters are kept
+fn utf8_prefix(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => s.to_string(),
+        Err(e) => String::from_utf8_lossy(&bytes[..e.valid_up_to()]).into_owned(),
+    }
+}
+
+fn utf8_suffix(bytes: &[u8]) -> String {
+    (0..=bytes.len())
+        .find_map(|start| std::str::from_utf8(&bytes[start..]).ok())
+        .unwrap_or_default()
+        .to_string()
+}
@@ -0,0 +1,72 @@
+use croaring::Bitmap;
+use ordered_float::OrderedFloat;
+
+use crate::index::{core::{query::{QueryMap, attr_parts, query_ops::QueryExpr}, structures::{composite_key::CompositeKey128, hybrid_set::HybridSetOps, string_interner::INTERNER}}, value::{PyValue, RustCastValue}};
+
+impl QueryMap {
+    // ids eq would match: exact where the index keeps the count at hand, an upper bound for numbers
+    pub fn estimate_eq(&self, value: &PyValue) -> u64 {
+        match value.get_primitive() {
+            RustCastValue::Bool(b) => self.get_bool_map_reader().get_exact(*b).cardinality(),
+            RustCastValue::None => self.get_nulls_reader().cardinality(),
+            RustCastValue::Wide(num) => self.read_wide().get_exact(num).cardinality(),
+            RustCastValue::Int(i) => self.read_num_ordered().estimate_exact(CompositeKey128::encode_i64_to_float76(*i)),
+            RustCastValue::Float(f) => self.read_num_ordered().estimate_exact(CompositeKey128::encode_f64_to_float76(OrderedFloat(*f))),
+            RustCastValue::Encoded(space, key) => self.read_encoded().get(space)
+                .map_or(0, |keys| keys.estimate_exact(CompositeKey128::encode_key(*key))),
+            RustCastValue::Str(s) => self.read_str_radix_map().exact_count(s),
+            _ => self.exact.get_with(&self.exact_key(value), |set| set.cardinality()).unwrap_or(0),
+        }
+    }
+}
+
+// rough upper bound on how many of all_valid expr matches, only used to order And branches
+pub fn estimate(index: &Vec<QueryMap>, all_valid: &Bitmap, expr: &QueryExpr) -> u64 {
+    let all = all_valid.cardinality();
+    match expr {
+        QueryExpr::And(exprs) => exprs.iter().map(|e| estimate(index, all_valid, e)).min().unwrap_or(all),
+        QueryExpr::Or(exprs) => exprs.iter().map(|e| estimate(index, all_valid, e)).sum::<u64>().min(all),
+        QueryExpr::Not(inner) => all.saturating_sub(estimate(index, all_valid, inner)),
+        QueryExpr::Ne(attr, value) => {
+            all.saturating_sub(estimate(index, all_valid, &QueryExpr::Eq(attr.clone(), value.clone())))
+        },
+        QueryExpr::Nin(attr, values) => {
+            all.saturating_sub(estimate(index, all_valid, &QueryExpr::In(attr.clone(), values.clone())))
+        },
+        QueryExpr::Missing(attr) => all.saturating_sub(estimate(index, all_valid, &QueryExpr::Exists(attr.clone()))),
+        // these can match objects without the attribute
+        QueryExpr::Agg(..) | QueryExpr::CountChildren(..) => all / 2,
+        leaf => {
+            let Some(attr) = leaf.attr() else {
+                return all;
+            };
+            let (base, nested_attr) = attr_parts(attr.clone());
+            let qm = match index.get(INTERNER.intern(&base) as usize) {
+                Some(qm) if !qm.is_placeholder() => qm,
+                _ => return 0,
+            };
+            if let Some(nested_attr) = nested_attr {
+                let nested_index = qm.nested.get_index_reader();
+                let nested_allowed = qm.nested.allowed_items.read().unwrap();
+                return estimate(&nested_index, &nested_allowed, &leaf.with_attr(nested_attr)).min(all);
+            }
+            match leaf {
+                QueryExpr::Eq(_, value) => qm.estimate_eq(value).min(all),
+                QueryExpr::In(_, values) => values.iter()
+                    .map(|v| qm.estimate_eq(v))
+                    .sum::<u64>()
+                    .min(all),
+                QueryExpr::Exists(_) => qm.get_present_reader().and_cardinality(all_valid),
+                QueryExpr::StartsWith(..) | QueryExpr::EndsWith(..) | QueryExpr::Contains(..) | QueryExpr::Matches(..) => all / 4,
+                _ => all / 2,
+            }
+        },
+    }
+}
+
+// most selective first, the static cost breaking ties, so the running intersection shrinks as early as possible
+pub fn order_and<'a>(index: &Vec<QueryMap>, all_valid: &Bitmap, exprs: impl IntoIterator<Item = &'a QueryExpr>) -> Vec<&'a QueryExpr> {
+    let mut ordered: Vec<&QueryExpr> = exprs.into_iter().collect();
+    ordered.sort_by_cached_key(|expr| (estimate(index, all_valid, expr), expr.estimated_cost()));
+    ordered
+}
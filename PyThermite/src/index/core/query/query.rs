@@ -1,68 +1,123 @@
-use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak};
+use std::sync::{Arc, RwLockReadGuard, RwLockWriteGuard, Weak};
 
 use rustc_hash::FxHashMap;
 use croaring::Bitmap;
 use ordered_float::OrderedFloat;
-use pyo3::{Python, types::{PyListMethods, PySetMethods, PyTupleMethods}};
 use smallvec::SmallVec;
 use smol_str::SmolStr;
 
 const QUERY_DEPTH_LEN: usize = 12;
 
-use crate::index::{Index, core::{id_alloc::{allocate_id, free_id}, query::{BulkQueryMapAdder, attr_parts, b_tree::ranged_b_tree::BitMapBTreeIter}, structures::{boolean_bitmap::BooleanBitmap, composite_key::CompositeKey128, hybrid_set::{HybridSet, HybridSetOps}, ordered_bitmap::NumericalBitmap, positional_bitmap::PositionalBitmap, shards::ShardedHashMap}}, types::StrId, value::{PyIterable, PyValue, RustCastValue, StoredIndexable}};
+use crate::index::{Index, core::{id_alloc::{allocate_id, free_id}, query::{BulkQueryMapAdder, attr_parts, b_tree::ranged_b_tree::BitMapBTreeIter}, structures::{boolean_bitmap::BooleanBitmap, composite_key::CompositeKey128, hybrid_set::{HybridSet, HybridSetOps}, ordered_bitmap::NumericalBitmap, positional_bitmap::PositionalBitmap, shards::ShardedHashMap}}, types::StrId, value::{ExactKey, HashMode, PyIterable, PyValue, RustCastValue, StoredIndexable}};
+use crate::index::core::encoders::KeySpace;
 use crate::index::core::index::IndexAPI;
+use crate::index::core::attr_decl::AttrDecl;
+use crate::index::core::attr_decls::AttrDecls;
 use crate::index::core::stored_item::StoredItem;
-use crate::index::core::query::b_tree::{BitMapBTree, Key};
+use crate::index::core::query::b_tree::{BitMapBTree, Key, key::parse_numeric};
+use crate::index::core::semver::parse_semver;
+use crate::index::core::fixed_point::{scale, to_fixed};
+use crate::index::core::structures::interval_bitmap::IntervalBitmap;
+use crate::index::core::structures::wide_bitmap::WideBitmap;
+use crate::index::core::structures::freeze_lock::{FreezeLock, FreezeReadGuard};
 
 #[derive(Default)]
 pub struct QueryMap {
-    pub exact: ShardedHashMap<PyValue, HybridSet>,
-    pub str_radix_map: RwLock<PositionalBitmap>,
-    pub num_ordered: RwLock<NumericalBitmap>,
-    pub bool_map: RwLock<BooleanBitmap>,
+    pub exact: ShardedHashMap<ExactKey, HybridSet>,
+    // what exact keys are hashed by, taken from the index config
+    pub hash_mode: HashMode,
+    pub str_radix_map: FreezeLock<PositionalBitmap>,
+    pub num_ordered: FreezeLock<NumericalBitmap>,
+    // encoded keys of every space but numbers, each space ordered apart from the others
+    pub encoded: FreezeLock<FxHashMap<KeySpace, NumericalBitmap>>,
+    pub bool_map: FreezeLock<BooleanBitmap>,
     pub parent: Weak<IndexAPI>,
     pub nested: Arc<IndexAPI>,
-    pub mapped_ids: RwLock<FxHashMap<u32, u32>>,
-    pub masked_ids: RwLock<Bitmap>,
-    stored_items: Arc<RwLock<Vec<StoredItem>>>,
+    pub mapped_ids: FreezeLock<FxHashMap<u32, u32>>,
+    pub masked_ids: FreezeLock<Bitmap>,
+    pub masked_by_parent: FreezeLock<FxHashMap<u32, SmallVec<[u32; 4]>>>,
+    pub child_counts: FreezeLock<FxHashMap<u32, u32>>,
+    // ids holding this attribute whatever the value, elements of iterables are not counted
+    pub present: FreezeLock<Bitmap>,
+    // ids holding None, kept out of the exact map so None never shares a key with other unknown values
+    pub nulls: FreezeLock<Bitmap>,
+    // big ints and Decimals with no exact float76 key, ranges over num_ordered take these in too
+    pub wide: FreezeLock<WideBitmap>,
+    // what the attribute was declared as when this map was built
+    pub decl: AttrDecl,
+    // (start, end) pairs held by this attribute, when declared as an interval
+    pub intervals: Option<FreezeLock<IntervalBitmap>>,
+    // ints held by this attribute as raw two's complement bits, when declared with flags
+    pub flags: Option<FreezeLock<NumericalBitmap>>,
+    // false for the Default placeholders padding the index vec
+    initialized: bool,
+    stored_items: Arc<FreezeLock<Vec<StoredItem>>>,
 }
 
 unsafe impl Send for QueryMap {}
 unsafe impl Sync for QueryMap {}
 
 impl QueryMap {
-    pub fn new(parent: Weak<IndexAPI>) -> Self {
-        let stored_items = if let Some(p) = parent.upgrade() {
-            p.items.clone()
+    pub fn new(parent: Weak<IndexAPI>, decls: AttrDecls) -> Self {
+        let (stored_items, config) = if let Some(p) = parent.upgrade() {
+            (p.items.clone(), p.config.clone())
         } else {
-            Arc::new(RwLock::new(Vec::new()))
+            (Arc::new(FreezeLock::new(Vec::new())), Arc::default())
         };
         Self{
-            exact: ShardedHashMap::<PyValue, HybridSet>::with_shard_count(16),
-            str_radix_map: RwLock::new(PositionalBitmap::new()),
+            decl: decls.decl,
+            intervals: decls.decl.interval.then(|| FreezeLock::new(IntervalBitmap::new())),
+            flags: decls.decl.flags.then(|| FreezeLock::new(NumericalBitmap::new())),
+            initialized: true,
+            exact: ShardedHashMap::<ExactKey, HybridSet>::with_shard_count(config.shards),
+            hash_mode: config.hash_mode,
+            str_radix_map: FreezeLock::new(PositionalBitmap::with_max_len(config.max_str_len)),
             parent: parent.clone(),
-            num_ordered: RwLock::new(NumericalBitmap::new()),
-            bool_map: RwLock::new(BooleanBitmap::new()),
-            nested: Arc::new(IndexAPI::new(Some(parent))),
-            mapped_ids: RwLock::new(FxHashMap::default()),
-            masked_ids: RwLock::new(Bitmap::new()),
+            num_ordered: FreezeLock::new(NumericalBitmap::new()),
+            encoded: FreezeLock::new(FxHashMap::default()),
+            bool_map: FreezeLock::new(BooleanBitmap::new()),
+            nested: Arc::new(IndexAPI::with_decls(Some(parent), decls).with_config(config)),
+            mapped_ids: FreezeLock::new(FxHashMap::default()),
+            masked_ids: FreezeLock::new(Bitmap::new()),
+            masked_by_parent: FreezeLock::new(FxHashMap::default()),
+            child_counts: FreezeLock::new(FxHashMap::default()),
+            present: FreezeLock::new(Bitmap::new()),
+            nulls: FreezeLock::new(Bitmap::new()),
+            wide: FreezeLock::new(WideBitmap::new()),
             stored_items
         }
     }
 
+    // placeholders have no parent or nested limits, so a real map must be built in their slot
+    #[inline]
+    pub fn is_placeholder(&self) -> bool {
+        !self.initialized
+    }
+
+    #[inline]
+    pub(crate) fn exact_key(&self, value: &PyValue) -> ExactKey {
+        ExactKey::new(value.clone(), self.hash_mode)
+    }
+
     #[inline(always)]
     pub(crate) fn insert_exact(&self, value: &PyValue, obj_id: u32){
-        let mut shard = self.exact.get_shard(&value);
-        match shard.get_mut (value) {
+        let key = self.exact_key(value);
+        let mut shard = self.exact.get_shard(&key);
+        match shard.get_mut(&key) {
             Some(hs) => {
                 hs.add(obj_id);
             }
             None => {
-                shard.insert(value.clone(), HybridSet::of(&[obj_id]));
+                shard.insert(key, HybridSet::of(&[obj_id]));
             }
         }
     }
 
+    #[inline]
+    pub fn mark_present(&self, obj_id: u32) {
+        self.get_present_writer().add(obj_id);
+    }
+
     #[inline]
     fn insert_bool(&self, value: bool, obj_id: u32) {
         self.insert_bool_from_guard(&mut self.get_bool_map_writer(), value, obj_id);
@@ -88,6 +143,48 @@ impl QueryMap {
         guard.add(value, obj_id);
     }
 
+    // key for a string when this attribute opted into coercion
+    #[inline]
+    pub(crate) fn coerced(&self, value: &str) -> Option<(KeySpace, Key)> {
+        if self.decl.semver {
+            parse_semver(value).map(|key| (KeySpace::Semver, key))
+        } else if self.decl.coerce_numeric {
+            parse_numeric(value).map(|key| (KeySpace::Number, key))
+        } else {
+            None
+        }
+    }
+
+    // numeric key for an int or float, scaled to a whole number on fixed point attributes
+    #[inline]
+    pub(crate) fn number_key(&self, value: &RustCastValue) -> Option<Key> {
+        if let Some(units) = self.decl.fixed_point.and_then(|places| to_fixed(value, places)) {
+            return Some(Key::Int(units));
+        }
+        match value {
+            RustCastValue::Int(i) => Some(Key::Int(*i)),
+            RustCastValue::Float(f) => Some(Key::FloatOrdered(OrderedFloat(*f))),
+            _ => None,
+        }
+    }
+
+    // a plain number in the units the numeric index holds for this attribute
+    #[inline]
+    pub fn stored_number(&self, value: f64) -> f64 {
+        match self.decl.fixed_point {
+            Some(places) => value * scale(places) as f64,
+            None => value,
+        }
+    }
+
+    #[inline]
+    pub fn plain_number(&self, stored: f64) -> f64 {
+        match self.decl.fixed_point {
+            Some(places) => stored / scale(places) as f64,
+            None => stored,
+        }
+    }
+
     #[inline]
     fn remove_str(&self, value: &str, obj_id: u32) {
         self.write_str_radix_map().remove(value, obj_id);
@@ -110,6 +207,35 @@ impl QueryMap {
         guard.add_delayed(composit_key.get_value_bits(), obj_id);
     }
 
+    // numbers go to num_ordered, every other space to its own map
+    #[inline]
+    pub(crate) fn insert_key(&self, space: KeySpace, key: Key, obj_id: u32) {
+        match space {
+            KeySpace::Number => self.insert_num_ordered(key, obj_id),
+            space => {
+                let composit_key = CompositeKey128::new(key, obj_id);
+                self.write_encoded().entry(space).or_default().add(composit_key.get_value_bits(), obj_id);
+            },
+        }
+    }
+
+    #[inline]
+    fn remove_key(&self, space: KeySpace, key: Key, obj_id: u32) {
+        match space {
+            KeySpace::Number => self.remove_num_ordered(key, obj_id),
+            space => {
+                let composit_key = CompositeKey128::new(key, obj_id);
+                let mut encoded = self.write_encoded();
+                if let Some(held) = encoded.get_mut(&space) {
+                    held.remove(composit_key.get_value_bits(), obj_id);
+                    if held.is_empty() {
+                        encoded.remove(&space);
+                    }
+                }
+            },
+        }
+    }
+
     #[inline]
     fn remove_num_ordered(&self, key: Key, obj_id: u32){
         let composit_key = CompositeKey128::new(key, obj_id);
@@ -133,6 +259,13 @@ impl QueryMap {
             }
         }
 
+        let parent_id = self.resolve_masked_id(obj_id);
+        *self.get_child_counts_writer().entry(parent_id).or_insert(0) += 1;
+
+        if !self.nested.allows_nesting() {
+            return;
+        }
+
         // register the index in the object
         let weak_nested = Arc::downgrade(&self.nested);
         index_obj.owned_handle.add_index(weak_nested.clone());
@@ -147,203 +280,407 @@ impl QueryMap {
         }
     }
 
-    pub(crate) fn insert_iterable(&self, iterable: &PyIterable, obj_id: u32){
-        Python::with_gil(|py| {
-            match iterable {
-                PyIterable::Dict(_) => {
-//                    let dict = py_dict.bind(py);
-//                    dict.iter().for_each(|(k, v)| {
-//                        self.iterable.entry(k).or_insert(k)
-//                    });
-                },
+    // a two item list or tuple of numbers, start first
+    fn interval_bounds(value: &PyValue) -> Option<(Key, Key)> {
+        let [start, end] = value.items() else {
+            return None;
+        };
+        let (start, end) = (Self::interval_key(start)?, Self::interval_key(end)?);
+        (start <= end).then_some((start, end))
+    }
 
-                PyIterable::List(py_list) => {
-                    for item in py_list.bind(py).iter(){
-                        let index_id = allocate_id();
-                        self.get_mapped_ids_writer().insert(index_id, obj_id);
-                        self.get_masked_ids_writer().add(index_id);
-                        self.insert(&PyValue::new(item), index_id);
-                    }
-                },
-                PyIterable::Tuple(py_tuple) => {
-                    for item in py_tuple.bind(py).iter(){
-                        let index_id = allocate_id();
-                        self.get_mapped_ids_writer().insert(index_id, obj_id);
-                        self.get_masked_ids_writer().add(index_id);
-                        self.insert(&PyValue::new(item), index_id);
-                    }
-                }
-                PyIterable::Set(py_set) => {
-                    for item in py_set.bind(py).iter(){
-                        let index_id = allocate_id();
-                        self.get_mapped_ids_writer().insert(index_id, obj_id);
-                        self.get_masked_ids_writer().add(index_id);
-                        self.insert(&PyValue::new(item), index_id);
-                    }
-                },
-            }
-        });
+    pub(crate) fn interval_key(value: &PyValue) -> Option<Key> {
+        match value.get_primitive() {
+            RustCastValue::Int(i) => Some(Key::Int(*i)),
+            RustCastValue::Float(f) => Some(Key::FloatOrdered(OrderedFloat(*f))),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn insert_interval(&self, value: &PyValue, obj_id: u32) {
+        if let (Some(intervals), Some((start, end))) = (&self.intervals, Self::interval_bounds(value)) {
+            intervals.write().unwrap().add(start, end, obj_id);
+        }
+    }
+
+    fn remove_interval(&self, value: &PyValue, obj_id: u32) {
+        if let (Some(intervals), Some((start, end))) = (&self.intervals, Self::interval_bounds(value)) {
+            intervals.write().unwrap().remove(start, end, obj_id);
+        }
+    }
+
+    pub(crate) fn insert_flags(&self, value: &PyValue, obj_id: u32) {
+        if let (Some(flags), RustCastValue::Int(i)) = (&self.flags, value.get_primitive()) {
+            flags.write().unwrap().add(*i as u64 as u128, obj_id);
+        }
+    }
+
+    fn remove_flags(&self, value: &PyValue, obj_id: u32) {
+        if let (Some(flags), RustCastValue::Int(i)) = (&self.flags, value.get_primitive()) {
+            flags.write().unwrap().remove(*i as u64 as u128, obj_id);
+        }
+    }
+
+    pub(crate) fn insert_iterable(&self, items: &[PyValue], obj_id: u32){
+        for item in items.iter() {
+            self.insert_masked(item, obj_id);
+        }
+    }
+
+    fn insert_masked(&self, value: &PyValue, obj_id: u32) {
+        let index_id = self.allocate_masked(obj_id);
+        self.insert(value, index_id);
+    }
+
+    pub(crate) fn allocate_masked(&self, obj_id: u32) -> u32 {
+        let index_id = allocate_id();
+        self.get_mapped_ids_writer().insert(index_id, obj_id);
+        self.get_masked_ids_writer().add(index_id);
+        self.get_masked_by_parent_writer().entry(obj_id).or_default().push(index_id);
+        index_id
+    }
+
+    #[inline]
+    pub fn resolve_masked_id(&self, id: u32) -> u32 {
+        self.get_mapped_ids_reader().get(&id).copied().unwrap_or(id)
     }
 
     #[inline(always)]
     pub fn insert(&self, value: &PyValue, obj_id: u32){
         // Insert into the right ordered map based on primitive type
         match &value.get_primitive() {
-            RustCastValue::Int(i) => {
+            primitive @ (RustCastValue::Int(_) | RustCastValue::Float(_)) => {
                 //self.insert_exact(value, obj_id);
-                self.insert_num_ordered(Key::Int(*i), obj_id);
-            }
-            RustCastValue::Float(f) => {
-                //elf.insert_exact(value, obj_id);
-                self.insert_num_ordered(Key::FloatOrdered(OrderedFloat(*f)), obj_id);
+                if let Some(key) = self.number_key(primitive) {
+                    self.insert_num_ordered(key, obj_id);
+                }
+                self.insert_flags(value, obj_id);
             }
             RustCastValue::Ind(index_obj) => {
                 self.insert_exact(value, obj_id);
                 self.insert_indexable(index_obj, obj_id);
             },
-            RustCastValue::Iterable(py_iterable) => {
-                self.insert_iterable(py_iterable, obj_id);
+            // dicts are not indexed
+            RustCastValue::Iterable(PyIterable::Dict(_)) => {},
+            RustCastValue::Iterable(_) => {
+                // whole value by content for equality, elements for membership
+                self.insert_exact(value, obj_id);
+                self.insert_interval(value, obj_id);
+                self.insert_iterable(value.items(), obj_id);
             }
             RustCastValue::Bool(b) => self.insert_bool(*b, obj_id),
             RustCastValue::Str(extracted_str) => {
                 self.insert_str(extracted_str, obj_id);
+                if let Some((space, key)) = self.coerced(extracted_str) {
+                    self.insert_key(space, key, obj_id);
+                }
                 // self.insert_exact(value, obj_id);
             },
+            RustCastValue::Encoded(space, key) => self.insert_key(*space, *key, obj_id),
+            RustCastValue::Wide(num) => self.write_wide().add(num, obj_id),
+            RustCastValue::None => self.get_nulls_writer().add(obj_id),
+            RustCastValue::Bytes(_) | RustCastValue::Uuid(_) => self.insert_exact(value, obj_id),
             RustCastValue::Unknown => {
                 self.insert_exact(value, obj_id);
+                if let Some(fields) = value.fields() {
+                    self.insert_indexable(fields, obj_id);
+                }
             },
         }
     }
 
     pub fn check_prune(&self, val: &PyValue) {
-        let mut shard = self.exact.get_shard(&val);
-        if let Some(ev) = shard.get(val) {
+        let key = self.exact_key(val);
+        let mut shard = self.exact.get_shard(&key);
+        if let Some(ev) = shard.get(&key) {
             if ev.is_empty() {
-                shard.remove(val); // no clone needed
+                shard.remove(&key);
             }
         }
     }
 
-    pub fn merge(&self, other: &Self) {
-        // Iterate over all values in `self` mutably
-        self.exact.for_each_mut(|key_self, bm_self| {
-            // Try to get the corresponding value from `other`
-            if let Some(bm_other) = other.exact.get(key_self) {
-                bm_self.or_inplace(&bm_other);
+    // takes other's postings of the objects in ids, with their elements
+    pub fn merge(&self, other: &Self, ids: &Bitmap) {
+        // parents this map holds already keep their own elements and child counts,
+        // other's elements of them would be left behind when the parent changes here
+        let held = self.get_present_reader().or(&self.get_nulls_reader());
+        let taken = ids.andnot(&held);
+        let mut skip = other.get_present_reader().or(&other.get_nulls_reader());
+        skip.andnot_inplace(ids);
+        skip.andnot_inplace(&held);
+        for (parent_id, masked) in other.get_masked_by_parent_reader().iter() {
+            if !taken.contains(*parent_id) {
+                skip.add_many(masked);
+            }
+        }
+
+        // values only other holds are taken too
+        // keyed again under this map's hash mode, other's may differ
+        other.exact.for_each(|key_other, bm_other| {
+            let key = self.exact_key(&key_other.value);
+            let mut shard = self.exact.get_shard(&key);
+            match shard.get_mut(&key) {
+                Some(bm_self) => bm_self.or_inplace(bm_other),
+                None => { shard.insert(key, bm_other.clone()); },
             }
         });
         self.write_str_radix_map().merge(&other.read_str_radix_map());
         self.write_num_ordered().merge(&other.read_num_ordered());
+        let mut encoded = self.write_encoded();
+        for (space, other_keys) in other.read_encoded().iter() {
+            encoded.entry(*space).or_default().merge(other_keys);
+        }
+        drop(encoded);
+        self.write_wide().merge(&other.read_wide());
         self.get_bool_map_writer().merge(&other.get_bool_map_reader());
+        if let (Some(intervals), Some(other_intervals)) = (&self.intervals, &other.intervals) {
+            intervals.write().unwrap().merge(&other_intervals.read().unwrap());
+        }
+        if let (Some(flags), Some(other_flags)) = (&self.flags, &other.flags) {
+            flags.write().unwrap().merge(&other_flags.read().unwrap());
+        }
         self.get_masked_ids_writer().or_inplace(&other.get_masked_ids_reader());
+        self.get_present_writer().or_inplace(&other.get_present_reader());
+        self.get_nulls_writer().or_inplace(&other.get_nulls_reader());
         self.get_mapped_ids_writer().extend(other.get_mapped_ids_reader().iter());
+        let mut masked_by_parent = self.get_masked_by_parent_writer();
+        for (parent_id, masked) in other.get_masked_by_parent_reader().iter().filter(|(parent_id, _)| taken.contains(**parent_id)) {
+            masked_by_parent.insert(*parent_id, masked.clone());
+        }
+        drop(masked_by_parent);
+        let mut counts = self.get_child_counts_writer();
+        for (parent_id, count) in other.get_child_counts_reader().iter().filter(|(parent_id, _)| taken.contains(**parent_id)) {
+            counts.insert(*parent_id, *count);
+        }
+        drop(counts);
+
+        if !skip.is_empty() {
+            let mut keep = self.get_present_reader().or(&self.get_nulls_reader());
+            keep.or_inplace(&self.get_masked_ids_reader());
+            keep.andnot_inplace(&skip);
+            self.keep_only(&keep);
+        }
+    }
+
+    // flushes and compresses every structure, exact values no object holds anymore are dropped
+    pub fn optimize(&self) {
+        self.exact.compact(|set| match set {
+            HybridSet::Empty => false,
+            HybridSet::Large(bm) => {
+                bm.run_optimize();
+                bm.shrink_to_fit();
+                !bm.is_empty()
+            },
+            set => !set.is_empty(),
+        });
+        self.write_str_radix_map().optimize();
+        self.write_num_ordered().optimize();
+        for keys in self.write_encoded().values_mut() {
+            keys.optimize();
+        }
+        self.write_wide().optimize();
+        self.get_bool_map_writer().optimize();
+        if let Some(intervals) = &self.intervals {
+            intervals.write().unwrap().optimize();
+        }
+        if let Some(flags) = &self.flags {
+            flags.write().unwrap().optimize();
+        }
+        let mut masked = self.get_masked_ids_writer();
+        masked.run_optimize();
+        masked.shrink_to_fit();
+        drop(masked);
+        let mut present = self.get_present_writer();
+        present.run_optimize();
+        present.shrink_to_fit();
+        drop(present);
+        let mut nulls = self.get_nulls_writer();
+        nulls.run_optimize();
+        nulls.shrink_to_fit();
+        drop(nulls);
+        self.get_mapped_ids_writer().shrink_to_fit();
+        self.get_masked_by_parent_writer().shrink_to_fit();
+        self.get_child_counts_writer().shrink_to_fit();
+    }
+
+    // moves every structure out of its lock for good, the nested index with them. optimize first,
+    // nothing can write them afterwards
+    pub fn freeze(&self) {
+        self.exact.freeze();
+        self.str_radix_map.freeze();
+        self.num_ordered.freeze();
+        self.encoded.freeze();
+        self.bool_map.freeze();
+        self.mapped_ids.freeze();
+        self.masked_ids.freeze();
+        self.masked_by_parent.freeze();
+        self.child_counts.freeze();
+        self.present.freeze();
+        self.nulls.freeze();
+        self.wide.freeze();
+        if let Some(intervals) = &self.intervals {
+            intervals.freeze();
+        }
+        if let Some(flags) = &self.flags {
+            flags.freeze();
+        }
+        self.nested.freeze_structures();
+    }
+
+    // keys emptied by removals still hold their values, and with them any nested objects
+    pub fn release_empty_keys(&self) {
+        self.exact.compact(|set| !set.is_empty());
+        self.nested.release_empty_keys();
     }
 
     pub fn is_empty(&self) -> bool {
         self.exact.is_empty()
+            && self.read_num_ordered().is_empty()
+            && self.read_encoded().is_empty()
+            && self.read_wide().is_empty()
+            && self.read_str_radix_map().is_empty()
+            && self.get_bool_map_reader().is_empty()
+            && self.get_present_reader().is_empty()
+            && self.get_nulls_reader().is_empty()
     }
 
     pub fn get<'a>(
         &self,
-        guard: &'a RwLockReadGuard<FxHashMap<PyValue, HybridSet>>,
-        key: &PyValue,
+        guard: &'a RwLockReadGuard<FxHashMap<ExactKey, HybridSet>>,
+        key: &ExactKey,
     ) -> Option<&'a HybridSet> {
         guard.get(key)
     }
 
     pub fn get_mut<'a>(
         &self,
-        guard: &'a mut RwLockWriteGuard<FxHashMap<PyValue, HybridSet>>,
-        key: &PyValue,
+        guard: &'a mut RwLockWriteGuard<FxHashMap<ExactKey, HybridSet>>,
+        key: &ExactKey,
     ) -> Option<&'a mut HybridSet> {
         guard.get_mut(key)
     }
 
     fn remove_exact(&self, py_value: &PyValue, idx: u32) {
-        let mut shard = self.exact.get_shard(py_value);
-        if let Some(hs) = shard.get_mut(py_value){
+        let key = self.exact_key(py_value);
+        let mut shard = self.exact.get_shard(&key);
+        if let Some(hs) = shard.get_mut(&key){
             hs.remove(idx);
         }
     }
 
-    fn remove_iterable(&self, iterable: &PyIterable, obj_id: u32) {
-        Python::with_gil(|py| {
-            match iterable {
-                PyIterable::Dict(_) => {
-    //                let dict = py_dict.bind(py);
-    //                dict.iter().for_each(|(k, v)| {
-    //                    self.iterable.entry(k).or_insert(k)
-    //                });
-                },
-
-                PyIterable::List(py_list) => {
-                    for item in py_list.bind(py).iter(){
-                        let mut writer = self.get_mapped_ids_writer();
-                        let mut ids_writer = self.get_masked_ids_writer();
-                        writer.get(&obj_id).map(|mapped_id| {
-                            free_id(*mapped_id);
-                            ids_writer.remove(*mapped_id);
-                            self.remove_id(&PyValue::new(item), *mapped_id);
-                        });
-                        writer.remove(&obj_id);
-                    }
-                },
-                PyIterable::Tuple(py_tuple) => {
-                    for item in py_tuple.bind(py).iter(){
-                        let mut writer = self.get_mapped_ids_writer();
-                        let mut ids_writer = self.get_masked_ids_writer();
-                        writer.get(&obj_id).map(|mapped_id| {
-                            free_id(*mapped_id);
-                            ids_writer.remove(*mapped_id);
-                            self.remove_id(&PyValue::new(item), *mapped_id);
-                        });
-                        writer.remove(&obj_id);
-                    }
+    fn remove_iterable(&self, items: &[PyValue], obj_id: u32) {
+        // masked ids were allocated in order over the frozen items, so they pair up
+        let masked = self.get_masked_by_parent_writer().remove(&obj_id).unwrap_or_default();
+        for (item, mapped_id) in items.iter().zip(masked.iter()) {
+            self.remove_masked(item, *mapped_id);
+        }
+    }
+
+    // re-points obj_id from old to new, touching only the elements that changed
+    pub(crate) fn update_iterable(&self, old: &PyValue, new: &PyValue, obj_id: u32) {
+        self.remove_exact(old, obj_id);
+        self.insert_exact(new, obj_id);
+        self.remove_interval(old, obj_id);
+        self.insert_interval(new, obj_id);
+
+        let masked = self.get_masked_by_parent_writer().remove(&obj_id).unwrap_or_default();
+        // old elements by hash, each as (item, masked id)
+        let mut unmatched: FxHashMap<u64, SmallVec<[(&PyValue, u32); 1]>> = FxHashMap::default();
+        for (item, mapped_id) in old.items().iter().zip(masked.iter()) {
+            unmatched.entry(item.get_hash()).or_default().push((item, *mapped_id));
+        }
+
+        // kept in the order of the new items so remove_iterable can pair them up later
+        let mut kept: SmallVec<[u32; 4]> = SmallVec::with_capacity(new.items().len());
+        let mut added = Vec::new();
+        for (pos, item) in new.items().iter().enumerate() {
+            let matched = unmatched.get_mut(&item.get_hash()).and_then(|olds| {
+                let found = olds.iter().position(|(old_item, _)| *old_item == item)?;
+                Some(olds.swap_remove(found).1)
+            });
+            match matched {
+                Some(mapped_id) => kept.push(mapped_id),
+                None => {
+                    kept.push(0);
+                    added.push(pos);
                 }
-                PyIterable::Set(py_set) => {
-                    for item in py_set.bind(py).iter(){
-                        let mut writer = self.get_mapped_ids_writer();
-                        let mut ids_writer = self.get_masked_ids_writer();
-                        writer.get(&obj_id).map(|mapped_id| {
-                            free_id(*mapped_id);
-                            ids_writer.remove(*mapped_id);
-                            self.remove_id(&PyValue::new(item), *mapped_id);
-                        });
-                        writer.remove(&obj_id);
-                    }
-                },
             }
-        })
+        }
+
+        for (item, mapped_id) in unmatched.into_values().flatten() {
+            self.remove_masked(item, mapped_id);
+        }
+        for pos in added {
+            let mapped_id = allocate_id();
+            self.get_mapped_ids_writer().insert(mapped_id, obj_id);
+            self.get_masked_ids_writer().add(mapped_id);
+            self.insert(&new.items()[pos], mapped_id);
+            kept[pos] = mapped_id;
+        }
+        if !kept.is_empty() {
+            self.get_masked_by_parent_writer().insert(obj_id, kept);
+        }
+    }
+
+    fn remove_masked(&self, value: &PyValue, mapped_id: u32) {
+        self.remove_id(value, mapped_id);
+        self.get_mapped_ids_writer().remove(&mapped_id);
+        self.get_masked_ids_writer().remove(mapped_id);
+        free_id(mapped_id);
     }
 
     pub fn remove_id(&self, py_value: &PyValue, idx: u32) {
         match &py_value.get_primitive(){
-            RustCastValue::Int(i) => {
-                // self.remove_exact(py_value, idx);
-                self.remove_num_ordered(Key::Int(*i), idx);
-            }
-            RustCastValue::Float(f) => {
+            primitive @ (RustCastValue::Int(_) | RustCastValue::Float(_)) => {
                 // self.remove_exact(py_value, idx);
-                self.remove_num_ordered(Key::FloatOrdered(OrderedFloat(*f)), idx);
+                if let Some(key) = self.number_key(primitive) {
+                    self.remove_num_ordered(key, idx);
+                }
+                self.remove_flags(py_value, idx);
             }
             RustCastValue::Str(extracted_str) => {
                 self.remove_str(extracted_str, idx);
+                if let Some((space, key)) = self.coerced(extracted_str) {
+                    self.remove_key(space, key, idx);
+                }
                 // self.remove_exact(py_value, idx);
             },
             RustCastValue::Bool(b) => self.remove_bool(*b, idx),
             RustCastValue::Ind(indexable) => {
                 self.remove_exact(py_value, idx);
                 self.nested.remove(&indexable.owned_handle, idx);
+                self.decrement_child_count(self.resolve_masked_id(idx));
             },
-            RustCastValue::Iterable(py_iterable) => {
-                self.remove_iterable(py_iterable, idx);
+            RustCastValue::Iterable(PyIterable::Dict(_)) => {},
+            RustCastValue::Iterable(_) => {
+                self.remove_exact(py_value, idx);
+                self.remove_interval(py_value, idx);
+                self.remove_iterable(py_value.items(), idx);
             },
+            RustCastValue::Encoded(space, key) => self.remove_key(*space, *key, idx),
+            RustCastValue::Wide(num) => self.write_wide().remove(num, idx),
+            RustCastValue::None => self.get_nulls_writer().remove(idx),
+            RustCastValue::Bytes(_) | RustCastValue::Uuid(_) => self.remove_exact(py_value, idx),
             RustCastValue::Unknown => {
                 self.remove_exact(py_value, idx);
+                if let Some(fields) = py_value.fields() {
+                    self.nested.remove(&fields.owned_handle, idx);
+                    self.decrement_child_count(self.resolve_masked_id(idx));
+                }
             },
         };
     }
 
+    fn decrement_child_count(&self, parent_id: u32) {
+        let mut counts = self.get_child_counts_writer();
+        if let Some(count) = counts.get_mut(&parent_id) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                counts.remove(&parent_id);
+            }
+        }
+    }
+
     pub fn unmask_ids(&self, ids: &mut Bitmap) {
         let to_find = self.get_masked_ids_reader().and(ids);
         ids.andnot_inplace(&to_find);
@@ -375,38 +712,74 @@ impl QueryMap {
         self.nested.get_parent_from_ids(child_bm)
     }
 
-    pub fn get_stored_items(&self) -> &Arc<RwLock<Vec<StoredItem>>> {
+    pub fn get_stored_items(&self) -> &Arc<FreezeLock<Vec<StoredItem>>> {
         &self.stored_items
     }
 }
 
 
 impl QueryMap {
-    pub fn read_num_ordered(&self) -> std::sync::RwLockReadGuard<'_, NumericalBitmap> {
+    pub fn read_num_ordered(&self) -> FreezeReadGuard<'_, NumericalBitmap> {
         self.num_ordered.read().unwrap()
     }
     pub fn write_num_ordered(&self) -> std::sync::RwLockWriteGuard<'_, NumericalBitmap> {
         self.num_ordered.write().unwrap()
     }
+    pub fn read_encoded(&self) -> FreezeReadGuard<'_, FxHashMap<KeySpace, NumericalBitmap>> {
+        self.encoded.read().unwrap()
+    }
+    pub fn write_encoded(&self) -> RwLockWriteGuard<'_, FxHashMap<KeySpace, NumericalBitmap>> {
+        self.encoded.write().unwrap()
+    }
+    pub fn read_wide(&self) -> FreezeReadGuard<'_, WideBitmap> {
+        self.wide.read().unwrap()
+    }
+    pub fn write_wide(&self) -> RwLockWriteGuard<'_, WideBitmap> {
+        self.wide.write().unwrap()
+    }
     pub fn write_str_radix_map(&self) -> std::sync::RwLockWriteGuard<'_, PositionalBitmap> {
         self.str_radix_map.write().unwrap()
     }
-    pub fn read_str_radix_map(&self) -> std::sync::RwLockReadGuard<'_, PositionalBitmap> {
+    pub fn read_str_radix_map(&self) -> FreezeReadGuard<'_, PositionalBitmap> {
         self.str_radix_map.read().unwrap()
     }
-    pub fn get_mapped_ids_reader(&self) -> std::sync::RwLockReadGuard<'_, FxHashMap<u32, u32>> {
+    pub fn get_mapped_ids_reader(&self) -> FreezeReadGuard<'_, FxHashMap<u32, u32>> {
         self.mapped_ids.read().unwrap()
     }
     pub fn get_mapped_ids_writer(&self) -> std::sync::RwLockWriteGuard<'_, FxHashMap<u32, u32>> {
         self.mapped_ids.write().unwrap()
     }
-    pub fn get_masked_ids_reader(&self) -> std::sync::RwLockReadGuard<'_, Bitmap> {
+    pub fn get_masked_ids_reader(&self) -> FreezeReadGuard<'_, Bitmap> {
         self.masked_ids.read().unwrap()
     }
     pub fn get_masked_ids_writer(&self) -> std::sync::RwLockWriteGuard<'_, Bitmap> {
         self.masked_ids.write().unwrap()
     }
-    pub fn get_bool_map_reader(&self) -> RwLockReadGuard<'_, BooleanBitmap> {
+    pub fn get_masked_by_parent_reader(&self) -> FreezeReadGuard<'_, FxHashMap<u32, SmallVec<[u32; 4]>>> {
+        self.masked_by_parent.read().unwrap()
+    }
+    pub fn get_masked_by_parent_writer(&self) -> std::sync::RwLockWriteGuard<'_, FxHashMap<u32, SmallVec<[u32; 4]>>> {
+        self.masked_by_parent.write().unwrap()
+    }
+    pub fn get_child_counts_reader(&self) -> FreezeReadGuard<'_, FxHashMap<u32, u32>> {
+        self.child_counts.read().unwrap()
+    }
+    pub fn get_child_counts_writer(&self) -> std::sync::RwLockWriteGuard<'_, FxHashMap<u32, u32>> {
+        self.child_counts.write().unwrap()
+    }
+    pub fn get_present_reader(&self) -> FreezeReadGuard<'_, Bitmap> {
+        self.present.read().unwrap()
+    }
+    pub fn get_present_writer(&self) -> RwLockWriteGuard<'_, Bitmap> {
+        self.present.write().unwrap()
+    }
+    pub fn get_nulls_reader(&self) -> FreezeReadGuard<'_, Bitmap> {
+        self.nulls.read().unwrap()
+    }
+    pub fn get_nulls_writer(&self) -> RwLockWriteGuard<'_, Bitmap> {
+        self.nulls.write().unwrap()
+    }
+    pub fn get_bool_map_reader(&self) -> FreezeReadGuard<'_, BooleanBitmap> {
         self.bool_map.read().unwrap()
     }
     pub fn get_bool_map_writer(&self) -> RwLockWriteGuard<'_, BooleanBitmap> {
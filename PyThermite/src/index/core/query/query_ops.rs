@@ -1,13 +1,13 @@
 
-use std::{collections:: HashSet, ops::Bound};
+use std::{collections:: HashSet, ops::Bound, time::Instant};
 
 use rustc_hash::FxHashMap;
 use croaring::Bitmap;
 use ordered_float::OrderedFloat;
-use pyo3::{PyAny, PyResult, types::{PyAnyMethods, PyString}};
+use pyo3::{PyAny, PyResult, Python, exceptions::PyTypeError, types::{PyAnyMethods, PyString, PyTypeMethods}};
 use smol_str::SmolStr;
 
-use crate::index::{core::{query::QueryMap, structures::{composite_key::CompositeKey128, hybrid_set::HybridSetOps, string_interner::{INTERNER, StrInternerView}}}, interfaces::PyQueryExpr, value::{PyValue, RustCastValue}};
+use crate::index::{core::{encoders::KeySpace, query::{QueryMap, aggregate::{AggFunc, CmpOp}, explain, operators::evaluate_custom, pattern::Pattern, planner::order_and}, structures::{composite_key::CompositeKey128, hybrid_set::HybridSetOps, ordered_bitmap::NumericalBitmap, string_interner::{INTERNER, StrInternerView}, wide_bitmap::WideNum}}, interfaces::PyQueryExpr, value::{PyValue, RustCastValue}};
 
 impl QueryMap {
 
@@ -22,10 +22,18 @@ impl QueryMap {
                 let bits = CompositeKey128::encode_f64_to_float76(OrderedFloat(*f));
                 self.read_num_ordered().get_gt_from_valid(bits, all_valid)
             }
+            RustCastValue::Encoded(space, key) => {
+                self.in_space(*space, |keys| keys.get_gt_from_valid(CompositeKey128::encode_key(*key), all_valid))
+            }
+            RustCastValue::Wide(num) => self.read_num_ordered().get_gt_from_valid(num.floor(), all_valid),
+            RustCastValue::Str(s) => {
+                self.read_str_radix_map().range(Bound::Excluded(s.as_str()), Bound::Unbounded)
+            }
             _ => {
                 Bitmap::new()
             }
         };
+        res.or_inplace(&self.wide_range(Bound::Excluded(val), Bound::Unbounded, all_valid));
         self.unmask_ids(&mut res);
         res
     }
@@ -41,10 +49,18 @@ impl QueryMap {
                 let bits = CompositeKey128::encode_f64_to_float76(OrderedFloat(*f));
                 self.read_num_ordered().get_gte_from_valid(bits, all_valid)
             }
+            RustCastValue::Encoded(space, key) => {
+                self.in_space(*space, |keys| keys.get_gte_from_valid(CompositeKey128::encode_key(*key), all_valid))
+            }
+            RustCastValue::Wide(num) => self.read_num_ordered().get_gte_from_valid(num.ceil(), all_valid),
+            RustCastValue::Str(s) => {
+                self.read_str_radix_map().range(Bound::Included(s.as_str()), Bound::Unbounded)
+            }
             _ => {
                 Bitmap::new()
             }
         };
+        res.or_inplace(&self.wide_range(Bound::Included(val), Bound::Unbounded, all_valid));
         self.unmask_ids(&mut res);
         res
     }
@@ -59,10 +75,18 @@ impl QueryMap {
                 let bits = CompositeKey128::encode_f64_to_float76(OrderedFloat(*f));
                 self.read_num_ordered().get_lt_from_valid(bits, all_valid)
             }
+            RustCastValue::Encoded(space, key) => {
+                self.in_space(*space, |keys| keys.get_lt_from_valid(CompositeKey128::encode_key(*key), all_valid))
+            }
+            RustCastValue::Wide(num) => self.read_num_ordered().get_lt_from_valid(num.ceil(), all_valid),
+            RustCastValue::Str(s) => {
+                self.read_str_radix_map().range(Bound::Unbounded, Bound::Excluded(s.as_str()))
+            }
             _ => {
                 Bitmap::new()
             }
         };
+        res.or_inplace(&self.wide_range(Bound::Unbounded, Bound::Excluded(val), all_valid));
         self.unmask_ids(&mut res);
         res
     }
@@ -78,33 +102,164 @@ impl QueryMap {
                 let bits = CompositeKey128::encode_f64_to_float76(OrderedFloat(*f));
                 self.read_num_ordered().get_lte_from_valid(bits, all_valid)
             }
+            RustCastValue::Encoded(space, key) => {
+                self.in_space(*space, |keys| keys.get_lte_from_valid(CompositeKey128::encode_key(*key), all_valid))
+            }
+            RustCastValue::Wide(num) => self.read_num_ordered().get_lte_from_valid(num.floor(), all_valid),
+            RustCastValue::Str(s) => {
+                self.read_str_radix_map().range(Bound::Unbounded, Bound::Included(s.as_str()))
+            }
             _ => {
                 Bitmap::new()
             }
         };
+        res.or_inplace(&self.wide_range(Bound::Unbounded, Bound::Included(val), all_valid));
         self.unmask_ids(&mut res);
         res
     }
 
     pub fn bt(&self, lower: &RustCastValue, upper: &RustCastValue, all_valid: &Bitmap) -> Bitmap {
-        let low_range = match lower {
-            RustCastValue::Int(i) => CompositeKey128::encode_i64_to_float76(*i),
-            RustCastValue::Float(f) => CompositeKey128::encode_f64_to_float76(OrderedFloat(*f)),
-            _ => todo!(),
+        if let (RustCastValue::Str(lower), RustCastValue::Str(upper)) = (lower, upper) {
+            let mut res = self.read_str_radix_map().range(Bound::Included(lower.as_str()), Bound::Included(upper.as_str()));
+            self.unmask_ids(&mut res);
+            return res;
+        }
+        let (low_space, low_range) = match lower {
+            RustCastValue::Int(i) => (KeySpace::Number, CompositeKey128::encode_i64_to_float76(*i)),
+            RustCastValue::Float(f) => (KeySpace::Number, CompositeKey128::encode_f64_to_float76(OrderedFloat(*f))),
+            RustCastValue::Encoded(space, key) => (*space, CompositeKey128::encode_key(*key)),
+            RustCastValue::Wide(num) => (KeySpace::Number, num.ceil()),
+            // rejected by QueryExpr::check_types before evaluation
+            _ => return Bitmap::new(),
+        };
+
+        let (upper_space, upper_range) = match upper {
+            RustCastValue::Int(i) => (KeySpace::Number, CompositeKey128::encode_i64_to_float76(*i)),
+            RustCastValue::Float(f) => (KeySpace::Number, CompositeKey128::encode_f64_to_float76(OrderedFloat(*f))),
+            RustCastValue::Encoded(space, key) => (*space, CompositeKey128::encode_key(*key)),
+            RustCastValue::Wide(num) => (KeySpace::Number, num.floor()),
+            _ => return Bitmap::new(),
+        };
+        // bounds of different spaces hold nothing between them
+        if low_space != upper_space {
+            return Bitmap::new();
+        }
+
+        let mut res = self.in_space(low_space, |keys| keys.get_bt_from_valid(low_range, upper_range, all_valid));
+        res.or_inplace(&self.wide_range(Bound::Included(lower), Bound::Included(upper), all_valid));
+        self.unmask_ids(&mut res);
+        res
+    }
+
+    // f over the ordered keys of space, numbers from num_ordered, empty for a space nothing holds
+    fn in_space(&self, space: KeySpace, f: impl FnOnce(&NumericalBitmap) -> Bitmap) -> Bitmap {
+        match space {
+            KeySpace::Number => f(&self.read_num_ordered()),
+            space => self.read_encoded().get(&space).map(f).unwrap_or_default(),
+        }
+    }
+
+    // the wide map's side of a numeric comparison, empty for bounds that aren't numbers
+    fn wide_range(&self, lower: Bound<&RustCastValue>, upper: Bound<&RustCastValue>, all_valid: &Bitmap) -> Bitmap {
+        let wide = self.read_wide();
+        if wide.is_empty() {
+            return Bitmap::new();
+        }
+        let (Some(lower), Some(upper)) = (wide_bound(lower, false), wide_bound(upper, true)) else {
+            return Bitmap::new();
+        };
+        wide.range(lower.as_ref(), upper.as_ref(), all_valid)
+    }
+
+    // empty unless attr was declared an interval
+    pub fn overlaps(&self, lower: &PyValue, upper: &PyValue, all_valid: &Bitmap) -> Bitmap {
+        let (Some(intervals), Some(lower), Some(upper)) = (&self.intervals, Self::interval_key(lower), Self::interval_key(upper)) else {
+            return Bitmap::new();
         };
+        intervals.read().unwrap().overlaps(lower, upper, all_valid)
+    }
+
+    pub fn exists(&self, all_valid: &Bitmap) -> Bitmap {
+        self.get_present_reader().and(all_valid)
+    }
 
-        let upper_range = match upper {
-            RustCastValue::Int(i) => CompositeKey128::encode_i64_to_float76(*i),
-            RustCastValue::Float(f) => CompositeKey128::encode_f64_to_float76(OrderedFloat(*f)),
-            _ => todo!(),
+    // ints with every bit of mask set, taken from the bit slices when attr was declared with flags
+    pub fn flags_all(&self, mask: i64, all_valid: &Bitmap) -> Bitmap {
+        let mut res = match &self.flags {
+            Some(flags) => {
+                let flags = flags.read().unwrap();
+                let mut bits = set_bits(mask);
+                match bits.next() {
+                    Some(first) => bits.fold(flags.ones(first).clone(), |mut res, bit| {
+                        res.and_inplace(flags.ones(bit));
+                        res
+                    }),
+                    None => flags.ids(),
+                }
+            },
+            None => self.scan_ints(|held| held & mask == mask),
         };
+        self.unmask_ids(&mut res);
+        res.and_inplace(all_valid);
+        res
+    }
 
-        let reader = self.read_num_ordered();
-        let mut res = reader.get_bt_from_valid(low_range, upper_range, all_valid);
+    // ints with at least one bit of mask set
+    pub fn flags_any(&self, mask: i64, all_valid: &Bitmap) -> Bitmap {
+        let mut res = match &self.flags {
+            Some(flags) => {
+                let flags = flags.read().unwrap();
+                let slices: Vec<&Bitmap> = set_bits(mask).map(|bit| flags.ones(bit)).collect();
+                Bitmap::fast_or(&slices)
+            },
+            None => self.scan_ints(|held| held & mask != 0),
+        };
         self.unmask_ids(&mut res);
+        res.and_inplace(all_valid);
         res
     }
 
+    // ints congruent to remainder, python's sign convention so -1 % 8 == 7. read from the bit slices
+    // when attr was declared with flags
+    pub fn mod_eq(&self, divisor: u64, remainder: u64, all_valid: &Bitmap) -> Bitmap {
+        let mut res = match &self.flags {
+            Some(flags) => flags_mod_eq(&flags.read().unwrap(), divisor, remainder),
+            None => self.scan_ints(|held| held.rem_euclid(divisor as i64) as u64 == remainder),
+        };
+        self.unmask_ids(&mut res);
+        res.and_inplace(all_valid);
+        res
+    }
+
+    // attributes not declared with flags decode every whole number they hold, exact up to 2**53
+    fn scan_ints(&self, test: impl Fn(i64) -> bool) -> Bitmap {
+        let num = self.read_num_ordered();
+        num.values_from_valid(&num.ids()).into_iter()
+            .filter_map(|(id, bits)| {
+                let held = CompositeKey128::decode_float76(bits);
+                (held.fract() == 0.0 && test(held as i64)).then_some(id)
+            })
+            .collect()
+    }
+
+    // objects holding any of values, one lookup each straight from the exact maps and ordered tree
+    pub fn eq_any(&self, values: &[PyValue], all_valid: &Bitmap) -> Bitmap {
+        let mut res = Bitmap::new();
+        for value in values {
+            res.or_inplace(&self.eq(value, all_valid));
+        }
+        res
+    }
+
+    // the objects of all_valid not holding val, those without the attribute included
+    pub fn ne(&self, val: &PyValue, all_valid: &Bitmap) -> Bitmap {
+        all_valid.andnot(&self.eq(val, all_valid))
+    }
+
+    pub fn nin(&self, values: &[PyValue], all_valid: &Bitmap) -> Bitmap {
+        all_valid.andnot(&self.eq_any(values, all_valid))
+    }
+
     pub fn eq(&self, val: &PyValue, all_valid: &Bitmap) -> Bitmap {
 
         let mut res = match val.get_primitive() {
@@ -116,14 +271,20 @@ impl QueryMap {
                 let bits = CompositeKey128::encode_f64_to_float76(OrderedFloat(*f));
                 self.read_num_ordered().get_exact(bits)
             }
+            RustCastValue::Encoded(space, key) => {
+                self.in_space(*space, |keys| keys.get_exact(CompositeKey128::encode_key(*key)))
+            }
+            // never equal to a number num_ordered holds exactly
+            RustCastValue::Wide(num) => self.read_wide().get_exact(num),
             RustCastValue::Str(extracted_str) => {
                 self.read_str_radix_map().get_exact(extracted_str)
             }
             RustCastValue::Bool(b) => {
                 self.get_bool_map_reader().get_exact(*b).clone()
             }
+            RustCastValue::None => self.get_nulls_reader().clone(),
             _ => {
-                if let Some(res) = self.exact.get(val){
+                if let Some(res) = self.exact.get(&self.exact_key(val)){
                     res.as_bitmap()
                 } else {
                     Bitmap::new()
@@ -171,6 +332,12 @@ impl QueryMap {
         res
     }
 
+    fn matches(&self, pattern: &Pattern) -> Bitmap {
+        let mut res = self.read_str_radix_map().matches(&pattern.prefix, &pattern.suffix, |s| pattern.is_match(s));
+        self.unmask_ids(&mut res);
+        res
+    }
+
 }
 
 #[derive(Clone, Debug)]
@@ -180,6 +347,7 @@ pub enum QueryExpr {
     Not(Box<QueryExpr>),
 
     In(SmolStr, Vec<PyValue>),
+    Nin(SmolStr, Vec<PyValue>),
     And(Vec<QueryExpr>),
     Or(Vec<QueryExpr>),
     // numeric ops
@@ -189,9 +357,26 @@ pub enum QueryExpr {
     Le(SmolStr, PyValue),
     Bt(SmolStr, PyValue, PyValue),
     // string ops
-    StartsWi(SmolStr, PyValue),
-    EndsWi(SmolStr, PyValue),
+    StartsWith(SmolStr, PyValue),
+    EndsWith(SmolStr, PyValue),
     Contains(SmolStr, PyValue),
+    Matches(SmolStr, Pattern),
+    // interval ops
+    Overlaps(SmolStr, PyValue, PyValue),
+    ContainsPoint(SmolStr, PyValue),
+    // nested collection ops
+    Agg(SmolStr, AggFunc, SmolStr, CmpOp, f64),
+    CountChildren(SmolStr, CmpOp, f64),
+    // presence ops, whatever the value
+    Exists(SmolStr),
+    Missing(SmolStr),
+    // bitwise ops on ints
+    FlagsAll(SmolStr, i64),
+    FlagsAny(SmolStr, i64),
+    // attr % divisor == remainder on ints, python's sign convention
+    ModEq(SmolStr, u64, u64),
+    // operator registered by name through register_operator, (attr, name, value)
+    Custom(SmolStr, SmolStr, PyValue),
 }
 
 impl QueryExpr {
@@ -201,9 +386,11 @@ impl QueryExpr {
             QueryExpr::Ne(_, _) => 1,
             QueryExpr::Not(_) => 2,
             QueryExpr::In(_, _) => 3,
-            QueryExpr::StartsWi(_, _) => 4,
-            QueryExpr::EndsWi(_, _) => 5,
+            QueryExpr::Nin(_, _) => 3,
+            QueryExpr::StartsWith(_, _) => 4,
+            QueryExpr::EndsWith(_, _) => 5,
             QueryExpr::Contains(_, _) => 6,
+            QueryExpr::Matches(_, _) => 6,
             QueryExpr::And(_) => 7,
             QueryExpr::Or(_) => 8,
             QueryExpr::Lt(_, _) => 9,
@@ -211,10 +398,237 @@ impl QueryExpr {
             QueryExpr::Gt(_, _) => 11,
             QueryExpr::Ge(_, _) => 12,
             QueryExpr::Bt(_, _, _) => 13,
+            QueryExpr::Overlaps(_, _, _) => 13,
+            QueryExpr::ContainsPoint(_, _) => 13,
+            QueryExpr::Agg(_, _, _, _, _) => 14,
+            QueryExpr::CountChildren(_, _, _) => 14,
+            QueryExpr::Exists(_) => 1,
+            QueryExpr::Missing(_) => 2,
+            QueryExpr::FlagsAll(_, _) => 4,
+            QueryExpr::FlagsAny(_, _) => 4,
+            QueryExpr::ModEq(_, _, _) => 6,
+            QueryExpr::Custom(_, _, _) => 14,
+        }
+    }
+
+    // rewrites every reference to old (or a path below it) to new, for saved queries across renames
+    pub fn rebind(&self, old: &str, new: &str) -> QueryExpr {
+        let attr = |a: &SmolStr| rebind_attr(a, old, new);
+        match self {
+            QueryExpr::Eq(a, v) => QueryExpr::Eq(attr(a), v.clone()),
+            QueryExpr::Ne(a, v) => QueryExpr::Ne(attr(a), v.clone()),
+            QueryExpr::Not(inner) => QueryExpr::Not(Box::new(inner.rebind(old, new))),
+            QueryExpr::In(a, vals) => QueryExpr::In(attr(a), vals.clone()),
+            QueryExpr::Nin(a, vals) => QueryExpr::Nin(attr(a), vals.clone()),
+            QueryExpr::And(exprs) => QueryExpr::And(exprs.iter().map(|e| e.rebind(old, new)).collect()),
+            QueryExpr::Or(exprs) => QueryExpr::Or(exprs.iter().map(|e| e.rebind(old, new)).collect()),
+            QueryExpr::Gt(a, v) => QueryExpr::Gt(attr(a), v.clone()),
+            QueryExpr::Ge(a, v) => QueryExpr::Ge(attr(a), v.clone()),
+            QueryExpr::Lt(a, v) => QueryExpr::Lt(attr(a), v.clone()),
+            QueryExpr::Le(a, v) => QueryExpr::Le(attr(a), v.clone()),
+            QueryExpr::Bt(a, lower, upper) => QueryExpr::Bt(attr(a), lower.clone(), upper.clone()),
+            QueryExpr::StartsWith(a, v) => QueryExpr::StartsWith(attr(a), v.clone()),
+            QueryExpr::EndsWith(a, v) => QueryExpr::EndsWith(attr(a), v.clone()),
+            QueryExpr::Contains(a, v) => QueryExpr::Contains(attr(a), v.clone()),
+            QueryExpr::Matches(a, pattern) => QueryExpr::Matches(attr(a), pattern.clone()),
+            QueryExpr::Overlaps(a, lower, upper) => QueryExpr::Overlaps(attr(a), lower.clone(), upper.clone()),
+            QueryExpr::ContainsPoint(a, v) => QueryExpr::ContainsPoint(attr(a), v.clone()),
+            QueryExpr::Agg(a, func, child_attr, op, threshold) => {
+                // child_attr is relative to a, so rebind the full path and split it back
+                let full = rebind_attr(&SmolStr::new(format!("{}.{}", a, child_attr)), old, new);
+                let new_attr = attr(a);
+                let child_attr = full
+                    .strip_prefix(new_attr.as_str())
+                    .and_then(|rest| rest.strip_prefix('.'))
+                    .map(SmolStr::new)
+                    .unwrap_or_else(|| child_attr.clone());
+                QueryExpr::Agg(new_attr, *func, child_attr, *op, *threshold)
+            },
+            QueryExpr::CountChildren(a, op, threshold) => QueryExpr::CountChildren(attr(a), *op, *threshold),
+            QueryExpr::Exists(a) => QueryExpr::Exists(attr(a)),
+            QueryExpr::Missing(a) => QueryExpr::Missing(attr(a)),
+            QueryExpr::FlagsAll(a, mask) => QueryExpr::FlagsAll(attr(a), *mask),
+            QueryExpr::FlagsAny(a, mask) => QueryExpr::FlagsAny(attr(a), *mask),
+            QueryExpr::ModEq(a, divisor, remainder) => QueryExpr::ModEq(attr(a), *divisor, *remainder),
+            QueryExpr::Custom(a, name, v) => QueryExpr::Custom(attr(a), name.clone(), v.clone()),
+        }
+    }
+
+    // rejects operator / value type combinations the index cannot answer
+    pub fn check_types(&self) -> PyResult<()> {
+        match self {
+            QueryExpr::Gt(attr, v) => expect_ordered("gt", attr, v),
+            QueryExpr::Ge(attr, v) => expect_ordered("ge", attr, v),
+            QueryExpr::Lt(attr, v) => expect_ordered("lt", attr, v),
+            QueryExpr::Le(attr, v) => expect_ordered("le", attr, v),
+            QueryExpr::Bt(attr, lower, upper) => {
+                if matches!(lower.get_primitive(), RustCastValue::Str(_)) {
+                    expect_str("bt", attr, upper)
+                } else {
+                    expect_numeric("bt", attr, lower)?;
+                    expect_numeric("bt", attr, upper)
+                }
+            },
+            QueryExpr::Overlaps(attr, lower, upper) => {
+                expect_numeric("overlaps", attr, lower)?;
+                expect_numeric("overlaps", attr, upper)
+            },
+            QueryExpr::ContainsPoint(attr, v) => expect_numeric("contains_point", attr, v),
+            QueryExpr::StartsWith(attr, v) => expect_str("starts_with", attr, v),
+            QueryExpr::EndsWith(attr, v) => expect_str("ends_with", attr, v),
+            QueryExpr::Contains(attr, v) => expect_str("contains", attr, v),
+            QueryExpr::Not(inner) => inner.check_types(),
+            QueryExpr::And(exprs) | QueryExpr::Or(exprs) => {
+                exprs.iter().try_for_each(|e| e.check_types())
+            },
+            _ => Ok(()),
+        }
+    }
+
+    // visits every constant in a fixed depth first order
+    pub fn for_each_value_mut<F: FnMut(&mut PyValue)>(&mut self, f: &mut F) {
+        match self {
+            QueryExpr::Eq(_, v)
+            | QueryExpr::Ne(_, v)
+            | QueryExpr::Gt(_, v)
+            | QueryExpr::Ge(_, v)
+            | QueryExpr::Lt(_, v)
+            | QueryExpr::Le(_, v)
+            | QueryExpr::StartsWith(_, v)
+            | QueryExpr::EndsWith(_, v)
+            | QueryExpr::Contains(_, v)
+            | QueryExpr::ContainsPoint(_, v)
+            | QueryExpr::Custom(_, _, v) => f(v),
+            QueryExpr::Bt(_, lower, upper) | QueryExpr::Overlaps(_, lower, upper) => {
+                f(lower);
+                f(upper);
+            },
+            QueryExpr::In(_, vals) | QueryExpr::Nin(_, vals) => {
+                for v in vals.iter_mut() {
+                    f(v);
+                }
+            },
+            QueryExpr::Not(inner) => inner.for_each_value_mut(f),
+            QueryExpr::And(exprs) | QueryExpr::Or(exprs) => {
+                for e in exprs.iter_mut() {
+                    e.for_each_value_mut(f);
+                }
+            },
+            QueryExpr::Matches(_, _) | QueryExpr::Agg(_, _, _, _, _) | QueryExpr::CountChildren(_, _, _)
+            | QueryExpr::Exists(_) | QueryExpr::Missing(_) | QueryExpr::FlagsAll(_, _) | QueryExpr::FlagsAny(_, _) | QueryExpr::ModEq(_, _, _) => {},
         }
     }
 }
 
+fn set_bits(mask: i64) -> impl Iterator<Item = usize> {
+    (0..64).filter(move |bit| (mask >> bit) & 1 == 1)
+}
+
+// a power of two divisor is its low bits alone. otherwise ids are split into residue classes from the
+// top bit down, a prefix p becoming 2p + bit, and negatives, held as x + 2^64, shifted back at the end
+fn flags_mod_eq(flags: &NumericalBitmap, divisor: u64, remainder: u64) -> Bitmap {
+    if divisor.is_power_of_two() {
+        return (0..divisor.trailing_zeros() as usize).fold(flags.ids(), |mut res, bit| {
+            if (remainder >> bit) & 1 == 1 {
+                res.and_inplace(flags.ones(bit));
+            } else {
+                res.andnot_inplace(flags.ones(bit));
+            }
+            res
+        });
+    }
+    let mut classes: FxHashMap<u64, Bitmap> = FxHashMap::default();
+    classes.insert(0, flags.ids());
+    for bit in (0..64).rev() {
+        let ones = flags.ones(bit);
+        let mut next: FxHashMap<u64, Bitmap> = FxHashMap::default();
+        for (residue, ids) in classes {
+            let doubled = (residue as u128 * 2 % divisor as u128) as u64;
+            let (set, clear) = (ids.and(ones), ids.andnot(ones));
+            if !clear.is_empty() {
+                next.entry(doubled).or_default().or_inplace(&clear);
+            }
+            if !set.is_empty() {
+                next.entry((doubled + 1) % divisor).or_default().or_inplace(&set);
+            }
+        }
+        classes = next;
+    }
+    let negative = flags.ones(63);
+    let wrap = (u64::MAX % divisor + 1) % divisor;
+    let shifted = ((remainder as u128 + wrap as u128) % divisor as u128) as u64;
+    let mut res = classes.get(&remainder).map(|ids| ids.andnot(negative)).unwrap_or_default();
+    if let Some(ids) = classes.get(&shifted) {
+        res.or_inplace(&ids.and(negative));
+    }
+    res
+}
+
+fn value_type_name(value: &PyValue) -> String {
+    Python::with_gil(|py| {
+        value.get_obj(py).bind(py).get_type().name()
+            .map(|name| name.to_string())
+            .unwrap_or_else(|_| "unknown".to_string())
+    })
+}
+
+// infinities leave the side they point away from open and match nothing on the other
+fn wide_bound(bound: Bound<&RustCastValue>, upper: bool) -> Option<Bound<WideNum>> {
+    let num = match bound {
+        Bound::Included(value) | Bound::Excluded(value) => match value {
+            RustCastValue::Int(i) => WideNum::from_i64(*i),
+            RustCastValue::Float(f) if f.is_infinite() => return (f.is_sign_positive() == upper).then_some(Bound::Unbounded),
+            RustCastValue::Float(f) => WideNum::from_f64(*f)?,
+            RustCastValue::Encoded(KeySpace::Number, key) => WideNum::from_key(*key)?,
+            RustCastValue::Wide(num) => WideNum::clone(num),
+            _ => return None,
+        },
+        Bound::Unbounded => return Some(Bound::Unbounded),
+    };
+    Some(match bound {
+        Bound::Included(_) => Bound::Included(num),
+        _ => Bound::Excluded(num),
+    })
+}
+
+fn expect_numeric(op: &str, attr: &SmolStr, value: &PyValue) -> PyResult<()> {
+    match value.get_primitive() {
+        RustCastValue::Int(_) | RustCastValue::Float(_) | RustCastValue::Encoded(..) | RustCastValue::Wide(_) => Ok(()),
+        _ => Err(PyTypeError::new_err(format!(
+            "unsupported operand for {} on '{}': expected int, float or an encoded type, got {}", op, attr, value_type_name(value)
+        ))),
+    }
+}
+
+// numbers order numerically and strings by code point
+fn expect_ordered(op: &str, attr: &SmolStr, value: &PyValue) -> PyResult<()> {
+    match value.get_primitive() {
+        RustCastValue::Int(_) | RustCastValue::Float(_) | RustCastValue::Encoded(..) | RustCastValue::Wide(_) | RustCastValue::Str(_) => Ok(()),
+        _ => Err(PyTypeError::new_err(format!(
+            "unsupported operand for {} on '{}': expected int, float, str or an encoded type, got {}", op, attr, value_type_name(value)
+        ))),
+    }
+}
+
+fn expect_str(op: &str, attr: &SmolStr, value: &PyValue) -> PyResult<()> {
+    match value.get_primitive() {
+        RustCastValue::Str(_) => Ok(()),
+        _ => Err(PyTypeError::new_err(format!(
+            "unsupported operand for {} on '{}': expected str, got {}", op, attr, value_type_name(value)
+        ))),
+    }
+}
+
+fn rebind_attr(attr: &SmolStr, old: &str, new: &str) -> SmolStr {
+    if attr == old {
+        return SmolStr::new(new);
+    }
+    match attr.strip_prefix(old) {
+        Some(rest) if rest.starts_with('.') => SmolStr::new(format!("{}{}", new, rest)),
+        _ => attr.clone(),
+    }
+}
+
 pub fn attr_parts(attr: SmolStr) -> (SmolStr, Option<SmolStr>) {
     if let Some(pos) = attr.find('.') {
         let (base, rest) = attr.split_at(pos);
@@ -230,11 +644,28 @@ pub fn evaluate_nested_query(
     expr: &QueryExpr,
 ) -> Bitmap {
     let wrapper = PyQueryExpr{inner: expr.clone()};
+    let hop = explain::enter_hop();
     let reduced = nested_map.nested.reduced_query(wrapper);
-    nested_map.get_allowed_parents(&reduced.allowed_items)
+    let rollup = hop.then(Instant::now);
+    let mut parents = nested_map.get_allowed_parents(&reduced.allowed_items);
+    // children of iterable values are registered under masked ids
+    nested_map.unmask_ids(&mut parents);
+    if let Some(rollup) = rollup {
+        explain::leave_hop(reduced.allowed_items.cardinality(), rollup.elapsed());
+    }
+    parents
 }
 
-pub fn evaluate_query(
+#[inline]
+pub fn evaluate_uncached(
+    index: &Vec<QueryMap>,
+    all_valid: &Bitmap,
+    expr: &QueryExpr,
+) -> Bitmap {
+    explain::traced(expr, || evaluate_clause(index, all_valid, expr))
+}
+
+fn evaluate_clause(
     index: &Vec<QueryMap>,
     all_valid: &Bitmap,
     expr: &QueryExpr,
@@ -254,12 +685,19 @@ pub fn evaluate_query(
                 Bitmap::new()
             }
         }
-        QueryExpr::Ne(attr, value ) => {
-            evaluate_query(
-                index,
-                all_valid,
-                &QueryExpr::Not(Box::new(QueryExpr::Eq(attr.clone(), value.clone())))
-            )
+        QueryExpr::Ne(attr, value) => {
+            let (base_attr, nested_attr) = attr_parts(attr.clone());
+            match index.get(INTERNER.intern(&base_attr) as usize) {
+                Some(qm) if nested_attr.is_none() => qm.ne(value, all_valid),
+                _ => all_valid.andnot(&ruled_out(index, all_valid, expr).unwrap_or_default()),
+            }
+        }
+        QueryExpr::Nin(attr, values) => {
+            let (base_attr, nested_attr) = attr_parts(attr.clone());
+            match index.get(INTERNER.intern(&base_attr) as usize) {
+                Some(qm) if nested_attr.is_none() => qm.nin(values, all_valid),
+                _ => all_valid.andnot(&ruled_out(index, all_valid, expr).unwrap_or_default()),
+            }
         }
         QueryExpr::In(attr, values) => {
             let (base_attr, nested_attr) = attr_parts(attr.clone());
@@ -271,16 +709,8 @@ pub fn evaluate_query(
                     let query = QueryExpr::In(nested_attr, values.clone());
                     result = evaluate_nested_query(qm, &query);
                 } else {
-                    result = Bitmap::new();
-                    for v in values {
-                        let mut r = evaluate_query(
-                            index,
-                            all_valid,
-                            &QueryExpr::Eq(attr.clone(), v.clone())
-                        );
-                        r.and_inplace(all_valid);
-                        result.or_inplace(&r);
-                    }
+                    result = qm.eq_any(values, all_valid);
+                    result.and_inplace(all_valid);
                 }
 
             } else {
@@ -358,8 +788,36 @@ pub fn evaluate_query(
                 Bitmap::new()
             }
         }
+        QueryExpr::Overlaps(attr, lower, upper) => {
+            let (base_attr, nested_attr) = attr_parts(attr.clone());
+            let base_attr_id = INTERNER.intern(&base_attr) as usize;
+            if let Some(qm) = index.get(base_attr_id) {
+                if let Some(nested_attr) = nested_attr {
+                    let query = QueryExpr::Overlaps(nested_attr, lower.clone(), upper.clone());
+                    evaluate_nested_query(qm, &query)
+                } else {
+                    qm.overlaps(lower, upper, all_valid)
+                }
+            } else {
+                Bitmap::new()
+            }
+        }
+        QueryExpr::ContainsPoint(attr, point) => {
+            let (base_attr, nested_attr) = attr_parts(attr.clone());
+            let base_attr_id = INTERNER.intern(&base_attr) as usize;
+            if let Some(qm) = index.get(base_attr_id) {
+                if let Some(nested_attr) = nested_attr {
+                    let query = QueryExpr::ContainsPoint(nested_attr, point.clone());
+                    evaluate_nested_query(qm, &query)
+                } else {
+                    qm.overlaps(point, point, all_valid)
+                }
+            } else {
+                Bitmap::new()
+            }
+        }
         QueryExpr::Not(inner) => {
-            let inner_bm = evaluate_query(index, all_valid, inner);
+            let inner_bm = evaluate_uncached(index, all_valid, inner);
                 all_valid - &inner_bm
         }
         QueryExpr::And(exprs) => {
@@ -389,13 +847,13 @@ pub fn evaluate_query(
                 .unwrap_or_else(Bitmap::new) // handle empty exprs
         }
         
-        QueryExpr::StartsWi(attr, py_value) => {
+        QueryExpr::StartsWith(attr, py_value) => {
             let (base_attr, nested_attr) = attr_parts(attr.clone());
             let base_attr_id = INTERNER.intern(&base_attr) as usize;
 
             if let Some(qm) = index.get(base_attr_id) {
                 if let Some(nested_attr) = nested_attr {
-                    let query = QueryExpr::StartsWi(nested_attr, py_value.clone());
+                    let query = QueryExpr::StartsWith(nested_attr, py_value.clone());
                     evaluate_nested_query(qm, &query)
                 } else {
                     qm.starts_with(py_value.get_primitive(), all_valid)
@@ -404,13 +862,13 @@ pub fn evaluate_query(
                 Bitmap::new()
             }
         },
-        QueryExpr::EndsWi(attr, py_value) => {
+        QueryExpr::EndsWith(attr, py_value) => {
             let (base_attr, nested_attr) = attr_parts(attr.clone());
             let base_attr_id = INTERNER.intern(&base_attr) as usize;
 
             if let Some(qm) = index.get(base_attr_id) {
                 if let Some(nested_attr) = nested_attr {
-                    let query = QueryExpr::EndsWi(nested_attr, py_value.clone());
+                    let query = QueryExpr::EndsWith(nested_attr, py_value.clone());
                     evaluate_nested_query(qm, &query)
                 } else {
                     qm.ends_with(py_value.get_primitive(), all_valid)
@@ -434,6 +892,93 @@ pub fn evaluate_query(
                 Bitmap::new()
             }
         },
+        QueryExpr::Matches(attr, pattern) => {
+            let (base_attr, nested_attr) = attr_parts(attr.clone());
+            let base_attr_id = INTERNER.intern(&base_attr) as usize;
+
+            if let Some(qm) = index.get(base_attr_id) {
+                if let Some(nested_attr) = nested_attr {
+                    let query = QueryExpr::Matches(nested_attr, pattern.clone());
+                    evaluate_nested_query(qm, &query)
+                } else {
+                    qm.matches(pattern)
+                }
+            } else {
+                Bitmap::new()
+            }
+        },
+        QueryExpr::Agg(attr, func, child_attr, op, threshold) => {
+            let (base_attr, nested_attr) = attr_parts(attr.clone());
+            let base_attr_id = INTERNER.intern(&base_attr) as usize;
+
+            if let Some(qm) = index.get(base_attr_id) {
+                if let Some(nested_attr) = nested_attr {
+                    let query = QueryExpr::Agg(nested_attr, *func, child_attr.clone(), *op, *threshold);
+                    evaluate_nested_query(qm, &query)
+                } else {
+                    qm.agg(*func, child_attr, *op, *threshold, all_valid)
+                }
+            } else if let Some(empty) = func.empty_value() {
+                // no children indexed at all
+                if op.matches(empty, *threshold) { all_valid.clone() } else { Bitmap::new() }
+            } else {
+                Bitmap::new()
+            }
+        },
+        QueryExpr::CountChildren(attr, op, threshold) => {
+            let (base_attr, nested_attr) = attr_parts(attr.clone());
+            let base_attr_id = INTERNER.intern(&base_attr) as usize;
+
+            if let Some(qm) = index.get(base_attr_id) {
+                if let Some(nested_attr) = nested_attr {
+                    let query = QueryExpr::CountChildren(nested_attr, *op, *threshold);
+                    evaluate_nested_query(qm, &query)
+                } else {
+                    qm.count_children(*op, *threshold, all_valid)
+                }
+            } else if op.matches(0.0, *threshold) {
+                all_valid.clone()
+            } else {
+                Bitmap::new()
+            }
+        },
+        QueryExpr::Exists(attr) => {
+            let (base_attr, nested_attr) = attr_parts(attr.clone());
+            match (index.get(INTERNER.intern(&base_attr) as usize), nested_attr) {
+                (Some(qm), Some(nested_attr)) => evaluate_nested_query(qm, &QueryExpr::Exists(nested_attr)),
+                (Some(qm), None) => qm.exists(all_valid),
+                (None, _) => Bitmap::new(),
+            }
+        },
+        QueryExpr::Missing(_) => all_valid.andnot(&ruled_out(index, all_valid, expr).unwrap_or_default()),
+        QueryExpr::FlagsAll(attr, mask) | QueryExpr::FlagsAny(attr, mask) => {
+            let (base_attr, nested_attr) = attr_parts(attr.clone());
+            match (index.get(INTERNER.intern(&base_attr) as usize), nested_attr) {
+                (Some(qm), Some(nested_attr)) => evaluate_nested_query(qm, &expr.with_attr(nested_attr)),
+                (Some(qm), None) if matches!(expr, QueryExpr::FlagsAll(..)) => qm.flags_all(*mask, all_valid),
+                (Some(qm), None) => qm.flags_any(*mask, all_valid),
+                (None, _) => Bitmap::new(),
+            }
+        },
+        QueryExpr::ModEq(attr, divisor, remainder) => {
+            let (base_attr, nested_attr) = attr_parts(attr.clone());
+            match (index.get(INTERNER.intern(&base_attr) as usize), nested_attr) {
+                (Some(qm), Some(nested_attr)) => evaluate_nested_query(qm, &expr.with_attr(nested_attr)),
+                (Some(qm), None) => qm.mod_eq(*divisor, *remainder, all_valid),
+                (None, _) => Bitmap::new(),
+            }
+        },
+        QueryExpr::Custom(attr, name, value) => {
+            let (base_attr, nested_attr) = attr_parts(attr.clone());
+            let base_attr_id = INTERNER.intern(&base_attr);
+            match (index.get(base_attr_id as usize), nested_attr) {
+                (Some(qm), Some(nested_attr)) => {
+                    evaluate_nested_query(qm, &QueryExpr::Custom(nested_attr, name.clone(), value.clone()))
+                },
+                (Some(qm), None) => evaluate_custom(qm, base_attr_id, name, value, all_valid),
+                (None, _) => Bitmap::new(),
+            }
+        },
     }
 }
 
@@ -444,7 +989,7 @@ pub fn evaluate_queries_vec(
 ) -> Vec<Bitmap> {
     exprs
         .iter()
-        .map(|expr| evaluate_query(index, &all_valid, expr))
+        .map(|expr| evaluate_uncached(index, &all_valid, expr))
         .collect()
 }
 
@@ -453,16 +998,55 @@ pub fn evaluate_and_queries_vec(
     all_valid: &Bitmap,
     exprs: &Vec<QueryExpr>,
 ) -> Bitmap {
+    let ordered = order_and(index, all_valid, exprs);
     let mut all_valid = all_valid.clone();
-
-    let mut ordered: Vec<&QueryExpr> = exprs.iter().collect();
-    ordered.sort_by_key(|expr| expr.estimated_cost());
     for o in ordered {
-        all_valid.and_inplace(&evaluate_query(index, &all_valid, o));
+        if all_valid.is_empty() {
+            break;
+        }
+        match excluded(index, &all_valid, o) {
+            Some(ruled_out) => all_valid.andnot_inplace(&ruled_out),
+            None => all_valid.and_inplace(&evaluate_uncached(index, &all_valid, o)),
+        }
     }
     all_valid
 }
 
+// the objects a ne, nin or missing clause rules out, so an And drops them from its running result in
+// place instead of building the complement first. None for any other clause
+pub fn excluded(index: &Vec<QueryMap>, all_valid: &Bitmap, expr: &QueryExpr) -> Option<Bitmap> {
+    if !matches!(expr, QueryExpr::Ne(..) | QueryExpr::Nin(..) | QueryExpr::Missing(..)) {
+        return None;
+    }
+    // traced with the objects the clause lets through
+    let ruled_out = explain::traced_with(
+        expr,
+        || ruled_out(index, all_valid, expr).unwrap_or_default(),
+        |ruled_out| all_valid.andnot_cardinality(ruled_out),
+    );
+    Some(ruled_out)
+}
+
+fn ruled_out(index: &Vec<QueryMap>, all_valid: &Bitmap, expr: &QueryExpr) -> Option<Bitmap> {
+    if let QueryExpr::Missing(attr) = expr {
+        return Some(evaluate_uncached(index, all_valid, &QueryExpr::Exists(attr.clone())));
+    }
+    let (attr, values) = match expr {
+        QueryExpr::Ne(attr, value) => (attr, std::slice::from_ref(value)),
+        QueryExpr::Nin(attr, values) => (attr, values.as_slice()),
+        _ => return None,
+    };
+    let (base_attr, nested_attr) = attr_parts(attr.clone());
+    let Some(qm) = index.get(INTERNER.intern(&base_attr) as usize) else {
+        return Some(Bitmap::new());
+    };
+    Some(match nested_attr {
+        // parents with any child holding one of values
+        Some(nested_attr) => evaluate_nested_query(qm, &QueryExpr::In(nested_attr, values.to_vec())),
+        None => qm.eq_any(values, all_valid),
+    })
+}
+
 pub fn kwargs_to_query<'py>(
     kwargs: Option<FxHashMap<String, pyo3::Bound<'py, PyAny>>>,
 ) -> FxHashMap<SmolStr, PyValue> {
@@ -11,7 +11,22 @@ impl QueryMap {
         });
         self.write_str_radix_map().keep_only(keep);
         self.write_num_ordered().keep_only(keep);
+        let mut encoded = self.write_encoded();
+        for keys in encoded.values_mut() {
+            keys.keep_only(keep);
+        }
+        encoded.retain(|_, keys| !keys.is_empty());
+        drop(encoded);
+        self.write_wide().keep_only(keep);
         self.get_bool_map_writer().keep_only(keep);
+        self.get_present_writer().and_inplace(keep);
+        self.get_nulls_writer().and_inplace(keep);
+        if let Some(intervals) = &self.intervals {
+            intervals.write().unwrap().keep_only(keep);
+        }
+        if let Some(flags) = &self.flags {
+            flags.write().unwrap().keep_only(keep);
+        }
         self.nested.keep_only_with_parent_ids(keep);
 
         let mut writer = self.get_masked_ids_writer();
@@ -21,5 +36,9 @@ impl QueryMap {
         for r in to_be_removed.iter() {
             mapped_ids.remove(&r);
         }
+        drop(mapped_ids);
+
+        self.get_masked_by_parent_writer().retain(|parent_id, _| keep.contains(*parent_id));
+        self.get_child_counts_writer().retain(|parent_id, _| keep.contains(*parent_id));
     }
 }
\ No newline at end of file
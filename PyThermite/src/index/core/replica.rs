@@ -0,0 +1,106 @@
+use std::sync::{Mutex, Weak};
+
+use croaring::Bitmap;
+use pyo3::{prelude::*, sync::MutexExt};
+use rustc_hash::FxHashMap;
+
+use crate::index::{Index, Indexable, core::{index::IndexAPI, stored_item::StoredItem}, types::StrId, value::PyValue};
+
+pub enum Change {
+    // copied with the values the object holds once the change is applied
+    Add(StoredItem),
+    Remove(u32),
+    Set(u32, StrId, PyValue),
+}
+
+// a standby kept in step with the index it was registered on. changes are queued while that index is
+// written and applied once its locks are released, to copies of its objects so the two share no locks
+pub struct Replica {
+    pub target: Weak<IndexAPI>,
+    pending: Mutex<Vec<Change>>,
+    // object id -> its copy in the standby
+    copies: Mutex<FxHashMap<u32, Py<Indexable>>>,
+}
+
+impl Replica {
+    pub fn new(target: Weak<IndexAPI>) -> Self {
+        Self { target, pending: Mutex::default(), copies: Mutex::default() }
+    }
+
+    pub fn push(&self, change: Change) {
+        self.pending.lock().unwrap().push(change);
+    }
+
+    pub fn extend(&self, changes: impl IntoIterator<Item = Change>) {
+        self.pending.lock().unwrap().extend(changes);
+    }
+
+    // one flush at a time, so changes reach the standby in the order they were made
+    // a change the standby refuses (frozen, strict) is dropped, the first such error is returned once the rest are applied
+    pub fn flush(&self, py: Python) -> PyResult<()> {
+        let Some(target) = self.target.upgrade() else {
+            self.pending.lock().unwrap().clear();
+            return Ok(());
+        };
+        let mut copies = self.copies.lock_py_attached(py).unwrap();
+        let changes = std::mem::take(&mut *self.pending.lock().unwrap());
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        let mut first_err = None;
+        // a copy takes the values its object holds when made, so sets on it before it is added are kept
+        let mut added: Vec<Bound<PyAny>> = vec![];
+        let mut removed = Bitmap::new();
+        for change in changes {
+            let res = match change {
+                Change::Add(item) if !copies.contains_key(&item.get_owned_handle().id) => match item.get_py_ref(py) {
+                    Some(obj) => Indexable::copy(obj.bind(py)).map(|copy| {
+                        copies.insert(item.get_owned_handle().id, copy.clone().unbind());
+                        added.push(copy.into_any());
+                    }),
+                    // freed before it could be copied
+                    None => Ok(()),
+                },
+                Change::Add(_) => Ok(()),
+                Change::Remove(id) => {
+                    if let Some(copy) = copies.remove(&id) {
+                        removed.add(copy.borrow(py).id);
+                    }
+                    Ok(())
+                },
+                Change::Set(id, attr, value) => match copies.get(&id) {
+                    Some(copy) => {
+                        let copy = copy.borrow(py);
+                        copy.check_strict(attr, &value).and_then(|_mutating| {
+                            copy.store_value(py, attr, value);
+                            copy.fire_alerts(py)
+                        })
+                    },
+                    None => Ok(()),
+                },
+            };
+            if let Err(e) = res {
+                first_err.get_or_insert(e);
+            }
+        }
+        drop(copies);
+
+        let standby = Index { inner: target };
+        if !added.is_empty() {
+            if let Err(e) = standby.add_built(py, &added) {
+                first_err.get_or_insert(e);
+            }
+        }
+        if !removed.is_empty() {
+            let res = standby.inner.check_mutable().and_then(|_mutating| {
+                py.allow_threads(|| standby.inner.discard(&removed));
+                standby.inner.fire_alerts(py)
+            });
+            if let Err(e) = res {
+                first_err.get_or_insert(e);
+            }
+        }
+        first_err.map_or(Ok(()), Err)
+    }
+}
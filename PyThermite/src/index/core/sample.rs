@@ -0,0 +1,71 @@
+use croaring::Bitmap;
+use pyo3::{PyResult, Python};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use crate::index::{core::{group_by::{attr_path, group_by, merge_groups}, query::QueryMap, stored_item::StoredItem, structures::{freeze_lock::FreezeLock, string_interner::INTERNER}}, types::StrId, value::PyValue};
+
+// n ids drawn evenly, all of them when there are no more than n
+fn uniform(ids: &Bitmap, n: usize, rng: &mut StdRng) -> Bitmap {
+    let len = ids.cardinality() as usize;
+    if n >= len {
+        return ids.clone();
+    }
+    rand::seq::index::sample(rng, len, n).iter()
+        .filter_map(|rank| ids.select(rank as u32))
+        .collect()
+}
+
+// without replacement, each draw proportional to attr (Efraimidis-Spirakis keys).
+// ids without a positive number there are never drawn
+fn weighted(items: &[StoredItem], ids: &Bitmap, n: usize, attr: StrId, rng: &mut StdRng) -> Bitmap {
+    let mut keyed: Vec<(f64, u32)> = ids.iter()
+        .filter_map(|id| {
+            let weight = items.get(id as usize)?.with_attr_id(attr, PyValue::as_f64).flatten()?;
+            (weight > 0.0 && weight.is_finite()).then(|| (-(1.0 - rng.random::<f64>()).ln() / weight, id))
+        })
+        .collect();
+    if keyed.len() > n {
+        keyed.select_nth_unstable_by(n, |a, b| a.0.total_cmp(&b.0));
+        keyed.truncate(n);
+    }
+    keyed.into_iter().map(|(_, id)| id).collect()
+}
+
+// the ids split by value of strata, or all of them as one group
+pub fn strata_groups(
+    py: Python,
+    index: &FreezeLock<Vec<QueryMap>>,
+    items: &FreezeLock<Vec<StoredItem>>,
+    scope: &Bitmap,
+    strata: Option<&str>,
+) -> PyResult<Vec<Bitmap>> {
+    Ok(match strata {
+        Some(attr) => {
+            let groups = group_by(&index.read().unwrap(), &items.read().unwrap(), scope, &[attr_path(attr)]);
+            merge_groups(py, groups)?.into_iter().map(|(_, ids)| ids).collect()
+        },
+        None => vec![scope.clone()],
+    })
+}
+
+// n per group, seeded runs draw the same ids from the same index
+pub fn sample(
+    items: &FreezeLock<Vec<StoredItem>>,
+    groups: &[Bitmap],
+    n: usize,
+    weights: Option<&str>,
+    seed: Option<u64>,
+) -> Bitmap {
+    let weights = weights.map(|attr| INTERNER.intern(attr));
+    let mut rng = seed.map_or_else(StdRng::from_os_rng, StdRng::seed_from_u64);
+    let items = items.read().unwrap();
+    let mut res = Bitmap::new();
+    for ids in groups.iter() {
+        let drawn = match weights {
+            Some(attr) => weighted(&items, ids, n, attr, &mut rng),
+            None => uniform(ids, n, &mut rng),
+        };
+        res.or_inplace(&drawn);
+    }
+    res
+}
@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use pyo3::{prelude::*, types::PyTuple};
+
+use crate::index::{core::{encoders::{KeyEncoder, KeySpace, is_registered, register_key_encoder}, query::{b_tree::Key, query_ops::QueryExpr}}, types::packaging_version_type, value::{PyValue, RustCastValue}};
+
+// [major u14][minor u14][patch u14][prerelease u21], a release sorts above all of its prereleases
+const PART_BITS: u32 = 14;
+const PRE_BITS: u32 = 21;
+const RELEASE: u64 = (1 << PRE_BITS) - 1;
+// prerelease tag as up to 3 letters (base 27) followed by its first number
+const PRE_NUM_BITS: u32 = 6;
+
+fn pack(major: u64, minor: u64, patch: u64, pre: u64) -> Option<Key> {
+    if [major, minor, patch].iter().any(|part| part >> PART_BITS != 0) {
+        return None;
+    }
+    let packed = (((major << PART_BITS | minor) << PART_BITS | patch) << PRE_BITS) | pre;
+    Some(Key::Int(packed as i64))
+}
+
+// "alpha.1" and ("a", 1) style tags, ordered by letters then number, a bare tag below its numbered ones
+fn prerelease(letters: &str, number: Option<u64>) -> u64 {
+    let letters = letters.chars()
+        .filter(|c| c.is_ascii_alphabetic())
+        .map(|c| c.to_ascii_lowercase() as u64 - 'a' as u64 + 1)
+        .chain(std::iter::repeat(0))
+        .take(3)
+        .fold(0, |acc, letter| acc * 27 + letter);
+    let number = number.map_or(0, |n| n.saturating_add(1).min((1 << PRE_NUM_BITS) - 1));
+    letters << PRE_NUM_BITS | number
+}
+
+// "1.10.0", "v2.0.0-rc.1+build.5", "1.4" (missing parts are 0), build metadata is ignored
+pub fn parse_semver(s: &str) -> Option<Key> {
+    let s = s.trim();
+    let s = s.strip_prefix(['v', 'V']).unwrap_or(s);
+    let s = s.split_once('+').map_or(s, |(version, _)| version);
+    let (core, pre) = match s.split_once('-') {
+        Some((core, pre)) if !pre.is_empty() => (core, Some(pre)),
+        Some(_) => return None,
+        None => (s, None),
+    };
+
+    let mut parts = [0u64; 3];
+    for (i, part) in core.split('.').enumerate() {
+        if i == 3 || part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        parts[i] = part.parse().ok()?;
+    }
+
+    let pre = match pre {
+        Some(pre) => {
+            let tag = pre.split('.').next().unwrap_or(pre);
+            let number = pre.split(|c: char| !c.is_ascii_digit())
+                .find(|run| !run.is_empty())
+                .and_then(|run| run.parse().ok());
+            prerelease(tag, number)
+        },
+        None => RELEASE,
+    };
+    pack(parts[0], parts[1], parts[2], pre)
+}
+
+// packaging.version.Version on the same scale, dev and post releases are not distinguished.
+// versions with a part past the scale are left to identity rather than failing the object
+struct PackagingVersionEncoder;
+
+impl KeyEncoder for PackagingVersionEncoder {
+    fn encode(&self, value: &Bound<'_, PyAny>) -> PyResult<Option<Key>> {
+        let major = value.getattr("major")?.extract::<u64>()?;
+        let minor = value.getattr("minor")?.extract::<u64>()?;
+        let patch = value.getattr("micro")?.extract::<u64>()?;
+        let pre = value.getattr("pre")?;
+        let pre = if pre.is_none() {
+            RELEASE
+        } else {
+            let pre = pre.downcast::<PyTuple>()?;
+            prerelease(&pre.get_item(0)?.extract::<String>()?, Some(pre.get_item(1)?.extract::<u64>()?))
+        };
+        Ok(pack(major, minor, patch, pre))
+    }
+
+    // shared with the version strings of declared attributes
+    fn space(&self) -> Option<KeySpace> {
+        Some(KeySpace::Semver)
+    }
+}
+
+// keeps a user registered encoder for the type if there is one
+pub fn ensure_packaging_encoder(py: Python) {
+    if let Some(ty) = packaging_version_type(py) {
+        let ty = ty.bind(py);
+        if !is_registered(ty) {
+            register_key_encoder(ty, Arc::new(PackagingVersionEncoder));
+        }
+    }
+}
+
+// version strings compared against declared attributes become keys, other values are left for check_types
+pub fn encode_version_bounds<F: Fn(&str) -> bool>(expr: &mut QueryExpr, is_semver: &F) {
+    let encode = |value: &mut PyValue| {
+        if let RustCastValue::Str(s) = value.get_primitive() {
+            if let Some(key) = parse_semver(s) {
+                *value = value.with_key(KeySpace::Semver, key);
+            }
+        }
+    };
+    match expr {
+        QueryExpr::Gt(attr, v) | QueryExpr::Ge(attr, v) | QueryExpr::Lt(attr, v) | QueryExpr::Le(attr, v)
+            if is_semver(attr) => encode(v),
+        QueryExpr::Bt(attr, lower, upper) if is_semver(attr) => {
+            encode(lower);
+            encode(upper);
+        },
+        QueryExpr::Not(inner) => encode_version_bounds(inner, is_semver),
+        QueryExpr::And(exprs) | QueryExpr::Or(exprs) => {
+            for e in exprs.iter_mut() {
+                encode_version_bounds(e, is_semver);
+            }
+        },
+        _ => {},
+    }
+}
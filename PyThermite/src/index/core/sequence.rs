@@ -0,0 +1,66 @@
+use croaring::Bitmap;
+
+use crate::index::core::structures::{composite_key::CompositeKey128, ordered_bitmap::NumericalBitmap};
+
+// every object added gets the next number of a counter that never goes back,
+// held in a numeric index so ranges of it can be read back in order
+#[derive(Debug)]
+pub struct Sequence {
+    next: u64,
+    ordered: NumericalBitmap,
+}
+
+impl Default for Sequence {
+    fn default() -> Self {
+        Self { next: 0, ordered: NumericalBitmap::new() }
+    }
+}
+
+impl Sequence {
+    #[inline]
+    fn encode(seq: u64) -> u128 {
+        CompositeKey128::encode_i64_to_float76(seq as i64)
+    }
+
+    pub fn stamp(&mut self, id: u32) {
+        self.ordered.add(Self::encode(self.next), id);
+        self.next += 1;
+    }
+
+    pub fn remove(&mut self, id: u32) {
+        if let Some((_, bits)) = self.ordered.values_from_valid(&Bitmap::of(&[id])).first() {
+            self.ordered.remove(*bits, id);
+        }
+    }
+
+    pub fn keep_only(&mut self, keep: &Bitmap) {
+        self.ordered.keep_only(keep);
+    }
+
+    // the number the next object added gets
+    pub fn next(&self) -> u64 {
+        self.next
+    }
+
+    // ids of scope added at seq or later
+    pub fn since(&self, seq: u64, scope: &Bitmap) -> Bitmap {
+        self.ordered.get_gte_from_valid(Self::encode(seq), scope)
+    }
+
+    // up to n ids of scope, the most recently added first
+    pub fn latest(&self, n: usize, scope: &Bitmap) -> Vec<u32> {
+        let population = self.ordered.ids().and(scope);
+        let count = population.cardinality();
+        (count.saturating_sub(n as u64)..count).rev()
+            .filter_map(|rank| self.ordered.kth_from_valid(rank, &population))
+            .filter_map(|bits| self.ordered.get_exact(bits).and(&population).minimum())
+            .collect()
+    }
+
+    // ids of scope oldest first
+    pub fn in_order(&self, scope: &Bitmap) -> Vec<u32> {
+        let mut stamped = self.ordered.values_from_valid(scope);
+        stamped.sort_unstable_by_key(|(_, bits)| *bits);
+        stamped.into_iter().map(|(id, _)| id).collect()
+    }
+}
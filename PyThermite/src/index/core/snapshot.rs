@@ -0,0 +1,441 @@
+use pyo3::{PyResult, exceptions::PyValueError};
+use rustc_hash::FxHashMap;
+
+use rustc_hash::FxHashSet;
+
+use crate::index::{core::{arrow::Columns, attr_decls::AttrDecls, config::{IndexConfig, ResultOrder}, stored_item::StoredItem, structures::string_interner::{INTERNER, StrValue}, units::Unit}, types::StrId, value::{HashMode, PyValue, RustCastValue}};
+
+// layout, little endian:
+//   magic, version u16
+//   max_str_len u64, strict u8, auto_remove_on_gc u8, order u8, sequence u8, hash_mode u8, shards u32, threads u32 (0 for None)
+//   declarations: the AttrDecls tree, see write_decls
+//   names: count u32, then len u32 + utf8 bytes each
+//   objects: count u64, then per object a value count u32 and per value name u32, tag u8, payload
+//   log records appended by the write ahead log, see wal.rs, the objects above numbered from 0 in order
+const MAGIC: &[u8] = b"PYTHERMITE";
+const VERSION: u16 = 5;
+
+const TAG_INT: u8 = 0;
+const TAG_FLOAT: u8 = 1;
+const TAG_STR: u8 = 2;
+const TAG_BOOL: u8 = 3;
+// in a log record, the attribute no longer holds a value that is saved
+const TAG_UNSET: u8 = 255;
+
+pub const RECORD_ADD: u8 = 1;
+pub const RECORD_REMOVE: u8 = 2;
+pub const RECORD_SET: u8 = 3;
+
+pub struct Snapshot {
+    pub config: IndexConfig,
+    pub decls: AttrDecls,
+    pub names: Vec<String>,
+    pub rows: usize,
+    // one value per object for every name, None where the object has no value for it
+    pub columns: Columns,
+}
+
+// false for values that are not saved, anything but int, float, str and bool
+pub fn write_value(value: &RustCastValue, out: &mut Vec<u8>) -> bool {
+    match value {
+        RustCastValue::Int(i) => {
+            out.push(TAG_INT);
+            out.extend_from_slice(&i.to_le_bytes());
+        },
+        RustCastValue::Float(f) => {
+            out.push(TAG_FLOAT);
+            out.extend_from_slice(&f.to_le_bytes());
+        },
+        RustCastValue::Bool(b) => {
+            out.push(TAG_BOOL);
+            out.push(*b as u8);
+        },
+        RustCastValue::Str(s) => {
+            out.push(TAG_STR);
+            write_str(s, out);
+        },
+        _ => return false,
+    }
+    true
+}
+
+pub fn write_unset(out: &mut Vec<u8>) {
+    out.push(TAG_UNSET);
+}
+
+pub fn write_str(s: &str, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+// the saved values of item, each written as name then value by write_name
+pub fn write_values<F: FnMut(StrId, &mut Vec<u8>)>(item: &StoredItem, out: &mut Vec<u8>, mut write_name: F) {
+    let values = item.get_owned_handle().get_py_values();
+    let count_at = out.len();
+    out.extend_from_slice(&0u32.to_le_bytes());
+    let mut count = 0u32;
+    for (attr, value) in values.iter() {
+        if !is_saved(value) {
+            continue;
+        }
+        write_name(*attr, out);
+        write_value(value.get_primitive(), out);
+        count += 1;
+    }
+    out[count_at..count_at + 4].copy_from_slice(&count.to_le_bytes());
+}
+
+fn is_saved(value: &PyValue) -> bool {
+    matches!(value.get_primitive(), RustCastValue::Int(_) | RustCastValue::Float(_) | RustCastValue::Str(_) | RustCastValue::Bool(_))
+}
+
+// one node per attribute path:
+//   depth: flag u8 then u32, fields: flag u8 then count u32 and a name each
+//   decl: coerce_numeric, semver, opaque, interval and flags as bits of a u8,
+//     tolerance flag u8 then f64, unit flag u8 then name, fixed_point flag u8 then u8
+//   children: count u32, then a name and the child's node each
+fn write_decls(decls: &AttrDecls, out: &mut Vec<u8>) {
+    out.push(decls.depth.is_some() as u8);
+    if let Some(depth) = decls.depth {
+        out.extend_from_slice(&depth.to_le_bytes());
+    }
+    out.push(decls.fields.is_some() as u8);
+    if let Some(fields) = &decls.fields {
+        out.extend_from_slice(&(fields.len() as u32).to_le_bytes());
+        for field in fields {
+            write_str(&INTERNER.resolve(*field), out);
+        }
+    }
+    let decl = &decls.decl;
+    out.push(decl.coerce_numeric as u8 | (decl.semver as u8) << 1 | (decl.opaque as u8) << 2 | (decl.interval as u8) << 3 | (decl.flags as u8) << 4);
+    out.push(decl.tolerance.is_some() as u8);
+    if let Some(tolerance) = decl.tolerance {
+        out.extend_from_slice(&tolerance.to_le_bytes());
+    }
+    out.push(decl.unit.is_some() as u8);
+    if let Some(unit) = decl.unit {
+        write_str(unit.name, out);
+    }
+    out.push(decl.fixed_point.is_some() as u8);
+    if let Some(places) = decl.fixed_point {
+        out.push(places);
+    }
+    out.extend_from_slice(&(decls.children.len() as u32).to_le_bytes());
+    for (attr, child) in decls.children.iter() {
+        write_str(&INTERNER.resolve(*attr), out);
+        write_decls(child, out);
+    }
+}
+
+// the saved values of items, in the order given, after the settings and declarations of the index. anything else is left out
+pub fn encode(config: &IndexConfig, decls: &AttrDecls, items: &[StoredItem]) -> Vec<u8> {
+    let mut names: Vec<StrId> = vec![];
+    let mut name_ids: FxHashMap<StrId, u32> = FxHashMap::default();
+    let mut body = Vec::new();
+    body.extend_from_slice(&(items.len() as u64).to_le_bytes());
+
+    for item in items {
+        write_values(item, &mut body, |attr, out| {
+            let name = *name_ids.entry(attr).or_insert_with(|| {
+                names.push(attr);
+                names.len() as u32 - 1
+            });
+            out.extend_from_slice(&name.to_le_bytes());
+        });
+    }
+
+    let mut out = Vec::with_capacity(body.len() + 64);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.extend_from_slice(&(config.max_str_len as u64).to_le_bytes());
+    out.push(config.strict as u8);
+    out.push(config.auto_remove_on_gc as u8);
+    out.push(match config.order {
+        ResultOrder::Id => 0,
+        ResultOrder::Insertion => 1,
+    });
+    out.push(config.sequence as u8);
+    out.push(match config.hash_mode {
+        HashMode::Identity => 0,
+        HashMode::Stable => 1,
+    });
+    out.extend_from_slice(&(config.shards as u32).to_le_bytes());
+    out.extend_from_slice(&(config.threads.unwrap_or(0) as u32).to_le_bytes());
+    write_decls(decls, &mut out);
+    out.extend_from_slice(&(names.len() as u32).to_le_bytes());
+    for name in names {
+        write_str(&INTERNER.resolve(name), &mut out);
+    }
+    out.extend_from_slice(&body);
+    out
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> PyResult<&'a [u8]> {
+        let end = self.pos.checked_add(n).filter(|end| *end <= self.buf.len())
+            .ok_or_else(|| PyValueError::new_err("snapshot is truncated or corrupt"))?;
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> PyResult<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("length checked"))
+    }
+
+    fn u8(&mut self) -> PyResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> PyResult<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> PyResult<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn str(&mut self) -> PyResult<&'a str> {
+        let len = self.u32()? as usize;
+        std::str::from_utf8(self.take(len)?).map_err(|_| PyValueError::new_err("snapshot holds invalid utf-8"))
+    }
+
+    // the flag byte written before an optional field
+    fn flag(&mut self) -> PyResult<bool> {
+        Ok(self.u8()? != 0)
+    }
+
+    fn decls(&mut self) -> PyResult<AttrDecls> {
+        let mut decls = AttrDecls::default();
+        if self.flag()? {
+            decls.depth = Some(self.u32()?);
+        }
+        if self.flag()? {
+            let mut fields = FxHashSet::default();
+            for _ in 0..self.u32()? {
+                fields.insert(INTERNER.intern(self.str()?));
+            }
+            decls.fields = Some(fields);
+        }
+        let bits = self.u8()?;
+        let decl = &mut decls.decl;
+        decl.coerce_numeric = bits & 1 != 0;
+        decl.semver = bits & 1 << 1 != 0;
+        decl.opaque = bits & 1 << 2 != 0;
+        decl.interval = bits & 1 << 3 != 0;
+        decl.flags = bits & 1 << 4 != 0;
+        if self.flag()? {
+            decl.tolerance = Some(f64::from_le_bytes(self.array()?));
+        }
+        if self.flag()? {
+            decl.unit = Some(Unit::parse(self.str()?)?);
+        }
+        if self.flag()? {
+            decl.fixed_point = Some(self.u8()?);
+        }
+        for _ in 0..self.u32()? {
+            let attr = INTERNER.intern(self.str()?);
+            let child = self.decls()?;
+            decls.children.insert(attr, child);
+        }
+        Ok(decls)
+    }
+
+    // None for an unset attribute
+    fn value(&mut self) -> PyResult<Option<RustCastValue>> {
+        Ok(Some(match self.u8()? {
+            TAG_INT => RustCastValue::Int(i64::from_le_bytes(self.array()?)),
+            TAG_FLOAT => RustCastValue::Float(f64::from_le_bytes(self.array()?)),
+            TAG_STR => RustCastValue::Str(StrValue::new(self.str()?)),
+            TAG_BOOL => RustCastValue::Bool(self.u8()? != 0),
+            TAG_UNSET => return Ok(None),
+            tag => return Err(PyValueError::new_err(format!("snapshot holds unknown value tag {}", tag))),
+        }))
+    }
+}
+
+type Row = Vec<(usize, RustCastValue)>;
+
+// names read from log records, which carry them inline
+struct Names {
+    names: Vec<String>,
+    ids: FxHashMap<String, usize>,
+}
+
+impl Names {
+    fn id(&mut self, name: &str) -> usize {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), self.names.len() - 1);
+        self.names.len() - 1
+    }
+}
+
+fn set(row: &mut Row, name: usize, value: Option<RustCastValue>) {
+    row.retain(|(n, _)| *n != name);
+    if let Some(value) = value {
+        row.push((name, value));
+    }
+}
+
+enum Record {
+    Add(usize, Row),
+    Remove(usize),
+    Set(usize, usize, Option<RustCastValue>),
+}
+
+// one log record, read whole before any of it is applied
+fn read_record(reader: &mut Reader, names: &mut Names) -> PyResult<Record> {
+    let kind = reader.u8()?;
+    let log_id = reader.u64()? as usize;
+    Ok(match kind {
+        RECORD_ADD => {
+            let mut row = Row::new();
+            for _ in 0..reader.u32()? {
+                let name = names.id(reader.str()?);
+                set(&mut row, name, reader.value()?);
+            }
+            Record::Add(log_id, row)
+        },
+        RECORD_REMOVE => Record::Remove(log_id),
+        RECORD_SET => {
+            let name = names.id(reader.str()?);
+            Record::Set(log_id, name, reader.value()?)
+        },
+        kind => return Err(PyValueError::new_err(format!("snapshot holds unknown record kind {}", kind))),
+    })
+}
+
+// rows by log id, ids are handed out in order so a record never skips past the next one
+fn apply_record(record: Record, rows: &mut Vec<Option<Row>>) -> PyResult<()> {
+    let log_id = match &record {
+        Record::Add(id, _) | Record::Remove(id) | Record::Set(id, _, _) => *id,
+    };
+    if log_id > rows.len() {
+        return Err(PyValueError::new_err("snapshot is truncated or corrupt"));
+    }
+    if log_id == rows.len() {
+        rows.push(None);
+    }
+    match record {
+        Record::Add(_, row) => rows[log_id] = Some(row),
+        Record::Remove(_) => rows[log_id] = None,
+        Record::Set(_, name, value) => {
+            if let Some(row) = rows[log_id].as_mut() {
+                set(row, name, value);
+            }
+        },
+    }
+    Ok(())
+}
+
+pub fn decode(buf: &[u8]) -> PyResult<Snapshot> {
+    if !buf.starts_with(MAGIC) {
+        return Err(PyValueError::new_err("not a PyThermite snapshot"));
+    }
+    let mut reader = Reader { buf, pos: MAGIC.len() };
+    let version = u16::from_le_bytes(reader.array()?);
+    if version != VERSION {
+        return Err(PyValueError::new_err(format!("unsupported snapshot version {}, expected {}", version, VERSION)));
+    }
+
+    let config = IndexConfig {
+        max_str_len: reader.u64()? as usize,
+        strict: reader.u8()? != 0,
+        auto_remove_on_gc: reader.u8()? != 0,
+        order: if reader.u8()? == 1 { ResultOrder::Insertion } else { ResultOrder::Id },
+        sequence: reader.u8()? != 0,
+        hash_mode: if reader.u8()? == 1 { HashMode::Stable } else { HashMode::Identity },
+        shards: IndexConfig::check_shards(reader.u32()? as usize)?,
+        threads: Some(reader.u32()? as usize).filter(|threads| *threads > 0),
+    };
+    let decls = reader.decls()?;
+
+    let mut names = Names { names: vec![], ids: FxHashMap::default() };
+    for _ in 0..reader.u32()? {
+        let name = reader.str()?;
+        names.names.push(name.to_string());
+        names.ids.insert(name.to_string(), names.names.len() - 1);
+    }
+
+    let objects = reader.u64()? as usize;
+    // capped by what the remaining bytes could hold, so a corrupt count can't allocate unbounded
+    let mut rows: Vec<Option<Row>> = Vec::with_capacity(objects.min(buf.len() - reader.pos));
+    for _ in 0..objects {
+        let mut row = Row::new();
+        for _ in 0..reader.u32()? {
+            let name = reader.u32()? as usize;
+            if name >= names.names.len() {
+                return Err(PyValueError::new_err("snapshot is truncated or corrupt"));
+            }
+            set(&mut row, name, reader.value()?);
+        }
+        rows.push(Some(row));
+    }
+
+    // a record cut short by a crash mid write ends the log
+    while reader.pos < buf.len() {
+        let Ok(record) = read_record(&mut reader, &mut names) else {
+            break;
+        };
+        apply_record(record, &mut rows)?;
+    }
+
+    let live: Vec<Row> = rows.into_iter().flatten().collect();
+    let mut columns: Columns = vec![vec![None; live.len()]; names.names.len()];
+    for (i, row) in live.iter().enumerate() {
+        for (name, value) in row {
+            columns[*name][i] = Some(value.clone());
+        }
+    }
+    Ok(Snapshot { config, decls, names: names.names, rows: live.len(), columns })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_round_trips_config_and_decls() {
+        let config = IndexConfig {
+            max_str_len: 12,
+            strict: true,
+            order: ResultOrder::Insertion,
+            hash_mode: HashMode::Stable,
+            shards: 4,
+            threads: Some(3),
+            ..IndexConfig::default()
+        };
+        let mut decls = AttrDecls::default();
+        decls.declare("latency", |decl| decl.unit = Some(Unit::parse("ms").unwrap()));
+        decls.declare("owner.version", |decl| decl.semver = true);
+        decls.declare("price", |decl| {
+            decl.fixed_point = Some(2);
+            decl.tolerance = Some(0.5);
+        });
+        decls.set("owner", AttrDecls::new(Some(1), Some(vec!["version".to_string()])));
+
+        let snapshot = decode(&encode(&config, &decls, &[])).unwrap();
+        assert_eq!(snapshot.config, config);
+        assert_eq!(snapshot.rows, 0);
+        for attr in ["latency", "owner.version", "price", "missing"] {
+            assert_eq!(snapshot.decls.decl(attr), decls.decl(attr));
+        }
+        let owner = snapshot.decls.child("owner").unwrap();
+        assert_eq!(owner.depth, Some(1));
+        assert_eq!(owner.fields, Some([INTERNER.intern("version")].into_iter().collect()));
+    }
+
+    #[test]
+    fn header_rejects_shards_other_than_powers_of_two() {
+        let config = IndexConfig { shards: 6, ..IndexConfig::default() };
+        assert!(decode(&encode(&config, &AttrDecls::default(), &[])).is_err());
+    }
+}
@@ -0,0 +1,92 @@
+use croaring::Bitmap;
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyDict};
+
+use crate::index::core::{fixed_point::from_fixed, query::{QueryMap, aggregate::AggFunc}, structures::{composite_key::CompositeKey128, ordered_bitmap::NumericalBitmap, string_interner::INTERNER}};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stat {
+    Agg(AggFunc),
+    // 0 to 1
+    Percentile(f64),
+}
+
+impl Stat {
+    // sum, count, min, max, mean / avg, median or p followed by a percentage like p95 or p99.9
+    pub fn parse(name: &str) -> PyResult<Self> {
+        if let Some(func) = AggFunc::parse(name) {
+            return Ok(Stat::Agg(func));
+        }
+        let pct = match name {
+            "median" => Some(50.0),
+            _ => name.strip_prefix('p').and_then(|pct| pct.parse::<f64>().ok()),
+        };
+        match pct {
+            Some(pct) if (0.0..=100.0).contains(&pct) => Ok(Stat::Percentile(pct / 100.0)),
+            _ => Err(PyValueError::new_err(format!(
+                "unknown aggregate '{}', expected one of sum, count, min, max, mean, median or a percentile like p95", name
+            ))),
+        }
+    }
+}
+
+// linear between the ranks either side of p, like numpy's default
+pub fn quantile(num: &NumericalBitmap, population: &Bitmap, p: f64) -> Option<f64> {
+    let n = population.cardinality();
+    let pos = p * n.checked_sub(1)? as f64;
+    let lower = pos.floor() as u64;
+    let low = CompositeKey128::decode_float76(num.kth_from_valid(lower, population)?);
+    if lower + 1 >= n {
+        return Some(low);
+    }
+    let high = CompositeKey128::decode_float76(num.kth_from_valid(lower + 1, population)?);
+    Some(low + (high - low) * (pos - lower as f64))
+}
+
+// every stat over the numbers attr holds within scope, read from the numeric index alone
+pub fn aggregate(index: &[QueryMap], scope: &Bitmap, attr: &str, stats: &[Stat]) -> Vec<Option<f64>> {
+    let Some(qmap) = index.get(INTERNER.intern(attr) as usize).filter(|qmap| !qmap.is_placeholder()) else {
+        return stats.iter().map(|stat| match stat {
+            Stat::Agg(func) => func.empty_value(),
+            Stat::Percentile(_) => None,
+        }).collect();
+    };
+    let num = qmap.read_num_ordered();
+    let population = num.ids().and(scope);
+    let count = population.cardinality();
+    // only walked when a stat needs every value
+    let sum = stats.iter()
+        .any(|stat| matches!(stat, Stat::Agg(AggFunc::Sum | AggFunc::Avg)))
+        .then(|| {
+            let values = num.values_from_valid(&population).into_iter()
+                .map(|(_, bits)| CompositeKey128::decode_float76(bits));
+            match qmap.decl.fixed_point {
+                // whole units, added exactly
+                Some(places) => from_fixed(values.map(|units| units as i128).sum(), places),
+                None => values.sum(),
+            }
+        });
+    let plain = |stored: f64| qmap.plain_number(stored);
+
+    stats.iter().map(|stat| match stat {
+        Stat::Agg(func) if count == 0 => func.empty_value(),
+        Stat::Agg(AggFunc::Count) => Some(count as f64),
+        Stat::Agg(AggFunc::Sum) => sum,
+        Stat::Agg(AggFunc::Avg) => sum.map(|sum| sum / count as f64),
+        Stat::Agg(AggFunc::Min) => num.kth_from_valid(0, &population).map(|bits| plain(CompositeKey128::decode_float76(bits))),
+        Stat::Agg(AggFunc::Max) => num.kth_from_valid(count - 1, &population).map(|bits| plain(CompositeKey128::decode_float76(bits))),
+        Stat::Percentile(p) => quantile(&num, &population, *p).map(plain),
+    }).collect()
+}
+
+// {name: value} in the order the names were given, counts as ints
+pub fn aggregate_dict<'py>(py: Python<'py>, index: &[QueryMap], scope: &Bitmap, attr: &str, names: &[String]) -> PyResult<Bound<'py, PyDict>> {
+    let stats = names.iter().map(|name| Stat::parse(name)).collect::<PyResult<Vec<_>>>()?;
+    let out = PyDict::new(py);
+    for ((name, stat), value) in names.iter().zip(&stats).zip(aggregate(index, scope, attr, &stats)) {
+        match (stat, value) {
+            (Stat::Agg(AggFunc::Count), Some(count)) => out.set_item(name, count as u64)?,
+            _ => out.set_item(name, value)?,
+        }
+    }
+    Ok(out)
+}
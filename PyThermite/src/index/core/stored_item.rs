@@ -1,14 +1,21 @@
-use pyo3::{Bound, IntoPyObject, Py, PyAny, PyRef, Python};
-use std::{hash::{Hash, Hasher}, sync::{Arc, Weak}};
+use pyo3::{Bound, IntoPyObject, Py, PyAny, PyRef, PyResult, Python, types::{PyCFunction, PyWeakrefMethods, PyWeakrefReference}};
+use std::{hash::{Hash, Hasher}, sync::{Arc, Mutex}};
 
-use crate::index::{core::{index::IndexAPI, structures::hybrid_set::{HybridSet, HybridSetOps}}, types::{DEFAULT_INDEXABLE_ARC, DEFAULT_PY_INDEXABLE_ARC, StrId}, value::PyValue};
+use crate::index::{types::{DEFAULT_INDEXABLE_ARC, DEFAULT_PY_INDEXABLE_ARC, StrId}, value::PyValue};
 use crate::index::Indexable;
 
 
+#[derive(Clone, Debug)]
+enum PyHandle {
+    Strong(Arc<Py<Indexable>>),
+    // held by an auto_remove_on_gc index, the callback queues the id once the object is freed
+    Weak(Arc<Py<PyWeakrefReference>>),
+}
+
 #[derive(Clone, Debug)]
 pub struct StoredItem{
     // these two are the same object, one is a rust handle and the other is a python handle
-    py_item: Arc<Py<Indexable>>,
+    py_item: PyHandle,
     owned_py_item: Arc<Indexable>,
 }
 
@@ -18,11 +25,33 @@ impl<'py> StoredItem {
         rust_handle: Arc<Indexable>,
     ) -> Self {
         Self {
-            py_item: py_handle,
+            py_item: PyHandle::Strong(py_handle),
             owned_py_item: rust_handle,
         }
     }
 
+    // holds obj weakly, its id is pushed to freed once python frees it
+    pub fn weak(obj: &Bound<'py, Indexable>, rust_handle: Arc<Indexable>, freed: &Arc<Mutex<Vec<u32>>>) -> PyResult<Self> {
+        let id = rust_handle.id;
+        let freed = freed.clone();
+        let callback = PyCFunction::new_closure(obj.py(), None, None, move |_args, _kwargs| {
+            freed.lock().unwrap().push(id);
+        })?;
+        let weak = PyWeakrefReference::new_with(obj.as_any(), callback)?;
+        Ok(Self {
+            py_item: PyHandle::Weak(Arc::new(weak.unbind())),
+            owned_py_item: rust_handle,
+        })
+    }
+
+    // the same object held strongly, None once it has been freed
+    pub fn strong(&self, py: Python) -> Option<Self> {
+        match &self.py_item {
+            PyHandle::Strong(_) => Some(self.clone()),
+            PyHandle::Weak(_) => self.get_py_ref(py).map(|obj| Self::new(Arc::new(obj), self.owned_py_item.clone())),
+        }
+    }
+
     pub fn with_attr_id<F, R>(&self, str_id: StrId, f: F) -> Option<R>
     where
         F: FnOnce(&PyValue) -> R,
@@ -30,23 +59,68 @@ impl<'py> StoredItem {
         self.owned_py_item.with_attr_id(str_id, f)
     }
 
+    pub fn with_path<F, R>(&self, path: &[StrId], f: F) -> Option<R>
+    where
+        F: FnOnce(&PyValue) -> R,
+    {
+        self.owned_py_item.with_path(path, f)
+    }
+
     pub fn get_owned_handle(&self) -> &Arc<Indexable> {
         &self.owned_py_item
     }
 
-    pub fn get_py_ref(&self, py: Python) -> Py<Indexable> {
-        self.py_item.clone_ref(py)
+    // None for a weakly held object python has freed, until the next write removes it
+    pub fn get_py_ref(&self, py: Python) -> Option<Py<Indexable>> {
+        match &self.py_item {
+            PyHandle::Strong(handle) => Some(handle.clone_ref(py)),
+            PyHandle::Weak(weak) => weak.bind(py).upgrade_as::<Indexable>().ok().flatten().map(Bound::unbind),
+        }
+    }
+
+    pub fn borrow_py_ref(&self, py: Python<'py>) -> Option<PyRef<'py, Indexable>> {
+        self.get_py_ref(py).map(|obj| obj.into_bound(py).borrow())
+    }
+
+    // the default filling slots without an object
+    pub fn is_placeholder(&self) -> bool {
+        match &self.py_item {
+            PyHandle::Strong(handle) => Arc::ptr_eq(handle, &DEFAULT_PY_INDEXABLE_ARC),
+            PyHandle::Weak(_) => false,
+        }
+    }
+
+    // weakly held and already freed by python
+    pub fn is_freed(&self, py: Python) -> bool {
+        matches!(&self.py_item, PyHandle::Weak(weak) if weak.bind(py).upgrade().is_none())
     }
 
-    pub fn borrow_py_ref(&self, py: Python<'py>) -> PyRef<'py, Indexable> {
-        self.py_item.bind(py).borrow()
+    // strong references to the python handle, from every index holding it, none when held weakly
+    pub fn handle_refs(&self) -> usize {
+        match &self.py_item {
+            PyHandle::Strong(handle) => Arc::strong_count(handle),
+            PyHandle::Weak(_) => 0,
+        }
+    }
+
+    // nothing outside the indexes sharing this handle still refers to the object
+    pub fn only_held_by_index(&self, py: Python) -> bool {
+        match &self.py_item {
+            PyHandle::Strong(handle) => handle.get_refcnt(py) == 1,
+            PyHandle::Weak(_) => false,
+        }
+    }
+
+    // the values are shared by the python object and every rust handle to it, so they identify it however it is held
+    fn identity(&self) -> usize {
+        Arc::as_ptr(&self.owned_py_item.py_values) as usize
     }
 }
 
 impl Default for StoredItem {
     fn default() -> Self {
         Self {
-            py_item: DEFAULT_PY_INDEXABLE_ARC.clone(),
+            py_item: PyHandle::Strong(DEFAULT_PY_INDEXABLE_ARC.clone()),
             owned_py_item: DEFAULT_INDEXABLE_ARC.clone(),
         }
     }
@@ -54,7 +128,7 @@ impl Default for StoredItem {
 
 impl PartialEq for StoredItem {
     fn eq(&self, other: &Self) -> bool {
-        self.py_item.as_ptr() == other.py_item.as_ptr()
+        self.identity() == other.identity()
     }
 }
 
@@ -62,10 +136,7 @@ impl Eq for StoredItem {}
 
 impl Hash for StoredItem {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        // Better:
-        let py_id = self.py_item.as_ptr();
-        py_id.hash(state);
-
+        self.identity().hash(state);
     }
 }
 
@@ -75,6 +146,9 @@ impl<'py> IntoPyObject<'py> for StoredItem {
     type Error = std::convert::Infallible;
 
     fn into_pyobject(self, py: Python<'py>) -> Result<Self::Output, Self::Error> {
-        Ok(self.py_item.clone_ref(py).into_pyobject(py)?.into_any())
+        match self.get_py_ref(py) {
+            Some(obj) => Ok(obj.into_pyobject(py)?.into_any()),
+            None => Ok(py.None().into_bound(py)),
+        }
     }
 }
\ No newline at end of file
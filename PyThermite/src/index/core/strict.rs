@@ -0,0 +1,69 @@
+use rustc_hash::FxHashSet;
+use pyo3::{PyResult, Python, exceptions::PyTypeError, types::{PyAnyMethods, PyTypeMethods}};
+
+use crate::index::{Indexable, core::{attr_decls::AttrDecls, structures::string_interner::INTERNER}, types::StrId, value::{PyIterable, PyValue, RustCastValue}};
+
+// first value only the exact map could hold, None is allowed since eq None is fully answered
+fn opaque_value(value: &PyValue) -> Option<&PyValue> {
+    match value.get_primitive() {
+        RustCastValue::Unknown if value.fields().is_none() => Some(value),
+        RustCastValue::Iterable(PyIterable::Dict(_)) => None,
+        RustCastValue::Iterable(_) => value.items().iter().find_map(opaque_value),
+        _ => None,
+    }
+}
+
+fn rejected(attr_id: StrId, value: &PyValue) -> pyo3::PyErr {
+    let type_name = Python::with_gil(|py| {
+        value.get_obj(py).bind(py).get_type().name()
+            .map(|name| name.to_string())
+            .unwrap_or_else(|_| "unknown".to_string())
+    });
+    PyTypeError::new_err(format!(
+        "strict index cannot index {} value of '{}', list the attribute as opaque to allow it",
+        type_name, INTERNER.resolve(attr_id)
+    ))
+}
+
+// checks a value about to be stored under attr_id, and the objects nested in it, against the declarations
+pub fn check_value(decls: &AttrDecls, attr_id: StrId, value: &PyValue, visited: &mut FxHashSet<u32>) -> PyResult<()> {
+    if !decls.is_indexed(attr_id) {
+        return Ok(());
+    }
+    let child = decls.for_child(attr_id);
+    if child.decl.opaque {
+        return Ok(());
+    }
+    if let Some(bad) = opaque_value(value) {
+        return Err(rejected(attr_id, bad));
+    }
+    if !child.allows_nesting() {
+        return Ok(());
+    }
+    let nested = match value.get_primitive() {
+        RustCastValue::Ind(_) => std::slice::from_ref(value),
+        RustCastValue::Iterable(PyIterable::Dict(_)) => &[],
+        _ => value.items(),
+    };
+    for element in nested {
+        if let RustCastValue::Ind(ind) = element.get_primitive() {
+            check_object(&child, &ind.owned_handle, visited)?;
+        }
+    }
+    if let Some(fields) = value.fields() {
+        check_object(&child, &fields.owned_handle, visited)?;
+    }
+    Ok(())
+}
+
+pub fn check_object(decls: &AttrDecls, obj: &Indexable, visited: &mut FxHashSet<u32>) -> PyResult<()> {
+    if !visited.insert(obj.id) {
+        return Ok(());
+    }
+    // copied out so objects pointing back at obj can be locked while walking
+    let values: Vec<(StrId, PyValue)> = obj.get_py_values().iter()
+        .filter(|(attr_id, _)| obj.is_indexed(**attr_id))
+        .map(|(attr_id, value)| (*attr_id, value.clone()))
+        .collect();
+    values.iter().try_for_each(|(attr_id, value)| check_value(decls, *attr_id, value, visited))
+}
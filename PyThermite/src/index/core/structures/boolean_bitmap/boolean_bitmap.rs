@@ -53,12 +53,21 @@ impl BooleanBitmap {
         self.flush_false();
     }
 
+    pub fn optimize(&mut self) {
+        self.true_bitmap.optimize();
+        self.false_bitmap.optimize();
+    }
+
     #[inline(always)]
     pub fn merge(&mut self, other: &BooleanBitmap) {
         self.true_bitmap.or_inplace(&other.true_bitmap);
         self.false_bitmap.or_inplace(&other.false_bitmap);
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.true_bitmap.is_empty() && self.false_bitmap.is_empty()
+    }
+
     #[inline(always)]
     pub fn get_exact(&self, value: bool) -> &Bitmap {
         [&self.false_bitmap, &self.true_bitmap][value as usize]
@@ -84,6 +84,13 @@ impl<const N: usize> BufferedBitmap<N> {
         fn remove(&mut self, to_remove: u32);
     }
 
+    // pending ids flushed and runs compressed, for indexes done changing
+    pub fn optimize(&mut self) {
+        self.flush();
+        self.inner.run_optimize();
+        self.inner.shrink_to_fit();
+    }
+
 }
 
 impl<const N: usize> Default for BufferedBitmap<N> {
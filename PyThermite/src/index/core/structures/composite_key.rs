@@ -42,6 +42,14 @@ impl CompositeKey128 {
         }
     }
 
+    #[inline(always)]
+    pub fn encode_key(key: Key) -> u128 {
+        match key {
+            Key::Int(int) => Self::encode_i64_to_float76(int),
+            Key::FloatOrdered(float) => Self::encode_f64_to_float76(float),
+        }
+    }
+
     #[inline(always)]
     pub fn encode_f64_to_float76(val: OrderedFloat<f64>) -> u128 {
 
@@ -108,8 +116,24 @@ impl CompositeKey128 {
 
     }
 
+    // mantissa carries its leading 1 at bit 63 and is worth mantissa * 2^(leading - 63)
+    pub fn encode_parts_to_float76(negative: bool, leading: i32, mantissa: u64) -> u128 {
+        let exponent = (EXPONENT_BIAS as i32 + leading) as u128;
+        let key_bits = ((negative as u128) << SIGN_BIT_POS) | (exponent << MANTISSA_BITS) | mantissa as u128;
+        if negative {
+            !key_bits
+        } else {
+            key_bits | 1u128 << SIGN_BIT_POS
+        }
+    }
+
     pub fn decode_float(&self) -> f64 {
-        let mut key = self.get_value_bits() & ((1u128 << FLOAT_LENGTH)-1);
+        Self::decode_float76(self.get_value_bits())
+    }
+
+    // inverse of encode_f64_to_float76, ints come back as the nearest float
+    pub fn decode_float76(bits: u128) -> f64 {
+        let mut key = bits & ((1u128 << FLOAT_LENGTH)-1);
 
         if key == (1u128 << SIGN_BIT_POS) {
             return 0.0;
@@ -160,6 +184,13 @@ impl CompositeKey128 {
         if was_neg { -abs } else { abs }
     }
 
+    // big endian and padded to whole bytes, so byte order matches numeric order
+    pub fn float76_to_bytes(bits: u128) -> [u8; 10] {
+        let mut bytes = [0u8; 10];
+        bytes.copy_from_slice(&(bits << (80 - FLOAT_LENGTH)).to_be_bytes()[6..]);
+        bytes
+    }
+
     #[inline(always)]
     pub fn get_id(&self) -> u32 {
         // (self.raw & 0xFFFF_FFFF) as u32
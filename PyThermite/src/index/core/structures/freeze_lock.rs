@@ -0,0 +1,167 @@
+use std::{fmt, ops::Deref, sync::{LockResult, OnceLock, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError, TryLockResult}};
+
+// a RwLock whose value can be frozen: freeze moves it out of the lock, and from then on reads hand it out
+// without taking the lock. for the structures of a frozen index, nothing writes them once frozen.
+// the frozen value is boxed so the unfrozen lock costs a pointer more than a RwLock
+pub struct FreezeLock<T> {
+    lock: RwLock<T>,
+    frozen: OnceLock<Box<T>>,
+}
+
+pub enum FreezeReadGuard<'a, T> {
+    Locked(RwLockReadGuard<'a, T>),
+    Frozen(&'a T),
+    // the write lock, taken by read_exclusive
+    Exclusive(RwLockWriteGuard<'a, T>),
+}
+
+impl<T> Deref for FreezeReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match self {
+            Self::Locked(guard) => guard,
+            Self::Frozen(value) => value,
+            Self::Exclusive(guard) => guard,
+        }
+    }
+}
+
+impl<T> FreezeLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            lock: RwLock::new(value),
+            frozen: OnceLock::new(),
+        }
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.get().is_some()
+    }
+
+    pub fn read(&self) -> LockResult<FreezeReadGuard<'_, T>> {
+        if let Some(value) = self.frozen.get() {
+            return Ok(FreezeReadGuard::Frozen(value));
+        }
+        match self.lock.read() {
+            Ok(guard) => Ok(self.or_frozen(FreezeReadGuard::Locked(guard))),
+            Err(poisoned) => Err(PoisonError::new(self.or_frozen(FreezeReadGuard::Locked(poisoned.into_inner())))),
+        }
+    }
+
+    pub fn try_read(&self) -> TryLockResult<FreezeReadGuard<'_, T>> {
+        if let Some(value) = self.frozen.get() {
+            return Ok(FreezeReadGuard::Frozen(value));
+        }
+        match self.lock.try_read() {
+            Ok(guard) => Ok(self.or_frozen(FreezeReadGuard::Locked(guard))),
+            Err(TryLockError::Poisoned(poisoned)) => Err(TryLockError::Poisoned(PoisonError::new(self.or_frozen(FreezeReadGuard::Locked(poisoned.into_inner()))))),
+            Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+        }
+    }
+
+    // reads with writers and other readers held off until the guard is dropped, a frozen value has none to hold off
+    pub fn read_exclusive(&self) -> LockResult<FreezeReadGuard<'_, T>> {
+        if let Some(value) = self.frozen.get() {
+            return Ok(FreezeReadGuard::Frozen(value));
+        }
+        match self.lock.write() {
+            Ok(guard) => Ok(self.or_frozen(FreezeReadGuard::Exclusive(guard))),
+            Err(poisoned) => Err(PoisonError::new(self.or_frozen(FreezeReadGuard::Exclusive(poisoned.into_inner())))),
+        }
+    }
+
+    // panics once frozen, the value the lock holds by then is an empty default
+    pub fn write(&self) -> LockResult<RwLockWriteGuard<'_, T>> {
+        let guard = self.lock.write();
+        assert!(!self.is_frozen(), "frozen structure written");
+        guard
+    }
+
+    pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<'_, T>> {
+        let guard = self.lock.try_write();
+        assert!(!self.is_frozen(), "frozen structure written");
+        guard
+    }
+
+    // a reader that found the value unfrozen and took the lock after freeze let it go reads the frozen value,
+    // freeze sets it before releasing the lock
+    fn or_frozen<'a>(&'a self, guard: FreezeReadGuard<'a, T>) -> FreezeReadGuard<'a, T> {
+        match self.frozen.get() {
+            Some(value) => FreezeReadGuard::Frozen(value),
+            None => guard,
+        }
+    }
+}
+
+impl<T: Default> FreezeLock<T> {
+    // waits out readers and writers holding the lock, frozen values stay as they are
+    pub fn freeze(&self) {
+        let Ok(mut guard) = self.lock.write() else {
+            return;
+        };
+        if !self.is_frozen() {
+            let _ = self.frozen.set(Box::new(std::mem::take(&mut *guard)));
+        }
+    }
+}
+
+impl<T: Default> Default for FreezeLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for FreezeLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.read() {
+            Ok(value) => f.debug_tuple("FreezeLock").field(&*value).finish(),
+            Err(_) => f.write_str("FreezeLock(<poisoned>)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn reads_frozen_value_without_the_lock() {
+        let lock = FreezeLock::new(vec![1, 2]);
+        lock.write().unwrap().push(3);
+        lock.freeze();
+
+        assert!(matches!(lock.read().unwrap(), FreezeReadGuard::Frozen(_)));
+        assert_eq!(*lock.read().unwrap(), [1, 2, 3]);
+        // the lock is never taken again, a writer holding it would block a locking read
+        let _held = lock.lock.write().unwrap();
+        assert_eq!(*lock.try_read().unwrap(), [1, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "frozen structure written")]
+    fn write_after_freeze_panics() {
+        let lock = FreezeLock::new(0);
+        lock.freeze();
+        drop(lock.write());
+    }
+
+    #[test]
+    fn readers_racing_freeze_see_the_value() {
+        let lock = Arc::new(FreezeLock::new(vec![7; 64]));
+        let readers: Vec<_> = (0..4).map(|_| {
+            let lock = lock.clone();
+            thread::spawn(move || {
+                for _ in 0..10_000 {
+                    assert_eq!(lock.read().unwrap().len(), 64);
+                }
+            })
+        }).collect();
+        lock.freeze();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use croaring::Bitmap;
+
+use crate::index::core::{query::b_tree::Key, structures::{composite_key::CompositeKey128, ordered_bitmap::NumericalBitmap}};
+
+// closed [start, end] intervals as one ordered index per endpoint
+#[derive(Debug, Default)]
+pub struct IntervalBitmap {
+    starts: NumericalBitmap,
+    ends: NumericalBitmap,
+}
+
+impl IntervalBitmap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, start: Key, end: Key, id: u32) {
+        self.starts.add(CompositeKey128::encode_key(start), id);
+        self.ends.add(CompositeKey128::encode_key(end), id);
+    }
+
+    pub fn remove(&mut self, start: Key, end: Key, id: u32) {
+        self.starts.remove(CompositeKey128::encode_key(start), id);
+        self.ends.remove(CompositeKey128::encode_key(end), id);
+    }
+
+    pub fn keep_only(&mut self, ids: &Bitmap) {
+        self.starts.keep_only(ids);
+        self.ends.keep_only(ids);
+    }
+
+    pub fn merge(&mut self, other: &IntervalBitmap) {
+        self.starts.merge(&other.starts);
+        self.ends.merge(&other.ends);
+    }
+
+    pub fn optimize(&mut self) {
+        self.starts.optimize();
+        self.ends.optimize();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.starts.is_empty()
+    }
+
+    // intervals sharing at least one point with [lower, upper]
+    pub fn overlaps(&self, lower: Key, upper: Key, valid: &Bitmap) -> Bitmap {
+        let started = self.starts.get_lte_from_valid(CompositeKey128::encode_key(upper), valid);
+        self.ends.get_gte_from_valid(CompositeKey128::encode_key(lower), &started)
+    }
+
+    pub fn contains_point(&self, point: Key, valid: &Bitmap) -> Bitmap {
+        self.overlaps(point, point, valid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn intervals() -> IntervalBitmap {
+        let mut idx = IntervalBitmap::new();
+        idx.add(Key::Int(0), Key::Int(10), 1);
+        idx.add(Key::Int(5), Key::Int(7), 2);
+        idx.add(Key::Int(-4), Key::Int(-1), 3);
+        idx.add(Key::Int(10), Key::Int(20), 4);
+        idx
+    }
+
+    #[test]
+    fn overlaps_includes_touching_endpoints() {
+        let idx = intervals();
+        let valid = Bitmap::of(&[1, 2, 3, 4]);
+
+        assert_eq!(idx.overlaps(Key::Int(8), Key::Int(12), &valid).to_vec(), vec![1, 4]);
+        assert_eq!(idx.overlaps(Key::Int(-1), Key::Int(0), &valid).to_vec(), vec![1, 3]);
+        assert!(idx.overlaps(Key::Int(21), Key::Int(30), &valid).is_empty());
+        assert_eq!(idx.overlaps(Key::Int(-100), Key::Int(100), &Bitmap::of(&[2, 3])).to_vec(), vec![2, 3]);
+    }
+
+    #[test]
+    fn contains_point_and_remove() {
+        let mut idx = intervals();
+        let valid = Bitmap::of(&[1, 2, 3, 4]);
+
+        assert_eq!(idx.contains_point(Key::Int(6), &valid).to_vec(), vec![1, 2]);
+        idx.remove(Key::Int(5), Key::Int(7), 2);
+        assert_eq!(idx.contains_point(Key::Int(6), &valid).to_vec(), vec![1]);
+        assert_eq!(idx.contains_point(Key::Int(10), &valid).to_vec(), vec![1, 4]);
+    }
+}
@@ -0,0 +1,3 @@
+mod interval_bitmap;
+
+pub use interval_bitmap::IntervalBitmap;
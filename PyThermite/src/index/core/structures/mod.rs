@@ -7,4 +7,7 @@ pub mod ordered_bitmap;
 pub mod composite_key;
 pub mod boolean_bitmap;
 pub mod m2m;
-pub mod buffered_bitmap;
\ No newline at end of file
+pub mod buffered_bitmap;
+pub mod interval_bitmap;
+pub mod wide_bitmap;
+pub mod freeze_lock;
@@ -55,6 +55,12 @@ impl NumericBitIndex {
         }
     }
 
+    pub fn optimize(&mut self) {
+        for byte_id in 0..2 {
+            self.bits[byte_id].optimize();
+        }
+    }
+
     pub fn all(&self) -> Bitmap {
         Bitmap::fast_or(
             &[
@@ -130,6 +136,12 @@ impl NumericalBitmap {
         }
     }
 
+    pub fn optimize(&mut self) {
+        for bit in self.bits.iter_mut() {
+            bit.optimize();
+        }
+    }
+
     #[inline(always)]
     pub fn get_exact(&self, value: u128) -> Bitmap {
         let mut res = Bitmap::new();
@@ -137,12 +149,26 @@ impl NumericalBitmap {
         res
     }
 
+    // every id sets one side of the lowest bit
+    pub fn is_empty(&self) -> bool {
+        self.bits[0].all().is_empty()
+    }
+
     pub fn merge(&mut self, other: &NumericalBitmap) {
         for (self_bit, other_bit) in self.bits.iter_mut().zip(other.bits.iter()) {
             self_bit.merge(other_bit);
         }
     }
 
+    // upper bound on what get_exact returns, from the two smallest bit slices it would intersect
+    pub fn estimate_exact(&self, value: u128) -> u64 {
+        let mut slices: Vec<&Bitmap> = (0..BIT_LENGTH)
+            .map(|bit| self.bits[bit].contains(((value >> bit) & 1) as usize))
+            .collect();
+        slices.sort_by_cached_key(|slice| slice.cardinality());
+        slices[0].and_cardinality(slices[1])
+    }
+
     #[inline(always)]
     pub fn get_exact_into(&self, value: u128, out: &mut Bitmap) {
         let first_bit = 0;
@@ -156,6 +182,48 @@ impl NumericalBitmap {
         }
     }
 
+    // ids whose value has bit set
+    pub fn ones(&self, bit: usize) -> &Bitmap {
+        self.bits[bit].contains(1)
+    }
+
+    // every id holding a value
+    pub fn ids(&self) -> Bitmap {
+        self.bits[0].all()
+    }
+
+    // (id, value) of every id of valid holding a value, ascending by id
+    pub fn values_from_valid(&self, valid: &Bitmap) -> Vec<(u32, u128)> {
+        let ids = self.ids().and(valid);
+        let mut values: Vec<(u32, u128)> = ids.iter().map(|id| (id, 0)).collect();
+        for bit in 0..BIT_LENGTH {
+            for id in self.bits[bit].contains(1).and(&ids).iter() {
+                values[ids.rank(id) as usize - 1].1 |= 1 << bit;
+            }
+        }
+        values
+    }
+
+    // value of rank k (0 is the smallest) among the ids of valid, narrowing from the top bit down
+    pub fn kth_from_valid(&self, mut k: u64, valid: &Bitmap) -> Option<u128> {
+        let mut candidates = self.ids().and(valid);
+        if k >= candidates.cardinality() {
+            return None;
+        }
+        let mut value = 0u128;
+        for bit in (0..BIT_LENGTH).rev() {
+            let zeros = candidates.and(self.bits[bit].contains(0));
+            let below = zeros.cardinality();
+            if k < below {
+                candidates = zeros;
+            } else {
+                k -= below;
+                candidates.and_inplace(self.bits[bit].contains(1));
+                value |= 1 << bit;
+            }
+        }
+        Some(value)
+    }
 }
 
 impl Default for NumericalBitmap {
@@ -172,6 +240,17 @@ mod tests {
     use super::*;
     use croaring::Bitmap;
 
+    #[test]
+    fn values_from_valid_decodes_each_id() {
+        let mut idx = NumericalBitmap::new();
+        idx.add(0b1011u128, 7);
+        idx.add(1u128 << 75, 3);
+        idx.add(0, 9);
+
+        assert_eq!(idx.values_from_valid(&Bitmap::of(&[3, 7, 9, 11])), vec![(3, 1u128 << 75), (7, 0b1011), (9, 0)]);
+        assert_eq!(idx.values_from_valid(&Bitmap::of(&[7])), vec![(7, 0b1011)]);
+    }
+
     #[test]
     fn empty_index_returns_empty() {
         let idx = NumericalBitmap::new();
@@ -223,6 +302,24 @@ mod tests {
         assert!(res.contains(3));
     }
 
+    #[test]
+    fn kth_walks_ranks_in_order() {
+        let mut idx = NumericalBitmap::new();
+
+        idx.add(0b1100u128, 1);
+        idx.add(0b0011u128, 2);
+        idx.add(0b0110u128, 3);
+        idx.add(0b0011u128, 4);
+
+        let valid = Bitmap::of(&[1, 2, 3, 4]);
+        assert_eq!(idx.kth_from_valid(0, &valid), Some(0b0011));
+        assert_eq!(idx.kth_from_valid(1, &valid), Some(0b0011));
+        assert_eq!(idx.kth_from_valid(2, &valid), Some(0b0110));
+        assert_eq!(idx.kth_from_valid(3, &valid), Some(0b1100));
+        assert_eq!(idx.kth_from_valid(4, &valid), None);
+        assert_eq!(idx.kth_from_valid(0, &Bitmap::of(&[1, 3])), Some(0b0110));
+    }
+
     #[test]
     fn remove_existing_id() {
         let mut idx = NumericalBitmap::new();
@@ -307,4 +404,19 @@ mod tests {
         assert!(r3.contains(3));
     }
 
+
+    #[test]
+    fn empty_after_remove() {
+        let mut idx = NumericalBitmap::new();
+        assert!(idx.is_empty());
+
+        idx.add(0b1011u128, 1);
+        idx.add(0b0101u128, 2);
+        idx.remove(0b1011u128, 1);
+        assert!(!idx.is_empty());
+
+        idx.remove(0b0101u128, 2);
+        assert!(idx.is_empty());
+    }
+
 }
\ No newline at end of file
@@ -1,4 +1,5 @@
 
 mod positional_bitmap;
+mod overflow;
 
-pub use positional_bitmap::PositionalBitmap;
\ No newline at end of file
+pub use positional_bitmap::{PositionalBitmap, DEFAULT_MAX_STR_LEN};
\ No newline at end of file
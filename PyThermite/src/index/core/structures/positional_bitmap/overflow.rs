@@ -0,0 +1,145 @@
+use std::{collections::BTreeMap, ops::Bound};
+
+use croaring::Bitmap;
+use rustc_hash::{FxHashMap, FxHashSet};
+use smol_str::SmolStr;
+
+// strings past the positional map's length cap, looked up by content instead of scanned
+#[derive(Debug, Default)]
+pub struct OverflowStrings {
+    // ids per string, and per string reversed so ends_with is a prefix range too
+    forward: BTreeMap<SmolStr, Bitmap>,
+    reversed: BTreeMap<SmolStr, SmolStr>,
+    // strings holding each adjacent character pair, a single character is keyed as [c, c]
+    pairs: FxHashMap<[char; 2], FxHashSet<SmolStr>>,
+}
+
+fn pairs_of(s: &str) -> impl Iterator<Item = [char; 2]> + '_ {
+    let singles = s.chars().map(|c| [c, c]);
+    let adjacent = s.chars().zip(s.chars().skip(1)).map(|(a, b)| [a, b]);
+    singles.chain(adjacent)
+}
+
+fn reverse(s: &str) -> SmolStr {
+    s.chars().rev().collect()
+}
+
+impl OverflowStrings {
+    pub fn add(&mut self, s: &str, id: u32) {
+        if let Some(ids) = self.forward.get_mut(s) {
+            ids.add(id);
+            return;
+        }
+        let s = SmolStr::new(s);
+        self.reversed.insert(reverse(&s), s.clone());
+        for pair in pairs_of(&s) {
+            self.pairs.entry(pair).or_default().insert(s.clone());
+        }
+        self.forward.insert(s, Bitmap::of(&[id]));
+    }
+
+    pub fn remove(&mut self, s: &str, id: u32) {
+        if let Some(ids) = self.forward.get_mut(s) {
+            ids.remove(id);
+            if ids.is_empty() {
+                self.forget(s);
+            }
+        }
+    }
+
+    // drops a string no id holds any more
+    fn forget(&mut self, s: &str) {
+        self.forward.remove(s);
+        self.reversed.remove(&reverse(s));
+        for pair in pairs_of(s) {
+            if let Some(held) = self.pairs.get_mut(&pair) {
+                held.remove(s);
+                if held.is_empty() {
+                    self.pairs.remove(&pair);
+                }
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.forward.is_empty()
+    }
+
+    pub fn keep_only(&mut self, ids: &Bitmap) {
+        let mut emptied = vec![];
+        for (s, held) in self.forward.iter_mut() {
+            held.and_inplace(ids);
+            if held.is_empty() {
+                emptied.push(s.clone());
+            }
+        }
+        for s in emptied {
+            self.forget(&s);
+        }
+    }
+
+    pub fn merge(&mut self, other: &OverflowStrings) {
+        for (s, ids) in other.forward.iter() {
+            match self.forward.get_mut(s) {
+                Some(held) => held.or_inplace(ids),
+                None => {
+                    for id in ids.iter() {
+                        self.add(s, id);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn optimize(&mut self) {
+        for ids in self.forward.values_mut() {
+            ids.run_optimize();
+        }
+    }
+
+    pub fn all(&self) -> Bitmap {
+        let held: Vec<&Bitmap> = self.forward.values().collect();
+        Bitmap::fast_or(&held)
+    }
+
+    pub fn exact(&self, s: &str) -> Bitmap {
+        self.forward.get(s).cloned().unwrap_or_default()
+    }
+
+    pub fn starts_with(&self, prefix: &str) -> Bitmap {
+        let held: Vec<&Bitmap> = self.forward.range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(|(s, _)| s.starts_with(prefix))
+            .map(|(_, ids)| ids)
+            .collect();
+        Bitmap::fast_or(&held)
+    }
+
+    pub fn ends_with(&self, suffix: &str) -> Bitmap {
+        let reversed = reverse(suffix);
+        let held: Vec<&Bitmap> = self.reversed.range::<str, _>((Bound::Included(reversed.as_str()), Bound::Unbounded))
+            .take_while(|(r, _)| r.starts_with(reversed.as_str()))
+            .filter_map(|(_, s)| self.forward.get(s))
+            .collect();
+        Bitmap::fast_or(&held)
+    }
+
+    // only strings holding every character pair of inner are checked
+    pub fn contains(&self, inner: &str) -> Bitmap {
+        let mut sets = vec![];
+        for pair in pairs_of(inner) {
+            match self.pairs.get(&pair) {
+                Some(held) => sets.push(held),
+                None => return Bitmap::new(),
+            }
+        }
+        sets.sort_by_key(|held| held.len());
+        let Some((smallest, rest)) = sets.split_first() else {
+            return self.all();
+        };
+        let held: Vec<&Bitmap> = smallest.iter()
+            .filter(|s| rest.iter().all(|other| other.contains(*s)) && s.contains(inner))
+            .filter_map(|s| self.forward.get(s))
+            .collect();
+        Bitmap::fast_or(&held)
+    }
+}
@@ -1,13 +1,74 @@
+use std::{borrow::Borrow, cmp::Ordering, collections::BTreeMap, ops::Bound};
+
 use croaring::Bitmap;
+use once_cell::sync::Lazy;
+use smallvec::SmallVec;
 
-use crate::index::core::structures::buffered_bitmap::BufferedBitmap;
+use crate::index::{core::structures::{buffered_bitmap::BufferedBitmap, positional_bitmap::overflow::OverflowStrings, string_interner::{INTERNER, InternedStr}}, types::StrId};
 
 const BUFF_SIZE: usize = 32;
 
+// strings with more characters than this are matched by content instead of by position
+pub const DEFAULT_MAX_STR_LEN: usize = 256;
+
+static EMPTY: Lazy<Bitmap> = Lazy::new(Bitmap::new);
+
+type Chars = SmallVec<[char; 32]>;
+
+// an interned string keyed by id, ordered by its text so ranges walk in code point order
 #[derive(Debug, Clone)]
+struct OrderedKey {
+    id: StrId,
+    text: InternedStr,
+}
+
+impl OrderedKey {
+    fn intern(s: &str) -> Self {
+        let (id, text) = INTERNER.intern_shared(s);
+        Self { id, text }
+    }
+
+    fn as_str(&self) -> &str {
+        self.text.as_str()
+    }
+}
+
+impl PartialEq for OrderedKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for OrderedKey {}
+
+impl Ord for OrderedKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self.id == other.id {
+            return Ordering::Equal;
+        }
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl PartialOrd for OrderedKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Borrow<str> for OrderedKey {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 struct CharacterMap {
-    maps_u8: [BufferedBitmap<BUFF_SIZE>; 256],
-    boundry_bytes: BufferedBitmap<BUFF_SIZE> // used to mark boundries for start - end
+    // only the characters seen at this position, sorted
+    maps: Vec<(char, BufferedBitmap<BUFF_SIZE>)>,
+    // ids whose string starts / ends at this position
+    starts: BufferedBitmap<BUFF_SIZE>,
+    ends: BufferedBitmap<BUFF_SIZE>,
 }
 
 impl CharacterMap {
@@ -16,144 +77,208 @@ impl CharacterMap {
     }
 
     #[inline(always)]
-    pub fn add(&mut self, byte_id: u8, id: u32, is_boundry: bool) {
-        if is_boundry {
-            self.boundry_bytes.add(id);
-        }
+    fn column_mut(&mut self, ch: char) -> &mut BufferedBitmap<BUFF_SIZE> {
+        let pos = match self.maps.binary_search_by_key(&ch, |(c, _)| *c) {
+            Ok(pos) => pos,
+            Err(pos) => {
+                self.maps.insert(pos, (ch, BufferedBitmap::new()));
+                pos
+            }
+        };
         unsafe {
-            self.maps_u8.get_unchecked_mut(byte_id as usize).add(id)
+            &mut self.maps.get_unchecked_mut(pos).1
         }
     }
 
     #[inline(always)]
-    pub fn add_delayed(&mut self, byte_id: u8, id: u32, is_boundry: bool) {
-        if is_boundry {
-            self.boundry_bytes.add_delayed(id);
+    pub fn add(&mut self, ch: char, id: u32, is_start: bool, is_end: bool) {
+        if is_start {
+            self.starts.add(id);
         }
-        unsafe {
-            self.maps_u8.get_unchecked_mut(byte_id as usize).add_delayed(id)
+        if is_end {
+            self.ends.add(id);
+        }
+        self.column_mut(ch).add(id)
+    }
+
+    #[inline(always)]
+    pub fn add_delayed(&mut self, ch: char, id: u32, is_start: bool, is_end: bool) {
+        if is_start {
+            self.starts.add_delayed(id);
         }
+        if is_end {
+            self.ends.add_delayed(id);
+        }
+        self.column_mut(ch).add_delayed(id)
     }
 
     pub fn keep_only(&mut self, ids: &Bitmap) {
-        self.boundry_bytes.and_inplace(ids);
-        for byte_id in 0..256 {
-            unsafe {
-                let byte_map = self.maps_u8.get_unchecked_mut(byte_id);
-                byte_map.and_inplace(ids);
-            }
+        self.starts.and_inplace(ids);
+        self.ends.and_inplace(ids);
+        for (_, char_map) in self.maps.iter_mut() {
+            char_map.and_inplace(ids);
         }
     }
 
     #[inline(always)]
-    pub fn remove(&mut self, byte_id: u8, id: u32) {
-        self.boundry_bytes.remove(id);
-        unsafe {
-            self.maps_u8.get_unchecked_mut(byte_id as usize).remove(id)
+    pub fn remove(&mut self, ch: char, id: u32) {
+        self.starts.remove(id);
+        self.ends.remove(id);
+        if let Ok(pos) = self.maps.binary_search_by_key(&ch, |(c, _)| *c) {
+            self.maps[pos].1.remove(id)
         }
     }
 
     #[inline(always)]
-    pub fn get_boundry_bytes(&self) -> &Bitmap {
-        &self.boundry_bytes
+    pub fn get_starts(&self) -> &Bitmap {
+        &self.starts
+    }
+
+    #[inline(always)]
+    pub fn get_ends(&self) -> &Bitmap {
+        &self.ends
     }
 
     #[inline(always)]
     pub fn flush(&mut self) {
-        self.boundry_bytes.flush();
-        for byte_id in 0..256 {
-            unsafe {
-                let byte_map = self.maps_u8.get_unchecked_mut(byte_id);
-                byte_map.flush();
-            }
+        self.starts.flush();
+        self.ends.flush();
+        for (_, char_map) in self.maps.iter_mut() {
+            char_map.flush();
         }
     }
 
-    #[inline(always)]
-    pub fn merge(&mut self, other: &CharacterMap) {
-        unsafe {
-            for byte_id in 0..256 {
-                self.maps_u8.get_unchecked_mut(byte_id).or_inplace(other.contains(byte_id as u8));
-            }
+    pub fn optimize(&mut self) {
+        self.starts.optimize();
+        self.ends.optimize();
+        for (_, char_map) in self.maps.iter_mut() {
+            char_map.optimize();
         }
-        self.boundry_bytes.or_inplace(&other.boundry_bytes);
+        self.maps.shrink_to_fit();
     }
 
     #[inline(always)]
-    pub fn contains(&self, byte_id: u8) -> &Bitmap {
-        unsafe {
-            self.maps_u8.get_unchecked(byte_id as usize)
+    pub fn merge(&mut self, other: &CharacterMap) {
+        for (ch, other_map) in other.maps.iter() {
+            self.column_mut(*ch).or_inplace(other_map);
         }
+        self.starts.or_inplace(&other.starts);
+        self.ends.or_inplace(&other.ends);
     }
-}
 
-impl Default for CharacterMap {
-    fn default() -> Self {
-        Self { 
-            maps_u8: std::array::from_fn( |_| BufferedBitmap::new()),
-            boundry_bytes: BufferedBitmap::new()
+    #[inline(always)]
+    pub fn contains(&self, ch: char) -> &Bitmap {
+        match self.maps.binary_search_by_key(&ch, |(c, _)| *c) {
+            Ok(pos) => &self.maps[pos].1,
+            Err(_) => &EMPTY,
         }
     }
 }
 
-#[derive(Debug, Default)]
+// strings are laid out by code point, centered in the map so that
+// strings of any length share the middle positions
+#[derive(Debug)]
 pub struct PositionalBitmap {
     map: Vec<CharacterMap>,
     empty: BufferedBitmap<BUFF_SIZE>,
+    max_len: usize,
+    overflow: OverflowStrings,
+    // every string held, in code point order, for range queries
+    ordered: BTreeMap<OrderedKey, Bitmap>,
+}
+
+impl Default for PositionalBitmap {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl PositionalBitmap {
     pub fn new() -> Self {
+        Self::with_max_len(DEFAULT_MAX_STR_LEN)
+    }
+
+    pub fn with_max_len(max_len: usize) -> Self {
         Self {
             map: Vec::new(),
             empty: BufferedBitmap::new(),
+            max_len,
+            overflow: OverflowStrings::default(),
+            ordered: BTreeMap::new(),
         }
     }
 
     #[inline(always)]
     pub fn add(&mut self, s: &str, id: u32) {
-        let bytes = s.as_bytes();
-        self.ensure_size(bytes);
-        let start = self.get_start(bytes);
-        match bytes.len() {
-            0 => self.empty.add(id),
-            _ => {
-                for i in 0..bytes.len() {
-                    let is_boundry = i == 0 || i == bytes.len() - 1;
-                    self.map[i + start].add(bytes[i], id, is_boundry);
-                }
-            }
+        self.add_ordered(s, id);
+        let chars: Chars = s.chars().collect();
+        if chars.len() > self.max_len {
+            self.overflow.add(s, id);
+            return;
+        }
+        if chars.is_empty() {
+            self.empty.add(id);
+            return;
+        }
+        self.ensure_size(chars.len());
+        let start = self.get_start(chars.len());
+        let last = chars.len() - 1;
+        for (i, ch) in chars.iter().enumerate() {
+            self.map[i + start].add(*ch, id, i == 0, i == last);
         }
     }
 
     #[inline(always)]
     pub fn add_delayed(&mut self, s: &str, id: u32) {
-        let bytes = s.as_bytes();
-        self.ensure_size(bytes);
-        let start = self.get_start(bytes);
-        match bytes.len() {
-            0 => self.empty.add(id),
-            _ => {
-                for i in 0..bytes.len() {
-                    let is_boundry = i == 0 || i == bytes.len() - 1;
-                    self.map[i + start].add_delayed(bytes[i], id, is_boundry);
-                }
-            }
+        self.add_ordered(s, id);
+        let chars: Chars = s.chars().collect();
+        if chars.len() > self.max_len {
+            self.overflow.add(s, id);
+            return;
+        }
+        if chars.is_empty() {
+            self.empty.add(id);
+            return;
+        }
+        self.ensure_size(chars.len());
+        let start = self.get_start(chars.len());
+        let last = chars.len() - 1;
+        for (i, ch) in chars.iter().enumerate() {
+            self.map[i + start].add_delayed(*ch, id, i == 0, i == last);
         }
     }
 
     #[inline(always)]
     pub fn remove(&mut self, s: &str, id: u32) {
-        let bytes = s.as_bytes();
-        let start = ((self.map.len() / 2) - (bytes.len() / 2)).saturating_sub(1);
-        match bytes.len() {
-            0 => self.empty.remove(id),
-            _ => {
-                for i in 0..bytes.len() {
-                    self.map[i + start].remove(bytes[i], id);
-                }
+        if let Some(ids) = self.ordered.get_mut(s) {
+            ids.remove(id);
+            if ids.is_empty() {
+                self.ordered.remove(s);
             }
         }
+        let chars: Chars = s.chars().collect();
+        if chars.len() > self.max_len {
+            self.overflow.remove(s, id);
+            return;
+        }
+        if chars.is_empty() {
+            self.empty.remove(id);
+            return;
+        }
+        if !self.fits(chars.len()) {
+            return;
+        }
+        let start = self.get_start(chars.len());
+        for (i, ch) in chars.iter().enumerate() {
+            self.map[i + start].remove(*ch, id);
+        }
+    }
+
+    // every non empty string marks its first character as a start
+    pub fn is_empty(&self) -> bool {
+        self.empty.is_empty()
+            && self.overflow.is_empty()
+            && self.map.iter().all(|cm| cm.get_starts().is_empty())
     }
 
     #[inline(always)]
@@ -162,60 +287,151 @@ impl PositionalBitmap {
             cm.keep_only(ids);
         }
         self.empty.and_inplace(ids);
+        self.overflow.keep_only(ids);
+        self.ordered.retain(|_, held| {
+            held.and_inplace(ids);
+            !held.is_empty()
+        });
+    }
+
+    fn add_ordered(&mut self, s: &str, id: u32) {
+        match self.ordered.get_mut(s) {
+            Some(ids) => ids.add(id),
+            None => {
+                self.ordered.insert(OrderedKey::intern(s), Bitmap::of(&[id]));
+            }
+        }
+    }
+
+    // ids holding a string between the bounds, compared by code point
+    pub fn range(&self, lower: Bound<&str>, upper: Bound<&str>) -> Bitmap {
+        let empty = match (lower, upper) {
+            (Bound::Included(lo), Bound::Included(hi)) => lo > hi,
+            (Bound::Included(lo) | Bound::Excluded(lo), Bound::Included(hi) | Bound::Excluded(hi)) => lo >= hi,
+            _ => false,
+        };
+        if empty {
+            return Bitmap::new();
+        }
+        let held: Vec<&Bitmap> = self.ordered.range::<str, _>((lower, upper)).map(|(_, ids)| ids).collect();
+        Bitmap::fast_or(&held)
+    }
+
+    // ids holding each string, in code point order of the strings
+    pub fn ordered(&self) -> impl DoubleEndedIterator<Item = &Bitmap> {
+        self.ordered.values()
     }
 
     #[inline(always)]
     fn get_all(&self) -> Bitmap {
         let mut res = Bitmap::new();
-        for i in 0..(self.map.len() + 1) / 2 {
-            res.or_inplace(self.map[i].get_boundry_bytes());
+        for cm in self.map.iter().take(self.map.len() / 2) {
+            res.or_inplace(cm.get_starts());
         }
         res.or_inplace(&self.empty);
+        res.or_inplace(&self.overflow.all());
         res
     }
 
     #[inline(always)]
     pub fn get_exact(&self, chars: &str) -> Bitmap {
+        let mut res = self.get_exact_positional(chars);
+        res.or_inplace(&self.overflow.exact(chars));
+        res
+    }
+
+    // how many ids get_exact returns, without intersecting the position maps
+    pub fn exact_count(&self, chars: &str) -> u64 {
+        self.ordered.get(chars).map_or(0, |ids| ids.cardinality())
+    }
+
+    #[inline(always)]
+    pub fn starts_with(&self, chars: &str) -> Bitmap {
+        if chars.is_empty() {
+            return self.get_all()
+        }
+        let mut res = self.starts_with_positional(chars);
+        res.or_inplace(&self.overflow.starts_with(chars));
+        res
+    }
+
+    #[inline(always)]
+    pub fn ends_with(&self, chars: &str) -> Bitmap {
+        if chars.is_empty() {
+            return self.get_all()
+        }
+        let mut res = self.ends_with_positional(chars);
+        res.or_inplace(&self.overflow.ends_with(chars));
+        res
+    }
+
+    #[inline(always)]
+    pub fn contains(&self, chars: &str) -> Bitmap {
+        if chars.is_empty() {
+            return self.get_all()
+        }
+        let mut res = self.contains_positional(chars);
+        res.or_inplace(&self.overflow.contains(chars));
+        res
+    }
+
+    // ids whose string passes is_match. the literal prefix and suffix every match has narrow the
+    // candidates by position first, is_match only runs once per distinct string left
+    pub fn matches<F: Fn(&str) -> bool>(&self, prefix: &str, suffix: &str, is_match: F) -> Bitmap {
+        let mut candidates = self.starts_with(prefix);
+        if !suffix.is_empty() {
+            candidates.and_inplace(&self.ends_with(suffix));
+        }
         let mut res = Bitmap::new();
-        let bytes = chars.as_bytes();
-        if bytes.len() > self.map.len() {
+        if candidates.is_empty() {
             return res;
-        } else if bytes.is_empty() {
+        }
+        let held = self.ordered.range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(|(s, _)| s.as_str().starts_with(prefix));
+        for (s, ids) in held {
+            if ids.intersect(&candidates) && is_match(s.as_str()) {
+                res.or_inplace(ids);
+            }
+        }
+        res
+    }
+
+    #[inline(always)]
+    fn get_exact_positional(&self, chars: &str) -> Bitmap {
+        let chars: Chars = chars.chars().collect();
+        if chars.is_empty() {
+            let mut res = Bitmap::new();
             res.or_inplace(&self.empty);
             return res;
+        } else if !self.fits(chars.len()) {
+            return Bitmap::new();
         }
 
-        let start = ((self.map.len() / 2) - (bytes.len() / 2)).saturating_sub(1);
-
-        res.or_inplace(self.map[start].contains(bytes[0]));
-        res.and_inplace(self.map[start].get_boundry_bytes());
-
-        for i in 1..bytes.len() {
-            res.and_inplace(self.map[i + start].contains(bytes[i]));
+        let start = self.get_start(chars.len());
+        let mut res = self.map[start].get_starts().clone();
+        for (i, ch) in chars.iter().enumerate() {
+            res.and_inplace(self.map[i + start].contains(*ch));
         }
-
-        res.and_inplace(self.map[start + bytes.len() - 1].get_boundry_bytes());
+        res.and_inplace(self.map[start + chars.len() - 1].get_ends());
         res
     }
 
     #[inline(always)]
-    pub fn starts_with(&self, chars: &str) -> Bitmap {
-        let bytes = chars.as_bytes();
-        if bytes.is_empty() {
-            return self.get_all()
-        }
+    fn starts_with_positional(&self, chars: &str) -> Bitmap {
+        let chars: Chars = chars.chars().collect();
         let mut res = Bitmap::new();
+        if chars.len() > self.map.len() {
+            return res;
+        }
         let mut inner_res = Bitmap::new();
-        let upper_bound = usize::min(self.map.len() / 2, self.map.len().saturating_sub(bytes.len()));
+        // every string starts in the first half of the map
+        let upper_bound = usize::min(self.map.len() / 2, self.map.len() - chars.len() + 1);
         for pos in 0..upper_bound {
-
-            let byte_map = &self.map[pos];
             inner_res.clear();
-            inner_res.or_inplace(byte_map.get_boundry_bytes());
-            inner_res.and_inplace(byte_map.contains(bytes[0]));
+            inner_res.or_inplace(self.map[pos].get_starts());
 
-            for inner_pos in 1..bytes.len() {
-                inner_res.and_inplace(&self.map[pos + inner_pos].contains(bytes[inner_pos]));
+            for (inner_pos, ch) in chars.iter().enumerate() {
+                inner_res.and_inplace(self.map[pos + inner_pos].contains(*ch));
             }
 
             res.or_inplace(&inner_res);
@@ -224,26 +440,22 @@ impl PositionalBitmap {
     }
 
     #[inline(always)]
-    pub fn ends_with(&self, chars: &str) -> Bitmap {
-        let bytes = chars.as_bytes();
-        if bytes.is_empty() {
-            return self.get_all()
-        }
+    fn ends_with_positional(&self, chars: &str) -> Bitmap {
+        let chars: Chars = chars.chars().collect();
         let mut res = Bitmap::new();
+        if chars.len() > self.map.len() {
+            return res;
+        }
         let mut inner_res = Bitmap::new();
-        let max_byte_index = bytes.len().saturating_sub(1);
-
-        let lower_bound = usize::max(max_byte_index, (self.map.len() / 2).saturating_sub(1));
-        for pos in (lower_bound..self.map.len()).rev() {
-
-            let byte_map = &self.map[pos];
+        let last = chars.len() - 1;
+        // every string ends in the second half of the map
+        let lower_bound = usize::max(last, (self.map.len() / 2).saturating_sub(1));
+        for pos in lower_bound..self.map.len() {
             inner_res.clear();
-            inner_res.or_inplace(byte_map.get_boundry_bytes());
-            inner_res.and_inplace(byte_map.contains(bytes[max_byte_index]));
+            inner_res.or_inplace(self.map[pos].get_ends());
 
-            for inner_pos in (0..max_byte_index).rev() {
-                let map_position = pos + inner_pos - max_byte_index;
-                inner_res.and_inplace(&self.map[map_position].contains(bytes[inner_pos]));
+            for (inner_pos, ch) in chars.iter().enumerate() {
+                inner_res.and_inplace(self.map[pos - last + inner_pos].contains(*ch));
             }
 
             res.or_inplace(&inner_res);
@@ -252,23 +464,22 @@ impl PositionalBitmap {
     }
 
     #[inline(always)]
-    pub fn contains(&self, chars: &str) -> Bitmap {
-        let bytes = chars.as_bytes();
-        if bytes.is_empty() {
-            return self.get_all()
-        }
+    fn contains_positional(&self, chars: &str) -> Bitmap {
+        let chars: Chars = chars.chars().collect();
         let mut res = Bitmap::new();
+        if chars.len() > self.map.len() {
+            return res;
+        }
         let mut inner_res = Bitmap::new();
-
-        let upper_bound = usize::min((self.map.len() / 2) + 1, self.map.len().saturating_sub(bytes.len().saturating_sub(1)));
-        for pos in 0..upper_bound {
-
-            let byte_map = &self.map[pos];
+        for pos in 0..=(self.map.len() - chars.len()) {
             inner_res.clear();
-            inner_res.or_inplace(byte_map.contains(bytes[0]));
+            inner_res.or_inplace(self.map[pos].contains(chars[0]));
 
-            for inner in 1..bytes.len() {
-                inner_res.and_inplace(&self.map[pos + inner].contains(bytes[inner]));
+            for (inner_pos, ch) in chars.iter().enumerate().skip(1) {
+                if inner_res.is_empty() {
+                    break;
+                }
+                inner_res.and_inplace(self.map[pos + inner_pos].contains(*ch));
             }
 
             res.or_inplace(&inner_res);
@@ -281,9 +492,16 @@ impl PositionalBitmap {
             self.expand_map(other.map.len());
         }
 
-        for (self_cm, other_cm) in self.map.iter_mut().zip(other.map.iter()) {
+        // a smaller map is centered within this one
+        let offset = (self.map.len() - other.map.len()) / 2;
+        for (self_cm, other_cm) in self.map[offset..].iter_mut().zip(other.map.iter()) {
             self_cm.merge(other_cm);
         }
+        self.empty.or_inplace(&other.empty);
+        self.overflow.merge(&other.overflow);
+        for (s, ids) in other.ordered.iter() {
+            self.ordered.entry(s.clone()).or_default().or_inplace(ids);
+        }
     }
 
     pub fn flush(&mut self) {
@@ -292,13 +510,36 @@ impl PositionalBitmap {
         }
     }
 
-    fn get_start(&self, bytes: &[u8]) -> usize {
-        ((self.map.len() / 2) - (bytes.len() / 2)).saturating_sub(1)
+    pub fn optimize(&mut self) {
+        for char_map in self.map.iter_mut() {
+            char_map.optimize();
+        }
+        self.empty.optimize();
+        self.overflow.optimize();
+        for ids in self.ordered.values_mut() {
+            ids.run_optimize();
+        }
+    }
+
+    // a string of len characters needs a free slot on each side of the center
+    #[inline(always)]
+    fn required_size(len: usize) -> usize {
+        (len / 2) * 2 + 2
+    }
+
+    #[inline(always)]
+    fn fits(&self, len: usize) -> bool {
+        Self::required_size(len) <= self.map.len()
+    }
+
+    #[inline(always)]
+    fn get_start(&self, len: usize) -> usize {
+        (self.map.len() / 2) - (len / 2) - 1
     }
 
-    fn ensure_size(&mut self, bytes: &[u8]) {
-        if bytes.len() > self.map.len() {
-            self.expand_map(bytes.len());
+    fn ensure_size(&mut self, len: usize) {
+        if !self.fits(len) {
+            self.expand_map(Self::required_size(len));
         }
     }
 
@@ -495,4 +736,152 @@ mod tests {
         assert!(result3.contains(3));
 
     }
+
+    #[test]
+    fn test_overflow_past_max_len() {
+        let mut pb = PositionalBitmap::with_max_len(4);
+        pb.add("abc", 1);
+        pb.add("abcdefgh", 2);
+        pb.add_delayed("xxabcdxx", 3);
+        pb.flush();
+
+        assert!(pb.map.len() <= 6, "long strings should not widen the positional map");
+        assert_eq!(pb.get_exact("abcdefgh").iter().collect::<Vec<_>>(), vec![2]);
+        assert_eq!(pb.starts_with("abc").iter().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(pb.ends_with("fgh").iter().collect::<Vec<_>>(), vec![2]);
+        assert_eq!(pb.contains("abcd").iter().collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(pb.starts_with("").cardinality(), 3);
+
+        pb.remove("abcdefgh", 2);
+        assert_eq!(pb.contains("abc").iter().collect::<Vec<_>>(), vec![1, 3]);
+
+        let mut keep = Bitmap::new();
+        keep.add(1);
+        pb.keep_only(&keep);
+        assert_eq!(pb.contains("abc").iter().collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[test]
+    fn test_overflow_lookups_by_content() {
+        let mut pb = PositionalBitmap::with_max_len(4);
+        pb.add("hello world", 1);
+        pb.add("hello there", 2);
+        pb.add("hello world", 3);
+        pb.add("a", 4);
+
+        assert_eq!(pb.get_exact("hello world").iter().collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(pb.starts_with("hello").iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(pb.ends_with("there").iter().collect::<Vec<_>>(), vec![2]);
+        assert_eq!(pb.contains("o w").iter().collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(pb.contains("w").iter().collect::<Vec<_>>(), vec![1, 3]);
+        assert!(pb.contains("wx").is_empty());
+
+        pb.remove("hello world", 1);
+        assert_eq!(pb.contains("world").iter().collect::<Vec<_>>(), vec![3]);
+        pb.remove("hello world", 3);
+        assert!(pb.contains("world").is_empty());
+        assert!(pb.ends_with("world").is_empty());
+
+        let mut other = PositionalBitmap::with_max_len(4);
+        other.add("goodbye world", 5);
+        pb.merge(&other);
+        assert_eq!(pb.contains("world").iter().collect::<Vec<_>>(), vec![5]);
+        assert_eq!(pb.starts_with("").cardinality(), 3);
+    }
+
+    #[test]
+    fn test_columns_only_for_seen_chars() {
+        let mut pb = PositionalBitmap::new();
+        pb.add("ab", 1);
+        pb.add("ac", 2);
+        pb.add("ab", 3);
+
+        let columns: usize = pb.map.iter().map(|cm| cm.maps.len()).sum();
+        assert_eq!(columns, 3);
+        assert_eq!(pb.get_exact("ab").iter().collect::<Vec<_>>(), vec![1, 3]);
+        assert!(pb.get_exact("zz").is_empty());
+
+        let mut other = PositionalBitmap::new();
+        other.add("zc", 4);
+        pb.merge(&other);
+        assert_eq!(pb.ends_with("c").iter().collect::<Vec<_>>(), vec![2, 4]);
+    }
+
+    #[test]
+    fn test_mixed_lengths() {
+        let mut pb = PositionalBitmap::new();
+        pb.add("hello", 1);
+        pb.add("ab", 2);
+        pb.add("abcdefghi", 3);
+        pb.add("b", 4);
+
+        assert_eq!(pb.get_exact("hello").iter().collect::<Vec<_>>(), vec![1]);
+        assert_eq!(pb.get_exact("abcdefghi").iter().collect::<Vec<_>>(), vec![3]);
+        assert_eq!(pb.contains("ghi").iter().collect::<Vec<_>>(), vec![3]);
+        assert_eq!(pb.contains("o").iter().collect::<Vec<_>>(), vec![1]);
+        assert_eq!(pb.starts_with("b").iter().collect::<Vec<_>>(), vec![4]);
+        assert_eq!(pb.ends_with("a").iter().collect::<Vec<_>>(), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn test_unicode() {
+        let mut pb = PositionalBitmap::new();
+        pb.add("héllo", 1);
+        pb.add("日本語", 2);
+        pb.add("naïve café", 3);
+        pb.add("e", 4);
+
+        assert_eq!(pb.get_exact("héllo").iter().collect::<Vec<_>>(), vec![1]);
+        assert_eq!(pb.get_exact("日本語").iter().collect::<Vec<_>>(), vec![2]);
+        assert_eq!(pb.starts_with("hé").iter().collect::<Vec<_>>(), vec![1]);
+        assert_eq!(pb.starts_with("日本").iter().collect::<Vec<_>>(), vec![2]);
+        assert_eq!(pb.ends_with("語").iter().collect::<Vec<_>>(), vec![2]);
+        assert_eq!(pb.ends_with("café").iter().collect::<Vec<_>>(), vec![3]);
+        assert_eq!(pb.contains("é").iter().collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(pb.contains("本").iter().collect::<Vec<_>>(), vec![2]);
+        assert_eq!(pb.contains("e").iter().collect::<Vec<_>>(), vec![3, 4]);
+
+        pb.remove("日本語", 2);
+        assert!(pb.contains("本").is_empty());
+    }
+
+    #[test]
+    fn test_range() {
+        let mut pb = PositionalBitmap::with_max_len(4);
+        pb.add("A100", 1);
+        pb.add("A150", 2);
+        pb.add("A200", 3);
+        pb.add("B", 4);
+        pb.add("A1500000", 5);
+        let ids = |bm: Bitmap| bm.iter().collect::<Vec<_>>();
+
+        assert_eq!(ids(pb.range(Bound::Included("A100"), Bound::Included("A200"))), vec![1, 2, 3, 5]);
+        assert_eq!(ids(pb.range(Bound::Excluded("A100"), Bound::Excluded("A200"))), vec![2, 5]);
+        assert_eq!(ids(pb.range(Bound::Excluded("A150"), Bound::Unbounded)), vec![3, 4, 5]);
+        assert_eq!(ids(pb.range(Bound::Unbounded, Bound::Excluded("A150"))), vec![1]);
+        assert!(pb.range(Bound::Included("B"), Bound::Included("A")).is_empty());
+        assert!(pb.range(Bound::Excluded("B"), Bound::Excluded("B")).is_empty());
+
+        pb.remove("A150", 2);
+        pb.keep_only(&Bitmap::of(&[1, 3, 4]));
+        assert_eq!(ids(pb.range(Bound::Unbounded, Bound::Unbounded)), vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn test_matches() {
+        let mut pb = PositionalBitmap::with_max_len(6);
+        pb.add("ab-12", 1);
+        pb.add("ab-x2", 2);
+        pb.add("ac-12", 3);
+        pb.add("ab-123456", 4);
+        pb.add("ab-12", 5);
+        let ids = |bm: Bitmap| bm.iter().collect::<Vec<_>>();
+        let digits = |s: &str| s[3..].chars().all(|c| c.is_ascii_digit());
+
+        assert_eq!(ids(pb.matches("ab-", "", digits)), vec![1, 4, 5]);
+        assert_eq!(ids(pb.matches("ab-", "2", digits)), vec![1, 5]);
+        assert_eq!(ids(pb.matches("", "2", |_| true)), vec![1, 2, 3, 5]);
+        // nothing left after pruning, so is_match never runs
+        assert!(pb.matches("zz", "", |_| panic!()).is_empty());
+    }
 }
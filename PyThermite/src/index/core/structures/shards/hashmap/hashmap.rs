@@ -1,10 +1,12 @@
-use std::{collections::HashMap, hash::{BuildHasher, Hash, Hasher}, sync::{Arc, RwLock, RwLockWriteGuard}};
+use std::{collections::HashMap, hash::{BuildHasher, Hash, Hasher}, sync::{Arc, RwLockWriteGuard}};
+
+use crate::index::core::structures::freeze_lock::FreezeLock;
 
 use rustc_hash::FxBuildHasher;
 
 #[derive(Clone)]
 pub struct ShardedHashMap<K, V> {
-    shards: Arc<[RwLock<HashMap<K, V>>]>,
+    shards: Arc<[FreezeLock<HashMap<K, V>>]>,
     mask: usize,
 }
 
@@ -20,7 +22,7 @@ where
         let mut shards = Vec::with_capacity(shard_count);
         for _ in 0..shard_count {
             let map: HashMap<K, V> = HashMap::new();
-            shards.push(RwLock::new(map));
+            shards.push(FreezeLock::new(map));
         }
 
         Self {
@@ -62,6 +64,13 @@ where
         guard.get(key).cloned()
     }
 
+    // reads the value in place, for callers that only need something derived from it
+    pub fn get_with<R>(&self, key: &K, f: impl FnOnce(&V) -> R) -> Option<R> {
+        let shard_idx = self.shard_for(key);
+        let guard = self.shards[shard_idx].read().unwrap();
+        guard.get(key).map(f)
+    }
+
     pub fn get_shard(&self, key: &K) -> RwLockWriteGuard<HashMap<K, V>> {
         let shard_idx = self.shard_for(key);
         let guard = self.shards[shard_idx].write().unwrap();
@@ -107,6 +116,22 @@ where
         self.shards.iter().all(|shard| shard.read().unwrap().is_empty())
     }
 
+    // drops the entries keep rejects and rehashes each shard to fit what is left
+    pub fn compact<F: FnMut(&mut V) -> bool>(&self, mut keep: F) {
+        for shard in self.shards.iter() {
+            let mut guard = shard.write().unwrap();
+            guard.retain(|_, v| keep(v));
+            guard.shrink_to_fit();
+        }
+    }
+
+    // moves every shard out of its lock, see FreezeLock
+    pub fn freeze(&self) {
+        for shard in self.shards.iter() {
+            shard.freeze();
+        }
+    }
+
 }
 
 impl<K, V> Default for ShardedHashMap<K, V>
@@ -0,0 +1,80 @@
+use std::cell::RefCell;
+
+use rustc_hash::FxHashMap;
+use smol_str::SmolStr;
+
+use crate::index::{core::structures::string_interner::INTERNER, types::StrId};
+
+// attribute ids resolved per class (type pointer -> name -> id) so steady state
+// setattr never loads the interner snapshot or takes its lock.
+// ids are global per name, so a type pointer reused by another class only shares entries.
+// bounded, the least recently used class is dropped past MAX_CLASSES and names past
+// MAX_ATTRS_PER_CLASS go straight to the interner, so classes made on the fly can't grow it without end
+const MAX_CLASSES: usize = 256;
+const MAX_ATTRS_PER_CLASS: usize = 256;
+
+#[derive(Default)]
+struct ClassAttrIds {
+    classes: FxHashMap<usize, (u64, FxHashMap<SmolStr, StrId>)>,
+    tick: u64,
+}
+
+thread_local! {
+    static CLASS_ATTR_IDS: RefCell<ClassAttrIds> = RefCell::new(ClassAttrIds::default());
+}
+
+pub fn intern_for_class(type_ptr: usize, name: &str) -> StrId {
+    CLASS_ATTR_IDS.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        cache.tick += 1;
+        let tick = cache.tick;
+        if !cache.classes.contains_key(&type_ptr) && cache.classes.len() >= MAX_CLASSES {
+            let oldest = cache.classes.iter().min_by_key(|(_, (used, _))| *used).map(|(ptr, _)| *ptr);
+            if let Some(oldest) = oldest {
+                cache.classes.remove(&oldest);
+            }
+        }
+        let (used, attrs) = cache.classes.entry(type_ptr).or_default();
+        *used = tick;
+        if let Some(id) = attrs.get(name) {
+            return *id;
+        }
+        let id = INTERNER.intern(name);
+        if attrs.len() < MAX_ATTRS_PER_CLASS {
+            attrs.insert(SmolStr::new(name), id);
+        }
+        id
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cached_ids_match_interner() {
+        let a = intern_for_class(1, "class_cache_a");
+        assert_eq!(a, INTERNER.intern("class_cache_a"));
+        assert_eq!(intern_for_class(1, "class_cache_a"), a);
+        // another class sees the same id for the same name
+        assert_eq!(intern_for_class(2, "class_cache_a"), a);
+        assert_ne!(intern_for_class(1, "class_cache_b"), a);
+    }
+
+    #[test]
+    fn cache_stays_bounded() {
+        for ptr in 0..MAX_CLASSES * 2 {
+            intern_for_class(1000 + ptr, "class_cache_c");
+        }
+        for i in 0..MAX_ATTRS_PER_CLASS * 2 {
+            assert_eq!(intern_for_class(1, &format!("class_cache_{}", i)), INTERNER.intern(&format!("class_cache_{}", i)));
+        }
+        CLASS_ATTR_IDS.with(|cache| {
+            let cache = cache.borrow();
+            assert!(cache.classes.len() <= MAX_CLASSES);
+            assert!(cache.classes.values().all(|(_, attrs)| attrs.len() <= MAX_ATTRS_PER_CLASS));
+            // the most recently used class is kept
+            assert!(cache.classes.contains_key(&1));
+        });
+    }
+}
@@ -6,7 +6,7 @@ use crate::index::{core::structures::string_interner::{ImmutableInterner, Mutabl
 
 
 
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct InternedStr {
     pub(crate) ptr: Arc<[u8]>,
 }
@@ -40,6 +40,18 @@ impl StrInterner {
         id
     }
 
+    // the id along with the interner's shared copy of the text
+    pub fn intern_shared(&self, s: &str) -> (StrId, InternedStr) {
+        let id = self.intern(s);
+        (id, self.snapshot.load().strings[id as usize].clone())
+    }
+
+    // the id and shared copy of s when it has been interned already, without interning it
+    pub fn get_shared(&self, s: &str) -> Option<(StrId, InternedStr)> {
+        let snapshot = self.snapshot.load();
+        snapshot.get(s).map(|id| (id, snapshot.strings[id as usize].clone()))
+    }
+
     pub fn resolve(&self, id: StrId) -> String {
         self.snapshot.load().resolve(id).to_owned()
     }
@@ -49,6 +61,12 @@ impl StrInterner {
     }
 }
 
+impl InternedStr {
+    pub fn as_str(&self) -> &str {
+        unsafe { std::str::from_utf8_unchecked(&self.ptr) }
+    }
+}
+
 
 #[cfg(test)]
 mod tests {
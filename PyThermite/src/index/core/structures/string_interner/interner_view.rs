@@ -6,15 +6,15 @@ use crate::index::{core::structures::string_interner::{ImmutableInterner, StrInt
 
 
 
-pub struct StrInternerView<'a> {
-    interner: &'a StrInterner,
+// read only snapshot for resolving many ids without reloading the interner,
+// interning goes through intern_for_class or the interner itself
+pub struct StrInternerView {
     snapshot: Guard<Arc<ImmutableInterner>>,
 }
 
-impl<'a> StrInternerView<'a> {
-    pub fn new(interner: &'a StrInterner) -> Self {
+impl StrInternerView {
+    pub fn new(interner: &StrInterner) -> Self {
         Self {
-            interner,
             snapshot: interner.snapshot.load(),
         }
     }
@@ -22,19 +22,4 @@ impl<'a> StrInternerView<'a> {
     pub fn resolve(&self, id: StrId) -> &str {
         self.snapshot.resolve(id)
     }
-
-    pub fn intern(&mut self, s: &str) -> StrId {
-        if let Some(id) = self.snapshot.get(s) {
-            return id;
-        }
-
-        let id = self.interner.intern(s);
-        self.snapshot = self.interner.snapshot.load();
-
-        id
-    }
-
-    pub fn len(&self) -> usize {
-        self.snapshot.len()
-    }
 }
\ No newline at end of file
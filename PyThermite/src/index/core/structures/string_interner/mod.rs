@@ -2,12 +2,16 @@ mod interner;
 mod immutable_interner;
 mod mutable_interner;
 mod interner_view;
+mod class_cache;
+mod str_value;
 
 pub use interner::StrInterner;
 pub use interner_view::StrInternerView;
 pub use interner::InternedStr;
 pub use immutable_interner::ImmutableInterner;
 pub use mutable_interner::MutableInterner;
+pub use class_cache::intern_for_class;
+pub use str_value::StrValue;
 
 pub static INTERNER: once_cell::sync::Lazy<StrInterner> = once_cell::sync::Lazy::new(|| {
     StrInterner::with_capacity(1024)
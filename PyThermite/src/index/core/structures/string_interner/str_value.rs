@@ -0,0 +1,133 @@
+use std::{borrow::Borrow, cmp::Ordering, fmt, hash::{Hash, Hasher}, ops::Deref};
+
+use smol_str::SmolStr;
+
+use crate::index::{core::structures::string_interner::{INTERNER, InternedStr}, types::StrId};
+
+// the text of a str value. strings an index has interned already are held as the interner's id and shared copy,
+// so objects repeating them share one. the rest keep their own, values read for queries or never indexed
+// don't grow the interner
+#[derive(Debug, Clone)]
+pub enum StrValue {
+    Interned(StrId, InternedStr),
+    Own(SmolStr),
+}
+
+impl StrValue {
+    pub fn new(s: &str) -> Self {
+        match INTERNER.get_shared(s) {
+            Some((id, text)) => Self::Interned(id, text),
+            None => Self::Own(SmolStr::new(s)),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Interned(_, text) => text.as_str(),
+            Self::Own(text) => text.as_str(),
+        }
+    }
+}
+
+impl Deref for StrValue {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for StrValue {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for StrValue {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for StrValue {
+    fn from(s: &str) -> Self {
+        Self::new(s)
+    }
+}
+
+impl From<String> for StrValue {
+    fn from(s: String) -> Self {
+        Self::new(&s)
+    }
+}
+
+impl PartialEq for StrValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Interned(a, _), Self::Interned(b, _)) => a == b,
+            _ => self.as_str() == other.as_str(),
+        }
+    }
+}
+
+impl Eq for StrValue {}
+
+impl Ord for StrValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl PartialOrd for StrValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// by the text, as str hashes, so interned and own copies of a string hash alike
+impl Hash for StrValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl fmt::Display for StrValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interned_strings_share_one_copy() {
+        INTERNER.intern("str value shared by every object holding it");
+        let a = StrValue::new("str value shared by every object holding it");
+        let b = StrValue::new(&String::from("str value shared by every object holding it"));
+
+        match (&a, &b) {
+            (StrValue::Interned(id_a, text_a), StrValue::Interned(id_b, text_b)) => {
+                assert_eq!(id_a, id_b);
+                assert!(std::ptr::eq(text_a.as_str(), text_b.as_str()));
+            },
+            _ => panic!("interned text not shared"),
+        }
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn unseen_strings_are_not_interned() {
+        let own = StrValue::new("str value no index has seen");
+
+        assert!(matches!(own, StrValue::Own(_)));
+        assert!(INTERNER.get_shared("str value no index has seen").is_none());
+
+        INTERNER.intern("str value no index has seen");
+        let interned = StrValue::new("str value no index has seen");
+        assert_eq!(own, interned);
+        assert_eq!(own.cmp(&interned), Ordering::Equal);
+        assert_eq!(&*interned, "str value no index has seen");
+    }
+}
@@ -0,0 +1,5 @@
+mod wide_num;
+mod wide_bitmap;
+
+pub use wide_num::WideNum;
+pub use wide_bitmap::WideBitmap;
@@ -0,0 +1,105 @@
+use std::{collections::BTreeMap, ops::Bound};
+
+use croaring::Bitmap;
+
+use crate::index::core::structures::wide_bitmap::WideNum;
+
+// numbers the numeric index can't hold exactly, in order, each with the ids holding it
+#[derive(Debug, Default)]
+pub struct WideBitmap {
+    values: BTreeMap<WideNum, Bitmap>,
+}
+
+impl WideBitmap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, value: &WideNum, id: u32) {
+        self.values.entry(value.clone()).or_default().add(id);
+    }
+
+    pub fn remove(&mut self, value: &WideNum, id: u32) {
+        if let Some(ids) = self.values.get_mut(value) {
+            ids.remove(id);
+            if ids.is_empty() {
+                self.values.remove(value);
+            }
+        }
+    }
+
+    pub fn keep_only(&mut self, ids: &Bitmap) {
+        self.values.retain(|_, held| {
+            held.and_inplace(ids);
+            !held.is_empty()
+        });
+    }
+
+    pub fn merge(&mut self, other: &WideBitmap) {
+        for (value, ids) in other.values.iter() {
+            self.values.entry(value.clone()).or_default().or_inplace(ids);
+        }
+    }
+
+    pub fn optimize(&mut self) {
+        for ids in self.values.values_mut() {
+            ids.run_optimize();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    // ascending by value
+    pub fn ordered(&self) -> impl DoubleEndedIterator<Item = (&WideNum, &Bitmap)> {
+        self.values.iter()
+    }
+
+    pub fn get_exact(&self, value: &WideNum) -> Bitmap {
+        self.values.get(value).cloned().unwrap_or_default()
+    }
+
+    pub fn range(&self, lower: Bound<&WideNum>, upper: Bound<&WideNum>, valid: &Bitmap) -> Bitmap {
+        // BTreeMap::range panics on inverted bounds
+        if let (Bound::Included(l) | Bound::Excluded(l), Bound::Included(u) | Bound::Excluded(u)) = (lower, upper) {
+            let both_inclusive = matches!((lower, upper), (Bound::Included(_), Bound::Included(_)));
+            if l > u || (l == u && !both_inclusive) {
+                return Bitmap::new();
+            }
+        }
+        let parts: Vec<&Bitmap> = self.values.range((lower, upper)).map(|(_, ids)| ids).collect();
+        let mut res = Bitmap::fast_or(&parts);
+        res.and_inplace(valid);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::BigUint;
+
+    fn tenths(n: u32) -> WideNum {
+        WideNum::new(false, BigUint::from(n), -1).unwrap()
+    }
+
+    #[test]
+    fn range_and_remove() {
+        let mut idx = WideBitmap::new();
+        idx.add(&tenths(1), 1);
+        idx.add(&tenths(3), 2);
+        idx.add(&tenths(3), 3);
+        let valid = Bitmap::of(&[1, 2, 3]);
+
+        assert_eq!(idx.range(Bound::Excluded(&tenths(1)), Bound::Unbounded, &valid).to_vec(), vec![2, 3]);
+        assert_eq!(idx.range(Bound::Included(&tenths(1)), Bound::Included(&tenths(3)), &Bitmap::of(&[1, 3])).to_vec(), vec![1, 3]);
+        assert!(idx.range(Bound::Included(&tenths(3)), Bound::Excluded(&tenths(1)), &valid).is_empty());
+
+        idx.remove(&tenths(3), 2);
+        assert_eq!(idx.get_exact(&tenths(3)).to_vec(), vec![3]);
+        idx.remove(&tenths(3), 3);
+        idx.remove(&tenths(1), 1);
+        assert!(idx.is_empty());
+    }
+}
@@ -0,0 +1,241 @@
+use std::{cmp::Ordering, hash::{Hash, Hasher}};
+
+use num_bigint::{BigInt, BigUint, Sign};
+use ordered_float::OrderedFloat;
+
+use crate::index::core::{query::b_tree::Key, structures::composite_key::CompositeKey128};
+
+// float76 exponents a wide number may take, the normal f64 range
+const MIN_LEADING: i64 = -1022;
+const MAX_LEADING: i64 = 1023;
+// digits kept past this many bits are refused rather than compared slowly
+const MAX_DIGIT_BITS: u64 = 4096;
+// the encoders leave the bits above a negative key's 76 set, ordering needs them cleared
+const FLOAT76_MASK: u128 = (1 << 76) - 1;
+
+// an exact decimal, digits * 10^exp10, for numbers the numeric index can't hold exactly -
+// python ints past i64 and decimal.Decimal values. digits keep no trailing zeros so equal
+// numbers share one representation
+#[derive(Debug, Clone)]
+pub struct WideNum {
+    negative: bool,
+    digits: BigUint,
+    exp10: i32,
+    // float76 key at or below the number and the least key not below it, equal when the
+    // number is exact as a float76. an inexact number sits strictly between floor and
+    // floor + 1, which is never a stored key
+    floor: u128,
+    ceil: u128,
+}
+
+impl WideNum {
+    // None when the magnitude falls outside 2^-1022 .. 2^1024
+    pub fn new(negative: bool, digits: BigUint, exp10: i32) -> Option<Self> {
+        let (negative, digits, exp10) = normalize(negative, digits, exp10);
+        let (floor, ceil) = float76_bounds(negative, &digits, exp10)?;
+        Some(Self { negative, digits, exp10, floor, ceil })
+    }
+
+    pub fn from_i64(value: i64) -> Self {
+        let (negative, digits, exp10) = normalize(value < 0, BigUint::from(value.unsigned_abs()), 0);
+        let key = CompositeKey128::encode_i64_to_float76(value) & FLOAT76_MASK;
+        Self { negative, digits, exp10, floor: key, ceil: key }
+    }
+
+    // exact, None for NaN and infinities
+    pub fn from_f64(value: f64) -> Option<Self> {
+        if !value.is_finite() {
+            return None;
+        }
+        let bits = value.to_bits();
+        let exp = ((bits >> 52) & 0x7FF) as i32;
+        let frac = bits & ((1 << 52) - 1);
+        let (mant, exp2) = if exp == 0 { (frac, -1074) } else { (frac | 1 << 52, exp - 1075) };
+        // m * 2^-k == m * 5^k * 10^-k
+        let (digits, exp10) = if exp2 >= 0 {
+            (BigUint::from(mant) << exp2 as usize, 0)
+        } else {
+            (BigUint::from(mant) * BigUint::from(5u32).pow(exp2.unsigned_abs()), exp2)
+        };
+        let (negative, digits, exp10) = normalize(value < 0.0, digits, exp10);
+        let key = CompositeKey128::encode_f64_to_float76(OrderedFloat(value)) & FLOAT76_MASK;
+        Some(Self { negative, digits, exp10, floor: key, ceil: key })
+    }
+
+    pub fn from_key(key: Key) -> Option<Self> {
+        match key {
+            Key::Int(i) => Some(Self::from_i64(i)),
+            Key::FloatOrdered(f) => Self::from_f64(f.0),
+        }
+    }
+
+    // the i64 or f64 key holding this number exactly, if there is one
+    pub fn to_key(&self) -> Option<Key> {
+        if self.floor != self.ceil {
+            return None;
+        }
+        if (0..=18).contains(&self.exp10) {
+            let sign = if self.negative { Sign::Minus } else { Sign::Plus };
+            let value = BigInt::from_biguint(sign, &self.digits * pow10(self.exp10 as u32));
+            if let Ok(i) = i64::try_from(&value) {
+                return Some(Key::Int(i));
+            }
+        }
+        let f = CompositeKey128::decode_float76(self.floor);
+        (CompositeKey128::encode_f64_to_float76(OrderedFloat(f)) & FLOAT76_MASK == self.floor).then_some(Key::FloatOrdered(OrderedFloat(f)))
+    }
+
+    pub fn floor(&self) -> u128 {
+        self.floor
+    }
+
+    pub fn ceil(&self) -> u128 {
+        self.ceil
+    }
+
+    // nearest f64 at or below the number
+    pub fn approx_f64(&self) -> f64 {
+        CompositeKey128::decode_float76(self.floor)
+    }
+}
+
+fn pow10(exp: u32) -> BigUint {
+    BigUint::from(10u32).pow(exp)
+}
+
+fn normalize(negative: bool, mut digits: BigUint, mut exp10: i32) -> (bool, BigUint, i32) {
+    if digits == BigUint::ZERO {
+        return (false, digits, 0);
+    }
+    let ten = BigUint::from(10u32);
+    while &digits % &ten == BigUint::ZERO {
+        digits /= &ten;
+        exp10 += 1;
+    }
+    (negative, digits, exp10)
+}
+
+fn float76_bounds(negative: bool, digits: &BigUint, exp10: i32) -> Option<(u128, u128)> {
+    if *digits == BigUint::ZERO {
+        let zero = CompositeKey128::encode_i64_to_float76(0);
+        return Some((zero, zero));
+    }
+    // rough log2 first, so far out exponents never build their power of ten
+    let approx = digits.bits() as f64 + exp10 as f64 * std::f64::consts::LOG2_10;
+    if digits.bits() > MAX_DIGIT_BITS || !(-1100.0..1100.0).contains(&approx) {
+        return None;
+    }
+    let (num, den) = if exp10 >= 0 {
+        (digits * pow10(exp10 as u32), BigUint::from(1u32))
+    } else {
+        (digits.clone(), pow10(exp10.unsigned_abs()))
+    };
+    // scaled so the quotient has 64 or 65 bits
+    let shift = num.bits() as i64 - den.bits() as i64 - 64;
+    let (num, den) = if shift >= 0 { (num, den << shift as usize) } else { (num << shift.unsigned_abs() as usize, den) };
+    let mut quotient = &num / &den;
+    let mut exact = &num % &den == BigUint::ZERO;
+    let mut leading = shift + 63;
+    if quotient.bits() > 64 {
+        exact &= !quotient.bit(0);
+        quotient >>= 1;
+        leading += 1;
+    }
+    let mut mantissa = u64::try_from(&quotient).ok()?;
+    // the magnitude was truncated, a negative number's floor lies one step further out
+    if negative && !exact {
+        mantissa = mantissa.checked_add(1).unwrap_or_else(|| {
+            leading += 1;
+            1 << 63
+        });
+    }
+    if !(MIN_LEADING..=MAX_LEADING).contains(&leading) {
+        return None;
+    }
+    let floor = CompositeKey128::encode_parts_to_float76(negative, leading as i32, mantissa) & FLOAT76_MASK;
+    Some((floor, if exact { floor } else { floor + 1 }))
+}
+
+impl PartialEq for WideNum {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for WideNum {}
+
+impl PartialOrd for WideNum {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for WideNum {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // float76 floors already order numbers in different gaps, only a shared gap needs the digits
+        self.floor.cmp(&other.floor).then_with(|| {
+            let exp10 = self.exp10.min(other.exp10);
+            let a = &self.digits * pow10((self.exp10 - exp10) as u32);
+            let b = &other.digits * pow10((other.exp10 - exp10) as u32);
+            if self.negative { b.cmp(&a) } else { a.cmp(&b) }
+        })
+    }
+}
+
+impl Hash for WideNum {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.negative.hash(state);
+        self.digits.hash(state);
+        self.exp10.hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(s: &str) -> WideNum {
+        let negative = s.starts_with('-');
+        let s = s.trim_start_matches('-');
+        let (int, frac) = s.split_once('.').unwrap_or((s, ""));
+        let digits = BigUint::parse_bytes(format!("{int}{frac}").as_bytes(), 10).unwrap();
+        WideNum::new(negative, digits, -(frac.len() as i32)).unwrap()
+    }
+
+    #[test]
+    fn orders_against_native_numbers() {
+        let tenth = dec("0.1");
+        assert!(tenth > WideNum::from_i64(0));
+        assert!(tenth < WideNum::from_f64(0.1).unwrap());
+        assert!(dec("-0.1") > WideNum::from_f64(-0.1).unwrap());
+        assert!(dec("-0.1") < WideNum::from_i64(0));
+
+        let big = dec("18446744073709551617");
+        assert!(big > WideNum::from_f64(18446744073709551616.0).unwrap());
+        assert!(big < dec("18446744073709551618"));
+        assert!(dec("-18446744073709551617") < dec("-18446744073709551616"));
+    }
+
+    #[test]
+    fn floor_and_ceil_bracket_the_number() {
+        let tenth = dec("0.1");
+        assert!(tenth.floor() < CompositeKey128::encode_f64_to_float76(OrderedFloat(0.1)));
+        assert_eq!(tenth.ceil(), tenth.floor() + 1);
+
+        // the f64 -0.1 lies further from zero than the float76 floor
+        let neg = dec("-0.1");
+        assert!(neg.floor() > CompositeKey128::encode_f64_to_float76(OrderedFloat(-0.1)) & FLOAT76_MASK);
+        assert!(neg.floor() > CompositeKey128::encode_i64_to_float76(-1) & FLOAT76_MASK);
+        assert!(neg.ceil() < CompositeKey128::encode_i64_to_float76(0));
+    }
+
+    #[test]
+    fn exact_values_map_to_native_keys() {
+        assert_eq!(dec("42.000").to_key(), Some(Key::Int(42)));
+        assert_eq!(dec("-1.5").to_key(), Some(Key::FloatOrdered(OrderedFloat(-1.5))));
+        assert_eq!(dec("1180591620717411303424").to_key(), Some(Key::FloatOrdered(OrderedFloat(2f64.powi(70)))));
+        assert_eq!(dec("0.1").to_key(), None);
+        assert_eq!(dec("9223372036854775809").to_key(), None);
+        assert_eq!(dec("1.50"), dec("1.5"));
+    }
+}
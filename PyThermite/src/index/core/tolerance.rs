@@ -0,0 +1,43 @@
+use smol_str::SmolStr;
+
+use crate::index::{core::query::query_ops::QueryExpr, value::{PyValue, RustCastValue}};
+
+fn band(attr: &SmolStr, value: &PyValue, tolerance: f64) -> Option<QueryExpr> {
+    let center = value.as_f64()?;
+    let bound = |v: f64| PyValue::from_primitave(RustCastValue::Float(v));
+    Some(QueryExpr::Bt(attr.clone(), bound(center - tolerance), bound(center + tolerance)))
+}
+
+// rewrites eq, ne, in and nin on numbers into ranges on attributes declared with a tolerance
+pub fn widen_eq<F: Fn(&str) -> Option<f64>>(expr: &mut QueryExpr, tolerance: &F) {
+    match expr {
+        QueryExpr::Eq(attr, v) => {
+            if let Some(widened) = tolerance(attr).and_then(|t| band(attr, v, t)) {
+                *expr = widened;
+            }
+        },
+        QueryExpr::Ne(attr, v) => {
+            if let Some(widened) = tolerance(attr).and_then(|t| band(attr, v, t)) {
+                *expr = QueryExpr::Not(Box::new(widened));
+            }
+        },
+        QueryExpr::In(attr, values) | QueryExpr::Nin(attr, values) => {
+            if let Some(t) = tolerance(attr) {
+                let branches = values.iter()
+                    .map(|v| band(attr, v, t).unwrap_or_else(|| QueryExpr::Eq(attr.clone(), v.clone())))
+                    .collect();
+                *expr = match expr {
+                    QueryExpr::In(..) => QueryExpr::Or(branches),
+                    _ => QueryExpr::Not(Box::new(QueryExpr::Or(branches))),
+                };
+            }
+        },
+        QueryExpr::Not(inner) => widen_eq(inner, tolerance),
+        QueryExpr::And(exprs) | QueryExpr::Or(exprs) => {
+            for e in exprs.iter_mut() {
+                widen_eq(e, tolerance);
+            }
+        },
+        _ => {},
+    }
+}
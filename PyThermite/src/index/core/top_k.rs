@@ -0,0 +1,78 @@
+use std::collections::BTreeSet;
+
+use croaring::Bitmap;
+use rustc_hash::FxHashMap;
+
+use crate::index::core::structures::ordered_bitmap::NumericalBitmap;
+
+// the k largest numeric values of one attribute, kept current as objects change.
+// ties on value keep the higher id
+#[derive(Debug, Default)]
+pub struct TopK {
+    k: usize,
+    // ascending by (encoded value, id), the largest last
+    members: BTreeSet<(u128, u32)>,
+    values: FxHashMap<u32, u128>,
+}
+
+impl TopK {
+    pub fn new(k: usize, num: Option<&NumericalBitmap>, allowed: &Bitmap) -> Self {
+        let mut top = Self { k, ..Default::default() };
+        top.rebuild(num, allowed);
+        top
+    }
+
+    // refills from the tail of the numeric index, everything at or above the value of rank k
+    fn rebuild(&mut self, num: Option<&NumericalBitmap>, allowed: &Bitmap) {
+        self.members.clear();
+        self.values.clear();
+        let Some(num) = num else {
+            return;
+        };
+        let population = num.ids().and(allowed);
+        let n = population.cardinality();
+        let tail = match n.checked_sub(self.k as u64).and_then(|rank| num.kth_from_valid(rank, &population)) {
+            Some(threshold) => num.get_gte_from_valid(threshold, &population),
+            None => population,
+        };
+        for (id, bits) in num.values_from_valid(&tail) {
+            self.offer(id, bits);
+        }
+    }
+
+    fn offer(&mut self, id: u32, bits: u128) {
+        self.members.insert((bits, id));
+        self.values.insert(id, bits);
+        if self.members.len() > self.k {
+            if let Some((_, evicted)) = self.members.pop_first() {
+                self.values.remove(&evicted);
+            }
+        }
+    }
+
+    // ids were added, updated or removed, num and allowed already reflect it.
+    // losing a member means an outsider may belong now, so only that rebuilds
+    pub fn refresh(&mut self, num: Option<&NumericalBitmap>, allowed: &Bitmap, ids: &Bitmap) {
+        let mut lost = false;
+        for id in ids.iter() {
+            if let Some(bits) = self.values.remove(&id) {
+                self.members.remove(&(bits, id));
+                lost = true;
+            }
+        }
+        if lost {
+            self.rebuild(num, allowed);
+            return;
+        }
+        if let Some(num) = num {
+            for (id, bits) in num.values_from_valid(&ids.and(allowed)) {
+                self.offer(id, bits);
+            }
+        }
+    }
+
+    // largest first
+    pub fn ids(&self) -> Vec<u32> {
+        self.members.iter().rev().map(|(_, id)| *id).collect()
+    }
+}
@@ -0,0 +1,122 @@
+use croaring::Bitmap;
+use pyo3::{prelude::*, exceptions::PyValueError, types::PyString};
+use rustc_hash::FxHashMap;
+
+use crate::index::{Indexable, core::{stored_item::StoredItem, structures::string_interner::INTERNER}, types::StrId, value::{PyIterable, PyValue, RustCastValue}};
+
+// a single attribute name or a non empty list of them
+pub fn via_attrs(via: &Bound<'_, PyAny>) -> PyResult<Vec<StrId>> {
+    if let Ok(attr) = via.downcast::<PyString>() {
+        return Ok(vec![INTERNER.intern(attr.to_str()?)]);
+    }
+    let attrs = via.extract::<Vec<String>>()?;
+    if attrs.is_empty() {
+        return Err(PyValueError::new_err("via needs at least one attribute"));
+    }
+    Ok(attrs.iter().map(|attr| INTERNER.intern(attr)).collect())
+}
+
+// ids of the Indexables item holds in any of via, directly or as elements of a list, tuple or set
+fn referenced(item: &Indexable, via: &[StrId], out: &mut Bitmap) {
+    let mut add = |value: &PyValue| {
+        if let RustCastValue::Ind(ind) = value.get_primitive() {
+            out.add(ind.owned_handle.id);
+        }
+    };
+    for attr in via {
+        item.with_attr_id(*attr, |value| match value.get_primitive() {
+            RustCastValue::Iterable(PyIterable::Dict(_)) => {},
+            RustCastValue::Iterable(_) => value.items().iter().for_each(&mut add),
+            _ => add(value),
+        });
+    }
+}
+
+// objects of scope reachable from start in at most depth hops (no limit without one), one bitmap frontier per hop.
+// the start objects are only part of the result when reached again, objects outside scope are not followed
+pub fn traverse(items: &[StoredItem], scope: &Bitmap, start: &[&Indexable], via: &[StrId], depth: Option<u32>) -> Bitmap {
+    let mut visited = Bitmap::new();
+    let mut frontier = Bitmap::new();
+    for item in start {
+        referenced(item, via, &mut frontier);
+    }
+
+    let mut reached = Bitmap::new();
+    for _ in 0..depth.unwrap_or(u32::MAX) {
+        frontier.and_inplace(scope);
+        frontier.andnot_inplace(&visited);
+        if frontier.is_empty() {
+            break;
+        }
+        visited.or_inplace(&frontier);
+        reached.or_inplace(&frontier);
+
+        let mut next = Bitmap::new();
+        for id in frontier.iter() {
+            if let Some(item) = items.get(id as usize) {
+                referenced(item.get_owned_handle(), via, &mut next);
+            }
+        }
+        frontier = next;
+    }
+    reached
+}
+
+// references between objects of scope in both directions, by id
+fn links(items: &[StoredItem], scope: &Bitmap, via: &[StrId]) -> FxHashMap<u32, Bitmap> {
+    let mut links: FxHashMap<u32, Bitmap> = FxHashMap::default();
+    for id in scope.iter() {
+        let Some(item) = items.get(id as usize) else {
+            continue;
+        };
+        let mut out = Bitmap::new();
+        referenced(item.get_owned_handle(), via, &mut out);
+        out.and_inplace(scope);
+        for other in out.iter() {
+            links.entry(id).or_default().add(other);
+            links.entry(other).or_default().add(id);
+        }
+    }
+    links
+}
+
+// hop by hop from start over links until nothing new is reached or max_depth hops were taken,
+// stopping early once the hop reaching target is done
+fn reach(links: &FxHashMap<u32, Bitmap>, start: u32, target: Option<u32>, max_depth: Option<u32>) -> Bitmap {
+    let mut visited = Bitmap::of(&[start]);
+    let mut frontier = visited.clone();
+    for _ in 0..max_depth.unwrap_or(u32::MAX) {
+        let mut next = Bitmap::new();
+        for id in frontier.iter() {
+            if let Some(adjacent) = links.get(&id) {
+                next.or_inplace(adjacent);
+            }
+        }
+        next.andnot_inplace(&visited);
+        if next.is_empty() {
+            break;
+        }
+        visited.or_inplace(&next);
+        if target.is_some_and(|target| visited.contains(target)) {
+            break;
+        }
+        frontier = next;
+    }
+    visited
+}
+
+// whether a and b are linked through at most max_depth references, followed either way
+pub fn connected(items: &[StoredItem], scope: &Bitmap, a: u32, b: u32, via: &[StrId], max_depth: Option<u32>) -> bool {
+    if !scope.contains(a) || !scope.contains(b) {
+        return false;
+    }
+    reach(&links(items, scope, via), a, Some(b), max_depth).contains(b)
+}
+
+// every object of scope linked to start through references followed either way, start included
+pub fn component_of(items: &[StoredItem], scope: &Bitmap, start: u32, via: &[StrId]) -> Bitmap {
+    if !scope.contains(start) {
+        return Bitmap::new();
+    }
+    reach(&links(items, scope, via), start, None, None)
+}
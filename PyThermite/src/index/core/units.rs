@@ -0,0 +1,115 @@
+use pyo3::{PyResult, exceptions::PyValueError};
+use smol_str::SmolStr;
+
+use crate::index::{core::query::query_ops::QueryExpr, value::{PyValue, RustCastValue}};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Unit {
+    pub name: &'static str,
+    pub dimension: &'static str,
+    // size of one of this unit in the dimension's base unit
+    pub scale: f64,
+}
+
+const fn unit(name: &'static str, dimension: &'static str, scale: f64) -> Unit {
+    Unit { name, dimension, scale }
+}
+
+const KIB: f64 = 1024.0;
+
+static UNITS: &[Unit] = &[
+    unit("ns", "time", 1e-9),
+    unit("us", "time", 1e-6),
+    unit("µs", "time", 1e-6),
+    unit("ms", "time", 1e-3),
+    unit("s", "time", 1.0),
+    unit("min", "time", 60.0),
+    unit("h", "time", 3600.0),
+    unit("d", "time", 86400.0),
+    unit("B", "data size", 1.0),
+    unit("KB", "data size", 1e3),
+    unit("MB", "data size", 1e6),
+    unit("GB", "data size", 1e9),
+    unit("TB", "data size", 1e12),
+    unit("KiB", "data size", KIB),
+    unit("MiB", "data size", KIB * KIB),
+    unit("GiB", "data size", KIB * KIB * KIB),
+    unit("TiB", "data size", KIB * KIB * KIB * KIB),
+    unit("mm", "length", 1e-3),
+    unit("cm", "length", 1e-2),
+    unit("m", "length", 1.0),
+    unit("km", "length", 1e3),
+    unit("mg", "mass", 1e-3),
+    unit("g", "mass", 1.0),
+    unit("kg", "mass", 1e3),
+];
+
+impl Unit {
+    pub fn parse(name: &str) -> PyResult<Unit> {
+        UNITS.iter().find(|unit| unit.name == name).copied().ok_or_else(|| PyValueError::new_err(format!(
+            "unknown unit '{}', expected one of {}", name, UNITS.iter().map(|unit| unit.name).collect::<Vec<_>>().join(", ")
+        )))
+    }
+}
+
+// splits "2s", "1.5 KiB" or "-3e2ms" into the number and its unit, longest unit name first so ms is not read as s
+fn parse_quantity(s: &str) -> Option<(f64, Unit)> {
+    let s = s.trim();
+    UNITS.iter()
+        .filter_map(|unit| {
+            let number = s.strip_suffix(unit.name)?.trim_end().parse::<f64>().ok()?;
+            Some((unit.name.len(), number, *unit))
+        })
+        .max_by_key(|(len, _, _)| *len)
+        .map(|(_, number, unit)| (number, unit))
+}
+
+fn convert(attr: &SmolStr, value: &mut PyValue, target: Unit) -> PyResult<()> {
+    let RustCastValue::Str(s) = value.get_primitive() else {
+        // plain numbers are already in the attribute's unit
+        return Ok(());
+    };
+    let Some((number, unit)) = parse_quantity(s) else {
+        return Err(PyValueError::new_err(format!(
+            "'{}' is in {}, '{}' is not a number followed by a unit", attr, target.name, s
+        )));
+    };
+    if unit.dimension != target.dimension {
+        return Err(PyValueError::new_err(format!(
+            "cannot compare '{}' ({}, a {}) with '{}' (a {})", attr, target.name, target.dimension, s, unit.dimension
+        )));
+    }
+    *value = PyValue::from_primitave(RustCastValue::Float(number * unit.scale / target.scale));
+    Ok(())
+}
+
+// rewrites quantities like "2s" in comparisons on attributes declared with a unit into numbers in that unit
+pub fn convert_units<F: Fn(&str) -> Option<Unit>>(expr: &mut QueryExpr, unit_of: &F) -> PyResult<()> {
+    match expr {
+        QueryExpr::Eq(attr, v)
+        | QueryExpr::Ne(attr, v)
+        | QueryExpr::Gt(attr, v)
+        | QueryExpr::Ge(attr, v)
+        | QueryExpr::Lt(attr, v)
+        | QueryExpr::Le(attr, v) => match unit_of(attr) {
+            Some(unit) => convert(attr, v, unit),
+            None => Ok(()),
+        },
+        QueryExpr::Bt(attr, lower, upper) => match unit_of(attr) {
+            Some(unit) => {
+                convert(attr, lower, unit)?;
+                convert(attr, upper, unit)
+            },
+            None => Ok(()),
+        },
+        QueryExpr::In(attr, values) | QueryExpr::Nin(attr, values) => match unit_of(attr) {
+            Some(unit) => values.iter_mut().try_for_each(|v| convert(attr, v, unit)),
+            None => Ok(()),
+        },
+        QueryExpr::Not(inner) => convert_units(inner, unit_of),
+        QueryExpr::And(exprs) | QueryExpr::Or(exprs) => {
+            exprs.iter_mut().try_for_each(|e| convert_units(e, unit_of))
+        },
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,89 @@
+use std::{fs::{File, OpenOptions}, io::Write, path::{Path, PathBuf}};
+
+use rustc_hash::FxHashMap;
+
+use crate::index::{core::{attr_decls::AttrDecls, config::IndexConfig, snapshot::{self, RECORD_ADD, RECORD_REMOVE, RECORD_SET}, stored_item::StoredItem, structures::string_interner::INTERNER}, types::StrId, value::PyValue};
+
+// a snapshot followed by a record for every add, remove and update since, so loading the file replays
+// the index to its latest state. records go straight to the file, a crash loses at most the one being written
+pub struct Wal {
+    path: PathBuf,
+    file: File,
+    // object id -> its number in the log, the snapshot's objects numbered from 0 in order
+    log_ids: FxHashMap<u32, u64>,
+    next_log_id: u64,
+    // the first failed write, records after it are not written
+    pub error: Option<std::io::Error>,
+}
+
+impl Wal {
+    // starts the log at path with a snapshot of the index, replacing the file through a rename so a
+    // crash part way leaves the previous one whole
+    pub fn create(path: &Path, config: &IndexConfig, decls: &AttrDecls, items: &[StoredItem]) -> std::io::Result<Self> {
+        let tmp = path.with_extension("tmp");
+        {
+            let mut file = File::create(&tmp)?;
+            file.write_all(&snapshot::encode(config, decls, items))?;
+            file.sync_all()?;
+        }
+        std::fs::rename(&tmp, path)?;
+        let file = OpenOptions::new().append(true).open(path)?;
+        let log_ids = items.iter().enumerate()
+            .map(|(i, item)| (item.get_owned_handle().id, i as u64))
+            .collect();
+        Ok(Self { path: path.to_path_buf(), file, log_ids, next_log_id: items.len() as u64, error: None })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn append(&mut self, record: &[u8]) {
+        if self.error.is_none() {
+            if let Err(e) = self.file.write_all(record) {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    fn header(kind: u8, log_id: u64) -> Vec<u8> {
+        let mut record = vec![kind];
+        record.extend_from_slice(&log_id.to_le_bytes());
+        record
+    }
+
+    // an object newly held, or held again, with all of its saved values
+    pub fn log_add(&mut self, item: &StoredItem) {
+        let id = item.get_owned_handle().id;
+        let log_id = match self.log_ids.get(&id) {
+            Some(log_id) => *log_id,
+            None => {
+                self.next_log_id += 1;
+                self.log_ids.insert(id, self.next_log_id - 1);
+                self.next_log_id - 1
+            },
+        };
+        let mut record = Self::header(RECORD_ADD, log_id);
+        snapshot::write_values(item, &mut record, |attr, out| snapshot::write_str(&INTERNER.resolve(attr), out));
+        self.append(&record);
+    }
+
+    pub fn log_remove(&mut self, id: u32) {
+        if let Some(log_id) = self.log_ids.remove(&id) {
+            self.append(&Self::header(RECORD_REMOVE, log_id));
+        }
+    }
+
+    // values that are not saved are logged as unset, like a snapshot leaves them out
+    pub fn log_set(&mut self, id: u32, attr: StrId, value: &PyValue) {
+        let Some(log_id) = self.log_ids.get(&id).copied() else {
+            return;
+        };
+        let mut record = Self::header(RECORD_SET, log_id);
+        snapshot::write_str(&INTERNER.resolve(attr), &mut record);
+        if !snapshot::write_value(value.get_primitive(), &mut record) {
+            snapshot::write_unset(&mut record);
+        }
+        self.append(&record);
+    }
+}
@@ -109,6 +109,19 @@ impl<K, V> Iterator for Drain<K, V> {
     }
 }
 
+impl<K, V> Drop for Drain<K, V> {
+    fn drop(&mut self) {
+        // drop what was not read, then leave nothing for SmallKVMap to drop again
+        for i in self.idx..self.small.len {
+            unsafe {
+                self.small.keys[i].assume_init_drop();
+                self.small.values[i].assume_init_drop();
+            }
+        }
+        self.small.len = 0;
+    }
+}
+
 #[derive(Debug)]
 pub enum HybridHashmap<K, V> {
     Small(SmallKVMap<K, V>),
@@ -123,6 +136,15 @@ where
         HybridHashmap::Small(SmallKVMap::new())
     }
 
+    // skips the small to map migration when the final size is known up front
+    pub fn with_capacity(capacity: usize) -> Self {
+        if capacity <= SMALL_SIZE {
+            Self::new()
+        } else {
+            HybridHashmap::Map(FxHashMap::with_capacity_and_hasher(capacity, Default::default()))
+        }
+    }
+
     #[inline(always)]
     pub fn insert(&mut self, key: K, value: V) {
         match self {
@@ -144,7 +166,8 @@ where
                             map.insert(k, v);
                         }
                         map.insert(key, value);
-                        *self = HybridHashmap::Map(map);
+                        // the small map was moved out above, so it must not be dropped here
+                        ptr::write(self, HybridHashmap::Map(map));
                     }
                 }
             }
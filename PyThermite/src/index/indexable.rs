@@ -1,37 +1,106 @@
-use pyo3::exceptions::PyAttributeError;
-use pyo3::types::PyDictMethods;
+use pyo3::exceptions::{PyAttributeError, PyValueError};
+use pyo3::types::{PyDictMethods, PyListMethods};
 use pyo3::types::PyStringMethods;
 use pyo3::{ffi, IntoPyObjectExt, PyErr, PyRef};
+use pyo3::sync::GILProtected;
 
+use rustc_hash::FxHashMap;
 use smallvec::SmallVec;
 
+use std::cell::RefCell;
 use std::fmt;
 use std::sync::MutexGuard;
 use std::sync::{Arc, Mutex, Weak};
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::hash::{Hash, Hasher};
-use pyo3::{pyclass, pymethods, types::{PyAnyMethods, PyDict, PyList, PyString}, Bound, Py, PyAny, PyObject, PyResult, Python};
+use pyo3::{pyclass, pymethods, types::{PyAnyMethods, PyDict, PyList, PyString, PySuper, PyTuple, PyTupleMethods, PyType}, Bound, Py, PyAny, PyObject, PyResult, Python};
 
 use crate::index::core::id_alloc::allocate_id;
 use crate::index::core::id_alloc::free_id;
 use crate::index::core::structures::string_interner::INTERNER;
 use crate::index::core::structures::string_interner::StrInternerView;
+use crate::index::core::structures::string_interner::intern_for_class;
 use crate::index::types::DEFAULT_INDEX_ARC;
 use crate::index::types::StrId;
-use crate::index::value::PyValue;
+use crate::index::value::{PyValue, RustCastValue};
 use crate::index::HybridHashmap;
 use crate::index::core::index::IndexAPI;
+use crate::index::core::mutation_gate::Mutating;
+use crate::index::core::class_fields::{class_fields, class_value, declare_field, register_class_fields, ClassFields};
 
 
 struct IndexMeta{
     index: Weak<IndexAPI>,
 }
 
-#[pyclass(subclass, freelist = 512)]
+const ATTR_CACHE_SLOTS: usize = 8;
+
+// recent attribute reads, open addressed on the name's PyString pointer.
+// names are held so their pointers cannot be reused while cached, cleared whenever a value is set
+#[derive(Default)]
+struct AttrCache {
+    slots: [Option<(Py<PyString>, PyObject)>; ATTR_CACHE_SLOTS],
+}
+
+impl AttrCache {
+    #[inline(always)]
+    fn start(ptr: usize) -> usize {
+        (ptr >> 4) % ATTR_CACHE_SLOTS
+    }
+
+    #[inline(always)]
+    fn get(&self, py: Python, name: &Bound<'_, PyString>) -> Option<PyObject> {
+        let ptr = name.as_ptr() as usize;
+        let start = Self::start(ptr);
+        for i in 0..ATTR_CACHE_SLOTS {
+            match &self.slots[(start + i) % ATTR_CACHE_SLOTS] {
+                Some((key, value)) if key.as_ptr() as usize == ptr => return Some(value.clone_ref(py)),
+                Some(_) => continue,
+                None => return None,
+            }
+        }
+        None
+    }
+
+    fn insert(&mut self, name: &Bound<'_, PyString>, value: PyObject) {
+        let ptr = name.as_ptr() as usize;
+        let start = Self::start(ptr);
+        // a full table evicts the home slot
+        let mut target = start;
+        for i in 0..ATTR_CACHE_SLOTS {
+            let idx = (start + i) % ATTR_CACHE_SLOTS;
+            if self.slots[idx].as_ref().is_none_or(|(key, _)| key.as_ptr() as usize == ptr) {
+                target = idx;
+                break;
+            }
+        }
+        self.slots[target] = Some((name.clone().unbind(), value));
+    }
+}
+
+/// Base class for objects that can be indexed.
+/// All attributes will be indexed unless the attribute name is prefixed with an underscore
+/// Nested Indexable objects are supported and fully queryable via dot notation.
+/// list/tuple/set values are indexed by element and compared by content, their contents are
+/// frozen on assignment so in-place mutation is not seen until the attribute is reassigned
+/// or reindex_attr is called
+/// annotated fields of subclasses are interned once when the class is defined,
+/// __thermite_fields__ maps their names to the ids used by the index
+/// a subclass can limit the attributes indexes maintain with class Car(Indexable, index_only=["vin", "price"]),
+/// index_exclude=[...] or an __index_fields__ list, other attributes are stored and read back but never indexed,
+/// so assigning them skips index maintenance. subclasses inherit the setting unless they declare their own
+#[pyclass(subclass, weakref, freelist = 512)]
 pub struct Indexable{
     meta: Arc<Mutex<SmallVec<[IndexMeta; 4]>>>,
     pub py_values: Arc<Mutex<HybridHashmap<StrId, PyValue>>>,
     pub id: u32,
-    pub recycle_id_on_drop: bool
+    pub recycle_id_on_drop: bool,
+    // attributes of the object's class kept out of every index, None indexes all of them
+    class_fields: Option<Arc<ClassFields>>,
+    // allocated on the first attribute read
+    attr_cache: GILProtected<RefCell<Option<Box<AttrCache>>>>,
+    // stores in progress, shared with every handle on py_values
+    pending_writes: Arc<AtomicU32>,
 }
 
 
@@ -39,68 +108,213 @@ pub struct Indexable{
 impl Indexable{
 
     #[new]
+    #[classmethod]
     #[pyo3(signature = (*_args, **kwargs))]
     fn new(
-        _args: &Bound<'_, PyAny>, kwargs: Option<&Bound<'_, PyDict>>
-    ) -> Self {
+        cls: &Bound<'_, PyType>, _args: &Bound<'_, PyAny>, kwargs: Option<&Bound<'_, PyDict>>
+    ) -> PyResult<Self> {
 
         let mut py_values: HybridHashmap<StrId, PyValue>;
-        let mut interner = StrInternerView::new(&INTERNER);
+        let type_ptr = cls.as_ptr() as usize;
 
         if let Some(dict) = kwargs {
             py_values = HybridHashmap::new();
             for (key, value) in dict.iter() {
                 if let Ok(key_str) = key.extract::<&str>() {
-                    let key_id: StrId = interner.intern(key_str);
-                    py_values.insert(key_id, PyValue::new(value));
+                    let key_id: StrId = intern_for_class(type_ptr, key_str);
+                    py_values.insert(key_id, class_value(type_ptr, key_id, value)?);
                 }
             }
         } else {
             py_values = HybridHashmap::new();
         }
 
-        Self {
+        Ok(Self {
             meta: Arc::new(Mutex::new(SmallVec::new())),
             id: allocate_id(),
             py_values: Arc::new(Mutex::new(py_values)),
-            recycle_id_on_drop: true
+            recycle_id_on_drop: true,
+            class_fields: class_fields(type_ptr),
+            attr_cache: GILProtected::new(RefCell::new(None)),
+            pending_writes: Arc::new(AtomicU32::new(0)),
+        })
+    }
+
+    // interns the annotated fields of a subclass (and its bases) once at class definition,
+    // the ids are kept on the class as __thermite_fields__ and seed the per class cache
+    #[classmethod]
+    #[pyo3(signature = (**kwargs))]
+    fn __init_subclass__(cls: &Bound<'_, PyType>, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<()> {
+        let py = cls.py();
+        let fields = PyDict::new(py);
+        // base classes first, so fields keep their declaration order like dataclasses
+        let mro: Vec<Bound<'_, PyAny>> = cls.getattr("__mro__")?.try_iter()?.collect::<PyResult<_>>()?;
+        for base in mro.into_iter().rev() {
+            let Ok(annotations) = base.getattr("__annotations__") else {
+                continue;
+            };
+            let Ok(annotations) = annotations.downcast_into::<PyDict>() else {
+                continue;
+            };
+            for name in annotations.keys() {
+                let Ok(name_str) = name.extract::<&str>() else {
+                    continue;
+                };
+                if !name_str.starts_with('_') && !fields.contains(&name)? {
+                    fields.set_item(&name, intern_for_class(cls.as_ptr() as usize, name_str))?;
+                }
+            }
         }
+        cls.setattr("__thermite_fields__", fields)?;
+        register_class_fields(cls, kwargs)?;
+
+        let base = PySuper::new(&py.get_type::<Indexable>(), cls)?;
+        base.call_method("__init_subclass__", (), kwargs)?;
+        Ok(())
+    }
+
+    /// declares the type of attribute name on objects of this class (and subclasses defined afterwards),
+    /// one of int, float, str, bool or bytes. values are then read as that type without checking for any other,
+    /// ints given to a float field are stored as floats, and assigning a value of another type raises TypeError
+    /// naming the attribute. nullable also allows None. objects built before the declaration are not checked
+    #[classmethod]
+    #[pyo3(signature = (name, r#type, nullable=false))]
+    fn field(cls: &Bound<'_, PyType>, name: &str, r#type: &Bound<'_, PyType>, nullable: bool) -> PyResult<()> {
+        declare_field(cls, name, r#type, nullable)
     }
 
-    fn __setattr__<'py>(&self, py: Python, name: &str, value: Bound<'py, PyAny>) -> PyResult<()> {
+    /// builds an instance from values given in __thermite_fields__ order (base class fields first)
+    /// __init__ is not run, raises ValueError if the number of values does not match
+    #[classmethod]
+    fn from_values<'py>(cls: &Bound<'py, PyType>, values: &Bound<'py, PyTuple>) -> PyResult<Bound<'py, PyAny>> {
+        let fields = match cls.getattr("__thermite_fields__") {
+            Ok(fields) => fields.downcast_into::<PyDict>()?,
+            Err(_) => PyDict::new(cls.py()),
+        };
+        if fields.len() != values.len() {
+            return Err(PyValueError::new_err(format!(
+                "expected {} values for fields {}, got {}",
+                fields.len(), fields.keys(), values.len()
+            )));
+        }
 
-        let val: PyValue = PyValue::new(value);
+        let type_ptr = cls.as_ptr() as usize;
+        let mut py_values = HybridHashmap::with_capacity(values.len());
+        for ((_, id), value) in fields.iter().zip(values.iter()) {
+            let id = id.extract::<StrId>()?;
+            py_values.insert(id, class_value(type_ptr, id, value)?);
+        }
+        Self::new_bare(cls, py_values)
+    }
 
-        let mut interner = StrInternerView::new(&INTERNER);
-        for ind in self.meta.lock().unwrap().iter() {
-            py.allow_threads(||{
-                if let Some(full_index) = ind.index.upgrade() {
-                    let name_id = interner.intern(name);
-                    if let Some(old_val) = self.get_py_values().get(&name_id){
-                        full_index.update_index(ind.index.clone(), name_id, Some(old_val), &val, self.id);
-                    } else {
-                        full_index.update_index(ind.index.clone(), name_id, None, &val, self.id);
+    /// builds one instance per dict of attribute values in a single call, without running __init__
+    /// much faster than calling the class in a loop when loading many objects
+    /// a value that can't be read (e.g. an unhashable object or a str with lone surrogates) raises its error and nothing
+    /// is returned, unless errors is a list: the row is then skipped and (row position, attr, exception) appended to it
+    // builds one instance per dict, interning each distinct key object once for the whole batch
+    #[classmethod]
+    #[pyo3(signature = (rows, errors=None))]
+    pub fn construct_many<'py>(
+        cls: &Bound<'py, PyType>,
+        rows: Vec<Bound<'py, PyDict>>,
+        errors: Option<&Bound<'py, PyList>>,
+    ) -> PyResult<Vec<Bound<'py, PyAny>>> {
+        let py = cls.py();
+        let type_ptr = cls.as_ptr() as usize;
+        // keys stay alive in their dicts for the whole call, so their addresses are stable
+        let mut key_ids: FxHashMap<usize, StrId> = FxHashMap::default();
+
+        let mut objs = Vec::with_capacity(rows.len());
+        'rows: for (position, row) in rows.iter().enumerate() {
+            let mut py_values = HybridHashmap::with_capacity(row.len());
+            for (key, value) in row.iter() {
+                let key_id = match key_ids.get(&(key.as_ptr() as usize)) {
+                    Some(id) => *id,
+                    None => {
+                        let id = intern_for_class(type_ptr, key.extract::<&str>()?);
+                        key_ids.insert(key.as_ptr() as usize, id);
+                        id
                     }
-                }
-            });
+                };
+                match (class_value(type_ptr, key_id, value), errors) {
+                    (Ok(value), _) => py_values.insert(key_id, value),
+                    (Err(err), Some(errors)) => {
+                        errors.append((position, key, err.into_value(py)))?;
+                        continue 'rows;
+                    },
+                    (Err(err), None) => return Err(err),
+                };
+            }
+            objs.push(Self::new_bare(cls, py_values)?);
         }
+        Ok(objs)
+    }
 
-        // update value
-        let str_id: StrId = interner.intern(name);
-        self.py_values.lock().unwrap().insert(str_id, val);
-        Ok(())
+    fn __setattr__<'py>(slf: &Bound<'py, Self>, name: &str, value: Bound<'py, PyAny>) -> PyResult<()> {
+        let type_ptr = slf.get_type_ptr() as usize;
+        let name_id = intern_for_class(type_ptr, name);
+        let value = class_value(type_ptr, name_id, value)?;
+        let obj = slf.borrow();
+        let _mutating = obj.check_strict(name_id, &value)?;
+        obj.set_value(slf.py(), name_id, value)
+    }
+
+    /// sets every attribute given, like assigning them one at a time but reindexing the object once per index
+    /// every value is read and checked (declared field types, strict indexes) before any is set, alerts run once after
+    #[pyo3(signature = (**kwargs))]
+    fn update<'py>(slf: &Bound<'py, Self>, kwargs: Option<&Bound<'py, PyDict>>) -> PyResult<()> {
+        let Some(kwargs) = kwargs else {
+            return Ok(());
+        };
+        let values = Self::class_values(slf.get_type_ptr() as usize, kwargs)?;
+        let obj = slf.borrow();
+        let _mutating = values.iter().map(|(name_id, value)| obj.check_strict(*name_id, value)).collect::<PyResult<Vec<_>>>()?;
+        obj.store_values(slf.py(), values);
+        obj.fire_alerts(slf.py())
+    }
+
+    /// re-reads an attribute whose value was mutated in place (e.g. a list after append)
+    /// and updates every index holding this object, only elements that changed are reindexed
+    /// returns whether the value changed, raises AttributeError if the attribute is not set
+    pub fn reindex_attr(&self, py: Python, name: &str) -> PyResult<bool> {
+        let name_id = INTERNER.intern(name);
+        let Some(obj) = self.with_attr_id(name_id, |old| old.get_obj(py)) else {
+            return Err(PyAttributeError::new_err(format!("no attribute '{}'", name)));
+        };
+        let val = PyValue::try_new(obj.into_bound(py))?;
+        if self.with_attr_id(name_id, |old| *old == val).unwrap_or(false) {
+            return Ok(false);
+        }
+        let _mutating = self.check_strict(name_id, &val)?;
+        self.set_value(py, name_id, val)?;
+        Ok(true)
     }
 
     fn __getattribute__(self_: PyRef<'_, Self>, py: Python, name: Bound<'_, PyString>) -> PyResult<PyObject> {
+        if let Some(cache) = self_.attr_cache.get(py).borrow().as_ref() {
+            if let Some(value) = cache.get(py, &name) {
+                return Ok(value);
+            }
+        }
 
         let name_str = match name.to_str() {
             Ok(s) => s,
             Err(_) => return Err(PyAttributeError::new_err("Invalid attribute name")),
         };
+        let name_id = intern_for_class(unsafe { ffi::Py_TYPE(self_.as_ptr()) } as usize, name_str);
         let py_values = self_.get_py_values();
 
-        if let Some(value) = py_values.get(&INTERNER.intern(name_str)) {
-            Ok(value.get_obj(py))
+        if let Some(value) = py_values.get(&name_id) {
+            let obj = value.get_obj(py);
+            // a value mid write is returned but not cached, the write clears the cache before it starts
+            let cacheable = self_.pending_writes.load(Ordering::Acquire) == 0;
+            drop(py_values);
+            if cacheable {
+                self_.attr_cache.get(py).borrow_mut()
+                    .get_or_insert_with(Default::default)
+                    .insert(&name, obj.clone_ref(py));
+            }
+            Ok(obj)
         } else {
             drop(py_values);
             let res = unsafe { ffi::PyObject_GenericGetAttr(self_.into_ptr(), name.into_ptr()) };
@@ -142,13 +356,53 @@ impl Indexable{
 
 impl Indexable {
 
+    // one object per row holding its value of each column, columns[i] the values of attrs[i]
+    pub fn construct_values<'py>(
+        cls: &Bound<'py, PyType>,
+        attrs: &[StrId],
+        columns: &[Vec<Option<PyValue>>],
+        rows: usize,
+    ) -> PyResult<Vec<Bound<'py, PyAny>>> {
+        (0..rows).map(|row| {
+            let mut py_values = HybridHashmap::with_capacity(attrs.len());
+            for (attr, column) in attrs.iter().zip(columns) {
+                if let Some(Some(value)) = column.get(row) {
+                    py_values.insert(*attr, value.clone());
+                }
+            }
+            Self::new_bare(cls, py_values)
+        }).collect()
+    }
+
+    // a new object of the same class holding the same values, in no index
+    pub(crate) fn copy<'py>(obj: &Bound<'py, Indexable>) -> PyResult<Bound<'py, Indexable>> {
+        let source = obj.borrow();
+        let source = source.get_py_values();
+        let mut values = HybridHashmap::with_capacity(source.len());
+        for (key, value) in source.iter() {
+            values.insert(*key, value.clone());
+        }
+        drop(source);
+        Ok(Self::new_bare(&obj.get_type(), values)?.downcast_into::<Indexable>()?)
+    }
+
+    // allocates cls through __new__ alone and fills in the values directly
+    fn new_bare<'py>(cls: &Bound<'py, PyType>, py_values: HybridHashmap<StrId, PyValue>) -> PyResult<Bound<'py, PyAny>> {
+        let obj = cls.call_method1("__new__", (cls,))?;
+        *obj.downcast::<Indexable>()?.borrow().get_py_values() = py_values;
+        Ok(obj)
+    }
+
     pub fn from_py_ref(reference: &PyRef<Indexable>, _py: Python) -> Self {
         // `reference` is a GIL-bound borrow; we clone the Arc pointers for Rust ownership
         Self {
             meta: reference.meta.clone(),
             py_values: reference.py_values.clone(),
             id: reference.id,
-            recycle_id_on_drop: false // ID authority is the Python handle
+            recycle_id_on_drop: false, // ID authority is the Python handle
+            class_fields: reference.class_fields.clone(),
+            attr_cache: GILProtected::new(RefCell::new(None)),
+            pending_writes: reference.pending_writes.clone(),
         }
     }
 
@@ -183,6 +437,114 @@ impl Indexable {
         Self::trim_indexes(&mut meta_lock, index);
     }
 
+    // frozen or strict indexes holding this object may refuse the value, checked before any index is touched
+    // the value is stored while the guards returned are held, so none of those indexes is frozen meanwhile
+    pub(crate) fn check_strict(&self, name_id: StrId, val: &PyValue) -> PyResult<Vec<Mutating>> {
+        if !self.is_indexed(name_id) {
+            return Ok(Vec::new());
+        }
+        let mut mutating = Vec::new();
+        for ind in self.meta.lock().unwrap().iter() {
+            if let Some(index) = ind.index.upgrade() {
+                mutating.push(index.check_mutable()?);
+                index.check_strict_value(name_id, val)?;
+            }
+        }
+        Ok(mutating)
+    }
+
+    fn set_value(&self, py: Python, name_id: StrId, val: PyValue) -> PyResult<()> {
+        self.store_value(py, name_id, val);
+        self.fire_alerts(py)
+    }
+
+    // reindexes and stores the value without running alerts, for batches that run them once at the end.
+    // py_values is held from the first posting update until the value is in place, so a reader
+    // that finds the object by its new value reads the new value back
+    pub(crate) fn store_value(&self, py: Python, name_id: StrId, val: PyValue) {
+        let indexed = self.is_indexed(name_id);
+        self.begin_write(py);
+        py.allow_threads(||{
+            let indexes = self.live_indexes();
+            let mut py_values = self.get_py_values();
+            for (weak, full_index) in indexes.iter() {
+                if !indexed {
+                    // still logged, restores and standbys must see the new value
+                    full_index.log_set(self.id, name_id, &val);
+                    continue;
+                }
+                full_index.update_index(weak.clone(), name_id, py_values.get(&name_id), &val, self.id);
+            }
+            py_values.insert(name_id, val);
+            self.end_write();
+        });
+    }
+
+    // the values of kwargs as stored on an object of the class at type_ptr
+    pub(crate) fn class_values(type_ptr: usize, kwargs: &Bound<'_, PyDict>) -> PyResult<Vec<(StrId, PyValue)>> {
+        kwargs.iter().map(|(name, value)| {
+            let name_id = intern_for_class(type_ptr, name.extract::<&str>()?);
+            Ok((name_id, class_value(type_ptr, name_id, value)?))
+        }).collect()
+    }
+
+    // store_value for several attributes, each index updated in one pass
+    pub(crate) fn store_values(&self, py: Python, values: Vec<(StrId, PyValue)>) {
+        self.begin_write(py);
+        py.allow_threads(||{
+            let indexes = self.live_indexes();
+            let mut py_values = self.get_py_values();
+            for (weak, full_index) in indexes.iter() {
+                let mut changes = Vec::with_capacity(values.len());
+                for (name_id, val) in values.iter() {
+                    if self.is_indexed(*name_id) {
+                        changes.push((*name_id, py_values.get(name_id), val));
+                    } else {
+                        full_index.log_set(self.id, *name_id, val);
+                    }
+                }
+                full_index.update_values(weak.clone(), self.id, &changes);
+            }
+            for (name_id, val) in values {
+                py_values.insert(name_id, val);
+            }
+            self.end_write();
+        });
+    }
+
+    // the cache is dropped under the GIL and stays unfilled until end_write, reads in between
+    // go to py_values and wait there for the value
+    fn begin_write(&self, py: Python) {
+        self.pending_writes.fetch_add(1, Ordering::AcqRel);
+        self.attr_cache.get(py).borrow_mut().take();
+    }
+
+    // called with py_values held, once every value is stored
+    fn end_write(&self) {
+        self.pending_writes.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    fn live_indexes(&self) -> SmallVec<[(Weak<IndexAPI>, Arc<IndexAPI>); 4]> {
+        self.meta.lock().unwrap().iter()
+            .filter_map(|ind| ind.index.upgrade().map(|index| (ind.index.clone(), index)))
+            .collect()
+    }
+
+    // alerts read the stored value, so only once it is in place
+    pub(crate) fn fire_alerts(&self, py: Python) -> PyResult<()> {
+        let indexes: Vec<Arc<IndexAPI>> = self.meta.lock().unwrap().iter().filter_map(|ind| ind.index.upgrade()).collect();
+        for index in indexes {
+            index.fire_alerts(py)?;
+        }
+        Ok(())
+    }
+
+    // whether indexes maintain attr_id for this object's class
+    #[inline]
+    pub fn is_indexed(&self, attr_id: StrId) -> bool {
+        self.class_fields.as_ref().is_none_or(|fields| fields.is_indexed(attr_id))
+    }
+
     pub fn get_py_values(&self) -> MutexGuard<'_, HybridHashmap<StrId, PyValue>>{
         // self.py_values.try_lock().expect("cannot read from indexable")
         self.py_values.lock().expect("cannot read from indexable")
@@ -195,6 +557,23 @@ impl Indexable {
         let guard = self.get_py_values();
         guard.get(&str_id).map(f)
     }
+
+    // the value at the end of a path of attributes, stepping through nested indexables
+    pub fn with_path<F, R>(&self, path: &[StrId], f: F) -> Option<R>
+    where
+        F: FnOnce(&PyValue) -> R
+    {
+        let (last, heads) = path.split_last()?;
+        let mut nested: Option<Arc<Indexable>> = None;
+        for head in heads {
+            let current = nested.as_deref().unwrap_or(self);
+            nested = Some(current.with_attr_id(*head, |value| match value.get_primitive() {
+                RustCastValue::Ind(ind) => Some(ind.owned_handle.clone()),
+                _ => None,
+            }).flatten()?);
+        }
+        nested.as_deref().unwrap_or(self).with_attr_id(*last, f)
+    }
 }
 
 impl Drop for Indexable {
@@ -232,7 +611,10 @@ impl Default for Indexable {
             meta: Arc::new(Mutex::new(SmallVec::new())),
             id: allocate_id(),
             py_values: Arc::new(Mutex::new(HybridHashmap::new())),
-            recycle_id_on_drop: true
+            recycle_id_on_drop: true,
+            class_fields: None,
+            attr_cache: GILProtected::new(RefCell::new(None)),
+            pending_writes: Arc::new(AtomicU32::new(0)),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,118 @@
+use pyo3::{Bound, PyAny, PyResult, pyclass, pyfunction, pymethods, pyclass::CompareOp};
+use smol_str::SmolStr;
+
+use crate::index::{core::query::{pattern::Pattern, query_ops::QueryExpr}, interfaces::PyQueryExpr, value::PyValue};
+
+/// attribute proxy returned by attr(name) that builds query expressions with python operators
+/// e.g. (attr("price") > 5) & (attr("owner.name") == "bob")
+#[pyclass(frozen, name = "Attr")]
+pub struct PyAttr {
+    name: SmolStr,
+}
+
+impl PyAttr {
+    fn expr(inner: QueryExpr) -> PyQueryExpr {
+        PyQueryExpr { inner }
+    }
+}
+
+#[pymethods]
+impl PyAttr {
+    fn __richcmp__<'py>(&self, other: Bound<'py, PyAny>, op: CompareOp) -> PyQueryExpr {
+        let name = self.name.clone();
+        let value = PyValue::new(other);
+        Self::expr(match op {
+            CompareOp::Eq => QueryExpr::Eq(name, value),
+            CompareOp::Ne => QueryExpr::Ne(name, value),
+            CompareOp::Gt => QueryExpr::Gt(name, value),
+            CompareOp::Ge => QueryExpr::Ge(name, value),
+            CompareOp::Lt => QueryExpr::Lt(name, value),
+            CompareOp::Le => QueryExpr::Le(name, value),
+        })
+    }
+
+    fn in_<'py>(&self, values: Vec<Bound<'py, PyAny>>) -> PyQueryExpr {
+        let values = values.into_iter().map(PyValue::new).collect();
+        Self::expr(QueryExpr::In(self.name.clone(), values))
+    }
+
+    fn nin<'py>(&self, values: Vec<Bound<'py, PyAny>>) -> PyQueryExpr {
+        let values = values.into_iter().map(PyValue::new).collect();
+        Self::expr(QueryExpr::Nin(self.name.clone(), values))
+    }
+
+    fn is_null(&self) -> PyQueryExpr {
+        PyQueryExpr::is_null(self.name.to_string())
+    }
+
+    fn is_not_null(&self) -> PyQueryExpr {
+        PyQueryExpr::is_not_null(self.name.to_string())
+    }
+
+    fn exists(&self) -> PyQueryExpr {
+        Self::expr(QueryExpr::Exists(self.name.clone()))
+    }
+
+    fn missing(&self) -> PyQueryExpr {
+        Self::expr(QueryExpr::Missing(self.name.clone()))
+    }
+
+    fn has_flag(&self, flag: i64) -> PyResult<PyQueryExpr> {
+        PyQueryExpr::has_flag(self.name.to_string(), flag)
+    }
+
+    fn flags_all(&self, mask: i64) -> PyQueryExpr {
+        PyQueryExpr::flags_all(self.name.to_string(), mask)
+    }
+
+    fn flags_any(&self, mask: i64) -> PyQueryExpr {
+        PyQueryExpr::flags_any(self.name.to_string(), mask)
+    }
+
+    fn mod_eq(&self, divisor: i64, remainder: i64) -> PyResult<PyQueryExpr> {
+        PyQueryExpr::mod_eq(self.name.to_string(), divisor, remainder)
+    }
+
+    fn custom<'py>(&self, name: &str, value: Bound<'py, PyAny>) -> PyResult<PyQueryExpr> {
+        PyQueryExpr::custom(name, self.name.to_string(), value)
+    }
+
+    fn bt<'py>(&self, lower: Bound<'py, PyAny>, upper: Bound<'py, PyAny>) -> PyQueryExpr {
+        Self::expr(QueryExpr::Bt(self.name.clone(), PyValue::new(lower), PyValue::new(upper)))
+    }
+
+    fn starts_with<'py>(&self, value: Bound<'py, PyAny>) -> PyQueryExpr {
+        Self::expr(QueryExpr::StartsWith(self.name.clone(), PyValue::new(value)))
+    }
+
+    fn ends_with<'py>(&self, value: Bound<'py, PyAny>) -> PyQueryExpr {
+        Self::expr(QueryExpr::EndsWith(self.name.clone(), PyValue::new(value)))
+    }
+
+    fn contains<'py>(&self, value: Bound<'py, PyAny>) -> PyQueryExpr {
+        Self::expr(QueryExpr::Contains(self.name.clone(), PyValue::new(value)))
+    }
+
+    #[pyo3(signature = (pattern, regex=false))]
+    fn matches(&self, pattern: &str, regex: bool) -> PyResult<PyQueryExpr> {
+        Ok(Self::expr(QueryExpr::Matches(self.name.clone(), Pattern::new(pattern, regex)?)))
+    }
+
+    fn overlaps<'py>(&self, lower: Bound<'py, PyAny>, upper: Bound<'py, PyAny>) -> PyQueryExpr {
+        Self::expr(QueryExpr::Overlaps(self.name.clone(), PyValue::new(lower), PyValue::new(upper)))
+    }
+
+    fn contains_point<'py>(&self, point: Bound<'py, PyAny>) -> PyQueryExpr {
+        Self::expr(QueryExpr::ContainsPoint(self.name.clone(), PyValue::new(point)))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Attr: {}>", self.name)
+    }
+}
+
+/// returns an attribute proxy for building queries with operators
+#[pyfunction]
+pub fn attr(name: &str) -> PyAttr {
+    PyAttr { name: SmolStr::new(name) }
+}
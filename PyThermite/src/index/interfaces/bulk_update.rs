@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::index::core::index::IndexAPI;
+
+/// context manager returned by Index.bulk_update, holding back index maintenance for assignments
+/// to the index's objects until it exits
+#[pyclass]
+pub struct BulkUpdate {
+    index: Arc<IndexAPI>,
+    open: bool,
+}
+
+impl BulkUpdate {
+    pub fn new(index: Arc<IndexAPI>) -> Self {
+        Self { index, open: false }
+    }
+}
+
+#[pymethods]
+impl BulkUpdate {
+    /// raises ValueError when this block is already open
+    fn __enter__(mut slf: PyRefMut<'_, Self>) -> PyResult<PyRefMut<'_, Self>> {
+        if slf.open {
+            return Err(PyValueError::new_err("bulk update is already open"));
+        }
+        slf.index.check_mutable()?;
+        slf.index.open_deferred();
+        slf.open = true;
+        Ok(slf)
+    }
+
+    // the maps are brought up to date whether or not the block raised, the assignments made in it stay
+    fn __exit__(&mut self, py: Python, _exc_type: Option<PyObject>, _exc: Option<PyObject>, _tb: Option<PyObject>) -> PyResult<bool> {
+        if !std::mem::take(&mut self.open) {
+            return Ok(false);
+        }
+        let weak_index = Arc::downgrade(&self.index);
+        py.allow_threads(|| self.index.close_deferred(weak_index));
+        self.index.fire_alerts(py)?;
+        Ok(false)
+    }
+}
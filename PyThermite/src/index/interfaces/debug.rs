@@ -0,0 +1,59 @@
+use pyo3::{Bound, PyAny, PyResult, Python, pyfunction, types::{PyAnyMethods, PyDict, PyDictMethods, PyList, PyListMethods}};
+
+use crate::index::{Indexable, core::handles::handle_counts, interfaces::{PyQueryExpr, index::Index}};
+
+// follows a dotted path from obj, fanning out over lists and tuples
+fn resolve<'py>(py: Python<'py>, value: Bound<'py, PyAny>, path: &str) -> Option<Bound<'py, PyAny>> {
+    let (head, rest) = match path.split_once('.') {
+        Some((head, rest)) => (head, Some(rest)),
+        None => (path, None),
+    };
+    let next = value.getattr(head).ok()?;
+    let Some(rest) = rest else {
+        return Some(next);
+    };
+    if next.is_instance_of::<PyList>() || next.is_instance_of::<pyo3::types::PyTuple>() {
+        let out = PyList::empty(py);
+        for element in next.try_iter().ok()?.flatten() {
+            if let Some(v) = resolve(py, element, rest) {
+                out.append(v).ok()?;
+            }
+        }
+        return Some(out.into_any());
+    }
+    resolve(py, next, rest)
+}
+
+/// lists the clauses of query that obj fails, empty when obj matches
+/// each entry has clause, attr, present (whether obj has the attribute) and actual (its value)
+#[pyfunction]
+pub fn why_not<'py>(
+    py: Python<'py>,
+    index: &Index,
+    mut query: PyQueryExpr,
+    obj: Bound<'py, Indexable>,
+) -> PyResult<Vec<Bound<'py, PyDict>>> {
+    index.inner.apply_declarations(&mut query.inner)?;
+    query.inner.check_types()?;
+    let failures = index.inner.why_not(obj.borrow().id, &query.inner)?;
+    let obj = obj.into_any();
+
+    failures.iter().map(|clause| {
+        let entry = PyDict::new(py);
+        entry.set_item("clause", clause.to_string())?;
+        let attr = clause.attr().map(|attr| attr.as_str());
+        entry.set_item("attr", attr)?;
+        let actual = attr.and_then(|attr| resolve(py, obj.clone(), attr));
+        entry.set_item("present", actual.is_some())?;
+        entry.set_item("actual", actual)?;
+        Ok(entry)
+    }).collect()
+}
+
+/// python handles kept alive by index, for tracking down objects that are not freed
+/// held and stale (held but no longer allowed) count slots, handle_refs the strong references across all indexes sharing them
+/// classes breaks held down by type name, nested_indexes and nested_held cover the attribute indexes below
+#[pyfunction]
+pub fn handles<'py>(py: Python<'py>, index: &Index) -> PyResult<Bound<'py, PyDict>> {
+    handle_counts(py, &index.inner).to_dict(py)
+}
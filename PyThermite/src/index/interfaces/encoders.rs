@@ -0,0 +1,47 @@
+use std::sync::Arc;
+
+use pyo3::{exceptions::PyTypeError, prelude::*, pyfunction, types::PyType};
+
+use crate::index::core::encoders::{PyCallableEncoder, register_field_extractor, register_key_encoder, unregister_field_extractor, unregister_key_encoder};
+
+/// indexes values of exactly type cls under encoder(value), which returns an int, a float
+/// or a tuple of up to 4 non negative ints (each below 2**(63 // len)) compared in tuple order
+/// eq, gt, lt, bt etc. then take instances of cls, ordered against other instances of cls only,
+/// so an encoded value never matches or compares with a plain number
+/// an encoder raising fails the object's construction or the assignment with its exception,
+/// query values it raises on are matched by identity only
+/// applies to values assigned afterwards, so register before building indexes
+#[pyfunction]
+pub fn register_encoder(cls: &Bound<'_, PyType>, encoder: Bound<'_, PyAny>) -> PyResult<()> {
+    if !encoder.is_callable() {
+        return Err(PyTypeError::new_err("encoder must be callable"));
+    }
+    register_key_encoder(cls, Arc::new(PyCallableEncoder::new(encoder.unbind())));
+    Ok(())
+}
+
+/// removes the encoder for cls, returns whether one was registered
+#[pyfunction]
+pub fn unregister_encoder(cls: &Bound<'_, PyType>) -> bool {
+    unregister_key_encoder(cls)
+}
+
+/// indexes values of exactly type cls that have no encoder through extractor(value), which returns a dict
+/// of sub fields indexed like a nested object, so "attr.field" queries reach them (e.g. numpy scalars, value objects)
+/// the value itself is still matched by identity. an extractor raising, or returning something other than a dict,
+/// fails the object's construction or the assignment with that error
+/// applies to values assigned afterwards, so register before building indexes
+#[pyfunction]
+pub fn register_extractor(cls: &Bound<'_, PyType>, extractor: Bound<'_, PyAny>) -> PyResult<()> {
+    if !extractor.is_callable() {
+        return Err(PyTypeError::new_err("extractor must be callable"));
+    }
+    register_field_extractor(cls, extractor.unbind());
+    Ok(())
+}
+
+/// removes the extractor for cls, returns whether one was registered
+#[pyfunction]
+pub fn unregister_extractor(cls: &Bound<'_, PyType>) -> bool {
+    unregister_field_extractor(cls)
+}
@@ -1,27 +1,49 @@
 use std::{sync::{Arc, RwLock}};
 
-use croaring::Bitmap;
-use pyo3::{pyclass, pymethods, Py, PyAny, PyResult, Python};
+use croaring::{Bitmap, Portable};
+use pyo3::{pyclass, pymethods, types::{PyBytes, PyDict}, Bound, IntoPyObjectExt, Py, PyAny, PyObject, PyRef, PyResult, Python};
 use rustc_hash::FxHashMap;
 use smol_str::SmolStr;
 
-use crate::index::{Index, Indexable, PyQueryExpr, core::{query::query_ops::{QueryExpr, evaluate_and_queries_vec}, structures::m2m::M2MU32}, types::IndexTree, value::PyValue};
+use crate::index::{Index, Indexable, PyQueryExpr, core::{query::query_ops::{QueryExpr, evaluate_and_queries_vec}, structures::{freeze_lock::FreezeLock, m2m::M2MU32}}, types::IndexTree, value::PyValue};
+use crate::index::core::coverage::{AppliedQuery, coverage};
+use crate::index::core::group_by::{duplicate_groups, group_attrs, group_by, group_names, groups_to_dict, redundant_ids};
+use crate::index::core::sample::{sample, strata_groups};
+use crate::index::core::nearest::nearest;
+use crate::index::core::order_by::order_by;
+use crate::index::core::stats::aggregate_dict;
+use crate::index::core::column::sort_keys;
+use crate::index::core::traverse::{component_of, connected, traverse, via_attrs};
+use crate::index::core::outliers::{OutlierMethod, outliers};
+use crate::index::core::group_agg::{Metric, group_rows, parse_metrics, pivot};
 use crate::index::core::stored_item::StoredItem;
+use crate::index::core::parquet::write_parquet;
+use crate::index::core::sequence::Sequence;
+use crate::index::interfaces::id_set::PyIdSet;
+use crate::index::interfaces::result_iter::{ResultIds, ResultIter};
 use crate::index::core::index::IndexAPI;
+use crate::index::core::attr_decls::AttrDecls;
 use crate::index::core::query::{evaluate_query, QueryMap};
 
+/// FilteredIndex is a view into an Index with an allow list of items.
+/// It supports the same querying and filtering operations as Index
+/// but does not support adding or removing objects.
 #[pyclass]
 #[derive(Clone)]
 pub struct FilteredIndex {
     pub index: IndexTree,
-    pub items: Arc<RwLock<Vec<StoredItem>>>,
+    pub items: Arc<FreezeLock<Vec<StoredItem>>>,
     pub allowed_items: Bitmap,
+    pub applied: Option<Arc<AppliedQuery>>,
+    // set when results follow the index's insertion order rather than id order
+    pub order: Option<Arc<FreezeLock<Sequence>>>,
 }
 
 
 #[pymethods]
 impl FilteredIndex{
 
+    /// returns a FilteredIndex containing only items that match the given attribute filters
     #[pyo3(signature = (**kwargs))]
     pub fn reduced<'py>(
         &self,
@@ -46,39 +68,309 @@ impl FilteredIndex{
                 QueryExpr::Eq(k, v)
             }).collect();
 
-            Ok(FilteredIndex {
+            let filtered = FilteredIndex {
                 index: self.index.clone(),
                 items: self.items.clone(),
-                allowed_items: evaluate_and_queries_vec(&index, &self.allowed_items, &exprs)
-            })
+                allowed_items: evaluate_and_queries_vec(&index, &self.allowed_items, &exprs),
+                applied: self.applied.clone(),
+                order: self.order.clone(),
+            };
+            Ok(filtered.applying(exprs, || self.allowed_items.clone()))
         })
     }
 
+    /// returns a FilteredIndex containing only items that match the given query expression
     pub fn reduced_query(
         &self,
-        query: PyQueryExpr,
-    ) -> FilteredIndex {
+        mut query: PyQueryExpr,
+    ) -> PyResult<FilteredIndex> {
+        self.apply_declarations(&mut query.inner)?;
+        query.inner.check_types()?;
         let index = self.index.read().unwrap();
         let allowed = &self.allowed_items;
-        self.filter_from_bitmap(
-            evaluate_query(&index, &allowed, &query.inner)
-        )
+        let filtered = self.filter_from_bitmap(
+            evaluate_query(&index, &allowed, &query.inner.normalized())
+        );
+        Ok(filtered.applying(vec![query.inner], || self.allowed_items.clone()))
+    }
+
+    /// for each leaf clause of the queries that produced this FilteredIndex (against the index they started from)
+    /// returns clause, indexed (matched objects holding a value the clause's index structure answers),
+    /// fallback (matched objects without one, let in through not / ne), unindexable (objects left out holding
+    /// a value the structure cannot answer, e.g. a str for gt or an unknown type) and missing (objects left out
+    /// without the attribute), nonzero unindexable counts mean the results may be incomplete
+    pub fn coverage<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        let Some(applied) = &self.applied else {
+            return Ok(vec![]);
+        };
+        let clauses = {
+            let index = self.index.read().unwrap();
+            let items = self.items.read().unwrap();
+            coverage(&index, &items, applied, &self.allowed_items)
+        };
+        clauses.iter().map(|clause| clause.to_dict(py)).collect()
+    }
+
+    /// sortable keys of the objects in this FilteredIndex like Index.sort_keys
+    pub fn sort_keys<'py>(&self, py: Python<'py>, attr: &str) -> Vec<(Py<Indexable>, Bound<'py, PyBytes>)> {
+        let keys = sort_keys(&self.index.read().unwrap(), &self.allowed_items, attr);
+        let items = self.items.read().unwrap();
+        keys.iter().filter_map(|(id, key)| Some((items[*id as usize].get_py_ref(py)?, PyBytes::new(py, key)))).collect()
+    }
+
+    /// computes aggregates over the objects in this FilteredIndex like Index.aggregate
+    pub fn aggregate<'py>(&self, py: Python<'py>, attr: &str, funcs: Vec<String>) -> PyResult<Bound<'py, PyDict>> {
+        aggregate_dict(py, &self.index.read().unwrap(), &self.allowed_items, attr, &funcs)
+    }
+
+    /// returns the objects in this FilteredIndex sorted by attr like Index.order_by
+    #[pyo3(signature = (attr, descending=false, limit=None))]
+    pub fn order_by(&self, py: Python, attr: &str, descending: bool, limit: Option<usize>) -> Vec<Py<Indexable>> {
+        let ids = order_by(&self.index.read().unwrap(), &self.allowed_items, attr, descending, limit);
+        let items = self.items.read().unwrap();
+        ids.iter().filter_map(|id| items[*id as usize].get_py_ref(py)).collect()
+    }
+
+    /// finds the objects in this FilteredIndex closest to target like Index.nearest
+    #[pyo3(signature = (attr, target, k=5))]
+    pub fn nearest(&self, py: Python, attr: &str, target: f64, k: usize) -> Vec<Py<Indexable>> {
+        let ids = nearest(&self.index.read().unwrap(), &self.allowed_items, attr, target, k);
+        let items = self.items.read().unwrap();
+        ids.iter().filter_map(|id| items[*id as usize].get_py_ref(py)).collect()
+    }
+
+    /// follows references from start_objs to the objects in this FilteredIndex like Index.traverse
+    #[pyo3(signature = (start_objs, via, depth=None, filter=None))]
+    pub fn traverse(&self, start_objs: Vec<PyRef<Indexable>>, via: &Bound<'_, PyAny>, depth: Option<u32>, filter: Option<PyQueryExpr>) -> PyResult<FilteredIndex> {
+        let via = via_attrs(via)?;
+        let scope = match filter {
+            Some(filter) => self.reduced_query(filter)?.allowed_items,
+            None => self.allowed_items.clone(),
+        };
+        let start: Vec<&Indexable> = start_objs.iter().map(|obj| &**obj).collect();
+        let ids = traverse(&self.items.read().unwrap(), &scope, &start, &via, depth);
+        Ok(self.filter_from_bitmap(ids))
+    }
+
+    /// whether a and b are linked within this FilteredIndex like Index.connected
+    #[pyo3(signature = (a, b, via, max_depth=Some(6)))]
+    pub fn connected(&self, a: PyRef<Indexable>, b: PyRef<Indexable>, via: &Bound<'_, PyAny>, max_depth: Option<u32>) -> PyResult<bool> {
+        let via = via_attrs(via)?;
+        Ok(connected(&self.items.read().unwrap(), &self.allowed_items, a.id, b.id, &via, max_depth))
+    }
+
+    /// the objects in this FilteredIndex linked to obj like Index.component_of
+    pub fn component_of(&self, obj: PyRef<Indexable>, via: &Bound<'_, PyAny>) -> PyResult<FilteredIndex> {
+        let via = via_attrs(via)?;
+        let ids = component_of(&self.items.read().unwrap(), &self.allowed_items, obj.id, &via);
+        Ok(self.filter_from_bitmap(ids))
     }
 
-    pub fn collect(&self, py:Python) -> PyResult<Vec<Py<Indexable>>> {
-        self.get_from_indexes(py, &self.allowed_items)
+    /// flags outliers among the objects in this FilteredIndex like Index.outliers
+    #[pyo3(signature = (attr, method="iqr", threshold=None, filter=None))]
+    pub fn outliers(&self, attr: &str, method: &str, threshold: Option<f64>, filter: Option<PyQueryExpr>) -> PyResult<FilteredIndex> {
+        let method = OutlierMethod::parse(method)?;
+        let scope = match filter {
+            Some(filter) => self.reduced_query(filter)?.allowed_items,
+            None => self.allowed_items.clone(),
+        };
+        let threshold = threshold.unwrap_or(method.default_threshold());
+        let ids = outliers(&self.index.read().unwrap(), &scope, attr, method, threshold);
+        Ok(self.filter_from_bitmap(ids))
     }
 
-    pub fn rebase(&self) -> PyResult<Index> {
+    /// finds duplicates among the objects in this FilteredIndex like Index.duplicates
+    #[pyo3(signature = (on, keep=None))]
+    pub fn duplicates<'py>(&self, py: Python<'py>, on: &Bound<'py, PyAny>, keep: Option<&str>) -> PyResult<Bound<'py, PyAny>> {
+        let (attrs, _) = group_attrs(on, false)?;
+        let groups = duplicate_groups(py, &self.index, &self.items, &self.allowed_items, &attrs)?;
+        match keep {
+            Some(keep) => self.filter_from_bitmap(redundant_ids(&groups, keep)?).into_bound_py_any(py),
+            None => groups.into_iter().map(PyIdSet::from_bitmap).collect::<Vec<_>>().into_bound_py_any(py),
+        }
+    }
+
+    /// draws from the objects in this FilteredIndex like Index.sample
+    #[pyo3(signature = (n, weights=None, stratify_by=None, seed=None))]
+    pub fn sample(&self, py: Python, n: usize, weights: Option<&str>, stratify_by: Option<&str>, seed: Option<u64>) -> PyResult<FilteredIndex> {
+        let groups = strata_groups(py, &self.index, &self.items, &self.allowed_items, stratify_by)?;
+        let ids = sample(&self.items, &groups, n, weights, seed);
+        Ok(self.filter_from_bitmap(ids))
+    }
+
+    /// crosstab of the objects in this FilteredIndex like Index.pivot
+    #[pyo3(signature = (rows, cols, metric=None))]
+    pub fn pivot<'py>(&self, py: Python<'py>, rows: &str, cols: &str, metric: Option<(String, String)>) -> PyResult<Bound<'py, PyDict>> {
+        let metric = metric.map(|(attr, func)| Metric::new(&attr, &func)).transpose()?
+            .map(|metric| metric.declared(&|attr| self.declared_fixed_point(attr)));
+        pivot(py, &self.index, &self.items, &self.allowed_items, rows, cols, metric.as_ref())
+    }
+
+    /// aggregates the groups of the objects in this FilteredIndex like Index.group_agg
+    #[pyo3(signature = (by, metrics=None, having=None))]
+    pub fn group_agg<'py>(
+        &self,
+        py: Python<'py>,
+        by: &Bound<'py, PyAny>,
+        metrics: Option<&Bound<'py, PyDict>>,
+        having: Option<PyQueryExpr>,
+    ) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        let (names, _) = group_names(by)?;
+        let metrics = parse_metrics(metrics, &|attr| self.declared_fixed_point(attr))?;
+        group_rows(py, &self.index, &self.items, &self.allowed_items, &names, &metrics, having.as_ref().map(|having| &having.inner))
+    }
+
+    /// groups the objects in this FilteredIndex like Index.group_by
+    #[pyo3(signature = (by, flat=false))]
+    pub fn group_by<'py>(&self, py: Python<'py>, by: &Bound<'py, PyAny>, flat: bool) -> PyResult<Bound<'py, PyDict>> {
+        let (attrs, keys) = group_attrs(by, flat)?;
+        let groups = {
+            let index = self.index.read().unwrap();
+            let items = self.items.read().unwrap();
+            group_by(&index, &items, &self.allowed_items, &attrs)
+        };
+        groups_to_dict(py, groups, keys, |ids| self.filter_from_bitmap(ids))
+    }
+
+    /// counts the objects in this FilteredIndex per group like Index.group_by_count
+    #[pyo3(signature = (by, flat=false))]
+    pub fn group_by_count<'py>(&self, py: Python<'py>, by: &Bound<'py, PyAny>, flat: bool) -> PyResult<Bound<'py, PyDict>> {
+        let (attrs, keys) = group_attrs(by, flat)?;
+        let groups = {
+            let index = self.index.read().unwrap();
+            let items = self.items.read().unwrap();
+            group_by(&index, &items, &self.allowed_items, &attrs)
+        };
+        groups_to_dict(py, groups, keys, |ids| ids.cardinality())
+    }
+
+    /// returns the ids of the objects in this FilteredIndex
+    pub fn ids(&self) -> PyIdSet {
+        PyIdSet::from_bitmap(self.allowed_items.clone())
+    }
+
+    /// returns the ids of the objects in this FilteredIndex as a roaring bitmap in the portable format,
+    /// readable by pyroaring, java roaring and others, and by Index.filter_from_roaring_bytes
+    pub fn to_roaring_bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.allowed_items.serialize::<Portable>())
+    }
+
+    /// writes fields of the objects in this FilteredIndex to a parquet file at path, one row per object in collect order,
+    /// read straight from the index without building python objects. dotted fields read nested objects like collect
+    /// each column takes the type of its values (int, float, bool or str, ints and floats together as float),
+    /// None and objects without the field are written as null. returns the number of rows written
+    /// raises ValueError for a field holding other values or values no single column type can hold
+    pub fn to_parquet(&self, py: Python, path: std::path::PathBuf, fields: Vec<String>) -> PyResult<usize> {
+        let held = py.allow_threads(|| self.held_items());
+        let rows: Vec<&StoredItem> = held.iter().collect();
+        write_parquet(py, &path, &rows, &fields)
+    }
+
+    /// returns a FilteredIndex containing only items whose ids are in ids
+    pub fn reduced_ids(&self, ids: &PyIdSet) -> FilteredIndex {
+        self.filter_from_bitmap(ids.ids.clone())
+    }
+
+    /// returns a FilteredIndex of the objects in either this or other, both taken from the same index
+    /// like the other set operations, raises ValueError when they are not, and coverage of the result is empty
+    pub fn union(&self, other: &FilteredIndex) -> PyResult<FilteredIndex> {
+        self.combine(other, |a, b| a.or(b))
+    }
+
+    /// returns a FilteredIndex of the objects in both this and other
+    pub fn intersect(&self, other: &FilteredIndex) -> PyResult<FilteredIndex> {
+        self.combine(other, |a, b| a.and(b))
+    }
+
+    /// returns a FilteredIndex of the objects in this but not in other
+    pub fn difference(&self, other: &FilteredIndex) -> PyResult<FilteredIndex> {
+        self.combine(other, |a, b| a.andnot(b))
+    }
+
+    /// returns a FilteredIndex of the objects in exactly one of this and other
+    pub fn symmetric_difference(&self, other: &FilteredIndex) -> PyResult<FilteredIndex> {
+        self.combine(other, |a, b| a.xor(b))
+    }
+
+    /// collects all valid objects in the FilteredIndex and returns them as a list, ordered like Index.collect
+    /// with attrs, returns a dict per object holding only those attributes (None where missing) instead of the objects.
+    /// objects removed from the index after this FilteredIndex was made are left out
+    #[pyo3(signature = (attrs=None))]
+    pub fn collect(&self, py:Python, attrs: Option<Vec<String>>) -> PyResult<Vec<PyObject>> {
+        match attrs {
+            Some(attrs) => self.collect_attrs(py, &attrs),
+            None => Ok(self.get_from_indexes(py, &self.allowed_items)?.into_iter().map(|obj| obj.into_any()).collect()),
+        }
+    }
+
+    /// returns an iterator yielding the objects in collect order without building the whole list,
+    /// batch_size objects are looked up and handed to python at a time. objects added to the index
+    /// after the iterator is made never show up, and objects removed before their batch is reached
+    /// are skipped like collect skips them. attribute values are read live from the objects
+    #[pyo3(signature = (batch_size=1024))]
+    pub fn iter(&self, py: Python, batch_size: usize) -> ResultIter {
+        let ids = match &self.order {
+            Some(_) => ResultIds::Ordered(py.allow_threads(|| self.ordered_ids()).into_iter()),
+            None => ResultIds::sorted(self.allowed_items.clone()),
+        };
+        ResultIter::new(self.items.clone(), ids, batch_size)
+    }
+
+    /// iterates like iter() with its default batch_size
+    fn __iter__(&self, py: Python) -> ResultIter {
+        self.iter(py, 1024)
+    }
+
+    /// returns a FilteredIndex of the first n objects in collect order
+    pub fn take(&self, py: Python, n: usize) -> FilteredIndex {
+        py.allow_threads(|| self.slice(0, n))
+    }
+
+    /// returns a FilteredIndex without the first n objects in collect order, skip(offset).take(limit) pages through results
+    pub fn skip(&self, py: Python, n: usize) -> FilteredIndex {
+        py.allow_threads(|| self.slice(n, usize::MAX))
+    }
+
+    /// returns a new Index containing only the items in this FilteredIndex
+    /// Only use this when a full Index is needed, as it is much less performant
+    pub fn rebase(&self, py: Python) -> PyResult<Index> {
 
         let max_size = self.allowed_items.maximum().unwrap_or(0);
         let index_api = IndexAPI {
-            index: Arc::new(RwLock::new(vec![])),
-            items: Arc::new(RwLock::new(Vec::with_capacity(max_size as usize))),
-            allowed_items: Arc::new(RwLock::new(self.allowed_items.clone())),
+            index: Arc::default(),
+            items: Arc::new(FreezeLock::new(Vec::with_capacity(max_size as usize))),
+            allowed_items: Arc::new(FreezeLock::new(self.allowed_items.clone())),
             parent_child_map: Arc::new(RwLock::new(M2MU32::new())),
             parent_index: None,
+            decls: Default::default(),
+            config: Default::default(),
+            top_k: Default::default(),
+            tracking_top_k: Default::default(),
+            alerts: Default::default(),
+            cross_lock: Default::default(),
+            frozen: Default::default(),
+            mutations: Default::default(),
+            sequence: Default::default(),
+            numbering: Default::default(),
+            churn: Default::default(),
+            metrics: Default::default(),
+            wal: Default::default(),
+            replicas: Default::default(),
+            deferred: Default::default(),
+            freed: Default::default(),
+            pool: Default::default(),
         };
+        {
+            // the rebased index takes the objects in the order collect gives them
+            let ids = match &self.order {
+                Some(order) => order.read().unwrap().in_order(&self.allowed_items),
+                None => self.allowed_items.iter().collect(),
+            };
+            let mut sequence = index_api.sequence.write().unwrap();
+            for idx in ids {
+                sequence.stamp(idx);
+            }
+        }
         
         let mut new_index = index_api.index.write().unwrap();
         let mut new_items = index_api.items.write().unwrap();
@@ -88,22 +380,29 @@ impl FilteredIndex{
         new_items.resize(max_size as usize + 1, StoredItem::default());
         
         let items = self.items.read().unwrap();
+        let mut freed = Bitmap::new();
         for idx in self.allowed_items.iter() {
-            let item = &items[idx as usize];
+            // held strongly like in any other index, even when taken from an auto_remove_on_gc one
+            let Some(item) = items[idx as usize].strong(py) else {
+                freed.add(idx);
+                continue;
+            };
 
             let owned_ref = item.get_owned_handle();
             owned_ref.add_index(res_index_arc.clone());
 
             new_items[idx as usize] = item.clone();
             
-            for (attr_id, val) in owned_ref.get_py_values().iter() {
+            for (attr_id, val) in owned_ref.get_py_values().iter().filter(|(attr_id, _)| owned_ref.is_indexed(**attr_id)) {
                 match new_index.get_mut(*attr_id as usize){
                     Some(val_map) => {
                         val_map.insert(&val, idx);
+                        val_map.mark_present(idx);
                     },
                     None => {
-                        let qmap = QueryMap::new(res_index_arc.clone());
+                        let qmap = QueryMap::new(res_index_arc.clone(), AttrDecls::default());
                         qmap.insert(&val, idx);
+                        qmap.mark_present(idx);
                         new_index.insert(*attr_id as usize, qmap);
                     }
                 }
@@ -112,6 +411,13 @@ impl FilteredIndex{
 
         drop(new_index);
         drop(new_items);
+        if !freed.is_empty() {
+            index_api.allowed_items.write().unwrap().andnot_inplace(&freed);
+            let mut sequence = index_api.sequence.write().unwrap();
+            for idx in freed.iter() {
+                sequence.remove(idx);
+            }
+        }
 
         let res_index = Index {
             inner: Arc::new(index_api)
@@ -0,0 +1,87 @@
+use croaring::Bitmap;
+use pyo3::{prelude::*, pyclass::CompareOp, types::{PyBytes, PyList}};
+
+/// immutable set of object ids backed by a roaring bitmap, returned by ids()
+/// supports len, in, iteration in ascending order, & | - ^ with other IdSets
+/// and == != < <= > >= as set equality and subset tests
+#[pyclass(frozen)]
+#[derive(Clone)]
+pub struct PyIdSet {
+    pub ids: Bitmap,
+}
+
+impl PyIdSet {
+    pub fn from_bitmap(ids: Bitmap) -> Self {
+        Self { ids }
+    }
+}
+
+#[pymethods]
+impl PyIdSet {
+    #[new]
+    #[pyo3(signature = (ids=None))]
+    fn new(ids: Option<Vec<u32>>) -> Self {
+        Self::from_bitmap(ids.map_or_else(Bitmap::new, |ids| Bitmap::of(&ids)))
+    }
+
+    fn __len__(&self) -> usize {
+        self.ids.cardinality() as usize
+    }
+
+    fn __bool__(&self) -> bool {
+        !self.ids.is_empty()
+    }
+
+    fn __contains__(&self, id: i64) -> bool {
+        u32::try_from(id).is_ok_and(|id| self.ids.contains(id))
+    }
+
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        Ok(PyList::new(py, self.ids.to_vec())?.try_iter()?.into_any())
+    }
+
+    fn __and__(&self, other: &Self) -> Self {
+        Self::from_bitmap(self.ids.and(&other.ids))
+    }
+
+    fn __or__(&self, other: &Self) -> Self {
+        Self::from_bitmap(self.ids.or(&other.ids))
+    }
+
+    fn __sub__(&self, other: &Self) -> Self {
+        Self::from_bitmap(self.ids.andnot(&other.ids))
+    }
+
+    fn __xor__(&self, other: &Self) -> Self {
+        Self::from_bitmap(self.ids.xor(&other.ids))
+    }
+
+    fn __richcmp__(&self, other: &Self, op: CompareOp) -> bool {
+        match op {
+            CompareOp::Eq => self.ids == other.ids,
+            CompareOp::Ne => self.ids != other.ids,
+            CompareOp::Le => self.ids.is_subset(&other.ids),
+            CompareOp::Ge => other.ids.is_subset(&self.ids),
+            CompareOp::Lt => self.ids.is_subset(&other.ids) && self.ids != other.ids,
+            CompareOp::Gt => other.ids.is_subset(&self.ids) && self.ids != other.ids,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<IdSet: {} ids>", self.ids.cardinality())
+    }
+
+    /// returns the ids in ascending order
+    pub fn to_list(&self) -> Vec<u32> {
+        self.ids.to_vec()
+    }
+
+    /// returns the ids as a uint32 numpy array in ascending order, requires numpy
+    pub fn to_numpy<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let bytes: Vec<u8> = self.ids.iter().flat_map(u32::to_ne_bytes).collect();
+        let numpy = py.import("numpy")?;
+        numpy.getattr("frombuffer")?
+            .call1((PyBytes::new(py, &bytes), numpy.getattr("uint32")?))?
+            .call_method0("copy")
+    }
+}
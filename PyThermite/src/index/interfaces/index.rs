@@ -1,15 +1,43 @@
 
-use std::sync::Arc;
+use std::{collections::{BTreeMap, hash_map::Entry}, sync::Arc, time::Duration};
+use croaring::{Bitmap, Portable};
 use pyo3::prelude::*;
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::types::{PyBytes, PyDict, PyList, PyType};
+use pyo3::IntoPyObjectExt;
 use rustc_hash::FxHashMap;
 use smol_str::SmolStr;
 
 use crate::index::{Indexable, PyQueryExpr, core::{query::{query_ops::{QueryExpr, kwargs_to_query}}, structures::{hybrid_set::HybridSetOps, string_interner::INTERNER}}, types::StrId};
 use crate::index::interfaces::filtered_index::FilteredIndex;
+use crate::index::interfaces::id_set::PyIdSet;
+use crate::index::interfaces::{BulkUpdate, IngestStream, PyIndexConfig};
 use crate::index::core::index::IndexAPI;
+use crate::index::core::class_fields::class_value;
+use crate::index::core::column::{ColumnType, sort_keys};
+use crate::index::core::group_by::{duplicate_groups, group_attrs, group_names, redundant_ids};
+use crate::index::core::sample::{sample, strata_groups};
+use crate::index::core::nearest::nearest;
+use crate::index::core::order_by::order_by;
+use crate::index::core::stats::aggregate_dict;
+use crate::index::core::alerts::{Alert, parse_op};
+use crate::index::core::traverse::{component_of, connected, traverse, via_attrs};
+use crate::index::core::outliers::{OutlierMethod, outliers};
+use crate::index::core::group_agg::{Metric, parse_metrics, pivot};
+use crate::index::core::config::{IndexConfig, ResultOrder};
+use crate::index::core::attr_decls::AttrDecls;
+use crate::index::core::structures::positional_bitmap::DEFAULT_MAX_STR_LEN;
 use crate::index::core::stored_item::StoredItem;
-use crate::index::value::PyValue;
+use crate::index::core::arrow::read_columns;
+use crate::index::core::semver::ensure_packaging_encoder;
+use crate::index::core::metrics::DEFAULT_SLOW_LOG_CAPACITY;
+use crate::index::core::snapshot;
+use crate::index::value::{PyValue, RustCastValue};
+use crate::index::core::structures::string_interner::intern_for_class;
 
+/// Index is a collection of Indexable objects that can be queried and filtered.
+/// It supports adding and removing objects, as well as querying via attribute filters or query expressions.
+/// Experimental Thread safe, but not officially supported yet.
 #[pyclass]
 pub struct Index {
     pub inner: Arc<IndexAPI>
@@ -17,18 +45,66 @@ pub struct Index {
 
 #[pymethods]
 impl Index {
+    /// max_str_len caps the length, in characters, of strings kept in the positional string index,
+    /// longer strings are still queryable, matched by content rather than by position
+    /// strict raises TypeError when adding an object, or assigning a value to an indexed one, holding a value
+    /// of a type the index can only match by identity (anything but numbers, str, bool, Indexable, containers and None),
+    /// including inside containers and nested objects, the object is left unchanged
+    /// opaque lists attributes (dotted paths allowed) that may hold such values in a strict index
+    /// auto_remove_on_gc holds objects weakly, so python frees them once nothing else refers to them. freed objects are
+    /// left out of the objects returned straight away, and removed from the index by the next add, reduce, set_column,
+    /// adopt or union_with, which only look at the objects freed since
+    /// order sets the order collect returns objects in, on the index and every FilteredIndex taken from it:
+    /// "id" (ascending object id, the guaranteed default) or "insertion" (the order objects were first added,
+    /// adding an object already held keeps its place, and numbers objects as sequence() does). raises ValueError for any other order
+    /// config takes all of these, and attribute declarations, from an IndexConfig instead, raises ValueError
+    /// when given together with any of the other arguments
     #[new]
-    pub fn new() -> Self {
-        let index = IndexAPI::new(None);
-        Self {
-            inner: Arc::new(index)
+    #[pyo3(signature = (max_str_len=None, strict=None, opaque=None, auto_remove_on_gc=None, order=None, *, config=None))]
+    pub fn new(
+        py: Python,
+        max_str_len: Option<usize>,
+        strict: Option<bool>,
+        opaque: Option<Vec<String>>,
+        auto_remove_on_gc: Option<bool>,
+        order: Option<&str>,
+        config: Option<PyRef<PyIndexConfig>>,
+    ) -> PyResult<Self> {
+        if let Some(config) = config {
+            if max_str_len.is_some() || strict.is_some() || opaque.is_some() || auto_remove_on_gc.is_some() || order.is_some() {
+                return Err(PyValueError::new_err("pass either config or the settings it holds, not both"));
+            }
+            return Ok(Self { inner: Arc::new(config.build(py)?) });
         }
+        let config = IndexConfig {
+            max_str_len: max_str_len.unwrap_or(DEFAULT_MAX_STR_LEN),
+            strict: strict.unwrap_or(false),
+            auto_remove_on_gc: auto_remove_on_gc.unwrap_or(false),
+            order: ResultOrder::parse(order.unwrap_or("id"))?,
+            ..IndexConfig::default()
+        };
+        let mut decls = AttrDecls::default();
+        for attr in opaque.iter().flatten() {
+            decls.declare(attr, |decl| decl.opaque = true);
+        }
+        let index = IndexAPI::with_decls(None, decls).with_config(Arc::new(config));
+        Ok(Self {
+            inner: Arc::new(index)
+        })
     }
 
-    pub fn collect(&self, py: Python) -> PyResult<Vec<Py<Indexable>>> {
-        self.inner.collect(py)
+    /// collects all valid objects in the index and returns them as a list, ordered as set by the index's order
+    /// with attrs, returns a dict per object holding only those attributes (None where missing) instead of the objects,
+    /// dotted attributes like "owner.name" read a nested object's value
+    #[pyo3(signature = (attrs=None))]
+    pub fn collect(&self, py: Python, attrs: Option<Vec<String>>) -> PyResult<Vec<PyObject>> {
+        match attrs {
+            Some(attrs) => self.inner.filter_from_bitmap(self.inner.ids()).collect_attrs(py, &attrs),
+            None => Ok(self.inner.collect(py)?.into_iter().map(|obj| obj.into_any()).collect()),
+        }
     }
 
+    /// returns a FilteredIndex containing only items that match the given attribute filters
     #[pyo3(signature = (**kwargs))]
     pub fn reduced<'py>(
         &self,
@@ -36,24 +112,33 @@ impl Index {
         kwargs: Option<FxHashMap<String, pyo3::Bound<'py, PyAny>>>,
     ) -> PyResult<FilteredIndex> {
         let eq_query = kwargs_to_query(kwargs);
+        let clauses = eq_query.iter().map(|(k, v)| QueryExpr::Eq(k.clone(), v.clone())).collect();
         py.allow_threads(move || {
-            Ok(self.inner.reduced(eq_query))
+            Ok(self.inner.reduced(eq_query).applying(clauses, || self.inner.get_allowed_items_reader().clone()))
         })
     }
 
+    /// removes items in place that do not match the given attribute filters
+    /// Note that this modifies the current index in place and is less performant
+    /// than using a method that returns a filteredIndex such as reduced_query or reduced
     #[pyo3(signature = (**kwargs))]
     pub fn reduce<'py>(
         &self,
         py: Python,
         kwargs: Option<FxHashMap<String, pyo3::Bound<'py, PyAny>>>,
     ) -> PyResult<()> {
+        let _mutating = self.inner.check_mutable()?;
+        self.inner.reap_if_auto(py);
         let eq_query = kwargs_to_query(kwargs);
         py.allow_threads(move || {
             self.inner.reduce(eq_query);
         });
-        Ok(())
+        self.inner.fire_alerts(py)
     }
 
+    /// a shorthand for reduced(**kwargs).collect()
+    /// more performant than using reduced when you only need the collected results
+    /// and not the FilteredIndex to further query
     #[pyo3(signature = (**kwargs))]
     pub fn get_by_attribute<'py>(
         &self,
@@ -61,61 +146,1112 @@ impl Index {
         kwargs: Option<FxHashMap<String, pyo3::Bound<'py, PyAny>>>,
     ) -> PyResult<Vec<Py<Indexable>>> {
         let eq_query = kwargs_to_query(kwargs);
-        py.allow_threads(move || {
+        let filtered = py.allow_threads(move || {
             self.inner.reduced(eq_query)
-        }).collect(py)
+        });
+        filtered.get_from_indexes(py, &filtered.allowed_items)
     }
 
+    /// returns an IngestStream adding the batches fed to it on a background thread, for producers that
+    /// must not outrun indexing. at most capacity batches wait to be added, feeding a full queue waits
+    /// for room when block is true, otherwise raises queue.Full. errors adding a batch are raised by the
+    /// next feed, flush or close, use it as a context manager to flush and close on exit
+    #[pyo3(signature = (capacity=16, block=true))]
+    pub fn ingest_stream(&self, capacity: usize, block: bool) -> PyResult<IngestStream> {
+        let _mutating = self.inner.check_mutable()?;
+        if capacity == 0 {
+            return Err(PyValueError::new_err("capacity must be at least 1"));
+        }
+        Ok(IngestStream::new(self.inner.clone(), capacity, block))
+    }
+
+    /// adds a multiple objects to the index
     pub fn add_object_many(&self, py: Python, objs: Vec<PyRef<Indexable>>) -> PyResult<()> {
-        
-        let raw_objs: Vec<(Indexable, Py<Indexable>)> = objs.into_iter().map(|obj| {
-            (
-                Indexable::from_py_ref(&obj, py),
-                obj.into_pyobject(py).unwrap().unbind()
-            )
-        }).collect();
+        let _mutating = self.inner.check_mutable()?;
+        self.inner.reap_if_auto(py);
+        // all or nothing, every object is checked before any is added
+        for obj in objs.iter() {
+            self.inner.check_strict_object(obj)?;
+        }
+
+        let stored_items: Vec<StoredItem> = objs.into_iter().map(|obj| {
+            let rust_handle = Arc::new(Indexable::from_py_ref(&obj, py));
+            self.inner.hold(py, obj.into_pyobject(py)?.unbind(), rust_handle)
+        }).collect::<PyResult<_>>()?;
 
         py.allow_threads(|| {
             let weak_index = Arc::downgrade(&self.inner);
-            self.inner.add_object_many(weak_index, raw_objs);
+            self.inner.add_object_many(weak_index, stored_items);
         });
 
+        self.inner.fire_alerts(py)
+
+    }
+
+    /// builds an object of cls (Indexable by default) from each dict of attribute values and adds them all
+    /// to the index, like Indexable.construct_many followed by add_object_many. returns the new objects
+    /// a value that can't be read raises its error and nothing is added, unless errors is a list: the row is then
+    /// skipped and (row position, attr, exception) appended to it, the other rows are still added
+    #[pyo3(signature = (rows, cls=None, *, errors=None))]
+    pub fn add_dicts<'py>(
+        &self,
+        py: Python<'py>,
+        rows: Vec<Bound<'py, PyDict>>,
+        cls: Option<Bound<'py, PyType>>,
+        errors: Option<&Bound<'py, PyList>>,
+    ) -> PyResult<Vec<Bound<'py, PyAny>>> {
+        let _mutating = self.inner.check_mutable()?;
+        let objs = Indexable::construct_many(&indexable_class(py, cls)?, rows, errors)?;
+        self.add_built(py, &objs)?;
+        Ok(objs)
+    }
+
+    /// builds a new Index holding one object of cls (Indexable by default) per row of an arrow record batch,
+    /// table or anything else exporting the arrow pycapsule interface (__arrow_c_stream__ or __arrow_c_array__).
+    /// the columns are decoded from the arrow buffers and each is indexed in one pass, both in parallel.
+    /// values are copied out of the buffers onto the objects directly, without attribute assignment.
+    /// supports bool, integer, float and string columns, dictionary encoded ones included,
+    /// null values leave the attribute unset. raises TypeError for any other column type
+    #[staticmethod]
+    #[pyo3(signature = (data, cls=None))]
+    pub fn from_arrow<'py>(py: Python<'py>, data: &Bound<'py, PyAny>, cls: Option<Bound<'py, PyType>>) -> PyResult<Self> {
+        let index = Self::new(py, None, None, None, None, None, None)?;
+        index.add_arrow(py, data, cls, None, None, 0)?;
+        Ok(index)
+    }
+
+    /// adds one object of cls (Indexable by default) per row of data, read as from_arrow reads it (pandas DataFrames included),
+    /// returns the number of rows of data added, counting those skipped by resume_from
+    /// with checkpoint_every, rows are added that many at a time and each chunk is queryable as soon as it is added.
+    /// on_checkpoint is called with the count after each chunk, the token to resume from: after a crash part way,
+    /// restore what was added (Index.load of a log kept by enable_wal) and call add_arrow again with resume_from set to it
+    #[pyo3(signature = (data, cls=None, *, checkpoint_every=None, on_checkpoint=None, resume_from=0))]
+    pub fn add_arrow<'py>(
+        &self,
+        py: Python<'py>,
+        data: &Bound<'py, PyAny>,
+        cls: Option<Bound<'py, PyType>>,
+        checkpoint_every: Option<usize>,
+        on_checkpoint: Option<Bound<'py, PyAny>>,
+        resume_from: usize,
+    ) -> PyResult<usize> {
+        if checkpoint_every == Some(0) {
+            return Err(PyValueError::new_err("checkpoint_every must be at least 1"));
+        }
+        let _mutating = self.inner.check_mutable()?;
+        let cls = indexable_class(py, cls)?;
+        let (names, columns) = read_columns(py, data)?;
+        let type_ptr = cls.as_ptr() as usize;
+        let attrs: Vec<StrId> = names.iter().map(|name| intern_for_class(type_ptr, name)).collect();
+        let rows = columns.first().map_or(0, Vec::len);
+        let mut added = resume_from.min(rows);
+        let mut columns: Vec<_> = columns.into_iter().map(|mut column| {
+            column.drain(..added);
+            column.into_iter()
+        }).collect();
+
+        let chunk = checkpoint_every.unwrap_or(rows);
+        while added < rows {
+            let n = chunk.min(rows - added);
+            self.add_rows(py, &cls, &attrs, &mut columns, n)?;
+            added += n;
+            if let Some(on_checkpoint) = &on_checkpoint {
+                on_checkpoint.call1((added,))?;
+            }
+        }
+        Ok(added)
+    }
+
+    /// writes a snapshot of the index to path: the int, float, str and bool attribute values of every object,
+    /// in the order collect gives them, the constructor settings (max_str_len, strict, auto_remove_on_gc, order, sequence,
+    /// hash_mode) and the declarations made on the index or its IndexConfig (semver, units, limit_nested and the like)
+    /// other values (nested objects, containers, arbitrary objects) are not saved
+    pub fn save(&self, py: Python, path: std::path::PathBuf) -> PyResult<()> {
+        let bytes = py.allow_threads(|| snapshot::encode(&self.inner.config, &self.inner.get_decls_reader(), &self.inner.ordered_items()));
+        std::fs::write(path, bytes)?;
         Ok(())
+    }
+
+    /// logs every add, remove and update to path so the index can be restored with Index.load(path) after a crash
+    /// path starts as a snapshot of the index (see save), each change is then appended as a record written straight
+    /// to the file, so a crash loses at most the change being written. values save leaves out are logged as unset
+    /// calling it again moves the log to a new path. once a write fails, adding or removing objects raises OSError
+    /// until compact succeeds
+    pub fn enable_wal(&self, py: Python, path: std::path::PathBuf) -> PyResult<()> {
+        py.allow_threads(|| self.inner.enable_wal(&path))?;
+        Ok(())
+    }
+
+    /// rewrites the log as a snapshot of the current state, dropping the records it has replaced
+    /// raises ValueError if enable_wal has not been called
+    pub fn compact(&self, py: Python) -> PyResult<()> {
+        let path = match self.inner.wal.lock().unwrap().as_ref() {
+            Some(wal) => wal.path().to_path_buf(),
+            None => return Err(PyValueError::new_err("no write ahead log to compact, call enable_wal first")),
+        };
+        self.enable_wal(py, path)
+    }
+
+    /// builds a new Index from a snapshot written by save or a log kept by enable_wal, one object of cls (Indexable by default) per saved object
+    /// holding its saved values, added in the saved order. the index takes the saved settings and declarations, and its maps
+    /// are filled a column at a time from the saved values, without going through python objects
+    /// raises ValueError if path is not a snapshot or is corrupt
+    /// with config (an IndexConfig) the index is built from it instead of from what the snapshot holds
+    #[staticmethod]
+    #[pyo3(signature = (path, cls=None, *, config=None))]
+    pub fn load<'py>(py: Python<'py>, path: std::path::PathBuf, cls: Option<Bound<'py, PyType>>, config: Option<PyRef<PyIndexConfig>>) -> PyResult<Self> {
+        let cls = indexable_class(py, cls)?;
+        let bytes = std::fs::read(path)?;
+        let snapshot = py.allow_threads(|| snapshot::decode(&bytes))?;
+        let inner = match config {
+            Some(config) => config.build(py)?,
+            None => {
+                if snapshot.decls.any_decl(&|decl| decl.semver) {
+                    ensure_packaging_encoder(py);
+                }
+                IndexAPI::with_decls(None, snapshot.decls).with_config(Arc::new(snapshot.config))
+            },
+        };
+        let index = Self {
+            inner: Arc::new(inner),
+        };
+        let type_ptr = cls.as_ptr() as usize;
+        let attrs: Vec<StrId> = snapshot.names.iter().map(|name| intern_for_class(type_ptr, name)).collect();
+        let mut columns: Vec<_> = snapshot.columns.into_iter().map(Vec::into_iter).collect();
+        index.add_rows(py, &cls, &attrs, &mut columns, snapshot.rows)?;
+        Ok(index)
+    }
 
+    /// like from_arrow for a pandas DataFrame, one object per row with an attribute per column.
+    /// needs pandas 2.2 or newer with pyarrow installed, the DataFrame is exported through __arrow_c_stream__
+    #[staticmethod]
+    #[pyo3(signature = (df, cls=None))]
+    pub fn from_pandas<'py>(py: Python<'py>, df: &Bound<'py, PyAny>, cls: Option<Bound<'py, PyType>>) -> PyResult<Self> {
+        if !df.hasattr("__arrow_c_stream__")? {
+            return Err(PyTypeError::new_err(format!(
+                "from_pandas needs a DataFrame exporting __arrow_c_stream__ (pandas 2.2 or newer), got {}",
+                df.get_type().name()?
+            )));
+        }
+        Self::from_arrow(py, df, cls)
     }
 
+    /// adds a single object to the index
     pub fn add_object(&self, py: Python, py_ref: PyRef<Indexable>) -> PyResult<()> {
+        let _mutating = self.inner.check_mutable()?;
+        self.inner.reap_if_auto(py);
+        self.inner.check_strict_object(&py_ref)?;
 
         let rust_handle = Arc::new(Indexable::from_py_ref(&py_ref, py));
-        let py_handle = Arc::new(py_ref.into_pyobject(py)?.unbind());
+        let stored_item = self.inner.hold(py, py_ref.into_pyobject(py)?.unbind(), rust_handle.clone())?;
 
         py.allow_threads(move ||{
             let weak_index = Arc::downgrade(&self.inner);
             rust_handle.add_index(weak_index.clone());
-            let stored_item = StoredItem::new(py_handle, rust_handle.clone());
             // i dont like this clone - need to remove
             let py_val_hashmap = rust_handle.get_py_values();
             self.inner.add_object(weak_index, rust_handle.id, stored_item, py_val_hashmap);
         });
 
+        self.inner.fire_alerts(py)
+    }
 
-        Ok(())
+    /// calls reindex_attr on obj for each of attrs (all of its attributes by default)
+    /// returns the attributes whose values changed, raises ValueError if obj is not in the index
+    #[pyo3(signature = (obj, attrs=None))]
+    pub fn refresh(&self, py: Python, obj: PyRef<Indexable>, attrs: Option<Vec<String>>) -> PyResult<Vec<String>> {
+        if !self.inner.contains_id(obj.id) {
+            return Err(PyValueError::new_err("object is not in the index"));
+        }
+        let attrs = attrs.unwrap_or_else(|| {
+            obj.get_py_values().keys().map(|id| INTERNER.resolve(*id)).collect()
+        });
+        let mut changed = Vec::new();
+        for attr in attrs {
+            if obj.reindex_attr(py, &attr)? {
+                changed.push(attr);
+            }
+        }
+        Ok(changed)
     }
 
+    /// returns a FilteredIndex containing only items that match the given query expression
     pub fn reduced_query(
         &self,
         py: Python,
-        query: PyQueryExpr,
+        mut query: PyQueryExpr,
     ) -> PyResult<FilteredIndex> {
+        self.inner.apply_declarations(&mut query.inner)?;
+        query.inner.check_types()?;
+        let clause = query.inner.clone();
+        py.allow_threads(move || {
+            Ok(self.inner.reduced_query(query).applying(vec![clause], || self.inner.get_allowed_items_reader().clone()))
+        })
+    }
+
+    /// returns a FilteredIndex for each query, like calling reduced_query on each in turn
+    /// but evaluated together, clauses repeated across the queries are only evaluated once
+    pub fn reduced_many(
+        &self,
+        py: Python,
+        queries: Vec<PyQueryExpr>,
+    ) -> PyResult<Vec<FilteredIndex>> {
+        let mut clauses = Vec::with_capacity(queries.len());
+        for mut query in queries {
+            self.inner.apply_declarations(&mut query.inner)?;
+            query.inner.check_types()?;
+            clauses.push(query.inner);
+        }
         py.allow_threads(move || {
-            Ok(self.inner.reduced_query(query))
+            Ok(self.inner.reduced_many(&clauses).into_iter()
+                .zip(clauses.iter())
+                .map(|(filtered, clause)| filtered.applying(vec![clause.clone()], || self.inner.get_allowed_items_reader().clone()))
+                .collect())
         })
     }
 
+    /// returns the ids of every object in the index
+    pub fn ids(&self) -> PyIdSet {
+        PyIdSet::from_bitmap(self.inner.ids())
+    }
+
+    /// returns a FilteredIndex of the objects in the index whose ids are in ids
+    pub fn reduced_ids(&self, ids: &PyIdSet) -> FilteredIndex {
+        self.inner.reduced_ids(&ids.ids)
+    }
+
+    /// returns a FilteredIndex of the objects in the index whose ids are in the roaring bitmap buf,
+    /// read in the portable format shared by pyroaring, java roaring and others, ids not in the index are ignored
+    /// raises ValueError if buf is not a portable roaring bitmap
+    pub fn filter_from_roaring_bytes(&self, buf: &[u8]) -> PyResult<FilteredIndex> {
+        let ids = Bitmap::try_deserialize::<Portable>(buf)
+            .ok_or_else(|| PyValueError::new_err("buf is not a portable roaring bitmap"))?;
+        Ok(self.inner.reduced_ids(&ids))
+    }
+
+    /// runs the query and returns a trace of how it was evaluated
+    /// each node has clause, matched, time_ns, levels (nested indexes traversed below it) and children
+    /// clauses on nested attributes also carry nested with attr, levels, child_matches, rollup_ns
+    /// (time spent mapping matching children back to parents) and the child trace
+    pub fn explain<'py>(&self, py: Python<'py>, mut query: PyQueryExpr) -> PyResult<Bound<'py, PyDict>> {
+        self.inner.apply_declarations(&mut query.inner)?;
+        query.inner.check_types()?;
+        let trace = py.allow_threads(|| self.inner.explain(&query.inner));
+        trace.to_dict(py)
+    }
+
+    /// builds a dense array.array of attr's numeric values, position i holding the object with id i
+    /// dtype is f64, f32 or i64, ids without a numeric value take fill (NaN by default for floats)
+    /// i64 columns require fill, unknown dtypes raise ValueError
+    #[pyo3(signature = (attr, dtype="f64", fill=None))]
+    pub fn column<'py>(&self, py: Python<'py>, attr: &str, dtype: &str, fill: Option<f64>) -> PyResult<Bound<'py, PyAny>> {
+        self.inner.column(py, attr, ColumnType::parse(dtype)?, fill)
+    }
+
+    /// returns (object, key) for every object with a numeric or encoded attr, where the 10 byte keys compare like the values,
+    /// so external or distributed sorts can order objects the way gt, lt and bt do, coerce_numeric and semver included.
+    /// keys of different kinds, such as numbers and versions, share no meaningful order
+    /// keys are the index's own encoding and stay the same across indexes and runs
+    pub fn sort_keys<'py>(&self, py: Python<'py>, attr: &str) -> Vec<(Py<Indexable>, Bound<'py, PyBytes>)> {
+        let keys = sort_keys(&self.inner.get_index_reader(), &self.inner.ids(), attr);
+        let items = self.inner.items.read().unwrap();
+        keys.iter().filter_map(|(id, key)| Some((items[*id as usize].get_py_ref(py)?, PyBytes::new(py, key)))).collect()
+    }
+
+    /// computes numeric aggregates of attr from the numeric index without reading the objects, keyed by func in the order given
+    /// funcs are sum, count, min, max, mean (or avg), median and percentiles like p95 or p99.9, interpolated like numpy's default
+    /// objects whose attr is not a number are left out, count is an int and the rest are None when no object has a number there
+    /// raises ValueError for an unknown func
+    pub fn aggregate<'py>(&self, py: Python<'py>, attr: &str, funcs: Vec<String>) -> PyResult<Bound<'py, PyDict>> {
+        aggregate_dict(py, &self.inner.get_index_reader(), &self.inner.ids(), attr, &funcs)
+    }
+
+    /// returns the objects sorted by attr, read in key order from the numeric and string indexes so only
+    /// the first limit are visited when limit is given. numbers sort first, then encoded values such as versions
+    /// a kind at a time, then strings, which sort by code point, descending reverses the order.
+    /// ties go by id and objects without a number, encoded value or string at attr come last
+    #[pyo3(signature = (attr, descending=false, limit=None))]
+    pub fn order_by(&self, py: Python, attr: &str, descending: bool, limit: Option<usize>) -> Vec<Py<Indexable>> {
+        let ids = order_by(&self.inner.get_index_reader(), &self.inner.ids(), attr, descending, limit);
+        let items = self.inner.items.read().unwrap();
+        ids.iter().filter_map(|id| items[*id as usize].get_py_ref(py)).collect()
+    }
+
+    /// returns up to k objects whose numeric attr is closest to target, nearest first
+    /// found by walking the numeric index outward from target's position, on equal distance the lower value comes first
+    #[pyo3(signature = (attr, target, k=5))]
+    pub fn nearest(&self, py: Python, attr: &str, target: f64, k: usize) -> Vec<Py<Indexable>> {
+        let ids = nearest(&self.inner.get_index_reader(), &self.inner.ids(), attr, target, k);
+        let items = self.inner.items.read().unwrap();
+        ids.iter().filter_map(|id| items[*id as usize].get_py_ref(py)).collect()
+    }
+
+    /// returns the objects reachable from start_objs by following the Indexables held in the via attributes,
+    /// directly or inside lists, tuples and sets, for at most depth hops (until nothing new is reached without one)
+    /// only objects in the index (and matching filter when given) are returned and followed further,
+    /// start_objs are only returned when reached again e.g. traverse([alice], via=["manager"]) is alice's chain of managers
+    /// references are read from the stored values, so graphs with cycles can use limit_nested(attr, depth=0) on the via attributes
+    #[pyo3(signature = (start_objs, via, depth=None, filter=None))]
+    pub fn traverse(&self, py: Python, start_objs: Vec<PyRef<Indexable>>, via: &Bound<'_, PyAny>, depth: Option<u32>, filter: Option<PyQueryExpr>) -> PyResult<FilteredIndex> {
+        let via = via_attrs(via)?;
+        let scope = match filter {
+            Some(filter) => self.reduced_query(py, filter)?.allowed_items,
+            None => self.inner.ids(),
+        };
+        let start: Vec<&Indexable> = start_objs.iter().map(|obj| &**obj).collect();
+        let ids = traverse(&self.inner.items.read().unwrap(), &scope, &start, &via, depth);
+        Ok(self.inner.filter_from_bitmap(ids))
+    }
+
+    /// whether a and b are linked through at most max_depth references held in the via attributes (no limit with None),
+    /// references count in both directions and only objects in the index are passed through
+    #[pyo3(signature = (a, b, via, max_depth=Some(6)))]
+    pub fn connected(&self, a: PyRef<Indexable>, b: PyRef<Indexable>, via: &Bound<'_, PyAny>, max_depth: Option<u32>) -> PyResult<bool> {
+        let via = via_attrs(via)?;
+        Ok(connected(&self.inner.items.read().unwrap(), &self.inner.ids(), a.id, b.id, &via, max_depth))
+    }
+
+    /// returns obj and every object in the index linked to it through references held in the via attributes,
+    /// followed in both directions, empty when obj is not in the index
+    pub fn component_of(&self, obj: PyRef<Indexable>, via: &Bound<'_, PyAny>) -> PyResult<FilteredIndex> {
+        let via = via_attrs(via)?;
+        let ids = component_of(&self.inner.items.read().unwrap(), &self.inner.ids(), obj.id, &via);
+        Ok(self.inner.filter_from_bitmap(ids))
+    }
+
+    /// returns a FilteredIndex of the objects whose numeric attr lies outside the bounds of method, taken over
+    /// the objects matching filter (all of them by default). iqr bounds are threshold (1.5) interquartile ranges
+    /// beyond the quartiles, read from the numeric index by rank, zscore bounds threshold (3.0) standard deviations
+    /// from the mean. both take the same numbers aggregate does
+    #[pyo3(signature = (attr, method="iqr", threshold=None, filter=None))]
+    pub fn outliers(&self, py: Python, attr: &str, method: &str, threshold: Option<f64>, filter: Option<PyQueryExpr>) -> PyResult<FilteredIndex> {
+        let method = OutlierMethod::parse(method)?;
+        let scope = match filter {
+            Some(filter) => self.reduced_query(py, filter)?.allowed_items,
+            None => self.inner.ids(),
+        };
+        let threshold = threshold.unwrap_or(method.default_threshold());
+        let ids = outliers(&self.inner.get_index_reader(), &scope, attr, method, threshold);
+        Ok(self.inner.filter_from_bitmap(ids))
+    }
+
+    /// returns the sequence number the next object added will get. every add numbers each object new to the index
+    /// with the next number of a counter that never goes back, re-adding an object already held keeps its number
+    /// objects are only numbered by an index created with IndexConfig().sequence() or order="insertion",
+    /// sequence, since and latest raise ValueError on any other
+    pub fn sequence(&self) -> PyResult<u64> {
+        Ok(self.inner.read_sequence()?.next())
+    }
+
+    /// returns the objects added at or after seq (as given by sequence()), only those matching filter when given
+    #[pyo3(signature = (seq, filter=None))]
+    pub fn since(&self, py: Python, seq: u64, filter: Option<PyQueryExpr>) -> PyResult<FilteredIndex> {
+        let scope = match filter {
+            Some(filter) => self.reduced_query(py, filter)?.allowed_items,
+            None => self.inner.ids(),
+        };
+        let ids = self.inner.read_sequence()?.since(seq, &scope);
+        Ok(self.inner.filter_from_bitmap(ids))
+    }
+
+    /// returns up to n of the most recently added objects, newest first, only those matching filter when given
+    #[pyo3(signature = (n, filter=None))]
+    pub fn latest(&self, py: Python, n: usize, filter: Option<PyQueryExpr>) -> PyResult<Vec<Py<Indexable>>> {
+        let scope = match filter {
+            Some(filter) => self.reduced_query(py, filter)?.allowed_items,
+            None => self.inner.ids(),
+        };
+        let ids = self.inner.read_sequence()?.latest(n, &scope);
+        let items = self.inner.items.read().unwrap();
+        Ok(ids.iter().filter_map(|id| items[*id as usize].get_py_ref(py)).collect())
+    }
+
+    /// returns the ids of each group of objects sharing the values of every attribute in on
+    /// with keep, returns a FilteredIndex of the redundant objects instead, all but the lowest id ("first")
+    /// or the highest id ("last") of each group. objects missing an attribute or holding a list, set or
+    /// dict there are never duplicates
+    #[pyo3(signature = (on, keep=None))]
+    pub fn duplicates<'py>(&self, py: Python<'py>, on: &Bound<'py, PyAny>, keep: Option<&str>) -> PyResult<Bound<'py, PyAny>> {
+        let (attrs, _) = group_attrs(on, false)?;
+        let groups = duplicate_groups(py, &self.inner.index, &self.inner.items, &self.inner.ids(), &attrs)?;
+        match keep {
+            Some(keep) => self.inner.filter_from_bitmap(redundant_ids(&groups, keep)?).into_bound_py_any(py),
+            None => groups.into_iter().map(PyIdSet::from_bitmap).collect::<Vec<_>>().into_bound_py_any(py),
+        }
+    }
+
+    /// returns a FilteredIndex of n objects drawn at random without replacement, all of them when there are fewer
+    /// weights draws proportionally to a numeric attribute, objects without a positive number there are never drawn
+    /// stratify_by draws n objects for each value of the attribute, seed makes the draw repeatable
+    #[pyo3(signature = (n, weights=None, stratify_by=None, seed=None))]
+    pub fn sample(&self, py: Python, n: usize, weights: Option<&str>, stratify_by: Option<&str>, seed: Option<u64>) -> PyResult<FilteredIndex> {
+        let groups = strata_groups(py, &self.inner.index, &self.inner.items, &self.inner.ids(), stratify_by)?;
+        let ids = sample(&self.inner.items, &groups, n, weights, seed);
+        Ok(self.inner.filter_from_bitmap(ids))
+    }
+
+    /// crosstab of rows against cols as {row value: {col value: cell}}, combinations without objects are left out
+    /// a cell holds the number of objects, or with metric as (attr, func) the aggregate of attr over them
+    /// like the group_agg metrics
+    #[pyo3(signature = (rows, cols, metric=None))]
+    pub fn pivot<'py>(&self, py: Python<'py>, rows: &str, cols: &str, metric: Option<(String, String)>) -> PyResult<Bound<'py, PyDict>> {
+        let metric = metric.map(|(attr, func)| Metric::new(&attr, &func)).transpose()?
+            .map(|metric| metric.declared(&|attr| self.inner.declared_fixed_point(attr)));
+        pivot(py, &self.inner.index, &self.inner.items, &self.inner.ids(), rows, cols, metric.as_ref())
+    }
+
+    /// one row per group of by, sorted by key, holding the group's values, its count and a {attr}_{func} column
+    /// for each metric (sum, count, min, max, avg or mean over the attr's numbers, None when a group has none)
+    /// having filters the rows with comparisons, bt, in, and, or and not over count and the metric columns
+    #[pyo3(signature = (by, metrics=None, having=None))]
+    pub fn group_agg<'py>(
+        &self,
+        py: Python<'py>,
+        by: &Bound<'py, PyAny>,
+        metrics: Option<&Bound<'py, PyDict>>,
+        having: Option<PyQueryExpr>,
+    ) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        let (names, _) = group_names(by)?;
+        let metrics = parse_metrics(metrics, &|attr| self.inner.declared_fixed_point(attr))?;
+        self.inner.group_agg(py, &names, &metrics, having.as_ref().map(|having| &having.inner))
+    }
+
+    /// groups the objects by the value of one attribute, or of each attribute in a list, into FilteredIndexes
+    /// a single attribute is keyed by its value, a list gives one nested dict level per attribute
+    /// or, with flat, a dict keyed by tuples of values. objects missing an attribute or holding
+    /// a list, set or dict there are left out. dotted attributes like "owner.region" group by a nested object's value.
+    /// groups are sorted by key, numbers before strings, values python considers equal (1, 1.0, True) sharing one
+    #[pyo3(signature = (by, flat=false))]
+    pub fn group_by<'py>(&self, py: Python<'py>, by: &Bound<'py, PyAny>, flat: bool) -> PyResult<Bound<'py, PyDict>> {
+        let (attrs, keys) = group_attrs(by, flat)?;
+        self.inner.group_by(py, &attrs, keys)
+    }
+
+    /// like group_by, with the number of objects in each group in place of the FilteredIndex
+    #[pyo3(signature = (by, flat=false))]
+    pub fn group_by_count<'py>(&self, py: Python<'py>, by: &Bound<'py, PyAny>, flat: bool) -> PyResult<Bound<'py, PyDict>> {
+        let (attrs, keys) = group_attrs(by, flat)?;
+        self.inner.group_by_count(py, &attrs, keys)
+    }
+
+    /// describes every queryable attribute on the index, sorted by name
+    /// each entry has name, types (python types seen), count (objects holding the attribute),
+    /// structures (numeric, string, boolean, exact) and nested (whether nested objects are indexed under it)
+    pub fn attributes<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.inner.attributes(py).iter().map(|stats| stats.to_dict(py)).collect()
+    }
+
+    /// lists the attributes whose values changed on objects already in the index, the most changed first
+    /// each entry has name, updates (how many times a value was set) and last_modified (the number of the latest
+    /// change, counted across all attributes so a higher one changed more recently). adding objects is not counted
+    #[pyo3(signature = (limit=None))]
+    pub fn hot_attributes<'py>(&self, py: Python<'py>, limit: Option<usize>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        let hottest = self.inner.churn.hottest(limit);
+        hottest.iter().map(|(attr, churn)| churn.to_dict(py, *attr)).collect()
+    }
+
+    /// returns the index's counters: reads (queries run through reduced, reduced_query, reduced_many and
+    /// get_by_attribute, each query of a batch counted), writes (objects added, updated or removed, counted per
+    /// object and change), lock_contention (times a lock was found held and had to be waited on) and
+    /// slow_queries (entries in the slow query log)
+    pub fn metrics<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.inner.metrics.to_dict(py)
+    }
+
+    /// logs queries taking at least threshold_ms milliseconds, keeping the latest capacity of them,
+    /// see slow_queries. a threshold of None turns the log off, entries already logged are kept
+    #[pyo3(signature = (threshold_ms, capacity=DEFAULT_SLOW_LOG_CAPACITY))]
+    pub fn log_slow_queries(&self, threshold_ms: Option<f64>, capacity: usize) -> PyResult<()> {
+        let threshold = match threshold_ms {
+            Some(ms) if !ms.is_finite() || ms < 0.0 => {
+                return Err(PyValueError::new_err(format!("threshold_ms must be a non negative number, got {}", ms)));
+            },
+            Some(ms) => Some(Duration::from_secs_f64(ms / 1000.0)),
+            None => None,
+        };
+        self.inner.metrics.set_slow_log(threshold, capacity);
+        Ok(())
+    }
+
+    /// returns the logged slow queries, oldest first, each with query (its repr), time_ns, matched and
+    /// profile (an explain trace of running it again, None for a reduced_many batch, logged as one entry)
+    /// clear empties the log after reading it
+    #[pyo3(signature = (clear=false))]
+    pub fn slow_queries<'py>(&self, py: Python<'py>, clear: bool) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.inner.metrics.slow_queries(py, clear)
+    }
+
+    /// maps every attribute held by an object in the index to the fraction of objects holding it, sorted by name
+    /// attributes excluded from indexing are reported too, an empty index gives an empty dict
+    pub fn sparsity(&self, py: Python) -> BTreeMap<String, f64> {
+        py.allow_threads(|| self.inner.sparsity())
+    }
+
+    /// returns a json-schema like description of the indexed objects
+    /// properties carry the json type(s) seen, nested objects and list elements are described recursively,
+    /// "null" is added to the type when None was seen and only attributes on every object are required
+    /// string attributes with at most categorical_limit repeated values are reported with an enum
+    #[pyo3(signature = (categorical_limit=20))]
+    pub fn infer_schema<'py>(&self, py: Python<'py>, categorical_limit: usize) -> PyResult<Bound<'py, PyDict>> {
+        self.inner.infer_schema(py, categorical_limit)
+    }
+
+    /// limits how much of the object graph under attr is indexed
+    /// depth is the number of nested levels indexed below attr (0 indexes none of attr's fields)
+    /// only is a projection of attr's fields to index, dotted paths reach further down e.g. ["id", "address.city"]
+    /// must be called before any objects are added
+    #[pyo3(signature = (attr, depth=None, only=None))]
+    pub fn limit_nested(&self, attr: &str, depth: Option<u32>, only: Option<Vec<String>>) -> PyResult<()> {
+        self.inner.limit_nested(attr, AttrDecls::new(depth, only))
+    }
+
+    /// also indexes strings held by attr (dotted paths allowed) that parse as numbers, e.g. "42" or "1.5e3",
+    /// so numeric comparisons and ranges match them, the stored value stays the original string
+    /// must be called before any objects are added
+    pub fn coerce_numeric(&self, attr: &str) -> PyResult<()> {
+        self.inner.coerce_numeric(attr)
+    }
+
+    /// orders strings held by attr (dotted paths allowed) that parse as semantic versions, e.g. "1.10.0" or "v2.0.0-rc.1",
+    /// so gt, ge, lt, le and bt take version strings and compare by major, minor, patch then prerelease
+    /// major, minor and patch must be below 16384, prereleases are ordered by the first three letters of their tag then its first number
+    /// packaging.version.Version values are ordered on the same scale when packaging is installed, through an encoder
+    /// registered for the whole process like register_encoder, unregister_encoder(Version) removes it
+    /// eq keeps comparing the strings, must be called before any objects are added
+    pub fn semver(&self, py: Python, attr: &str) -> PyResult<()> {
+        self.inner.semver(py, attr)
+    }
+
+    /// indexes (start, end) pairs held by attr (dotted paths allowed) as closed intervals,
+    /// so overlaps and contains_point can match them, pairs are two item lists or tuples of numbers with start <= end
+    /// must be called before any objects are added
+    pub fn interval(&self, attr: &str) -> PyResult<()> {
+        self.inner.interval(attr)
+    }
+
+    /// indexes ints held by attr (dotted paths allowed) bit by bit, so has_flag, flags_all and flags_any
+    /// are a few bitmap ANDs / ORs instead of a scan over every number held by attr
+    /// must be called before any objects are added
+    pub fn flags(&self, attr: &str) -> PyResult<()> {
+        self.inner.flags(attr)
+    }
+
+    /// makes eq, ne and in on numbers match values held by attr (dotted paths allowed) within tolerance of them,
+    /// e.g. after float_tolerance("temp", 0.05) eq("temp", 21.5) is bt("temp", 21.45, 21.55), also through FilteredIndex
+    /// only read at query time, so it can be set or changed at any point, 0 goes back to exact matching
+    /// raises ValueError on a negative or non finite tolerance
+    pub fn float_tolerance(&self, attr: &str, tolerance: f64) -> PyResult<()> {
+        self.inner.float_tolerance(attr, tolerance)
+    }
+
+    /// declares the numbers held by attr (dotted paths allowed) to be in unit, so comparisons on it also take quantities,
+    /// e.g. after units("latency", "ms") gt("latency", "2s") is gt("latency", 2000), plain numbers are taken as already in unit
+    /// units are ns, us, ms, s, min, h, d (time), B, KB, MB, GB, TB, KiB, MiB, GiB, TiB (data size), mm, cm, m, km (length)
+    /// and mg, g, kg (mass). only read at query time, stored values are not converted
+    /// raises ValueError for an unknown unit, and when queried with a quantity of another dimension or a string that is not one
+    pub fn units(&self, attr: &str, unit: &str) -> PyResult<()> {
+        self.inner.units(attr, unit)
+    }
+
+    /// stores the numbers held by attr (dotted paths allowed) as whole counts of 10^-places, places being 2 or 4,
+    /// so comparisons on it are exact and sum / avg in group_agg, pivot, agg and alert_when add without float error.
+    /// floats are rounded to places when stored and when given in a query. must be declared before objects are added
+    #[pyo3(signature = (attr, places=2))]
+    pub fn fixed_point(&self, attr: &str, places: u8) -> PyResult<()> {
+        self.inner.fixed_point(attr, places)
+    }
+
+    /// assigns values[i] to attr of the object with id ids[i] in one pass, ids as given by ids()
+    /// any sequences work, numpy arrays included, and alerts run once after every value is in place
+    /// every id and value is checked first, nothing is assigned if one is not in the index or refused by a strict index
+    pub fn set_column<'py>(&self, py: Python<'py>, attr: &str, ids: Vec<u32>, values: Vec<Bound<'py, PyAny>>) -> PyResult<()> {
+        if ids.len() != values.len() {
+            return Err(PyValueError::new_err(format!("{} ids but {} values", ids.len(), values.len())));
+        }
+        let _mutating = self.inner.check_mutable()?;
+        self.inner.reap_if_auto(py);
+        let name_id = INTERNER.intern(attr);
+        // handles taken under the lock, the values converted once it is released as that may call into python
+        let objs: Vec<Py<Indexable>> = {
+            let items = self.inner.items.read().unwrap();
+            ids.iter().map(|id| {
+                self.inner.contains_id(*id)
+                    .then(|| items[*id as usize].get_py_ref(py))
+                    .flatten()
+                    .ok_or_else(|| PyValueError::new_err(format!("id {} is not in the index", id)))
+            }).collect::<PyResult<_>>()?
+        };
+        // every value checked before anything is assigned, other indexes holding the objects are kept from freezing until it is
+        let mut others_mutating = Vec::new();
+        let targets: Vec<(Py<Indexable>, PyValue)> = objs.into_iter().zip(values).map(|(obj, value)| {
+            let value = class_value(obj.bind(py).get_type_ptr() as usize, name_id, value)?;
+            others_mutating.extend(obj.borrow(py).check_strict(name_id, &value)?);
+            Ok((obj, value))
+        }).collect::<PyResult<_>>()?;
+        // held back like bulk_update, so this index's maps take the whole column in one pass
+        self.inner.open_deferred();
+        for (obj, value) in targets.iter() {
+            obj.borrow(py).store_value(py, name_id, value.clone());
+        }
+        let weak_index = Arc::downgrade(&self.inner);
+        py.allow_threads(|| self.inner.close_deferred(weak_index));
+        // an index drains its alerts on the first call reaching it, later ones find nothing to do
+        for (obj, _) in targets.iter() {
+            obj.borrow(py).fire_alerts(py)?;
+        }
+        Ok(())
+    }
+
+    /// returns a context manager holding back index maintenance for assignments to this index's objects until
+    /// it exits, then updating each object assigned to once, from its value before the block to the one it holds.
+    /// for many assignments, or several to the same attribute, in a row. queries in the block see the values
+    /// from before it for the attributes assigned, alerts run on exit. blocks may be nested, the outermost applies
+    /// the changes. the index is brought up to date even when the block raises
+    pub fn bulk_update(&self) -> PyResult<BulkUpdate> {
+        let _mutating = self.inner.check_mutable()?;
+        Ok(BulkUpdate::new(self.inner.clone()))
+    }
+
+    /// sets every attribute given on each of objs, like Indexable.update on each but with alerts run once at the end
+    /// every value is checked for every object first, nothing is set if one is refused (declared field types, strict indexes)
+    #[pyo3(signature = (objs, **kwargs))]
+    pub fn update_many<'py>(&self, py: Python<'py>, objs: Vec<Bound<'py, Indexable>>, kwargs: Option<&Bound<'py, PyDict>>) -> PyResult<()> {
+        let Some(kwargs) = kwargs else {
+            return Ok(());
+        };
+        let _mutating = self.inner.check_mutable()?;
+        self.inner.reap_if_auto(py);
+        // values read once per class, an object's own declarations decide how they are stored
+        let mut by_class: FxHashMap<usize, Vec<(StrId, PyValue)>> = FxHashMap::default();
+        let mut others_mutating = Vec::new();
+        for obj in objs.iter() {
+            let type_ptr = obj.get_type_ptr() as usize;
+            if let Entry::Vacant(entry) = by_class.entry(type_ptr) {
+                entry.insert(Indexable::class_values(type_ptr, kwargs)?);
+            }
+            let obj = obj.borrow();
+            for (name_id, value) in by_class[&type_ptr].iter() {
+                others_mutating.extend(obj.check_strict(*name_id, value)?);
+            }
+        }
+        for obj in objs.iter() {
+            obj.borrow().store_values(py, by_class[&(obj.get_type_ptr() as usize)].clone());
+        }
+        for obj in objs.iter() {
+            obj.borrow().fire_alerts(py)?;
+        }
+        Ok(())
+    }
+
+    /// keeps the k objects with the largest numeric attr up to date as objects are added, updated and removed,
+    /// so top_k can return them without querying, ties on value are broken by object id
+    /// calling it again for attr replaces k
+    #[pyo3(signature = (attr, k=100))]
+    pub fn track_top_k(&self, attr: &str, k: usize) -> PyResult<()> {
+        self.inner.track_top_k(attr, k)
+    }
+
+    /// returns the objects tracked for attr by track_top_k, largest first
+    /// raises ValueError when attr is not tracked
+    pub fn top_k(&self, py: Python, attr: &str) -> PyResult<Vec<Py<Indexable>>> {
+        let ids = self.inner.top_k(attr)?;
+        let items = self.inner.items.read().unwrap();
+        Ok(ids.iter().filter_map(|id| items[*id as usize].get_py_ref(py)).collect())
+    }
+
+    /// calls callback with the aggregate when a mutation (add, update, reduce, adopt, union) brings it across the threshold
+    /// metric is (func, attr) with func one of sum, count, min, max, avg over the objects matching filter,
+    /// op is one of == != > >= < <= comparing the aggregate to value, e.g. alert_when(("sum", "exposure"), ">", 1e6, callback=notify)
+    /// the aggregate is kept up to date from the changed objects only, it fires again only after falling back out of the threshold
+    /// a callback raising is reported as a RuntimeWarning, the mutation that fired it still goes through
+    #[pyo3(signature = (metric, op, value, filter=None, *, callback))]
+    pub fn alert_when(&self, metric: (String, String), op: &str, value: f64, filter: Option<PyQueryExpr>, callback: Py<PyAny>) -> PyResult<()> {
+        let filter = match filter {
+            Some(mut filter) => {
+                self.inner.apply_declarations(&mut filter.inner)?;
+                filter.inner.check_types()?;
+                Some(filter.inner.normalized())
+            },
+            None => None,
+        };
+        let (func, attr) = metric;
+        let places = self.inner.declared_fixed_point(&attr);
+        self.inner.alert_when(Alert::new(&func, &attr, parse_op(op)?, value, filter, callback)?.with_places(places));
+        Ok(())
+    }
+
+    /// adds every object held by another index (optionally only those matching query) to this index
+    /// the objects are shared, so later attribute updates keep both indexes consistent
+    #[pyo3(signature = (other, query=None))]
+    pub fn adopt(&self, py: Python, other: &Index, query: Option<PyQueryExpr>) -> PyResult<()> {
+        let _mutating = self.inner.check_mutable()?;
+        self.inner.reap_if_auto(py);
+        let adopted = py.allow_threads(|| {
+            let weak_index = Arc::downgrade(&self.inner);
+            self.inner.adopt(weak_index, &other.inner, query.as_ref().map(|q| &q.inner))
+        })?;
+        if self.inner.config.auto_remove_on_gc || other.inner.config.auto_remove_on_gc {
+            self.inner.rehold(py, &adopted)?;
+        }
+        self.inner.fire_alerts(py)
+    }
+
+    /// prepares the structures behind attrs, every attribute when None, so the first queries after a load run as fast as later ones
+    /// pending ids are flushed, bitmaps run compressed and shrunk, hash shards rehashed, nested indexes under attrs included
+    /// queries return the same results before and after, raises ValueError if an attribute is not indexed
+    #[pyo3(signature = (attrs=None))]
+    pub fn warmup(&self, py: Python, attrs: Option<Vec<String>>) -> PyResult<()> {
+        py.allow_threads(|| self.inner.warmup(attrs.as_deref()))
+    }
+
+    /// makes the index read-only, for serving queries after a load phase
+    /// adding, removing (reduce, reap) or updating objects held by it then raises ValueError, on nested objects too:
+    /// attribute assignment, Indexable.update, refresh, set_column, update_many, bulk_update, add_dicts, add_arrow and ingest streams
+    /// queries and views keep working, a frozen index cannot be unfrozen
+    /// waits for changes already under way and applies those an open bulk_update holds back, then optimizes the maps
+    /// like warmup and takes them out of their locks, so queries from many threads read them without waiting on each other.
+    /// raises ValueError when called by code changing the index, such as an alert callback
+    pub fn freeze_mutations(&self, py: Python) -> PyResult<()> {
+        let weak_self = Arc::downgrade(&self.inner);
+        py.allow_threads(|| self.inner.freeze(weak_self))
+    }
+
+    /// whether freeze_mutations has been called
+    pub fn is_frozen(&self) -> bool {
+        self.inner.is_frozen()
+    }
+
+    /// removes objects nothing outside the index refers to any more, letting python free them, returns how many were removed
+    /// the index itself keeps every object it holds alive, so they would otherwise stay until removed by reduce.
+    /// scans every object, an index made with auto_remove_on_gc only has to drop those python already freed
+    /// raises ValueError on a frozen index
+    pub fn reap(&self, py: Python) -> PyResult<u64> {
+        let _mutating = self.inner.check_mutable()?;
+        let reaped = self.inner.reap(py);
+        self.inner.fire_alerts(py)?;
+        Ok(reaped)
+    }
+
+    /// keeps other a warm standby of this index: it gets a copy of every object held here, then every add, update and
+    /// removal made here is applied to it as well, before the call making it returns. copies hold the same values as the
+    /// objects they copy but are separate objects, so queries on other never wait on writes here
+    /// other stays writable, changes made to it are not sent back. replication ends when other is dropped
+    /// a change other refuses (frozen, strict) is raised by the call here that made it, changes made while this runs may be left out
+    /// raises ValueError if other is this index, already a standby of it, or replicates back to it
+    /// for a standby in another process, keep a log with enable_wal and read it there with Index.load
+    pub fn replicate_to(&self, py: Python, other: &Index) -> PyResult<()> {
+        self.inner.replicate_to(py, &other.inner)
+    }
+
+    /// returns a new Index that is the union of this index and another index
+    /// does not mutate the other index
+    /// safe to run from several threads at once, in either direction, objects added to other while it runs are left out
     pub fn union_with(&self, py: Python, other: &Index) -> PyResult<()>{
+        let _mutating = self.inner.check_mutable()?;
+        self.inner.reap_if_auto(py);
+        let copied = py.allow_threads(|| {
+            self.inner.union_with(Arc::downgrade(&self.inner), &other.inner)
+        })?;
+        if self.inner.config.auto_remove_on_gc || other.inner.config.auto_remove_on_gc {
+            self.inner.rehold(py, &copied)?;
+        }
+        self.inner.fire_alerts(py)
+    }
+
+}
+impl Index {
+    // adds objects just built by one of the constructors, which are all Indexable
+    pub(crate) fn add_built(&self, py: Python, objs: &[Bound<'_, PyAny>]) -> PyResult<()> {
+        let refs = objs.iter()
+            .map(|obj| Ok(obj.downcast::<Indexable>()?.borrow()))
+            .collect::<PyResult<Vec<PyRef<Indexable>>>>()?;
+        self.add_object_many(py, refs)
+    }
+
+    // adds the next rows of decoded columns as objects of cls, like add_built but each column is
+    // indexed in one pass rather than each object on its own
+    fn add_rows(
+        &self,
+        py: Python,
+        cls: &Bound<'_, PyType>,
+        attrs: &[StrId],
+        columns: &mut [std::vec::IntoIter<Option<RustCastValue>>],
+        rows: usize,
+    ) -> PyResult<()> {
+        let columns: Vec<Vec<Option<PyValue>>> = columns.iter_mut()
+            .map(|column| column.by_ref().take(rows).map(|value| value.map(PyValue::from_primitave)).collect())
+            .collect();
+        let objs = Indexable::construct_values(cls, attrs, &columns, rows)?;
+        let refs = objs.iter()
+            .map(|obj| Ok(obj.downcast::<Indexable>()?.borrow()))
+            .collect::<PyResult<Vec<PyRef<Indexable>>>>()?;
+
+        self.inner.reap_if_auto(py);
+        for obj in refs.iter() {
+            self.inner.check_strict_object(obj)?;
+        }
+        let stored_items: Vec<StoredItem> = refs.into_iter().map(|obj| {
+            let rust_handle = Arc::new(Indexable::from_py_ref(&obj, py));
+            self.inner.hold(py, obj.into_pyobject(py)?.unbind(), rust_handle)
+        }).collect::<PyResult<_>>()?;
+
         py.allow_threads(|| {
-            self.inner.union_with(&other.inner)
-        })
+            self.inner.add_columns(Arc::downgrade(&self.inner), stored_items, attrs, &columns);
+        });
+        self.inner.fire_alerts(py)
+    }
+}
+
+fn indexable_class<'py>(py: Python<'py>, cls: Option<Bound<'py, PyType>>) -> PyResult<Bound<'py, PyType>> {
+    match cls {
+        Some(cls) if !cls.is_subclass_of::<Indexable>()? => {
+            Err(PyTypeError::new_err(format!("cls must be an Indexable subclass, got {}", cls.name()?)))
+        },
+        Some(cls) => Ok(cls),
+        None => Ok(py.get_type::<Indexable>()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{mpsc, Barrier};
+    use std::{process, thread};
+
+    const WRITERS: usize = 4;
+    const PER_WRITER: usize = 100;
+    const ROUNDS: usize = 200;
+
+    type Task<'a> = Box<dyn Fn() + Send + Sync + 'a>;
+
+    fn new_index(py: Python) -> Index {
+        Index::new(py, None, None, None, None, None, None).unwrap()
+    }
+
+    fn new_obj(py: Python, values: &[(&str, usize)]) -> Py<Indexable> {
+        let obj = Bound::new(py, Indexable::default()).unwrap();
+        for (attr, value) in values {
+            obj.setattr(*attr, *value).unwrap();
+        }
+        obj.unbind()
+    }
+
+    fn get(py: Python, obj: &Py<Indexable>, attr: &str) -> usize {
+        obj.bind(py).getattr(attr).unwrap().extract().unwrap()
+    }
+
+    fn value(py: Python, value: usize) -> PyValue {
+        PyValue::new(value.into_bound_py_any(py).unwrap())
+    }
+
+    fn query(py: Python, index: &Index, inner: QueryExpr) -> Vec<Py<Indexable>> {
+        let filtered = index.reduced_query(py, PyQueryExpr { inner }).unwrap();
+        filtered.get_from_indexes(py, &filtered.allowed_items).unwrap()
     }
 
-}
\ No newline at end of file
+    fn ptrs(objs: &[Py<Indexable>]) -> HashSet<usize> {
+        objs.iter().map(|obj| obj.as_ptr() as usize).collect()
+    }
+
+    // writers run once and readers repeat until every writer is done. tasks take the GIL a step at a time,
+    // rust holding it never yields to a waiting thread. a phase still running after a minute has deadlocked
+    // and aborts the run instead of hanging it
+    fn run(writers: Vec<Task>, readers: Vec<Task>) {
+        let writing = AtomicUsize::new(writers.len());
+        let (done, finished) = mpsc::channel::<()>();
+        thread::scope(|scope| {
+            scope.spawn(move || {
+                if finished.recv_timeout(Duration::from_secs(60)).is_err() {
+                    eprintln!("concurrent phase deadlocked");
+                    process::abort();
+                }
+            });
+            let writing = &writing;
+            let handles: Vec<_> = writers.iter().map(|task| scope.spawn(move || {
+                let result = panic::catch_unwind(AssertUnwindSafe(task));
+                writing.fetch_sub(1, Ordering::AcqRel);
+                if let Err(err) = result {
+                    panic::resume_unwind(err);
+                }
+            })).chain(readers.iter().map(|task| scope.spawn(move || {
+                task();
+                while writing.load(Ordering::Acquire) > 0 {
+                    task();
+                }
+            }))).collect();
+            let results: Vec<_> = handles.into_iter().map(|handle| handle.join()).collect();
+            done.send(()).unwrap();
+            for result in results {
+                if let Err(err) = result {
+                    panic::resume_unwind(err);
+                }
+            }
+        });
+    }
+
+    fn check(py: Python, index: &Index) {
+        let objs = index.inner.collect(py).unwrap();
+        assert_eq!(ptrs(&objs).len(), objs.len());
+        assert_eq!(index.inner.ids().cardinality() as usize, objs.len());
+        for obj in &objs {
+            let found = query(py, index, QueryExpr::Eq("num".into(), value(py, get(py, obj, "num"))));
+            assert!(ptrs(&found).contains(&(obj.as_ptr() as usize)));
+        }
+        for t in [0, 25, 50] {
+            let expected: Vec<Py<Indexable>> = objs.iter().filter(|obj| get(py, obj, "score") >= t).map(|obj| obj.clone_ref(py)).collect();
+            assert_eq!(ptrs(&query(py, index, QueryExpr::Ge("score".into(), value(py, t)))), ptrs(&expected));
+        }
+    }
+
+    // several threads drive the index at once, each phase followed by a check that every query
+    // agrees with the values the objects hold
+    #[test]
+    fn concurrent_mutation() {
+        let (index, target, batches) = Python::with_gil(|py| {
+            let batches: Vec<Vec<Py<Indexable>>> = (0..WRITERS).map(|w| {
+                (0..PER_WRITER).map(|i| new_obj(py, &[("num", w * PER_WRITER + i), ("score", 0), ("writer", w)])).collect()
+            }).collect();
+            (new_index(py), new_index(py), batches)
+        });
+        let (index, target) = (&index, &target);
+
+        // adds in batches while readers query
+        let adds: Vec<Task> = batches.iter().map(|batch| Box::new(move || {
+            for chunk in batch.chunks(10) {
+                Python::with_gil(|py| index.add_object_many(py, chunk.iter().map(|obj| obj.borrow(py)).collect()).unwrap());
+            }
+        }) as Task).collect();
+        let read: Task = Box::new(|| Python::with_gil(|py| {
+            for obj in query(py, index, QueryExpr::Eq("score".into(), value(py, 0))) {
+                assert_eq!(get(py, &obj, "score"), 0);
+            }
+        }));
+        run(adds, vec![read]);
+        Python::with_gil(|py| {
+            assert_eq!(index.inner.ids().cardinality() as usize, WRITERS * PER_WRITER);
+            check(py, index);
+        });
+
+        // scores only ever rise, so anything a query returns still matches it afterwards.
+        // each object's owner is replaced along with its score, dropping the old one from the nested index
+        let raise_scores: Vec<Task> = batches.iter().map(|batch| Box::new(move || {
+            for score in (1..60).step_by(7) {
+                for obj in batch {
+                    Python::with_gil(|py| {
+                        let obj = obj.bind(py);
+                        obj.setattr("score", score).unwrap();
+                        obj.setattr("owner", new_obj(py, &[("num", score)])).unwrap();
+                    });
+                }
+            }
+        }) as Task).collect();
+        let read_rising = |t: usize| -> Task {
+            Box::new(move || Python::with_gil(|py| {
+                for obj in query(py, index, QueryExpr::Ge("score".into(), value(py, t))) {
+                    assert!(get(py, &obj, "score") >= t);
+                }
+            }))
+        };
+        run(raise_scores, vec![read_rising(10), read_rising(40)]);
+        Python::with_gil(|py| {
+            check(py, index);
+            assert_eq!(query(py, index, QueryExpr::Eq("owner.num".into(), value(py, 57))).len(), WRITERS * PER_WRITER);
+            assert!(query(py, index, QueryExpr::Eq("owner.num".into(), value(py, 50))).is_empty());
+        });
+
+        // removal through reduce while others query and union the index into another
+        let reduce: Task = Box::new(|| {
+            for _ in 0..3 {
+                Python::with_gil(|py| {
+                    let kwargs = FxHashMap::from_iter([("writer".to_string(), 0.into_bound_py_any(py).unwrap())]);
+                    index.reduce(py, Some(kwargs)).unwrap();
+                });
+            }
+        });
+        let union: Task = Box::new(|| {
+            for _ in 0..10 {
+                Python::with_gil(|py| target.union_with(py, index).unwrap());
+            }
+        });
+        let read_kept: Task = Box::new(|| Python::with_gil(|py| {
+            for obj in query(py, index, QueryExpr::Ge("score".into(), value(py, 0))) {
+                assert!(get(py, &obj, "writer") < WRITERS);
+            }
+        }));
+        run(vec![reduce, union], vec![read_kept]);
+        Python::with_gil(|py| {
+            let writers: HashSet<usize> = index.inner.collect(py).unwrap().iter().map(|obj| get(py, obj, "writer")).collect();
+            assert_eq!(writers, HashSet::from([0]));
+            check(py, index);
+            target.union_with(py, index).unwrap();
+            check(py, target);
+            assert!(ptrs(&index.inner.collect(py).unwrap()).is_subset(&ptrs(&target.inner.collect(py).unwrap())));
+        });
+
+        // each round one object gives up the last value of a fresh attribute, emptying its map, while the
+        // writers add objects holding it. none of those may be lost when the emptied map is reset
+        let attrs: Vec<String> = (0..ROUNDS).map(|round| format!("tag{}", round)).collect();
+        let holders: Vec<Py<Indexable>> = Python::with_gil(|py| {
+            let holders: Vec<Py<Indexable>> = attrs.iter().map(|attr| new_obj(py, &[(attr.as_str(), 1)])).collect();
+            index.add_object_many(py, holders.iter().map(|obj| obj.borrow(py)).collect()).unwrap();
+            holders
+        });
+        let (attrs, holders) = (&attrs, &holders);
+        let round = &Barrier::new(WRITERS + 1);
+        let give_up: Task = Box::new(move || {
+            for (attr, holder) in attrs.iter().zip(holders) {
+                round.wait();
+                Python::with_gil(|py| holder.bind(py).setattr(attr.as_str(), 0).unwrap());
+            }
+        });
+        let mut tasks: Vec<Task> = (0..WRITERS).map(|_| Box::new(move || {
+            for attr in attrs {
+                round.wait();
+                Python::with_gil(|py| index.add_object(py, new_obj(py, &[(attr.as_str(), 2)]).borrow(py)).unwrap());
+            }
+        }) as Task).collect();
+        tasks.push(give_up);
+        run(tasks, Vec::new());
+        Python::with_gil(|py| {
+            for attr in attrs {
+                assert_eq!(query(py, index, QueryExpr::Eq(attr.as_str().into(), value(py, 2))).len(), WRITERS, "{} lost an add", attr);
+                assert_eq!(query(py, index, QueryExpr::Eq(attr.as_str().into(), value(py, 0))).len(), 1);
+            }
+        });
+    }
+
+    // decoded arrow columns, added a chunk at a time as add_arrow does
+    #[test]
+    fn add_rows_indexes_columns() {
+        Python::with_gil(|py| {
+            let index = new_index(py);
+            let cls = py.get_type::<Indexable>();
+            let attrs: Vec<StrId> = ["sku", "qty", "active"].iter().map(|name| intern_for_class(cls.as_ptr() as usize, name)).collect();
+            let columns: Vec<Vec<Option<RustCastValue>>> = vec![
+                (0..6).map(|i| Some(RustCastValue::Str(format!("s{}", i).into()))).collect(),
+                vec![Some(RustCastValue::Int(0)), Some(RustCastValue::Int(1)), None, Some(RustCastValue::Int(3)), Some(RustCastValue::Int(4)), Some(RustCastValue::Int(5))],
+                (0..6).map(|i| Some(RustCastValue::Bool(i % 2 == 0))).collect(),
+            ];
+            let mut columns: Vec<_> = columns.into_iter().map(Vec::into_iter).collect();
+            index.add_rows(py, &cls, &attrs, &mut columns, 4).unwrap();
+            assert_eq!(index.inner.ids().cardinality(), 4);
+            index.add_rows(py, &cls, &attrs, &mut columns, 2).unwrap();
+
+            let skus = |objs: Vec<Py<Indexable>>| -> Vec<String> {
+                let mut skus: Vec<String> = objs.iter().map(|obj| obj.bind(py).getattr("sku").unwrap().extract().unwrap()).collect();
+                skus.sort();
+                skus
+            };
+            assert_eq!(skus(index.inner.collect(py).unwrap()).len(), 6);
+            assert_eq!(skus(query(py, &index, QueryExpr::Ge("qty".into(), value(py, 3)))), ["s3", "s4", "s5"]);
+            let active = PyValue::new(true.into_bound_py_any(py).unwrap());
+            assert_eq!(skus(query(py, &index, QueryExpr::Eq("active".into(), active))), ["s0", "s2", "s4"]);
+            // nulls leave the attribute unset
+            let unset = query(py, &index, QueryExpr::Eq("sku".into(), PyValue::new("s2".into_bound_py_any(py).unwrap())));
+            assert!(!unset[0].bind(py).hasattr("qty").unwrap());
+        });
+    }
+}
@@ -0,0 +1,292 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use pyo3::{Bound, PyResult, Python, exceptions::PyValueError, pyclass, pymethods, types::{PyAnyMethods, PyDict, PyDictMethods}};
+
+use crate::index::core::{config::{IndexConfig, ResultOrder}, index::IndexAPI, attr_decls::AttrDecls, units::Unit};
+use crate::index::value::HashMode;
+
+/// settings for a new Index, built up one call at a time and passed as Index(config=...)
+/// every method returns a new IndexConfig, e.g. IndexConfig().strict().order("insertion").semver("version")
+/// attribute declarations are applied when the index is created, as if called on it before adding objects
+/// to_dict / from_dict round trip it through plain python values. Index.save writes every setting but wal into the snapshot,
+/// declarations included, so Index.load rebuilds the index without one. object ids are 32 bit whatever the settings,
+/// an index holds at most 2**32 objects
+#[pyclass(frozen, name = "IndexConfig")]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PyIndexConfig {
+    config: IndexConfig,
+    opaque: Vec<String>,
+    coerce_numeric: Vec<String>,
+    semver: Vec<String>,
+    interval: Vec<String>,
+    flags: Vec<String>,
+    units: BTreeMap<String, String>,
+    fixed_point: BTreeMap<String, u8>,
+    float_tolerance: BTreeMap<String, f64>,
+    limit_nested: BTreeMap<String, (Option<u32>, Option<Vec<String>>)>,
+    wal: Option<PathBuf>,
+}
+
+const KEYS: [&str; 18] = [
+    "max_str_len", "strict", "auto_remove_on_gc", "order", "sequence", "hash_mode", "shards", "threads", "opaque",
+    "coerce_numeric", "semver", "interval", "flags", "units", "fixed_point", "float_tolerance", "limit_nested", "wal",
+];
+
+impl PyIndexConfig {
+    fn with(&self, f: impl FnOnce(&mut Self)) -> Self {
+        let mut next = self.clone();
+        f(&mut next);
+        next
+    }
+
+    fn push(list: &mut Vec<String>, attr: &str) {
+        if !list.iter().any(|held| held == attr) {
+            list.push(attr.to_string());
+        }
+    }
+
+    pub fn build(&self, py: Python) -> PyResult<IndexAPI> {
+        let mut decls = AttrDecls::default();
+        for attr in self.opaque.iter() {
+            decls.declare(attr, |decl| decl.opaque = true);
+        }
+        let index = IndexAPI::with_decls(None, decls).with_config(self.config.clone().into());
+        for (attr, (depth, only)) in self.limit_nested.iter() {
+            index.limit_nested(attr, AttrDecls::new(*depth, only.clone()))?;
+        }
+        for attr in self.coerce_numeric.iter() {
+            index.coerce_numeric(attr)?;
+        }
+        for attr in self.semver.iter() {
+            index.semver(py, attr)?;
+        }
+        for attr in self.interval.iter() {
+            index.interval(attr)?;
+        }
+        for attr in self.flags.iter() {
+            index.flags(attr)?;
+        }
+        for (attr, unit) in self.units.iter() {
+            index.units(attr, unit)?;
+        }
+        for (attr, places) in self.fixed_point.iter() {
+            index.fixed_point(attr, *places)?;
+        }
+        for (attr, tolerance) in self.float_tolerance.iter() {
+            index.float_tolerance(attr, *tolerance)?;
+        }
+        if let Some(path) = &self.wal {
+            index.enable_wal(path)?;
+        }
+        Ok(index)
+    }
+}
+
+#[pymethods]
+impl PyIndexConfig {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// see Index for what each setting does
+    fn max_str_len(&self, max_str_len: usize) -> Self {
+        self.with(|c| c.config.max_str_len = max_str_len)
+    }
+
+    #[pyo3(signature = (strict=true))]
+    fn strict(&self, strict: bool) -> Self {
+        self.with(|c| c.config.strict = strict)
+    }
+
+    #[pyo3(signature = (auto_remove_on_gc=true))]
+    fn auto_remove_on_gc(&self, auto_remove_on_gc: bool) -> Self {
+        self.with(|c| c.config.auto_remove_on_gc = auto_remove_on_gc)
+    }
+
+    /// raises ValueError for an order other than "id" or "insertion"
+    fn order(&self, order: &str) -> PyResult<Self> {
+        let order = ResultOrder::parse(order)?;
+        Ok(self.with(|c| c.config.order = order))
+    }
+
+    /// numbers objects as they are added, for Index.sequence / since / latest
+    #[pyo3(signature = (sequence=true))]
+    fn sequence(&self, sequence: bool) -> Self {
+        self.with(|c| c.config.sequence = sequence)
+    }
+
+    /// raises ValueError for a mode other than "identity" or "stable"
+    fn hash_mode(&self, mode: &str) -> PyResult<Self> {
+        let mode = HashMode::parse(mode)?;
+        Ok(self.with(|c| c.config.hash_mode = mode))
+    }
+
+    /// shards of each attribute's exact value map, assignments to values in different shards don't wait on each other
+    /// raises ValueError unless a power of two
+    fn shards(&self, shards: usize) -> PyResult<Self> {
+        let shards = IndexConfig::check_shards(shards)?;
+        Ok(self.with(|c| c.config.shards = shards))
+    }
+
+    /// worker threads for add_arrow and load, which fill the maps a column per thread, rayon's global pool when not set
+    /// raises ValueError for 0
+    fn threads(&self, threads: usize) -> PyResult<Self> {
+        let threads = IndexConfig::check_threads(threads)?;
+        Ok(self.with(|c| c.config.threads = Some(threads)))
+    }
+
+    /// keeps a write ahead log at path from the start, as Index.enable_wal(path) does
+    fn wal(&self, path: PathBuf) -> Self {
+        self.with(|c| c.wal = Some(path))
+    }
+
+    fn opaque(&self, attr: &str) -> Self {
+        self.with(|c| Self::push(&mut c.opaque, attr))
+    }
+
+    fn coerce_numeric(&self, attr: &str) -> Self {
+        self.with(|c| Self::push(&mut c.coerce_numeric, attr))
+    }
+
+    fn semver(&self, attr: &str) -> Self {
+        self.with(|c| Self::push(&mut c.semver, attr))
+    }
+
+    fn interval(&self, attr: &str) -> Self {
+        self.with(|c| Self::push(&mut c.interval, attr))
+    }
+
+    fn flags(&self, attr: &str) -> Self {
+        self.with(|c| Self::push(&mut c.flags, attr))
+    }
+
+    /// raises ValueError for an unknown unit
+    fn units(&self, attr: &str, unit: &str) -> PyResult<Self> {
+        Unit::parse(unit)?;
+        Ok(self.with(|c| { c.units.insert(attr.to_string(), unit.to_string()); }))
+    }
+
+    #[pyo3(signature = (attr, places=2))]
+    fn fixed_point(&self, attr: &str, places: u8) -> Self {
+        self.with(|c| { c.fixed_point.insert(attr.to_string(), places); })
+    }
+
+    fn float_tolerance(&self, attr: &str, tolerance: f64) -> Self {
+        self.with(|c| { c.float_tolerance.insert(attr.to_string(), tolerance); })
+    }
+
+    #[pyo3(signature = (attr, depth=None, only=None))]
+    fn limit_nested(&self, attr: &str, depth: Option<u32>, only: Option<Vec<String>>) -> Self {
+        self.with(|c| { c.limit_nested.insert(attr.to_string(), (depth, only)); })
+    }
+
+    /// returns the settings as a dict of plain python values, only those changed from the defaults
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        let defaults = IndexConfig::default();
+        if self.config.max_str_len != defaults.max_str_len {
+            dict.set_item("max_str_len", self.config.max_str_len)?;
+        }
+        if self.config.strict {
+            dict.set_item("strict", true)?;
+        }
+        if self.config.auto_remove_on_gc {
+            dict.set_item("auto_remove_on_gc", true)?;
+        }
+        if self.config.order != defaults.order {
+            dict.set_item("order", self.config.order.name())?;
+        }
+        if self.config.sequence {
+            dict.set_item("sequence", true)?;
+        }
+        if self.config.hash_mode != HashMode::default() {
+            dict.set_item("hash_mode", self.config.hash_mode.name())?;
+        }
+        if self.config.shards != defaults.shards {
+            dict.set_item("shards", self.config.shards)?;
+        }
+        if let Some(threads) = self.config.threads {
+            dict.set_item("threads", threads)?;
+        }
+        for (key, attrs) in [("opaque", &self.opaque), ("coerce_numeric", &self.coerce_numeric), ("semver", &self.semver), ("interval", &self.interval), ("flags", &self.flags)] {
+            if !attrs.is_empty() {
+                dict.set_item(key, attrs)?;
+            }
+        }
+        if !self.units.is_empty() {
+            dict.set_item("units", &self.units)?;
+        }
+        if !self.fixed_point.is_empty() {
+            dict.set_item("fixed_point", &self.fixed_point)?;
+        }
+        if !self.float_tolerance.is_empty() {
+            dict.set_item("float_tolerance", &self.float_tolerance)?;
+        }
+        if !self.limit_nested.is_empty() {
+            let nested = PyDict::new(py);
+            for (attr, (depth, only)) in self.limit_nested.iter() {
+                let limits = PyDict::new(py);
+                limits.set_item("depth", depth)?;
+                limits.set_item("only", only)?;
+                nested.set_item(attr, limits)?;
+            }
+            dict.set_item("limit_nested", nested)?;
+        }
+        if let Some(path) = &self.wal {
+            dict.set_item("wal", path.to_string_lossy())?;
+        }
+        Ok(dict)
+    }
+
+    /// builds an IndexConfig from a dict written by to_dict, raises ValueError for an unknown key or setting
+    #[staticmethod]
+    fn from_dict(data: &Bound<'_, PyDict>) -> PyResult<Self> {
+        let mut config = Self::default();
+        for (key, value) in data.iter() {
+            let key: String = key.extract()?;
+            match key.as_str() {
+                "max_str_len" => config.config.max_str_len = value.extract()?,
+                "strict" => config.config.strict = value.extract()?,
+                "auto_remove_on_gc" => config.config.auto_remove_on_gc = value.extract()?,
+                "order" => config.config.order = ResultOrder::parse(value.extract()?)?,
+                "sequence" => config.config.sequence = value.extract()?,
+                "hash_mode" => config.config.hash_mode = HashMode::parse(value.extract()?)?,
+                "shards" => config.config.shards = IndexConfig::check_shards(value.extract()?)?,
+                "threads" => config.config.threads = Some(IndexConfig::check_threads(value.extract()?)?),
+                "opaque" => config.opaque = value.extract()?,
+                "coerce_numeric" => config.coerce_numeric = value.extract()?,
+                "semver" => config.semver = value.extract()?,
+                "interval" => config.interval = value.extract()?,
+                "flags" => config.flags = value.extract()?,
+                "units" => {
+                    config.units = value.extract()?;
+                    for unit in config.units.values() {
+                        Unit::parse(unit)?;
+                    }
+                },
+                "fixed_point" => config.fixed_point = value.extract()?,
+                "float_tolerance" => config.float_tolerance = value.extract()?,
+                "limit_nested" => {
+                    for (attr, limits) in value.downcast::<PyDict>()?.iter() {
+                        let limits = limits.downcast_into::<PyDict>()?;
+                        let depth = limits.get_item("depth")?.map(|d| d.extract()).transpose()?.flatten();
+                        let only = limits.get_item("only")?.map(|o| o.extract()).transpose()?.flatten();
+                        config.limit_nested.insert(attr.extract()?, (depth, only));
+                    }
+                },
+                "wal" => config.wal = Some(value.extract()?),
+                _ => return Err(PyValueError::new_err(format!("unknown setting '{}', expected one of {}", key, KEYS.join(", ")))),
+            }
+        }
+        Ok(config)
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self == other
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!("IndexConfig({})", self.to_dict(py)?.repr()?))
+    }
+}
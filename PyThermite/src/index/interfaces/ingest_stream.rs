@@ -0,0 +1,124 @@
+use std::{sync::{Arc, Mutex, mpsc::{self, Receiver, SyncSender, TrySendError}}, thread::JoinHandle};
+
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyType};
+
+use crate::index::{Index, Indexable, core::index::IndexAPI};
+
+enum Message {
+    Batch(Vec<Py<Indexable>>),
+    // answered once every batch sent before it is applied
+    Flush(mpsc::Sender<()>),
+}
+
+/// writer returned by Index.ingest_stream, adding batches of objects to the index on a background thread
+// at most capacity batches wait in the queue, a full queue blocks feed or makes it raise
+#[pyclass]
+pub struct IngestStream {
+    sender: Option<SyncSender<Message>>,
+    block: bool,
+    // the first error adding a batch, raised by the next feed, flush or close
+    error: Arc<Mutex<Option<PyErr>>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl IngestStream {
+    pub fn new(index: Arc<IndexAPI>, capacity: usize, block: bool) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let error = Arc::new(Mutex::new(None));
+        let worker_error = error.clone();
+        let worker = std::thread::spawn(move || run(index, receiver, worker_error));
+        Self { sender: Some(sender), block, error, worker: Some(worker) }
+    }
+
+    fn check_error(&self) -> PyResult<()> {
+        match self.error.lock().unwrap().take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn sender(&self) -> PyResult<&SyncSender<Message>> {
+        self.sender.as_ref().ok_or_else(|| PyValueError::new_err("ingest stream is closed"))
+    }
+}
+
+// batches are added in the order fed, a refused batch is dropped and the rest still added
+fn run(index: Arc<IndexAPI>, receiver: Receiver<Message>, error: Arc<Mutex<Option<PyErr>>>) {
+    let index = Index { inner: index };
+    for message in receiver {
+        match message {
+            Message::Batch(batch) => Python::with_gil(|py| {
+                let refs = batch.iter().map(|obj| obj.bind(py).borrow()).collect();
+                if let Err(e) = index.add_object_many(py, refs) {
+                    error.lock().unwrap().get_or_insert(e);
+                }
+            }),
+            Message::Flush(done) => {
+                let _ = done.send(());
+            },
+        }
+    }
+}
+
+#[pymethods]
+impl IngestStream {
+    /// queues a batch of objects to be added to the index. when the queue is full, waits for room if the
+    /// stream blocks, otherwise raises queue.Full and the batch is not queued
+    /// raises the error adding an earlier batch, if one failed since the last check
+    pub fn feed(&self, py: Python, batch: Vec<Py<Indexable>>) -> PyResult<()> {
+        self.check_error()?;
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let sender = self.sender()?;
+        if self.block {
+            return py.allow_threads(|| sender.send(Message::Batch(batch)))
+                .map_err(|_| PyValueError::new_err("ingest stream is closed"));
+        }
+        match sender.try_send(Message::Batch(batch)) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                let full = py.import("queue")?.getattr("Full")?.downcast_into::<PyType>()?;
+                Err(PyErr::from_type(full, "ingest queue is full"))
+            },
+            Err(TrySendError::Disconnected(_)) => Err(PyValueError::new_err("ingest stream is closed")),
+        }
+    }
+
+    /// waits until every batch fed so far is in the index, then raises the error adding one of them, if any
+    pub fn flush(&self, py: Python) -> PyResult<()> {
+        let sender = self.sender()?;
+        let (done, wait) = mpsc::channel();
+        py.allow_threads(move || {
+            if sender.send(Message::Flush(done)).is_ok() {
+                let _ = wait.recv();
+            }
+        });
+        self.check_error()
+    }
+
+    /// flushes and stops the background thread, feeding afterwards raises ValueError. closing twice does nothing
+    pub fn close(&mut self, py: Python) -> PyResult<()> {
+        let Some(sender) = self.sender.take() else {
+            return Ok(());
+        };
+        drop(sender);
+        if let Some(worker) = self.worker.take() {
+            py.allow_threads(|| worker.join()).ok();
+        }
+        self.check_error()
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    // closes the stream, raising the error adding a batch only when the block did not raise one itself
+    fn __exit__(&mut self, py: Python, exc_type: Option<PyObject>, _exc: Option<PyObject>, _tb: Option<PyObject>) -> PyResult<bool> {
+        let res = self.close(py);
+        if exc_type.is_none() {
+            res?;
+        }
+        Ok(false)
+    }
+}
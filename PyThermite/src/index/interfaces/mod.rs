@@ -1,5 +1,27 @@
 pub mod index;
 pub mod filtered_index;
 mod query;
+mod prepared;
+mod attribute;
+mod debug;
+mod settings;
+mod encoders;
+mod operators;
+mod id_set;
+mod result_iter;
+mod ingest_stream;
+mod index_config;
+mod bulk_update;
 
-pub use query::PyQueryExpr;
\ No newline at end of file
+pub use query::PyQueryExpr;
+pub use prepared::{PreparedQuery, QueryParam};
+pub use attribute::{PyAttr, attr};
+pub use debug::{handles, why_not};
+pub use settings::{get_hash_mode, set_hash_mode};
+pub use encoders::{register_encoder, register_extractor, unregister_encoder, unregister_extractor};
+pub use operators::{register_operator, unregister_operator};
+pub use id_set::PyIdSet;
+pub use result_iter::ResultIter;
+pub use ingest_stream::IngestStream;
+pub use index_config::PyIndexConfig;
+pub use bulk_update::BulkUpdate;
\ No newline at end of file
@@ -0,0 +1,24 @@
+use pyo3::{exceptions::PyTypeError, prelude::*, pyfunction};
+
+use crate::index::core::query::operators::{register_py_operator, unregister_operator as unregister};
+
+/// makes name usable in QueryExpr.custom(name, attr, value), evaluated as predicate(attribute value, value)
+/// once per object holding the attribute, objects where it returns a truthy value match and raising counts as no match
+/// python operators hold the GIL for the whole scan, so they suit small candidate sets,
+/// bits_all and bits_any (int flag tests) are built in and run natively
+/// replaces any operator already registered under name
+#[pyfunction]
+pub fn register_operator(name: &str, predicate: Bound<'_, PyAny>) -> PyResult<()> {
+    if !predicate.is_callable() {
+        return Err(PyTypeError::new_err("predicate must be callable"));
+    }
+    register_py_operator(name, predicate.unbind());
+    Ok(())
+}
+
+/// removes the operator registered under name, returns whether one was registered
+/// queries already built with it match nothing afterwards
+#[pyfunction]
+pub fn unregister_operator(name: &str) -> bool {
+    unregister(name)
+}
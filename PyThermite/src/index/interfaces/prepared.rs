@@ -0,0 +1,84 @@
+use pyo3::{Bound, PyAny, PyResult, Python, exceptions::PyValueError, pyclass, pymethods, types::PyAnyMethods};
+use rustc_hash::FxHashMap;
+use smol_str::SmolStr;
+
+use crate::index::{core::query::query_ops::QueryExpr, interfaces::PyQueryExpr, value::PyValue};
+
+/// a named placeholder created by QueryExpr.param
+#[pyclass(frozen)]
+pub struct QueryParam {
+    pub name: SmolStr,
+}
+
+#[pymethods]
+impl QueryParam {
+    fn __repr__(&self) -> String {
+        format!("<QueryParam: {}>", self.name)
+    }
+}
+
+/// a normalized query template with named placeholders
+#[pyclass]
+#[derive(Clone)]
+pub struct PreparedQuery {
+    template: QueryExpr,
+    // position of each placeholder in QueryExpr::for_each_value_mut order
+    slots: FxHashMap<usize, SmolStr>,
+}
+
+impl PreparedQuery {
+    pub fn new(py: Python, template: QueryExpr) -> Self {
+        let mut template = template.normalized();
+        let mut slots = FxHashMap::default();
+        let mut pos = 0;
+        template.for_each_value_mut(&mut |value| {
+            if let Ok(param) = value.get_obj(py).bind(py).downcast::<QueryParam>() {
+                slots.insert(pos, param.get().name.clone());
+            }
+            pos += 1;
+        });
+        Self { template, slots }
+    }
+}
+
+#[pymethods]
+impl PreparedQuery {
+    #[getter]
+    fn params(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.slots.values().map(|n| n.to_string()).collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// returns the query with every placeholder replaced by the matching keyword value
+    /// raises ValueError on missing or unknown parameters
+    #[pyo3(signature = (**kwargs))]
+    fn bind<'py>(&self, kwargs: Option<FxHashMap<String, Bound<'py, PyAny>>>) -> PyResult<PyQueryExpr> {
+        let kwargs = kwargs.unwrap_or_default();
+        let mut values: FxHashMap<&str, PyValue> = FxHashMap::default();
+        for (name, value) in kwargs.iter() {
+            if !self.slots.values().any(|n| n == name) {
+                return Err(PyValueError::new_err(format!("unknown query parameter '{}'", name)));
+            }
+            values.insert(name.as_str(), PyValue::new(value.clone()));
+        }
+        if let Some(missing) = self.slots.values().find(|n| !values.contains_key(n.as_str())) {
+            return Err(PyValueError::new_err(format!("missing value for query parameter '{}'", missing)));
+        }
+
+        let mut inner = self.template.clone();
+        let mut pos = 0;
+        inner.for_each_value_mut(&mut |value| {
+            if let Some(name) = self.slots.get(&pos) {
+                *value = values[name.as_str()].clone();
+            }
+            pos += 1;
+        });
+        Ok(PyQueryExpr { inner })
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<PreparedQuery: {:?}>", self.template)
+    }
+}
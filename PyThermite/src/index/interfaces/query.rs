@@ -1,7 +1,8 @@
-use pyo3::{PyAny, pyclass, pymethods};
+use pyo3::{PyAny, PyResult, Python, exceptions::PyValueError, pyclass, pymethods};
+use rustc_hash::FxHashMap;
 use smol_str::SmolStr;
 
-use crate::index::{core::query::query_ops::QueryExpr, value::PyValue};
+use crate::index::{core::query::{aggregate::{AggFunc, CmpOp}, operators::is_operator, pattern::Pattern, query_ops::QueryExpr}, interfaces::{PreparedQuery, QueryParam}, value::{PyValue, RustCastValue}};
 
 
 #[pyclass]
@@ -12,6 +13,7 @@ pub struct PyQueryExpr {
 
 #[pymethods]
 impl PyQueryExpr {
+    /// attribute equals value
     #[staticmethod]
     pub fn eq<'py>(attr: String, value: pyo3::Bound<'py, PyAny>) -> Self {
         Self {
@@ -19,6 +21,7 @@ impl PyQueryExpr {
         }
     }
 
+    /// attribute does not equal value
     #[staticmethod]
     pub fn ne<'py>(attr: String, value: pyo3::Bound<'py, PyAny>) -> Self {
         Self {
@@ -26,6 +29,27 @@ impl PyQueryExpr {
         }
     }
 
+    /// attribute is set to None, objects without the attribute are not included (see missing)
+    #[staticmethod]
+    pub fn is_null(attr: String) -> Self {
+        Self {
+            inner: QueryExpr::Eq(SmolStr::new(attr), PyValue::from_primitave(RustCastValue::None)),
+        }
+    }
+
+    /// attribute is set to a value other than None, objects without the attribute are not included
+    #[staticmethod]
+    pub fn is_not_null(attr: String) -> Self {
+        let attr = SmolStr::new(attr);
+        Self {
+            inner: QueryExpr::And(vec![
+                QueryExpr::Exists(attr.clone()),
+                QueryExpr::Ne(attr, PyValue::from_primitave(RustCastValue::None)),
+            ]),
+        }
+    }
+
+    /// attribute is greater than value
     #[staticmethod]
     pub fn gt<'py>(attr: String, value: pyo3::Bound<'py, PyAny>) -> Self {
         Self {
@@ -33,6 +57,7 @@ impl PyQueryExpr {
         }
     }
 
+    /// attribute is greater than or equal to value
     #[staticmethod]
     pub fn ge<'py>(attr: String, value: pyo3::Bound<'py, PyAny>) -> Self {
         Self {
@@ -40,6 +65,7 @@ impl PyQueryExpr {
         }
     }
 
+    /// attribute is less than or equal to value
     #[staticmethod]
     pub fn le<'py>(attr: String, value: pyo3::Bound<'py, PyAny>) -> Self {
         Self {
@@ -47,6 +73,10 @@ impl PyQueryExpr {
         }
     }
 
+    /// attribute is between lower and upper, inclusive
+    /// string bounds match string values, compared by code point (so "B" < "a" and "A100" < "A20"),
+    /// lower and upper must both be strings or both numbers. datetimes, dates and timedeltas are ordered
+    /// against their own kind only (datetimes as instants in UTC, naive datetimes taken as UTC)
     #[staticmethod]
     pub fn bt<'py>(attr: String, lower: pyo3::Bound<'py, PyAny>, upper: pyo3::Bound<'py, PyAny>) -> Self {
         Self {
@@ -54,6 +84,7 @@ impl PyQueryExpr {
         }
     }
 
+    /// attribute is less than value
     #[staticmethod]
     pub fn lt<'py>(attr: String, value: pyo3::Bound<'py, PyAny>) -> Self {
         Self {
@@ -61,6 +92,8 @@ impl PyQueryExpr {
         }
     }
 
+    /// attribute is in the list of values
+    /// equivalent to multiple or'd eq queries
     #[staticmethod]
     pub fn in_<'py>(attr: String, values: Vec<pyo3::Bound<'py, PyAny>>) -> Self {
         let values = values.into_iter().map(|obj| PyValue::new(obj)).collect();
@@ -69,6 +102,17 @@ impl PyQueryExpr {
         }
     }
 
+    /// attribute is not in the list of values, objects without the attribute included
+    /// equivalent to not_(in_(attr, values))
+    #[staticmethod]
+    pub fn nin<'py>(attr: String, values: Vec<pyo3::Bound<'py, PyAny>>) -> Self {
+        let values = values.into_iter().map(|obj| PyValue::new(obj)).collect();
+        Self {
+            inner: QueryExpr::Nin(SmolStr::new(attr), values),
+        }
+    }
+
+    /// all subqueries must be true
     #[staticmethod]
     #[pyo3(signature = (*exprs))]
     fn and_(exprs: Vec<Self>) -> Self {
@@ -77,6 +121,7 @@ impl PyQueryExpr {
         }
     }
 
+    /// at least one subquery must be true
     #[staticmethod]
     #[pyo3(signature = (*exprs))]
     fn or_(exprs: Vec<Self>) -> Self {
@@ -85,6 +130,7 @@ impl PyQueryExpr {
         }
     }
 
+    /// negates the subquery
     #[staticmethod]
     fn not_(exprs: Self) -> Self {
         Self {
@@ -92,20 +138,23 @@ impl PyQueryExpr {
         }
     }
 
+    /// attribute where the string value starts with the specified start
     #[staticmethod]
     fn starts_with<'py>(attr: String, value: pyo3::Bound<'py, PyAny>) -> Self {
         Self {
-            inner: QueryExpr::StartsWi(SmolStr::new(attr), PyValue::new(value)),
+            inner: QueryExpr::StartsWith(SmolStr::new(attr), PyValue::new(value)),
         }
     }
 
+    /// attribute where the string value ends with the specified end
     #[staticmethod]
     fn ends_with<'py>(attr: String, value: pyo3::Bound<'py, PyAny>) -> Self {
         Self {
-            inner: QueryExpr::EndsWi(SmolStr::new(attr), PyValue::new(value)),
+            inner: QueryExpr::EndsWith(SmolStr::new(attr), PyValue::new(value)),
         }
     }
 
+    /// attribute where the string value contains with the specified string
     #[staticmethod]
     fn contains<'py>(attr: String, value: pyo3::Bound<'py, PyAny>) -> Self {
         Self {
@@ -113,7 +162,204 @@ impl PyQueryExpr {
         }
     }
 
+    /// attribute where the whole string value matches pattern, a glob (* any run, ? one character,
+    /// [abc] / [a-z] / [!abc] a class) or with regex a regular expression. raises ValueError on an invalid pattern
+    /// e.g. matches("sku", "AB-*-2?") or matches("sku", "AB-[0-9]+", regex=True)
+    #[staticmethod]
+    #[pyo3(signature = (attr, pattern, regex=false))]
+    fn matches(attr: String, pattern: &str, regex: bool) -> PyResult<Self> {
+        Ok(Self {
+            inner: QueryExpr::Matches(SmolStr::new(attr), Pattern::new(pattern, regex)?),
+        })
+    }
+
+    /// attribute declared with Index.interval shares at least one point with [lower, upper], endpoints included
+    #[staticmethod]
+    fn overlaps<'py>(attr: String, lower: pyo3::Bound<'py, PyAny>, upper: pyo3::Bound<'py, PyAny>) -> Self {
+        Self {
+            inner: QueryExpr::Overlaps(SmolStr::new(attr), PyValue::new(lower), PyValue::new(upper)),
+        }
+    }
+
+    /// attribute declared with Index.interval contains point, endpoints included
+    #[staticmethod]
+    fn contains_point<'py>(attr: String, point: pyo3::Bound<'py, PyAny>) -> Self {
+        Self {
+            inner: QueryExpr::ContainsPoint(SmolStr::new(attr), PyValue::new(point)),
+        }
+    }
+
+    /// int attribute has flag set, flag is a single bit e.g. has_flag("permissions", 0b0100)
+    /// raises ValueError when flag has no or several bits set (see flags_all)
+    #[staticmethod]
+    pub fn has_flag(attr: String, flag: i64) -> PyResult<Self> {
+        if flag.count_ones() != 1 {
+            return Err(PyValueError::new_err(format!("has_flag takes a single bit, got {:#b}, use flags_all or flags_any for masks", flag)));
+        }
+        Ok(Self::flags_all(attr, flag))
+    }
+
+    /// int attribute has every bit of mask set, attr & mask == mask
+    /// answered from per bit bitmaps when attr was declared with Index.flags, otherwise by scanning its numbers
+    #[staticmethod]
+    pub fn flags_all(attr: String, mask: i64) -> Self {
+        Self {
+            inner: QueryExpr::FlagsAll(SmolStr::new(attr), mask),
+        }
+    }
+
+    /// int attribute has at least one bit of mask set, attr & mask != 0
+    #[staticmethod]
+    pub fn flags_any(attr: String, mask: i64) -> Self {
+        Self {
+            inner: QueryExpr::FlagsAny(SmolStr::new(attr), mask),
+        }
+    }
+
+    /// int attribute leaves remainder when divided by divisor, python's % so negatives count too
+    /// e.g. workers each claim mod_eq("id", divisor=8, remainder=worker), every object falls to exactly one.
+    /// answered from per bit bitmaps when attr was declared with Index.flags, otherwise by scanning its numbers.
+    /// raises ValueError unless divisor > 0 and 0 <= remainder < divisor
+    #[staticmethod]
+    pub fn mod_eq(attr: String, divisor: i64, remainder: i64) -> PyResult<Self> {
+        if divisor <= 0 || !(0..divisor).contains(&remainder) {
+            return Err(PyValueError::new_err(format!(
+                "mod_eq needs divisor > 0 and 0 <= remainder < divisor, got divisor={} remainder={}", divisor, remainder
+            )));
+        }
+        Ok(Self {
+            inner: QueryExpr::ModEq(SmolStr::new(attr), divisor as u64, remainder as u64),
+        })
+    }
+
+    /// aggregate over the nested Indexables held in a collection attribute
+    /// func is one of sum, count, min, max, avg and exactly one comparison
+    /// (eq, ne, gt, ge, lt, le) is applied to the aggregated value
+    /// e.g. agg("items", "sum", "price", gt=100)
+    #[staticmethod]
+    #[pyo3(signature = (attr, func, child_attr, **kwargs))]
+    fn agg(attr: String, func: &str, child_attr: String, kwargs: Option<FxHashMap<String, f64>>) -> PyResult<Self> {
+        let func = AggFunc::parse(func).ok_or_else(|| {
+            PyValueError::new_err(format!("unknown aggregate '{}', expected one of sum, count, min, max, avg", func))
+        })?;
+        let (op, threshold) = single_comparison("agg", kwargs)?;
+
+        Ok(Self {
+            inner: QueryExpr::Agg(SmolStr::new(attr), func, SmolStr::new(child_attr), op, threshold),
+        })
+    }
+
+    /// number of nested Indexables currently indexed under attr
+    /// exactly one comparison (eq, ne, gt, ge, lt, le) is applied to the count
+    /// e.g. count_children("items", ge=3)
+    #[staticmethod]
+    #[pyo3(signature = (attr, **kwargs))]
+    fn count_children(attr: String, kwargs: Option<FxHashMap<String, f64>>) -> PyResult<Self> {
+        let (op, threshold) = single_comparison("count_children", kwargs)?;
+        Ok(Self {
+            inner: QueryExpr::CountChildren(SmolStr::new(attr), op, threshold),
+        })
+    }
+
+    /// attribute is set on the object, whatever its value (None included)
+    #[staticmethod]
+    fn exists(attr: String) -> Self {
+        Self {
+            inner: QueryExpr::Exists(SmolStr::new(attr)),
+        }
+    }
+
+    /// attribute is not set on the object at all
+    /// equivalent to not_(exists(attr))
+    #[staticmethod]
+    fn missing(attr: String) -> Self {
+        Self {
+            inner: QueryExpr::Missing(SmolStr::new(attr)),
+        }
+    }
+
+    /// attribute matches the operator registered under name (see register_operator) for value
+    /// e.g. custom("bits_all", "flags", 0b101). raises ValueError when no operator is registered under name
+    #[staticmethod]
+    pub fn custom<'py>(name: &str, attr: String, value: pyo3::Bound<'py, PyAny>) -> PyResult<Self> {
+        if !is_operator(name) {
+            return Err(PyValueError::new_err(format!("no query operator registered under '{}'", name)));
+        }
+        Ok(Self {
+            inner: QueryExpr::Custom(SmolStr::new(attr), SmolStr::new(name), PyValue::new(value)),
+        })
+    }
+
+    /// placeholder usable anywhere a constant is expected, bound later through prepare()
+    /// e.g. QueryExpr.gt("price", QueryExpr.param("min_price"))
+    #[staticmethod]
+    fn param(name: &str) -> QueryParam {
+        QueryParam { name: SmolStr::new(name) }
+    }
+
+    /// returns a PreparedQuery for this expression so its placeholders can be bound repeatedly
+    fn prepare(&self, py: Python) -> PreparedQuery {
+        PreparedQuery::new(py, self.inner.clone())
+    }
+
+    /// returns a copy of this expression with attribute old renamed to new
+    /// paths below old are renamed too, e.g. rebind("owner", "holder") turns owner.name into holder.name
+    pub fn rebind(&self, old: &str, new: &str) -> Self {
+        Self {
+            inner: self.inner.rebind(old, new),
+        }
+    }
+
+    /// returns a simplified copy of this expression
+    /// nested and_/or_ are flattened, not_ is pushed through and_/or_ (not_ of eq becomes ne and back)
+    /// and an inverted bt collapses to an expression that matches nothing
+    /// bounds on the same attribute are kept apart, list and nested values can meet each with a different element
+    /// queries are normalized automatically when evaluated, and_ branches then run most selective first,
+    /// estimated from the index (bool and object values are counted exactly, missing attributes match nothing)
+    pub fn normalize(&self) -> Self {
+        Self {
+            inner: self.inner.normalized(),
+        }
+    }
+
+    /// a & b is the same as and_(a, b)
+    fn __and__(&self, other: &Self) -> Self {
+        Self {
+            inner: QueryExpr::And(vec![self.inner.clone(), other.inner.clone()]),
+        }
+    }
+
+    /// a | b is the same as or_(a, b)
+    fn __or__(&self, other: &Self) -> Self {
+        Self {
+            inner: QueryExpr::Or(vec![self.inner.clone(), other.inner.clone()]),
+        }
+    }
+
+    /// ~a is the same as not_(a)
+    fn __invert__(&self) -> Self {
+        Self {
+            inner: QueryExpr::Not(Box::new(self.inner.clone())),
+        }
+    }
+
+    fn __str__(&self) -> String {
+        self.inner.to_string()
+    }
+
     fn __repr__(&self) -> String {
         format!("<QueryExpr: {:?}>", self.inner)
     }
-}
\ No newline at end of file
+}
+
+fn single_comparison(name: &str, kwargs: Option<FxHashMap<String, f64>>) -> PyResult<(CmpOp, f64)> {
+    let mut comparisons: Vec<(String, f64)> = kwargs.unwrap_or_default().into_iter().collect();
+    if comparisons.len() != 1 {
+        return Err(PyValueError::new_err(format!("{} expects exactly one comparison, e.g. gt=100", name)));
+    }
+    let (op_name, threshold) = comparisons.pop().unwrap();
+    let op = CmpOp::parse(&op_name).ok_or_else(|| {
+        PyValueError::new_err(format!("unknown comparison '{}', expected one of eq, ne, gt, ge, lt, le", op_name))
+    })?;
+    Ok((op, threshold))
+}
@@ -0,0 +1,106 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use croaring::Bitmap;
+use pyo3::{Py, PyRef, Python, pyclass, pymethods};
+
+use crate::index::{Indexable, core::{stored_item::StoredItem, structures::freeze_lock::FreezeLock}};
+
+// the ids an iterator has still to yield
+pub enum ResultIds {
+    // id order, walked from the FilteredIndex's bitmap from the id after the last one taken, None once done
+    Sorted { ids: Bitmap, from: Option<u32> },
+    // insertion order, the ids put in order when the iterator is made
+    Ordered(std::vec::IntoIter<u32>),
+}
+
+impl ResultIds {
+    pub fn sorted(ids: Bitmap) -> Self {
+        Self::Sorted { ids, from: Some(0) }
+    }
+
+    fn next_batch(&mut self, batch_size: usize) -> Vec<u32> {
+        match self {
+            Self::Sorted { ids, from } => {
+                let Some(start) = *from else {
+                    return Vec::new();
+                };
+                let mut batch = vec![0; batch_size];
+                let mut iter = ids.iter();
+                iter.reset_at_or_after(start);
+                let taken = iter.next_many(&mut batch);
+                batch.truncate(taken);
+                *from = match batch.last() {
+                    Some(last) if batch.len() == batch_size => last.checked_add(1),
+                    _ => None,
+                };
+                batch
+            }
+            Self::Ordered(ids) => ids.by_ref().take(batch_size).collect(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Sorted { ids, from: Some(start) } => ids.range_cardinality(*start..) as usize,
+            Self::Sorted { from: None, .. } => 0,
+            Self::Ordered(ids) => ids.len(),
+        }
+    }
+}
+
+/// lazy iterator over query results returned by FilteredIndex.iter
+/// the ids are fixed when the iterator is made, objects added afterwards never show up
+/// and objects removed before their batch is reached are skipped
+// ids are looked up a batch at a time without the GIL, then made python references under it
+#[pyclass]
+pub struct ResultIter {
+    items: Arc<FreezeLock<Vec<StoredItem>>>,
+    ids: ResultIds,
+    batch_size: usize,
+    batch: VecDeque<Py<Indexable>>,
+}
+
+impl ResultIter {
+    pub fn new(items: Arc<FreezeLock<Vec<StoredItem>>>, ids: ResultIds, batch_size: usize) -> Self {
+        Self { items, ids, batch_size: batch_size.max(1), batch: VecDeque::new() }
+    }
+
+    // a batch whose objects were all removed is passed over for the next one
+    fn refill(&mut self, py: Python) {
+        while self.batch.is_empty() {
+            let (items, ids, batch_size) = (&self.items, &mut self.ids, self.batch_size);
+            let held = py.allow_threads(|| {
+                let batch = ids.next_batch(batch_size);
+                let items = items.read().unwrap();
+                batch.into_iter()
+                    .filter_map(|id| items.get(id as usize))
+                    .filter(|item| !item.is_placeholder())
+                    .cloned()
+                    .collect::<Vec<StoredItem>>()
+            });
+            if held.is_empty() && self.ids.len() == 0 {
+                return;
+            }
+            self.batch.extend(held.iter().filter_map(|item| item.get_py_ref(py)));
+        }
+    }
+}
+
+#[pymethods]
+impl ResultIter {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python) -> Option<Py<Indexable>> {
+        if self.batch.is_empty() {
+            self.refill(py);
+        }
+        self.batch.pop_front()
+    }
+
+    // objects not yet yielded, counting those removed since that will be skipped
+    fn __length_hint__(&self) -> usize {
+        self.batch.len() + self.ids.len()
+    }
+}
@@ -0,0 +1,19 @@
+use pyo3::{PyResult, pyfunction};
+
+use crate::index::value::HashMode;
+
+/// selects how indexes created afterwards hash Indexable values, "identity" (default) hashes by object address,
+/// "stable" hashes by id so hashes repeat across processes
+/// list/tuple/set/dict values always hash by content
+/// indexes already built keep their mode, IndexConfig().hash_mode(...) sets it for one index
+#[pyfunction]
+pub fn set_hash_mode(mode: &str) -> PyResult<()> {
+    HashMode::set(HashMode::parse(mode)?);
+    Ok(())
+}
+
+/// returns the hash mode new indexes take, "identity" or "stable"
+#[pyfunction]
+pub fn get_hash_mode() -> &'static str {
+    HashMode::current().name()
+}
@@ -4,6 +4,18 @@ pub use indexable::Indexable;
 pub use hybrid_hashmap::HybridHashmap;
 pub use interfaces::filtered_index::FilteredIndex;
 pub use interfaces::PyQueryExpr;
+pub use interfaces::{PreparedQuery, QueryParam};
+pub use interfaces::{PyAttr, attr};
+pub use interfaces::{handles, why_not};
+pub use interfaces::{get_hash_mode, set_hash_mode};
+pub use interfaces::{register_encoder, register_extractor, unregister_encoder, unregister_extractor};
+pub use interfaces::{register_operator, unregister_operator};
+pub use core::query::operators::register_builtin_operators;
+pub use interfaces::PyIdSet;
+pub use interfaces::ResultIter;
+pub use interfaces::IngestStream;
+pub use interfaces::PyIndexConfig;
+pub use interfaces::BulkUpdate;
 
 
 pub(crate) mod core;
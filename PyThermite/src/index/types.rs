@@ -1,13 +1,13 @@
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
 
-use once_cell::sync::{Lazy, OnceCell};
-use pyo3::{Py, PyAny, PyTypeInfo, Python, types::{PyAnyMethods, PyType}};
+use once_cell::sync::Lazy;
+use pyo3::{Py, PyAny, PyTypeInfo, Python, sync::GILOnceCell, types::{PyAnyMethods, PyType}};
 use smallvec::SmallVec;
 
-use crate::index::{Indexable, core::{index::IndexAPI, query::QueryMap}};
+use crate::index::{Indexable, core::{index::IndexAPI, query::QueryMap, structures::freeze_lock::FreezeLock}};
 
 pub type StrId = u32;
-pub type IndexTree = Arc<RwLock<Vec<QueryMap>>>;
+pub type IndexTree = Arc<FreezeLock<Vec<QueryMap>>>;
 
 // devaults
 pub static DEFAULT_INDEX_ARC: Lazy<Arc<IndexAPI>> = Lazy::new(|| Arc::new(IndexAPI::new(None)));
@@ -29,35 +29,31 @@ pub static DEFAULT_PY_NONE_ARC: Lazy<Arc<Py<PyAny>>> = Lazy::new(|| {
 
 
 // types
-static INDEXABLE_TYPE: OnceCell<Py<PyType>> = OnceCell::new();
+// GILOnceCell throughout, the initializers run python that can release the GIL (an import does), and a
+// thread holding the GIL parked on a std OnceCell meanwhile would never let the first one finish
+static INDEXABLE_TYPE: GILOnceCell<Py<PyType>> = GILOnceCell::new();
 
-pub fn indexable_type() -> &'static Py<PyType> {
-    INDEXABLE_TYPE
-    .get_or_init(|| {
-            Python::with_gil(| py | {
-                py.get_type::<Indexable>().into()
-            })
-        }
-    ) // runs once
+pub fn indexable_type(py: Python<'_>) -> &Py<PyType> {
+    INDEXABLE_TYPE.get_or_init(py, || py.get_type::<Indexable>().into())
 }
 
 macro_rules! cached_py_type {
     // Built-in type
     ($fn_name:ident, $static_name:ident, $builtin:ident) => {
-        static $static_name: OnceCell<Py<PyType>> = OnceCell::new();
+        static $static_name: GILOnceCell<Py<PyType>> = GILOnceCell::new();
 
         pub fn $fn_name(py: Python<'_>) -> &Py<PyType> {
-            $static_name.get_or_init(|| {
+            $static_name.get_or_init(py, || {
                 pyo3::types::$builtin::type_object(py).unbind()
             })
         }
     };
     // Module type
     ($fn_name:ident, $static_name:ident, $module:literal, $attr:literal) => {
-        static $static_name: OnceCell<Option<Py<PyType>>> = OnceCell::new();
+        static $static_name: GILOnceCell<Option<Py<PyType>>> = GILOnceCell::new();
 
         pub fn $fn_name(py: Python<'_>) -> Option<&Py<PyType>> {
-            $static_name.get_or_init(|| {
+            $static_name.get_or_init(py, || {
                 let module = py.import($module).ok()?;
                 let attr = module.getattr($attr).ok()?;
                 let ty = attr.downcast_into::<PyType>().ok()?;
@@ -79,10 +75,10 @@ macro_rules! cached_type_ptrs {
 
 cached_py_type!(py_int_type, PY_INT_TYPE_CELL, PyInt); // built-in int
 cached_py_type!(np_int64_type, NP_INT64_CELL, "numpy", "int64"); // module type
-static INT_TYPES: OnceCell<SmallVec<[Py<PyType>; 2]>> = OnceCell::new();
+static INT_TYPES: GILOnceCell<SmallVec<[Py<PyType>; 2]>> = GILOnceCell::new();
 
 pub fn int_types(py: Python<'_>) -> &'_ [Py<PyType>] {
-    INT_TYPES.get_or_init(|| {
+    INT_TYPES.get_or_init(py, || {
         let mut types = SmallVec::<[_; 2]>::new();
         // Python int
         types.push(py_int_type(py).clone_ref(py));
@@ -100,10 +96,10 @@ cached_type_ptrs!(int_type_ptrs, int_types, 2);
 
 cached_py_type!(py_float_type, PY_FLOAT_TYPE_CELL, PyFloat); // built-in int
 cached_py_type!(np_float64_type, NP_FLOAT64_CELL, "numpy", "float64"); // module type
-static FLOAT_TYPES: OnceCell<SmallVec<[Py<PyType>; 2]>> = OnceCell::new();
+static FLOAT_TYPES: GILOnceCell<SmallVec<[Py<PyType>; 2]>> = GILOnceCell::new();
 
 pub fn float_types(py: Python<'_>) -> &'_ [Py<PyType>] {
-    FLOAT_TYPES.get_or_init(|| {
+    FLOAT_TYPES.get_or_init(py, || {
         let mut types = SmallVec::<[_; 2]>::new();
         // Python float
         types.push(py_float_type(py).clone_ref(py));
@@ -121,10 +117,10 @@ cached_type_ptrs!(float_type_ptrs, float_types, 2);
 
 cached_py_type!(py_bool_type, PY_BOOL_TYPE_CELL, PyBool); // built-in int
 cached_py_type!(np_bool_type, NP_BOOL_CELL, "numpy", "bool"); // module type
-static BOOL_TYPES: OnceCell<SmallVec<[Py<PyType>; 2]>> = OnceCell::new();
+static BOOL_TYPES: GILOnceCell<SmallVec<[Py<PyType>; 2]>> = GILOnceCell::new();
 
 pub fn bool_types(py: Python<'_>) -> &'_ [Py<PyType>] {
-    BOOL_TYPES.get_or_init(|| {
+    BOOL_TYPES.get_or_init(py, || {
         let mut types = SmallVec::<[_; 2]>::new();
         // Python float
         types.push(py_bool_type(py).clone_ref(py));
@@ -143,10 +139,10 @@ cached_type_ptrs!(bool_type_ptrs, bool_types, 2);
 
 cached_py_type!(py_str_type, PY_STR_TYPE_CELL, PyString); // built-in int
 cached_py_type!(np_str_type, NP_STR_CELL, "numpy", "str_"); // module type
-static STR_TYPES: OnceCell<SmallVec<[Py<PyType>; 2]>> = OnceCell::new();
+static STR_TYPES: GILOnceCell<SmallVec<[Py<PyType>; 2]>> = GILOnceCell::new();
 
 pub fn str_types(py: Python<'_>) -> &'_ [Py<PyType>] {
-    STR_TYPES.get_or_init(|| {
+    STR_TYPES.get_or_init(py, || {
         let mut types = SmallVec::<[_; 2]>::new();
         // Python float
         types.push(py_str_type(py).clone_ref(py));
@@ -158,4 +154,16 @@ pub fn str_types(py: Python<'_>) -> &'_ [Py<PyType>] {
     })
 }
 
-cached_type_ptrs!(str_type_ptrs, str_types, 2);
\ No newline at end of file
+cached_type_ptrs!(str_type_ptrs, str_types, 2);
+
+// Optional version types
+
+cached_py_type!(packaging_version_type, PACKAGING_VERSION_CELL, "packaging.version", "Version"); // module type
+
+// Decimal
+
+cached_py_type!(decimal_type, DECIMAL_CELL, "decimal", "Decimal"); // module type
+
+// UUID
+
+cached_py_type!(uuid_type, UUID_CELL, "uuid", "UUID"); // module type
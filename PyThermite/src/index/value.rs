@@ -1,14 +1,63 @@
-use pyo3::{IntoPyObjectExt, PyTypeInfo, prelude::*};
-use pyo3::types::{PyAny, PyDict, PyList, PySet, PyTuple};
+use pyo3::{IntoPyObjectExt, PyTypeInfo, exceptions::PyValueError, prelude::*};
+use pyo3::types::{PyAny, PyBool, PyBytes, PyDict, PyFloat, PyInt, PyList, PySet, PyString, PyTuple, PyType};
 use rustc_hash::FxHasher;
-use smol_str::SmolStr;
+use std::cmp::Ordering;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering as AtomicOrdering};
 use std::{hash::{Hash, Hasher}};
 use pyo3::conversion::IntoPyObject;
 
+use crate::index::core::class_fields::FieldType;
+use crate::index::core::encoders::{KeySpace, encode_registered, encode_temporal, encode_wide, extract_registered};
+use crate::index::core::query::b_tree::Key;
+use crate::index::core::query::b_tree::key::cmp_int_float;
+use crate::index::core::structures::string_interner::StrValue;
+use crate::index::core::structures::wide_bitmap::WideNum;
 use crate::index::types::{bool_type_ptrs, float_type_ptrs, int_type_ptrs, str_type_ptrs};
 use crate::index::{types, Indexable};
 
+// how Indexable values are hashed, iterables always hash by content
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HashMode {
+    // Indexables by python object address, cheapest but differs between runs
+    #[default]
+    Identity,
+    // Indexables by id, repeatable across processes
+    Stable,
+}
+
+static HASH_MODE: AtomicU8 = AtomicU8::new(HashMode::Identity as u8);
+
+impl HashMode {
+    pub fn parse(mode: &str) -> PyResult<Self> {
+        match mode {
+            "identity" => Ok(HashMode::Identity),
+            "stable" => Ok(HashMode::Stable),
+            _ => Err(PyValueError::new_err(format!("unknown hash mode '{}', expected identity or stable", mode))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashMode::Identity => "identity",
+            HashMode::Stable => "stable",
+        }
+    }
+
+    // the mode indexes take when their config does not set one
+    pub fn current() -> Self {
+        match HASH_MODE.load(AtomicOrdering::Relaxed) {
+            0 => HashMode::Identity,
+            _ => HashMode::Stable,
+        }
+    }
+
+    // indexes already built keep the mode they were made with
+    pub fn set(mode: HashMode) {
+        HASH_MODE.store(mode as u8, AtomicOrdering::Relaxed);
+    }
+}
+
 #[derive(Debug)]
 pub enum PyIterable {
     List(Py<PyList>),
@@ -17,6 +66,26 @@ pub enum PyIterable {
     Set(Py<PySet>)
 }
 
+impl PyIterable {
+    pub fn as_ptr(&self) -> *mut pyo3::ffi::PyObject {
+        match self {
+            Self::List(list) => list.as_ptr(),
+            Self::Dict(dict) => dict.as_ptr(),
+            Self::Tuple(tuple) => tuple.as_ptr(),
+            Self::Set(set) => set.as_ptr(),
+        }
+    }
+
+    pub fn clone_ref(&self, py: Python) -> Py<PyAny> {
+        match self {
+            Self::List(list) => list.clone_ref(py).into_any(),
+            Self::Dict(dict) => dict.clone_ref(py).into_any(),
+            Self::Tuple(tuple) => tuple.clone_ref(py).into_any(),
+            Self::Set(set) => set.clone_ref(py).into_any(),
+        }
+    }
+}
+
 impl Clone for PyIterable {
     fn clone(&self) -> Self {
         Python::with_gil(|py| {
@@ -50,66 +119,195 @@ impl StoredIndexable {
 pub enum RustCastValue {
     Int(i64),
     Float(f64),
-    Str(SmolStr),
+    Str(StrValue),
     Bool(bool),
     Iterable(PyIterable),
     Ind(StoredIndexable),
+    // value of a type with a registered key encoder, a datetime, date or timedelta, or a Decimal with an exact key,
+    // ordered against the other keys of its space
+    Encoded(KeySpace, Key),
+    // big int or Decimal with no exact i64 / f64 key, kept exactly beside the numeric index
+    Wide(Arc<WideNum>),
+    // bytes and uuid.UUID by value, held in the exact map
+    Bytes(Arc<[u8]>),
+    Uuid(u128),
+    // python None, indexed apart from other unknown values
+    None,
     Unknown,
 }
 
 #[derive(Debug)]
 pub struct PyValue {
+    // only for values get_obj can't rebuild from primitave, so the index doesn't keep the rest alive.
+    // numpy scalars keep theirs to read back as the same type
     obj: Option<Arc<Py<PyAny>>>,
     primitave: RustCastValue,
     hash: u64,
+    // iterable contents frozen when the value was taken, dicts as key, value pairs.
+    // in place mutation is not seen by the index until the attribute is assigned again
+    items: Option<Arc<[PyValue]>>,
+    // sub fields of an Unknown value from its type's registered extractor, indexed as a nested object
+    fields: Option<Arc<StoredIndexable>>,
 }
 
 impl PyValue {
+    // a value that can't be read (e.g. an unhashable object) is kept opaque and matched by identity,
+    // objects being built go through try_new so the failure is reported instead
     pub fn new<'py>(obj: Bound<'py, PyAny>) -> Self {
+        Self::try_new(obj.clone()).unwrap_or_else(|_| Self::opaque(obj))
+    }
+
+    pub fn try_new<'py>(obj: Bound<'py, PyAny>) -> PyResult<Self> {
 
         let py_type = obj.get_type();
         let py = obj.py();
 
         // primitave types - check first
         let primitave = 
-        if int_type_ptrs(py).contains(&py_type.as_ptr()) {
-            RustCastValue::Int(obj.extract::<i64>().expect("type checked"))
+        if obj.is_none() {
+            RustCastValue::None
+        } else if int_type_ptrs(py).contains(&py_type.as_ptr()) {
+            match obj.extract::<i64>() {
+                Ok(i) => RustCastValue::Int(i),
+                Err(_) => encode_wide(&obj).map_or(RustCastValue::Unknown, Self::wide_primitave),
+            }
         } else if float_type_ptrs(py).contains(&py_type.as_ptr()) {
-            RustCastValue::Float(obj.extract::<f64>().expect("type checked"))
+            RustCastValue::Float(obj.extract::<f64>()?)
         } else if str_type_ptrs(py).contains(&py_type.as_ptr()) {
-            RustCastValue::Str(SmolStr::new(obj.extract::<&str>().expect("type checked")))
+            RustCastValue::Str(StrValue::new(obj.extract::<&str>()?))
         } else if bool_type_ptrs(py).contains(&py_type.as_ptr()) {
-            RustCastValue::Bool(obj.extract::<bool>().expect("type checked"))
+            RustCastValue::Bool(obj.extract::<bool>()?)
+        } else if py_type.is(PyBytes::type_object(py)) {
+            RustCastValue::Bytes(Arc::from(obj.downcast::<PyBytes>()?.as_bytes()))
 
         // complex types - pointer based equality
-        } else if py_type.is_subclass(types::indexable_type().bind(py)).unwrap_or(false) {
-            let py_ref = obj.extract::<PyRef<Indexable>>().expect("type checked");
+        } else if py_type.is_subclass(types::indexable_type(py).bind(py)).unwrap_or(false) {
+            let py_ref = obj.extract::<PyRef<Indexable>>()?;
             RustCastValue::Ind(StoredIndexable::from_py_ref(py_ref, py))
         } else if py_type.is(pyo3::types::PyList::type_object(py)) {
-            RustCastValue::Iterable(PyIterable::List(obj.extract::<Py<PyList>>().expect("type checked")))
+            RustCastValue::Iterable(PyIterable::List(obj.extract::<Py<PyList>>()?))
         } else if py_type.is(pyo3::types::PyTuple::type_object(py)) {
-            RustCastValue::Iterable(PyIterable::Tuple(obj.extract::<Py<PyTuple>>().expect("type checked")))
+            RustCastValue::Iterable(PyIterable::Tuple(obj.extract::<Py<PyTuple>>()?))
         } else if py_type.is(pyo3::types::PyDict::type_object(py)) {
-            RustCastValue::Iterable(PyIterable::Dict(obj.extract::<Py<PyDict>>().expect("type checked")))
+            RustCastValue::Iterable(PyIterable::Dict(obj.extract::<Py<PyDict>>()?))
         } else if py_type.is(pyo3::types::PySet::type_object(py)) {
-            RustCastValue::Iterable(PyIterable::Set(obj.extract::<Py<PySet>>().expect("type checked")))
+            RustCastValue::Iterable(PyIterable::Set(obj.extract::<Py<PySet>>()?))
+        } else if let Some((space, key)) = encode_registered(&obj)? {
+            RustCastValue::Encoded(space, key)
+        } else if let Some((space, key)) = encode_temporal(&obj) {
+            RustCastValue::Encoded(space, key)
+        } else if let Some(num) = encode_wide(&obj) {
+            Self::wide_primitave(num)
+        } else if types::uuid_type(py).is_some_and(|ty| py_type.is(ty.bind(py))) {
+            obj.getattr("int").and_then(|int| int.extract::<u128>()).map_or(RustCastValue::Unknown, RustCastValue::Uuid)
         } else {
             RustCastValue::Unknown
         };
 
-        let hash = match &primitave {
-            RustCastValue::Unknown => obj.hash().unwrap() as u64,
-            _ => Self::hash_primitave(&primitave)
+        let items = match &primitave {
+            RustCastValue::Iterable(itr) => Some(Self::snapshot(py, itr)?),
+            _ => None,
         };
 
-        Self {
-            obj: Some(Arc::new(obj.into())),
+        let hash = match (&primitave, &items) {
+            (RustCastValue::Unknown, _) => obj.hash()? as u64,
+            (RustCastValue::Iterable(itr), Some(items)) => Self::hash_iterable_content(itr, items, HashMode::Identity),
+            _ => Self::hash_primitave(&primitave, HashMode::Identity)
+        };
+
+        let fields = match &primitave {
+            RustCastValue::Unknown => extract_registered(&obj)?.map(Arc::new),
+            _ => None,
+        };
+
+        let obj = match &primitave {
+            RustCastValue::Encoded(..) | RustCastValue::Wide(_) | RustCastValue::Uuid(_) | RustCastValue::Unknown => Some(Arc::new(obj.unbind())),
+            RustCastValue::Int(_) | RustCastValue::Float(_) | RustCastValue::Str(_) | RustCastValue::Bool(_) if !is_builtin(&py_type) => {
+                Some(Arc::new(obj.unbind()))
+            },
+            _ => None,
+        };
+
+        Ok(Self {
+            obj,
             primitave,
             hash,
+            items,
+            fields,
+        })
+    }
+
+    // a value of a declared field type, read without sniffing every other type first
+    // None when obj is not of that type, ints are taken as floats by float fields
+    pub fn of_type(obj: &Bound<'_, PyAny>, ty: FieldType) -> PyResult<Option<Self>> {
+        let py = obj.py();
+        let type_ptr = obj.get_type().as_ptr();
+        let primitave = match ty {
+            FieldType::Int if int_type_ptrs(py).contains(&type_ptr) => match obj.extract::<i64>() {
+                Ok(i) => RustCastValue::Int(i),
+                // past i64, kept exactly by the usual path
+                Err(_) => return Self::try_new(obj.clone()).map(Some),
+            },
+            FieldType::Float if float_type_ptrs(py).contains(&type_ptr) || int_type_ptrs(py).contains(&type_ptr) => {
+                RustCastValue::Float(obj.extract::<f64>()?)
+            },
+            FieldType::Str if str_type_ptrs(py).contains(&type_ptr) => RustCastValue::Str(StrValue::new(obj.extract::<&str>()?)),
+            FieldType::Bool if bool_type_ptrs(py).contains(&type_ptr) => RustCastValue::Bool(obj.extract::<bool>()?),
+            FieldType::Bytes if obj.get_type().is(PyBytes::type_object(py)) => {
+                RustCastValue::Bytes(Arc::from(obj.downcast::<PyBytes>()?.as_bytes()))
+            },
+            _ => return Ok(None),
+        };
+        let mut value = Self::from_primitave(primitave);
+        // ints a float field converts read back as floats
+        if !is_builtin(&obj.get_type()) && !(matches!(ty, FieldType::Float) && int_type_ptrs(py).contains(&type_ptr)) {
+            value.obj = Some(Arc::new(obj.clone().unbind()));
+        }
+        Ok(Some(value))
+    }
+
+    fn opaque(obj: Bound<'_, PyAny>) -> Self {
+        let mut hasher = FxHasher::default();
+        hasher.write_usize(obj.as_ptr() as usize);
+        Self {
+            obj: Some(Arc::new(obj.unbind())),
+            primitave: RustCastValue::Unknown,
+            hash: hasher.finish(),
+            items: None,
+            fields: None,
+        }
+    }
+
+    // exact i64 / f64 values go to the numeric index like any number
+    fn wide_primitave(num: WideNum) -> RustCastValue {
+        match num.to_key() {
+            Some(key) => RustCastValue::Encoded(KeySpace::Number, key),
+            None => RustCastValue::Wide(Arc::new(num)),
+        }
+    }
+
+    fn snapshot(py: Python, itr: &PyIterable) -> PyResult<Arc<[PyValue]>> {
+        match itr {
+            PyIterable::List(list) => list.bind(py).iter().map(PyValue::try_new).collect(),
+            PyIterable::Tuple(tuple) => tuple.bind(py).iter().map(PyValue::try_new).collect(),
+            PyIterable::Set(set) => set.bind(py).iter().map(PyValue::try_new).collect(),
+            PyIterable::Dict(dict) => dict.bind(py).iter()
+                .flat_map(|(key, value)| [PyValue::try_new(key), PyValue::try_new(value)])
+                .collect(),
         }
     }
 
-    fn hash_primitave(primitave: &RustCastValue) -> u64 {
+    // the hash an index using mode keys this value by, only Indexables, and containers holding them, differ from the cached hash
+    pub fn hash_in(&self, mode: HashMode) -> u64 {
+        match (mode, &self.primitave, &self.items) {
+            (HashMode::Identity, _, _) => self.hash,
+            (_, RustCastValue::Ind(_), _) => Self::hash_primitave(&self.primitave, mode),
+            (_, RustCastValue::Iterable(itr), Some(items)) => Self::hash_iterable_content(itr, items, mode),
+            _ => self.hash,
+        }
+    }
+
+    fn hash_primitave(primitave: &RustCastValue, mode: HashMode) -> u64 {
         let mut hasher = FxHasher::default();
         match &primitave {
             RustCastValue::Int(i) => {
@@ -124,12 +322,24 @@ impl PyValue {
             RustCastValue::Str(s) => {
                 s.hash(&mut hasher);
             },
-            RustCastValue::Ind(ind) => {
-                hasher.write_u64(ind.python_handle.as_ptr() as u64)
+            RustCastValue::Ind(ind) => match mode {
+                HashMode::Identity => hasher.write_u64(ind.python_handle.as_ptr() as u64),
+                HashMode::Stable => hasher.write_u64(ind.owned_handle.id as u64),
             },
             RustCastValue::Iterable(itr) => {
-                hasher.write_u64(itr as *const _ as u64)
+                hasher.write_u64(itr.as_ptr() as u64)
+            },
+            RustCastValue::Encoded(space, key) => {
+                space.hash(&mut hasher);
+                match key {
+                    Key::Int(i) => hasher.write_u64(i.cast_unsigned()),
+                    Key::FloatOrdered(f) => hasher.write_u64(f.0.to_bits()),
+                }
             },
+            RustCastValue::Wide(num) => num.hash(&mut hasher),
+            RustCastValue::Bytes(bytes) => bytes.hash(&mut hasher),
+            RustCastValue::Uuid(uuid) => hasher.write_u128(*uuid),
+            RustCastValue::None => hasher.write_u64(0u64),
             RustCastValue::Unknown => hasher.write_u64(0u64),
         };
         hasher.write_u8({
@@ -140,19 +350,90 @@ impl PyValue {
                 RustCastValue::Bool(_) => 4,
                 RustCastValue::Iterable(_) => 5,
                 RustCastValue::Ind(_) => 6,
-                RustCastValue::Unknown => 7
+                RustCastValue::Unknown => 7,
+                RustCastValue::Encoded(..) => 8,
+                RustCastValue::None => 9,
+                RustCastValue::Wide(_) => 10,
+                RustCastValue::Bytes(_) => 11,
+                RustCastValue::Uuid(_) => 12,
             }
         });
         hasher.finish()
     }
 
+    fn hash_iterable_content(itr: &PyIterable, items: &[PyValue], mode: HashMode) -> u64 {
+        let mut hasher = FxHasher::default();
+        match itr {
+            PyIterable::List(_) | PyIterable::Tuple(_) => {
+                hasher.write_u8(if matches!(itr, PyIterable::List(_)) { 5 } else { 6 });
+                for item in items.iter() {
+                    hasher.write_u64(item.hash_in(mode));
+                }
+            },
+            // unordered, so element hashes are combined commutatively
+            PyIterable::Set(_) => {
+                hasher.write_u8(7);
+                let sum = items.iter().fold(0u64, |acc, item| acc.wrapping_add(item.hash_in(mode)));
+                hasher.write_u64(sum);
+            },
+            PyIterable::Dict(_) => {
+                hasher.write_u8(8);
+                let sum = items.chunks(2).fold(0u64, |acc, pair| {
+                    let mut pair_hasher = FxHasher::default();
+                    for item in pair {
+                        pair_hasher.write_u64(item.hash_in(mode));
+                    }
+                    acc.wrapping_add(pair_hasher.finish())
+                });
+                hasher.write_u64(sum);
+            },
+        }
+        hasher.finish()
+    }
+
+    fn iterable_eq(a: &PyIterable, a_items: &[PyValue], b: &PyIterable, b_items: &[PyValue]) -> bool {
+        if std::mem::discriminant(a) != std::mem::discriminant(b) || a_items.len() != b_items.len() {
+            return false;
+        }
+        match a {
+            PyIterable::List(_) | PyIterable::Tuple(_) => a_items == b_items,
+            PyIterable::Set(_) => a_items.iter().all(|item| b_items.contains(item)),
+            PyIterable::Dict(_) => a_items.chunks(2).all(|pair| b_items.chunks(2).any(|other| other == pair)),
+        }
+    }
+
+    // the extracted sub fields of an Unknown value, if its type has an extractor
+    pub fn fields(&self) -> Option<&StoredIndexable> {
+        self.fields.as_deref()
+    }
+
+    // frozen iterable contents, empty for every other value
+    pub fn items(&self) -> &[PyValue] {
+        self.items.as_deref().unwrap_or(&[])
+    }
+
     pub fn from_primitave(prim: RustCastValue) -> Self {
-        let hash = Self::hash_primitave(&prim);
+        let hash = Self::hash_primitave(&prim, HashMode::Identity);
 
         Self {
             obj: None,
             primitave: prim,
             hash,
+            items: None,
+            fields: None,
+        }
+    }
+
+    // same python object, indexed under key in space
+    pub fn with_key(&self, space: KeySpace, key: Key) -> Self {
+        let primitave = RustCastValue::Encoded(space, key);
+        let obj = self.obj.clone().unwrap_or_else(|| Arc::new(Python::with_gil(|py| self.get_obj(py))));
+        Self {
+            obj: Some(obj),
+            hash: Self::hash_primitave(&primitave, HashMode::Identity),
+            primitave,
+            items: None,
+            fields: None,
         }
     }
 
@@ -160,21 +441,47 @@ impl PyValue {
         &self.primitave
     }
 
+    pub fn as_f64(&self) -> Option<f64> {
+        match &self.primitave {
+            RustCastValue::Int(i) => Some(*i as f64),
+            RustCastValue::Float(f) => Some(*f),
+            RustCastValue::Wide(num) => Some(num.approx_f64()),
+            _ => None,
+        }
+    }
+
+    pub fn is_none(&self) -> bool {
+        matches!(self.primitave, RustCastValue::None)
+    }
+
     pub fn get_hash(&self) -> u64 {
         self.hash
     }
 
     pub fn get_obj(&self, py: Python) -> Py<PyAny> {
+        if let Some(obj) = &self.obj {
+            return obj.clone_ref(py);
+        }
         match &self.primitave {
             RustCastValue::Int(v) => v.into_py_any(py).unwrap(),
             RustCastValue::Float(v) => v.into_py_any(py).unwrap(),
             RustCastValue::Bool(v) => v.into_py_any(py).unwrap(),
             RustCastValue::Str(v) => v.into_py_any(py).unwrap(),
+            RustCastValue::Bytes(v) => PyBytes::new(py, v).into_any().unbind(),
+            RustCastValue::Ind(ind) => ind.python_handle.clone_ref(py).into_any(),
+            RustCastValue::Iterable(itr) => itr.clone_ref(py),
+            RustCastValue::None => py.None(),
             _ => self.obj.as_ref().unwrap().clone_ref(py)
         }
     }
 }
 
+// numpy scalars are read like the builtins but handed back as given, only the builtins themselves are rebuilt
+fn is_builtin(ty: &Bound<'_, PyType>) -> bool {
+    let py = ty.py();
+    ty.is(PyInt::type_object(py)) || ty.is(PyFloat::type_object(py)) || ty.is(PyString::type_object(py)) || ty.is(PyBool::type_object(py))
+}
+
 impl PartialEq for PyValue {
     fn eq(&self, other: &Self) -> bool {
         if self.hash != other.hash {
@@ -183,8 +490,8 @@ impl PartialEq for PyValue {
         match (&self.primitave, &other.primitave) {
             (RustCastValue::Int(a), RustCastValue::Int(b)) => a == b,
             (RustCastValue::Float(a), RustCastValue::Float(b)) => a == b,
-            (RustCastValue::Int(a), RustCastValue::Float(b)) => (*a as f64) == *b,
-            (RustCastValue::Float(a), RustCastValue::Int(b)) => *a == (*b as f64),
+            (RustCastValue::Int(a), RustCastValue::Float(b)) => cmp_int_float(*a, *b) == Ordering::Equal,
+            (RustCastValue::Float(a), RustCastValue::Int(b)) => cmp_int_float(*b, *a) == Ordering::Equal,
             (RustCastValue::Bool(a), RustCastValue::Int(b)) => (*a as i64) == *b,
             (RustCastValue::Int(a), RustCastValue::Bool(b)) => *a == (*b as i64),
             (RustCastValue::Bool(a), RustCastValue::Bool(b)) => a == b,
@@ -192,8 +499,13 @@ impl PartialEq for PyValue {
             // fallback to pointer identity
             (RustCastValue::Ind(a), RustCastValue::Ind(b)) => a.python_handle.as_ptr() == b.python_handle.as_ptr(),
             (RustCastValue::Iterable(a), RustCastValue::Iterable(b)) => {
-                std::ptr::eq(a as *const _, b as *const _)
+                Self::iterable_eq(a, self.items(), b, other.items())
             },
+            (RustCastValue::Encoded(space_a, a), RustCastValue::Encoded(space_b, b)) => space_a == space_b && a == b,
+            (RustCastValue::Wide(a), RustCastValue::Wide(b)) => a == b,
+            (RustCastValue::Bytes(a), RustCastValue::Bytes(b)) => a == b,
+            (RustCastValue::Uuid(a), RustCastValue::Uuid(b)) => a == b,
+            (RustCastValue::None, RustCastValue::None) => true,
             (RustCastValue::Unknown, RustCastValue::Unknown) => {
                 std::ptr::eq(
                     self.obj.as_ref().unwrap().as_ptr(),
@@ -211,6 +523,8 @@ impl Clone for PyValue {
             obj: self.obj.clone(),
             primitave: self.primitave.clone(),
             hash: self.hash,
+            items: self.items.clone(),
+            fields: self.fields.clone(),
         }
     }
 }
@@ -223,6 +537,33 @@ impl Hash for PyValue {
     }
 }
 
+// a value keyed by its hash under an index's hash mode, equality is still the value's own
+#[derive(Clone, Debug)]
+pub struct ExactKey {
+    hash: u64,
+    pub value: PyValue,
+}
+
+impl ExactKey {
+    pub fn new(value: PyValue, mode: HashMode) -> Self {
+        Self { hash: value.hash_in(mode), value }
+    }
+}
+
+impl PartialEq for ExactKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash && self.value == other.value
+    }
+}
+
+impl Eq for ExactKey {}
+
+impl Hash for ExactKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
+
 impl<'py> IntoPyObject<'py> for PyValue {
     type Target = PyAny;
     type Output = Bound<'py, Self::Target>;
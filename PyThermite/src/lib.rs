@@ -4,6 +4,17 @@ use index::Indexable;
 use index::PyQueryExpr;
 use index::FilteredIndex;
 use index::Index;
+use index::{PreparedQuery, QueryParam};
+use index::{PyAttr, attr};
+use index::{handles, why_not};
+use index::{get_hash_mode, set_hash_mode};
+use index::{register_encoder, register_extractor, unregister_encoder, unregister_extractor};
+use index::{register_builtin_operators, register_operator, unregister_operator};
+use index::PyIdSet;
+use index::ResultIter;
+use index::IngestStream;
+use index::PyIndexConfig;
+use index::BulkUpdate;
 
 /// Formats the sum of two numbers as string.
 #[pyfunction]
@@ -19,5 +30,25 @@ fn PyThermite(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<Indexable>()?;
     m.add_class::<FilteredIndex>()?;
     m.add_class::<PyQueryExpr>()?;
+    m.add_class::<PreparedQuery>()?;
+    m.add_class::<QueryParam>()?;
+    m.add_class::<PyAttr>()?;
+    m.add_class::<PyIdSet>()?;
+    m.add_class::<ResultIter>()?;
+    m.add_class::<IngestStream>()?;
+    m.add_class::<PyIndexConfig>()?;
+    m.add_class::<BulkUpdate>()?;
+    m.add_function(wrap_pyfunction!(attr, m)?)?;
+    m.add_function(wrap_pyfunction!(why_not, m)?)?;
+    m.add_function(wrap_pyfunction!(handles, m)?)?;
+    m.add_function(wrap_pyfunction!(set_hash_mode, m)?)?;
+    m.add_function(wrap_pyfunction!(get_hash_mode, m)?)?;
+    m.add_function(wrap_pyfunction!(register_encoder, m)?)?;
+    m.add_function(wrap_pyfunction!(unregister_encoder, m)?)?;
+    m.add_function(wrap_pyfunction!(register_extractor, m)?)?;
+    m.add_function(wrap_pyfunction!(unregister_extractor, m)?)?;
+    register_builtin_operators();
+    m.add_function(wrap_pyfunction!(register_operator, m)?)?;
+    m.add_function(wrap_pyfunction!(unregister_operator, m)?)?;
     Ok(())
 }
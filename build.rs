@@ -0,0 +1,367 @@
+// writes PyThermite.pyi to OUT_DIR from the python facing items of the sources: #[pyclass] structs,
+// the methods of their #[pymethods] blocks and #[pyfunction]s. signatures come from #[pyo3(signature = ...)]
+// and the rust types, docstrings from the doc comments, which pyo3 also exposes as __doc__ at runtime.
+// tests/stub.rs fails while the checked in PyThermite/PyThermite.pyi differs from it
+use std::{collections::HashMap, env, fs, path::{Path, PathBuf}};
+
+use syn::{ext::IdentExt, Attribute, Expr, FnArg, GenericArgument, ImplItem, Item, Lit, Meta, Pat, PathArguments, ReturnType, Signature, Type};
+
+const SOURCES: &str = "PyThermite/src";
+const STUB: &str = "PyThermite.pyi";
+const HEADER: &str = "# generated by build.rs from the signatures and doc comments in PyThermite/src, edit those instead\n\
+# and update this file with UPDATE_STUB=1 cargo test --test stub\n\
+from typing import Any\n";
+
+struct Class {
+    rust: String,
+    name: String,
+    doc: Vec<String>,
+    members: Vec<String>,
+}
+
+struct Param {
+    name: String,
+    ty: Option<String>,
+    default: Option<String>,
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", SOURCES);
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let mut files = vec![];
+    rust_files(Path::new(SOURCES), &mut files);
+    let parsed: Vec<syn::File> = files.iter()
+        .filter_map(|path| syn::parse_file(&fs::read_to_string(path).ok()?).ok())
+        .collect();
+
+    // every class first, methods may name classes declared in later files
+    let mut classes: Vec<Class> = vec![];
+    for file in &parsed {
+        for item in &file.items {
+            if let Item::Struct(item) = item {
+                if let Some(attr) = item.attrs.iter().find(|attr| attr.path().is_ident("pyclass")) {
+                    let rust = item.ident.to_string();
+                    let name = named(attr).unwrap_or_else(|| rust.clone());
+                    classes.push(Class { rust, name, doc: doc(&item.attrs), members: vec![] });
+                }
+            }
+        }
+    }
+    let names: HashMap<String, String> = classes.iter().map(|class| (class.rust.clone(), class.name.clone())).collect();
+
+    let mut functions = vec![];
+    for file in &parsed {
+        for item in &file.items {
+            match item {
+                Item::Impl(block) if has(&block.attrs, "pymethods") => {
+                    let Type::Path(path) = &*block.self_ty else {
+                        continue;
+                    };
+                    let rust = path.path.segments.last().map(|seg| seg.ident.to_string()).unwrap_or_default();
+                    let Some(class) = classes.iter().position(|class| class.rust == rust) else {
+                        continue;
+                    };
+                    let types = Types { names: &names, this: classes[class].name.clone(), method: true };
+                    for member in &block.items {
+                        if let ImplItem::Fn(method) = member {
+                            classes[class].members.extend(method_stubs(&method.attrs, &method.sig, &types));
+                        }
+                    }
+                }
+                Item::Fn(func) if has(&func.attrs, "pyfunction") => {
+                    let types = Types { names: &names, this: String::new(), method: false };
+                    let name = func.attrs.iter().filter_map(named).next().unwrap_or_else(|| func.sig.ident.to_string());
+                    functions.push(def(&name, &params(&func.attrs, &func.sig, &types), returns(&func.sig, &types), &doc(&func.attrs), ""));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut stub = String::from(HEADER);
+    for class in &classes {
+        stub.push_str(&format!("\nclass {}:\n", class.name));
+        stub.push_str(&docstring(&class.doc, "    "));
+        if class.doc.is_empty() && class.members.is_empty() {
+            stub.push_str("    ...\n");
+        }
+        for member in &class.members {
+            stub.push_str(member);
+        }
+    }
+    for function in &functions {
+        stub.push('\n');
+        stub.push_str(function);
+    }
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("cargo sets OUT_DIR for build scripts"));
+    fs::write(out_dir.join(STUB), stub).expect("writing the stub to OUT_DIR");
+}
+
+fn rust_files(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            rust_files(&path, out);
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            out.push(path);
+        }
+    }
+}
+
+fn has(attrs: &[Attribute], name: &str) -> bool {
+    attrs.iter().any(|attr| attr.path().is_ident(name))
+}
+
+fn doc(attrs: &[Attribute]) -> Vec<String> {
+    attrs.iter().filter_map(|attr| match &attr.meta {
+        Meta::NameValue(meta) if meta.path.is_ident("doc") => match &meta.value {
+            Expr::Lit(expr) => match &expr.lit {
+                Lit::Str(text) => Some(text.value().strip_prefix(' ').map(str::to_string).unwrap_or_else(|| text.value())),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }).collect()
+}
+
+// the tokens of #[pyo3(...)], #[pyclass(...)] or #[pyfunction(...)], spaced by proc-macro2
+fn tokens(attr: &Attribute) -> Option<String> {
+    match &attr.meta {
+        Meta::List(list) if ["pyo3", "pyclass", "pyfunction"].iter().any(|name| list.path.is_ident(name)) => Some(list.tokens.to_string()),
+        _ => None,
+    }
+}
+
+// splits on the commas outside brackets and strings, up to the bracket closing the list
+fn split_top(list: &str) -> Vec<String> {
+    let mut entries = vec![];
+    let mut current = String::new();
+    let (mut depth, mut quoted) = (0, false);
+    for ch in list.chars() {
+        match ch {
+            '"' => quoted = !quoted,
+            '(' | '[' if !quoted => depth += 1,
+            ')' | ']' if !quoted && depth == 0 => break,
+            ')' | ']' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0 => {
+                entries.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(ch);
+    }
+    entries.push(current);
+    entries.into_iter().map(|entry| entry.trim().to_string()).filter(|entry| !entry.is_empty()).collect()
+}
+
+// name = "..." renaming the item in python
+fn named(attr: &Attribute) -> Option<String> {
+    split_top(&tokens(attr)?).into_iter()
+        .find_map(|entry| Some(entry.strip_prefix("name = \"")?.strip_suffix('"')?.to_string()))
+}
+
+// the entries of signature = (...)
+fn signature(attrs: &[Attribute]) -> Option<Vec<String>> {
+    let tokens = attrs.iter().filter_map(tokens).find(|tokens| tokens.contains("signature = ("))?;
+    Some(split_top(&tokens[tokens.find("signature = (")? + "signature = (".len()..]))
+}
+
+// rust default expressions as python, ... when there is no literal equivalent
+fn default(expr: &str) -> String {
+    let expr = expr.trim();
+    if let Some(inner) = expr.strip_prefix("Some (").and_then(|inner| inner.strip_suffix(')')) {
+        return default(inner);
+    }
+    match expr {
+        "None" => "None".to_string(),
+        "true" => "True".to_string(),
+        "false" => "False".to_string(),
+        _ if expr.starts_with('"') => expr.to_string(),
+        _ if expr.replace(' ', "").parse::<f64>().is_ok() => expr.replace(' ', ""),
+        _ => "...".to_string(),
+    }
+}
+
+struct Types<'a> {
+    names: &'a HashMap<String, String>,
+    // the python name of Self
+    this: String,
+    // in a #[pymethods] block
+    method: bool,
+}
+
+impl Types<'_> {
+    fn args<'b>(&self, args: &'b PathArguments) -> Vec<&'b Type> {
+        match args {
+            PathArguments::AngleBracketed(args) => args.args.iter().filter_map(|arg| match arg {
+                GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            }).collect(),
+            _ => vec![],
+        }
+    }
+
+    fn render(&self, ty: &Type) -> String {
+        match ty {
+            Type::Reference(inner) => self.render(&inner.elem),
+            Type::Paren(inner) => self.render(&inner.elem),
+            Type::Group(inner) => self.render(&inner.elem),
+            Type::Tuple(tuple) if tuple.elems.is_empty() => "None".to_string(),
+            Type::Tuple(tuple) => format!("tuple[{}]", tuple.elems.iter().map(|ty| self.render(ty)).collect::<Vec<_>>().join(", ")),
+            Type::Slice(slice) => self.sequence(&slice.elem),
+            Type::Array(array) => self.sequence(&array.elem),
+            Type::Path(path) => {
+                let Some(last) = path.path.segments.last() else {
+                    return "Any".to_string();
+                };
+                let args = self.args(&last.arguments);
+                let arg = |i: usize| args.get(i).map_or_else(|| "Any".to_string(), |ty| self.render(ty));
+                match last.ident.to_string().as_str() {
+                    "Option" => format!("{} | None", arg(0)),
+                    "PyResult" | "Result" | "Py" | "Bound" | "Borrowed" | "PyRef" | "PyRefMut" | "Box" | "Arc" => arg(0),
+                    "Vec" | "VecDeque" => args.first().map_or_else(|| "list".to_string(), |ty| self.sequence(ty)),
+                    "SmallVec" => match args.first() {
+                        Some(Type::Array(array)) => self.sequence(&array.elem),
+                        _ => "list".to_string(),
+                    },
+                    "HashSet" | "FxHashSet" | "BTreeSet" => format!("set[{}]", arg(0)),
+                    "HashMap" | "FxHashMap" | "BTreeMap" => format!("dict[{}, {}]", arg(0), arg(1)),
+                    "String" | "str" | "SmolStr" | "Cow" | "PyString" => "str".to_string(),
+                    "u8" | "u16" | "u32" | "u64" | "u128" | "usize" | "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "PyInt" => "int".to_string(),
+                    "f32" | "f64" | "PyFloat" => "float".to_string(),
+                    "bool" | "PyBool" => "bool".to_string(),
+                    "PyDict" => "dict".to_string(),
+                    "PyList" => "list".to_string(),
+                    "PyTuple" => "tuple".to_string(),
+                    "PySet" | "PyFrozenSet" => "set".to_string(),
+                    "PyBytes" => "bytes".to_string(),
+                    "PyType" => "type".to_string(),
+                    "Self" => self.this.clone(),
+                    other => self.names.get(other).cloned().unwrap_or_else(|| "Any".to_string()),
+                }
+            }
+            _ => "Any".to_string(),
+        }
+    }
+
+    // pyo3 turns byte vectors and slices into bytes
+    fn sequence(&self, elem: &Type) -> String {
+        match self.render(elem).as_str() {
+            "int" if matches!(elem, Type::Path(path) if path.path.is_ident("u8")) => "bytes".to_string(),
+            inner => format!("list[{}]", inner),
+        }
+    }
+}
+
+// arguments python passes, without self, the Python token and the slf or cls methods take in place of self
+fn rust_params(attrs: &[Attribute], sig: &Signature, types: &Types) -> Vec<(String, String)> {
+    let typed_receiver = types.method
+        && !sig.inputs.iter().any(|arg| matches!(arg, FnArg::Receiver(_)))
+        && !has(attrs, "staticmethod")
+        && (!has(attrs, "new") || has(attrs, "classmethod"));
+    sig.inputs.iter()
+        .filter_map(|arg| match arg {
+            FnArg::Typed(arg) => match &*arg.pat {
+                Pat::Ident(pat) => Some((pat.ident.unraw().to_string(), &*arg.ty)),
+                _ => None,
+            },
+            FnArg::Receiver(_) => None,
+        })
+        .skip(typed_receiver as usize)
+        .filter(|(_, ty)| !matches!(ty, Type::Path(path) if path.path.segments.last().is_some_and(|seg| seg.ident == "Python")))
+        .map(|(name, ty)| (name, types.render(ty)))
+        .collect()
+}
+
+fn params(attrs: &[Attribute], sig: &Signature, types: &Types) -> Vec<Param> {
+    let rust = rust_params(attrs, sig, types);
+    let ty_of = |name: &str| rust.iter().find(|(rust_name, _)| rust_name == name).map(|(_, ty)| ty.clone());
+    let Some(entries) = signature(attrs) else {
+        return rust.into_iter().map(|(name, ty)| Param { name, ty: Some(ty), default: None }).collect();
+    };
+    entries.iter().map(|entry| {
+        let entry = entry.replace("* *", "**");
+        if let Some(name) = entry.strip_prefix("**").or_else(|| entry.strip_prefix('*')) {
+            let stars = if entry.starts_with("**") { "**" } else { "*" };
+            return Param { name: format!("{}{}", stars, name.trim()), ty: None, default: None };
+        }
+        // python sees raw identifiers like r#type without the prefix
+        let (name, value) = entry.split_once('=').map_or((entry.as_str(), None), |(name, value)| (name, Some(value)));
+        let name = name.trim().trim_start_matches("r#");
+        Param { name: name.to_string(), ty: ty_of(name), default: value.map(default) }
+    }).collect()
+}
+
+fn returns(sig: &Signature, types: &Types) -> Option<String> {
+    match &sig.output {
+        ReturnType::Default => None,
+        ReturnType::Type(_, ty) => Some(types.render(ty)).filter(|ty| ty != "None"),
+    }
+}
+
+fn docstring(doc: &[String], indent: &str) -> String {
+    if doc.is_empty() {
+        return String::new();
+    }
+    let mut out = format!("{}'''\n", indent);
+    for line in doc {
+        match line.is_empty() {
+            true => out.push('\n'),
+            false => out.push_str(&format!("{}{}\n", indent, line)),
+        }
+    }
+    out.push_str(&format!("{}'''\n", indent));
+    out
+}
+
+// methods keep their docstring one level in and the ellipsis at the def, like the hand written stubs did
+fn def(name: &str, params: &[Param], ret: Option<String>, doc: &[String], indent: &str) -> String {
+    let params: Vec<String> = params.iter().map(|param| {
+        let mut out = param.name.clone();
+        if let Some(ty) = &param.ty {
+            out.push_str(&format!(": {}", ty));
+        }
+        if let Some(default) = &param.default {
+            out.push_str(&format!(" = {}", default));
+        }
+        out
+    }).collect();
+    let head = format!("{}def {}({}){}:", indent, name, params.join(", "), ret.map(|ret| format!(" -> {}", ret)).unwrap_or_default());
+    if doc.is_empty() {
+        return format!("{} ...\n", head);
+    }
+    let body = if indent.is_empty() { "    " } else { indent };
+    format!("{}\n{}{}...\n", head, docstring(doc, &format!("{}    ", indent)), body)
+}
+
+fn method_stubs(attrs: &[Attribute], sig: &Signature, types: &Types) -> Vec<String> {
+    if has(attrs, "setter") {
+        return vec![];
+    }
+    let name = attrs.iter().filter_map(named).next().unwrap_or_else(|| sig.ident.to_string());
+    let ret = returns(sig, types);
+    let doc = doc(attrs);
+    if has(attrs, "getter") {
+        let name = name.strip_prefix("get_").unwrap_or(&name);
+        return vec![format!("    {}: {}\n", name, ret.unwrap_or_else(|| "Any".to_string()))];
+    }
+    if has(attrs, "new") {
+        return vec![def("__init__", &params(attrs, sig, types), None, &doc, "    ")];
+    }
+    // one stub per operator, the CompareOp argument picks between them at runtime
+    if name == "__richcmp__" {
+        let other: Vec<Param> = params(attrs, sig, types).into_iter().take(1).collect();
+        return ["__eq__", "__ne__", "__lt__", "__le__", "__gt__", "__ge__"].iter()
+            .map(|op| def(op, &other, ret.clone(), &doc, "    "))
+            .collect();
+    }
+    vec![def(&name, &params(attrs, sig, types), ret, &doc, "    ")]
+}
@@ -0,0 +1,79 @@
+from datetime import datetime
+import random
+import string
+
+from PyThermite import Index, Indexable, QueryExpr as Q
+from threading import Thread
+
+class Record(Indexable):
+    pass
+
+def random_str(length=6):
+    return ''.join(random.choices(string.ascii_lowercase, k=length))
+
+def prep_data(size):
+    random.seed(42)
+
+    print("making build")
+    return [
+        Record(
+            id=i,
+            age=random.randint(18, 80),
+            score=random.random() * 100,
+            active=random.choice([True, False]),
+            country=random.choice(["US", "CA", "MX", "FR", "DE"]),
+            group=random_str(),
+            tags=random.choice(["a", "b", "c", "d"]),
+        )
+        for i in range(size)
+    ]
+
+QUERIES = [
+    Q.and_(Q.gt("age", 30), Q.lt("age", 40)),
+    Q.and_(Q.eq("country", "FR"), Q.eq("active", True)),
+    Q.and_(Q.in_("tags", ["a", "c"]), Q.gt("score", 50.0)),
+]
+
+# queries only, each thread runs rounds of every query, against the same index before and after freeze_mutations
+def multithreaded_query(index, thread_num, rounds, label):
+    def run_queries():
+        for _ in range(rounds):
+            for query in QUERIES:
+                index.reduced_query(query).ids()
+
+    threads = [Thread(target=run_queries) for _ in range(thread_num)]
+
+    start = datetime.now()
+
+    for thread in threads:
+        thread.start()
+
+    for thread in threads:
+        thread.join()
+
+    end = datetime.now()
+    queries = thread_num * rounds * len(QUERIES)
+    print(f"{label}: {queries} queries with {thread_num} threads: {end - start}, {queries / (end - start).total_seconds():.0f} queries/s")
+
+
+def compare(data, rounds):
+    index = Index()
+    index.add_object_many(data)
+    index.warmup()
+    expected = [len(index.reduced_query(query).ids()) for query in QUERIES]
+
+    for thread_num in (1, 2, 4, 8):
+        multithreaded_query(index, thread_num, rounds, f"{len(data)} records, unfrozen")
+
+    index.freeze_mutations()
+    assert [len(index.reduced_query(query).ids()) for query in QUERIES] == expected
+
+    for thread_num in (1, 2, 4, 8):
+        multithreaded_query(index, thread_num, rounds, f"{len(data)} records, frozen")
+
+
+if __name__ == "__main__":
+    data = prep_data(1_000_000)
+    compare(data[:10_000], 2_000)
+    compare(data[:100_000], 500)
+    compare(data[:1_000_000], 50)
@@ -23,6 +23,18 @@ for i in range(1_000_000):
     _ = pyth.x
 print(f"PyThermite time taken {datetime.now() - start}")
 
+start = datetime.now()
+for i in range(1_000_000):
+    py.x = i
+    _ = py.x
+print(f"native set + get time taken {datetime.now() - start}")
+
+start = datetime.now()
+for i in range(1_000_000):
+    pyth.x = i
+    _ = pyth.x
+print(f"PyThermite set + get time taken {datetime.now() - start}")
+
 start = datetime.now()
 for i in range(1_000_000):
     pass
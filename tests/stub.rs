@@ -0,0 +1,16 @@
+// PyThermite/PyThermite.pyi is generated by build.rs into OUT_DIR, a stale checked in copy fails here.
+// UPDATE_STUB=1 cargo test --test stub writes the current one in its place
+use std::{env, fs};
+
+const STUB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/PyThermite/PyThermite.pyi");
+const GENERATED: &str = include_str!(concat!(env!("OUT_DIR"), "/PyThermite.pyi"));
+
+#[test]
+fn stub_is_current() {
+    if env::var_os("UPDATE_STUB").is_some() {
+        fs::write(STUB, GENERATED).expect("writing PyThermite/PyThermite.pyi");
+        return;
+    }
+    let checked_in = fs::read_to_string(STUB).unwrap_or_default();
+    assert!(checked_in == GENERATED, "PyThermite/PyThermite.pyi is stale, update it with UPDATE_STUB=1 cargo test --test stub");
+}
@@ -1,8 +1,13 @@
 
 
+import math
+import queue
+import sys
+import weakref
+
 import pytest
 
-from PyThermite import Index, Indexable, QueryExpr as Q, FilteredIndex
+from PyThermite import Index, IndexConfig, Indexable, IdSet, QueryExpr as Q, FilteredIndex, why_not, handles, set_hash_mode, get_hash_mode, register_encoder, unregister_encoder, register_extractor, unregister_extractor, register_operator, unregister_operator
 
 class TestClass(Indexable):
     def some_method(self):
@@ -20,6 +25,11 @@ def index():
     return Index()
 
 
+# the attr values of the objects in index, or a FilteredIndex, matching query, sorted
+def matching(index, query, attr="num"):
+    return sorted(getattr(o, attr) for o in index.reduced_query(query).collect())
+
+
 def test_collect(index):
     # Test collecting from an empty index.
     result = index.collect()
@@ -109,6 +119,31 @@ def test_filtered_index(index):
     assert all(obj.active is True and obj.score > 50.0 for obj in result)
     assert len(result) == 2
 
+def test_filtered_set_algebra(index):
+    index.add_object_many([TestClass(num=i, active=(i % 2 == 0)) for i in range(10)])
+    active = index.reduced(active=True)
+    low = index.reduced_query(Q.lt("num", 4))
+
+    def nums(filtered):
+        return sorted(o.num for o in filtered.collect())
+
+    assert nums(active.union(low)) == [0, 1, 2, 3, 4, 6, 8]
+    assert nums(active.intersect(low)) == [0, 2]
+    assert nums(active.difference(low)) == [4, 6, 8]
+    assert nums(active.symmetric_difference(low)) == [1, 3, 4, 6, 8]
+    # the results can be queried further, and combined again
+    assert nums(active.union(low).reduced_query(Q.gt("num", 2))) == [3, 4, 6, 8]
+    assert nums(active.difference(low).intersect(index.reduced(num=6))) == [6]
+    assert active.union(low).coverage() == []
+
+    with pytest.raises(ValueError):
+        active.union(Index().reduced())
+    # the same objects held by another index
+    other = Index()
+    other.add_object_many(index.collect())
+    with pytest.raises(ValueError):
+        active.intersect(other.reduced(active=True))
+
 def test_reduce_index(index):
     objs = [TestClass(num=i, active=(i % 2 == 0), score=float(i) * 10.0) for i in range(10)]
     index.add_object_many(objs)
@@ -242,3 +277,3037 @@ def test_number_collissions_tuple(index):
         Q.eq("nums", 1023)
     )
     assert len(res.collect()) == 1
+
+def test_adopt(index):
+    objs = [TestClass(num=i, ind="A" if i % 2 else "B") for i in range(10)]
+    index.add_object_many(objs)
+
+    index_2 = Index()
+    index_2.adopt(index, Q.eq("ind", "A"))
+    assert len(index_2.collect()) == 5
+    assert all(r.ind == "A" for r in index_2.collect())
+
+    # adopting again does not duplicate
+    index_2.adopt(index)
+    assert len(index_2.collect()) == 10
+
+    # updates made through the shared objects reach both indexes
+    objs[1].num = 100
+    assert len(index.reduced_query(Q.eq("num", 100)).collect()) == 1
+    assert len(index_2.reduced_query(Q.eq("num", 100)).collect()) == 1
+    assert len(index_2.reduced_query(Q.eq("num", 1)).collect()) == 0
+
+def test_rebind(index):
+    objs = [TestClass(num=i, label=f"label_{i}", nested=TestClass(num=i * 10)) for i in range(5)]
+    index.add_object_many(objs)
+
+    saved = Q.and_(Q.ge("num", 2), Q.eq("nested.num", 30))
+    assert len(index.reduced_query(saved).collect()) == 1
+
+    renamed = [TestClass(count=i, inner=TestClass(num=i * 10)) for i in range(5)]
+    index_2 = Index()
+    index_2.add_object_many(renamed)
+
+    rebound = saved.rebind("num", "count").rebind("nested", "inner")
+    res = index_2.reduced_query(rebound).collect()
+    assert len(res) == 1
+    assert res[0].count == 3
+
+    # the original expression is untouched
+    assert len(index.reduced_query(saved).collect()) == 1
+
+def test_normalize(index):
+    objs = [TestClass(num=i, label="even" if i % 2 == 0 else "odd") for i in range(20)]
+    index.add_object_many(objs)
+
+    query = Q.and_(Q.gt("num", 5), Q.and_(Q.lt("num", 10), Q.gt("num", 7)))
+    normalized = query.normalize()
+    assert "And" in repr(query)
+    assert matching(index, query) == [8, 9]
+    assert matching(index, normalized) == [8, 9]
+
+    inverted = Q.bt("num", 6, 3).normalize()
+    assert repr(inverted) == "<QueryExpr: Or([])>"
+    assert index.reduced_query(inverted).collect() == []
+
+    double_not = Q.not_(Q.not_(Q.eq("label", "odd")))
+    assert "Not" not in repr(double_not.normalize())
+    assert len(index.reduced_query(double_not).collect()) == 10
+
+    contradiction = Q.and_(Q.eq("label", "odd"), Q.gt("num", 10), Q.lt("num", 5))
+    assert len(index.reduced_query(contradiction).collect()) == 0
+
+    # each bound can be met by a different nested child
+    index.add_object(TestClass(num=100, label="kids", kids=[TestClass(v=3), TestClass(v=12)]))
+    merged = index.reduced_query(Q.and_(Q.gt("kids.v", 10), Q.lt("kids.v", 5))).collect()
+    chained = index.reduced_query(Q.gt("kids.v", 10)).reduced_query(Q.lt("kids.v", 5)).collect()
+    assert [r.num for r in merged] == [r.num for r in chained] == [100]
+
+    either = Q.or_(Q.eq("label", "odd"), contradiction)
+    assert len(index.reduced_query(either).collect()) == 10
+
+def test_prepared_query(index):
+    objs = [TestClass(num=i, label="even" if i % 2 == 0 else "odd") for i in range(20)]
+    index.add_object_many(objs)
+
+    prepared = Q.and_(
+        Q.ge("num", Q.param("low")),
+        Q.lt("num", Q.param("high")),
+        Q.eq("label", Q.param("label")),
+    ).prepare()
+    assert prepared.params == ["high", "label", "low"]
+
+    res = index.reduced_query(prepared.bind(low=2, high=8, label="even")).collect()
+    assert sorted(r.num for r in res) == [2, 4, 6]
+
+    res = index.reduced_query(prepared.bind(low=10, high=15, label="odd")).collect()
+    assert sorted(r.num for r in res) == [11, 13]
+
+    with pytest.raises(ValueError):
+        prepared.bind(low=1, high=2)
+    with pytest.raises(ValueError):
+        prepared.bind(low=1, high=2, label="odd", other=3)
+
+def test_operator_queries(index):
+    from PyThermite import attr
+
+    objs = [TestClass(num=i, label="even" if i % 2 == 0 else "odd") for i in range(20)]
+    index.add_object_many(objs)
+
+    query = (attr("num") >= 4) & (attr("num") < 10) & (attr("label") == "even")
+    assert matching(index, query) == [4, 6, 8]
+
+    query = (attr("num") == 1) | (attr("num") == 19)
+    assert matching(index, query) == [1, 19]
+
+    query = ~(attr("label") == "odd") & attr("num").in_([2, 3, 4])
+    assert matching(index, query) == [2, 4]
+
+    mixed = Q.eq("label", "odd") & (attr("num") > 15)
+    assert matching(index, mixed) == [17, 19]
+
+def test_unsupported_operand_types(index):
+    objs = [TestClass(num=i, name=f"object_{i}") for i in range(5)]
+    index.add_object_many(objs)
+
+    with pytest.raises(TypeError, match="bt on 'name'.*got int"):
+        index.reduced_query(Q.bt("name", "a", 5))
+    with pytest.raises(TypeError, match="gt on 'child'.*got TestClass"):
+        index.reduced_query(Q.gt("child", TestClass()))
+    with pytest.raises(TypeError, match="starts_with on 'num'.*got int"):
+        index.reduced_query(Q.and_(Q.eq("num", 1), Q.starts_with("num", 1)))
+    with pytest.raises(TypeError):
+        index.reduced(num=1).reduced_query(Q.lt("num", [3]))
+
+    # index is still usable afterwards
+    assert len(index.reduced_query(Q.bt("num", 1, 3)).collect()) == 3
+
+def test_string_ranges(index):
+    from PyThermite import attr
+    codes = ["A100", "A150", "A200", "A2000", "B100", "a100", ""]
+    objs = [TestClass(code=c, tags=[c, 1]) for c in codes] + [TestClass(code=150)]
+    index.add_object_many(objs)
+
+    assert matching(index, Q.bt("code", "A100", "A200"), "code") == ["A100", "A150", "A200"]
+    assert matching(index, Q.gt("code", "A200"), "code") == ["A2000", "B100", "a100"]
+    assert matching(index, Q.ge("code", "B"), "code") == ["B100", "a100"]
+    assert matching(index, Q.lt("code", "A15"), "code") == ["", "A100"]
+    assert matching(index, Q.le("code", ""), "code") == [""]
+    assert matching(index, Q.bt("code", "z", "a"), "code") == []
+    assert matching(index, Q.and_(Q.gt("code", "A"), Q.lt("code", "B"), Q.ne("code", "A150")), "code") == ["A100", "A200", "A2000"]
+    assert matching(index, attr("code") > "a", "code") == ["a100"]
+
+    # list elements, views and removals
+    assert matching(index, Q.bt("tags", "B", "Z"), "code") == ["B100"]
+    view = index.reduced_query(Q.ne("code", "A150"))
+    assert matching(view, Q.bt("code", "A1", "A2"), "code") == ["A100"]
+    objs[0].code = "C"
+    assert matching(index, Q.bt("code", "A100", "A200"), "code") == ["A150", "A200"]
+    assert matching(index, Q.ge("code", "C"), "code") == ["C", "a100"]
+
+def test_attributes(index):
+    objs = [TestClass(num=i, name=f"object_{i}", tags=["a", i]) for i in range(4)]
+    objs[0].score = 1.5
+    objs[1].score = 2
+    objs[2].child = TestClass(name="child")
+    index.add_object_many(objs)
+
+    attrs = {a["name"]: a for a in index.attributes()}
+    assert list(attrs) == sorted(attrs)
+    assert attrs["num"]["types"] == ["int"]
+    assert attrs["num"]["count"] == 4
+    assert attrs["num"]["structures"] == ["numeric"]
+    assert attrs["name"]["structures"] == ["string"]
+    assert attrs["score"]["types"] == ["float", "int"]
+    assert attrs["score"]["count"] == 2
+    assert attrs["tags"]["types"] == ["list"]
+    assert attrs["tags"]["structures"] == ["numeric", "string"]
+    assert attrs["child"]["types"] == ["TestClass"]
+    assert attrs["child"]["nested"] is True
+    assert attrs["num"]["nested"] is False
+
+def test_add_object_many_with_lists(index):
+    objs = [TestClass(tags=["a", f"tag_{i}", i]) for i in range(5)]
+    index.add_object_many(objs)
+
+    assert len(index.reduced_query(Q.eq("tags", "a")).collect()) == 5
+    assert len(index.reduced_query(Q.eq("tags", "tag_3")).collect()) == 1
+    assert len(index.reduced_query(Q.eq("tags", 4)).collect()) == 1
+
+def test_query_planner(index):
+    objs = [TestClass(num=i, rare=(i == 7), label="even" if i % 2 == 0 else "odd", name=f"n{i}", bucket=i % 2) for i in range(100)]
+    index.add_object_many(objs)
+
+    # the rare flag narrows to one object, so it runs ahead of the wider range and the missing attribute short circuits
+    trace = index.explain(Q.and_(Q.ge("num", 5), Q.eq("rare", True)))
+    assert [c["clause"] for c in trace["children"]][0] == "rare == True"
+    assert trace["matched"] == 1
+    trace = index.explain(Q.and_(Q.ge("num", 5), Q.eq("missing", 1), Q.eq("label", "odd")))
+    assert trace["children"][0]["clause"] == "missing == 1"
+    assert trace["matched"] == 0
+
+    # ints, floats and strings are estimated from the index as well
+    trace = index.explain(Q.and_(Q.eq("bucket", 0), Q.eq("name", "n8")))
+    assert [c["clause"] for c in trace["children"]] == ['name == "n8"', "bucket == 0"]
+    trace = index.explain(Q.and_(Q.eq("label", "odd"), Q.eq("num", 7)))
+    assert [c["clause"] for c in trace["children"]] == ["num == 7", 'label == "odd"']
+    trace = index.explain(Q.and_(Q.eq("bucket", 1.0), Q.eq("num", 7.0)))
+    assert [c["clause"] for c in trace["children"]][0] == "num == 7.0"
+    assert trace["matched"] == 1
+
+    # not is pushed through and / or, which then flatten into the enclosing and
+    query = Q.and_(Q.lt("num", 50), Q.not_(Q.or_(Q.eq("label", "odd"), Q.lt("num", 10))))
+    normalized = repr(query.normalize())
+    assert normalized.startswith("<QueryExpr: And([Lt(") and "Ne(" in normalized and "Or(" not in normalized
+    expected = sorted(o.num for o in objs if o.num < 50 and not (o.label == "odd" or o.num < 10))
+    assert matching(index, query) == expected
+
+    query = Q.not_(Q.and_(Q.eq("label", "even"), Q.ge("num", 20)))
+    expected = sorted(o.num for o in objs if not (o.label == "even" and o.num >= 20))
+    assert matching(index, query) == expected
+    assert len(index.reduced_query(Q.not_(Q.or_())).collect()) == 100
+
+def test_ne_nin(index):
+    objs = [TestClass(num=i, key=i, label="even" if i % 2 == 0 else "odd", child=TestClass(size=i % 3)) for i in range(30)]
+    bare = TestClass(key=-1, other=1)
+    index.add_object_many(objs + [bare])
+
+    # objects without the attribute still match, same as negating eq / in
+    assert matching(index, Q.ne("num", 3), "key") == [-1] + [i for i in range(30) if i != 3]
+    assert matching(index, Q.nin("num", [3, 4, 99]), "key") == matching(index, Q.not_(Q.in_("num", [3, 4, 99])), "key")
+    assert -1 in matching(index, Q.nin("label", ["even"]), "key")
+    assert matching(index, Q.nin("child.size", [0, 1]), "key") == matching(index, Q.not_(Q.in_("child.size", [0, 1])), "key")
+    assert matching(index, Q.nin("missing", [1]), "key") == matching(index, Q.not_(Q.or_()), "key")
+
+    query = Q.and_(Q.lt("num", 20), Q.nin("child.size", [0]), Q.ne("label", "odd"))
+    expected = [i for i in range(20) if i % 3 != 0 and i % 2 == 0]
+    assert matching(index, query, "key") == expected
+    assert index.explain(query)["matched"] == len(expected)
+    assert matching(index, Q.or_(Q.ne("num", 1), Q.nin("num", [1])), "key") == matching(index, Q.ne("num", 1), "key")
+
+    assert str(Q.nin("num", [1, 2])) == "num not in [1, 2]"
+    assert repr(Q.not_(Q.in_("num", [1])).normalize()).startswith("<QueryExpr: Nin(")
+    assert repr(Q.not_(Q.nin("num", [1])).normalize()).startswith("<QueryExpr: In(")
+
+def test_exists_missing(index):
+    objs = [TestClass(num=i) for i in range(6)]
+    objs[1].tag = None
+    objs[2].tag = "a"
+    objs[3].meta = {"k": 1}
+    objs[4].owner = TestClass(name="bob")
+    objs[5].owner = TestClass(age=3)
+    index.add_object_many(objs[:3])
+    for obj in objs[3:]:
+        index.add_object(obj)
+
+    assert matching(index, Q.exists("tag")) == [1, 2]
+    assert matching(index, Q.missing("tag")) == [0, 3, 4, 5]
+    assert matching(index, Q.exists("meta")) == [3]
+    assert matching(index, Q.exists("owner.name")) == [4]
+    assert matching(index, Q.missing("owner.name")) == [0, 1, 2, 3, 5]
+    assert matching(index, Q.exists("nothing")) == []
+    assert matching(index, Q.missing("nothing")) == list(range(6))
+    assert matching(index, Q.and_(Q.missing("tag"), Q.lt("num", 4))) == [0, 3]
+
+    # changing a value keeps the attribute present, setting it on another object adds it
+    objs[2].tag = "b"
+    objs[0].tag = 7
+    assert matching(index, Q.exists("tag")) == [0, 1, 2]
+
+    assert str(Q.missing("tag")) == "missing(tag)"
+    assert repr(Q.not_(Q.exists("tag")).normalize()).startswith("<QueryExpr: Missing(")
+    assert index.explain(Q.missing("tag"))["matched"] == 3
+
+    index.reduce(num=1)
+    assert matching(index, Q.exists("tag")) == [1]
+    assert matching(index, Q.missing("tag")) == []
+
+def test_null_values(index):
+    class Opaque:
+        pass
+
+    objs = [TestClass(num=i) for i in range(6)]
+    objs[0].tag = None
+    objs[1].tag = None
+    objs[2].tag = 1
+    objs[3].tag = Opaque()
+    objs[4].items = [None, 2]
+    objs[5].owner = TestClass(tag=None)
+    index.add_object_many(objs[:3])
+    for obj in objs[3:]:
+        index.add_object(obj)
+
+    assert matching(index, Q.is_null("tag")) == [0, 1]
+    assert matching(index, Q.eq("tag", None)) == [0, 1]
+    assert matching(index, Q.is_not_null("tag")) == [2, 3]
+    assert matching(index, Q.ne("tag", None)) == [2, 3, 4, 5]
+    assert matching(index, Q.in_("tag", [None, 1])) == [0, 1, 2]
+    assert matching(index, Q.eq("items", None)) == [4]
+    assert matching(index, Q.is_null("owner.tag")) == [5]
+    assert matching(index, Q.is_null("nothing")) == []
+
+    objs[0].tag = 5
+    objs[2].tag = None
+    assert matching(index, Q.is_null("tag")) == [1, 2]
+    assert matching(index, Q.is_not_null("tag")) == [0, 3]
+
+    assert str(Q.is_null("tag")) == "tag == None"
+    attrs = {a["name"]: a for a in index.attributes()}
+    assert attrs["tag"]["structures"] == ["exact", "null", "numeric"]
+
+def test_why_not(index):
+    obj = TestClass(num=5, name="alpha", child=TestClass(score=3))
+    other = TestClass(num=1)
+    index.add_object_many([obj, other])
+
+    assert why_not(index, Q.and_(Q.eq("num", 5), Q.eq("child.score", 3)), obj) == []
+
+    failures = why_not(index, Q.and_(Q.gt("num", 10), Q.eq("name", "alpha"), Q.eq("child.score", 4)), obj)
+    assert [f["attr"] for f in failures] == ["num", "child.score"]
+    assert failures[0]["clause"] == "num > 10"
+    assert failures[0]["actual"] == 5
+    assert failures[1]["actual"] == 3
+
+    # an Or only fails when every branch fails
+    assert why_not(index, Q.or_(Q.eq("num", 1), Q.eq("name", "alpha")), obj) == []
+    failures = why_not(index, Q.or_(Q.eq("num", 1), Q.eq("missing", 2)), obj)
+    assert [f["attr"] for f in failures] == ["num", "missing"]
+    assert failures[1]["present"] is False
+    assert failures[1]["actual"] is None
+
+    with pytest.raises(ValueError):
+        why_not(index, Q.eq("num", 5), TestClass(num=5))
+
+def test_handles(index):
+    class Other(Indexable):
+        pass
+
+    objs = [TestClass(num=i, child=TestClass(score=i)) for i in range(3)]
+    index.add_object_many(objs + [Other(num=9)])
+
+    counts = handles(index)
+    assert counts["held"] == 4
+    assert counts["stale"] == 0
+    assert counts["handle_refs"] >= 4
+    assert counts["classes"] == {"TestClass": 3, "Other": 1}
+    assert counts["nested_indexes"] >= 2
+    assert counts["nested_held"] == 3
+
+    # reduce drops the handles along with the ids
+    index.reduce(num=0)
+    counts = handles(index)
+    assert counts["held"] == 1
+    assert counts["classes"] == {"TestClass": 1}
+    assert counts["nested_held"] == 1
+
+def test_max_str_len():
+    index = Index(max_str_len=8)
+    long_text = "lorem ipsum dolor sit amet"
+    index.add_object_many([TestClass(text=long_text), TestClass(text="lorem"), TestClass(text="x" * 9)])
+
+    assert len(index.reduced_query(Q.starts_with("text", "lorem")).collect()) == 2
+    assert len(index.reduced_query(Q.ends_with("text", "amet")).collect()) == 1
+    assert len(index.reduced_query(Q.contains("text", "dolor")).collect()) == 1
+    assert len(index.reduced_query(Q.eq("text", long_text)).collect()) == 1
+    assert len(index.reduced_query(Q.contains("text", "xxxxxxxxx")).collect()) == 1
+
+def test_unicode_string_matching(index):
+    index.add_object_many([TestClass(city="München"), TestClass(city="東京都"), TestClass(city="Mülheim")])
+
+    assert {o.city for o in index.reduced_query(Q.starts_with("city", "Mü")).collect()} == {"München", "Mülheim"}
+    assert [o.city for o in index.reduced_query(Q.ends_with("city", "京都")).collect()] == ["東京都"]
+    assert [o.city for o in index.reduced_query(Q.contains("city", "nch")).collect()] == ["München"]
+    assert [o.city for o in index.reduced_query(Q.contains("city", "京")).collect()] == ["東京都"]
+
+def test_matches():
+    from PyThermite import attr
+
+    index = Index(max_str_len=8)
+    skus = ["AB-12", "AB-1x", "AB-123", "ab-12", "CD-12", "AB-[1]", "AB-1234567890", "Mü-1"]
+    index.add_object_many([TestClass(sku=sku, owner=TestClass(sku=sku)) for sku in skus])
+
+    # globs match the whole string
+    assert matching(index, Q.matches("sku", "AB-??"), "sku") == ["AB-12", "AB-1x"]
+    assert matching(index, Q.matches("sku", "AB-1*"), "sku") == ["AB-12", "AB-123", "AB-1234567890", "AB-1x"]
+    assert matching(index, Q.matches("sku", "*-12"), "sku") == ["AB-12", "CD-12", "ab-12"]
+    assert matching(index, Q.matches("sku", "[A-C]?-1[0-9]"), "sku") == ["AB-12", "CD-12"]
+    assert matching(index, Q.matches("sku", "AB-1[!0-9]"), "sku") == ["AB-1x"]
+    assert matching(index, Q.matches("sku", "AB-\\[1]"), "sku") == ["AB-[1]"]
+    assert matching(index, Q.matches("sku", "M?-*"), "sku") == ["Mü-1"]
+    assert matching(index, Q.matches("sku", "AB-12"), "sku") == ["AB-12"]
+
+    # regexes are anchored too
+    assert matching(index, Q.matches("sku", "[A-Z]{2}-[0-9]+", regex=True), "sku") == ["AB-12", "AB-123", "AB-1234567890", "CD-12"]
+    assert matching(index, Q.matches("sku", "(?i)ab-12", regex=True), "sku") == ["AB-12", "ab-12"]
+    assert matching(index, Q.matches("sku", "AB|CD-12", regex=True), "sku") == ["CD-12"]
+
+    # nested attributes, the attr proxy and negation
+    assert matching(index, Q.matches("owner.sku", "CD-*"), "sku") == ["CD-12"]
+    assert matching(index, attr("sku").matches("ab-*"), "sku") == ["ab-12"]
+    assert matching(index, ~Q.matches("sku", "AB-*"), "sku") == ["CD-12", "Mü-1", "ab-12"]
+    assert str(Q.matches("sku", "AB-*")) == 'sku matches "AB-*"'
+
+    with pytest.raises(ValueError):
+        Q.matches("sku", "AB-[12")
+    with pytest.raises(ValueError):
+        attr("sku").matches("AB-\\")
+    with pytest.raises(ValueError):
+        Q.matches("sku", "AB-(", regex=True)
+
+def test_reduced_many(index):
+    index.add_object_many([
+        TestClass(num=i, region="eu" if i % 3 else "us", tag=TestClass(level=i % 4)) for i in range(30)
+    ])
+    active = Q.gt("num", 10)
+    queries = [
+        Q.and_(active, Q.eq("region", "eu")),
+        Q.and_(active, Q.eq("region", "us"), Q.lt("num", 25)),
+        Q.or_(active, Q.eq("tag.level", 1)),
+        Q.not_(active),
+        Q.and_(Q.not_(active), Q.or_(Q.eq("region", "us"), Q.eq("tag.level", 1))),
+        Q.eq("missing", 1),
+    ]
+
+    def nums(filtered):
+        return sorted(o.num for o in filtered.collect())
+
+    many = index.reduced_many(queries)
+    assert [nums(f) for f in many] == [nums(index.reduced_query(q)) for q in queries]
+    assert nums(many[1]) == [12, 15, 18, 21, 24]
+    assert nums(many[0].reduced_query(Q.lt("num", 14))) == [11, 13]
+    assert index.reduced_many([]) == []
+
+    with pytest.raises(TypeError):
+        index.reduced_many([Q.eq("num", 1), Q.starts_with("num", 1)])
+
+def test_repeated_subexpressions(index):
+    index.add_object_many([TestClass(num=i, flag=[True, 1, 1.0, "1"][i % 4], region="eu" if i % 3 else "us") for i in range(24)])
+
+    eu, late = Q.eq("region", "eu"), Q.ge("num", 12)
+    expr = Q.or_(
+        Q.and_(eu, late, Q.eq("flag", True)),
+        Q.and_(eu, late, Q.eq("flag", "1")),
+        Q.and_(Q.not_(Q.and_(eu, late)), Q.lt("num", 4)),
+        Q.and_(eu, late, Q.eq("flag", True)),
+    )
+    assert matching(index, expr) == [0, 1, 2, 3, 16, 19, 20, 23]
+
+    # equal in python but read from different maps, so never shared
+    assert matching(index, Q.or_(Q.and_(late, Q.eq("flag", True)), Q.and_(late, Q.eq("flag", 1)))) == [12, 13, 14, 16, 17, 18, 20, 21, 22]
+    assert matching(index, Q.or_(Q.and_(late, Q.eq("flag", True)), Q.and_(late, Q.eq("flag", True)))) == [12, 16, 20]
+
+def test_mixed_int_float_ranges(index):
+    big = 2 ** 53
+    index.add_object_many([TestClass(n=big + 1), TestClass(n=float(big)), TestClass(n=big - 1)])
+
+    assert [o.n for o in index.reduced_query(Q.gt("n", float(big))).collect()] == [big + 1]
+    assert {o.n for o in index.reduced_query(Q.ge("n", big)).collect()} == {big + 1, float(big)}
+    assert [o.n for o in index.reduced_query(Q.lt("n", float(big))).collect()] == [big - 1]
+    # bounds on the same attribute are merged exactly, not through f64
+    query = Q.and_(Q.gt("n", float(big)), Q.le("n", big + 1))
+    assert [o.n for o in index.reduced_query(query).collect()] == [big + 1]
+
+def test_stable_hash_mode(index):
+    assert get_hash_mode() == "identity"
+    with pytest.raises(ValueError):
+        set_hash_mode("random")
+
+    set_hash_mode("stable")
+    try:
+        assert get_hash_mode() == "stable"
+        child = TestClass(name="child")
+        objs = [TestClass(num=i, child=child if i % 2 else TestClass(name="other"), tags=[i, "x"]) for i in range(4)]
+        index.add_object_many(objs)
+
+        assert matching(index, Q.eq("child", child)) == [1, 3]
+        assert matching(index, Q.eq("tags", "x")) == [0, 1, 2, 3]
+        objs[1].child = TestClass(name="other")
+        assert [o.num for o in index.reduced_query(Q.eq("child", child)).collect()] == [3]
+    finally:
+        set_hash_mode("identity")
+
+def test_hash_mode_per_index():
+    stable = Index(config=IndexConfig().hash_mode("stable"))
+    plain = Index()
+    with pytest.raises(ValueError):
+        IndexConfig().hash_mode("random")
+    assert IndexConfig.from_dict(IndexConfig().hash_mode("stable").to_dict()) == IndexConfig().hash_mode("stable")
+
+    child = TestClass(name="child")
+    for idx in (stable, plain):
+        objs = [TestClass(num=i, child=child if i % 2 else TestClass(name="other"), pair=[child, i % 2]) for i in range(4)]
+        idx.add_object_many(objs)
+        assert matching(idx, Q.eq("child", child)) == [1, 3]
+        assert matching(idx, Q.eq("pair", [child, 1])) == [1, 3]
+        objs[1].child = TestClass(name="other")
+        assert [o.num for o in idx.reduced_query(Q.eq("child", child)).collect()] == [3]
+    assert get_hash_mode() == "identity"
+
+def test_iterable_content_equality(index):
+    a = TestClass(num=1, tags=[1, "x"])
+    b = TestClass(num=2, tags=[1, "x"])
+    c = TestClass(num=3, tags=("x", 1))
+    index.add_object_many([a, b, c])
+
+    assert matching(index, Q.eq("tags", [1, "x"])) == [1, 2]
+    assert [o.num for o in index.reduced_query(Q.eq("tags", ("x", 1))).collect()] == [3]
+
+    # in-place mutation is not seen until the attribute is reassigned
+    a.tags.append("y")
+    assert index.reduced_query(Q.eq("tags", "y")).collect() == []
+    a.tags = a.tags
+    assert [o.num for o in index.reduced_query(Q.eq("tags", "y")).collect()] == [1]
+    assert [o.num for o in index.reduced_query(Q.eq("tags", [1, "x"])).collect()] == [2]
+    assert matching(index, Q.eq("tags", "x")) == [1, 2, 3]
+
+def test_refresh_mutated_values(index):
+    a = TestClass(num=1, tags=["x", "y"], meta={"k": 1})
+    b = TestClass(num=2, tags=["x"])
+    index.add_object_many([a, b])
+
+    a.tags.remove("y")
+    a.tags.append("z")
+    assert a.reindex_attr("tags") is True
+    assert a.reindex_attr("tags") is False
+    assert index.reduced_query(Q.eq("tags", "y")).collect() == []
+    assert [o.num for o in index.reduced_query(Q.eq("tags", "z")).collect()] == [1]
+    assert matching(index, Q.eq("tags", "x")) == [1, 2]
+    assert [o.num for o in index.reduced_query(Q.eq("tags", ["x", "z"])).collect()] == [1]
+    with pytest.raises(AttributeError):
+        a.reindex_attr("missing")
+
+    b.tags.append("z")
+    assert index.refresh(b) == ["tags"]
+    assert index.refresh(b, ["tags", "num"]) == []
+    assert matching(index, Q.eq("tags", "z")) == [1, 2]
+
+    # removal after a refresh drops every element posting
+    b.tags = []
+    assert [o.num for o in index.reduced_query(Q.eq("tags", "z")).collect()] == [1]
+    assert [o.num for o in index.reduced_query(Q.eq("tags", "x")).collect()] == [1]
+
+    with pytest.raises(ValueError):
+        Index().refresh(a)
+
+def test_coerce_numeric_strings():
+    index = Index()
+    index.coerce_numeric("price")
+    index.coerce_numeric("child.size")
+    objs = [
+        TestClass(num=1, price="42", child=TestClass(size="7")),
+        TestClass(num=2, price=" 1.5e3 ", child=TestClass(size="n/a")),
+        TestClass(num=3, price="cheap", child=TestClass(size=3)),
+        TestClass(num=4, price=10, tags=["5", "x"]),
+    ]
+    index.add_object_many(objs[:2])
+    index.add_object(objs[2])
+    index.add_object(objs[3])
+
+    assert matching(index, Q.gt("price", 20)) == [1, 2]
+    assert [o.num for o in index.reduced_query(Q.eq("price", 42)).collect()] == [1]
+    assert [o.price for o in index.reduced_query(Q.eq("price", "42")).collect()] == ["42"]
+    assert matching(index, Q.lt("child.size", 10)) == [1, 3]
+    # only opted-in attributes are coerced
+    assert index.reduced_query(Q.eq("tags", 5)).collect() == []
+
+    objs[0].price = "5"
+    assert [o.num for o in index.reduced_query(Q.gt("price", 20)).collect()] == [2]
+    assert matching(index, Q.le("price", 10)) == [1, 4]
+
+    with pytest.raises(ValueError):
+        index.coerce_numeric("num")
+
+def test_update_keeps_other_postings(index):
+    a = TestClass(num=1, price=42, name="a", flag=True, child=TestClass(size=1))
+    b = TestClass(num=2, price=1500, name="b", flag=True, child=TestClass(size=2))
+    index.add_object_many([a, b])
+
+    a.price = 5
+    a.name = "z"
+    a.flag = False
+    a.child = TestClass(size=3)
+    assert [o.num for o in index.reduced_query(Q.gt("price", 20)).collect()] == [2]
+    assert [o.num for o in index.reduced_query(Q.eq("name", "b")).collect()] == [2]
+    assert [o.num for o in index.reduced_query(Q.eq("flag", True)).collect()] == [2]
+    assert [o.num for o in index.reduced_query(Q.eq("child.size", 2)).collect()] == [2]
+    assert [o.num for o in index.reduced_query(Q.eq("child.size", 3)).collect()] == [1]
+    assert index.reduced_query(Q.eq("child.size", 1)).collect() == []
+
+def test_subclass_fields_pre_interned(index):
+    class Person(TestClass):
+        name: str
+        age: int
+        _secret: str
+
+    class Employee(Person):
+        salary: float
+
+    assert TestClass.__thermite_fields__ == {}
+    assert set(Person.__thermite_fields__) == {"name", "age"}
+    assert set(Employee.__thermite_fields__) == {"name", "age", "salary"}
+    assert Employee.__thermite_fields__["name"] == Person.__thermite_fields__["name"]
+
+    index.add_object(Employee(name="a", age=3, salary=1.5))
+    assert [e.name for e in index.reduced_query(Q.gt("salary", 1)).collect()] == ["a"]
+
+    with pytest.raises(TypeError):
+        class Bad(Indexable, unknown=1):
+            pass
+
+def test_class_index_fields():
+    class Car(TestClass, index_only=["vin", "price"]):
+        pass
+
+    class Truck(Car):
+        pass
+
+    class Bike(TestClass):
+        __index_fields__ = ["vin"]
+
+    class Boat(TestClass, index_exclude=["notes"]):
+        pass
+
+    index = Index(strict=True)
+    car = Car(vin="c1", price=10, notes=object())
+    index.add_object_many([car, Truck(vin="t1", price=20, color="red"), Bike(vin="b1", color="red"), Boat(vin="x1", notes="n", color="red")])
+
+    assert matching(index, Q.gt("price", 5), "vin") == ["c1", "t1"]
+    # only Boat indexes color, the other classes never add it
+    assert matching(index, Q.eq("color", "red"), "vin") == ["x1"]
+    assert matching(index, Q.eq("notes", "n"), "vin") == []
+    assert matching(index, Q.eq("vin", "b1"), "vin") == ["b1"]
+
+    # unindexed values are stored and read back, assigning them leaves the indexes alone
+    car.color = "red"
+    car.notes = object()
+    assert car.color == "red"
+    assert matching(index, Q.eq("color", "red"), "vin") == ["x1"]
+    car.price = 1
+    assert matching(index, Q.gt("price", 5), "vin") == ["t1"]
+
+    with pytest.raises(ValueError):
+        class Bad(TestClass, index_only=["a"], index_exclude=["b"]):
+            pass
+
+def test_field_types(index):
+    class Product(TestClass):
+        pass
+
+    Product.field("price", float)
+    Product.field("sku", str)
+    Product.field("note", str, nullable=True)
+
+    class Special(Product):
+        pass
+
+    p = Product(price=3, sku="a", note=None)
+    # ints given to a float field are stored as floats
+    assert p.price == 3.0 and isinstance(p.price, float)
+    index.add_object_many([p, Special(price=9.5, sku="b")])
+    assert matching(index, Q.gt("price", 2.5), "sku") == ["a", "b"]
+
+    with pytest.raises(TypeError, match="'price' is declared float, got str"):
+        p.price = "cheap"
+    with pytest.raises(TypeError, match="'sku' is declared str, got int"):
+        Special(sku=1)
+    with pytest.raises(TypeError):
+        Product(note=1)
+    with pytest.raises(TypeError):
+        index.set_column("sku", list(index.reduced(sku="a").ids()), [2])
+    assert p.price == 3.0 and p.sku == "a"
+
+    errors = []
+    built = Product.construct_many([{"price": 1.5}, {"price": "x"}], errors=errors)
+    assert len(built) == 1 and [(pos, attr) for pos, attr, _ in errors] == [(1, "price")]
+    # undeclared attributes take any value
+    p.other = [1, 2]
+
+    with pytest.raises(TypeError):
+        Product.field("when", list)
+
+def test_fast_constructors(index):
+    class Point(TestClass):
+        x: int
+        y: int
+        label: str
+
+        def __init__(self):
+            raise AssertionError("fast constructors skip __init__")
+
+    p = Point.from_values((1, 2, "a"))
+    assert isinstance(p, Point)
+    assert (p.x, p.y, p.label) == (1, 2, "a")
+    with pytest.raises(ValueError):
+        Point.from_values((1, 2))
+
+    rows = [{"x": i, "y": -i, "label": f"p{i}", "extra": i % 2} for i in range(50)]
+    points = Point.construct_many(rows)
+    assert len(points) == 50 and all(isinstance(pt, Point) for pt in points)
+    assert points[7].label == "p7" and points[7].extra == 1
+
+    index.add_object_many(points + [p])
+    assert matching(index, Q.lt("y", -47), "x") == [48, 49]
+    assert [pt.label for pt in index.reduced_query(Q.eq("label", "a")).collect()] == ["a"]
+
+def test_add_dicts():
+    index = Index()
+    added = index.add_dicts([{"sku": f"s{i}", "qty": i, "owner": TestClass(name="bob")} for i in range(20)])
+    assert len(added) == 20 and type(added[0]) is Indexable
+    assert added[3].sku == "s3"
+    assert matching(index, Q.ge("qty", 18), "qty") == [18, 19]
+    assert len(index.reduced(**{"owner.name": "bob"}).collect()) == 20
+
+    # objects stay live, changes are reindexed like any other object
+    added[0].qty = 100
+    assert [o.sku for o in index.reduced(qty=100).collect()] == ["s0"]
+
+    rows = index.add_dicts([{"x": 1}, {"x": 2}], cls=TestClass)
+    assert all(isinstance(o, TestClass) for o in rows)
+    assert rows[0].some_method() == "Hello from TestClass"
+    assert index.add_dicts([]) == []
+
+    with pytest.raises(TypeError):
+        index.add_dicts([{"x": 1}], cls=dict)
+    with pytest.raises(TypeError):
+        index.add_dicts([{1: "x"}])
+
+def test_add_dicts_unreadable_values():
+    class Unhashable:
+        __hash__ = None
+
+    index = Index()
+    rows = [{"n": 0}, {"n": 1, "tag": "bad\ud800"}, {"n": 2, "extra": Unhashable()}, {"n": 3, "tags": [[1], Unhashable()]}, {"n": 4}]
+
+    # raised for the whole batch by default, nothing is added
+    with pytest.raises(UnicodeEncodeError):
+        index.add_dicts(rows)
+    assert len(index.collect()) == 0
+
+    errors = []
+    added = index.add_dicts(rows, errors=errors)
+    assert [o.n for o in added] == [0, 4]
+    assert sorted(o.n for o in index.collect()) == [0, 4]
+    assert [(pos, attr) for pos, attr, _ in errors] == [(1, "tag"), (2, "extra"), (3, "tags")]
+    assert isinstance(errors[1][2], TypeError)
+
+    # single objects raise instead of aborting the interpreter
+    with pytest.raises(TypeError):
+        TestClass(extra=Unhashable())
+    with pytest.raises(UnicodeEncodeError):
+        added[0].tag = "bad\ud800"
+    assert not hasattr(added[0], "tag")
+
+def test_index_config(tmp_path):
+    config = IndexConfig().order("insertion").semver("version").units("latency", "ms").float_tolerance("temp", 0.5)
+    assert config != IndexConfig() and IndexConfig().strict() == IndexConfig().strict(True)
+    index = Index(config=config)
+    objs = [TestClass(n=i, version=v, latency=i * 1000, temp=20.0 + i) for i, v in enumerate(["1.10.0", "1.9.0", "2.0.0"])]
+    index.add_object_many(objs[::-1])
+
+    assert [o.n for o in index.collect()] == [2, 1, 0]
+    assert matching(index, Q.gt("version", "1.9.0"), "n") == [0, 2]
+    assert matching(index, Q.ge("latency", "1s"), "n") == [1, 2]
+    assert matching(index, Q.eq("temp", 21.4), "n") == [1]
+
+    # round trips through plain values, kept beside a snapshot
+    data = config.to_dict()
+    assert data == {"order": "insertion", "semver": ["version"], "units": {"latency": "ms"}, "float_tolerance": {"temp": 0.5}}
+    assert IndexConfig.from_dict(data) == config
+    path = tmp_path / "index.bin"
+    index.save(path)
+    loaded = Index.load(path, config=IndexConfig.from_dict(data))
+    assert matching(loaded, Q.gt("version", "1.9.0"), "n") == [0, 2]
+    assert IndexConfig().to_dict() == {}
+
+    strict = Index(config=IndexConfig().strict().opaque("blob"))
+    strict.add_object(TestClass(blob=object()))
+    with pytest.raises(TypeError):
+        strict.add_object(TestClass(other=object()))
+
+    with pytest.raises(ValueError):
+        Index(strict=True, config=config)
+    with pytest.raises(ValueError):
+        IndexConfig().order("random")
+    with pytest.raises(ValueError):
+        IndexConfig().units("x", "parsecs")
+    with pytest.raises(ValueError):
+        IndexConfig.from_dict({"replicas": 4})
+    with pytest.raises(ValueError):
+        Index(config=IndexConfig().fixed_point("price", 3))
+
+    # shards, threads and a log from the start
+    wal = tmp_path / "index.wal"
+    tuned = IndexConfig().shards(4).threads(2).wal(str(wal))
+    assert tuned.to_dict() == {"shards": 4, "threads": 2, "wal": str(wal)}
+    assert IndexConfig.from_dict(tuned.to_dict()) == tuned
+    tuned_index = Index(config=tuned)
+    tuned_index.add_dicts([{"n": i, "tag": f"t{i % 3}"} for i in range(30)])
+    assert matching(tuned_index, Q.eq("tag", "t1"), "n") == list(range(1, 30, 3))
+    loaded = Index.load(wal)
+    assert matching(loaded, Q.eq("tag", "t1"), "n") == list(range(1, 30, 3))
+    for bad in (lambda: IndexConfig().shards(6), lambda: IndexConfig().threads(0), lambda: IndexConfig.from_dict({"shards": 0})):
+        with pytest.raises(ValueError):
+            bad()
+
+def test_save_load(tmp_path):
+    index = Index(max_str_len=16, order="insertion")
+    owner = TestClass(name="bob")
+    objs = [TestClass(num=i, price=i * 1.5, sku=f"s{i}", active=i % 2 == 0, owner=owner, tags=["a"]) for i in range(5, 0, -1)]
+    index.add_object_many(objs + [TestClass()])
+    path = tmp_path / "index.bin"
+    index.save(path)
+
+    loaded = Index.load(str(path))
+    restored = loaded.collect()
+    assert [getattr(o, "num", None) for o in restored] == [5, 4, 3, 2, 1, None]
+    assert (restored[0].price, restored[0].sku, restored[0].active) == (7.5, "s5", False)
+    # only int, float, str and bool values are saved
+    assert not hasattr(restored[0], "owner") and not hasattr(restored[0], "tags")
+    assert matching(loaded, Q.gt("price", 3) & Q.eq("active", True)) == [4]
+    assert [o.sku for o in loaded.reduced_query(Q.starts_with("sku", "s3")).collect()] == ["s3"]
+
+    # loaded objects are live like any other
+    restored[1].num = 40
+    assert [o.sku for o in loaded.reduced(num=40).collect()] == ["s4"]
+
+    rows = Index.load(path, cls=TestClass).collect()
+    assert all(o.some_method() == "Hello from TestClass" for o in rows)
+
+    bad = tmp_path / "bad.bin"
+    bad.write_bytes(b"not a snapshot")
+    with pytest.raises(ValueError):
+        Index.load(bad)
+    bad.write_bytes(path.read_bytes()[:-3])
+    with pytest.raises(ValueError):
+        Index.load(bad)
+    with pytest.raises(OSError):
+        Index.load(tmp_path / "missing.bin")
+
+def test_save_load_declarations(tmp_path):
+    index = Index(config=IndexConfig().units("latency", "ms").float_tolerance("temp", 0.5))
+    index.coerce_numeric("code")
+    index.limit_nested("owner", depth=0)
+    objs = [TestClass(n=i, latency=i * 1000, temp=20.0 + i, code=str(i * 10)) for i in range(3)]
+    index.add_object_many(objs)
+    path = tmp_path / "index.bin"
+    index.save(path)
+
+    # declarations made through the config and on the index both come back without a config
+    loaded = Index.load(path)
+    assert matching(loaded, Q.ge("latency", "1s"), "n") == [1, 2]
+    assert matching(loaded, Q.eq("temp", 21.4), "n") == [1]
+    assert matching(loaded, Q.gt("code", 5), "n") == [1, 2]
+    loaded.add_object(TestClass(n=3, owner=TestClass(n=30)))
+    assert matching(loaded, Q.eq("owner.n", 30), "n") == []
+
+    # so do those of a log
+    index.enable_wal(tmp_path / "index.wal")
+    objs[0].latency = 5000
+    assert matching(Index.load(tmp_path / "index.wal"), Q.ge("latency", "5s"), "n") == [0]
+
+def test_wal(tmp_path):
+    index = Index()
+    with pytest.raises(ValueError):
+        index.compact()
+    index.add_object_many([TestClass(num=i, sku=f"s{i}", active=i != 2) for i in range(4)])
+    path = tmp_path / "index.wal"
+    index.enable_wal(path)
+
+    added = TestClass(num=10, sku="s10", active=True)
+    index.add_object(added)
+    added.num = 11
+    # values that are not saved replay as unset
+    added.sku = TestClass()
+    [unset] = index.reduced(num=1).collect()
+    unset.num = None
+    index.reduce(active=True)
+    index.add_object_many([TestClass(num=20, price=2.5), TestClass(num=21)])
+
+    def state(idx):
+        return sorted((getattr(o, "num", None) or 0, getattr(o, "sku", ""), getattr(o, "price", None)) for o in idx.collect())
+
+    expected = [(0, "s0", None), (0, "s1", None), (3, "s3", None), (11, "", None), (20, "", 2.5), (21, "", None)]
+    assert state(Index.load(path)) == expected
+
+    # a record cut short by a crash is dropped
+    torn = tmp_path / "torn.wal"
+    torn.write_bytes(path.read_bytes()[:-3])
+    assert state(Index.load(torn)) == expected[:-1]
+
+    size = path.stat().st_size
+    index.compact()
+    assert path.stat().st_size < size
+    assert state(Index.load(path)) == expected
+    index.add_object(TestClass(num=30))
+    assert state(Index.load(path)) == expected + [(30, "", None)]
+
+def test_replicate_to():
+    primary = Index()
+    owner = TestClass(name="bob")
+    objs = [TestClass(num=i, owner=owner) for i in range(4)]
+    primary.add_object_many(objs)
+    standby = Index()
+    primary.replicate_to(standby)
+
+    def nums(idx):
+        return sorted(o.num for o in idx.collect())
+
+    assert nums(standby) == [0, 1, 2, 3]
+    # copies, not the objects held by primary
+    assert all(o not in objs for o in standby.collect())
+    assert len(standby.reduced_query(Q.eq("owner.name", "bob")).collect()) == 4
+    # nested objects are shared, not copied
+    owner.name = "alice"
+    assert len(standby.reduced_query(Q.eq("owner.name", "alice")).collect()) == 4
+
+    objs[0].num = 10
+    primary.add_object(TestClass(num=4))
+    primary.reduce(num=10)
+    primary.add_object_many([TestClass(num=5), TestClass(num=6)])
+    assert nums(standby) == nums(primary) == [5, 6, 10]
+    assert standby.reduced(num=10).collect()[0].owner is owner
+
+    # writes to the standby are not sent back
+    standby.collect()[0].num = 50
+    assert nums(primary) == [5, 6, 10]
+
+    # standbys chain, but never loop
+    chained = Index()
+    standby.replicate_to(chained)
+    primary.add_object(TestClass(num=7))
+    assert 7 in nums(chained)
+    for other in (primary, standby):
+        with pytest.raises(ValueError):
+            chained.replicate_to(other)
+    with pytest.raises(ValueError):
+        primary.replicate_to(standby)
+
+    standby.freeze_mutations()
+    with pytest.raises(ValueError):
+        primary.add_object(TestClass(num=8))
+    assert 8 in nums(primary)
+
+    # dropping the standbys ends replication
+    del standby, chained, other
+    primary.add_object(TestClass(num=9))
+
+def test_ingest_stream():
+    index = Index()
+    with index.ingest_stream(capacity=1) as stream:
+        for start in range(0, 100, 10):
+            stream.feed([TestClass(num=i) for i in range(start, start + 10)])
+        stream.flush()
+        assert len(index.collect()) == 100
+        stream.feed([TestClass(num=100)])
+    assert len(index.collect()) == 101
+    with pytest.raises(ValueError):
+        stream.feed([TestClass(num=101)])
+
+    # without blocking, a full queue raises. the worker needs the gil to add a batch, so holding it keeps the queue full
+    batches = [[TestClass(num=i)] for i in range(3)]
+    fed = 0
+    interval = sys.getswitchinterval()
+    sys.setswitchinterval(10)
+    try:
+        stream = index.ingest_stream(capacity=1, block=False)
+        with pytest.raises(queue.Full):
+            for batch in batches:
+                stream.feed(batch)
+                fed += 1
+    finally:
+        sys.setswitchinterval(interval)
+    stream.close()
+    assert fed >= 1
+    assert len(index.collect()) == 101 + fed
+
+    class Opaque:
+        pass
+
+    strict = Index(strict=True)
+    strict.add_object(TestClass(num=1))
+    stream = strict.ingest_stream()
+    stream.feed([TestClass(num=3, blob=Opaque())])
+    with pytest.raises(TypeError):
+        stream.flush()
+    # the refused batch is dropped, later ones are still added
+    stream.feed([TestClass(num=2)])
+    stream.close()
+    assert sorted(o.num for o in strict.collect()) == [1, 2]
+
+    strict.freeze_mutations()
+    with pytest.raises(ValueError):
+        strict.ingest_stream()
+
+def test_from_arrow():
+    with pytest.raises(TypeError):
+        Index.from_arrow([{"x": 1}])
+    with pytest.raises(TypeError):
+        Index.from_pandas(object())
+
+    pa = pytest.importorskip("pyarrow")
+    batch = pa.record_batch({
+        "sku": [f"s{i}" for i in range(6)],
+        "qty": [0, 1, None, 3, 4, 5],
+        "price": [1.5, 2.5, 3.5, None, 5.5, 6.5],
+        "active": [True, False, True, True, False, True],
+        "tier": pa.array(["gold", "silver", "gold", "gold", "bronze", "silver"]).dictionary_encode(),
+    })
+    index = Index.from_arrow(batch)
+    objs = sorted(index.collect(), key=lambda o: o.sku)
+    assert [o.sku for o in objs] == [f"s{i}" for i in range(6)]
+    assert (objs[1].qty, objs[1].price, objs[1].active, objs[1].tier) == (1, 2.5, False, "silver")
+    # nulls leave the attribute unset
+    assert not hasattr(objs[2], "qty") and not hasattr(objs[3], "price")
+    assert matching(index, Q.ge("qty", 3), "sku") == ["s3", "s4", "s5"]
+    assert sorted(o.sku for o in index.reduced(tier="gold", active=True).collect()) == ["s0", "s2", "s3"]
+
+    # tables stream every batch, sliced ones included
+    table = pa.Table.from_batches([batch.slice(4), batch.slice(0, 2)])
+    index = Index.from_arrow(table, cls=TestClass)
+    assert sorted(o.sku for o in index.collect()) == ["s0", "s1", "s4", "s5"]
+    assert all(o.some_method() == "Hello from TestClass" for o in index.collect())
+
+    with pytest.raises(TypeError):
+        Index.from_arrow(pa.record_batch({"when": pa.array([1], pa.timestamp("s"))}))
+
+def test_add_arrow_checkpoints(tmp_path):
+    with pytest.raises(ValueError):
+        Index().add_arrow([{"x": 1}], checkpoint_every=0)
+    with pytest.raises(TypeError):
+        Index().add_arrow([{"x": 1}])
+
+    pa = pytest.importorskip("pyarrow")
+    table = pa.table({"n": list(range(10))})
+    path = tmp_path / "load.wal"
+    index = Index()
+    index.enable_wal(path)
+    seen = []
+
+    def checkpoint(token):
+        # each chunk is queryable once added
+        seen.append((token, len(index.collect())))
+        if token == 8:
+            raise RuntimeError("crash")
+
+    with pytest.raises(RuntimeError):
+        index.add_arrow(table, checkpoint_every=4, on_checkpoint=checkpoint)
+    assert seen == [(4, 4), (8, 8)]
+
+    restored = Index.load(path)
+    assert restored.add_arrow(table, resume_from=seen[-1][0]) == 10
+    assert sorted(o.n for o in restored.collect()) == list(range(10))
+    assert Index().add_arrow(table, resume_from=20) == 10
+
+def test_to_parquet(tmp_path, index):
+    objs = [TestClass(num=i, price=i * 1.5, name=f"n{i}", flag=i % 2 == 0, owner=TestClass(name=f"o{i}")) for i in range(5)]
+    objs[1].price = 2
+    objs[2].name = None
+    objs.append(TestClass(num=5))
+    index.add_object_many(objs)
+    view = index.reduced_query(Q.ge("num", 1))
+
+    path = tmp_path / "out.parquet"
+    assert view.to_parquet(path, ["num", "price", "name", "flag", "owner.name", "absent"]) == 5
+    assert path.read_bytes()[:4] == b"PAR1"
+
+    with pytest.raises(ValueError):
+        view.to_parquet(tmp_path / "none.parquet", [])
+    with pytest.raises(ValueError):
+        view.to_parquet(tmp_path / "dup.parquet", ["num", "num"])
+    with pytest.raises(ValueError):
+        view.to_parquet(tmp_path / "obj.parquet", ["owner"])
+    objs[3].num = "three"
+    with pytest.raises(ValueError):
+        view.to_parquet(tmp_path / "mixed.parquet", ["num"])
+
+    pq = pytest.importorskip("pyarrow.parquet")
+    table = pq.read_table(path)
+    assert table.column_names == ["num", "price", "name", "flag", "owner.name", "absent"]
+    assert str(table.schema.field("num").type) == "int64"
+    assert str(table.schema.field("price").type) == "double"
+    rows = table.to_pylist()
+    assert [r["num"] for r in rows] == [1, 2, 3, 4, 5]
+    assert [r["price"] for r in rows] == [2.0, 3.0, 4.5, 6.0, None]
+    assert [r["name"] for r in rows] == ["n1", None, "n3", "n4", None]
+    assert [r["flag"] for r in rows] == [False, True, False, True, None]
+    assert [r["owner.name"] for r in rows] == ["o1", "o2", "o3", "o4", None]
+    assert all(r["absent"] is None for r in rows)
+
+def test_collect_attrs():
+    index = Index()
+    index.add_object_many([
+        TestClass(sku="a", qty=1, blob=list(range(1000)), owner=TestClass(name="bob")),
+        TestClass(sku="b", qty=2, blob=[]),
+        TestClass(sku="c"),
+    ])
+
+    rows = index.collect(attrs=["sku", "qty", "owner.name"])
+    assert sorted(rows, key=lambda row: row["sku"]) == [
+        {"sku": "a", "qty": 1, "owner.name": "bob"},
+        {"sku": "b", "qty": 2, "owner.name": None},
+        {"sku": "c", "qty": None, "owner.name": None},
+    ]
+    assert all(isinstance(o, TestClass) for o in index.collect())
+
+    # filtered indexes keep collect order and only hold their own objects
+    filtered = index.reduced_query(Q.ge("qty", 1))
+    assert [row["sku"] for row in filtered.collect(attrs=["sku"])] == [o.sku for o in filtered.collect()]
+    assert sorted(row["qty"] for row in filtered.collect(attrs=["qty"])) == [1, 2]
+    assert index.reduced(sku="zzz").collect(attrs=["sku"]) == []
+    assert [row["sku"] for row in index.reduced_ids(index.ids()).collect(attrs=["sku"])] == [o.sku for o in index.collect()]
+
+def test_attribute_read_cache():
+    obj = TestClass(**{f"a{i}": i for i in range(20)}, tags=[1])
+    for _ in range(3):
+        assert [getattr(obj, f"a{i}") for i in range(20)] == list(range(20))
+        # names built at runtime are distinct string objects
+        assert getattr(obj, "".join(["a", "1", "9"])) == 19
+
+    obj.a3 = "changed"
+    assert obj.a3 == "changed"
+    assert getattr(obj, "a" + "3") == "changed"
+    obj.tags.append(2)
+    assert obj.tags == [1, 2]
+    assert obj.some_method() == "Hello from TestClass"
+
+def test_column(index):
+    objs = [TestClass(price=10), TestClass(price=2.5), TestClass(price="x"), TestClass(name="a"), TestClass(price=True)]
+    index.add_object_many(objs)
+
+    col = index.column("price")
+    assert col.typecode == "d"
+    values = sorted(v for v in col if not math.isnan(v))
+    # bools count as 0/1
+    assert values == [1.0, 2.5, 10.0]
+
+    assert sorted(v for v in index.column("price", "f32", fill=-1.0) if v != -1.0) == [1.0, 2.5, 10.0]
+    ints = index.column("price", "i64", fill=0)
+    assert ints.typecode == "q" and len(ints) == len(col)
+    assert sorted(v for v in ints if v) == [1, 2, 10]
+
+    with pytest.raises(ValueError):
+        index.column("price", "i64")
+    with pytest.raises(ValueError):
+        index.column("price", "u8")
+
+def test_custom_key_encoders(index):
+    class Money:
+        def __init__(self, cents):
+            self.cents = cents
+
+    class Release:
+        def __init__(self, major, minor, patch):
+            self.parts = (major, minor, patch)
+
+    register_encoder(Money, lambda m: m.cents)
+    register_encoder(Release, lambda r: r.parts)
+    try:
+        objs = [
+            TestClass(price=Money(250), release=Release(1, 2, 0)),
+            TestClass(price=Money(1999), release=Release(1, 10, 0)),
+            TestClass(price=Money(5), release=Release(1, 9, 3)),
+        ]
+        index.add_object_many(objs)
+
+        def released(query):
+            return sorted(o.release.parts for o in index.reduced_query(query).collect())
+
+        assert sorted(o.price.cents for o in index.reduced_query(Q.gt("price", Money(100))).collect()) == [250, 1999]
+        assert [o.price.cents for o in index.reduced_query(Q.eq("price", Money(5))).collect()] == [5]
+        # ordered by parts, not lexicographically
+        assert released(Q.gt("release", Release(1, 9, 0))) == [(1, 9, 3), (1, 10, 0)]
+        assert released(Q.bt("release", Release(1, 2, 0), Release(1, 9, 3))) == [(1, 2, 0), (1, 9, 3)]
+
+        objs[2].release = Release(2, 0, 0)
+        assert released(Q.ge("release", Release(1, 10, 0))) == [(1, 10, 0), (2, 0, 0)]
+
+        # encoded keys are ordered against their own type only, never against plain numbers
+        index.add_object(TestClass(price=250))
+        assert [o.price.cents for o in index.reduced(price=Money(250)).collect()] == [250]
+        assert [o.price for o in index.reduced(price=250).collect()] == [250]
+        assert sorted(o.price.cents for o in index.reduced_query(Q.gt("price", Money(1))).collect()) == [5, 250, 1999]
+        assert [o.price for o in index.reduced_query(Q.gt("price", 1)).collect()] == [250]
+
+        with pytest.raises(TypeError):
+            index.reduced_query(Q.gt("release", object()))
+    finally:
+        assert unregister_encoder(Money)
+        assert unregister_encoder(Release)
+    assert not unregister_encoder(Money)
+
+    # the encoder's error is raised where the value is built, not swallowed
+    register_encoder(Release, lambda r: r.parts)
+    try:
+        with pytest.raises(ValueError):
+            TestClass(release=Release(1 << 30, 0, 0))
+        obj = TestClass(release=Release(3, 0, 0))
+        index.add_object(obj)
+        with pytest.raises(ValueError):
+            obj.release = Release(0, 1 << 30, 0)
+        assert obj.release.parts == (3, 0, 0)
+        assert released(Q.gt("release", Release(0, 0, 0))) == [(1, 2, 0), (1, 10, 0), (2, 0, 0), (3, 0, 0)]
+    finally:
+        unregister_encoder(Release)
+
+def test_field_extractors(index):
+    class Point:
+        def __init__(self, x, y):
+            self.x, self.y = x, y
+
+    class Opaque:
+        pass
+
+    register_extractor(Point, lambda p: {"x": p.x, "y": p.y, "quadrant": "ne" if p.x > 0 and p.y > 0 else "other"})
+    register_extractor(Opaque, lambda o: 1 / 0)
+    try:
+        points = [Point(1, 2), Point(-3, 4), Point(5, 6)]
+        objs = [TestClass(n=i, loc=p) for i, p in enumerate(points)]
+        index.add_object_many(objs)
+
+        assert matching(index, Q.gt("loc.x", 0), "n") == [0, 2]
+        assert [o.n for o in index.reduced(**{"loc.quadrant": "other"}).collect()] == [1]
+        # the value itself is still matched by identity and read back unchanged
+        assert [o.n for o in index.reduced(loc=points[1]).collect()] == [1]
+        assert objs[2].loc is points[2]
+
+        objs[0].loc = Point(-1, -1)
+        assert matching(index, Q.lt("loc.y", 5), "n") == [0, 1]
+        objs[1].loc = None
+        assert [o.n for o in index.reduced_query(Q.lt("loc.y", 5)).collect()] == [0]
+
+        # the extractor's error is raised where the value is built, not swallowed
+        with pytest.raises(ZeroDivisionError):
+            TestClass(other=Opaque())
+        with pytest.raises(ZeroDivisionError):
+            objs[0].other = Opaque()
+        assert not hasattr(objs[0], "other")
+        register_extractor(Opaque, lambda o: [o])
+        with pytest.raises(TypeError):
+            TestClass(other=Opaque())
+    finally:
+        assert unregister_extractor(Point)
+        assert unregister_extractor(Opaque)
+    assert not unregister_extractor(Point)
+    with pytest.raises(TypeError):
+        register_extractor(Point, "x")
+
+def test_custom_operators(index):
+    from PyThermite import attr
+
+    flags = [0b000, 0b001, 0b101, 0b110, 0b111]
+    index.add_object_many([TestClass(n=i, flags=f, owner=TestClass(flags=f), name=f"item{i}") for i, f in enumerate(flags)])
+    index.add_object(TestClass(n=9, flags="0b101"))
+
+    # built in native operators
+    assert matching(index, Q.custom("bits_all", "flags", 0b101), "n") == [2, 4]
+    assert matching(index, Q.custom("bits_any", "flags", 0b010), "n") == [3, 4]
+    assert matching(index, Q.custom("bits_all", "owner.flags", 0b110), "n") == [3, 4]
+    assert matching(index, ~Q.custom("bits_any", "flags", 0b100), "n") == [0, 1, 9]
+    assert matching(index, attr("flags").custom("bits_all", 1) & (attr("n") > 1), "n") == [2, 4]
+    assert str(Q.custom("bits_all", "flags", 5)) == "flags bits_all 5"
+
+    register_operator("longer_than", lambda value, n: isinstance(value, str) and len(value) > n)
+    register_operator("raises", lambda value, arg: 1 / 0)
+    try:
+        assert matching(index, Q.custom("longer_than", "flags", 3), "n") == [9]
+        assert matching(index, Q.custom("longer_than", "name", 4) & Q.custom("bits_any", "flags", 0b011), "n") == [1, 2, 3, 4]
+        assert matching(index, Q.custom("raises", "flags", 0), "n") == []
+        # re-registering replaces the predicate
+        register_operator("longer_than", lambda value, n: isinstance(value, int) and value > n)
+        assert matching(index, Q.custom("longer_than", "flags", 5), "n") == [3, 4]
+    finally:
+        assert unregister_operator("longer_than")
+        assert unregister_operator("raises")
+
+    assert not unregister_operator("longer_than")
+    with pytest.raises(ValueError):
+        Q.custom("longer_than", "flags", 3)
+    with pytest.raises(TypeError):
+        register_operator("longer_than", 3)
+
+def test_flag_operators():
+    from PyThermite import attr
+
+    declared = Index()
+    declared.flags("perms")
+    declared.flags("owner.perms")
+    plain = Index()
+    perms = [0b000, 0b001, 0b100, 0b101, 0b110, -1, 1 << 40]
+    objs = {}
+    for index in (declared, plain):
+        objs[index] = [TestClass(n=i, perms=p, owner=TestClass(perms=p), keep=i != 5) for i, p in enumerate(perms)]
+        index.add_object_many(objs[index])
+        index.add_object(TestClass(n=7, perms="0b100", keep=True))
+        index.add_object(TestClass(n=8, perms=[0b010, 0b1000], keep=True))
+
+    for index in (declared, plain):
+        assert matching(index, Q.has_flag("perms", 0b100), "n") == [2, 3, 4, 5]
+        assert matching(index, Q.flags_all("perms", 0b101), "n") == [3, 5]
+        assert matching(index, Q.flags_any("perms", 0b011), "n") == [1, 3, 4, 5, 8]
+        assert matching(index, Q.has_flag("perms", 1 << 40), "n") == [5, 6]
+        assert matching(index, Q.flags_all("owner.perms", 0b110), "n") == [4, 5]
+        # mask 0 holds for every int, matches nothing for any
+        assert matching(index, Q.flags_all("perms", 0), "n") == [0, 1, 2, 3, 4, 5, 6, 8]
+        assert matching(index, Q.flags_any("perms", 0), "n") == []
+        assert matching(index, ~Q.flags_any("perms", 0b100) & (attr("n") < 7), "n") == [0, 1, 6]
+        assert matching(index, attr("perms").has_flag(0b1000), "n") == [5, 8]
+        assert matching(index, Q.custom("bits_all", "perms", 0b101), "n") == [3, 5]
+
+        objs[index][3].perms = 0b010
+        assert matching(index, Q.has_flag("perms", 0b100), "n") == [2, 4, 5]
+        index.reduce(keep=True)
+        assert matching(index, Q.flags_any("perms", 0b110) & (attr("n") < 7), "n") == [2, 3, 4]
+
+    assert str(Q.flags_all("perms", 5)) == "perms & 5 == 5"
+    assert str(Q.flags_any("perms", 5)) == "perms & 5 != 0"
+    with pytest.raises(ValueError):
+        Q.has_flag("perms", 0b110)
+    with pytest.raises(ValueError):
+        Q.has_flag("perms", 0)
+    with pytest.raises(ValueError):
+        declared.flags("other")
+
+def test_mod_eq():
+    from PyThermite import attr
+
+    declared = Index()
+    declared.flags("id")
+    plain = Index()
+    ids = list(range(-6, 30)) + [2**40 + 3, -(2**40)]
+    for index in (declared, plain):
+        index.add_object_many([TestClass(id=i, owner=TestClass(id=i)) for i in ids])
+        index.add_object(TestClass(id="3"))
+
+    for index in (declared, plain):
+        for divisor in (1, 3, 7, 8):
+            claimed = []
+            for remainder in range(divisor):
+                got = matching(index, Q.mod_eq("id", divisor, remainder), "id")
+                assert got == sorted(i for i in ids if i % divisor == remainder)
+                claimed += got
+            # every int lands with exactly one worker
+            assert sorted(claimed) == sorted(ids)
+        assert matching(index, Q.mod_eq("owner.id", divisor=5, remainder=4), "id") == \
+            sorted(i for i in ids if i % 5 == 4)
+        assert len(index.reduced_query(attr("id").mod_eq(2, 1) & (attr("id") < 0)).collect()) == 3
+
+    assert str(Q.mod_eq("id", divisor=8, remainder=3)) == "id % 8 == 3"
+    for divisor, remainder in ((0, 0), (-8, 1), (8, 8), (8, -1)):
+        with pytest.raises(ValueError):
+            Q.mod_eq("id", divisor, remainder)
+
+def test_datetime_keys(index):
+    from datetime import date, datetime, timedelta, timezone
+
+    plus_two = timezone(timedelta(hours=2))
+    created = [
+        datetime(2024, 1, 1, 9, 30),
+        datetime(2024, 1, 1, 12, 0, tzinfo=plus_two),
+        datetime(2024, 1, 2, 0, 0, 0, 1),
+        datetime(1969, 12, 31, 23, 59, 59),
+        datetime(2024, 3, 1, tzinfo=timezone.utc),
+    ]
+    objs = [TestClass(n=i, created=c, due=c.date(), took=timedelta(minutes=i * 30 - 30)) for i, c in enumerate(created)]
+    index.add_object_many(objs)
+
+    # 12:00 at +02:00 is 10:00 UTC, after the naive 9:30
+    assert matching(index, Q.bt("created", datetime(2024, 1, 1, 9, 45), datetime(2024, 1, 2)), "n") == [1]
+    assert matching(index, Q.gt("created", datetime(2024, 1, 1, 9, 45, tzinfo=timezone.utc)), "n") == [1, 2, 4]
+    assert matching(index, Q.lt("created", datetime(1970, 1, 1)), "n") == [3]
+    # each kind is ordered against its own kind only, a date is not a datetime at midnight
+    assert matching(index, Q.ge("created", date(2024, 1, 2)), "n") == []
+    assert matching(index, Q.ge("created", datetime(2024, 1, 2)), "n") == [2, 4]
+    assert matching(index, Q.bt("due", date(2024, 1, 1), date(2024, 1, 31)), "n") == [0, 1, 2]
+    assert matching(index, Q.eq("created", datetime(2024, 1, 1, 10, 0)), "n") == [1]
+    assert matching(index, Q.eq("due", date(2024, 3, 1)), "n") == [4]
+    assert matching(index, Q.lt("took", timedelta(0)), "n") == [0]
+    assert matching(index, Q.bt("took", timedelta(minutes=30), timedelta(hours=1)), "n") == [2, 3]
+    assert [o.n for o in index.order_by("created")] == [3, 0, 1, 2, 4]
+
+    # values are read back unchanged
+    assert objs[1].created is created[1]
+    objs[3].created = datetime(2025, 1, 1)
+    assert matching(index, Q.gt("created", datetime(2024, 6, 1)), "n") == [3]
+    assert "2024" in str(Q.gt("created", datetime(2024, 1, 1)))
+
+    # kinds with the same encoding never match each other or plain numbers
+    mixed = Index()
+    second = [datetime(1970, 1, 1, 0, 0, 1), 1_000_000, timedelta(seconds=1), date(1970, 1, 2), datetime(1970, 1, 2)]
+    mixed.add_object_many([TestClass(n=i, t=t) for i, t in enumerate(second)])
+    for i, t in enumerate(second):
+        assert [o.n for o in mixed.reduced_query(Q.eq("t", t)).collect()] == [i]
+    assert [o.n for o in mixed.reduced_query(Q.gt("t", 0)).collect()] == [1]
+
+def test_wide_numbers(index):
+    from decimal import Decimal
+
+    values = [2**63 + 1, 2**64, -(2**70) - 1, Decimal("0.1"), Decimal("1.5"), 0.1, 7, Decimal("NaN")]
+    objs = [TestClass(n=i, v=v) for i, v in enumerate(values)]
+    index.add_object_many(objs)
+
+    # past i64 ints keep every digit
+    assert matching(index, Q.eq("v", 2**63 + 1), "n") == [0]
+    assert matching(index, Q.eq("v", 2**63 + 2), "n") == []
+    assert matching(index, Q.gt("v", 2**63), "n") == [0, 1]
+    assert matching(index, Q.ge("v", 2**64), "n") == [1]
+    assert matching(index, Q.lt("v", -(2**70)), "n") == [2]
+    # Decimal("0.1") sits just below the float 0.1, exact Decimals match the same number
+    assert matching(index, Q.lt("v", 0.1), "n") == [2, 3]
+    assert matching(index, Q.eq("v", Decimal("0.1")), "n") == [3]
+    assert matching(index, Q.eq("v", 1.5), "n") == [4]
+    assert matching(index, Q.bt("v", Decimal("0.05"), Decimal("0.1")), "n") == [3]
+    assert matching(index, Q.bt("v", 1, 2**64), "n") == [0, 1, 4, 6]
+    assert matching(index, Q.le("v", float("inf")), "n") == [0, 1, 2, 3, 4, 5, 6]
+    assert [o.n for o in index.order_by("v")] == [2, 3, 5, 4, 6, 0, 1, 7]
+    assert [o.n for o in index.order_by("v", descending=True)] == [1, 0, 6, 4, 5, 3, 2, 7]
+
+    # values are read back unchanged
+    assert objs[0].v == 2**63 + 1 and isinstance(objs[3].v, Decimal)
+    objs[0].v = 3
+    assert matching(index, Q.gt("v", 2**63), "n") == [1]
+
+def test_uuid_and_bytes(index):
+    import uuid
+
+    keys = [uuid.UUID(int=i) for i in range(3)]
+    objs = [TestClass(n=i, key=keys[i], raw=bytes([i]) * 4) for i in range(3)]
+    index.add_object_many(objs)
+
+    # fresh but equal instances match by value
+    assert matching(index, Q.eq("key", uuid.UUID(int=1)), "n") == [1]
+    assert matching(index, Q.eq("key", str(uuid.UUID(int=1))), "n") == []
+    assert matching(index, Q.in_("key", [uuid.UUID(int=0), uuid.UUID(int=2)]), "n") == [0, 2]
+    assert matching(index, Q.eq("raw", b"\x02" * 4), "n") == [2]
+    assert matching(index, Q.in_("raw", [b"\x00\x00\x00\x00", b"nope"]), "n") == [0]
+
+    # values are read back unchanged
+    assert objs[1].key is keys[1] and objs[2].raw == b"\x02" * 4
+    objs[1].key = uuid.UUID(int=2)
+    objs[2].raw = b"x"
+    assert matching(index, Q.eq("key", uuid.UUID(int=2)), "n") == [1, 2]
+    assert matching(index, Q.eq("raw", b"x"), "n") == [2]
+    assert matching(index, Q.eq("raw", b"\x02" * 4), "n") == []
+
+def test_semver_ordering():
+    index = Index()
+    index.semver("version")
+    index.semver("dep.version")
+    versions = ["1.2.0", "1.10.0", "1.9.3", "v2.0.0-rc.1", "2.0.0", "2.0.0-alpha", "not a version"]
+    index.add_object_many([TestClass(version=v, dep=TestClass(version=v)) for v in versions])
+
+    assert matching(index, Q.gt("version", "1.9.3"), "version") == ["1.10.0", "2.0.0", "2.0.0-alpha", "v2.0.0-rc.1"]
+    # prereleases sort below their release
+    assert matching(index, Q.lt("version", "2.0.0"), "version") == ["1.10.0", "1.2.0", "1.9.3", "2.0.0-alpha", "v2.0.0-rc.1"]
+    assert matching(index, Q.bt("version", "1.9", "2.0.0-beta"), "version") == ["1.10.0", "1.9.3", "2.0.0-alpha"]
+    assert matching(index, Q.gt("dep.version", "v2.0.0-alpha"), "version") == ["2.0.0", "v2.0.0-rc.1"]
+    # equality still compares the strings
+    assert matching(index, Q.eq("version", "1.10.0"), "version") == ["1.10.0"]
+    assert matching(index, Q.eq("version", "not a version"), "version") == ["not a version"]
+
+    filtered = index.reduced_query(Q.ge("version", "1.9.0"))
+    assert matching(filtered, Q.lt("version", "2.0.0-beta"), "version") == ["1.10.0", "1.9.3", "2.0.0-alpha"]
+
+    # without the declaration versions compare as plain strings
+    plain = Index()
+    plain.add_object_many([TestClass(version=v) for v in versions])
+    assert "1.9.3" in [o.version for o in plain.reduced_query(Q.gt("version", "1.10.0")).collect()]
+    with pytest.raises(ValueError):
+        index.semver("other")
+
+def test_semver_packaging_versions():
+    version = pytest.importorskip("packaging.version")
+    index = Index()
+    index.semver("version")
+    try:
+        index.add_object_many([TestClass(version=version.Version(v), label=v) for v in ["1.10.0", "1.9.0", "2.0.0rc1", "2.0.0", "99999.0"]])
+
+        assert matching(index, Q.gt("version", version.Version("1.9.0")), "label") == ["1.10.0", "2.0.0", "2.0.0rc1"]
+        assert matching(index, Q.lt("version", "2.0.0"), "label") == ["1.10.0", "1.9.0", "2.0.0rc1"]
+        assert matching(index, Q.ge("version", "2.0.0-rc.1"), "label") == ["2.0.0", "2.0.0rc1"]
+    finally:
+        # the encoder is process wide, so it is not left behind for other tests
+        assert unregister_encoder(version.Version)
+
+def test_id_sets(index):
+    objs = [TestClass(num=i, even=i % 2 == 0) for i in range(10)]
+    index.add_object_many(objs)
+
+    everything = index.ids()
+    evens = index.reduced(even=True).ids()
+    small = index.reduced_query(Q.lt("num", 4)).ids()
+    assert len(everything) == 10 and len(evens) == 5
+    assert evens <= everything and evens < everything and not everything <= evens
+    assert list(everything) == sorted(everything.to_list())
+
+    def nums(ids):
+        return sorted(o.num for o in index.reduced_ids(ids).collect())
+
+    assert nums(evens & small) == [0, 2]
+    assert nums(evens | small) == [0, 1, 2, 3, 4, 6, 8]
+    assert nums(evens - small) == [4, 6, 8]
+    assert nums(evens ^ small) == [1, 3, 4, 6, 8]
+    assert (evens & small) == IdSet(list(evens & small))
+    assert (evens & small) != evens
+
+    first = next(iter(small))
+    assert first in small and first in everything
+    assert -1 not in small
+    assert not IdSet() and len(IdSet()) == 0
+
+    filtered = index.reduced_query(Q.ge("num", 5))
+    assert sorted(o.num for o in filtered.reduced_ids(evens).collect()) == [6, 8]
+    assert filtered.ids() == everything - small - index.reduced_query(Q.eq("num", 4)).ids()
+
+def test_id_set_numpy(index):
+    np = pytest.importorskip("numpy")
+    index.add_object_many([TestClass(num=i) for i in range(5)])
+    ids = index.ids()
+    arr = ids.to_numpy()
+    assert arr.dtype == np.uint32
+    assert arr.tolist() == ids.to_list()
+
+def test_filtered_coverage(index):
+    class Opaque:
+        pass
+
+    index.add_object_many([
+        TestClass(num=1, price=10, name="a", child=TestClass(size=1)),
+        TestClass(num=2, price=20, name="b", child=TestClass(size="big")),
+        TestClass(num=3, price="30", name="c"),
+        TestClass(num=4, price=Opaque(), name=5),
+        TestClass(num=5, name="e"),
+    ])
+
+    def by_clause(filtered):
+        return {entry["clause"]: entry for entry in filtered.coverage()}
+
+    priced = index.reduced_query(Q.gt("price", 5))
+    entry = by_clause(priced)[str(Q.gt("price", 5))]
+    assert (entry["indexed"], entry["fallback"], entry["unindexable"], entry["missing"]) == (2, 0, 2, 1)
+
+    # chained queries report every clause against the original index
+    named = priced.reduced_query(Q.starts_with("name", "a") | ~Q.eq("child.size", 1))
+    assert sorted(o.num for o in named.collect()) == [1, 2]
+    entries = by_clause(named)
+    assert len(entries) == 3
+    starts = entries[str(Q.starts_with("name", "a"))]
+    assert (starts["indexed"], starts["fallback"], starts["unindexable"], starts["missing"]) == (2, 0, 1, 0)
+    nested = entries[str(Q.eq("child.size", 1))]
+    assert (nested["indexed"], nested["fallback"], nested["missing"]) == (2, 0, 3)
+
+    # objects matched through a negation without the attribute are fallbacks
+    unpriced = index.reduced_query(~Q.eq("price", 10))
+    entry = unpriced.coverage()[0]
+    assert (entry["indexed"], entry["fallback"]) == (3, 1)
+
+    eq = index.reduced(name="b")
+    assert [e["indexed"] for e in eq.coverage()] == [1]
+    assert index.reduced_ids(index.ids()).coverage() == []
+
+    # objects added after the query ran are not counted against it
+    index.add_object_many([TestClass(num=6, price=Opaque()), TestClass(num=7)])
+    entry = by_clause(priced)[str(Q.gt("price", 5))]
+    assert (entry["indexed"], entry["fallback"], entry["unindexable"], entry["missing"]) == (2, 0, 2, 1)
+
+def test_strict_index():
+    class Opaque:
+        pass
+
+    index = Index(strict=True, opaque=["blob", "child.raw"])
+    ok = TestClass(num=1, name=None, tags=[1, "a"], blob=Opaque(), child=TestClass(raw=Opaque()))
+    index.add_object(ok)
+
+    with pytest.raises(TypeError, match="'price'"):
+        index.add_object(TestClass(num=2, price=Opaque()))
+    with pytest.raises(TypeError):
+        index.add_object(TestClass(num=3, tags=[1, Opaque()]))
+    with pytest.raises(TypeError, match="'size'"):
+        index.add_object(TestClass(num=4, child=TestClass(size=Opaque())))
+    # all or nothing
+    with pytest.raises(TypeError):
+        index.add_object_many([TestClass(num=5), TestClass(num=6, price=Opaque())])
+    assert [o.num for o in index.collect()] == [1]
+
+    with pytest.raises(TypeError):
+        ok.price = Opaque()
+    assert not hasattr(ok, "price")
+    with pytest.raises(TypeError):
+        ok.child.size = Opaque()
+    ok.blob = Opaque()
+    ok.child.raw = Opaque()
+    ok.tags.append(Opaque())
+    with pytest.raises(TypeError):
+        index.refresh(ok)
+
+    loose = Index()
+    loose.add_object(TestClass(num=7, price=Opaque()))
+    with pytest.raises(TypeError):
+        index.adopt(loose)
+    with pytest.raises(TypeError):
+        index.union_with(loose)
+    assert [o.num for o in index.collect()] == [1]
+
+def test_result_order():
+    objs = [TestClass(num=n, even=n % 2 == 0) for n in range(6)]
+    shuffled = [objs[i] for i in (3, 0, 5, 1, 4, 2)]
+
+    # ids can be reused, so id order need not follow creation order
+    by_id = Index()
+    by_id.add_object_many(shuffled)
+    assert by_id.collect() == [by_id.reduced_ids(IdSet([i])).collect()[0] for i in by_id.ids()]
+
+    index = Index(order="insertion")
+    index.add_object_many(shuffled[:3])
+    for obj in shuffled[3:]:
+        index.add_object(obj)
+    assert index.collect() == shuffled
+    assert index.reduced(even=True).collect() == [objs[0], objs[4], objs[2]]
+    assert index.reduced_query(Q.gt("num", 1)).reduced(even=False).collect() == [objs[3], objs[5]]
+
+    # re-adding keeps the original place, removing and adding again moves to the end
+    index.add_object(objs[3])
+    assert index.collect()[0] is objs[3]
+    index.reduce(even=True)
+    index.add_object_many([objs[3], objs[1]])
+    assert index.collect() == [objs[0], objs[4], objs[2], objs[3], objs[1]]
+    index.union_with(by_id)
+    assert index.collect() == [objs[0], objs[4], objs[2], objs[3], objs[1], objs[5]]
+
+    with pytest.raises(ValueError):
+        Index(order="random")
+
+def test_recency():
+    index = Index(config=IndexConfig().sequence())
+    assert index.sequence() == 0 and index.latest(3) == []
+    first = [TestClass(num=n, kind="a" if n % 2 else "b") for n in range(4)]
+    index.add_object_many(first)
+    mark = index.sequence()
+    assert mark == 4
+    later = [TestClass(num=n, kind="a") for n in range(4, 7)]
+    for obj in later:
+        index.add_object(obj)
+
+    assert index.latest(2) == [later[2], later[1]]
+    assert index.latest(10) == list(reversed(first + later))
+    assert index.latest(3, filter=Q.eq("kind", "b")) == [first[2], first[0]]
+    assert sorted(o.num for o in index.since(mark).collect()) == [4, 5, 6]
+    assert sorted(o.num for o in index.since(mark, filter=Q.gt("num", 4)).collect()) == [5, 6]
+    assert index.since(index.sequence()).collect() == []
+
+    # re-adding keeps the number, removed objects drop out
+    index.add_object(first[0])
+    assert index.sequence() == 7 and index.latest(1) == [later[2]]
+    index.reduce(kind="a")
+    assert index.latest(10) == [later[2], later[1], later[0], first[3], first[1]]
+
+    # numbering is opt in, insertion order turns it on too
+    plain = Index()
+    plain.add_object_many(first)
+    for read in (plain.sequence, lambda: plain.latest(1), lambda: plain.since(0)):
+        with pytest.raises(ValueError):
+            read()
+    ordered = Index(order="insertion")
+    ordered.add_object_many(first)
+    assert ordered.sequence() == 4 and ordered.latest(1) == [first[3]]
+    assert IndexConfig().sequence().to_dict() == {"sequence": True}
+    assert IndexConfig.from_dict({"sequence": True}) == IndexConfig().sequence()
+
+    # objects taken from an index that does not number them are numbered by id
+    extra = Index()
+    extra.add_object_many(later)
+    ordered.adopt(extra, Q.gt("num", 4))
+    taken = ordered.collect()
+    assert ordered.sequence() == 6 and taken[:4] == first and sorted(o.num for o in taken[4:]) == [5, 6]
+    ordered.union_with(extra)
+    assert ordered.sequence() == 7 and ordered.latest(1) == [later[0]]
+
+def test_order_by():
+    objs = [
+        TestClass(num=0, score=3.5, name="b"),
+        TestClass(num=1, score=-2, name="a"),
+        TestClass(num=2, score=10, name="c"),
+        TestClass(num=3, score=3.5),
+        TestClass(num=4, score="high"),
+        TestClass(num=5, score="21"),
+        TestClass(num=6),
+    ]
+    index = Index()
+    index.add_object_many(objs)
+
+    def nums(results):
+        return [o.num for o in results]
+
+    ascending = nums(index.order_by("score"))
+    assert ascending[:1] == [1] and sorted(ascending[1:3]) == [0, 3] and ascending[3:6] == [2, 5, 4]
+    assert ascending[6:] == [6]
+    descending = nums(index.order_by("score", descending=True))
+    assert descending[:3] == [4, 5, 2] and sorted(descending[3:5]) == [0, 3] and descending[5] == 1
+    assert nums(index.order_by("score", limit=1)) == [1]
+    assert nums(index.order_by("score", descending=True, limit=2)) == [4, 5]
+    assert index.order_by("score", limit=0) == []
+    assert nums(index.order_by("name"))[:3] == [1, 0, 2]
+
+    filtered = index.reduced_query(Q.ne("num", 2))
+    assert nums(filtered.order_by("score", descending=True, limit=3)) == [4, 5, descending[3]]
+    assert sorted(nums(index.order_by("missing"))) == list(range(7))
+
+    # numeric strings are ordered once, among the numbers
+    coerced = Index()
+    coerced.coerce_numeric("score")
+    coerced.add_object_many(objs[:6])
+    assert nums(coerced.order_by("score"))[:5] in ([1, 0, 3, 2, 5], [1, 3, 0, 2, 5])
+    assert len(coerced.order_by("score")) == 6
+
+def test_hot_attributes():
+    objs = [TestClass(num=i, price=i, status="new") for i in range(3)]
+    index = Index()
+    index.add_object_many(objs)
+    assert index.hot_attributes() == []
+
+    for round in range(3):
+        for obj in objs:
+            obj.price = obj.price + 1
+    objs[0].status = "done"
+    objs[1].tag = "x"
+
+    hot = index.hot_attributes()
+    assert [entry["name"] for entry in hot] == ["price", "tag", "status"]
+    assert [entry["updates"] for entry in hot] == [9, 1, 1]
+    assert hot[0]["last_modified"] == 9 and hot[1]["last_modified"] == 11 and hot[2]["last_modified"] == 10
+    assert index.hot_attributes(limit=1) == hot[:1]
+    assert len(index.reduced_query(Q.eq("price", 3)).collect()) == 1
+
+def test_metrics_and_slow_queries():
+    index = Index()
+    objs = [TestClass(num=i, tag="a" if i % 2 else "b") for i in range(5)]
+    index.add_object_many(objs)
+    objs[0].num = 10
+    objs[1].tag = "b"
+    stats = index.metrics()
+    assert (stats["reads"], stats["writes"], stats["slow_queries"]) == (0, 7, 0)
+    assert stats["lock_contention"] >= 0
+
+    index.reduced_query(Q.gt("num", 2))
+    index.reduced_many([Q.eq("tag", "a"), Q.eq("tag", "b")])
+    index.get_by_attribute(tag="a")
+    assert index.metrics()["reads"] == 4
+    # nothing is logged until a threshold is set
+    assert index.slow_queries() == []
+
+    index.log_slow_queries(0)
+    index.reduced_query(Q.gt("num", 2) & Q.eq("tag", "b"))
+    index.reduced_many([Q.eq("tag", "a"), Q.lt("num", 2)])
+    index.reduced(tag="a")
+    slow = index.slow_queries()
+    assert len(slow) == 3 and index.metrics()["slow_queries"] == 3
+    assert "num" in slow[0]["query"] and slow[0]["matched"] == 2 and slow[0]["time_ns"] >= 0
+    assert slow[0]["profile"]["matched"] == 2 and slow[0]["profile"]["children"]
+    assert slow[1]["profile"] is None and slow[1]["matched"] == 2
+    assert slow[2]["matched"] == 1
+
+    # the oldest entries are dropped past capacity
+    index.log_slow_queries(0, capacity=2)
+    assert [q["matched"] for q in index.slow_queries()] == [2, 1]
+    index.reduced_query(Q.eq("num", 10))
+    assert [q["matched"] for q in index.slow_queries(clear=True)] == [1, 1]
+    assert index.slow_queries() == []
+
+    index.log_slow_queries(60_000)
+    index.reduced_query(Q.eq("num", 10))
+    index.log_slow_queries(None)
+    index.reduced_query(Q.eq("num", 10))
+    assert index.slow_queries() == []
+    with pytest.raises(ValueError):
+        index.log_slow_queries(-1)
+
+def test_iter_take_skip():
+    index = Index()
+    index.add_object_many([TestClass(num=i, even=i % 2 == 0) for i in range(10)])
+    evens = index.reduced_query(Q.eq("even", True))
+    expected = [o.num for o in evens.collect()]
+
+    assert [o.num for o in evens.iter()] == expected
+    assert [o.num for o in evens.iter(batch_size=2)] == expected
+    it = evens.iter(batch_size=3)
+    assert next(it).num == expected[0]
+    assert [o.num for o in it] == expected[1:]
+    assert list(index.reduced_query(Q.eq("num", 99)).iter()) == []
+
+    assert [o.num for o in evens.take(2).collect()] == expected[:2]
+    assert [o.num for o in evens.skip(3).collect()] == expected[3:]
+    assert [o.num for o in evens.skip(1).take(2).collect()] == expected[1:3]
+    assert evens.skip(10).collect() == []
+    assert len(evens.take(100).collect()) == 5
+    assert evens.take(0).collect() == []
+
+    ordered = Index(order="insertion")
+    objs = [TestClass(num=i) for i in range(6)]
+    ordered.add_object_many(objs[3:])
+    ordered.add_object_many(objs[:3])
+    everything = ordered.reduced_query(Q.ge("num", 0))
+    expected = [o.num for o in everything.collect()]
+    assert expected == [3, 4, 5, 0, 1, 2]
+    assert [o.num for o in everything.iter(batch_size=4)] == expected
+    assert [o.num for o in everything.skip(2).take(3).collect()] == expected[2:5]
+
+def test_iter_snapshot_while_mutating():
+    import threading
+
+    index = Index()
+    # held here so removed objects keep their ids
+    objs = [TestClass(num=i, keep=i % 3 != 0) for i in range(12)]
+    index.add_object_many(objs)
+    result = index.reduced_query(Q.lt("num", 8))
+    it = result.iter(batch_size=1)
+    first = next(it).num
+
+    # added objects never join a running iterator, removed ones are skipped once their batch is reached
+    index.reduce(keep=True)
+    index.add_object(TestClass(num=1, keep=True))
+    assert sorted([first] + [o.num for o in it]) == sorted({first, 1, 2, 4, 5, 7})
+
+    # batches whose objects were all removed are passed over
+    many = Index()
+    objs = [TestClass(num=i, keep=i >= 90) for i in range(100)]
+    many.add_object_many(objs)
+    it = many.reduced_query(Q.ge("num", 0)).iter(batch_size=7)
+    assert it.__length_hint__() == 100
+    many.reduce(keep=True)
+    assert sorted(o.num for o in it) == list(range(90, 100))
+
+    # a FilteredIndex made before the removal skips the objects it lost
+    assert sorted(o.num for o in result.collect()) == [1, 2, 4, 5, 7]
+    assert sorted(o["num"] for o in result.collect(attrs=["num"])) == [1, 2, 4, 5, 7]
+    assert [o.num for o in result] == [o.num for o in result.collect()]
+
+    stop = threading.Event()
+    def churn():
+        while not stop.is_set():
+            index.add_object_many([TestClass(num=100 + i, keep=i % 2 == 0) for i in range(50)])
+            index.reduce(keep=True)
+    writer = threading.Thread(target=churn)
+    writer.start()
+    try:
+        for _ in range(20):
+            snapshot = index.reduced_query(Q.lt("num", 8))
+            assert sorted(o.num for o in snapshot.iter(batch_size=1)) == [1, 1, 2, 4, 5, 7]
+    finally:
+        stop.set()
+        writer.join()
+
+def test_group_by():
+    index = Index()
+    index.add_object_many([
+        TestClass(num=0, region="eu", status="active"),
+        TestClass(num=1, region="eu", status="closed"),
+        TestClass(num=2, region="us", status="active"),
+        TestClass(num=3, region="eu", status="active"),
+        TestClass(num=4, region="us"),
+        TestClass(num=5, status="active"),
+        TestClass(num=6, region=["eu"], status="active"),
+    ])
+
+    def nums(filtered):
+        return sorted(o.num for o in filtered.collect())
+
+    by_region = index.group_by("region")
+    assert {k: nums(v) for k, v in by_region.items()} == {"eu": [0, 1, 3], "us": [2, 4]}
+
+    nested = index.group_by(["region", "status"])
+    assert {k: {s: nums(v) for s, v in inner.items()} for k, inner in nested.items()} == {
+        "eu": {"active": [0, 3], "closed": [1]},
+        "us": {"active": [2]},
+    }
+
+    flat = index.group_by(["region", "status"], flat=True)
+    assert {k: nums(v) for k, v in flat.items()} == {
+        ("eu", "active"): [0, 3], ("eu", "closed"): [1], ("us", "active"): [2],
+    }
+
+    # values python treats as equal share a group
+    numbers = Index()
+    numbers.add_object_many([TestClass(num=0, v=1), TestClass(num=1, v=1.0), TestClass(num=2, v=2)])
+    assert {k: nums(v) for k, v in numbers.group_by("v").items()} == {1: [0, 1], 2: [2]}
+
+    # groups come out sorted by key, numbers before strings, whatever the insertion order
+    mixed = Index()
+    mixed.add_object_many([TestClass(v=v) for v in ["b", 3, "a", 1.5, 3.0, -2, "b", True]])
+    assert list(mixed.group_by_count("v").items()) == [(-2, 1), (True, 1), (1.5, 1), (3, 2), ("a", 1), ("b", 2)]
+    assert list(index.group_by(["region", "status"], flat=True)) == [("eu", "active"), ("eu", "closed"), ("us", "active")]
+    assert [row["v"] for row in mixed.group_agg("v")] == [-2, True, 1.5, 3, "a", "b"]
+
+    # groups stay queryable and filtered indexes group their own objects
+    assert nums(by_region["eu"].reduced(status="active")) == [0, 3]
+    active = index.reduced(status="active")
+    assert {k: nums(v) for k, v in active.group_by("region").items()} == {"eu": [0, 3], "us": [2]}
+
+    # read from the index structures, so dates, coerced strings and tuples keep their own values
+    import datetime
+    typed = Index()
+    typed.coerce_numeric("v")
+    day = datetime.date(2024, 1, 2)
+    typed.add_object_many([TestClass(v=day), TestClass(v=day), TestClass(v="5"), TestClass(v=5), TestClass(v=(1, 2)), TestClass(v=None), TestClass(v=[1])])
+    counts = list(typed.group_by_count("v").items())
+    # values without a natural order follow the ordered ones
+    assert counts[:2] == [(5, 1), ("5", 1)]
+    assert dict(counts[2:]) == {day: 2, (1, 2): 1, None: 1}
+
+    with pytest.raises(ValueError):
+        index.group_by([])
+
+def test_group_by_count_and_nested():
+    eu, us = TestClass(region="eu"), TestClass(region="us")
+    index = Index()
+    index.add_object_many([
+        TestClass(num=0, owner=eu, status="active"),
+        TestClass(num=1, owner=us, status="active"),
+        TestClass(num=2, owner=eu, status="closed"),
+        TestClass(num=3, owner=TestClass(), status="active"),
+        TestClass(num=4, status="active"),
+    ])
+
+    by_region = index.group_by("owner.region")
+    assert {k: sorted(o.num for o in v.collect()) for k, v in by_region.items()} == {"eu": [0, 2], "us": [1]}
+    assert index.group_by_count("owner.region") == {"eu": 2, "us": 1}
+    assert index.group_by_count("status") == {"active": 4, "closed": 1}
+    assert index.group_by_count(["owner.region", "status"]) == {"eu": {"active": 1, "closed": 1}, "us": {"active": 1}}
+    assert index.group_by_count(["owner.region", "status"], flat=True) == {
+        ("eu", "active"): 1, ("eu", "closed"): 1, ("us", "active"): 1,
+    }
+
+    # nested values are read as they are now
+    us.region = "eu"
+    assert index.group_by_count("owner.region") == {"eu": 3}
+    active = index.reduced(status="active")
+    assert active.group_by_count("owner.region") == {"eu": 2}
+    assert {k: len(v.collect()) for k, v in active.group_by("owner.region").items()} == {"eu": 2}
+    assert index.group_agg("owner.region", metrics={"num": "sum"}) == [{"owner.region": "eu", "count": 3, "num_sum": 3.0}]
+
+    with pytest.raises(ValueError):
+        index.group_by_count([])
+
+def test_aggregate():
+    index = Index()
+    index.add_object_many([TestClass(num=n, kind="odd" if n % 2 else "even", price=float(n)) for n in range(1, 11)])
+    index.add_object(TestClass(num="n/a", price=None))
+
+    stats = index.aggregate("num", ["sum", "count", "min", "max", "mean", "median", "p95"])
+    assert list(stats) == ["sum", "count", "min", "max", "mean", "median", "p95"]
+    assert stats["sum"] == 55 and stats["count"] == 10 and isinstance(stats["count"], int)
+    assert (stats["min"], stats["max"], stats["mean"], stats["median"]) == (1, 10, 5.5, 5.5)
+    assert stats["p95"] == pytest.approx(9.55)
+
+    odd = index.reduced(kind="odd")
+    assert odd.aggregate("price", ["sum", "max", "p0", "p100"]) == {"sum": 25.0, "max": 9.0, "p0": 1.0, "p100": 9.0}
+    assert index.reduced(kind="none").aggregate("num", ["sum", "count", "min", "p50"]) == {"sum": 0.0, "count": 0, "min": None, "p50": None}
+    assert index.aggregate("missing", ["count", "avg"]) == {"count": 0, "avg": None}
+
+    money = Index()
+    money.fixed_point("price")
+    money.add_object_many([TestClass(price=0.1) for _ in range(10)])
+    assert money.aggregate("price", ["sum", "max", "median"]) == {"sum": 1.0, "max": 0.1, "median": 0.1}
+
+    # only numbers are aggregated, values keyed in other spaces are left out rather than decoded as floats
+    from datetime import date, datetime, timedelta
+    from decimal import Decimal
+    mixed = Index()
+    mixed.add_object_many([
+        TestClass(v=1), TestClass(v=2.5), TestClass(v=Decimal("7.25")),
+        TestClass(v=datetime(2024, 1, 1)), TestClass(v=date(2020, 1, 1)), TestClass(v=timedelta(days=3)),
+        TestClass(v="x"),
+    ])
+    assert mixed.aggregate("v", ["sum", "count", "min", "max", "median"]) == {"sum": 10.75, "count": 3, "min": 1, "max": 7.25, "median": 2.5}
+    dated = mixed.reduced(v=datetime(2024, 1, 1))
+    assert dated.aggregate("v", ["sum", "count", "max"]) == {"sum": 0.0, "count": 0, "max": None}
+
+    with pytest.raises(ValueError):
+        index.aggregate("num", ["p101"])
+    with pytest.raises(ValueError):
+        index.aggregate("num", ["mode"])
+
+def test_group_agg():
+    from PyThermite import attr
+
+    index = Index()
+    index.add_object_many([
+        TestClass(num=0, region="eu", status="active", price=10),
+        TestClass(num=1, region="eu", status="closed", price=20.5),
+        TestClass(num=2, region="us", status="active", price=5),
+        TestClass(num=3, region="eu", status="active", price="n/a"),
+        TestClass(num=4, region="us", status="active"),
+        TestClass(num=5, region="ap", status="active"),
+    ])
+
+    def by_key(rows, *keys):
+        return sorted(rows, key=lambda row: tuple(row[k] for k in keys))
+
+    rows = by_key(index.group_agg("region", metrics={"price": ["mean", "max", "count"]}), "region")
+    assert rows == [
+        {"region": "ap", "count": 1, "price_mean": None, "price_max": None, "price_count": 0},
+        {"region": "eu", "count": 3, "price_mean": 15.25, "price_max": 20.5, "price_count": 2},
+        {"region": "us", "count": 2, "price_mean": 5.0, "price_max": 5.0, "price_count": 1},
+    ]
+
+    rows = by_key(index.group_agg(["region", "status"], metrics={"price": "sum"}, having=attr("count") > 1), "region")
+    assert rows == [
+        {"region": "eu", "status": "active", "count": 2, "price_sum": 10.0},
+        {"region": "us", "status": "active", "count": 2, "price_sum": 5.0},
+    ]
+
+    # a group without numbers fails every comparison on the metric
+    rows = index.group_agg("region", metrics={"price": "max"}, having=(attr("price_max") < 100) | (attr("count") == 0))
+    assert sorted(row["region"] for row in rows) == ["eu", "us"]
+
+    active = index.reduced(status="active")
+    assert by_key(active.group_agg("region", having=Q.in_("count", [2])), "region") == [
+        {"region": "eu", "count": 2},
+        {"region": "us", "count": 2},
+    ]
+
+    with pytest.raises(ValueError, match="price_mean"):
+        index.group_agg("region", metrics={"price": "max"}, having=attr("price_mean") > 1)
+    with pytest.raises(ValueError):
+        index.group_agg("region", metrics={"price": "median"})
+    with pytest.raises(TypeError):
+        index.group_agg("region", having=attr("count") > "a")
+
+def test_pivot():
+    index = Index()
+    index.add_object_many([
+        TestClass(num=0, region="eu", status="active", price=10),
+        TestClass(num=1, region="eu", status="closed", price=20.5),
+        TestClass(num=2, region="us", status="active", price=5),
+        TestClass(num=3, region="eu", status="active", price=1),
+        TestClass(num=4, region="us", status="active"),
+        TestClass(num=5, status="active", price=100),
+    ])
+
+    assert index.pivot("region", "status") == {
+        "eu": {"active": 2, "closed": 1},
+        "us": {"active": 2},
+    }
+    assert index.pivot(rows="region", cols="status", metric=("price", "sum")) == {
+        "eu": {"active": 11.0, "closed": 20.5},
+        "us": {"active": 5.0},
+    }
+    assert index.pivot("status", "region", metric=("price", "count")) == {
+        "active": {"eu": 2, "us": 1},
+        "closed": {"eu": 1},
+    }
+    assert index.reduced(status="active").pivot("region", "status", metric=("price", "max")) == {
+        "eu": {"active": 10.0},
+        "us": {"active": 5.0},
+    }
+    with pytest.raises(ValueError):
+        index.pivot("region", "status", metric=("price", "median"))
+
+def test_sample():
+    index = Index()
+    index.add_object_many([
+        TestClass(num=i, region=["eu", "us", "ap"][i % 3], weight=(0 if i % 2 else 1) if i < 40 else 1000)
+        for i in range(60)
+    ])
+
+    def nums(filtered):
+        return sorted(o.num for o in filtered.collect())
+
+    picked = index.sample(10, seed=7)
+    assert len(nums(picked)) == 10
+    assert nums(picked) == nums(index.sample(10, seed=7))
+    assert len(nums(index.sample(100))) == 60
+
+    # zero weights are never drawn, heavy ones dominate
+    weighted = nums(index.sample(20, weights="weight", seed=1))
+    assert len(weighted) == 20
+    assert all(n % 2 == 0 or n >= 40 for n in weighted)
+    assert sum(n >= 40 for n in weighted) >= 15
+
+    stratified = index.sample(4, stratify_by="region", seed=3).collect()
+    counts = {}
+    for obj in stratified:
+        counts[obj.region] = counts.get(obj.region, 0) + 1
+    assert counts == {"eu": 4, "us": 4, "ap": 4}
+
+    eu = index.reduced(region="eu")
+    assert all(o.region == "eu" for o in eu.sample(5, seed=2).collect())
+    assert len(eu.sample(5, stratify_by="region", weights="weight").collect()) == 5
+
+def test_duplicates():
+    index = Index()
+    objs = [
+        TestClass(num=0, email="a@x.io", name="a"),
+        TestClass(num=1, email="b@x.io", name="b"),
+        TestClass(num=2, email="a@x.io", name="a"),
+        TestClass(num=3, email="a@x.io", name="c"),
+        TestClass(num=4, email="b@x.io", name="b"),
+        TestClass(num=5, name="a"),
+    ]
+    index.add_object_many(objs)
+    ids = {o.num: next(iter(index.reduced(num=o.num).ids())) for o in objs}
+
+    def groups(sets):
+        return sorted(sorted(num for num, id in ids.items() if id in s) for s in sets)
+
+    assert groups(index.duplicates("email")) == [[0, 2, 3], [1, 4]]
+    assert groups(index.duplicates(["email", "name"])) == [[0, 2], [1, 4]]
+    assert index.duplicates(["num"]) == []
+
+    redundant = index.duplicates("email", keep="first")
+    kept_first = {min(ids[0], ids[2], ids[3]), min(ids[1], ids[4])}
+    assert set(redundant.ids()) == {ids[n] for n in range(5)} - kept_first
+    kept_last = {max(ids[0], ids[2], ids[3]), max(ids[1], ids[4])}
+    assert set(index.duplicates("email", keep="last").ids()) == {ids[n] for n in range(5)} - kept_last
+
+    assert groups(index.reduced(name="a").duplicates("email")) == [[0, 2]]
+    with pytest.raises(ValueError):
+        index.duplicates("email", keep="middle")
+
+def test_outliers():
+    index = Index()
+    latencies = [10, 11, 12, 12.5, 13, 14, 15, 16, -40, 250]
+    index.add_object_many(
+        [TestClass(num=i, latency=v, region="eu" if i % 2 else "us") for i, v in enumerate(latencies)]
+        + [TestClass(num=100, latency="slow"), TestClass(num=101)]
+    )
+
+    def nums(filtered):
+        return sorted(o.num for o in filtered.collect())
+
+    assert nums(index.outliers("latency")) == [8, 9]
+    assert nums(index.outliers("latency", threshold=100)) == []
+    assert nums(index.outliers("latency", method="zscore", threshold=2)) == [9]
+    assert nums(index.outliers("latency", filter=Q.lt("latency", 200))) == [8]
+    assert nums(index.reduced(region="eu").outliers("latency")) == [9]
+    assert nums(index.outliers("missing")) == []
+
+    with pytest.raises(ValueError):
+        index.outliers("latency", method="mad")
+
+    # both methods and aggregate read the same numbers, dates and coerced strings included or not alike
+    from datetime import date
+    from decimal import Decimal
+    mixed = Index()
+    mixed.coerce_numeric("latency")
+    mixed.add_object_many([TestClass(num=i, latency=v) for i, v in enumerate(latencies)])
+    mixed.add_object_many([TestClass(num=20, latency=date(2024, 1, 1)), TestClass(num=21, latency="12"), TestClass(num=22, latency=Decimal("13"))])
+    assert mixed.aggregate("latency", ["count", "min", "max"]) == {"count": 12, "min": -40, "max": 250}
+    assert nums(mixed.outliers("latency")) == [8, 9]
+    assert nums(mixed.outliers("latency", method="zscore", threshold=2)) == [9]
+
+def test_nearest():
+    index = Index()
+    prices = [99, 100.25, 98, 101, 120, 99.5, 50, -3]
+    index.add_object_many(
+        [TestClass(num=i, price=p, kind="a" if i % 2 else "b") for i, p in enumerate(prices)]
+        + [TestClass(num=10, price="99.5"), TestClass(num=11)]
+    )
+
+    def nums(objs):
+        return [o.num for o in objs]
+
+    assert nums(index.nearest("price", 99.5, k=1)) == [5]
+    assert nums(index.nearest("price", 99.5, k=3)) == [5, 0, 1]
+    # 98 and 99 are equally far, the lower value first
+    assert nums(index.nearest("price", 98.5, k=2)) == [2, 0]
+    assert nums(index.nearest("price", 99.75, k=2)) == [5, 1]
+    assert nums(index.nearest("price", 1000, k=2)) == [4, 3]
+    assert nums(index.nearest("price", -100, k=2)) == [7, 6]
+    assert len(index.nearest("price", 0, k=100)) == 8
+    assert nums(index.nearest("price", 99.5)) == [5, 0, 1, 2, 3]
+
+    assert nums(index.reduced(kind="a").nearest("price", 99.5, k=2)) == [5, 1]
+    assert index.nearest("missing", 1.0) == []
+    assert index.nearest("price", float("nan")) == []
+
+def test_float_tolerance():
+    index = Index()
+    readings = [TestClass(temp=t, sensor=TestClass(temp=t)) for t in [21.49, 21.5, 21.53, 21.6, 22]]
+    index.add_object_many(readings)
+    assert [r.temp for r in index.reduced_query(Q.eq("temp", 21.5)).collect()] == [21.5]
+
+    index.float_tolerance("temp", 0.05)
+    index.float_tolerance("sensor.temp", 0.4)
+    assert matching(index, Q.eq("temp", 21.5), "temp") == [21.49, 21.5, 21.53]
+    assert matching(index, Q.ne("temp", 21.5), "temp") == [21.6, 22]
+    assert matching(index, Q.in_("temp", [21.5, 22]), "temp") == [21.49, 21.5, 21.53, 22]
+    assert len(index.reduced_query(Q.eq("sensor.temp", 21.5)).collect()) == 4
+
+    # views read the declaration from the index they came from
+    view = index.reduced_query(Q.gt("temp", 21.5))
+    assert matching(view, Q.eq("temp", 21.55), "temp") == [21.53, 21.6]
+
+    index.float_tolerance("temp", 0)
+    assert len(index.reduced_query(Q.eq("temp", 21.5)).collect()) == 1
+    with pytest.raises(ValueError):
+        index.float_tolerance("temp", -1)
+    with pytest.raises(ValueError):
+        index.float_tolerance("temp", float("nan"))
+
+def test_units():
+    index = Index()
+    index.units("latency", "ms")
+    index.units("disk.size", "MiB")
+    objs = [TestClass(latency=ms, disk=TestClass(size=mib)) for ms, mib in [(250, 512), (1500, 1024), (2000, 4096), (90000, 8)]]
+    index.add_object_many(objs)
+
+    assert matching(index, Q.gt("latency", "2s"), "latency") == [90000]
+    assert matching(index, Q.ge("latency", "2 s"), "latency") == [2000, 90000]
+    assert matching(index, Q.bt("latency", "0.2s", 1500), "latency") == [250, 1500]
+    assert matching(index, Q.eq("latency", "1.5e3ms"), "latency") == [1500]
+    assert matching(index, Q.in_("latency", ["250ms", "1.5min"]), "latency") == [250, 90000]
+    assert matching(index, Q.not_(Q.lt("latency", "1min")), "latency") == [90000]
+    assert matching(index, Q.ge("disk.size", "1GiB"), "latency") == [1500, 2000]
+    assert matching(index, Q.lt("disk.size", "10MB"), "latency") == [90000]
+
+    view = index.reduced_query(Q.lt("latency", "1min"))
+    assert matching(view, Q.gt("latency", "1s"), "latency") == [1500, 2000]
+
+    with pytest.raises(ValueError, match="time"):
+        index.reduced_query(Q.gt("latency", "2kg"))
+    with pytest.raises(ValueError):
+        index.reduced_query(Q.gt("latency", "soon"))
+    with pytest.raises(ValueError):
+        index.units("latency", "fortnight")
+
+def test_fixed_point():
+    index = Index()
+    index.fixed_point("price")
+    index.fixed_point("lines.amount", places=4)
+    objs = [TestClass(kind="a", price=0.1, lines=[TestClass(amount=0.0001) for _ in range(3)]) for _ in range(10)]
+    objs.append(TestClass(kind="b", price=0.1 + 0.2, lines=[]))
+    objs.append(TestClass(kind="b", price=5, lines=[]))
+    index.add_object_many(objs)
+
+    assert sum(o.price for o in objs[:10]) != 1.0
+    rows = {row["kind"]: row for row in index.group_agg("kind", metrics={"price": ["sum", "avg"]})}
+    assert rows["a"]["price_sum"] == 1.0
+    assert rows["a"]["price_avg"] == 0.1
+    assert rows["b"]["price_sum"] == 5.3
+    assert index.pivot("kind", "kind", metric=("price", "sum")) == {"a": {"a": 1.0}, "b": {"b": 5.3}}
+
+    assert index.reduced_query(Q.eq("price", 0.3)).collect() == [objs[10]]
+    assert len(index.reduced_query(Q.bt("price", 0.1, 0.3)).collect()) == 11
+    assert index.reduced_query(Q.gt("price", 4.999)).collect() == [objs[11]]
+    assert len(index.reduced_query(Q.agg("lines", "sum", "amount", eq=0.0003)).collect()) == 10
+
+    objs[11].price = 0.2
+    assert index.reduced_query(Q.eq("price", 0.2)).collect() == [objs[11]]
+    assert index.reduced_query(Q.eq("price", 5)).collect() == []
+
+    with pytest.raises(ValueError):
+        Index().fixed_point("price", places=3)
+    with pytest.raises(ValueError):
+        index.fixed_point("cost")
+
+def test_interval_overlaps():
+    index = Index()
+    index.interval("availability")
+    index.interval("shift.hours")
+    objs = [
+        TestClass(num=0, availability=(0, 10)),
+        TestClass(num=1, availability=[5, 7]),
+        TestClass(num=2, availability=(-4.5, -1)),
+        TestClass(num=3, availability=(10, 20)),
+        TestClass(num=4, availability=(3, 1)),
+        TestClass(num=5, availability=5),
+        TestClass(num=6, shift=TestClass(hours=(9, 17))),
+    ]
+    for obj in objs:
+        obj.keep = obj.num != 3
+    index.add_object_many(objs)
+
+    assert matching(index, Q.overlaps("availability", 8, 12)) == [0, 3]
+    assert matching(index, Q.overlaps("availability", -1, 0)) == [0, 2]
+    assert matching(index, Q.overlaps("availability", 21, 30)) == []
+    assert matching(index, Q.contains_point("availability", 6)) == [0, 1]
+    assert matching(index, Q.contains_point("availability", 10.0)) == [0, 3]
+    assert matching(index, Q.contains_point("shift.hours", 12)) == [6]
+    # pairs stay queryable by content
+    assert matching(index, Q.eq("availability", [5, 7])) == [1]
+
+    objs[1].availability = (11, 12)
+    assert matching(index, Q.contains_point("availability", 6)) == [0]
+    assert matching(index, Q.overlaps("availability", 12, 12)) == [1, 3]
+    index.reduce(keep=True)
+    assert matching(index, Q.overlaps("availability", 12, 12)) == [1]
+    assert matching(index, Q.not_(Q.contains_point("availability", 0))) == [1, 2, 4, 5, 6]
+
+    assert matching(index, Q.overlaps("undeclared", 0, 100)) == []
+    with pytest.raises(TypeError):
+        index.reduced_query(Q.overlaps("availability", "a", 1))
+    with pytest.raises(ValueError):
+        index.interval("late")
+
+def test_traverse():
+    ceo = TestClass(name="ceo", level=0)
+    vp = TestClass(name="vp", level=1, manager=ceo)
+    lead = TestClass(name="lead", level=2, manager=vp)
+    dev = TestClass(name="dev", level=3, manager=lead)
+    contractor = TestClass(name="contractor", level=3, manager=TestClass(name="agency"))
+    ceo.reports = [vp]
+    vp.reports = [lead]
+    lead.reports = (dev,)
+    index = Index()
+    # references are followed from the stored values, nested indexing would not end on the cycles
+    index.limit_nested("manager", depth=0)
+    index.limit_nested("reports", depth=0)
+    index.add_object_many([ceo, vp, lead, dev, contractor])
+
+    def names(filtered):
+        return sorted(o.name for o in filtered.collect())
+
+    assert names(index.traverse([dev], via=["manager"])) == ["ceo", "lead", "vp"]
+    assert names(index.traverse([dev], via=["manager"], depth=2)) == ["lead", "vp"]
+    assert names(index.traverse([dev], via=["manager"], depth=0)) == []
+    assert names(index.traverse([ceo], via=["reports"])) == ["dev", "lead", "vp"]
+    # the chain stops at objects failing the filter
+    assert names(index.traverse([dev], via=["manager"], filter=Q.ne("name", "vp"))) == ["lead"]
+    # cycles through both directions end once nothing new is reached, the start only when reached again
+    assert names(index.traverse([vp], via=["manager", "reports"])) == ["ceo", "dev", "lead", "vp"]
+    # references outside the index are not followed
+    assert names(index.traverse([contractor], via=["manager"])) == []
+    assert names(index.reduced(level=2).traverse([dev], via=["manager"])) == ["lead"]
+
+    with pytest.raises(ValueError):
+        index.traverse([dev], via=[])
+
+def test_connected_components():
+    nodes = {name: TestClass(name=name, group="x" if name < "f" else "y") for name in "abcdefgh"}
+    nodes["a"].links = [nodes["b"]]
+    nodes["b"].links = [nodes["c"], nodes["a"]]
+    nodes["c"].links = nodes["d"]
+    nodes["e"].links = [nodes["d"]]
+    nodes["f"].links = (nodes["g"],)
+    nodes["g"].peer = nodes["h"]
+    index = Index()
+    index.limit_nested("links", depth=0)
+    index.limit_nested("peer", depth=0)
+    index.add_object_many(list(nodes.values()))
+
+    def names(filtered):
+        return "".join(sorted(o.name for o in filtered.collect()))
+
+    assert index.connected(nodes["a"], nodes["e"], via="links")
+    # links count in both directions
+    assert index.connected(nodes["e"], nodes["a"], via="links")
+    assert not index.connected(nodes["a"], nodes["e"], via="links", max_depth=3)
+    assert index.connected(nodes["a"], nodes["e"], via="links", max_depth=4)
+    assert index.connected(nodes["a"], nodes["a"], via="links")
+    assert not index.connected(nodes["a"], nodes["f"], via="links", max_depth=None)
+    assert not index.connected(nodes["f"], nodes["h"], via="links")
+    assert index.connected(nodes["f"], nodes["h"], via=["links", "peer"])
+
+    assert names(index.component_of(nodes["d"], via="links")) == "abcde"
+    assert names(index.component_of(nodes["h"], via=["links", "peer"])) == "fgh"
+    assert names(index.component_of(nodes["h"], via="links")) == "h"
+    # paths only run through objects of the FilteredIndex
+    assert names(index.reduced(group="x").component_of(nodes["a"], via="links")) == "abcde"
+    assert names(index.reduced_query(Q.ne("name", "c")).component_of(nodes["a"], via="links")) == "ab"
+    assert not index.reduced_query(Q.ne("name", "c")).connected(nodes["a"], nodes["e"], via="links")
+    assert names(index.reduced(group="y").component_of(nodes["a"], via="links")) == ""
+
+def test_sort_keys():
+    index = Index()
+    index.semver("version")
+    prices = [3, -1.5, 0, 2.25, -1000, 1e300, 7]
+    objs = [TestClass(num=i, price=p, kind=i % 2) for i, p in enumerate(prices)]
+    objs += [TestClass(num=10, price="cheap"), TestClass(num=11)]
+    objs += [TestClass(num=20, version=v) for v in ["1.10.0", "1.2.0", "1.2.0-rc.1"]]
+    index.add_object_many(objs)
+
+    keys = index.sort_keys("price")
+    assert len(keys) == len(prices)
+    assert all(isinstance(key, bytes) and len(key) == 10 for _, key in keys)
+    ordered = [obj.price for obj, _ in sorted(keys, key=lambda pair: pair[1])]
+    assert ordered == sorted(prices)
+    # the same value encodes the same way in any index
+    other = Index()
+    other.add_object(TestClass(price=2.25))
+    assert other.sort_keys("price")[0][1] == dict((o.num, k) for o, k in keys)[3]
+
+    assert sorted(o.num for o, _ in index.reduced(kind=1).sort_keys("price")) == [1, 3, 5]
+    versions = [obj.version for obj, _ in sorted(index.sort_keys("version"), key=lambda pair: pair[1])]
+    assert versions == ["1.2.0-rc.1", "1.2.0", "1.10.0"]
+    assert index.sort_keys("missing") == []
+
+def test_track_top_k():
+    index = Index()
+    objs = [TestClass(num=i, score=s) for i, s in enumerate([5, 1, 9, 3, 7])]
+    index.add_object_many(objs)
+    index.track_top_k("score", k=3)
+
+    def top():
+        return [o.score for o in index.top_k("score")]
+
+    assert top() == [9, 7, 5]
+    index.add_object(TestClass(num=5, score=8.5))
+    assert top() == [9, 8.5, 7]
+    index.add_object_many([TestClass(num=6, score=0), TestClass(num=7, score="high"), TestClass(num=8)])
+    assert top() == [9, 8.5, 7]
+
+    # a member dropping out lets the next best in
+    objs[2].score = 2
+    assert top() == [8.5, 7, 5]
+    objs[1].score = 100
+    assert top() == [100, 8.5, 7]
+    objs[1].score = "gone"
+    assert top() == [8.5, 7, 5]
+
+    index.reduce(num=4)
+    assert top() == [7]
+
+    index.track_top_k("score", k=1)
+    index.add_object_many([TestClass(num=9, score=s) for s in [6, 11, 10]])
+    assert top() == [11]
+
+    with pytest.raises(ValueError):
+        index.top_k("missing")
+    with pytest.raises(ValueError):
+        index.track_top_k("score", k=0)
+
+def test_alert_when():
+    index = Index()
+    positions = [TestClass(desk="fx", exposure=e) for e in [400, 300]]
+    index.add_object_many(positions + [TestClass(desk="rates", exposure=900)])
+
+    fired = []
+    index.alert_when(("sum", "exposure"), ">", 1000, filter=Q.eq("desk", "fx"), callback=fired.append)
+    counts = []
+    index.alert_when(("count", "exposure"), ">=", 5, callback=counts.append)
+    assert fired == [] and counts == []
+
+    # only the mutation crossing the threshold fires
+    index.add_object(TestClass(desk="fx", exposure=200))
+    assert fired == []
+    positions[0].exposure = 600
+    assert fired == [1100]
+    positions[1].exposure = 350
+    assert fired == [1100]
+
+    # back under, then across again
+    positions[0].desk = "rates"
+    positions[0].desk = "fx"
+    assert fired == [1100, 1150]
+    assert counts == []
+    index.add_object_many([TestClass(desk="eq", exposure=1), TestClass(desk="eq", exposure="n/a")])
+    assert counts == [5]
+
+    # reduce drops every fx position, so the sum starts over
+    index.reduce(desk="rates")
+    index.add_object_many([TestClass(desk="fx", exposure=2000)])
+    assert fired == [1100, 1150, 2000]
+
+    # a failing callback is reported as a warning, the write it fired on still happens
+    import warnings
+
+    def broken(total):
+        raise RuntimeError("notify failed")
+
+    index.alert_when(("sum", "exposure"), ">", 5000, callback=broken)
+    with warnings.catch_warnings(record=True) as caught:
+        warnings.simplefilter("always")
+        index.add_object(TestClass(desk="fx", exposure=4000))
+    assert [w.category for w in caught] == [RuntimeWarning] and "notify failed" in str(caught[0].message)
+    assert fired == [1100, 1150, 2000]
+    assert [o.exposure for o in index.reduced(exposure=4000).collect()] == [4000]
+
+    # assigning a list fires like any other value
+    tagged = Index()
+    tickets = [TestClass(tags=["low"], exposure=10) for _ in range(2)]
+    tagged.add_object_many(tickets)
+    urgent = []
+    tagged.alert_when(("count", "exposure"), ">=", 1, filter=Q.eq("tags", "urgent"), callback=urgent.append)
+    tickets[0].tags = ["urgent"]
+    assert urgent == [1]
+
+    with pytest.raises(ValueError):
+        index.alert_when(("median", "exposure"), ">", 1, callback=print)
+    with pytest.raises(ValueError):
+        index.alert_when(("sum", "exposure"), "=>", 1, callback=print)
+
+def test_set_column():
+    index = Index()
+    objs = [TestClass(num=i, score=0) for i in range(5)]
+    index.add_object_many(objs)
+    index.track_top_k("score", k=2)
+    fired = []
+    index.alert_when(("sum", "score"), ">", 50, callback=fired.append)
+    ids = {o.num: next(iter(index.reduced(num=o.num).ids())) for o in objs}
+
+    index.set_column("score", [ids[n] for n in range(5)], [n * 10 for n in range(5)])
+    assert [o.score for o in objs] == [0, 10, 20, 30, 40]
+    assert {o.num for o in index.reduced_query(Q.gt("score", 15)).collect()} == {2, 3, 4}
+    assert [o.score for o in index.top_k("score")] == [40, 30]
+    # one event for the whole column
+    assert fired == [100]
+
+    # a new attribute, only for the ids given
+    index.set_column("label", (ids[1], ids[3]), ("b", "d"))
+    assert {o.num for o in index.get_by_attribute(label="d")} == {3}
+
+    # nothing is assigned when a check fails
+    with pytest.raises(ValueError):
+        index.set_column("score", [ids[0], ids[1]], [1])
+    with pytest.raises(ValueError):
+        index.set_column("score", [ids[0], max(ids.values()) + 100], [1, 2])
+    assert [o.score for o in objs] == [0, 10, 20, 30, 40]
+
+def test_update_batch():
+    index = Index()
+    objs = [TestClass(num=i, score=i, tags=["a"]) for i in range(4)]
+    index.add_object_many(objs)
+    index.track_top_k("score", k=2)
+    fired = []
+    index.alert_when(("sum", "score"), ">", 100, callback=fired.append)
+
+    objs[0].update(score=50, tags=["a", "b"], label="x")
+    assert (objs[0].score, objs[0].tags, objs[0].label) == (50, ["a", "b"], "x")
+    assert index.get_by_attribute(score=50) == [objs[0]]
+    assert index.get_by_attribute(score=0) == []
+    assert {o.num for o in index.reduced_query(Q.contains("tags", "b")).collect()} == {0}
+    assert [o.score for o in index.top_k("score")] == [50, 3]
+    objs[0].update()
+
+    index.update_many(objs[1:3], score=40, label="y")
+    assert {o.num for o in index.get_by_attribute(label="y")} == {1, 2}
+    assert [o.score for o in index.top_k("score")] == [50, 40]
+    # alerts run once the whole batch is in place
+    assert fired == [133]
+
+    # nothing is set when one value is refused
+    class Product(TestClass):
+        pass
+
+    Product.field("price", float)
+    p = Product(price=1.0)
+    index.add_object(p)
+    with pytest.raises(TypeError):
+        p.update(label="z", price="cheap")
+    with pytest.raises(TypeError):
+        index.update_many([objs[3], p], label="z", price="cheap")
+    assert p.price == 1.0 and not hasattr(p, "label")
+    assert index.get_by_attribute(label="z") == []
+
+    strict = Index(strict=True)
+    strict.add_object(objs[3])
+    with pytest.raises(TypeError):
+        objs[3].update(score=7, blob=object())
+    assert objs[3].score == 3
+
+def test_bulk_update():
+    index = Index()
+    objs = [TestClass(num=i, score=i, tags=["a"]) for i in range(5)]
+    index.add_object_many(objs)
+    index.track_top_k("score", k=2)
+    fired = []
+    index.alert_when(("sum", "score"), ">", 100, callback=fired.append)
+
+    def nums(**kwargs):
+        return sorted(o.num for o in index.get_by_attribute(**kwargs))
+
+    with index.bulk_update():
+        for step in range(10):
+            for o in objs:
+                o.score = o.num * 10 + step
+        objs[0].tags = ["b"]
+        objs[1].tags = ["a", "b"]
+        objs[2].label = "new"
+        # the maps still hold the values from before the block
+        assert nums(score=1) == [1]
+        assert nums(label="new") == []
+        assert fired == []
+    assert nums(score=9) == [0]
+    assert nums(score=49) == [4]
+    assert nums(score=1) == []
+    assert nums(label="new") == [2]
+    assert matching(index, Q.contains("tags", "b")) == [0, 1]
+    assert [o.score for o in index.top_k("score")] == [49, 39]
+    assert fired == [145]
+
+    # nested blocks apply the changes when the outermost exits, even when it raises
+    with pytest.raises(KeyError):
+        with index.bulk_update():
+            with index.bulk_update():
+                objs[0].score = 500
+            assert nums(score=500) == []
+            raise KeyError()
+    assert nums(score=500) == [0]
+
+    # objects dropped in the block are left out, set back values need nothing
+    with index.bulk_update():
+        objs[1].score = -1
+        objs[2].score = 1000
+        objs[2].score = 29
+        index.reduce(tags=["a"])
+    assert nums(score=-1) == []
+    assert nums(score=29) == [2]
+    assert {o.num for o in index.collect()} == {2, 3, 4}
+
+    block = index.bulk_update()
+    with block:
+        with pytest.raises(ValueError):
+            block.__enter__()
+    index.freeze_mutations()
+    with pytest.raises(ValueError):
+        index.bulk_update()
+
+def test_roaring_bytes():
+    index = Index()
+    objs = [TestClass(num=i, even=i % 2 == 0) for i in range(10)]
+    index.add_object_many(objs)
+
+    buf = index.reduced(even=True).to_roaring_bytes()
+    assert isinstance(buf, bytes)
+    restored = index.filter_from_roaring_bytes(buf)
+    assert restored.ids() == index.reduced(even=True).ids()
+    assert {o.num for o in restored.collect()} == {0, 2, 4, 6, 8}
+    assert index.filter_from_roaring_bytes(index.reduced(num=100).to_roaring_bytes()).collect() == []
+
+    # ids the index no longer holds are ignored
+    index.reduce(num=4)
+    assert [o.num for o in index.filter_from_roaring_bytes(buf).collect()] == [4]
+
+    with pytest.raises(ValueError):
+        index.filter_from_roaring_bytes(b"not a bitmap")
+
+def test_union_with_concurrent():
+    import threading
+
+    first, second = Index(), Index()
+    first.add_object_many([TestClass(num=i, side="first") for i in range(50)])
+    second.add_object_many([TestClass(num=i, side="second") for i in range(50)])
+
+    # unions in both directions and onto itself, all at once
+    def union_many(target, source):
+        for _ in range(50):
+            target.union_with(source)
+            target.union_with(target)
+
+    threads = [
+        threading.Thread(target=union_many, args=pair, daemon=True)
+        for pair in [(first, second), (second, first)] * 2
+    ]
+    for thread in threads:
+        thread.start()
+    for thread in threads:
+        thread.join(timeout=60)
+    assert not any(thread.is_alive() for thread in threads)
+
+    for index in (first, second):
+        assert len(index.collect()) == 100
+        assert len(index.reduced(side="first").collect()) == 50
+        assert len(index.reduced_query(Q.lt("num", 10)).collect()) == 20
+
+    # objects added to the source while it is unioned are taken whole or not at all,
+    # so once changed and added to the target they only match their new value
+    source, target = Index(), Index()
+    late = [TestClass(num=1000 + i) for i in range(200)]
+
+    def add_late():
+        for obj in late:
+            source.add_object(obj)
+
+    adder = threading.Thread(target=add_late, daemon=True)
+    adder.start()
+    while adder.is_alive():
+        target.union_with(source)
+    adder.join()
+    for obj in late:
+        obj.num = -obj.num
+    target.add_object_many(late)
+    assert target.reduced_query(Q.ge("num", 1000)).collect() == []
+    assert len(target.reduced_query(Q.le("num", -1000)).collect()) == 200
+
+    # changes to objects taken by a union reach the target
+    taken = TestClass(num=1)
+    source.add_object(taken)
+    target.union_with(source)
+    taken.num = 2
+    assert target.reduced(num=1).collect() == [] and target.reduced(num=2).collect() == [taken]
+
+def test_concurrent_mutation_stress():
+    import threading
+
+    def run(*targets):
+        errors = []
+
+        def guarded(target):
+            try:
+                target()
+            except BaseException as e:
+                errors.append(e)
+
+        threads = [threading.Thread(target=guarded, args=(t,), daemon=True) for t in targets]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join(timeout=120)
+        assert not any(thread.is_alive() for thread in threads)
+        assert errors == []
+
+    # every query agrees with the values the objects hold once the threads are done
+    def check(index):
+        objs = index.collect()
+        assert len({id(o) for o in objs}) == len(objs)
+        for o in objs:
+            assert o in index.get_by_attribute(num=o.num)
+        for t in (0, 25, 50):
+            expected = {id(o) for o in objs if o.score >= t}
+            assert {id(o) for o in index.reduced_query(Q.ge("score", t)).collect()} == expected
+
+    index = Index()
+    writers, per_writer = 4, 100
+    batches = [[TestClass(num=w * per_writer + i, score=0, writer=w) for i in range(per_writer)] for w in range(writers)]
+
+    # adds in batches while readers query
+    def add(batch):
+        def target():
+            for i in range(0, per_writer, 10):
+                index.add_object_many(batch[i:i + 10])
+        return target
+
+    def read():
+        for _ in range(30):
+            for o in index.reduced_query(Q.eq("score", 0)).collect():
+                assert o.score == 0
+
+    run(*[add(b) for b in batches], read, read)
+    assert len(index.collect()) == writers * per_writer
+    check(index)
+
+    # scores only ever rise, so anything a query returns still matches it afterwards
+    def raise_scores(batch):
+        def target():
+            for score in range(1, 60, 7):
+                for o in batch:
+                    o.score = score
+        return target
+
+    def read_rising():
+        for _ in range(30):
+            for t in (10, 40):
+                for o in index.reduced_query(Q.ge("score", t)).collect():
+                    assert o.score >= t
+
+    run(*[raise_scores(b) for b in batches], read_rising, read_rising)
+    check(index)
+
+    # removal through reduce while others query and union the index into another
+    target = Index()
+
+    def reduce():
+        for _ in range(3):
+            index.reduce(writer=0)
+
+    def union():
+        for _ in range(10):
+            target.union_with(index)
+
+    def read_kept():
+        for _ in range(30):
+            assert all(o.writer in range(writers) for o in index.reduced_query(Q.ge("score", 0)).collect())
+
+    run(reduce, union, read_kept)
+    assert {o.writer for o in index.collect()} == {0}
+    check(index)
+    target.union_with(index)
+    check(target)
+    assert set(map(id, index.collect())) <= set(map(id, target.collect()))
+
+def test_sparsity():
+    index = Index()
+    assert index.sparsity() == {}
+    index.add_object_many([TestClass(num=i, score=i) for i in range(4)])
+    index.add_object_many([TestClass(num=i, note="x") for i in range(4, 8)])
+    index.add_object(TestClass(num=8, score=None, note=["a"]))
+
+    sparsity = index.sparsity()
+    assert list(sparsity) == sorted(sparsity)
+    assert sparsity["num"] == 1.0
+    assert sparsity["score"] == pytest.approx(5 / 9)
+    assert sparsity["note"] == pytest.approx(5 / 9)
+
+    index.reduce(score=None)
+    assert index.sparsity() == {"note": 1.0, "num": 1.0, "score": 1.0}
+
+def test_freeze_mutations():
+    index = Index()
+    child = TestClass(num=10, score=1)
+    objs = [TestClass(num=i, score=i * 10) for i in range(5)]
+    objs[4].child = child
+    index.add_object_many(objs)
+    other = Index()
+    other.add_object(objs[0])
+    stream = index.ingest_stream()
+    assert not index.is_frozen()
+
+    index.freeze_mutations()
+    assert index.is_frozen()
+    with pytest.raises(ValueError):
+        index.add_object(TestClass(num=5))
+    with pytest.raises(ValueError):
+        index.add_object_many([TestClass(num=6)])
+    with pytest.raises(ValueError):
+        index.reduce(num=1)
+    with pytest.raises(ValueError):
+        index.union_with(Index())
+    with pytest.raises(ValueError):
+        objs[1].score = 99
+    assert objs[1].score == 10
+    with pytest.raises(ValueError):
+        child.score = 2
+    with pytest.raises(ValueError):
+        objs[1].update(score=99)
+    with pytest.raises(ValueError):
+        index.set_column("score", list(index.reduced(num=1).ids()), [99])
+    with pytest.raises(ValueError):
+        index.update_many(objs[:2], score=99)
+    with pytest.raises(ValueError):
+        with index.bulk_update():
+            pass
+    with pytest.raises(ValueError):
+        index.add_dicts([{"num": 5}])
+    with pytest.raises(ValueError):
+        index.reap()
+    with pytest.raises(ValueError):
+        index.ingest_stream()
+    # a stream opened before the freeze has its batches refused
+    stream.feed([TestClass(num=5)])
+    with pytest.raises(ValueError):
+        stream.flush()
+    stream.close()
+    assert objs[1].score == 10
+
+    # queries keep working
+    assert len(index.collect()) == 5
+    assert {o.num for o in index.reduced_query(Q.gt("score", 15)).collect()} == {2, 3, 4}
+
+    # other indexes are unaffected, apart from objects the frozen one holds
+    other.add_object(TestClass(num=7))
+    assert len(other.collect()) == 2
+    with pytest.raises(ValueError):
+        objs[0].score = 1
+
+def test_freeze_mutations_snapshot():
+    import threading
+
+    index = Index()
+    objs = [TestClass(num=i, tags=[i % 3], child=TestClass(num=i * 10)) for i in range(30)]
+    index.add_object_many(objs)
+
+    # assignments held back by an open bulk update reach the maps before they are frozen
+    with index.bulk_update():
+        objs[0].num = 100
+        index.freeze_mutations()
+        with pytest.raises(ValueError):
+            objs[1].num = 101
+    assert [o.num for o in index.reduced(num=100).collect()] == [100]
+    assert index.reduced(num=0).collect() == []
+
+    # frozen maps were optimized by the freeze, warmup has nothing left to do
+    index.warmup()
+    index.freeze_mutations()
+
+    # queries from many threads, nested attributes included
+    results = []
+    def query():
+        for _ in range(50):
+            results.append((
+                len(index.reduced_query(Q.lt("num", 10)).collect()),
+                len(index.reduced_query(Q.eq("child.num", 50)).collect()),
+                len(index.reduced_query(Q.eq("tags", 1)).collect()),
+            ))
+    threads = [threading.Thread(target=query, daemon=True) for _ in range(4)]
+    for thread in threads:
+        thread.start()
+    for thread in threads:
+        thread.join(timeout=60)
+    assert not any(thread.is_alive() for thread in threads)
+    assert set(results) == {(9, 1, 10)}
+
+    # a frozen index can still be the source of a union
+    target = Index()
+    target.union_with(index)
+    assert len(target.reduced_query(Q.lt("num", 10)).collect()) == 9
+    target.add_object(TestClass(num=1))
+    assert len(index.collect()) == 30
+
+    # an alert callback can't freeze the index whose change fired it
+    alerted = Index()
+    watched = TestClass(num=1)
+    alerted.add_object(watched)
+    errors = []
+    def freeze(_):
+        try:
+            alerted.freeze_mutations()
+        except ValueError as e:
+            errors.append(e)
+    alerted.alert_when(("sum", "num"), ">", 5, callback=freeze)
+    watched.num = 10
+    assert len(errors) == 1 and not alerted.is_frozen()
+
+def test_auto_remove_on_gc():
+    index = Index(auto_remove_on_gc=True)
+    kept = [TestClass(num=i) for i in range(3)]
+    index.add_object_many(kept + [TestClass(num=i) for i in range(3, 6)])
+    # the three objects nothing else refers to are freed straight away and left out
+    assert {o.num for o in index.collect()} == {0, 1, 2}
+    assert len(index.ids()) == 6
+    dropped = TestClass(num=6, child=TestClass(num=60))
+    ref = weakref.ref(dropped)
+    child_ref = weakref.ref(dropped.child)
+    # adding removes them first
+    index.add_object(dropped)
+    assert len(index.ids()) == 4
+    assert len(index.collect()) == 4
+    del dropped
+    assert ref() is None
+    assert {o.num for o in index.collect()} == {0, 1, 2}
+
+    index.add_object(kept[0])
+    assert len(index.ids()) == 3
+    assert child_ref() is None
+    assert len(index.reduced_query(Q.eq("child.num", 60)).collect()) == 0
+
+    del kept[1]
+    assert index.reap() == 1
+    assert {o.num for o in index.collect()} == {0, 2}
+    assert index.reap() == 0
+
+    # plain Indexable objects can be held weakly too, and a recycled id is not mistaken for a freed one
+    plain_obj = Indexable(num=7)
+    index.add_object(plain_obj)
+    del plain_obj
+    replacement = Indexable(num=8)
+    index.add_object(replacement)
+    assert {o.num for o in index.collect()} == {0, 2, 8}
+
+    plain = Index()
+    plain.add_object(TestClass(num=1))
+    plain.add_object(TestClass(num=2))
+    assert len(plain.collect()) == 2
+    assert plain.reap() == 2
+
+    # objects copied in are held the way the receiving index holds them
+    plain.add_object_many(kept)
+    auto = Index(auto_remove_on_gc=True)
+    auto.adopt(plain)
+    assert len(auto.collect()) == 2
+    copy = Index()
+    copy.union_with(index)
+    del kept, replacement
+    assert len(copy.collect()) == 3
+    del copy
+    assert {o.num for o in index.collect()} == {0, 2}
+    del plain
+    assert auto.collect() == []
+    assert auto.reap() == 2
+
+def test_warmup():
+    index = Index()
+    objs = [
+        TestClass(num=i, name=f"item{i}", flag=i % 2 == 0, tags=[i, i + 1], child=TestClass(num=i * 10))
+        for i in range(200)
+    ]
+    index.add_object_many(objs)
+    # leaves emptied sets behind for warmup to drop
+    for obj in objs[:50]:
+        obj.tags = [obj.num]
+
+    queries = [
+        Q.eq("num", 6),
+        Q.bt("num", 20, 40),
+        Q.starts_with("name", "item1"),
+        Q.eq("flag", True),
+        Q.eq("tags", 7),
+        Q.eq("child.num", 40),
+    ]
+    before = [matching(index, q) for q in queries]
+    assert all(before)
+
+    index.warmup(["num", "child"])
+    index.warmup()
+    assert [matching(index, q) for q in queries] == before
+
+    # still indexes changes made afterwards
+    objs[5].num = 1000
+    assert [o.name for o in index.reduced_query(Q.eq("num", 1000)).collect()] == ["item5"]
+    assert index.reduced_query(Q.eq("num", 5)).collect() == []
+
+    with pytest.raises(ValueError):
+        index.warmup(["missing"])
+
+def test_primitive_values_not_pinned():
+    import sys
+
+    num, name, ratio = int("123456789012345"), "".join(["long name ", "x" * 40]), float("2.5")
+    marker = object()
+    before = [sys.getrefcount(v) for v in (num, name, ratio, marker)]
+
+    index = Index()
+    obj = TestClass(num=num, name=name, ratio=ratio, marker=marker)
+    index.add_object(obj)
+
+    # primitives are rebuilt on access, other objects are kept as given
+    after = [sys.getrefcount(v) for v in (num, name, ratio, marker)]
+    assert after[:3] == before[:3]
+    assert after[3] > before[3]
+
+    assert (obj.num, obj.name, obj.ratio) == (num, name, ratio)
+    assert obj.marker is marker
+    assert index.reduced_query(Q.eq("num", num)).collect()[0] is obj
+    assert index.reduced_query(Q.eq("name", name)).collect()[0] is obj
+    assert index.reduced_query(Q.eq("marker", marker)).collect()[0] is obj
+
+def test_numpy_scalars_keep_type():
+    np = pytest.importorskip("numpy")
+    index = Index()
+    obj = TestClass(num=np.int64(5), ratio=np.float64(2.5), name=np.str_("a"))
+    index.add_object(obj)
+
+    assert (type(obj.num), type(obj.ratio), type(obj.name)) == (np.int64, np.float64, np.str_)
+    assert index.reduced_query(Q.eq("num", 5)).collect()[0] is obj
+    assert index.reduced_query(Q.gt("ratio", 2)).collect()[0] is obj
+    assert index.reduced_query(Q.eq("name", "a")).collect()[0] is obj
+
+def test_stub_matches_api():
+    import ast
+    import inspect
+    import pathlib
+    stub = (pathlib.Path(__file__).parent.parent / "PyThermite" / "PyThermite.pyi").read_text()
+    ast.parse(stub)
+    sections = {}
+    for block in stub.split("\nclass ")[1:]:
+        name, _, body = block.partition(":")
+        sections[name] = body.split("\ndef ")[0]
+
+    for cls in (Index, FilteredIndex, Q, IdSet, Indexable):
+        body = sections[cls.__name__]
+        for name in vars(cls):
+            if not name.startswith("_") and callable(getattr(cls, name)):
+                assert f"def {name}(" in body, f"{cls.__name__}.{name} missing from the stub"
+
+    assert "returns the objects sorted by attr" in Index.order_by.__doc__
+    assert "collection of Indexable objects" in Index.__doc__
+    assert list(inspect.signature(Index.order_by).parameters)[1:] == ["attr", "descending", "limit"]
+    assert inspect.signature(Index.order_by).parameters["descending"].default is False
@@ -272,5 +272,187 @@ def test_recursive_ownership_1(index):
     assert t_res[0].inner == 7
 
 
+def test_nested_list_query(index):
+    objs = [TestClass(name=f"object_{i}", items=[TestClass(price=i * 10 + j) for j in range(3)]) for i in range(4)]
+    index.add_object_many(objs)
+
+    result = index.reduced_query(Q.eq("items.price", 21)).collect()
+    assert [r.name for r in result] == ["object_2"]
+
+    result = index.reduced_query(Q.gt("items.price", 11)).collect()
+    assert {r.name for r in result} == {"object_1", "object_2", "object_3"}
+
+
+def test_nested_aggregates(index):
+    objs = [TestClass(name=f"object_{i}", items=[TestClass(price=i * 10 + j) for j in range(3)]) for i in range(4)]
+    objs.append(TestClass(name="empty", items=[]))
+    index.add_object_many(objs)
+
+    # sums are 3, 33, 63, 93
+    result = index.reduced_query(Q.agg("items", "sum", "price", gt=50)).collect()
+    assert {r.name for r in result} == {"object_2", "object_3"}
+
+    result = index.reduced_query(Q.agg("items", "sum", "price", lt=10)).collect()
+    assert {r.name for r in result} == {"object_0", "empty"}
+
+    result = index.reduced_query(Q.agg("items", "count", "price", eq=3)).collect()
+    assert len(result) == 4
+
+    result = index.reduced_query(Q.agg("items", "min", "price", ge=20)).collect()
+    assert {r.name for r in result} == {"object_2", "object_3"}
+
+    result = index.reduced_query(Q.agg("items", "max", "price", le=2)).collect()
+    assert {r.name for r in result} == {"object_0"}
+
+    result = index.reduced_query(Q.agg("items", "avg", "price", eq=11)).collect()
+    assert {r.name for r in result} == {"object_1"}
+
+    with pytest.raises(ValueError):
+        Q.agg("items", "median", "price", gt=1)
+    with pytest.raises(ValueError):
+        Q.agg("items", "sum", "price")
+
+def test_count_children(index):
+    objs = [TestClass(name=f"object_{i}", items=[TestClass(n=j) for j in range(i)]) for i in range(5)]
+    objs.append(TestClass(name="single", items=TestClass(n=1)))
+    index.add_object_many(objs)
+
+    result = index.reduced_query(Q.count_children("items", ge=3)).collect()
+    assert {r.name for r in result} == {"object_3", "object_4"}
+
+    result = index.reduced_query(Q.count_children("items", eq=0)).collect()
+    assert {r.name for r in result} == {"object_0"}
+
+    result = index.reduced_query(Q.count_children("items", eq=1)).collect()
+    assert {r.name for r in result} == {"object_1", "single"}
+
+    # replacing the collection updates the counters and drops the old children
+    objs[4].items = [TestClass(n=100)]
+    result = index.reduced_query(Q.count_children("items", ge=3)).collect()
+    assert {r.name for r in result} == {"object_3"}
+    assert index.reduced_query(Q.eq("items.n", 3)).collect() == []
+    assert [r.name for r in index.reduced_query(Q.eq("items.n", 100)).collect()] == ["object_4"]
+
+
+def test_count_children_after_union(index):
+    shared = TestClass(name="shared", items=[TestClass(n=0), TestClass(n=1)], tags=["a", "b"])
+    other = Index()
+    other.add_object_many([shared, TestClass(name="taken", items=[TestClass(n=j) for j in range(3)])])
+    index.add_object(shared)
+
+    # a parent both indexes hold keeps its own counts and elements, however often they are merged
+    index.union_with(other)
+    index.union_with(other)
+    assert [r.name for r in index.reduced_query(Q.count_children("items", eq=2)).collect()] == ["shared"]
+    assert [r.name for r in index.reduced_query(Q.count_children("items", eq=3)).collect()] == ["taken"]
+    assert index.reduced_query(Q.count_children("items", eq=4)).collect() == []
+
+    shared.tags = ["c"]
+    assert index.reduced_query(Q.eq("tags", "a")).collect() == []
+    assert [r.name for r in index.reduced_query(Q.eq("tags", "c")).collect()] == ["shared"]
+
+
+def test_limit_nested_depth(index):
+    index.limit_nested("child", depth=1)
+    objs = [
+        TestClass(name=f"object_{i}", child=TestClass(name="child_of", grandchild=TestClass(name="grandchild_of")))
+        for i in range(5)
+    ]
+    index.add_object_many(objs)
+
+    assert len(index.reduced_query(Q.eq("child.name", "child_of")).collect()) == 5
+    assert len(index.reduced_query(Q.eq("child.grandchild.name", "grandchild_of")).collect()) == 0
+
+    other = Index()
+    other.limit_nested("child", depth=0)
+    other.add_object_many(objs)
+    assert len(other.reduced_query(Q.eq("child.name", "child_of")).collect()) == 0
+    assert len(other.reduced_query(Q.eq("name", "object_1")).collect()) == 1
+
+
+def test_limit_nested_projection(index):
+    index.limit_nested("owner", only=["id", "address.city"])
+    objs = [
+        TestClass(
+            name=f"object_{i}",
+            owner=TestClass(id=i, email=f"owner_{i}@example.com", address=TestClass(city="Paris", street="Main")),
+        )
+        for i in range(5)
+    ]
+    index.add_object_many(objs)
+
+    assert len(index.reduced_query(Q.eq("owner.id", 3)).collect()) == 1
+    assert len(index.reduced_query(Q.eq("owner.address.city", "Paris")).collect()) == 5
+    assert len(index.reduced_query(Q.eq("owner.email", "owner_3@example.com")).collect()) == 0
+    assert len(index.reduced_query(Q.eq("owner.address.street", "Main")).collect()) == 0
+
+    # updates to projected out fields stay out of the index
+    objs[0].owner.email = "changed@example.com"
+    assert len(index.reduced_query(Q.eq("owner.email", "changed@example.com")).collect()) == 0
+    objs[0].owner.id = 42
+    assert len(index.reduced_query(Q.eq("owner.id", 42)).collect()) == 1
+
+
+def test_limit_nested_after_add_raises(index):
+    index.add_object(TestClass(name="object_0"))
+    with pytest.raises(ValueError):
+        index.limit_nested("child", depth=1)
+
+
+def test_infer_schema(index):
+    objs = [
+        TestClass(
+            name=f"object_{i}",
+            status="open" if i % 2 else "closed",
+            price=i * 1.5,
+            note=None if i == 0 else "text",
+            owner=TestClass(id=i, email=f"owner_{i}@example.com"),
+            tags=["a", "b"],
+        )
+        for i in range(6)
+    ]
+    objs[0].extra = 1
+    index.add_object_many(objs)
+
+    schema = index.infer_schema()
+    assert schema["type"] == "object"
+    props = schema["properties"]
+    assert props["name"]["type"] == "string"
+    assert "enum" not in props["name"]
+    assert props["status"]["enum"] == ["closed", "open"]
+    assert props["price"]["type"] == "number"
+    assert sorted(props["note"]["type"]) == ["null", "string"]
+    assert props["owner"]["type"] == "object"
+    assert props["owner"]["properties"]["id"]["type"] == "integer"
+    assert props["tags"]["type"] == "array"
+    assert props["tags"]["items"]["type"] == "string"
+
+    assert "extra" not in schema["required"]
+    assert "note" not in schema["required"]
+    assert "name" in schema["required"]
+
+def test_explain_nested_hops(index):
+    objs = []
+    for i in range(6):
+        leaf = TestClass(score=i)
+        objs.append(TestClass(num=i, child=TestClass(inner=leaf)))
+    index.add_object_many(objs)
+
+    trace = index.explain(Q.and_(Q.ge("child.inner.score", 2), Q.lt("num", 5)))
+    assert trace["matched"] == 3
+    assert trace["levels"] == 2
+    assert [c["clause"] for c in trace["children"]] == ["num < 5", "child.inner.score >= 2"]
+
+    nested = trace["children"][1]["nested"]
+    assert nested["attr"] == "child"
+    assert nested["levels"] == 2
+    assert nested["child_matches"] == 4
+    assert nested["rollup_ns"] >= 0
+    assert nested["child"]["clause"] == "inner.score >= 2"
+    assert nested["child"]["nested"]["child"]["matched"] == 4
+
+    assert index.explain(Q.eq("num", 1))["levels"] == 0
+
+
 if __name__ == "__main__":
     test_recursive_ownership_1(Index())